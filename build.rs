@@ -0,0 +1,14 @@
+fn main() {
+    // Platform link flags for the Node.js addon.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    // The gRPC service, compiled in Rust so building doesn't need protoc.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/soul_composer.proto");
+        let descriptors = protox::compile(["soul_composer.proto"], ["proto"]).expect("proto/soul_composer.proto is valid");
+        // `connect` needs the 2021 prelude, clients connect an `Endpoint` and pass the channel.
+        tonic_build::configure().build_transport(false).compile_fds(descriptors).expect("the gRPC service generates");
+    }
+}
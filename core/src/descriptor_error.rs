@@ -0,0 +1,67 @@
+use core::fmt;
+
+/// Why a `FlashDevice` or its sector table was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorError {
+    /// The descriptor isn't fully inside a loadable segment.
+    Malformed { address: u32, size: u32 },
+    SymbolNotFound(&'static str),
+    UnsupportedDriverVersion { address: u32, version: u16 },
+    NameTooLong { address: u32, length: usize, limit: usize },
+    TooManySectors { address: u32, limit: usize },
+    SectorTableEmpty,
+    SectorSizeZero { index: usize, address: u32 },
+    SectorTableGap { index: usize, address: u32 },
+    SectorTableUnsorted { index: usize, address: u32, previous: u32 },
+    SectorRegionMisaligned { index: usize, start: u32, end: u32, size: u32 },
+    SectorRegionOutOfRange { index: usize, address: u32, device_size: u32 },
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DescriptorError::Malformed { address, size } => {
+                write!(f, "FlashDevice at {:#010x} is malformed, {} bytes can't be read from the loadable segments", address, size)
+            }
+            DescriptorError::SymbolNotFound(name) => write!(f, "Symbol {} not found in the ELF symbol table", name),
+            DescriptorError::UnsupportedDriverVersion { address, version } => {
+                write!(f, "FlashDevice at {:#010x} has driver version {:#06x}, only 1.x descriptors are supported", address, version)
+            }
+            DescriptorError::NameTooLong { address, length, limit } => {
+                write!(f, "FlashDevice at {:#010x} has a {} byte name, the limit is {} bytes", address, length, limit)
+            }
+            DescriptorError::TooManySectors { address, limit } => {
+                write!(f, "FlashDevice at {:#010x} has more than {} sector regions", address, limit)
+            }
+            DescriptorError::SectorTableEmpty => {
+                write!(f, "FlashDevice sector table is empty, at least one sector region is required")
+            }
+            DescriptorError::SectorSizeZero { index, address } => {
+                write!(f, "Sector region {} at offset {:#010x} has a sector size of 0 bytes", index, address)
+            }
+            DescriptorError::SectorTableGap { index, address } => write!(
+                f,
+                "Sector region {} must start at offset 0x00000000 to cover the device, but starts at {:#010x}",
+                index, address
+            ),
+            DescriptorError::SectorTableUnsorted { index, address, previous } => write!(
+                f,
+                "Sector region {} at offset {:#010x} does not come after the previous region at {:#010x}; the sector table must be sorted and non-overlapping",
+                index, address, previous
+            ),
+            DescriptorError::SectorRegionMisaligned { index, start, end, size } => write!(
+                f,
+                "Sector region {} spans {:#010x}..{:#010x}, which is not a multiple of its {} byte sector size; check the region boundary or the device size",
+                index, start, end, size
+            ),
+            DescriptorError::SectorRegionOutOfRange { index, address, device_size } => write!(
+                f,
+                "Sector region {} at offset {:#010x} starts beyond the device size of {} bytes",
+                index, address, device_size
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DescriptorError {}
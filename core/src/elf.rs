@@ -0,0 +1,73 @@
+use core::convert::TryFrom;
+
+use goblin::elf::Elf;
+
+/// Values of the CMSIS entry point symbols, as linked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EntrySymbols {
+    pub init: Option<u32>,
+    pub uninit: Option<u32>,
+    pub program_page: Option<u32>,
+    pub erase_sector: Option<u32>,
+    pub erase_all: Option<u32>,
+}
+
+impl EntrySymbols {
+    /// Collects the entry points from the symbol table, the last definition wins.
+    pub fn from_elf(elf: &Elf<'_>) -> Self {
+        let mut entries = EntrySymbols::default();
+        for sym in elf.syms.iter() {
+            let value = Some(sym.st_value as u32);
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or_default();
+            let slot = match name {
+                "Init" => &mut entries.init,
+                "UnInit" => &mut entries.uninit,
+                "EraseChip" => &mut entries.erase_all,
+                "EraseSector" => &mut entries.erase_sector,
+                "ProgramPage" => &mut entries.program_page,
+                _ => continue,
+            };
+            tracing::debug!(symbol = name, address = sym.st_value, "entry point");
+            *slot = value;
+        }
+
+        entries
+    }
+}
+
+/// Value of the first symbol called `name`.
+pub fn find_symbol(elf: &Elf<'_>, name: &str) -> Option<u32> {
+    elf.syms
+        .iter()
+        .find(|sym| elf.strtab.get_at(sym.st_name) == Some(name))
+        .map(|sym| sym.st_value as u32)
+}
+
+/// The `size` bytes linked at `address`, if a segment holds all of them.
+///
+/// Segments and requests that run past the end of the address space hold nothing.
+pub fn read_segment_data<'a>(elf: &Elf<'_>, buffer: &'a [u8], address: u32, size: u32) -> Option<&'a [u8]> {
+    let _span = tracing::trace_span!("read_segment_data", address, size).entered();
+    let end = address.checked_add(size)?;
+
+    // Iterate all segments.
+    for ph in &elf.program_headers {
+        let segment_address = ph.p_paddr as u32;
+        let segment_size = ph.p_memsz.min(ph.p_filesz) as u32;
+
+        tracing::trace!(segment_address, segment_size, "segment");
+
+        let segment_end = match segment_address.checked_add(segment_size) {
+            Some(segment_end) => segment_end,
+            None => continue,
+        };
+
+        // If the requested data chunk is fully contained in the segment, extract and return the data segment.
+        if address >= segment_address && end <= segment_end {
+            let start = usize::try_from(ph.p_offset).ok()?.checked_add((address - segment_address) as usize)?;
+            return buffer.get(start..)?.get(..size as usize);
+        }
+    }
+
+    None
+}
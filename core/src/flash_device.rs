@@ -0,0 +1,315 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+use goblin::elf::Elf;
+use scroll::Pread;
+
+use crate::{
+    descriptor_error::DescriptorError,
+    elf::{find_symbol, read_segment_data},
+};
+
+/// A struct to describe one sector in Flash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectorInfo {
+    pub address: u32,
+    pub size: u32,
+}
+
+impl SectorInfo {
+    // This value signalizes the end of a sector.
+    const SECTOR_END: u32 = 0xFFFF_FFFF;
+
+    /// Reads one sector table entry, `None` for the end marker.
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let size = data.pread(0).ok()?;
+        let address = data.pread(4).ok()?;
+        if size != Self::SECTOR_END && address != Self::SECTOR_END {
+            Some(Self { address, size })
+        } else {
+            None
+        }
+    }
+}
+
+/// Bounds for parsing descriptors out of files nobody vouches for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Sector table entries read before the table counts as malformed.
+    pub max_sectors: usize,
+    /// Longest device name accepted, the struct has room for 128 bytes.
+    pub max_name_length: usize,
+    /// Cut names and sector tables down to the limits and accept other driver versions and sector
+    /// tables that don't tile the device, instead of refusing the descriptor.
+    pub lenient: bool,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self { max_sectors: 256, max_name_length: FlashDevice::MAX_ID_STRING_LENGTH, lenient: false }
+    }
+}
+
+/// The CMSIS `FlashDevice` struct.
+///
+// This struct takes 160 bytes + the size of all sectors at the end in the ELF binary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlashDevice {
+    pub driver_version: u16,
+    /// Max 128 bytes in the binary.
+    pub name: String,
+    pub device_type: u16,
+    pub start_address: u32,
+    pub device_size: u32,
+    pub page_size: u32,
+    pub erased_default_value: u8,
+    /// In milliseconds.
+    pub program_page_timeout: u32,
+    /// In milliseconds.
+    pub erase_sector_timeout: u32,
+    pub sectors: Vec<SectorInfo>,
+}
+
+impl FlashDevice {
+    pub const INFO_SIZE: u32 = 160;
+    pub const SECTOR_INFO_SIZE: u32 = 8;
+    pub const MAX_ID_STRING_LENGTH: usize = 128;
+    const DRIVER_VERSION_MAJOR: u16 = 1;
+
+    /// Finds the `FlashDevice` symbol of an FLM and parses the struct behind it.
+    pub fn from_elf(elf: &Elf<'_>, buffer: &[u8]) -> Result<Self, DescriptorError> {
+        Self::from_elf_with_limits(elf, buffer, None, &ParseLimits::default())
+    }
+
+    /// Same as `from_elf`, trying `symbol` before `FlashDevice` for vendors that renamed the struct.
+    pub fn from_elf_with_limits(
+        elf: &Elf<'_>,
+        buffer: &[u8],
+        symbol: Option<&str>,
+        limits: &ParseLimits,
+    ) -> Result<Self, DescriptorError> {
+        let address = symbol
+            .and_then(|symbol| find_symbol(elf, symbol))
+            .or_else(|| find_symbol(elf, "FlashDevice"))
+            .ok_or(DescriptorError::SymbolNotFound("FlashDevice"))?;
+        Self::at(elf, buffer, address, limits)
+    }
+
+    /// Parses the struct linked at `address`.
+    pub fn at(elf: &Elf<'_>, buffer: &[u8], address: u32, limits: &ParseLimits) -> Result<Self, DescriptorError> {
+        let data = read_segment_data(elf, buffer, address, Self::INFO_SIZE)
+            .ok_or(DescriptorError::Malformed { address, size: Self::INFO_SIZE })?;
+        let sectors = Self::parse_sectors(elf, buffer, address, limits)?;
+        Self::parse(address, data, sectors, limits)
+    }
+
+    /// Parses the 160 byte struct in `data` that was linked at `address`, with its sector table.
+    pub fn parse(
+        address: u32,
+        data: &[u8],
+        sectors: Vec<SectorInfo>,
+        limits: &ParseLimits,
+    ) -> Result<Self, DescriptorError> {
+        let malformed = DescriptorError::Malformed { address, size: Self::INFO_SIZE };
+        let data = data.get(..Self::INFO_SIZE as usize).ok_or_else(|| malformed.clone())?;
+        let read = |offset: usize| data.pread::<u32>(offset).map_err(|_| malformed.clone());
+
+        // The layout only changed between major versions, 0x0101 is what CMSIS ships today.
+        let driver_version: u16 = data.pread(0).map_err(|_| malformed.clone())?;
+        if driver_version >> 8 != Self::DRIVER_VERSION_MAJOR && !limits.lenient {
+            return Err(DescriptorError::UnsupportedDriverVersion { address, version: driver_version });
+        }
+
+        // Get the string length of the name
+        let mut name_length = data[2..2 + Self::MAX_ID_STRING_LENGTH]
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(Self::MAX_ID_STRING_LENGTH);
+        let name_limit = limits.max_name_length.min(Self::MAX_ID_STRING_LENGTH);
+        if name_length > name_limit {
+            if !limits.lenient {
+                return Err(DescriptorError::NameTooLong { address, length: name_length, limit: name_limit });
+            }
+            name_length = name_limit;
+        }
+        let device_size = read(136)?;
+
+        // Refuse broken vendor sector tables before they end up in the output, lenient callers
+        // check the table themselves and report it.
+        if !limits.lenient {
+            validate_sectors(&sectors, device_size)?;
+        }
+
+        Ok(Self {
+            driver_version,
+            name: String::from_utf8_lossy(&data[2..2 + name_length]).into_owned(),
+            device_type: data.pread(130).map_err(|_| malformed.clone())?,
+            start_address: read(132)?,
+            device_size,
+            page_size: read(140)?,
+            erased_default_value: data[148],
+            program_page_timeout: read(152)?,
+            erase_sector_timeout: read(156)?,
+            sectors,
+        })
+    }
+
+    /// Reads the sector table following the struct at `address`, up to the end marker or
+    /// `limits.max_sectors` entries.
+    pub fn parse_sectors(
+        elf: &Elf<'_>,
+        buffer: &[u8],
+        address: u32,
+        limits: &ParseLimits,
+    ) -> Result<Vec<SectorInfo>, DescriptorError> {
+        let mut sectors = Vec::new();
+        let mut offset = Self::INFO_SIZE;
+        // As long as we find new sectors, keep em comming.
+        loop {
+            // A table without an end marker that runs into the end of the address space.
+            let entry = address.checked_add(offset).ok_or(DescriptorError::Malformed { address, size: offset })?;
+            let data = match read_segment_data(elf, buffer, entry, Self::SECTOR_INFO_SIZE) {
+                Some(data) => data,
+                None => break,
+            };
+            let sector = match SectorInfo::new(data) {
+                Some(sector) => sector,
+                None => break,
+            };
+            if sectors.len() == limits.max_sectors {
+                if limits.lenient {
+                    break;
+                }
+                return Err(DescriptorError::TooManySectors { address, limit: limits.max_sectors });
+            }
+            sectors.push(sector);
+            offset += Self::SECTOR_INFO_SIZE;
+        }
+
+        Ok(sectors)
+    }
+
+    /// Absolute address range of the sector holding `address`, see `sector_containing`.
+    pub fn sector_containing(&self, address: u32) -> Option<Range<u64>> {
+        sector_containing(&self.sectors, self.start_address, self.device_size, address)
+    }
+
+    /// Absolute address range of every sector in order, see `sector_ranges`.
+    pub fn iter_sector_ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        sector_ranges(&self.sectors, self.start_address, self.device_size)
+    }
+
+    /// Bytes covered by whole sectors, see `total_size_from_sectors`.
+    pub fn total_size_from_sectors(&self) -> u64 {
+        total_size_from_sectors(&self.sectors, self.device_size)
+    }
+}
+
+/// The region of `sectors[index]` relative to the flash start, up to the next region or the
+/// device end. Empty for zero sized sectors and entries out of order.
+fn region(sectors: &[SectorInfo], index: usize, device_size: u32) -> Range<u64> {
+    let sector = &sectors[index];
+    let start = u64::from(sector.address);
+    let end = sectors.get(index + 1).map_or(u64::from(device_size), |next| u64::from(next.address));
+    if sector.size == 0 || end < start {
+        return start..start;
+    }
+    start..end
+}
+
+/// Absolute address range of the sector holding `address`, `None` outside the flash.
+///
+/// Sector addresses are relative to `start_address`. A region that isn't a whole number of
+/// sectors ends in a short sector, cut at the next region or the device end, so the range never
+/// reaches past the flash. The end is a `u64` for flash that ends at the top of the address space.
+pub fn sector_containing(sectors: &[SectorInfo], start_address: u32, device_size: u32, address: u32) -> Option<Range<u64>> {
+    let offset = address.checked_sub(start_address)?;
+    if offset >= device_size {
+        return None;
+    }
+
+    let offset = u64::from(offset);
+    let index = sectors.iter().rposition(|sector| u64::from(sector.address) <= offset)?;
+    let region = region(sectors, index, device_size);
+    if !region.contains(&offset) {
+        return None;
+    }
+
+    let size = u64::from(sectors[index].size);
+    let start = region.start + (offset - region.start) / size * size;
+    let base = u64::from(start_address);
+    Some(base + start..base + (start + size).min(region.end))
+}
+
+/// Absolute address range of every sector in order, short sectors cut like in `sector_containing`.
+pub fn sector_ranges(sectors: &[SectorInfo], start_address: u32, device_size: u32) -> impl Iterator<Item = Range<u64>> + '_ {
+    let base = u64::from(start_address);
+    (0..sectors.len()).flat_map(move |index| {
+        let region = region(sectors, index, device_size);
+        let size = u64::from(sectors[index].size).max(1);
+        (region.start..region.end)
+            .step_by(size as usize)
+            .map(move |start| base + start..base + (start + size).min(region.end))
+    })
+}
+
+/// Bytes covered by whole sectors, from the first region to the device end.
+///
+/// Equals `device_size` for a table that passes `validate_sectors`, less when a region isn't a
+/// whole number of sectors or the table doesn't start at the flash start.
+pub fn total_size_from_sectors(sectors: &[SectorInfo], device_size: u32) -> u64 {
+    (0..sectors.len())
+        .map(|index| {
+            let region = region(sectors, index, device_size);
+            match u64::from(sectors[index].size) {
+                0 => 0,
+                size => (region.end - region.start) / size * size,
+            }
+        })
+        .sum()
+}
+
+/// Check that the sector table is sorted, non-overlapping and tiles the device.
+///
+/// Each entry describes a region starting at `address` (relative to the flash start)
+/// made of sectors of `size` bytes, which runs until the next entry or the device end.
+pub fn validate_sectors(sectors: &[SectorInfo], device_size: u32) -> Result<(), DescriptorError> {
+    let first = sectors.first().ok_or(DescriptorError::SectorTableEmpty)?;
+    if first.address != 0 {
+        return Err(DescriptorError::SectorTableGap { index: 0, address: first.address });
+    }
+
+    for (index, sector) in sectors.iter().enumerate() {
+        if sector.size == 0 {
+            return Err(DescriptorError::SectorSizeZero { index, address: sector.address });
+        }
+
+        if sector.address >= device_size {
+            return Err(DescriptorError::SectorRegionOutOfRange { index, address: sector.address, device_size });
+        }
+
+        // The region ends where the next one begins, or at the device end for the last one.
+        let end = match sectors.get(index + 1) {
+            Some(next) if next.address <= sector.address => {
+                return Err(DescriptorError::SectorTableUnsorted {
+                    index: index + 1,
+                    address: next.address,
+                    previous: sector.address,
+                });
+            }
+            Some(next) => next.address,
+            None => device_size,
+        };
+
+        if (end - sector.address) % sector.size != 0 {
+            return Err(DescriptorError::SectorRegionMisaligned {
+                index,
+                start: sector.address,
+                end,
+                size: sector.size,
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! The parts of FLM parsing that don't need an operating system: the `FlashDevice` descriptor,
+//! its sector table and the entry point symbols.
+//!
+//! Without the default `std` feature this is a `no_std` crate that only needs `alloc`, so the
+//! programmer firmware can read stubs and raw FLMs with the same code as the host tool.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod descriptor_error;
+pub mod elf;
+pub mod flash_device;
+#[cfg(feature = "signature")]
+pub mod signed;
+
+pub use descriptor_error::DescriptorError;
+pub use flash_device::{FlashDevice, ParseLimits, SectorInfo};
@@ -0,0 +1,143 @@
+//! Signed bundles: a stub, group or any other file wrapped with the Ed25519 key that signed it,
+//! so the programmer only runs loader code from a key it trusts.
+//!
+//! ```text
+//! offset      size  field
+//! 0           4     magic, "SCSB"
+//! 4           1     format version, 1
+//! 5           3     reserved, zero
+//! 8           32    public key of the signer
+//! 40          4     payload length, little endian
+//! 44          n     payload, the file as exported
+//! 44 + n      64    Ed25519 signature over bytes 0 to 44 + n
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+
+pub const MAGIC: [u8; 4] = *b"SCSB";
+pub const FORMAT_VERSION: u8 = 1;
+
+pub const PUBLIC_KEY_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+/// Bytes before the payload.
+pub const HEADER_SIZE: usize = 44;
+
+/// Why a bundle was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleError {
+    /// Too short for the header and signature, or shorter than the payload length says.
+    Truncated { length: usize, needed: usize },
+    /// Longer than the payload length says.
+    TrailingData { length: usize, expected: usize },
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The signer isn't one of the trusted keys.
+    UntrustedKey([u8; PUBLIC_KEY_SIZE]),
+    /// The signer's key isn't a valid Ed25519 point.
+    InvalidKey,
+    /// The signature doesn't match the bundle, it was altered or signed with another key.
+    BadSignature,
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BundleError::Truncated { length, needed } => {
+                write!(f, "Signed bundle is {} bytes, at least {} are needed", length, needed)
+            }
+            BundleError::TrailingData { length, expected } => {
+                write!(f, "Signed bundle is {} bytes, its header says {}", length, expected)
+            }
+            BundleError::BadMagic => write!(f, "Not a signed bundle, the magic is missing"),
+            BundleError::UnsupportedVersion(version) => {
+                write!(f, "Signed bundle format {} isn't supported, only {} is", version, FORMAT_VERSION)
+            }
+            BundleError::UntrustedKey(key) => {
+                write!(f, "Signed by untrusted key ")?;
+                key.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+            BundleError::InvalidKey => write!(f, "Signer's public key isn't a valid Ed25519 key"),
+            BundleError::BadSignature => write!(f, "Signature doesn't match the bundle"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BundleError {}
+
+/// A bundle split into its parts, not verified yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedBundle<'a> {
+    pub public_key: [u8; PUBLIC_KEY_SIZE],
+    pub payload: &'a [u8],
+    pub signature: [u8; SIGNATURE_SIZE],
+    /// Header and payload, what the signature covers.
+    signed: &'a [u8],
+}
+
+/// The header of a bundle of `payload` signed by `public_key`, followed by the payload: what the
+/// signer signs. Append the 64 byte signature to get the bundle.
+pub fn signed_message(public_key: &[u8; PUBLIC_KEY_SIZE], payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_SIZE + payload.len() + SIGNATURE_SIZE);
+    message.extend_from_slice(&MAGIC);
+    message.extend_from_slice(&[FORMAT_VERSION, 0, 0, 0]);
+    message.extend_from_slice(public_key);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+impl<'a> SignedBundle<'a> {
+    /// Splits `data` into its parts, checking the layout but not the signature.
+    pub fn parse(data: &'a [u8]) -> Result<Self, BundleError> {
+        let needed = HEADER_SIZE + SIGNATURE_SIZE;
+        if data.len() < needed {
+            return Err(BundleError::Truncated { length: data.len(), needed });
+        }
+        if data[..4] != MAGIC {
+            return Err(BundleError::BadMagic);
+        }
+        if data[4] != FORMAT_VERSION {
+            return Err(BundleError::UnsupportedVersion(data[4]));
+        }
+
+        let mut public_key = [0; PUBLIC_KEY_SIZE];
+        public_key.copy_from_slice(&data[8..40]);
+        let payload_length = u32::from_le_bytes([data[40], data[41], data[42], data[43]]) as usize;
+        let expected = needed.saturating_add(payload_length);
+        if data.len() < expected {
+            return Err(BundleError::Truncated { length: data.len(), needed: expected });
+        }
+        if data.len() > expected {
+            return Err(BundleError::TrailingData { length: data.len(), expected });
+        }
+
+        let signed_end = HEADER_SIZE + payload_length;
+        let mut signature = [0; SIGNATURE_SIZE];
+        signature.copy_from_slice(&data[signed_end..]);
+        Ok(SignedBundle { public_key, payload: &data[HEADER_SIZE..signed_end], signature, signed: &data[..signed_end] })
+    }
+
+    /// Checks the signature against the key in the bundle, without asking whether it is trusted.
+    pub fn verify_signature(&self) -> Result<(), BundleError> {
+        let key = VerifyingKey::from_bytes(&self.public_key).map_err(|_| BundleError::InvalidKey)?;
+        key.verify_strict(self.signed, &Signature::from_bytes(&self.signature)).map_err(|_| BundleError::BadSignature)
+    }
+
+    /// Checks that one of `trusted` signed the bundle, returning the payload.
+    pub fn verify(&self, trusted: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<&'a [u8], BundleError> {
+        if !trusted.contains(&self.public_key) {
+            return Err(BundleError::UntrustedKey(self.public_key));
+        }
+        self.verify_signature()?;
+        Ok(self.payload)
+    }
+}
+
+/// Parses `data` and checks that one of `trusted` signed it, returning the payload.
+pub fn verify_bundle<'a>(data: &'a [u8], trusted: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<&'a [u8], BundleError> {
+    SignedBundle::parse(data)?.verify(trusted)
+}
@@ -0,0 +1,81 @@
+use soulcomposer_core::{flash_device::validate_sectors, DescriptorError, FlashDevice, ParseLimits, SectorInfo};
+
+fn descriptor(version: u16) -> Vec<u8> {
+    let mut data = vec![0u8; FlashDevice::INFO_SIZE as usize];
+    data[0..2].copy_from_slice(&version.to_le_bytes());
+    data[2..9].copy_from_slice(b"Onboard");
+    data[130..132].copy_from_slice(&1u16.to_le_bytes());
+    for (offset, value) in [(132, 0x0800_0000u32), (136, 0x8000), (140, 128), (148, 0xFF), (152, 50), (156, 500)] {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn parses_descriptor_bytes() {
+    let sectors = vec![SectorInfo { address: 0, size: 0x400 }];
+    let device = FlashDevice::parse(0x100, &descriptor(0x0101), sectors.clone(), &ParseLimits::default()).unwrap();
+    assert_eq!(device.name, "Onboard");
+    assert_eq!((device.start_address, device.device_size, device.page_size), (0x0800_0000, 0x8000, 128));
+    assert_eq!((device.program_page_timeout, device.erase_sector_timeout), (50, 500));
+    assert_eq!(device.sectors, sectors);
+    assert_eq!(SectorInfo::new(&[0xFF; 8]), None);
+}
+
+#[test]
+fn refuses_bad_descriptors() {
+    let sectors = vec![SectorInfo { address: 0, size: 0x400 }];
+    assert_eq!(
+        FlashDevice::parse(0x100, &descriptor(0x0101)[..100], sectors.clone(), &ParseLimits::default()),
+        Err(DescriptorError::Malformed { address: 0x100, size: 160 })
+    );
+    assert_eq!(
+        FlashDevice::parse(0x100, &descriptor(0x0200), sectors.clone(), &ParseLimits::default()),
+        Err(DescriptorError::UnsupportedDriverVersion { address: 0x100, version: 0x0200 })
+    );
+    assert_eq!(validate_sectors(&[], 0x8000), Err(DescriptorError::SectorTableEmpty));
+}
+
+#[test]
+fn applies_limits() {
+    let sectors = vec![SectorInfo { address: 0, size: 0x400 }];
+    let short = ParseLimits { max_name_length: 4, ..Default::default() };
+    assert_eq!(
+        FlashDevice::parse(0x100, &descriptor(0x0101), sectors.clone(), &short),
+        Err(DescriptorError::NameTooLong { address: 0x100, length: 7, limit: 4 })
+    );
+
+    let lenient = ParseLimits { lenient: true, ..short };
+    let device = FlashDevice::parse(0x100, &descriptor(0x0200), sectors, &lenient).unwrap();
+    assert_eq!((device.name.as_str(), device.driver_version), ("Onbo", 0x0200));
+
+    // Broken sector tables are left for lenient callers to report.
+    let unsorted = vec![SectorInfo { address: 0x400, size: 0x400 }, SectorInfo { address: 0, size: 0x400 }];
+    assert!(FlashDevice::parse(0x100, &descriptor(0x0101), unsorted.clone(), &lenient).is_ok());
+    assert_eq!(
+        FlashDevice::parse(0x100, &descriptor(0x0101), unsorted, &ParseLimits::default()),
+        Err(DescriptorError::SectorTableGap { index: 0, address: 0x400 })
+    );
+}
+
+#[test]
+fn sector_queries_handle_the_edges() {
+    use soulcomposer_core::flash_device::{sector_containing, sector_ranges, total_size_from_sectors};
+
+    // The last region of 0x1800 bytes ends in a short 0x800 byte sector.
+    let sectors = [SectorInfo { address: 0, size: 0x400 }, SectorInfo { address: 0x800, size: 0x1000 }];
+    assert_eq!(sector_containing(&sectors, 0x100, 0x2000, 0x5ff), Some(0x500..0x900));
+    assert_eq!(sector_containing(&sectors, 0x100, 0x2000, 0x1900), Some(0x1900..0x2100));
+    assert_eq!(sector_containing(&sectors, 0x100, 0x2000, 0x2100), None);
+    let ranges: Vec<_> = sector_ranges(&sectors, 0x100, 0x2000).collect();
+    assert_eq!(ranges, [0x100..0x500, 0x500..0x900, 0x900..0x1900, 0x1900..0x2100]);
+    assert_eq!(total_size_from_sectors(&sectors, 0x2000), 0x1800);
+
+    // Flash at the top of the address space, the end doesn't fit in a u32.
+    let top = [SectorInfo { address: 0, size: 0x1000 }];
+    assert_eq!(sector_containing(&top, 0xFFFF_F000, 0x1000, 0xFFFF_FFFF), Some(0xFFFF_F000..0x1_0000_0000));
+    assert_eq!(sector_ranges(&top, 0xFFFF_F000, 0x1000).count(), 1);
+
+    assert_eq!(sector_containing(&[SectorInfo { address: 0, size: 0 }], 0, 0x1000, 0x10), None);
+    assert_eq!(sector_ranges(&[], 0, 0x1000).count(), 0);
+}
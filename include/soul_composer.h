@@ -0,0 +1,80 @@
+/*
+ * C interface of soulcomposer, built with `cargo build --release --features ffi`.
+ *
+ * Functions return SC_OK or set the message sc_last_error() returns, and the stable code
+ * sc_last_error_code() returns when there is one. Everything the library
+ * allocates is released with the matching sc_*_free function.
+ */
+
+#ifndef SOUL_COMPOSER_H
+#define SOUL_COMPOSER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    SC_OK = 0,
+    /* A pointer was null or a string wasn't UTF-8. */
+    SC_INVALID_ARGUMENT = 1,
+    /* The FLM was refused, see sc_last_error(). */
+    SC_PARSE_FAILED = 2,
+} sc_status;
+
+/* One run of equally sized sectors, address relative to the flash start. */
+typedef struct {
+    uint32_t address;
+    uint32_t size;
+} sc_sector;
+
+/* The FlashDevice of an FLM. */
+typedef struct {
+    uint16_t driver_version;
+    uint16_t device_type;
+    char name[129];
+    uint32_t start_address;
+    uint32_t device_size;
+    uint32_t page_size;
+    uint8_t erased_default_value;
+    uint32_t program_page_timeout;
+    uint32_t erase_sector_timeout;
+    /* Owned by the library, released by sc_flash_device_free(). */
+    sc_sector *sectors;
+    size_t sector_count;
+} sc_flash_device;
+
+/* Message of the last failed call on this thread, or NULL. Valid until the next call fails. */
+const char *sc_last_error(void);
+
+/*
+ * Stable code of the last failed call on this thread, e.g. "SC0011", or NULL when the failure
+ * has none. Valid until the next call fails.
+ */
+const char *sc_last_error_code(void);
+
+/* Parses the FlashDevice of the FLM in data into out. */
+sc_status sc_parse_flm(const uint8_t *data, size_t len, sc_flash_device *out);
+
+/* Releases the sector table sc_parse_flm() allocated. */
+void sc_flash_device_free(sc_flash_device *device);
+
+/*
+ * Converts the FLM in data into a stub, written to out as a NUL terminated string in format
+ * ("json", "yaml", "c-header" or "rust", NULL for JSON). name NULL means "flash" and ram_size 0
+ * fills in what the algorithm needs. Release the string with sc_string_free().
+ */
+sc_status sc_compose_stub(const uint8_t *data, size_t len, const char *name, bool is_default, uint32_t ram_size,
+                          const char *format, char **out);
+
+/* Releases a string returned by sc_compose_stub(). */
+void sc_string_free(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
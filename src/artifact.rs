@@ -0,0 +1,243 @@
+//! Uploading generated files to generic HTTP artifact repositories, the raw or generic
+//! repositories of Artifactory, Nexus, GitLab and the like, for CI pipelines that keep their build
+//! output there.
+//!
+//! Each file is sent to `<base URL>/<path>` with PUT, or POST for repositories that want it, and
+//! an `X-Checksum-Sha256` header, which Artifactory checks the upload against. Other repositories
+//! ignore the header, so the file is fetched back afterwards and its SHA-256 compared. Transport
+//! errors, 5xx and 429 answers and mismatching checksums are retried with a doubling delay; other
+//! statuses fail at once, retrying won't fix a refused token.
+
+use std::{fmt, io::Read, thread, time::Duration};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Time a repository gets to answer each request.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum ArtifactError {
+    #[error("{method} {url} failed with status {status}, {body}")]
+    Status { method: String, url: String, status: u16, body: String },
+
+    #[error("{method} {url} failed, {reason}")]
+    Transport { method: String, url: String, reason: String },
+
+    #[error("{url} holds SHA-256 {actual} after the upload, expected {expected}")]
+    ChecksumMismatch { url: String, expected: String, actual: String },
+
+    #[error("Invalid repository URL {0}, expected http:// or https:// and a host")]
+    Url(String),
+}
+
+impl ArtifactError {
+    /// Whether trying again may help: the connection or the server failed, not the request.
+    fn is_transient(&self) -> bool {
+        match self {
+            ArtifactError::Status { status, .. } => *status >= 500 || *status == 429,
+            ArtifactError::Transport { .. } | ArtifactError::ChecksumMismatch { .. } => true,
+            ArtifactError::Url(_) => false,
+        }
+    }
+}
+
+/// HTTP method files are uploaded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UploadMethod {
+    /// What Artifactory, Nexus raw repositories and GitLab generic packages take.
+    #[default]
+    Put,
+    Post,
+}
+
+impl UploadMethod {
+    fn name(self) -> &'static str {
+        match self {
+            UploadMethod::Put => "PUT",
+            UploadMethod::Post => "POST",
+        }
+    }
+}
+
+/// A file in the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedArtifact {
+    pub url: String,
+    pub sha256: String,
+    pub size: usize,
+    /// Requests it took, more than one when uploads were retried.
+    pub attempts: u32,
+}
+
+/// Uploads files below a base URL of a repository.
+#[derive(Clone)]
+pub struct ArtifactUploader {
+    base: String,
+    method: UploadMethod,
+    /// Header carrying the credentials, `Authorization` for bearer and basic authentication.
+    auth: Option<(String, String)>,
+    retries: u32,
+    retry_delay: Duration,
+    verify: bool,
+}
+
+/// Leaves the credentials out of logs.
+impl fmt::Debug for ArtifactUploader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArtifactUploader")
+            .field("base", &self.base)
+            .field("method", &self.method)
+            .field("auth", &self.auth.as_ref().map(|(name, _)| name))
+            .field("retries", &self.retries)
+            .field("retry_delay", &self.retry_delay)
+            .field("verify", &self.verify)
+            .finish()
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Percent-encodes a path, keeping the slashes between its segments.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+impl ArtifactUploader {
+    /// Uploads below `base`, e.g. `https://artifactory.example.com/artifactory/stubs-local/nightly`.
+    /// By default files are PUT without credentials, retried 3 times from a 1 second delay and
+    /// verified.
+    pub fn new(base: &str) -> Result<Self, ArtifactError> {
+        let host = base.split_once("://").filter(|(scheme, _)| matches!(*scheme, "http" | "https")).map(|(_, rest)| rest);
+        if host.is_none_or(|host| host.is_empty() || host.starts_with('/')) {
+            return Err(ArtifactError::Url(base.to_string()));
+        }
+
+        Ok(ArtifactUploader {
+            base: base.trim_end_matches('/').to_string(),
+            method: UploadMethod::Put,
+            auth: None,
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+            verify: true,
+        })
+    }
+
+    pub fn method(mut self, method: UploadMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sends `token` as `Authorization: Bearer`.
+    pub fn bearer(self, token: &str) -> Self {
+        self.header("Authorization", &format!("Bearer {}", token))
+    }
+
+    /// Sends `user` and `password` as `Authorization: Basic`.
+    pub fn basic(self, user: &str, password: &str) -> Self {
+        self.header("Authorization", &format!("Basic {}", base64::encode(format!("{}:{}", user, password))))
+    }
+
+    /// Sends the credentials in a header of the repository's own, e.g. `X-JFrog-Art-Api` or
+    /// GitLab's `PRIVATE-TOKEN`.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.auth = Some((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Tries failed uploads `retries` more times, waiting `delay` and then twice as long each time.
+    pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Whether uploads are fetched back to compare their checksum.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// URL of the file at `path` below the base URL.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base, encode_path(path.trim_start_matches('/')))
+    }
+
+    /// Uploads `data` to `path` below the base URL, retrying and verifying as configured.
+    pub fn upload(&self, path: &str, data: &[u8], content_type: &str) -> Result<UploadedArtifact, ArtifactError> {
+        let url = self.url(path);
+        let sha256 = sha256_hex(data);
+        let mut delay = self.retry_delay;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let result = self.send(&url, data, content_type, &sha256).and_then(|_| match self.verify {
+                true => self.check(&url, &sha256),
+                false => Ok(()),
+            });
+            match result {
+                Ok(()) => return Ok(UploadedArtifact { url, sha256, size: data.len(), attempts }),
+                Err(err) if err.is_transient() && attempts <= self.retries => {
+                    tracing::warn!("Upload to {} failed, retrying in {:?}: {}", url, delay, err);
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = ureq::AgentBuilder::new().timeout(TIMEOUT).build().request(method, url);
+        match &self.auth {
+            Some((name, value)) => request.set(name, value),
+            None => request,
+        }
+    }
+
+    fn send(&self, url: &str, data: &[u8], content_type: &str, sha256: &str) -> Result<(), ArtifactError> {
+        let method = self.method.name();
+        let request = self.request(method, url).set("Content-Type", content_type).set("X-Checksum-Sha256", sha256);
+        request.send_bytes(data).map(drop).map_err(|err| failure(method, url, err))
+    }
+
+    /// Fetches the upload back and compares its SHA-256.
+    fn check(&self, url: &str, expected: &str) -> Result<(), ArtifactError> {
+        let response = self.request("GET", url).call().map_err(|err| failure("GET", url, err))?;
+        let mut data = Vec::new();
+        response.into_reader().read_to_end(&mut data).map_err(|err| ArtifactError::Transport {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            reason: err.to_string(),
+        })?;
+
+        let actual = sha256_hex(&data);
+        match actual == expected {
+            true => Ok(()),
+            false => Err(ArtifactError::ChecksumMismatch { url: url.to_string(), expected: expected.to_string(), actual }),
+        }
+    }
+}
+
+fn failure(method: &str, url: &str, err: ureq::Error) -> ArtifactError {
+    match err {
+        ureq::Error::Status(status, response) => ArtifactError::Status {
+            method: method.to_string(),
+            url: url.to_string(),
+            status,
+            body: response.into_string().unwrap_or_default(),
+        },
+        err => ArtifactError::Transport { method: method.to_string(), url: url.to_string(), reason: err.to_string() },
+    }
+}
@@ -0,0 +1,93 @@
+use std::{env, path::PathBuf};
+
+use clap::Args;
+
+use soulcomposer::{
+    mqtt::{connect_tcp, ConnectOptions, FleetTopics, UpdateKind},
+    prog::{
+        arm::stub_group::ArmFlashStubGroup,
+        export::{export_model, export_validated, OutputFormat},
+    },
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+    input,
+};
+
+/// Environment variable holding the broker password of --username.
+const PASSWORD_VAR: &str = "MQTT_PASSWORD";
+
+#[derive(Debug, Args)]
+pub struct AnnounceArgs {
+    /// Algorithms to announce as stubs, FLMs or stubs written by `convert`.
+    pub inputs: Vec<PathBuf>,
+
+    /// Manifests written by `merge`.
+    #[arg(long)]
+    pub manifest: Vec<PathBuf>,
+
+    /// Names of stubs or manifests to withdraw from --device.
+    #[arg(long)]
+    pub withdraw: Vec<String>,
+
+    /// Broker as host or host:port, port 1883 when left out.
+    #[arg(long)]
+    pub broker: Option<String>,
+
+    /// First levels of the fleet's topics.
+    #[arg(long, default_value = "soul-injector")]
+    pub topic_prefix: String,
+
+    /// User name on the broker, the password is read from MQTT_PASSWORD.
+    #[arg(long)]
+    pub username: Option<String>,
+
+    #[arg(long, default_value = "soul-composer")]
+    pub client_id: String,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: AnnounceArgs) -> Result<(), CliError> {
+    let device = args.source.device.clone().ok_or(CliError::AnnounceDeviceRequired)?;
+    let broker = args.broker.as_deref().ok_or(CliError::BrokerRequired)?;
+
+    // Everything is read and converted before connecting.
+    let mut messages = Vec::new();
+    for path in &args.inputs {
+        let stub = load_stub(path, None, &args.options, &args.source)?.validate()?;
+        messages.push((UpdateKind::Stub, stub.name.clone(), export_validated(&stub, OutputFormat::Json)?));
+    }
+    for path in &args.manifest {
+        let group: ArmFlashStubGroup = serde_json::from_slice(&input::read(path)?)
+            .map_err(|err| CliError::StubParse { path: path.clone(), reason: err.to_string() })?;
+        messages.push((UpdateKind::Manifest, group.name.clone(), export_model(&group, OutputFormat::Json)?));
+    }
+    if messages.is_empty() && args.withdraw.is_empty() {
+        return Err(CliError::NothingToAnnounce);
+    }
+
+    let mut options = ConnectOptions::new(args.client_id.as_str());
+    if let Some(username) = &args.username {
+        options = options.credentials(username.as_str(), env::var(PASSWORD_VAR).unwrap_or_default());
+    }
+    let mut client = connect_tcp(broker, &options)?;
+    let topics = FleetTopics::new(&args.topic_prefix);
+    for (kind, name, payload) in &messages {
+        let update = topics.announce(&mut client, &device, *kind, name, payload)?;
+        println!("Announced {:?} {} on {}", update.kind, update.name, update.topic);
+    }
+    for name in &args.withdraw {
+        let update = topics.withdraw(&mut client, &device, UpdateKind::Stub, name)?;
+        println!("Withdrew {} from {}", update.name, update.topic);
+    }
+    client.disconnect()?;
+
+    Ok(())
+}
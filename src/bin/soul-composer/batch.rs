@@ -0,0 +1,205 @@
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{Args, ValueEnum};
+use glob::{glob_with, MatchOptions};
+
+use soulcomposer::{
+    artifact::{ArtifactUploader, UploadMethod},
+    progress::{Progress, ProgressSink},
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{check_output, compose, write_stub, OutputOptions, SourceOptions, StubOptions},
+    progress_line::ProgressLine,
+    webhook::{BatchFailure, Notice, Webhooks},
+};
+
+#[derive(Debug, Args)]
+pub struct BatchArgs {
+    /// Glob pattern of the algorithms to convert, e.g. "packs/**/*.FLM". Matching ignores case.
+    pub pattern: String,
+
+    /// Directory for the stubs, the layout below the pattern's fixed prefix is kept.
+    #[arg(short, long, default_value = ".")]
+    pub output: PathBuf,
+
+    /// URL that is POSTed a JSON notice when the run ends, repeat for more.
+    #[arg(long = "webhook")]
+    pub webhooks: Vec<String>,
+
+    #[command(flatten)]
+    pub output_options: OutputOptions,
+
+    #[command(flatten)]
+    pub artifacts: ArtifactOptions,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+/// Environment variable holding the token of the artifact repository.
+const TOKEN_VAR: &str = "ARTIFACT_TOKEN";
+
+/// Environment variable holding the password of --upload-user.
+const PASSWORD_VAR: &str = "ARTIFACT_PASSWORD";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Method {
+    Put,
+    Post,
+}
+
+/// Pushing the stubs to an HTTP artifact repository as they are written.
+#[derive(Debug, Args)]
+pub struct ArtifactOptions {
+    /// Base URL of an Artifactory, Nexus or other HTTP repository to upload each stub to, with the
+    /// layout below -o.
+    #[arg(long)]
+    pub upload_url: Option<String>,
+
+    #[arg(long, value_enum, default_value = "put")]
+    pub upload_method: Method,
+
+    /// User for basic authentication, the password is read from ARTIFACT_PASSWORD. Without it
+    /// ARTIFACT_TOKEN is sent as a bearer token, if set.
+    #[arg(long)]
+    pub upload_user: Option<String>,
+
+    /// Header to send ARTIFACT_TOKEN in instead, e.g. X-JFrog-Art-Api or PRIVATE-TOKEN.
+    #[arg(long)]
+    pub upload_auth_header: Option<String>,
+
+    /// Times a failed upload is tried again.
+    #[arg(long, default_value_t = 3)]
+    pub upload_retries: u32,
+
+    /// Don't fetch uploads back to compare their SHA-256.
+    #[arg(long)]
+    pub no_upload_verify: bool,
+}
+
+impl ArtifactOptions {
+    /// The uploader of --upload-url, `None` without one.
+    fn uploader(&self) -> Result<Option<ArtifactUploader>, CliError> {
+        let url = match &self.upload_url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let method = match self.upload_method {
+            Method::Put => UploadMethod::Put,
+            Method::Post => UploadMethod::Post,
+        };
+        let mut uploader = ArtifactUploader::new(url)?
+            .method(method)
+            .retries(self.upload_retries, Duration::from_secs(1))
+            .verify(!self.no_upload_verify);
+        let token = env::var(TOKEN_VAR).ok();
+        match (&self.upload_user, &self.upload_auth_header, token) {
+            (Some(user), _, _) => uploader = uploader.basic(user, &env::var(PASSWORD_VAR).unwrap_or_default()),
+            (None, Some(header), Some(token)) => uploader = uploader.header(header, &token),
+            (None, None, Some(token)) => uploader = uploader.bearer(&token),
+            (None, _, None) => {}
+        }
+        Ok(Some(uploader))
+    }
+}
+
+/// The leading directories of `pattern` that contain no wildcards.
+fn fixed_prefix(pattern: &str) -> PathBuf {
+    let mut prefix = PathBuf::new();
+    let components: Vec<Component<'_>> = Path::new(pattern).components().collect();
+    for component in &components[..components.len().saturating_sub(1)] {
+        if component.as_os_str().to_string_lossy().contains(['*', '?', '[']) {
+            break;
+        }
+        prefix.push(component);
+    }
+    prefix
+}
+
+/// Uploads the stub written to `output` to `relative` below the repository URL.
+fn upload(uploader: &ArtifactUploader, output: &Path, relative: &Path) -> Result<(), CliError> {
+    let data = fs::read(output).map_err(CliError::io(output))?;
+    let segments: Vec<String> = relative.components().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect();
+    let artifact = uploader.upload(&segments.join("/"), &data, "application/octet-stream")?;
+    tracing::info!(url = %artifact.url, sha256 = %artifact.sha256, attempts = artifact.attempts, "uploaded stub");
+    Ok(())
+}
+
+pub fn run(args: BatchArgs) -> Result<(), CliError> {
+    let options = MatchOptions { case_sensitive: false, ..Default::default() };
+    let paths = glob_with(&args.pattern, options).map_err(|err| CliError::Pattern(err.to_string()))?;
+    let prefix = fixed_prefix(&args.pattern);
+    let format = args.output_options.format();
+    let uploader = args.artifacts.uploader()?.filter(|_| !args.output_options.dry_run);
+
+    let mut converted = 0;
+    let mut failures = Vec::new();
+    let mut files = Vec::new();
+    for entry in paths {
+        match entry {
+            Ok(path) if path.is_file() => files.push(path),
+            Ok(_) => {}
+            Err(err) => failures.push((err.path().to_path_buf(), err.to_string())),
+        }
+    }
+
+    let mut progress = ProgressLine::new();
+    for (completed, path) in files.iter().enumerate() {
+        progress.progress(Progress { completed, total: files.len(), current: &path.to_string_lossy() });
+
+        let relative = path.strip_prefix(&prefix).unwrap_or(path);
+        let output = args.output.join(relative).with_extension(format.extension());
+        let result = check_output(path, &output).and_then(|()| compose(path, None, &args.options, &args.source)).and_then(|stub| {
+            match output.parent() {
+                Some(parent) if !args.output_options.dry_run => fs::create_dir_all(parent).map_err(CliError::io(parent))?,
+                _ => {}
+            }
+            write_stub(&stub, &output, &args.output_options)?;
+            match &uploader {
+                Some(uploader) => upload(uploader, &output, &relative.with_extension(format.extension())),
+                None => Ok(()),
+            }
+        });
+
+        progress.clear();
+        match result {
+            Ok(()) => {
+                println!("ok      {}", path.display());
+                converted += 1;
+            }
+            Err(err) => {
+                println!("failed  {}: {}", path.display(), err);
+                failures.push((path.clone(), err.to_string()));
+            }
+        }
+    }
+    progress.finish(files.len());
+
+    println!("\n{} converted, {} failed", converted, failures.len());
+    for (path, reason) in &failures {
+        println!("  {}: {}", path.display(), reason);
+    }
+    Webhooks::new(args.webhooks, "batch").notify(&Notice::BatchCompleted {
+        pattern: args.pattern.clone(),
+        converted,
+        failures: failures.iter().map(|(path, reason)| BatchFailure::new(path, reason)).collect(),
+    });
+
+    if !failures.is_empty() {
+        return Err(CliError::BatchFailed(failures.len()));
+    }
+    if converted == 0 {
+        return Err(CliError::NoMatches(args.pattern));
+    }
+
+    Ok(())
+}
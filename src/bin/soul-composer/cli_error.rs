@@ -0,0 +1,246 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+#[cfg(feature = "database")]
+use soulcomposer::database::DatabaseError;
+#[cfg(feature = "encryption")]
+use soulcomposer::encryption::EncryptionError;
+#[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+use soulcomposer::hil::HilError;
+#[cfg(feature = "sync")]
+use soulcomposer::git_library::SyncError;
+#[cfg(feature = "ota")]
+use soulcomposer::ota::OtaError;
+#[cfg(feature = "upload")]
+use soulcomposer::injector::UploadError;
+#[cfg(feature = "announce")]
+use soulcomposer::mqtt::MqttError;
+#[cfg(feature = "signing")]
+use soulcomposer::signing::SigningError;
+#[cfg(feature = "flash")]
+use soulcomposer::probe::ProbeError;
+#[cfg(feature = "publish")]
+use soulcomposer::publish::PublishError;
+use soulcomposer::{
+    artifact::ArtifactError,
+    msc::MscError,
+    pack::pack_error::PackError,
+    prog::{
+        arm::arm_error::ArmError, export::export_error::ExportError, generic::generic_error::GenericError,
+        riscv::riscv_error::RiscvError, xtensa::xtensa_error::XtensaError,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("Failed to access {path}, {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Invalid Intel HEX record on line {line}, {reason}")]
+    HexParse { line: usize, reason: String },
+
+    #[error("{0} has no symbols, pass a loader descriptor with --descriptor to convert it")]
+    DescriptorRequired(PathBuf),
+
+    #[error("--device is required to pick a device from the pack")]
+    DeviceRequired,
+
+    #[error("Invalid glob pattern, {0}")]
+    Pattern(String),
+
+    #[error("No files match {0}")]
+    NoMatches(String),
+
+    #[error("{0} files failed to convert")]
+    BatchFailed(usize),
+
+    #[error("Invalid configuration in {path}, {reason}")]
+    Config { path: PathBuf, reason: String },
+
+    #[error("Failed to read the stub in {path}, {reason}")]
+    StubParse { path: PathBuf, reason: String },
+
+    #[error("The algorithms differ in {0} fields")]
+    Differences(usize),
+
+    #[error("The pack lists no flash algorithms for {0}")]
+    NoAlgorithms(String),
+
+    #[error("--family is required to pick one of {0}")]
+    FamilyRequired(String),
+
+    #[error("Failed to download {url}, {reason}")]
+    Download { url: String, reason: String },
+
+    #[error("No PDSC files cached in {0}, run with --update first")]
+    CacheEmpty(PathBuf),
+
+    #[error("No devices match {0}")]
+    NoDevices(String),
+
+    #[cfg(feature = "serve")]
+    #[error("Failed to listen on {address}, {reason}")]
+    Serve { address: String, reason: String },
+
+    #[error("--split-at splits a single algorithm, {0} were given")]
+    SplitNeedsOneInput(usize),
+
+    #[error("Watching needs a file, stdin can't be watched")]
+    WatchStdin,
+
+    #[error("{0} is the input, refusing to overwrite it, pass -o to write elsewhere")]
+    OutputIsInput(PathBuf),
+
+    #[cfg(feature = "flash")]
+    #[error(transparent)]
+    Probe(#[from] ProbeError),
+
+    #[cfg(feature = "database")]
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+
+    #[cfg(feature = "upload")]
+    #[error("Nothing to upload, give algorithms or --manifest")]
+    NothingToUpload,
+
+    #[cfg(feature = "upload")]
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+
+    #[cfg(feature = "signing")]
+    #[error("--key or signing-key in the configuration is required to sign")]
+    KeyRequired,
+
+    #[cfg(feature = "signing")]
+    #[error("{0} exists, pass --force to replace it")]
+    KeyExists(PathBuf),
+
+    #[cfg(feature = "signing")]
+    #[error("{path}: {source}")]
+    Signing { path: PathBuf, source: SigningError },
+
+    #[cfg(feature = "ota")]
+    #[error(transparent)]
+    Ota(#[from] OtaError),
+
+    #[cfg(feature = "encryption")]
+    #[error("--keys or encryption-keys in the configuration is required")]
+    KeysRequired,
+
+    #[cfg(feature = "encryption")]
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+
+    #[cfg(feature = "sync")]
+    #[error("--remote or remote in the [sync] configuration is required")]
+    RemoteRequired,
+
+    #[cfg(feature = "sync")]
+    #[error("--device is required to store algorithms in the library")]
+    LibraryDeviceRequired,
+
+    #[cfg(feature = "sync")]
+    #[error(transparent)]
+    Sync(#[from] SyncError),
+
+    #[cfg(feature = "publish")]
+    #[error("--device is required to list the files in the index")]
+    PublishDeviceRequired,
+
+    #[cfg(feature = "publish")]
+    #[error("{0} or the [publish] configuration is required")]
+    PublishTarget(&'static str),
+
+    #[cfg(feature = "publish")]
+    #[error("Set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY to publish")]
+    CredentialsRequired,
+
+    #[cfg(feature = "publish")]
+    #[error("Nothing to publish, give algorithms, --manifest or --bundle")]
+    NothingToPublish,
+
+    #[cfg(feature = "publish")]
+    #[error(transparent)]
+    Publish(#[from] PublishError),
+
+    #[cfg(feature = "announce")]
+    #[error("--device is required, it names the topics of the algorithms")]
+    AnnounceDeviceRequired,
+
+    #[cfg(feature = "announce")]
+    #[error("--broker or broker in the [mqtt] configuration is required")]
+    BrokerRequired,
+
+    #[cfg(feature = "announce")]
+    #[error("Nothing to announce, give algorithms, --manifest or --withdraw")]
+    NothingToAnnounce,
+
+    #[cfg(feature = "announce")]
+    #[error(transparent)]
+    Mqtt(#[from] MqttError),
+
+    #[error("{tool} failed, {reason}")]
+    EspTool { tool: String, reason: String },
+
+    #[error(transparent)]
+    Artifact(#[from] ArtifactError),
+
+    #[error(transparent)]
+    Msc(#[from] MscError),
+
+    #[error("{0} files of the layout are missing or differ")]
+    MscCheckFailed(usize),
+
+    #[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+    #[error("Give --port for a Soul Injector programmer or --chip-ram for a debug probe")]
+    HilTargetRequired,
+
+    #[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+    #[error("{0} stubs failed on the target")]
+    HilFailed(usize),
+
+    #[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+    #[error(transparent)]
+    Hil(#[from] HilError),
+
+    #[cfg(feature = "emulator")]
+    #[error("The algorithm failed the simulated run")]
+    SimulationFailed,
+
+    #[error("Validation found {0} errors")]
+    ValidationFailed(usize),
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+
+    #[error(transparent)]
+    Descriptor(#[from] GenericError),
+
+    #[error(transparent)]
+    Pack(#[from] PackError),
+
+    #[error(transparent)]
+    Export(#[from] ExportError),
+
+    #[error(transparent)]
+    Xtensa(#[from] XtensaError),
+
+    #[error(transparent)]
+    Riscv(#[from] RiscvError),
+}
+
+impl CliError {
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> CliError {
+        let path = path.into();
+        move |source| CliError::Io { path, source }
+    }
+
+    /// The stable `ArmError::code` behind the failure, if it came from composing.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            CliError::Arm(err) => Some(err.code()),
+            _ => None,
+        }
+    }
+}
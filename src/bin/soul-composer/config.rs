@@ -0,0 +1,236 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Command, ValueEnum};
+use serde::Deserialize;
+
+use crate::{
+    batch::Method,
+    cli_error::CliError,
+    convert::{Format, Naming, StrictnessLevel},
+};
+
+/// Name of the project configuration, looked up in the working directory and its parents.
+pub const CONFIG_FILE: &str = "soul-composer.toml";
+
+/// Project settings from `soul-composer.toml`. Each one replaces the default of the matching
+/// flag, so the command line still wins.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// `--output-format`
+    pub output_format: Option<String>,
+    /// `--field-naming`
+    pub field_naming: Option<String>,
+    /// Where stubs are written, `-o` of batch and pack and `--output-dir` of convert. Relative
+    /// to the configuration file.
+    pub output_dir: Option<PathBuf>,
+    /// `--strictness`
+    pub strictness: Option<String>,
+    /// `--key` of sign, the secret key written by `keygen`. Relative to the configuration file.
+    pub signing_key: Option<PathBuf>,
+    /// `--keys` of encrypt and decrypt, a key file or directory of them. Relative to the
+    /// configuration file.
+    pub encryption_keys: Option<PathBuf>,
+    /// `--webhook` of serve and batch.
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    #[serde(default)]
+    pub ram: RamConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub publish: PublishConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub artifact: ArtifactConfig,
+
+    /// The file this was read from.
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// The `[ram]` table, RAM of the target and how the blob is laid out in it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RamConfig {
+    /// Start of the RAM, `--ram-base` of simulate and the start of `--chip-ram` of flash and self-test.
+    pub base: Option<u32>,
+    /// `--ram-size`, and the size of `--chip-ram` of flash and self-test.
+    pub size: Option<u32>,
+    /// `--split-data`
+    pub split_data: Option<bool>,
+    /// `--data-alignment`
+    pub data_alignment: Option<u32>,
+    /// `--padding`
+    pub padding: Option<u8>,
+}
+
+/// The `[sync]` table, the git repository of the stub library.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SyncConfig {
+    /// `--remote`
+    pub remote: Option<String>,
+    /// `--branch`
+    pub branch: Option<String>,
+    /// `--checkout`, relative to the configuration file.
+    pub checkout: Option<PathBuf>,
+}
+
+/// The `[publish]` table, the bucket stubs are published to. Credentials stay in the environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PublishConfig {
+    /// `--endpoint`
+    pub endpoint: Option<String>,
+    /// `--region`
+    pub region: Option<String>,
+    /// `--bucket`
+    pub bucket: Option<String>,
+    /// `--prefix`
+    pub prefix: Option<String>,
+}
+
+/// The `[mqtt]` table, the broker programmers listen to. The password stays in the environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MqttConfig {
+    /// `--broker`
+    pub broker: Option<String>,
+    /// `--topic-prefix`
+    pub topic_prefix: Option<String>,
+    /// `--username`
+    pub username: Option<String>,
+    /// `--client-id`
+    pub client_id: Option<String>,
+}
+
+/// The `[artifact]` table, the HTTP repository batch uploads to. Credentials stay in the
+/// environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ArtifactConfig {
+    /// `--upload-url`
+    pub url: Option<String>,
+    /// `--upload-method`
+    pub method: Option<String>,
+    /// `--upload-user`
+    pub user: Option<String>,
+    /// `--upload-auth-header`
+    pub auth_header: Option<String>,
+    /// `--upload-retries`
+    pub retries: Option<u32>,
+    /// The opposite of `--no-upload-verify`.
+    pub verify: Option<bool>,
+}
+
+impl Config {
+    /// Reads the configuration at `path`, checking the values flags would reject.
+    pub fn load(path: &Path) -> Result<Config, CliError> {
+        let invalid = |reason: String| CliError::Config { path: path.to_path_buf(), reason };
+        let text = fs::read_to_string(path).map_err(CliError::io(path))?;
+        let mut config: Config = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+
+        if let Some(format) = &config.output_format {
+            <Format as ValueEnum>::from_str(format, true).map_err(invalid)?;
+        }
+        if let Some(naming) = &config.field_naming {
+            <Naming as ValueEnum>::from_str(naming, true).map_err(invalid)?;
+        }
+        if let Some(strictness) = &config.strictness {
+            <StrictnessLevel as ValueEnum>::from_str(strictness, true).map_err(invalid)?;
+        }
+        if let Some(method) = &config.artifact.method {
+            <Method as ValueEnum>::from_str(method, true).map_err(invalid)?;
+        }
+
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.output_dir = config.output_dir.map(|dir| base.join(dir));
+        config.signing_key = config.signing_key.map(|key| base.join(key));
+        config.encryption_keys = config.encryption_keys.map(|keys| base.join(keys));
+        config.sync.checkout = config.sync.checkout.map(|checkout| base.join(checkout));
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    /// Finds `soul-composer.toml` in the working directory or the closest parent holding one.
+    pub fn discover() -> Result<Option<Config>, CliError> {
+        let cwd = env::current_dir().map_err(CliError::io("."))?;
+        match cwd.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file()) {
+            Some(path) => Config::load(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Flag defaults this configuration sets, by argument id, for the subcommand `command`.
+    fn defaults(&self, command: &str) -> Vec<(&'static str, String)> {
+        let mut defaults = Vec::new();
+        let mut set = |id: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                defaults.push((id, value));
+            }
+        };
+
+        set("output_format", self.output_format.clone());
+        set("field_naming", self.field_naming.clone());
+        let output_dir = self.output_dir.as_ref().map(|dir| dir.display().to_string());
+        match command {
+            "batch" | "pack" => set("output", output_dir),
+            _ => set("output_dir", output_dir),
+        }
+        set("strictness", self.strictness.clone());
+        set("signing_key", self.signing_key.as_ref().map(|key| key.display().to_string()));
+        set("encryption_keys", self.encryption_keys.as_ref().map(|keys| keys.display().to_string()));
+        set("ram_base", self.ram.base.map(|base| format!("{:#x}", base)));
+        set("ram_size", self.ram.size.map(|size| size.to_string()));
+        if let (Some(base), Some(size)) = (self.ram.base, self.ram.size) {
+            set("chip_ram", Some(format!("{:#x}:{}", base, size)));
+        }
+        set("split_data", self.ram.split_data.map(|split| split.to_string()));
+        set("data_alignment", self.ram.data_alignment.map(|alignment| alignment.to_string()));
+        set("padding", self.ram.padding.map(|padding| padding.to_string()));
+        set("remote", self.sync.remote.clone());
+        set("branch", self.sync.branch.clone());
+        set("checkout", self.sync.checkout.as_ref().map(|checkout| checkout.display().to_string()));
+        set("endpoint", self.publish.endpoint.clone());
+        set("region", self.publish.region.clone());
+        set("bucket", self.publish.bucket.clone());
+        set("prefix", self.publish.prefix.clone());
+        set("broker", self.mqtt.broker.clone());
+        set("topic_prefix", self.mqtt.topic_prefix.clone());
+        set("username", self.mqtt.username.clone());
+        set("client_id", self.mqtt.client_id.clone());
+        set("upload_url", self.artifact.url.clone());
+        set("upload_method", self.artifact.method.clone());
+        set("upload_user", self.artifact.user.clone());
+        set("upload_auth_header", self.artifact.auth_header.clone());
+        set("upload_retries", self.artifact.retries.map(|retries| retries.to_string()));
+        set("no_upload_verify", self.artifact.verify.map(|verify| (!verify).to_string()));
+        defaults
+    }
+
+    /// Installs the settings as defaults of the subcommands that have the matching flags.
+    pub fn apply(&self, command: Command) -> Command {
+        command.mut_subcommands(|subcommand| {
+            let has = |subcommand: &Command, id: &str| subcommand.get_arguments().any(|arg| arg.get_id() == id);
+            let defaults = self.defaults(subcommand.get_name());
+            let subcommand = defaults.into_iter().fold(subcommand, |subcommand, (id, value)| {
+                if has(&subcommand, id) {
+                    subcommand.mut_arg(id, |arg| arg.default_value(value))
+                } else {
+                    subcommand
+                }
+            });
+            // The one list setting, repeated flags replace it as a whole.
+            if !self.webhooks.is_empty() && has(&subcommand, "webhooks") {
+                subcommand.mut_arg("webhooks", |arg| arg.default_values(&self.webhooks))
+            } else {
+                subcommand
+            }
+        })
+    }
+}
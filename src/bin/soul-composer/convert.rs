@@ -0,0 +1,507 @@
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+
+use soulcomposer::{
+    compose::arm::{compose_stub, ComposeOptions, Elf},
+    diagnostic::Diagnostic,
+    pack::pdsc::Pdsc,
+    prog::{
+        arm::{
+            arm_error::ArmError,
+            flash_stub_gen::{ArmFlashStub, ClockSource},
+            ram_layout::RamRequirement,
+            stub_group::ArmFlashStubGroup,
+            parse_options::{ConflictPolicy, ParseOptions, Strictness},
+        },
+        export::{export_model_named, export_named, naming::FieldNaming, OutputFormat},
+        generic::descriptor::LoaderDescriptor,
+    },
+};
+
+use crate::{
+    cli_error::CliError,
+    input::{self, InputFormat},
+    prompt::{fill_metadata, Prompter},
+    validate::lint,
+    watch::watch,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PageSizePolicy {
+    /// Keep the FLM page size.
+    Flm,
+    /// Take the PDSC page size.
+    Pdsc,
+    /// Fail when they differ.
+    Error,
+}
+
+impl From<PageSizePolicy> for ConflictPolicy {
+    fn from(policy: PageSizePolicy) -> Self {
+        match policy {
+            PageSizePolicy::Flm => ConflictPolicy::PreferFlm,
+            PageSizePolicy::Pdsc => ConflictPolicy::PreferPdsc,
+            PageSizePolicy::Error => ConflictPolicy::Error,
+        }
+    }
+}
+
+/// How picky parsing is, see `Strictness`. `validate` also fails on warnings when strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StrictnessLevel {
+    /// Cut over-long names and sector tables, accept other driver versions, warn about broken
+    /// sector tables.
+    Lenient,
+    /// Refuse malformed descriptors, warn about the rest.
+    Normal,
+    /// Refuse stubs that come with warnings.
+    Strict,
+}
+
+impl From<StrictnessLevel> for Strictness {
+    fn from(level: StrictnessLevel) -> Self {
+        match level {
+            StrictnessLevel::Lenient => Strictness::Lenient,
+            StrictnessLevel::Normal => Strictness::Normal,
+            StrictnessLevel::Strict => Strictness::Strict,
+        }
+    }
+}
+
+/// The formats stubs can be written in, see `OutputFormat`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// The stub model as JSON, what Soul Injector reads.
+    Json,
+    /// The stub model as YAML.
+    Yaml,
+    /// The stub model as CBOR.
+    Cbor,
+    /// The stub model as MessagePack.
+    Msgpack,
+    /// The raw RAM image.
+    Bin,
+    /// A C header with the image and its parameters.
+    CHeader,
+    /// Rust constants for `include!`.
+    Rust,
+    /// A probe-rs `flash_algorithms` entry.
+    ProbeRsYaml,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => OutputFormat::Json,
+            Format::Yaml => OutputFormat::Yaml,
+            Format::Cbor => OutputFormat::Cbor,
+            Format::Msgpack => OutputFormat::Msgpack,
+            Format::Bin => OutputFormat::Bin,
+            Format::CHeader => OutputFormat::CHeader,
+            Format::Rust => OutputFormat::Rust,
+            Format::ProbeRsYaml => OutputFormat::ProbeRsYaml,
+        }
+    }
+}
+
+/// Field names of the model formats, see `FieldNaming`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Naming {
+    /// `flashStartAddr`, what Soul Injector reads.
+    #[value(name = "camel-case")]
+    Camel,
+    /// `flash_start_addr`
+    #[value(name = "snake-case")]
+    Snake,
+    /// `flash-start-addr`
+    #[value(name = "kebab-case")]
+    Kebab,
+}
+
+impl From<Naming> for FieldNaming {
+    fn from(naming: Naming) -> Self {
+        match naming {
+            Naming::Camel => FieldNaming::Camel,
+            Naming::Snake => FieldNaming::Snake,
+            Naming::Kebab => FieldNaming::Kebab,
+        }
+    }
+}
+
+/// Options shared by every subcommand that writes stubs.
+#[derive(Debug, Args)]
+pub struct OutputOptions {
+    /// Format to write the stubs in, which also picks the file extension.
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub output_format: Format,
+
+    /// Field names of the JSON, YAML, CBOR and MessagePack output.
+    #[arg(long, value_enum, default_value_t = Naming::Camel)]
+    pub field_naming: Naming,
+
+    /// Compose and check everything, but only print what would be written.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl OutputOptions {
+    pub fn format(&self) -> OutputFormat {
+        self.output_format.into()
+    }
+
+    pub fn naming(&self) -> FieldNaming {
+        self.field_naming.into()
+    }
+}
+
+/// Options shared by every subcommand that composes stubs.
+#[derive(Debug, Clone, Args)]
+pub struct StubOptions {
+    /// Mark the stub as the default algorithm of the target.
+    #[arg(long)]
+    pub default: bool,
+
+    /// RAM available on the target in bytes, 0 fills in the computed requirement.
+    #[arg(long, default_value_t = 0, value_parser = parse_number)]
+    pub ram_size: u32,
+
+    /// What to do when the FLM and PDSC page sizes differ.
+    #[arg(long, value_enum, default_value_t = PageSizePolicy::Flm)]
+    pub page_size_policy: PageSizePolicy,
+
+    /// Init clock in Hz, overrides the PDSC.
+    #[arg(long, value_parser = parse_number)]
+    pub clock: Option<u32>,
+
+    /// Emit the data as a blob of its own.
+    #[arg(long)]
+    pub split_data: bool,
+
+    /// Alignment of the data relative to the load address, a power of two.
+    #[arg(long, default_value_t = 1, value_parser = parse_number)]
+    pub data_alignment: u32,
+
+    /// Byte the code is padded with up to the data.
+    #[arg(long, default_value_t = 0, value_parser = parse_byte)]
+    pub padding: u8,
+
+    /// Route entry points through shims ending in `bkpt`.
+    #[arg(long)]
+    pub breakpoint_shims: bool,
+
+    /// How picky to be about descriptors and warnings.
+    #[arg(long, value_enum, default_value_t = StrictnessLevel::Normal)]
+    pub strictness: StrictnessLevel,
+
+    /// Symbol of the descriptor, tried before `FlashDevice`.
+    #[arg(long)]
+    pub descriptor_symbol: Option<String>,
+
+    /// Metadata stored in the stub, `KEY=VALUE`, VALUE is JSON or else a string. Repeatable.
+    #[arg(long = "extra", value_name = "KEY=VALUE", value_parser = parse_extra)]
+    pub extra: Vec<(String, Value)>,
+}
+
+/// Where the metadata missing from an algorithm file comes from.
+#[derive(Debug, Args)]
+pub struct SourceOptions {
+    /// Loader descriptor (TOML or JSON) giving the entry points and flash of a HEX or bin image.
+    #[arg(long)]
+    pub descriptor: Option<PathBuf>,
+
+    /// Pack description to take the clock and page size from.
+    #[arg(long, requires = "device")]
+    pub pdsc: Option<PathBuf>,
+
+    /// Device or variant to look up in the PDSC.
+    #[arg(long)]
+    pub device: Option<String>,
+}
+
+impl StubOptions {
+    pub fn parse_options(&self) -> ParseOptions {
+        let mut options = ParseOptions {
+            page_size_policy: self.page_size_policy.into(),
+            breakpoint_shims: self.breakpoint_shims,
+            strictness: self.strictness.into(),
+            descriptor_symbol: self.descriptor_symbol.clone(),
+            ..Default::default()
+        };
+        options.blob_layout.split = self.split_data;
+        options.blob_layout.data_alignment = self.data_alignment;
+        options.blob_layout.padding = self.padding;
+        options
+    }
+
+    /// Applies the settings that override the algorithm and pack, run last.
+    pub fn apply_overrides(&self, stub: &mut ArmFlashStub) {
+        if let Some(clock) = self.clock {
+            stub.init_parameters.clock = clock;
+            stub.init_parameters.clock_source = ClockSource::User;
+        }
+        stub.extra.extend(self.extra.iter().cloned());
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// FLM, HEX or bin file holding the algorithm, `-` reads stdin.
+    pub input: PathBuf,
+
+    /// Where to write the stub, `-` for stdout. Next to the input with the extension of the
+    /// output format by default, or stdout when reading stdin.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Directory for the stub when `--output` isn't given, next to the input by default.
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub output_options: OutputOptions,
+
+    /// Stub name, the input file name by default.
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+
+    /// Print the worst-case erase and program time for an image of this many bytes at the flash start.
+    #[arg(long, value_parser = parse_number)]
+    pub estimate: Option<u32>,
+
+    /// Convert again whenever the input, descriptor or PDSC changes.
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Never prompt for a missing name or description, take the fallbacks instead. Implied when
+    /// stdin isn't a terminal and in watch mode.
+    #[arg(long)]
+    pub no_input: bool,
+}
+
+/// Accepts decimal or `0x` prefixed hexadecimal numbers.
+pub fn parse_number(value: &str) -> Result<u32, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|err| err.to_string())
+}
+
+/// Parses START:SIZE of a RAM region, the size may end in k or M.
+pub fn parse_ram(value: &str) -> Result<(u32, u32), String> {
+    let (start, size) = value.split_once(':').ok_or("expected START:SIZE")?;
+    let (size, scale) = match size.strip_suffix(['k', 'K']) {
+        Some(size) => (size, 1024),
+        None => match size.strip_suffix('M') {
+            Some(size) => (size, 1024 * 1024),
+            None => (size, 1),
+        },
+    };
+    let size = parse_number(size)?.checked_mul(scale).ok_or("size out of range")?;
+    Ok((parse_number(start)?, size))
+}
+
+/// A number that fits in a byte, decimal or `0x` prefixed.
+pub fn parse_byte(value: &str) -> Result<u8, String> {
+    u8::try_from(parse_number(value)?).map_err(|err| err.to_string())
+}
+
+/// `KEY=VALUE` of `--extra`, a VALUE that isn't JSON is taken as a string.
+pub fn parse_extra(value: &str) -> Result<(String, Value), String> {
+    let (key, value) = value.split_once('=').ok_or_else(|| format!("expected KEY=VALUE, got {:?}", value))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+/// The input file name without its extension, "stdin" for `-`.
+pub fn file_stem(path: &Path) -> String {
+    if input::is_stdio(path) {
+        return "stdin".to_string();
+    }
+
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Composes the stub for one algorithm file, logging the diagnostics.
+///
+/// `name` overrides the stub name, which otherwise comes from the descriptor or the file name.
+pub fn compose(input: &Path, name: Option<&str>, options: &StubOptions, source: &SourceOptions) -> Result<ArmFlashStub, CliError> {
+    let (stub, diagnostics) = compose_with_diagnostics(input, name, options, source)?;
+    for diagnostic in diagnostics {
+        tracing::warn!("{}", diagnostic);
+    }
+
+    Ok(stub)
+}
+
+/// Same as `compose`, returning the diagnostics instead of logging them.
+pub fn compose_with_diagnostics(
+    input: &Path,
+    name: Option<&str>,
+    options: &StubOptions,
+    source: &SourceOptions,
+) -> Result<(ArmFlashStub, Vec<Diagnostic>), CliError> {
+    let _span = tracing::info_span!("compose", input = %input.display()).entered();
+    let parse_options = options.parse_options();
+    let stub_name = name.map_or_else(|| file_stem(input), str::to_string);
+    let data = input::read(input)?;
+    let (mut stub, mut diagnostics) = match InputFormat::detect(input, &data) {
+        InputFormat::Elf => {
+            let elf = Elf::parse(&data).map_err(ArmError::from)?;
+            let mut compose_options = ComposeOptions::new(stub_name);
+            compose_options.default = options.default;
+            compose_options.ram_size = options.ram_size;
+            compose_options.parse = parse_options.clone();
+            compose_stub(&elf, &data, &compose_options)?
+        }
+        format => {
+            let path = source.descriptor.as_ref().ok_or_else(|| CliError::DescriptorRequired(input.to_path_buf()))?;
+            let text = fs::read_to_string(path).map_err(CliError::io(path))?;
+            let mut descriptor = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => LoaderDescriptor::from_json(&text)?,
+                _ => LoaderDescriptor::from_toml(&text)?,
+            };
+            if name.is_some() || descriptor.name.is_empty() {
+                descriptor.name = stub_name;
+            }
+
+            let image = input::load_image(format, &data)?;
+            let mut stub = ArmFlashStub::from_loader_descriptor(&descriptor, &image)?;
+            stub.default = options.default;
+            if options.ram_size != 0 {
+                RamRequirement::of_stub(&stub).check(options.ram_size)?;
+                stub.ram_size = options.ram_size;
+            }
+            (stub, Vec::new())
+        }
+    };
+
+    if let Some(path) = &source.pdsc {
+        let device_name = source.device.as_deref().ok_or(CliError::DeviceRequired)?;
+        let text = fs::read_to_string(path).map_err(CliError::io(path))?;
+        let pdsc = Pdsc::parse(&text)?;
+        diagnostics.extend(stub.apply_pdsc_device(pdsc.device(device_name)?, &parse_options)?);
+    }
+
+    options.apply_overrides(&mut stub);
+
+    Ok((stub, diagnostics))
+}
+
+/// Reads a stub written by `convert`, or composes one from an algorithm file.
+pub fn load_stub(path: &Path, name: Option<&str>, options: &StubOptions, source: &SourceOptions) -> Result<ArmFlashStub, CliError> {
+    let data = input::read(path)?;
+    if data.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{') {
+        return serde_json::from_slice(&data).map_err(|err| CliError::StubParse { path: path.to_path_buf(), reason: err.to_string() });
+    }
+
+    compose(path, name, options, source)
+}
+
+/// Refuses an `output` that is the `input` file, e.g. `--output-format bin` next to a `.bin`
+/// algorithm.
+pub fn check_output(input: &Path, output: &Path) -> Result<(), CliError> {
+    if input::is_stdio(input) || input::is_stdio(output) {
+        return Ok(());
+    }
+    match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(input), Ok(resolved)) if input == resolved => Err(CliError::OutputIsInput(output.to_path_buf())),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the stub in the chosen format, or describes what would be written on a dry run.
+pub fn write_stub(stub: &ArmFlashStub, output: &Path, options: &OutputOptions) -> Result<(), CliError> {
+    let findings = if options.dry_run { lint(stub, None, None) } else { Vec::new() };
+    write_output(&export_named(stub, options.format(), options.naming())?, output, options)?;
+    for finding in findings {
+        println!("  {}", finding);
+    }
+
+    Ok(())
+}
+
+/// Writes a group of algorithms, only the model formats can hold one.
+pub fn write_group(group: &ArmFlashStubGroup, output: &Path, options: &OutputOptions) -> Result<(), CliError> {
+    write_output(&export_model_named(group, options.format(), options.naming())?, output, options)
+}
+
+fn write_output(data: &[u8], output: &Path, options: &OutputOptions) -> Result<(), CliError> {
+    if !options.dry_run {
+        input::write(output, data)?;
+        tracing::info!(bytes = data.len(), output = %output.display(), "wrote stub");
+        return Ok(());
+    }
+
+    let target = if input::is_stdio(output) { "stdout".to_string() } else { output.display().to_string() };
+    println!("would write {}: {} bytes, crc32 {:08x}", target, data.len(), crc32fast::hash(data));
+    Ok(())
+}
+
+pub fn run(args: ConvertArgs) -> Result<(), CliError> {
+    if !args.watch {
+        return convert(&args);
+    }
+    if input::is_stdio(&args.input) {
+        return Err(CliError::WatchStdin);
+    }
+
+    let mut paths = vec![args.input.clone()];
+    paths.extend(args.source.descriptor.iter().chain(args.source.pdsc.iter()).cloned());
+    watch(|| paths.clone(), || convert(&args))
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), CliError> {
+    let mut stub = compose(&args.input, args.name.as_deref(), &args.options, &args.source)?;
+    let ask_default = !args.options.default && args.source.pdsc.is_none();
+    fill_metadata(&mut stub, ask_default, &Prompter::new(args.no_input || args.watch))?;
+
+    if let Some(size) = args.estimate {
+        let estimate = stub.estimate_programming_time(stub.flash_start_addr, size)?;
+        eprintln!(
+            "{} bytes: {} sectors erased in {} ms, {} pages programmed in {} ms, {} ms in total",
+            size,
+            estimate.sectors_erased,
+            estimate.erase_ms,
+            estimate.pages_programmed,
+            estimate.program_ms,
+            estimate.total_ms()
+        );
+    }
+
+    let output = match &args.output {
+        Some(output) => output.clone(),
+        None if input::is_stdio(&args.input) => PathBuf::from("-"),
+        None => {
+            let output = args.input.with_extension(args.output_options.format().extension());
+            match (&args.output_dir, output.file_name()) {
+                (Some(dir), Some(file_name)) => dir.join(file_name),
+                _ => output,
+            }
+        }
+    };
+    check_output(&args.input, &output)?;
+    if let Some(parent) = output.parent().filter(|parent| args.output_dir.is_some() && !parent.as_os_str().is_empty()) {
+        if !args.output_options.dry_run {
+            fs::create_dir_all(parent).map_err(CliError::io(parent))?;
+        }
+    }
+    write_stub(&stub, &output, &args.output_options)?;
+    if args.watch && !args.output_options.dry_run && !input::is_stdio(&output) {
+        println!("Wrote {}", output.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use soulcomposer::{
+    database::{StubDatabase, StubQuery},
+    prog::export::{export_model, OutputFormat},
+};
+
+use crate::{cli_error::CliError, convert::parse_number, input};
+
+#[derive(Debug, Args)]
+pub struct DbArgs {
+    /// SQLite database written by `pack --database`.
+    pub database: PathBuf,
+
+    /// Device name, matched ignoring case.
+    #[arg(long)]
+    pub device: Option<String>,
+
+    /// Vendor, matched ignoring case.
+    #[arg(long)]
+    pub vendor: Option<String>,
+
+    /// Only stubs whose flash holds this address.
+    #[arg(long, value_parser = parse_number)]
+    pub address: Option<u32>,
+
+    /// Leave out stubs validation found errors in.
+    #[arg(long)]
+    pub without_errors: bool,
+
+    /// Only stubs whose last run on hardware, `soul-composer hil`, passed with their current image.
+    #[arg(long)]
+    pub verified: bool,
+
+    /// Print the matching stubs as a JSON list instead of a table.
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn run(args: DbArgs) -> Result<(), CliError> {
+    let database = StubDatabase::open(&args.database)?;
+    let mut query = StubQuery::new();
+    query.device = args.device;
+    query.vendor = args.vendor;
+    query.without_errors = args.without_errors;
+    query.verified = args.verified;
+    if let Some(address) = args.address {
+        query = query.covering(address);
+    }
+    let stored = database.query(&query)?;
+
+    if args.json {
+        let stubs: Vec<_> = stored.iter().map(|stored| &stored.stub).collect();
+        return input::write(Path::new("-"), &export_model(&stubs, OutputFormat::Json)?);
+    }
+
+    for stored in &stored {
+        let stub = &stored.stub;
+        let source = match (&stored.source.pack, &stored.source.pack_version) {
+            (Some(pack), Some(version)) => format!("{} {}", pack, version),
+            (Some(pack), None) => pack.clone(),
+            _ => String::new(),
+        };
+        println!(
+            "{:>4}  {:<16}  {:<24}  {:#010x}..{:#010x}  image {:08x}  {} findings  {}",
+            stored.id,
+            stored.source.device,
+            stub.name,
+            stub.flash_start_addr,
+            stub.flash_end_addr,
+            stored.image_crc32,
+            stored.findings.len(),
+            source
+        );
+    }
+
+    Ok(())
+}
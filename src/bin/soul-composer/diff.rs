@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use soulcomposer::prog::arm::flash_stub_gen::ArmFlashStub;
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+};
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Old algorithm, an FLM or a stub written by `convert`, `-` reads stdin.
+    pub old: PathBuf,
+
+    /// New algorithm, an FLM or a stub written by `convert`.
+    pub new: PathBuf,
+
+    /// Exit with an error when the algorithms differ.
+    #[arg(long)]
+    pub exit_code: bool,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+fn load(path: &Path, args: &DiffArgs) -> Result<ArmFlashStub, CliError> {
+    // The name comes from the file, which is expected to differ.
+    load_stub(path, Some(""), &args.options, &args.source)
+}
+
+pub fn run(args: DiffArgs) -> Result<(), CliError> {
+    let old = load(&args.old, &args)?;
+    let new = load(&args.new, &args)?;
+    let diff = old.diff(&new);
+
+    if diff.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+
+    print!("{}", diff);
+
+    if args.exit_code {
+        return Err(CliError::Differences(diff.len()));
+    }
+
+    Ok(())
+}
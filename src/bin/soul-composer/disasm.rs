@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+};
+
+#[derive(Debug, Args)]
+pub struct DisasmArgs {
+    /// Algorithm to disassemble, an FLM or a stub written by `convert`, `-` reads stdin.
+    pub input: PathBuf,
+
+    /// Entry point to disassemble: Init, UnInit, EraseChip, EraseSector or ProgramPage.
+    #[arg(short, long, default_value = "ProgramPage")]
+    pub function: String,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: DisasmArgs) -> Result<(), CliError> {
+    let stub = load_stub(&args.input, None, &args.options, &args.source)?;
+    for line in stub.disassemble(&args.function)? {
+        let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        println!("{:8x}:  {:<12}{}", line.offset, bytes.join(" "), line.text);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use soulcomposer::{
+    encryption::{EncryptionError, KeyRing},
+    prog::{
+        arm::flash_stub_gen::ArmFlashStub,
+        export::{export_model, OutputFormat},
+    },
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct EncryptArgs {
+    /// FLM or stub written by `convert`.
+    pub input: PathBuf,
+
+    /// Key file, or a directory of `<key id>.key` files with 64 hex digits each. The key of
+    /// `--device` is used, else `fleet.key`.
+    #[arg(long = "keys")]
+    pub encryption_keys: Option<PathBuf>,
+
+    /// Where the encrypted stub is written as JSON, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct DecryptArgs {
+    /// Stub written by `encrypt`.
+    pub input: PathBuf,
+
+    /// Key file, or a directory of `<key id>.key` files, holding the key the stub names.
+    #[arg(long = "keys")]
+    pub encryption_keys: Option<PathBuf>,
+
+    /// Where the decrypted stub is written as JSON, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    pub output: PathBuf,
+}
+
+fn load_keys(path: Option<&Path>) -> Result<KeyRing, CliError> {
+    let path = path.ok_or(CliError::KeysRequired)?;
+    let keys = KeyRing::load(path)?;
+    if keys.is_empty() {
+        return Err(CliError::Encryption(EncryptionError::NoKey(path.display().to_string())));
+    }
+    Ok(keys)
+}
+
+fn write(stub: &ArmFlashStub, output: &Path) -> Result<(), CliError> {
+    input::write(output, &export_model(stub, OutputFormat::Json)?)
+}
+
+pub fn run_encrypt(args: EncryptArgs) -> Result<(), CliError> {
+    let keys = load_keys(args.encryption_keys.as_deref())?;
+    let key = keys.select(args.source.device.as_deref())?;
+    // Checked before sealing, the CRC can't be compared again until the stub is decrypted.
+    let stub = load_stub(&args.input, None, &args.options, &args.source)?.validate()?.into_inner();
+
+    let encrypted = stub.encrypt(key)?;
+    tracing::info!(key = %key.id, name = %stub.name, "encrypted stub");
+    write(&encrypted, &args.output)
+}
+
+pub fn run_decrypt(args: DecryptArgs) -> Result<(), CliError> {
+    let keys = load_keys(args.encryption_keys.as_deref())?;
+    let stub: ArmFlashStub = serde_json::from_slice(&input::read(&args.input)?)
+        .map_err(|err| CliError::StubParse { path: args.input.clone(), reason: err.to_string() })?;
+
+    let decrypted = stub.decrypt(&keys)?.validate()?.into_inner();
+    write(&decrypted, &args.output)
+}
@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use clap::{Args, ValueEnum};
+
+use soulcomposer::prog::xtensa::{
+    esptool::{EspChip, EsptoolStub},
+    flash_stub_gen::XtensaFlashStub,
+};
+
+use crate::{cli_error::CliError, input};
+
+/// Host tool `--run` starts the loader with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EspTool {
+    Esptool,
+    Espflash,
+}
+
+#[derive(Debug, Args)]
+pub struct EspArgs {
+    /// Loader ELF, Xtensa for the ESP32, S2 and S3 or RISC-V for the C and H series.
+    pub input: PathBuf,
+
+    /// Chip the loader is built for, e.g. esp32s3 or esp32c3.
+    #[arg(long)]
+    pub chip: EspChip,
+
+    /// Directory to write the stub JSON, espflash TOML and RAM image to, the input's by default.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Serial port of the chip, left to the tools to find when not given.
+    #[arg(long)]
+    pub port: Option<String>,
+
+    /// Load the loader into RAM and start it with --tool instead of printing the commands.
+    #[arg(long)]
+    pub run: bool,
+
+    #[arg(long, value_enum, default_value = "esptool")]
+    pub tool: EspTool,
+
+    /// The esptool executable.
+    #[arg(long, default_value = "esptool.py")]
+    pub esptool: String,
+
+    /// The espflash executable.
+    #[arg(long, default_value = "espflash")]
+    pub espflash: String,
+}
+
+/// A host tool invocation, printed so it can be pasted into a shell.
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Invocation {
+    fn new(program: &str) -> Self {
+        Invocation { program: program.to_string(), args: Vec::new() }
+    }
+
+    fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    fn target(self, chip: EspChip, port: Option<&str>) -> Self {
+        let invocation = self.arg("--chip").arg(chip.name());
+        match port {
+            Some(port) => invocation.arg("--port").arg(port),
+            None => invocation,
+        }
+    }
+
+    fn run(&self) -> Result<(), CliError> {
+        let failed = |reason: String| CliError::EspTool { tool: self.program.clone(), reason };
+        let status = Command::new(&self.program).args(&self.args).status().map_err(|err| failed(err.to_string()))?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(failed(status.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.program)?;
+        for arg in &self.args {
+            match arg.contains(char::is_whitespace) {
+                true => write!(f, " '{}'", arg)?,
+                false => write!(f, " {}", arg)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn run(args: EspArgs) -> Result<(), CliError> {
+    let elf = input::read(&args.input)?;
+    let stub = match args.chip.is_riscv() {
+        true => EsptoolStub::from_riscv_elf(&elf)?,
+        false => EsptoolStub::from(&XtensaFlashStub::from_elf(&elf, args.chip.name().to_string())?),
+    };
+
+    let stem = args.input.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| args.chip.name().to_string());
+    let directory = match &args.output {
+        Some(directory) => directory.clone(),
+        None => args.input.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let json = directory.join(format!("{}.json", stem));
+    let toml = directory.join(format!("{}.toml", stem));
+    let image = directory.join(format!("{}.bin", stem));
+    input::write(&json, stub.to_json()?.as_bytes())?;
+    input::write(&toml, stub.to_espflash_toml()?.as_bytes())?;
+    input::write(&image, &stub.to_ram_image(args.chip)?)?;
+    println!("Wrote {}, {} and {}", json.display(), toml.display(), image.display());
+
+    // esptool's own stub is skipped, the loader takes its place in RAM.
+    let port = args.port.as_deref();
+    let esptool = Invocation::new(&args.esptool).target(args.chip, port).arg("--no-stub").arg("load_ram").arg(image.display().to_string());
+    let espflash = Invocation::new(&args.espflash).arg("flash").target(args.chip, port).arg("--ram").arg(args.input.display().to_string());
+    if !args.run {
+        println!("{}", esptool);
+        println!("{}", espflash);
+        return Ok(());
+    }
+
+    let invocation = match args.tool {
+        EspTool::Esptool => esptool,
+        EspTool::Espflash => espflash,
+    };
+    println!("Running {}", invocation);
+    invocation.run()
+}
@@ -0,0 +1,170 @@
+//! Progress of the server's long operations, streamed to WebSocket clients of `GET /events`.
+//!
+//! Every event is a JSON text frame tagged with `event`:
+//!
+//! * `started`, with `operation` and `task`
+//! * `progress`, with `operation`, `completed`, `total` and `current`, before each item
+//! * `finished`, with `operation` and `completed`
+//! * `failed`, with `operation` and `error`
+//!
+//! The operation id is the `operation` query parameter of the request that started it, so a
+//! frontend can pick its own before sending the request, or a number the server counts up. It is
+//! also sent back in the `X-Operation-Id` header.
+
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use tiny_http::{Header, Request, Response};
+
+use soulcomposer::progress::{Progress, ProgressSink};
+
+/// Appended to the client's key in the opening handshake, from RFC 6455.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Idle time after which a ping checks the client is still there.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event<'a> {
+    Started { operation: &'a str, task: &'a str },
+    Progress { operation: &'a str, completed: usize, total: usize, current: &'a str },
+    Finished { operation: &'a str, completed: usize },
+    Failed { operation: &'a str, error: String },
+}
+
+/// Hands events to every connected client.
+#[derive(Debug, Default)]
+pub struct Events {
+    subscribers: Mutex<Vec<Sender<String>>>,
+    next_id: AtomicU64,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Events::default()
+    }
+
+    /// A new receiver of every event from now on.
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().expect("event subscribers poisoned").push(sender);
+        receiver
+    }
+
+    pub fn emit(&self, event: &Event<'_>) {
+        let text = match serde_json::to_string(event) {
+            Ok(text) => text,
+            Err(err) => return tracing::warn!("Dropping an event, {}", err),
+        };
+        // Clients that went away dropped their receiver.
+        self.subscribers.lock().expect("event subscribers poisoned").retain(|sender| sender.send(text.clone()).is_ok());
+    }
+
+    /// Id of an operation the client didn't name.
+    pub fn next_id(&self) -> String {
+        (self.next_id.fetch_add(1, Ordering::Relaxed) + 1).to_string()
+    }
+
+    /// Announces the operation `id`, running `task`.
+    pub fn start(&self, id: &str, task: &str) -> Operation<'_> {
+        self.emit(&Event::Started { operation: id, task });
+        Operation { events: self, id: id.to_string() }
+    }
+}
+
+/// An operation in progress, reporting each item to the clients.
+pub struct Operation<'a> {
+    events: &'a Events,
+    pub id: String,
+}
+
+impl Operation<'_> {
+    pub fn fail(&self, error: impl ToString) {
+        self.events.emit(&Event::Failed { operation: &self.id, error: error.to_string() });
+    }
+}
+
+impl ProgressSink for Operation<'_> {
+    fn progress(&mut self, progress: Progress<'_>) {
+        let (completed, total, current) = (progress.completed, progress.total, progress.current);
+        self.events.emit(&Event::Progress { operation: &self.id, completed, total, current });
+    }
+
+    fn finish(&mut self, completed: usize) {
+        self.events.emit(&Event::Finished { operation: &self.id, completed });
+    }
+}
+
+/// `Sec-WebSocket-Accept` answering the client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(HANDSHAKE_GUID.as_bytes());
+    base64::encode(sha1.finalize())
+}
+
+/// A frame from the server, which is never masked.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("header is valid")
+}
+
+/// `GET /events`, upgrades the request to a WebSocket and streams events to it on a thread of its
+/// own, so clients don't hold on to the workers. Anything the client sends is ignored.
+pub fn stream(request: Request, events: &Events) {
+    let value = |field: &'static str| request.headers().iter().find(|h| h.field.equiv(field)).map(|h| h.value.to_string());
+    let websocket = value("Upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let key = match value("Sec-WebSocket-Key") {
+        Some(key) if websocket => key,
+        _ => {
+            tracing::info!(status = 426, "responded");
+            let response = Response::from_string("GET /events is a WebSocket").with_status_code(426);
+            if let Err(err) = request.respond(response.with_header(header("Upgrade", "websocket"))) {
+                tracing::warn!("Failed to respond, {}", err);
+            }
+            return;
+        }
+    };
+
+    let receiver = events.subscribe();
+    let response = Response::empty(101).with_header(header("Sec-WebSocket-Accept", &accept_key(&key)));
+    let mut socket = request.upgrade("websocket", response);
+    thread::spawn(move || loop {
+        let data = match receiver.recv_timeout(PING_INTERVAL) {
+            Ok(text) => frame(0x1, text.as_bytes()),
+            Err(RecvTimeoutError::Timeout) => frame(0x9, &[]),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if socket.write_all(&data).and_then(|_| socket.flush()).is_err() {
+            break;
+        }
+    });
+    tracing::info!(status = 101, "streaming events");
+}
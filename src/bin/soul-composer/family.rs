@@ -0,0 +1,71 @@
+use std::{fs::File, path::PathBuf};
+
+use clap::Args;
+
+use soulcomposer::{
+    pack::archive::PackArchive,
+    prog::{
+        arm::validated::ValidatedArmFlashStub,
+        export::{export_probe_rs_family, probe_rs::ProbeRsChipFamily},
+    },
+    progress::NoProgress,
+};
+
+use crate::{cli_error::CliError, convert::StubOptions, input, pack::device_algorithms};
+
+#[derive(Debug, Args)]
+pub struct FamilyArgs {
+    /// CMSIS pack, e.g. Keil.STM32F4xx_DFP.2.15.0.pack.
+    pub pack: PathBuf,
+
+    /// Device family to describe, needed when the pack has more than one.
+    #[arg(long)]
+    pub family: Option<String>,
+
+    /// Target description to write, `<family>.yaml` by default.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+}
+
+pub fn run(args: FamilyArgs) -> Result<(), CliError> {
+    let file = File::open(&args.pack).map_err(CliError::io(&args.pack))?;
+    let pdsc = PackArchive::new(file)?.pdsc()?;
+
+    let mut families: Vec<&str> = pdsc.devices.iter().filter_map(|device| device.family.as_deref()).collect();
+    families.dedup();
+    let family = match (&args.family, families.as_slice()) {
+        (Some(family), _) => family.clone(),
+        (None, [family]) => family.to_string(),
+        (None, _) => return Err(CliError::FamilyRequired(families.join(", "))),
+    };
+
+    // Devices share FLMs, each is converted for the first device listing it.
+    let mut algorithms: Vec<(String, ValidatedArmFlashStub)> = Vec::new();
+    let devices = pdsc.devices.iter().filter(|device| device.family.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(&family)));
+    for device in devices {
+        for algorithm in device_algorithms(&args.pack, &device.name, &args.options, &mut NoProgress)? {
+            if algorithms.iter().any(|(file, _)| *file == algorithm.file) {
+                continue;
+            }
+            match algorithm.stub.and_then(|stub| Ok(stub.validate()?)) {
+                Ok(stub) => algorithms.push((algorithm.file, stub)),
+                Err(err) => tracing::warn!("Leaving out {}, {}", algorithm.file, err),
+            }
+        }
+    }
+
+    let description = ProbeRsChipFamily::from_pdsc(&pdsc, &family, &algorithms)?;
+    let output = args.output.unwrap_or_else(|| PathBuf::from(format!("{}.yaml", description.name.replace([' ', '/'], "_"))));
+    input::write(&output, &export_probe_rs_family(&description)?)?;
+    println!(
+        "Wrote {} with {} devices and {} algorithms",
+        output.display(),
+        description.variants.len(),
+        description.flash_algorithms.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+use probe_rs::{flashing::DownloadOptions, probe::DebugProbeSelector};
+
+use soulcomposer::{
+    probe::{attach, self_test, ProbeError, ProbeTarget},
+    prog::arm::core_isa::Core,
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, parse_number, parse_ram, SourceOptions, StubOptions},
+    validate::parse_core,
+};
+
+/// The target and probe, for the commands that run an algorithm on hardware.
+#[derive(Debug, Args)]
+pub struct TargetOptions {
+    /// RAM the algorithm runs from, as START:SIZE such as 0x20000000:64k.
+    #[arg(long, value_parser = parse_ram)]
+    pub chip_ram: (u32, u32),
+
+    /// Core of the target, e.g. M0+ or M4.
+    #[arg(long, default_value = "M4", value_parser = parse_core)]
+    pub core: Core,
+
+    /// Probe to use as VID:PID or VID:PID:SERIAL, the first one found by default.
+    #[arg(long)]
+    pub probe: Option<DebugProbeSelector>,
+}
+
+impl TargetOptions {
+    fn target(&self) -> ProbeTarget {
+        let (ram_start, ram_size) = self.chip_ram;
+        ProbeTarget { core: self.core, ram_start, ram_size }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct FlashArgs {
+    /// Raw binary image to program.
+    pub image: PathBuf,
+
+    /// Algorithm to program with, an FLM or a stub written by `convert`.
+    #[arg(long)]
+    pub algo: PathBuf,
+
+    /// Address the image is written to, the start of the algorithm's flash by default.
+    #[arg(long, value_parser = parse_number)]
+    pub address: Option<u32>,
+
+    /// Erase the whole flash instead of only the sectors the image covers.
+    #[arg(long)]
+    pub chip_erase: bool,
+
+    /// Read the image back after programming.
+    #[arg(long)]
+    pub verify: bool,
+
+    #[command(flatten)]
+    pub target: TargetOptions,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+fn probe_error(err: impl ToString) -> CliError {
+    ProbeError::Probe(err.to_string()).into()
+}
+
+pub fn run(args: FlashArgs) -> Result<(), CliError> {
+    let stub = load_stub(&args.algo, None, &args.options, &args.source)?.validate()?;
+    let image = fs::read(&args.image).map_err(CliError::io(&args.image))?;
+
+    let mut session = attach(&stub, &args.target.target(), args.target.probe.clone())?;
+    let mut loader = session.target().flash_loader();
+    let address = args.address.unwrap_or(stub.flash_start_addr);
+    loader.add_data(address.into(), &image).map_err(probe_error)?;
+
+    let mut options = DownloadOptions::new();
+    options.do_chip_erase = args.chip_erase;
+    options.verify = args.verify;
+    loader.commit(&mut session, options).map_err(probe_error)?;
+
+    println!("Programmed {} bytes at {:#010x} with {}", image.len(), address, stub.name);
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub struct SelfTestArgs {
+    /// Algorithm to test, an FLM or a stub written by `convert`.
+    pub algo: PathBuf,
+
+    /// An address in the sector to overwrite, the last sector of the flash by default.
+    #[arg(long, value_parser = parse_number)]
+    pub scratch: Option<u32>,
+
+    #[command(flatten)]
+    pub target: TargetOptions,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run_self_test(args: SelfTestArgs) -> Result<(), CliError> {
+    let stub = load_stub(&args.algo, None, &args.options, &args.source)?.validate()?;
+    let mut session = attach(&stub, &args.target.target(), args.target.probe.clone())?;
+    let report = self_test(&mut session, &stub, args.scratch)?;
+
+    println!(
+        "{} passed on {:#010x}..{:#010x}: erased {} bytes in {:?}, programmed {} bytes in {:?}, {:?} in total",
+        stub.name,
+        report.sector.start,
+        report.sector.end,
+        report.erased,
+        report.erase_time,
+        report.programmed,
+        report.program_time,
+        report.total_time
+    );
+    Ok(())
+}
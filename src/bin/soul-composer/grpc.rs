@@ -0,0 +1,97 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use tonic::{transport::server::TcpIncoming, Request, Response, Status};
+
+use soulcomposer::grpc::proto::{
+    soul_composer_server::{SoulComposer, SoulComposerServer},
+    Conversion, ConvertRequest, DeviceStubs, GetDeviceRequest, ListDevicesReply, ListDevicesRequest, ListPacksReply,
+    ListPacksRequest, Pack, UploadPackRequest,
+};
+
+use crate::{
+    cli_error::CliError,
+    serve::{cached_packs, convert_upload, store_pack, CachedPack, ConversionRequest, State},
+};
+
+/// The REST routes of `serve` as a gRPC service, sharing their registry and pack cache.
+struct Service {
+    state: Arc<State>,
+}
+
+fn status(err: CliError) -> Status {
+    match err {
+        CliError::Io { .. } => Status::internal(err.to_string()),
+        _ => Status::invalid_argument(err.to_string()),
+    }
+}
+
+impl From<CachedPack> for Pack {
+    fn from(pack: CachedPack) -> Self {
+        Pack { id: pack.id, vendor: pack.vendor, name: pack.name, version: pack.version, devices: pack.devices }
+    }
+}
+
+#[tonic::async_trait]
+impl SoulComposer for Service {
+    async fn convert(&self, request: Request<ConvertRequest>) -> Result<Response<Conversion>, Status> {
+        let request = request.into_inner();
+        let conversion = ConversionRequest {
+            name: Some(request.name.as_str()).filter(|name| !name.is_empty()),
+            device: request.device.as_deref(),
+            default: request.default,
+            operation: None,
+        };
+        let conversion = convert_upload(&request.data, &conversion, &self.state).map_err(status)?;
+
+        Ok(Response::new(Conversion {
+            stubs: conversion.stubs.iter().map(Into::into).collect(),
+            diagnostics: conversion
+                .diagnostics
+                .into_iter()
+                .map(|finding| soulcomposer::grpc::proto::Diagnostic::new(&finding.diagnostic, finding.file))
+                .collect(),
+        }))
+    }
+
+    async fn get_device(&self, request: Request<GetDeviceRequest>) -> Result<Response<DeviceStubs>, Status> {
+        let device = request.into_inner().device;
+        let stubs = self.state.registry.get(&device);
+        self.state.metrics.cache("devices", stubs.is_some());
+        let stubs = stubs.ok_or_else(|| Status::not_found(format!("no stubs for {}", device)))?;
+        Ok(Response::new(DeviceStubs { device, stubs: stubs.iter().map(Into::into).collect() }))
+    }
+
+    async fn list_devices(&self, _request: Request<ListDevicesRequest>) -> Result<Response<ListDevicesReply>, Status> {
+        Ok(Response::new(ListDevicesReply { devices: self.state.registry.devices() }))
+    }
+
+    async fn upload_pack(&self, request: Request<UploadPackRequest>) -> Result<Response<Pack>, Status> {
+        let (id, pdsc) = store_pack(&request.into_inner().data, &self.state).map_err(status)?;
+        Ok(Response::new(CachedPack::new(id, pdsc).into()))
+    }
+
+    async fn list_packs(&self, _request: Request<ListPacksRequest>) -> Result<Response<ListPacksReply>, Status> {
+        let packs = cached_packs(&self.state.cache).map_err(status)?;
+        Ok(Response::new(ListPacksReply { packs: packs.into_iter().map(Into::into).collect() }))
+    }
+}
+
+/// Binds `address` and answers gRPC on a thread of its own, returning the bound address.
+pub fn spawn(address: &str, state: Arc<State>) -> Result<SocketAddr, CliError> {
+    let serve_error = |err: &dyn std::fmt::Display| CliError::Serve { address: address.to_string(), reason: err.to_string() };
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().map_err(|err| serve_error(&err))?;
+    let listener = runtime.block_on(tokio::net::TcpListener::bind(address)).map_err(|err| serve_error(&err))?;
+    let bound = listener.local_addr().map_err(|err| serve_error(&err))?;
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|err| serve_error(&err))?;
+
+    std::thread::spawn(move || {
+        let server = tonic::transport::Server::builder()
+            .add_service(SoulComposerServer::new(Service { state }))
+            .serve_with_incoming(incoming);
+        if let Err(err) = runtime.block_on(server) {
+            tracing::error!("gRPC server stopped, {}", err);
+        }
+    });
+
+    Ok(bound)
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::{
+    database::{StubDatabase, StubQuery},
+    hil::{verify_stubs, HilBackend},
+    progress::Progress,
+};
+
+use crate::{cli_error::CliError, convert::parse_number};
+
+#[derive(Debug, Args)]
+pub struct HilArgs {
+    /// SQLite database written by `pack --database`.
+    pub database: PathBuf,
+
+    /// Only stubs of this device, matched ignoring case.
+    #[arg(long)]
+    pub device: Option<String>,
+
+    /// An address in the sector to overwrite, the last sector of the flash by default.
+    #[arg(long, value_parser = parse_number)]
+    pub scratch: Option<u32>,
+
+    /// Serial port of a Soul Injector programmer to run the stubs on, e.g. /dev/ttyACM0.
+    #[cfg(feature = "upload")]
+    #[arg(long, conflicts_with = "chip_ram")]
+    pub port: Option<String>,
+
+    #[cfg(feature = "upload")]
+    #[arg(long, default_value_t = soulcomposer::injector::DEFAULT_BAUD_RATE)]
+    pub baud: u32,
+
+    /// Times a frame is sent before giving up.
+    #[cfg(feature = "upload")]
+    #[arg(long, default_value_t = 3)]
+    pub attempts: u32,
+
+    /// RAM of the target, as START:SIZE such as 0x20000000:64k, to run the stubs through a debug
+    /// probe.
+    #[cfg(any(feature = "flash", feature = "dap"))]
+    #[arg(long, value_parser = crate::convert::parse_ram)]
+    pub chip_ram: Option<(u32, u32)>,
+
+    /// Drive a CMSIS-DAP probe with the built-in driver instead of probe-rs.
+    #[cfg(feature = "dap")]
+    #[arg(long, requires = "chip_ram")]
+    pub dap: bool,
+
+    /// Serial number of the CMSIS-DAP probe, the first one found by default.
+    #[cfg(feature = "dap")]
+    #[arg(long, requires = "dap")]
+    pub dap_serial: Option<String>,
+
+    /// SWD clock of the CMSIS-DAP probe in Hz.
+    #[cfg(feature = "dap")]
+    #[arg(long, default_value_t = soulcomposer::dap::DEFAULT_CLOCK)]
+    pub dap_clock: u32,
+
+    /// Core of the target, e.g. M0+ or M4.
+    #[cfg(feature = "flash")]
+    #[arg(long, default_value = "M4", value_parser = crate::validate::parse_core)]
+    pub core: soulcomposer::prog::arm::core_isa::Core,
+
+    /// Probe to use as VID:PID or VID:PID:SERIAL, the first one found by default.
+    #[cfg(feature = "flash")]
+    #[arg(long)]
+    pub probe: Option<probe_rs::probe::DebugProbeSelector>,
+}
+
+pub fn run(args: HilArgs) -> Result<(), CliError> {
+    let database = StubDatabase::open(&args.database)?;
+    let mut query = StubQuery::new();
+    query.device = args.device.clone();
+    let stubs = database.query(&query)?;
+
+    #[cfg(feature = "upload")]
+    if let Some(port) = &args.port {
+        use soulcomposer::{
+            hil::InjectorBackend,
+            injector::{open_serial, Uploader},
+        };
+
+        let uploader = Uploader::connect(open_serial(port, args.baud)?)?.with_attempts(args.attempts);
+        return verify(&database, &mut InjectorBackend { uploader }, &stubs, args.scratch);
+    }
+
+    #[cfg(feature = "dap")]
+    if let (true, Some((ram_start, ram_size))) = (args.dap, args.chip_ram) {
+        use soulcomposer::{
+            dap::{CmsisDap, DapTarget, UsbDap},
+            hil::{DapBackend, HilError},
+        };
+
+        let connect = || -> Result<_, HilError> {
+            let mut dap = CmsisDap::new(UsbDap::open(args.dap_serial.as_deref())?)?;
+            // Stubs pinned to another core name its access port.
+            let ap = stubs.iter().find_map(|stored| stored.stub.pinned_core.as_ref().and_then(|pinned| pinned.ap)).unwrap_or(0);
+            dap.connect(args.dap_clock, ap as u8)?;
+            Ok(dap)
+        };
+        let mut backend = DapBackend { dap: connect()?, target: DapTarget { ram_start, ram_size } };
+        return verify(&database, &mut backend, &stubs, args.scratch);
+    }
+
+    #[cfg(feature = "flash")]
+    if let Some((ram_start, ram_size)) = args.chip_ram {
+        use soulcomposer::{hil::ProbeRsBackend, probe::ProbeTarget};
+
+        let mut backend = ProbeRsBackend { target: ProbeTarget { core: args.core, ram_start, ram_size }, selector: args.probe };
+        return verify(&database, &mut backend, &stubs, args.scratch);
+    }
+
+    Err(CliError::HilTargetRequired)
+}
+
+fn verify(
+    database: &StubDatabase,
+    backend: &mut impl HilBackend,
+    stubs: &[soulcomposer::database::StoredStub],
+    scratch: Option<u32>,
+) -> Result<(), CliError> {
+    let mut progress = |progress: Progress<'_>| {
+        tracing::info!(name = progress.current, done = progress.completed, total = progress.total, "testing");
+    };
+    let runs = verify_stubs(database, backend, stubs, scratch, &mut progress)?;
+
+    for (stored, run) in stubs.iter().zip(&runs) {
+        let sector = match &run.sector {
+            Some(sector) => format!("{:#010x}..{:#010x}", sector.start, sector.end),
+            None => String::new(),
+        };
+        match run.passed {
+            true => println!("passed  {:>4}  {:<24}  {}  {} ms", stored.id, stored.stub.name, sector, run.duration_ms),
+            false => println!(
+                "FAILED  {:>4}  {:<24}  {}  {}",
+                stored.id,
+                stored.stub.name,
+                sector,
+                run.detail.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+
+    let failed = runs.iter().filter(|run| !run.passed).count();
+    println!("{} of {} stubs passed on {}", runs.len() - failed, runs.len(), backend.name());
+    match failed {
+        0 => Ok(()),
+        failed => Err(CliError::HilFailed(failed)),
+    }
+}
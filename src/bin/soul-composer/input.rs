@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::cli_error::CliError;
+
+/// How an algorithm file is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// An FLM or any other ELF with symbols.
+    Elf,
+    /// Intel HEX dump of the algorithm image.
+    Hex,
+    /// Raw binary dump of the algorithm image.
+    Bin,
+}
+
+impl InputFormat {
+    /// Tells the format apart by content, falling back to the extension for raw binaries.
+    pub fn detect(path: &Path, data: &[u8]) -> InputFormat {
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        if data.starts_with(b"\x7fELF") {
+            InputFormat::Elf
+        } else if data.first() == Some(&b':') || matches!(extension.as_deref(), Some("hex") | Some("ihex")) {
+            InputFormat::Hex
+        } else {
+            InputFormat::Bin
+        }
+    }
+}
+
+/// Whether `path` is `-`, which stands for stdin or stdout.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Reads a file, or stdin for `-`.
+pub fn read(path: &Path) -> Result<Vec<u8>, CliError> {
+    if !is_stdio(path) {
+        return fs::read(path).map_err(CliError::io(path));
+    }
+
+    let mut data = Vec::new();
+    io::stdin().lock().read_to_end(&mut data).map_err(CliError::io("stdin"))?;
+    Ok(data)
+}
+
+/// Writes a file, or stdout for `-`.
+pub fn write(path: &Path, data: &[u8]) -> Result<(), CliError> {
+    if !is_stdio(path) {
+        return fs::write(path, data).map_err(CliError::io(path));
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(data).and_then(|()| stdout.flush()).map_err(CliError::io("stdout"))
+}
+
+/// Reads an image for the raw formats, HEX images start at their lowest address.
+pub fn load_image(format: InputFormat, data: &[u8]) -> Result<Vec<u8>, CliError> {
+    match format {
+        InputFormat::Hex => parse_hex(&String::from_utf8_lossy(data)),
+        _ => Ok(data.to_vec()),
+    }
+}
+
+/// Parses Intel HEX data, gaps between records are filled with zeroes.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, CliError> {
+    let mut chunks = BTreeMap::new();
+    let mut base = 0u32;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = |reason: &str| CliError::HexParse { line: index + 1, reason: reason.to_string() };
+        let digits = line.strip_prefix(':').ok_or_else(|| invalid("missing start code"))?;
+        if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid("not a hex digit"));
+        }
+        if digits.len() % 2 != 0 {
+            return Err(invalid("odd number of digits"));
+        }
+        let bytes = (0..digits.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&digits[at..at + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid("not a hex digit"))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid("length does not match the byte count"));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let payload = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => {
+                chunks.insert(base + offset, payload.to_vec());
+            }
+            0x01 => break,
+            0x02 if payload.len() == 2 => base = u32::from(u16::from_be_bytes([payload[0], payload[1]])) << 4,
+            0x04 if payload.len() == 2 => base = u32::from(u16::from_be_bytes([payload[0], payload[1]])) << 16,
+            0x03 | 0x05 => {}
+            _ => return Err(invalid("unsupported record type")),
+        }
+    }
+
+    let start = match chunks.keys().next() {
+        Some(&start) => start,
+        None => return Ok(Vec::new()),
+    };
+    let mut image = Vec::new();
+    for (address, data) in chunks {
+        let offset = (address - start) as usize;
+        if image.len() < offset + data.len() {
+            image.resize(offset + data.len(), 0);
+        }
+        image[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    Ok(image)
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::{
+    prog::arm::{
+        debug_dump::{DebugDump, RawDescriptor},
+        flash_stub_gen::ArmFlashStub,
+    },
+    ArmError, Elf, ParseOptions,
+};
+
+use crate::{cli_error::CliError, convert::file_stem, input};
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    /// FLM file to inspect, `-` reads stdin.
+    pub input: PathBuf,
+
+    /// Print the FlashDevice struct and sector table as an annotated hex dump first.
+    #[arg(long)]
+    pub hex_dump: bool,
+}
+
+pub fn run(args: InspectArgs) -> Result<(), CliError> {
+    let data = input::read(&args.input)?;
+    if args.hex_dump {
+        // Before parsing, so the bytes are shown even when the descriptor gets refused.
+        let elf = Elf::parse(&data).map_err(ArmError::from)?;
+        println!("{}", RawDescriptor::from_elf(&elf, &data, &ParseOptions::default())?.debug_dump());
+    }
+    let stub = ArmFlashStub::from_elf(&data, file_stem(&args.input), false, 0)?;
+    print!("{}", stub);
+
+    Ok(())
+}
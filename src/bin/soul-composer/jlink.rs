@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use soulcomposer::prog::export::jlink::{jlink_script, JLinkOptions, DEFAULT_SPEED_KHZ};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, parse_number, parse_ram, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct JLinkArgs {
+    /// Algorithm to exercise, an FLM or a stub written by `convert`.
+    pub algo: PathBuf,
+
+    /// RAM of the target the algorithm runs from, as START:SIZE, e.g. 0x20000000:64k.
+    #[arg(long, value_parser = parse_ram)]
+    pub chip_ram: (u32, u32),
+
+    /// J-Link's name of the device, e.g. STM32F407VG, `--device` by default. J-Link asks for it
+    /// when neither is given.
+    #[arg(long)]
+    pub jlink_device: Option<String>,
+
+    /// SWD clock in kHz.
+    #[arg(long, default_value_t = DEFAULT_SPEED_KHZ)]
+    pub speed: u32,
+
+    /// An address in the sector to overwrite, the last sector of the flash by default.
+    #[arg(long, value_parser = parse_number)]
+    pub scratch: Option<u32>,
+
+    /// Directory to write the script, image and test page to, the algorithm's by default. The
+    /// script finds the other two by their paths from where this runs.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: JLinkArgs) -> Result<(), CliError> {
+    let stub = load_stub(&args.algo, None, &args.options, &args.source)?.validate()?;
+
+    let stem = args.algo.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| stub.name.clone());
+    let directory = match &args.output {
+        Some(directory) => directory.clone(),
+        None => args.algo.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let (ram_start, ram_size) = args.chip_ram;
+    let options = JLinkOptions {
+        ram_start,
+        ram_size,
+        device: args.jlink_device.or(args.source.device),
+        speed_khz: args.speed,
+        scratch: args.scratch,
+        image_file: directory.join(format!("{}.bin", stem)).display().to_string(),
+        page_file: directory.join(format!("{}.page.bin", stem)).display().to_string(),
+    };
+    let jlink = jlink_script(&stub, &options)?;
+
+    let script = directory.join(format!("{}.jlink", stem));
+    input::write(&script, jlink.script.as_bytes())?;
+    input::write(Path::new(&options.image_file), &jlink.image)?;
+    input::write(Path::new(&options.page_file), &jlink.page)?;
+    println!(
+        "Wrote {}, {} and {}, overwriting {:#010x}..{:#010x} when run",
+        script.display(),
+        options.image_file,
+        options.page_file,
+        jlink.sector.start,
+        jlink.sector.end
+    );
+    println!("JLinkExe -CommandFile {}", script.display());
+    Ok(())
+}
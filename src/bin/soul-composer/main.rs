@@ -0,0 +1,232 @@
+#[cfg(feature = "announce")]
+mod announce;
+mod batch;
+mod cli_error;
+mod config;
+mod convert;
+#[cfg(feature = "database")]
+mod db;
+mod diff;
+mod disasm;
+#[cfg(feature = "encryption")]
+mod encrypt;
+mod esp;
+#[cfg(feature = "serve")]
+mod events;
+mod family;
+#[cfg(feature = "flash")]
+mod flash;
+#[cfg(all(feature = "serve", feature = "grpc"))]
+mod grpc;
+#[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+mod hil;
+mod input;
+mod inspect;
+mod jlink;
+mod merge;
+mod msc;
+#[cfg(feature = "ota")]
+mod ota;
+#[cfg(feature = "serve")]
+mod metrics;
+mod pack;
+mod progress_line;
+#[cfg(feature = "publish")]
+mod publish;
+mod prompt;
+mod search;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "signing")]
+mod sign;
+#[cfg(feature = "emulator")]
+mod simulate;
+#[cfg(feature = "sync")]
+mod sync;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "upload")]
+mod upload;
+mod validate;
+mod watch;
+mod webhook;
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use cli_error::CliError;
+use config::Config;
+
+/// Turns flash algorithms into stubs for the Soul Injector programmer.
+#[derive(Debug, Parser)]
+#[command(name = "soul-composer", version, about)]
+struct Cli {
+    /// Log more, repeat for debug and trace output.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Announce stubs and manifests to Soul Injector programmers through an MQTT broker.
+    #[cfg(feature = "announce")]
+    Announce(announce::AnnounceArgs),
+    /// Convert an FLM, HEX or bin flash algorithm to a stub.
+    Convert(convert::ConvertArgs),
+    /// Convert every algorithm matching a glob pattern, carrying on past failures.
+    Batch(batch::BatchArgs),
+    /// Query the stub database written by `pack --database`.
+    #[cfg(feature = "database")]
+    Db(db::DbArgs),
+    /// Decrypt a stub written by `encrypt`, checking it against its CRC.
+    #[cfg(feature = "encryption")]
+    Decrypt(encrypt::DecryptArgs),
+    /// Compare two algorithms field by field.
+    Diff(diff::DiffArgs),
+    /// Disassemble an entry point of an algorithm.
+    Disasm(disasm::DisasmArgs),
+    /// Encrypt the code of an algorithm with AES-256-GCM under a device or fleet key.
+    #[cfg(feature = "encryption")]
+    Encrypt(encrypt::EncryptArgs),
+    /// Write the esptool and espflash files of an ESP loader, and the commands to run it.
+    Esp(esp::EspArgs),
+    /// Generate a probe-rs target description of a device family from a CMSIS pack.
+    Family(family::FamilyArgs),
+    /// Program an image onto a connected target with a converted algorithm, through probe-rs.
+    #[cfg(feature = "flash")]
+    Flash(flash::FlashArgs),
+    /// Run the stubs of a database on a connected target and record which passed.
+    #[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+    Hil(hil::HilArgs),
+    /// Print the FlashDevice and entry points of an FLM.
+    Inspect(inspect::InspectArgs),
+    /// Write a J-Link Commander script that erases and programs a scratch sector with an algorithm.
+    Jlink(jlink::JLinkArgs),
+    /// Generate an Ed25519 key pair for `sign` and `verify`.
+    #[cfg(feature = "signing")]
+    Keygen(sign::KeygenArgs),
+    /// Group algorithms for several banks or regions of one device into a manifest.
+    Merge(merge::MergeArgs),
+    /// Write the directory layout a programmer installs from its USB drive, or check a copy of it.
+    Msc(msc::MscArgs),
+    /// Write the signed OTA manifest of a stub library release, what deployed programmers poll.
+    #[cfg(feature = "ota")]
+    Ota(ota::OtaArgs),
+    /// Convert the algorithms a CMSIS pack lists for a device.
+    Pack(pack::PackArgs),
+    /// Publish stubs, manifests and signed bundles to S3-compatible object storage.
+    #[cfg(feature = "publish")]
+    Publish(publish::PublishArgs),
+    /// Find devices in the cached pack index.
+    Search(search::SearchArgs),
+    /// Erase and program a scratch sector of a connected target with a converted algorithm.
+    #[cfg(feature = "flash")]
+    SelfTest(flash::SelfTestArgs),
+    /// Convert uploaded FLMs and packs over HTTP.
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+    /// Wrap a stub, group or manifest in a bundle signed with Ed25519.
+    #[cfg(feature = "signing")]
+    Sign(sign::SignArgs),
+    /// Run an algorithm's Init, erase and program routines in an emulated Cortex-M.
+    #[cfg(feature = "emulator")]
+    Simulate(simulate::SimulateArgs),
+    /// Pull or push the stub library kept in a git repository.
+    #[cfg(feature = "sync")]
+    Sync(sync::SyncArgs),
+    /// Browse algorithms interactively, with a map of their sectors.
+    #[cfg(feature = "tui")]
+    Tui(tui::TuiArgs),
+    /// Push stubs and manifests onto a Soul Injector programmer over its serial port.
+    #[cfg(feature = "upload")]
+    Upload(upload::UploadArgs),
+    /// Check algorithms and stubs, failing on errors.
+    Validate(validate::ValidateArgs),
+    /// Check a signed bundle against trusted public keys.
+    #[cfg(feature = "signing")]
+    Verify(sign::VerifyArgs),
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    match cli.command {
+        #[cfg(feature = "announce")]
+        Command::Announce(args) => announce::run(args),
+        Command::Convert(args) => convert::run(args),
+        Command::Batch(args) => batch::run(args),
+        #[cfg(feature = "database")]
+        Command::Db(args) => db::run(args),
+        #[cfg(feature = "encryption")]
+        Command::Decrypt(args) => encrypt::run_decrypt(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Disasm(args) => disasm::run(args),
+        #[cfg(feature = "encryption")]
+        Command::Encrypt(args) => encrypt::run_encrypt(args),
+        Command::Esp(args) => esp::run(args),
+        Command::Family(args) => family::run(args),
+        #[cfg(feature = "flash")]
+        Command::Flash(args) => flash::run(args),
+        #[cfg(all(feature = "hil", any(feature = "flash", feature = "upload", feature = "dap")))]
+        Command::Hil(args) => hil::run(args),
+        Command::Inspect(args) => inspect::run(args),
+        Command::Jlink(args) => jlink::run(args),
+        #[cfg(feature = "signing")]
+        Command::Keygen(args) => sign::run_keygen(args),
+        Command::Merge(args) => merge::run(args),
+        Command::Msc(args) => msc::run(args),
+        #[cfg(feature = "ota")]
+        Command::Ota(args) => ota::run(args),
+        Command::Pack(args) => pack::run(args),
+        #[cfg(feature = "publish")]
+        Command::Publish(args) => publish::run(args),
+        Command::Search(args) => search::run(args),
+        #[cfg(feature = "flash")]
+        Command::SelfTest(args) => flash::run_self_test(args),
+        #[cfg(feature = "serve")]
+        Command::Serve(args) => serve::run(args),
+        #[cfg(feature = "signing")]
+        Command::Sign(args) => sign::run_sign(args),
+        #[cfg(feature = "emulator")]
+        Command::Simulate(args) => simulate::run(args),
+        #[cfg(feature = "sync")]
+        Command::Sync(args) => sync::run(args),
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => tui::run(args),
+        #[cfg(feature = "upload")]
+        Command::Upload(args) => upload::run(args),
+        Command::Validate(args) => validate::run(args),
+        #[cfg(feature = "signing")]
+        Command::Verify(args) => sign::run_verify(args),
+    }
+}
+
+fn main() {
+    let config = Config::discover().unwrap_or_else(|err| {
+        match err.code() {
+            Some(code) => eprintln!("error[{}]: {}", code, err),
+            None => eprintln!("error: {}", err),
+        }
+        std::process::exit(1);
+    });
+    let mut command = Cli::command();
+    if let Some(config) = &config {
+        command = config.apply(command);
+    }
+    let cli = Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit());
+    let level = match cli.verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    tracing_subscriber::fmt().with_max_level(level).without_time().with_writer(std::io::stderr).init();
+    if let Some(config) = &config {
+        tracing::info!("Using {}", config.path.display());
+    }
+
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::prog::arm::stub_group::ArmFlashStubGroup;
+
+use crate::{
+    cli_error::CliError,
+    convert::{file_stem, load_stub, parse_number, write_group, OutputOptions, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// Stubs or algorithm files, one per bank or region, e.g. both banks of a dual-bank device or
+    /// flash plus EEPROM.
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Where to write the grouped manifest, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    pub output: PathBuf,
+
+    /// Name of the group, the output file name by default.
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Split a single algorithm covering both banks at the start of the second one.
+    #[arg(long, value_parser = parse_number)]
+    pub split_at: Option<u32>,
+
+    #[command(flatten)]
+    pub output_options: OutputOptions,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: MergeArgs) -> Result<(), CliError> {
+    let stubs = args
+        .inputs
+        .iter()
+        .map(|path| load_stub(path, None, &args.options, &args.source))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None if input::is_stdio(&args.output) => stubs[0].name.clone(),
+        None => file_stem(&args.output),
+    };
+
+    let group = match args.split_at {
+        Some(address) => {
+            let mut stubs = stubs.into_iter();
+            match (stubs.next(), stubs.next()) {
+                (Some(stub), None) => ArmFlashStubGroup::split_banks(name, stub, address)?,
+                _ => return Err(CliError::SplitNeedsOneInput(args.inputs.len())),
+            }
+        }
+        None => ArmFlashStubGroup::merge(name, stubs)?,
+    };
+
+    write_group(&group, &args.output, &args.output_options)?;
+    for bank in &group.banks {
+        eprintln!(
+            "bank {}  {:#010x}..{:#010x}  {}",
+            bank.bank, bank.start, bank.end, group.algorithms[bank.algorithm].name
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! What `GET /metrics` reports, in the Prometheus text format:
+//!
+//! * `soul_composer_conversions_total`, algorithms converted by `outcome`, `completed` or `failed`
+//! * `soul_composer_conversion_failures_total`, failed algorithms by the `code` `validate` reports,
+//!   the failure rate of a code is its rate over the rate of all conversions
+//! * `soul_composer_cache_requests_total`, lookups of the pack cache and the converted devices by
+//!   `result`, `hit` or `miss`, and `soul_composer_cache_hit_ratio` of them so far
+//! * `soul_composer_requests_total` by `route` and `status`, and the
+//!   `soul_composer_request_duration_seconds` histogram by `route`
+//!
+//! Routes are the patterns, `/packs/{id}` rather than each id, so the series stay few.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// Upper bounds of the latency buckets in seconds. Converting a whole pack takes seconds.
+const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Code of failures that didn't come from composing, see `CliError::code`.
+const UNKNOWN_CODE: &str = "unknown";
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations at or below each bound, `count` is the `+Inf` bucket.
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    completed: u64,
+    failures: BTreeMap<String, u64>,
+    /// Lookups by cache and whether they hit.
+    cache: BTreeMap<(&'static str, bool), u64>,
+    requests: BTreeMap<(&'static str, u16), u64>,
+    latencies: BTreeMap<&'static str, Histogram>,
+}
+
+/// Counters of the server, shared by the workers.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    fn counters(&self) -> MutexGuard<'_, Counters> {
+        self.counters.lock().expect("metrics poisoned")
+    }
+
+    pub fn converted(&self, count: usize) {
+        self.counters().completed += count as u64;
+    }
+
+    /// An algorithm that failed with the stable error `code`, if it has one.
+    pub fn failed(&self, code: Option<&str>) {
+        *self.counters().failures.entry(code.unwrap_or(UNKNOWN_CODE).to_string()).or_default() += 1;
+    }
+
+    pub fn cache(&self, cache: &'static str, hit: bool) {
+        *self.counters().cache.entry((cache, hit)).or_default() += 1;
+    }
+
+    pub fn request(&self, route: &'static str, status: u16, elapsed: Duration) {
+        let mut counters = self.counters();
+        *counters.requests.entry((route, status)).or_default() += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        let histogram = counters.latencies.entry(route).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Every metric in the text exposition format.
+    pub fn render(&self) -> String {
+        let counters = self.counters();
+        let mut text = String::new();
+
+        family(&mut text, "soul_composer_conversions_total", "counter", "Algorithms converted, by outcome.");
+        let failed: u64 = counters.failures.values().sum();
+        for (outcome, count) in [("completed", counters.completed), ("failed", failed)] {
+            let _ = writeln!(text, "soul_composer_conversions_total{{outcome=\"{}\"}} {}", outcome, count);
+        }
+
+        family(&mut text, "soul_composer_conversion_failures_total", "counter", "Algorithms that failed to convert, by error code.");
+        for (code, count) in &counters.failures {
+            let _ = writeln!(text, "soul_composer_conversion_failures_total{{code=\"{}\"}} {}", escape(code), count);
+        }
+
+        family(&mut text, "soul_composer_cache_requests_total", "counter", "Lookups of cached packs and devices, by result.");
+        for ((cache, hit), count) in &counters.cache {
+            let result = if *hit { "hit" } else { "miss" };
+            let _ = writeln!(text, "soul_composer_cache_requests_total{{cache=\"{}\",result=\"{}\"}} {}", cache, result, count);
+        }
+
+        family(&mut text, "soul_composer_cache_hit_ratio", "gauge", "Share of the lookups of each cache that hit.");
+        let mut caches: Vec<&str> = counters.cache.keys().map(|(cache, _)| *cache).collect();
+        caches.dedup();
+        for cache in caches {
+            let count = |hit: bool| counters.cache.get(&(cache, hit)).copied().unwrap_or(0) as f64;
+            let ratio = count(true) / (count(true) + count(false));
+            let _ = writeln!(text, "soul_composer_cache_hit_ratio{{cache=\"{}\"}} {}", cache, ratio);
+        }
+
+        family(&mut text, "soul_composer_requests_total", "counter", "HTTP requests answered, by route and status.");
+        for ((route, status), count) in &counters.requests {
+            let _ = writeln!(text, "soul_composer_requests_total{{route=\"{}\",status=\"{}\"}} {}", route, status, count);
+        }
+
+        let name = "soul_composer_request_duration_seconds";
+        family(&mut text, name, "histogram", "Time taken to answer HTTP requests, by route.");
+        for (route, histogram) in &counters.latencies {
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(text, "{}_bucket{{route=\"{}\",le=\"{}\"}} {}", name, route, bound, count);
+            }
+            let _ = writeln!(text, "{}_bucket{{route=\"{}\",le=\"+Inf\"}} {}", name, route, histogram.count);
+            let _ = writeln!(text, "{}_sum{{route=\"{}\"}} {}", name, route, histogram.sum);
+            let _ = writeln!(text, "{}_count{{route=\"{}\"}} {}", name, route, histogram.count);
+        }
+
+        text
+    }
+}
+
+fn family(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::{msc::MscLayout, prog::arm::stub_group::ArmFlashStubGroup};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct MscArgs {
+    /// Algorithms to copy onto the programmer, FLMs or stubs written by `convert`.
+    #[arg(required_unless_present_any = ["manifest", "check"])]
+    pub inputs: Vec<PathBuf>,
+
+    /// Manifests written by `merge` to copy after the algorithms.
+    #[arg(long)]
+    pub manifest: Vec<PathBuf>,
+
+    /// Directory to write the layout to, the mounted programmer or one to copy onto it.
+    #[arg(short, long, required_unless_present = "check")]
+    pub output: Option<PathBuf>,
+
+    /// Check the layout in this directory against its manifest instead, e.g. after copying it.
+    #[arg(long, conflicts_with_all = ["inputs", "manifest", "output"])]
+    pub check: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: MscArgs) -> Result<(), CliError> {
+    if let Some(directory) = &args.check {
+        let bad = soulcomposer::msc::check(directory)?;
+        for entry in &bad {
+            println!("{} {} is missing or differs", entry.path, entry.name);
+        }
+        return match bad.len() {
+            0 => Ok(()),
+            count => Err(CliError::MscCheckFailed(count)),
+        };
+    }
+
+    let mut layout = MscLayout::new();
+    for path in &args.inputs {
+        let stub = load_stub(path, None, &args.options, &args.source)?.validate()?;
+        layout.add_stub(&stub)?;
+    }
+    for path in &args.manifest {
+        let group: ArmFlashStubGroup = serde_json::from_slice(&input::read(path)?)
+            .map_err(|err| CliError::StubParse { path: path.clone(), reason: err.to_string() })?;
+        layout.add_group(&group)?;
+    }
+
+    let output = args.output.unwrap_or_default();
+    let written = layout.write_to(&output)?;
+    for entry in layout.manifest().files {
+        println!("{} {}, {} bytes, CRC32 {:08x}", entry.path, entry.name, entry.size, entry.crc32);
+    }
+    println!("Wrote {} files below {}", written.len(), output.display());
+    Ok(())
+}
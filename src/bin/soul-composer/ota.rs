@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use soulcomposer::{ota::OtaManifest, signing::parse_signing_key};
+
+use crate::{cli_error::CliError, input, sign::read_text};
+
+#[derive(Debug, Args)]
+pub struct OtaArgs {
+    /// Signed bundle of the release, written by `sign`.
+    pub bundle: PathBuf,
+
+    /// Version of the release, numbers separated by dots such as 1.4.0.
+    #[arg(long)]
+    pub version: String,
+
+    /// Where programmers download the bundle from.
+    #[arg(long)]
+    pub url: String,
+
+    /// Programmer model the release is for. Repeat for several, every model when not given.
+    #[arg(long = "model")]
+    pub models: Vec<String>,
+
+    /// Release notes shown by the programmers.
+    #[arg(long)]
+    pub notes: Option<String>,
+
+    /// Secret key written by `keygen`.
+    #[arg(long = "key")]
+    pub signing_key: Option<PathBuf>,
+
+    /// Where the signed manifest is written, `-` for stdout. Defaults to `ota.json.signed` next
+    /// to the bundle.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+pub fn run(args: OtaArgs) -> Result<(), CliError> {
+    let OtaArgs { bundle: path, version, url, models, notes, signing_key, output } = args;
+    let key_path = signing_key.ok_or(CliError::KeyRequired)?;
+    let key = parse_signing_key(&read_text(&key_path)?).map_err(|source| CliError::Signing { path: key_path, source })?;
+    let bundle = input::read(&path)?;
+
+    let mut manifest = OtaManifest::new(&version, &url, &bundle, models)?;
+    if let Some(notes) = &notes {
+        manifest = manifest.notes(notes);
+    }
+    let signed = manifest.sign(&key)?;
+
+    let output = output.unwrap_or_else(|| path.parent().unwrap_or(Path::new("")).join("ota.json.signed"));
+    input::write(&output, &signed)?;
+    if !input::is_stdio(&output) {
+        println!("{}", manifest.to_json()?);
+    }
+    Ok(())
+}
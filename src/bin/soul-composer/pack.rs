@@ -0,0 +1,182 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+#[cfg(any(feature = "serve", feature = "database"))]
+use soulcomposer::diagnostic::Diagnostic;
+use soulcomposer::{
+    pack::archive::PackArchive,
+    prog::arm::flash_stub_gen::ArmFlashStub,
+    progress::{Progress, ProgressSink},
+};
+
+#[cfg(feature = "database")]
+use crate::validate::lint;
+use crate::{
+    cli_error::CliError,
+    convert::{write_stub, OutputOptions, StubOptions},
+    progress_line::ProgressLine,
+};
+
+#[derive(Debug, Args)]
+pub struct PackArgs {
+    /// CMSIS pack, e.g. Keil.STM32F4xx_DFP.2.15.0.pack.
+    pub pack: PathBuf,
+
+    /// Device or variant whose algorithms to convert.
+    #[arg(long)]
+    pub device: String,
+
+    /// Directory for the stubs.
+    #[arg(short, long, default_value = ".")]
+    pub output: PathBuf,
+
+    /// Store the stubs in this SQLite database with the pack and validation results, instead of
+    /// writing files to --output.
+    #[cfg(feature = "database")]
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub output_options: OutputOptions,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+}
+
+/// An algorithm listed for a device, with the stub or why it could not be composed.
+pub struct PackAlgorithm {
+    /// Path of the FLM inside the pack.
+    pub file: String,
+    pub stub: Result<ArmFlashStub, CliError>,
+    /// Problems applying the device that didn't stop the conversion, already logged.
+    #[cfg(any(feature = "serve", feature = "database"))]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Composes every algorithm the pack at `path` lists for `device_name`.
+pub fn device_algorithms(
+    path: &Path,
+    device_name: &str,
+    options: &StubOptions,
+    progress: &mut impl ProgressSink,
+) -> Result<Vec<PackAlgorithm>, CliError> {
+    let file = File::open(path).map_err(CliError::io(path))?;
+    let mut pack = PackArchive::new(file)?;
+    let pdsc = pack.pdsc()?;
+    let device = pdsc.device(device_name)?;
+    if device.algorithms.is_empty() {
+        return Err(CliError::NoAlgorithms(device.name.clone()));
+    }
+
+    let parse_options = options.parse_options();
+    let mut algorithms = Vec::new();
+    for (completed, algorithm) in device.algorithms.iter().enumerate() {
+        progress.progress(Progress { completed, total: device.algorithms.len(), current: &algorithm.file });
+        let name = algorithm.file.rsplit('/').next().unwrap_or(&algorithm.file);
+        let name = name.rsplit_once('.').map_or(name, |(stem, _)| stem).to_string();
+        let ram_size = match options.ram_size {
+            0 => algorithm.ram_size.unwrap_or(0),
+            ram_size => ram_size,
+        };
+
+        let mut diagnostics = Vec::new();
+        let stub = pack.read_file(&algorithm.file).map_err(CliError::from).and_then(|data| {
+            let default = algorithm.default || options.default;
+            let mut stub = ArmFlashStub::from_elf_with_options(&data, name, default, ram_size, &parse_options)?;
+            diagnostics = stub.apply_pdsc_device(device, &parse_options)?;
+            for diagnostic in &diagnostics {
+                tracing::warn!("{}", diagnostic);
+            }
+            options.apply_overrides(&mut stub);
+            Ok(stub)
+        });
+        algorithms.push(PackAlgorithm {
+            file: algorithm.file.clone(),
+            stub,
+            #[cfg(any(feature = "serve", feature = "database"))]
+            diagnostics,
+        });
+    }
+    progress.finish(algorithms.len());
+
+    Ok(algorithms)
+}
+
+/// Stores the algorithms that converted, with lint findings next to the device diagnostics.
+#[cfg(feature = "database")]
+fn store(args: &PackArgs, database: &Path, algorithms: Vec<PackAlgorithm>) -> Result<(), CliError> {
+    use soulcomposer::database::{StubDatabase, StubSource};
+
+    let pdsc = PackArchive::new(File::open(&args.pack).map_err(CliError::io(&args.pack))?)?.pdsc()?;
+    let device = pdsc.device(&args.device)?;
+    let database = StubDatabase::open(database)?;
+
+    let mut failures = 0;
+    for algorithm in algorithms {
+        let stub = match algorithm.stub {
+            Ok(stub) => stub,
+            Err(err) => {
+                println!("failed  {}: {}", algorithm.file, err);
+                failures += 1;
+                continue;
+            }
+        };
+
+        let source = StubSource {
+            device: device.name.clone(),
+            vendor: device.vendor.clone().or_else(|| Some(pdsc.vendor.clone())),
+            pack: Some(pdsc.name.clone()),
+            pack_version: pdsc.version.clone(),
+            file: Some(algorithm.file.clone()),
+        };
+        let mut findings = algorithm.diagnostics;
+        findings.extend(lint(&stub, None, None));
+        let id = database.insert(&source, &stub, &findings)?;
+        println!("stored  {} as {}", algorithm.file, id);
+    }
+
+    if failures > 0 {
+        return Err(CliError::BatchFailed(failures));
+    }
+
+    Ok(())
+}
+
+pub fn run(args: PackArgs) -> Result<(), CliError> {
+    let algorithms = device_algorithms(&args.pack, &args.device, &args.options, &mut ProgressLine::new())?;
+    #[cfg(feature = "database")]
+    if let Some(database) = &args.database {
+        return store(&args, database, algorithms);
+    }
+    if !args.output_options.dry_run {
+        fs::create_dir_all(&args.output).map_err(CliError::io(&args.output))?;
+    }
+    let format = args.output_options.format();
+
+    let mut failures = 0;
+    for algorithm in algorithms {
+        let result = algorithm
+            .stub
+            .and_then(|stub| {
+                let output = args.output.join(format!("{}.{}", stub.name, format.extension()));
+                write_stub(&stub, &output, &args.output_options)
+            });
+        match result {
+            Ok(()) => println!("ok      {}", algorithm.file),
+            Err(err) => {
+                println!("failed  {}: {}", algorithm.file, err);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(CliError::BatchFailed(failures));
+    }
+
+    Ok(())
+}
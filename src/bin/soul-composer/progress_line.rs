@@ -0,0 +1,40 @@
+use std::io::{self, IsTerminal, Write};
+
+use soulcomposer::progress::{Progress, ProgressSink};
+
+/// Redraws one `[completed/total] current` line on stderr, or stays quiet when stderr isn't a
+/// terminal so logs and pipes don't fill up with carriage returns.
+pub struct ProgressLine {
+    enabled: bool,
+    drawn: bool,
+}
+
+impl ProgressLine {
+    pub fn new() -> Self {
+        ProgressLine { enabled: io::stderr().is_terminal(), drawn: false }
+    }
+
+    /// Erases the line, before printing something else to the terminal.
+    pub fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            self.drawn = false;
+        }
+    }
+}
+
+impl ProgressSink for ProgressLine {
+    fn progress(&mut self, progress: Progress<'_>) {
+        if !self.enabled {
+            return;
+        }
+
+        eprint!("\r\x1b[K[{}/{}] {}", progress.completed + 1, progress.total, progress.current);
+        let _ = io::stderr().flush();
+        self.drawn = true;
+    }
+
+    fn finish(&mut self, _completed: usize) {
+        self.clear();
+    }
+}
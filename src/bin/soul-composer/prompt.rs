@@ -0,0 +1,73 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use soulcomposer::prog::arm::flash_stub_gen::ArmFlashStub;
+
+use crate::cli_error::CliError;
+
+/// Asks for values on the terminal, or takes the defaults when there is nobody to ask.
+pub struct Prompter {
+    enabled: bool,
+}
+
+impl Prompter {
+    /// Prompts only when stdin and stderr are both terminals and `no_input` isn't set.
+    pub fn new(no_input: bool) -> Self {
+        Prompter { enabled: !no_input && io::stdin().is_terminal() && io::stderr().is_terminal() }
+    }
+
+    fn ask(&self, question: &str, hint: &str) -> Result<String, CliError> {
+        eprint!("{} [{}]: ", question, hint);
+        io::stderr().flush().map_err(CliError::io("stderr"))?;
+
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).map_err(CliError::io("stdin"))?;
+        Ok(answer.trim().to_string())
+    }
+
+    /// Asks for a line of text, an empty answer keeps `default`.
+    pub fn text(&self, question: &str, default: &str) -> Result<String, CliError> {
+        if !self.enabled {
+            return Ok(default.to_string());
+        }
+
+        let answer = self.ask(question, default)?;
+        Ok(if answer.is_empty() { default.to_string() } else { answer })
+    }
+
+    /// Asks a yes or no question, an empty or unrecognised answer keeps `default`.
+    pub fn confirm(&self, question: &str, default: bool) -> Result<bool, CliError> {
+        if !self.enabled {
+            return Ok(default);
+        }
+
+        let answer = self.ask(question, if default { "Y/n" } else { "y/N" })?;
+        Ok(match answer.to_ascii_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => default,
+        })
+    }
+}
+
+/// Fills in the name and description the input didn't provide, and whether the stub is the
+/// default if `ask_default` says no flag or PDSC decided it.
+///
+/// The description falls back to the name, so neither ends up empty. The default is only asked
+/// about when something else was missing too, most algorithms aren't the default one.
+pub fn fill_metadata(stub: &mut ArmFlashStub, ask_default: bool, prompter: &Prompter) -> Result<(), CliError> {
+    let name_missing = stub.name.is_empty();
+    let description_missing = stub.description.is_empty();
+
+    if name_missing {
+        let fallback = if stub.name.is_empty() { "flash" } else { stub.name.as_str() };
+        stub.name = prompter.text("Stub name", fallback)?;
+    }
+    if description_missing {
+        stub.description = prompter.text("Description", &stub.name)?;
+    }
+    if ask_default && (name_missing || description_missing) {
+        stub.default = prompter.confirm("Default algorithm of the target?", stub.default)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::{
+    prog::{
+        arm::stub_group::ArmFlashStubGroup,
+        export::{export_model, export_validated, OutputFormat},
+    },
+    publish::{Credentials, ObjectKind, Publication, Publisher, S3Store},
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{file_stem, load_stub, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct PublishArgs {
+    /// Algorithms to publish as stubs, FLMs or stubs written by `convert`.
+    pub inputs: Vec<PathBuf>,
+
+    /// Manifests written by `merge`.
+    #[arg(long)]
+    pub manifest: Vec<PathBuf>,
+
+    /// Signed bundles written by `sign`, published as they are.
+    #[arg(long)]
+    pub bundle: Vec<PathBuf>,
+
+    /// Scheme and host of the S3-compatible service, e.g. https://s3.eu-central-1.amazonaws.com.
+    #[arg(long)]
+    pub endpoint: Option<String>,
+
+    #[arg(long, default_value = "us-east-1")]
+    pub region: String,
+
+    #[arg(long)]
+    pub bucket: Option<String>,
+
+    /// Key prefix of the library in the bucket.
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// Address the bucket as a subdomain of the endpoint instead of in the path.
+    #[arg(long)]
+    pub virtual_hosted: bool,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: PublishArgs) -> Result<(), CliError> {
+    let device = args.source.device.clone().ok_or(CliError::PublishDeviceRequired)?;
+    let endpoint = args.endpoint.as_deref().ok_or(CliError::PublishTarget("--endpoint"))?;
+    let bucket = args.bucket.as_deref().ok_or(CliError::PublishTarget("--bucket"))?;
+    let credentials = Credentials::from_env().ok_or(CliError::CredentialsRequired)?;
+
+    // Everything is read and converted before the first upload.
+    let mut files = Vec::new();
+    for path in &args.inputs {
+        let stub = load_stub(path, None, &args.options, &args.source)?.validate()?;
+        files.push((ObjectKind::Stub, stub.name.clone(), export_validated(&stub, OutputFormat::Json)?));
+    }
+    for path in &args.manifest {
+        let group: ArmFlashStubGroup = serde_json::from_slice(&input::read(path)?)
+            .map_err(|err| CliError::StubParse { path: path.clone(), reason: err.to_string() })?;
+        files.push((ObjectKind::Manifest, group.name.clone(), export_model(&group, OutputFormat::Json)?));
+    }
+    for path in &args.bundle {
+        files.push((ObjectKind::Bundle, file_stem(path), input::read(path)?));
+    }
+    if files.is_empty() {
+        return Err(CliError::NothingToPublish);
+    }
+
+    let mut store = S3Store::new(endpoint, &args.region, bucket, credentials)?;
+    if args.virtual_hosted {
+        store = store.virtual_hosted();
+    }
+    let publications: Vec<Publication<'_>> =
+        files.iter().map(|(kind, name, data)| Publication { kind: *kind, name, data }).collect();
+    for object in Publisher::new(store, &args.prefix).publish(&device, &publications)? {
+        println!("Published {:?} {} as {}", object.kind, object.name, object.key);
+    }
+
+    Ok(())
+}
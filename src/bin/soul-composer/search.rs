@@ -0,0 +1,127 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+use soulcomposer::{
+    pack::{
+        index::{parse_index, KEIL_INDEX_URL},
+        pdsc::Pdsc,
+    },
+    progress::{Progress, ProgressSink},
+};
+
+use crate::{cli_error::CliError, progress_line::ProgressLine};
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Part of a device name, matched ignoring case.
+    pub query: String,
+
+    /// Download the pack index and all PDSC files into the cache first.
+    #[arg(long)]
+    pub update: bool,
+
+    /// Pack index to download from.
+    #[arg(long, default_value = KEIL_INDEX_URL)]
+    pub index_url: String,
+
+    /// Cache directory, `$XDG_CACHE_HOME/soul-composer` or `~/.cache/soul-composer` by default.
+    #[arg(long)]
+    pub cache: Option<PathBuf>,
+}
+
+pub fn default_cache() -> PathBuf {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("soul-composer")
+}
+
+fn download(url: &str) -> Result<String, CliError> {
+    let failed = |reason: String| CliError::Download { url: url.to_string(), reason };
+    let response = ureq::get(url).call().map_err(|err| failed(err.to_string()))?;
+    response.into_string().map_err(|err| failed(err.to_string()))
+}
+
+/// Fetches the index and every PDSC it lists, skipping the ones that fail to download.
+fn update(index_url: &str, cache: &Path) -> Result<(), CliError> {
+    let pdsc_dir = cache.join("pdsc");
+    fs::create_dir_all(&pdsc_dir).map_err(CliError::io(&pdsc_dir))?;
+
+    let index = download(index_url)?;
+    let index_path = cache.join("index.pidx");
+    fs::write(&index_path, &index).map_err(CliError::io(&index_path))?;
+
+    let entries = parse_index(&index)?;
+    let mut progress = ProgressLine::new();
+    for (completed, entry) in entries.iter().enumerate() {
+        let url = entry.pdsc_url();
+        let _span = tracing::info_span!("pdsc", number = completed + 1, total = entries.len(), url = %url).entered();
+        progress.progress(Progress { completed, total: entries.len(), current: &url });
+        match download(&url) {
+            Ok(text) => {
+                let path = pdsc_dir.join(entry.pdsc_file_name());
+                fs::write(&path, text).map_err(CliError::io(&path))?;
+            }
+            Err(err) => {
+                progress.clear();
+                tracing::warn!("{}", err);
+            }
+        }
+    }
+    progress.finish(entries.len());
+
+    Ok(())
+}
+
+pub fn run(args: SearchArgs) -> Result<(), CliError> {
+    let cache = args.cache.clone().unwrap_or_else(default_cache);
+    if args.update {
+        update(&args.index_url, &cache)?;
+    }
+
+    let pdsc_dir = cache.join("pdsc");
+    let mut paths = match fs::read_dir(&pdsc_dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    if paths.is_empty() {
+        return Err(CliError::CacheEmpty(pdsc_dir));
+    }
+    paths.sort();
+
+    let query = args.query.to_ascii_lowercase();
+    let mut matches = 0;
+    for path in paths {
+        let pdsc = match fs::read_to_string(&path).map_err(CliError::io(&path)).and_then(|text| Ok(Pdsc::parse(&text)?)) {
+            Ok(pdsc) => pdsc,
+            Err(err) => {
+                tracing::warn!("Skipping {}, {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let pack = format!("{}.{} {}", pdsc.vendor, pdsc.name, pdsc.version.as_deref().unwrap_or("?"));
+        for device in pdsc.devices.iter().filter(|device| device.name.to_ascii_lowercase().contains(&query)) {
+            let defaults: Vec<&str> = device
+                .algorithms
+                .iter()
+                .filter(|algorithm| algorithm.default)
+                .map(|algorithm| algorithm.file.rsplit('/').next().unwrap_or(&algorithm.file))
+                .collect();
+            let algorithms = if defaults.is_empty() { "-".to_string() } else { defaults.join(", ") };
+            println!("{:<24}{:<40}{}", device.name, pack, algorithms);
+            matches += 1;
+        }
+    }
+
+    if matches == 0 {
+        return Err(CliError::NoDevices(args.query));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,658 @@
+use std::{
+    fs, io,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+use clap::Args;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use soulcomposer::{
+    compose_stub,
+    diagnostic::Diagnostic,
+    pack::{archive::PackArchive, pdsc::Pdsc},
+    prog::{
+        arm::flash_stub_gen::ArmFlashStub,
+        export::{export_model_named, export_named, naming::FieldNaming, OutputFormat},
+    },
+    registry::StubRegistry,
+    progress::{Progress, ProgressSink},
+    ArmError, ComposeOptions, Elf,
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::StubOptions,
+    events::{self, Events},
+    metrics::Metrics,
+    pack::device_algorithms,
+    search::default_cache,
+    webhook::{Notice, Webhooks},
+};
+
+/// Largest FLM or pack converted from memory, up to one per worker at a time. Larger packs go
+/// through `POST /packs`.
+const MAX_UPLOAD: u64 = 64 * 1024 * 1024;
+
+/// Largest pack `POST /packs` streams into the cache, packs with many devices run to a few hundred MB.
+const MAX_PACK_UPLOAD: u64 = 512 * 1024 * 1024;
+
+/// Numbers the partial uploads in the cache.
+static PARTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Port to listen on, 0 picks a free one.
+    #[arg(short, long, default_value_t = 8080)]
+    pub port: u16,
+
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1")]
+    pub bind: String,
+
+    /// Requests handled at the same time.
+    #[arg(long, default_value_t = 4)]
+    pub threads: usize,
+
+    /// Cache directory for uploaded packs, the `search` cache by default.
+    #[arg(long)]
+    pub cache: Option<PathBuf>,
+
+    /// Also answer gRPC, see `proto/soul_composer.proto`, on this port of the same address.
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    pub grpc_port: Option<u16>,
+
+    /// URL that is POSTed a JSON notice of every conversion, repeat for more.
+    #[arg(long = "webhook")]
+    pub webhooks: Vec<String>,
+
+    /// Defaults for every conversion, requests can override the name, format and default flag.
+    #[command(flatten)]
+    pub options: StubOptions,
+}
+
+/// A response ready to send.
+struct Reply {
+    status: u16,
+    media_type: &'static str,
+    body: Vec<u8>,
+    /// Id of the operation whose progress went to `GET /events`.
+    operation: Option<String>,
+}
+
+impl Reply {
+    fn ok(media_type: &'static str, body: Vec<u8>) -> Reply {
+        Reply { status: 200, media_type, body, operation: None }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Reply {
+        let body = serde_json::json!({ "error": message.to_string() });
+        Reply { status, media_type: "application/json", body: body.to_string().into_bytes(), operation: None }
+    }
+}
+
+/// The error with the diagnostic `validate` would report for it, so clients can match on the code.
+impl From<CliError> for Reply {
+    fn from(err: CliError) -> Reply {
+        let status = match err {
+            CliError::Io { .. } => 500,
+            CliError::DeviceRequired => 400,
+            _ => 422,
+        };
+        let finding = Finding { file: None, diagnostic: failure_diagnostic(&err) };
+        let body = serde_json::json!({ "error": err.to_string(), "diagnostics": [finding] });
+        Reply { status, media_type: "application/json", body: body.to_string().into_bytes(), operation: None }
+    }
+}
+
+pub fn failure_diagnostic(err: &CliError) -> Diagnostic {
+    let diagnostic = Diagnostic::error("conversion-failed", err.to_string());
+    match err.code() {
+        Some(code) => diagnostic.with_id(code),
+        None => diagnostic,
+    }
+}
+
+/// What `POST /packs` and `GET /packs` answer with.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedPack {
+    pub id: String,
+    pub vendor: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub devices: Vec<String>,
+}
+
+impl CachedPack {
+    pub fn new(id: String, pdsc: Pdsc) -> Self {
+        CachedPack {
+            id,
+            vendor: pdsc.vendor,
+            name: pdsc.name,
+            version: pdsc.version,
+            devices: pdsc.devices.into_iter().map(|device| device.name).collect(),
+        }
+    }
+}
+
+/// A diagnostic and the algorithm in a pack it is about.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+}
+
+/// What `POST /conversions` answers with.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversion {
+    pub stubs: Vec<ArmFlashStub>,
+    pub diagnostics: Vec<Finding>,
+}
+
+/// What a conversion asks for besides the upload, from the query of `POST /conversions` or a
+/// gRPC `ConvertRequest`.
+#[derive(Debug, Default)]
+pub struct ConversionRequest<'a> {
+    /// Stub name of an FLM, `flash` by default.
+    pub name: Option<&'a str>,
+    /// Device whose algorithms to convert from a pack, the stubs are kept under it.
+    pub device: Option<&'a str>,
+    /// Overrides `StubOptions::default`.
+    pub default: Option<bool>,
+    /// Id the progress goes to `GET /events` under, a new number when `None`.
+    pub operation: Option<&'a str>,
+}
+
+/// Splits the query string into decoded key/value pairs.
+fn query(url: &str) -> Vec<(String, String)> {
+    let query = url.split_once('?').map_or("", |(_, query)| query);
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        let escaped = bytes.get(at + 1..at + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[at], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                at += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        at += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn param<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+}
+
+fn format_param(params: &[(String, String)]) -> Result<OutputFormat, Reply> {
+    param(params, "format").unwrap_or("json").parse().map_err(|err| Reply::error(400, err))
+}
+
+fn naming_param(params: &[(String, String)]) -> Result<FieldNaming, Reply> {
+    param(params, "naming").map_or(Ok(FieldNaming::Camel), |naming| naming.parse().map_err(|err| Reply::error(400, err)))
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>, Reply> {
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_UPLOAD + 1).read_to_end(&mut body).map_err(|err| Reply::error(400, err))?;
+    if body.len() as u64 > MAX_UPLOAD {
+        return Err(Reply::error(413, format!("uploads are limited to {} bytes", MAX_UPLOAD)));
+    }
+    Ok(body)
+}
+
+/// `POST /convert`, the body is an FLM.
+fn convert(body: &[u8], params: &[(String, String)], state: &State) -> Result<Reply, Reply> {
+    let (format, naming) = (format_param(params)?, naming_param(params)?);
+    let name = param(params, "name").unwrap_or("flash").to_string();
+    let options = &state.options;
+    let default = param(params, "default").map_or(options.default, |value| value == "true" || value == "1");
+
+    let mut stub = ArmFlashStub::from_elf_with_options(body, name, default, options.ram_size, &options.parse_options())
+        .map_err(|err| {
+            state.metrics.failed(Some(err.code()));
+            Reply::from(CliError::from(err))
+        })?;
+    state.metrics.converted(1);
+    options.apply_overrides(&mut stub);
+    let data = export_named(&stub, format, naming).map_err(|err| Reply::error(422, err))?;
+    Ok(Reply::ok(format.media_type(), data))
+}
+
+/// Packs are cached under their SHA-256, uploading the same pack twice gives the same id.
+fn pack_path(cache: &Path, id: &str) -> Option<PathBuf> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit());
+    Some(cache.join(format!("{}.pack", id))).filter(|_| valid)
+}
+
+/// A pack being written to the cache, removed unless it is kept.
+struct Part {
+    path: PathBuf,
+    id: String,
+    size: u64,
+}
+
+impl Part {
+    /// Copies at most `limit + 1` bytes of `reader` into a new file of `cache`, hashing them on the way.
+    fn spool(mut reader: impl Read, cache: &Path, limit: u64) -> Result<Part, CliError> {
+        fs::create_dir_all(cache).map_err(CliError::io(cache))?;
+        let path = cache.join(format!(".{}-{}.part", process::id(), PARTS.fetch_add(1, Ordering::Relaxed)));
+        let file = fs::File::create(&path).map_err(CliError::io(&path))?;
+        let mut part = Part { path, id: String::new(), size: 0 };
+
+        let (mut file, mut hasher, mut buffer) = (io::BufWriter::new(file), Sha256::new(), vec![0u8; 64 * 1024]);
+        while part.size <= limit {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(CliError::io(&part.path)(err)),
+            };
+            hasher.update(&buffer[..read]);
+            file.write_all(&buffer[..read]).map_err(CliError::io(&part.path))?;
+            part.size += read as u64;
+        }
+        file.flush().map_err(CliError::io(&part.path))?;
+        part.id = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(part)
+    }
+
+    /// Checks that the part is a pack and moves it into the cache, returning its id and description.
+    fn keep(self, state: &State) -> Result<(String, Pdsc), CliError> {
+        let pdsc = read_pdsc(&self.path)?;
+        let path = state.cache.join(format!("{}.pack", self.id));
+        let cached = path.is_file();
+        state.metrics.cache("packs", cached);
+        if !cached {
+            fs::rename(&self.path, &path).map_err(CliError::io(&path))?;
+        }
+        Ok((self.id.clone(), pdsc))
+    }
+}
+
+impl Drop for Part {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Checks that `body` is a pack and caches it, returning its id and description.
+pub fn store_pack(body: &[u8], state: &State) -> Result<(String, Pdsc), CliError> {
+    Part::spool(body, &state.cache, u64::MAX)?.keep(state)
+}
+
+fn read_pdsc(path: &Path) -> Result<Pdsc, CliError> {
+    let file = fs::File::open(path).map_err(CliError::io(path))?;
+    Ok(PackArchive::new(file)?.pdsc()?)
+}
+
+/// Every pack in the cache sorted by id. Files that stopped being readable packs are skipped.
+pub fn cached_packs(cache: &Path) -> Result<Vec<CachedPack>, CliError> {
+    let mut packs = Vec::new();
+    let entries = match fs::read_dir(cache) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(packs),
+        Err(err) => return Err(CliError::io(cache)(err)),
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let id = match path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".pack")) {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        match read_pdsc(&path) {
+            Ok(pdsc) => packs.push(CachedPack::new(id, pdsc)),
+            Err(err) => tracing::warn!("Skipping {}, {}", path.display(), err),
+        }
+    }
+    packs.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(packs)
+}
+
+/// The cached pack `id`, `None` when there is none.
+pub fn cached_pack(cache: &Path, id: &str) -> Result<Option<CachedPack>, CliError> {
+    match pack_path(cache, id).filter(|path| path.is_file()) {
+        Some(path) => Ok(Some(CachedPack::new(id.to_string(), read_pdsc(&path)?))),
+        None => Ok(None),
+    }
+}
+
+fn json_reply(value: &impl Serialize) -> Result<Reply, Reply> {
+    let body = serde_json::to_vec_pretty(value).map_err(|err| Reply::error(500, err))?;
+    Ok(Reply::ok("application/json", body))
+}
+
+/// `POST /packs`, the body is a CMSIS pack. It is streamed into the cache rather than read into memory.
+fn upload_pack(request: &mut Request, state: &State) -> Result<Reply, Reply> {
+    let part = Part::spool(request.as_reader(), &state.cache, MAX_PACK_UPLOAD)?;
+    if part.size > MAX_PACK_UPLOAD {
+        return Err(Reply::error(413, format!("packs are limited to {} bytes", MAX_PACK_UPLOAD)));
+    }
+    let (id, pdsc) = part.keep(state)?;
+    json_reply(&CachedPack::new(id, pdsc))
+}
+
+/// Packs are zip archives, anything else is taken for an FLM.
+fn is_pack(body: &[u8]) -> bool {
+    body.starts_with(b"PK\x03\x04")
+}
+
+/// Converts an uploaded FLM or pack. A pack needs a device, whose algorithms are converted and
+/// kept in the registry, an FLM is kept under the device when given. Algorithms of a pack that
+/// fail come back as diagnostics, a failing FLM as the error.
+pub fn convert_upload(body: &[u8], request: &ConversionRequest<'_>, state: &State) -> Result<Conversion, CliError> {
+    let id = request.operation.map_or_else(|| state.events.next_id(), str::to_string);
+    let mut operation = state.events.start(&id, "conversion");
+    let conversion = convert_reporting(body, request, state, &mut operation);
+    let device = request.device.map(str::to_string);
+    match &conversion {
+        Ok(conversion) => state.webhooks.notify_in_background(Notice::ConversionCompleted {
+            device,
+            stubs: conversion.stubs.iter().map(|stub| stub.name.clone()).collect(),
+            diagnostics: conversion.diagnostics.len(),
+        }),
+        Err(err) => {
+            operation.fail(err);
+            state.metrics.failed(err.code());
+            state.webhooks.notify_in_background(failure_notice(device, err));
+        }
+    }
+    conversion
+}
+
+fn failure_notice(device: Option<String>, err: &CliError) -> Notice {
+    Notice::ConversionFailed { device, error: err.to_string(), code: err.code().map(str::to_string) }
+}
+
+fn convert_reporting(
+    body: &[u8],
+    request: &ConversionRequest<'_>,
+    state: &State,
+    operation: &mut events::Operation<'_>,
+) -> Result<Conversion, CliError> {
+    let mut conversion = Conversion { stubs: Vec::new(), diagnostics: Vec::new() };
+
+    if is_pack(body) {
+        let device = request.device.ok_or(CliError::DeviceRequired)?;
+        let (id, _) = store_pack(body, state)?;
+        let path = state.cache.join(format!("{}.pack", id));
+        for algorithm in device_algorithms(&path, device, &state.options, operation)? {
+            let file = Some(algorithm.file);
+            conversion.diagnostics.extend(
+                algorithm.diagnostics.into_iter().map(|diagnostic| Finding { file: file.clone(), diagnostic }),
+            );
+            match algorithm.stub {
+                Ok(stub) => conversion.stubs.push(stub),
+                Err(err) => {
+                    state.metrics.failed(err.code());
+                    conversion.diagnostics.push(Finding { file, diagnostic: failure_diagnostic(&err) });
+                }
+            }
+        }
+    } else {
+        let options = &state.options;
+        let mut compose_options = ComposeOptions::new(request.name.unwrap_or("flash"));
+        compose_options.default = request.default.unwrap_or(options.default);
+        compose_options.ram_size = options.ram_size;
+        compose_options.parse = options.parse_options();
+
+        operation.progress(Progress { completed: 0, total: 1, current: &compose_options.name });
+        let elf = Elf::parse(body).map_err(ArmError::from)?;
+        let (mut stub, diagnostics) = compose_stub(&elf, body, &compose_options)?;
+        options.apply_overrides(&mut stub);
+        conversion.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| Finding { file: None, diagnostic }));
+        conversion.stubs.push(stub);
+        operation.finish(1);
+    }
+
+    state.metrics.converted(conversion.stubs.len());
+    if let (Some(device), false) = (request.device, conversion.stubs.is_empty()) {
+        state.registry.insert(device, conversion.stubs.clone());
+    }
+    Ok(conversion)
+}
+
+/// `POST /conversions`, the body is an FLM or a pack, see `convert_upload`. Answers with the
+/// stubs and every diagnostic in JSON, whatever `format` says.
+fn conversions(body: &[u8], params: &[(String, String)], state: &State) -> Result<Reply, Reply> {
+    let operation = param(params, "operation").map_or_else(|| state.events.next_id(), str::to_string);
+    let request = ConversionRequest {
+        name: param(params, "name"),
+        device: param(params, "device"),
+        default: param(params, "default").map(|value| value == "true" || value == "1"),
+        operation: Some(&operation),
+    };
+    let with_operation = |mut reply: Reply| {
+        reply.operation = Some(operation.clone());
+        reply
+    };
+    let conversion = convert_upload(body, &request, state).map_err(|err| with_operation(err.into()))?;
+
+    let mut reply = with_operation(json_reply(&conversion)?);
+    if conversion.stubs.is_empty() {
+        reply.status = 422;
+    }
+    Ok(reply)
+}
+
+/// `GET /packs`, every cached pack.
+fn list_packs(cache: &Path) -> Result<Reply, Reply> {
+    json_reply(&cached_packs(cache)?)
+}
+
+/// `GET /packs/<id>`, one cached pack.
+fn get_pack(id: &str, cache: &Path) -> Result<Reply, Reply> {
+    let pack = cached_pack(cache, id)?.ok_or_else(|| Reply::error(404, format!("no pack {}", id)))?;
+    json_reply(&pack)
+}
+
+/// `GET /packs/<id>/devices/<device>`, every algorithm of the device as a list of stubs. They are
+/// kept in the registry for `GET /devices`.
+fn pack_device(id: &str, device: &str, params: &[(String, String)], state: &State) -> Result<Reply, Reply> {
+    let (format, naming) = (format_param(params)?, naming_param(params)?);
+    let path = pack_path(&state.cache, id).filter(|path| path.is_file());
+    state.metrics.cache("packs", path.is_some());
+    let path = path.ok_or_else(|| Reply::error(404, format!("no pack {}", id)))?;
+
+    let device = percent_decode(device);
+    let id = param(params, "operation").map_or_else(|| state.events.next_id(), str::to_string);
+    let mut operation = state.events.start(&id, "conversion");
+    let with_operation = |mut reply: Reply| {
+        reply.operation = Some(id.clone());
+        reply
+    };
+    let algorithms = device_algorithms(&path, &device, &state.options, &mut operation).map_err(|err| {
+        operation.fail(&err);
+        state.metrics.failed(err.code());
+        state.webhooks.notify_in_background(failure_notice(Some(device.clone()), &err));
+        with_operation(err.into())
+    })?;
+    let mut stubs = Vec::new();
+    for algorithm in algorithms {
+        match algorithm.stub {
+            Ok(stub) => stubs.push(stub),
+            Err(err) => {
+                tracing::warn!("{}: {}", algorithm.file, err);
+                state.metrics.failed(err.code());
+            }
+        }
+    }
+    state.metrics.converted(stubs.len());
+    if stubs.is_empty() {
+        return Err(with_operation(Reply::error(422, format!("no algorithm of {} converted", device))));
+    }
+
+    let data = export_model_named(&stubs, format, naming).map_err(|err| Reply::error(400, err))?;
+    state.webhooks.notify_in_background(Notice::ConversionCompleted {
+        device: Some(device.clone()),
+        stubs: stubs.iter().map(|stub| stub.name.clone()).collect(),
+        diagnostics: 0,
+    });
+    state.registry.insert(device, stubs);
+    Ok(with_operation(Reply::ok(format.media_type(), data)))
+}
+
+/// `GET /devices`, every device converted so far with its stubs.
+fn devices(params: &[(String, String)], registry: &StubRegistry) -> Result<Reply, Reply> {
+    let (format, naming) = (format_param(params)?, naming_param(params)?);
+    let data = export_model_named(&registry.snapshot(), format, naming).map_err(|err| Reply::error(400, err))?;
+    Ok(Reply::ok(format.media_type(), data))
+}
+
+/// `GET /devices/<device>`, the stubs of a device converted before.
+fn device(device: &str, params: &[(String, String)], state: &State) -> Result<Reply, Reply> {
+    let (format, naming) = (format_param(params)?, naming_param(params)?);
+    let device = percent_decode(device);
+    let stubs = state.registry.get(&device);
+    state.metrics.cache("devices", stubs.is_some());
+    let stubs = stubs.ok_or_else(|| Reply::error(404, format!("no stubs for {}", device)))?;
+    let data = export_model_named(&stubs[..], format, naming).map_err(|err| Reply::error(400, err))?;
+    Ok(Reply::ok(format.media_type(), data))
+}
+
+/// What every worker shares, the gRPC service too.
+pub struct State {
+    pub cache: PathBuf,
+    pub options: StubOptions,
+    pub registry: StubRegistry,
+    pub events: Events,
+    pub webhooks: Webhooks,
+    pub metrics: Metrics,
+}
+
+/// The pattern of the route `segments` take, the `route` label of the metrics.
+fn route(segments: &[&str]) -> &'static str {
+    match segments {
+        ["health"] => "/health",
+        ["metrics"] => "/metrics",
+        ["convert"] => "/convert",
+        ["conversions"] => "/conversions",
+        ["packs"] => "/packs",
+        ["packs", _] => "/packs/{id}",
+        ["packs", _, "devices", _] => "/packs/{id}/devices/{device}",
+        ["devices"] => "/devices",
+        ["devices", _] => "/devices/{device}",
+        _ => "other",
+    }
+}
+
+fn segments(url: &str) -> Vec<&str> {
+    let path = url.split('?').next().unwrap_or_default();
+    path.split('/').filter(|segment| !segment.is_empty()).collect()
+}
+
+fn handle(request: &mut Request, state: &State) -> Result<Reply, Reply> {
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or_default();
+    let params = query(&url);
+
+    match (request.method(), segments(&url).as_slice()) {
+        (Method::Get, ["health"]) => Ok(Reply::ok("text/plain", b"ok".to_vec())),
+        (Method::Get, ["metrics"]) => Ok(Reply::ok("text/plain; version=0.0.4", state.metrics.render().into_bytes())),
+        (Method::Post, ["convert"]) => convert(&read_body(request)?, &params, state),
+        (Method::Post, ["conversions"]) => conversions(&read_body(request)?, &params, state),
+        (Method::Get, ["packs"]) => list_packs(&state.cache),
+        (Method::Post, ["packs"]) => upload_pack(request, state),
+        (Method::Get, ["packs", id]) => get_pack(id, &state.cache),
+        (Method::Get, ["packs", id, "devices", device]) => pack_device(id, device, &params, state),
+        (Method::Get, ["devices"]) => devices(&params, &state.registry),
+        (Method::Get, ["devices", name]) => device(name, &params, state),
+        _ => Err(Reply::error(404, format!("no route for {} {}", request.method(), path))),
+    }
+}
+
+fn respond(mut request: Request, state: &State) {
+    let span = tracing::info_span!("request", method = %request.method(), url = %request.url());
+    let _entered = span.enter();
+    if request.method() == &Method::Get && request.url().split('?').next() == Some("/events") {
+        return events::stream(request, &state.events);
+    }
+    let started = Instant::now();
+    let reply = handle(&mut request, state).unwrap_or_else(|reply| reply);
+    tracing::info!(status = reply.status, "responded");
+    state.metrics.request(route(&segments(request.url())), reply.status, started.elapsed());
+
+    let content_type = Header::from_bytes("Content-Type", reply.media_type).expect("static header is valid");
+    let mut response = Response::from_data(reply.body).with_status_code(reply.status).with_header(content_type);
+    // Ids that aren't ASCII can't go in a header, the events still carry them.
+    if let Some(header) = reply.operation.and_then(|operation| Header::from_bytes("X-Operation-Id", operation).ok()) {
+        response.add_header(header);
+    }
+    if let Err(err) = request.respond(response) {
+        tracing::warn!("Failed to respond, {}", err);
+    }
+}
+
+pub fn run(args: ServeArgs) -> Result<(), CliError> {
+    let address = format!("{}:{}", args.bind, args.port);
+    let server = Server::http(&address).map_err(|err| CliError::Serve { address: address.clone(), reason: err.to_string() })?;
+    match server.server_addr().to_ip() {
+        Some(address) => println!("Listening on http://{}", address),
+        None => println!("Listening on {}", address),
+    }
+
+    let server = Arc::new(server);
+    let threads = args.threads.max(1);
+    let state = Arc::new(State {
+        cache: args.cache.unwrap_or_else(default_cache).join("packs"),
+        options: args.options,
+        registry: StubRegistry::new(),
+        events: Events::new(),
+        webhooks: Webhooks::new(args.webhooks, "serve"),
+        metrics: Metrics::new(),
+    });
+    #[cfg(feature = "grpc")]
+    if let Some(port) = args.grpc_port {
+        let address = crate::grpc::spawn(&format!("{}:{}", args.bind, port), state.clone())?;
+        println!("gRPC on {}", address);
+    }
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let (server, state) = (server.clone(), state.clone());
+            thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    respond(request, &state);
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Ok(())
+}
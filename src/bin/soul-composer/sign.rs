@@ -0,0 +1,109 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+use soulcomposer::signing::{self, generate_key, parse_public_key, parse_signing_key, to_hex, SignedBundle};
+
+use crate::{cli_error::CliError, input};
+
+#[derive(Debug, Args)]
+pub struct KeygenArgs {
+    /// Where the secret key is written. The public key goes next to it, with `.pub` appended.
+    pub output: PathBuf,
+
+    /// Replace existing key files.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SignArgs {
+    /// Stub, group or manifest to sign, as exported.
+    pub input: PathBuf,
+
+    /// Secret key written by `keygen`.
+    #[arg(long = "key")]
+    pub signing_key: Option<PathBuf>,
+
+    /// Where the bundle is written, `-` for stdout. Defaults to the input with `.signed` appended.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Bundle written by `sign`.
+    pub input: PathBuf,
+
+    /// Public keys to trust, written by `keygen`. Repeat for several.
+    #[arg(long = "key", required = true)]
+    pub keys: Vec<PathBuf>,
+
+    /// Write the payload here once the bundle checks out, `-` for stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+pub fn read_text(path: &Path) -> Result<String, CliError> {
+    String::from_utf8(input::read(path)?).map_err(|err| CliError::Signing {
+        path: path.to_path_buf(),
+        source: signing::SigningError::Key(err.to_string()),
+    })
+}
+
+pub fn run_keygen(args: KeygenArgs) -> Result<(), CliError> {
+    let mut public = args.output.clone().into_os_string();
+    public.push(".pub");
+    let public = PathBuf::from(public);
+    for path in [&args.output, &public] {
+        if path.exists() && !args.force {
+            return Err(CliError::KeyExists(path.clone()));
+        }
+    }
+
+    let key = generate_key().map_err(|source| CliError::Signing { path: args.output.clone(), source })?;
+    let public_key = to_hex(key.verifying_key().as_bytes());
+    fs::write(&args.output, format!("{}\n", to_hex(&key.to_bytes()))).map_err(CliError::io(&args.output))?;
+    fs::write(&public, format!("{}\n", public_key)).map_err(CliError::io(&public))?;
+
+    println!("Public key {}", public_key);
+    Ok(())
+}
+
+pub fn run_sign(args: SignArgs) -> Result<(), CliError> {
+    let SignArgs { input: path, signing_key, output } = args;
+    let key_path = signing_key.ok_or(CliError::KeyRequired)?;
+    let key = parse_signing_key(&read_text(&key_path)?).map_err(|source| CliError::Signing { path: key_path, source })?;
+    let payload = input::read(&path)?;
+    let bundle = signing::sign(&payload, &key).map_err(|source| CliError::Signing { path: path.clone(), source })?;
+
+    let output = output.unwrap_or_else(|| {
+        let mut output = path.into_os_string();
+        output.push(".signed");
+        output.into()
+    });
+    input::write(&output, &bundle)
+}
+
+pub fn run_verify(args: VerifyArgs) -> Result<(), CliError> {
+    let mut trusted = Vec::with_capacity(args.keys.len());
+    for path in &args.keys {
+        trusted.push(parse_public_key(&read_text(path)?).map_err(|source| CliError::Signing { path: path.clone(), source })?);
+    }
+
+    let data = input::read(&args.input)?;
+    let signing_error = |source: signing::BundleError| CliError::Signing { path: args.input.clone(), source: source.into() };
+    let bundle = SignedBundle::parse(&data).map_err(signing_error)?;
+    let payload = bundle.verify(&trusted).map_err(signing_error)?;
+
+    match &args.output {
+        Some(output) => input::write(output, payload),
+        None => {
+            println!("{} is signed by {}, {} bytes", args.input.display(), to_hex(&bundle.public_key), payload.len());
+            Ok(())
+        }
+    }
+}
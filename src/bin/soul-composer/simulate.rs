@@ -0,0 +1,121 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Args;
+
+use soulcomposer::prog::arm::emulator::{dry_run_with_progress, EmulatorConfig};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, parse_number, SourceOptions, StubOptions},
+    progress_line::ProgressLine,
+};
+
+/// What ProgramPage is asked to write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageData {
+    /// A fresh pseudo-random page, reproducible with `--seed`.
+    Random,
+    /// Hex bytes repeated over the page.
+    Pattern(Vec<u8>),
+}
+
+fn parse_page_data(value: &str) -> Result<PageData, String> {
+    if value.eq_ignore_ascii_case("random") {
+        return Ok(PageData::Random);
+    }
+
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return Err("expected \"random\" or an even number of hex digits".to_string());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).map_err(|err| err.to_string()))
+        .collect::<Result<_, _>>()
+        .map(PageData::Pattern)
+}
+
+#[derive(Debug, Args)]
+pub struct SimulateArgs {
+    /// Algorithm to run, an FLM or a stub written by `convert`, `-` reads stdin.
+    pub input: PathBuf,
+
+    /// Data for ProgramPage, "random" or hex bytes repeated over the page such as a55a1234.
+    #[arg(long, default_value = "a55a1234", value_parser = parse_page_data)]
+    pub page_data: PageData,
+
+    /// Seed for `--page-data random`, taken from the clock if not given.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Address the algorithm is loaded at.
+    #[arg(long, default_value = "0x20000000", value_parser = parse_number)]
+    pub ram_base: u32,
+
+    /// Instructions a routine may run before it counts as hung.
+    #[arg(long, default_value_t = 10_000_000)]
+    pub instruction_limit: usize,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+/// `len` bytes of xorshift64 output, plenty for exercising a programming routine.
+fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed.max(1);
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn check(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "ok",
+        Some(false) => "FAILED",
+        None => "not checked",
+    }
+}
+
+pub fn run(args: SimulateArgs) -> Result<(), CliError> {
+    let stub = load_stub(&args.input, None, &args.options, &args.source)?;
+
+    let page_data = match &args.page_data {
+        PageData::Pattern(pattern) => pattern.clone(),
+        PageData::Random => {
+            let seed = args.seed.unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |elapsed| elapsed.as_nanos() as u64)
+            });
+            println!("Page data seed {}", seed);
+            random_bytes(seed, stub.flash_page_size as usize)
+        }
+    };
+
+    let config = EmulatorConfig { ram_base: args.ram_base, page_data, instruction_limit: args.instruction_limit };
+    let report = dry_run_with_progress(&stub, &config, &mut ProgressLine::new())?;
+
+    println!("{:<14}{:>8}{:>14}{:>14}  Fault", "Routine", "Result", "Instructions", "~Cycles");
+    for routine in &report.routines {
+        let result = routine.return_value.map_or_else(|| "-".to_string(), |value| format!("{:#x}", value));
+        let fault = routine.fault.as_ref().map_or_else(String::new, ToString::to_string);
+        println!("{:<14}{:>8}{:>14}{:>14}  {}", routine.name, result, routine.instructions, routine.cycles, fault);
+    }
+    println!("\nSector erased   {}", check(report.erased_blank));
+    println!("Page programmed {}", check(report.flash_matches));
+
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(CliError::SimulationFailed)
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use soulcomposer::git_library::{GitLibrary, Resolve, SyncReport};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SyncAction {
+    /// Bring the checkout up to the remote.
+    Pull,
+    /// Store the given algorithms under --device, then pull, commit and push.
+    Push,
+}
+
+/// `Resolve` as a flag value.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ResolveArg {
+    Abort,
+    Ours,
+    Theirs,
+}
+
+impl From<ResolveArg> for Resolve {
+    fn from(resolve: ResolveArg) -> Self {
+        match resolve {
+            ResolveArg::Abort => Resolve::Abort,
+            ResolveArg::Ours => Resolve::Ours,
+            ResolveArg::Theirs => Resolve::Theirs,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SyncArgs {
+    #[arg(value_enum)]
+    pub action: SyncAction,
+
+    /// Algorithms to push as the stubs of --device, FLMs or stubs written by `convert`.
+    pub inputs: Vec<PathBuf>,
+
+    /// URL or path of the git repository holding the library.
+    #[arg(long)]
+    pub remote: Option<String>,
+
+    #[arg(long, default_value = "main")]
+    pub branch: String,
+
+    /// Local checkout of the library.
+    #[arg(long, default_value = "stub-library")]
+    pub checkout: PathBuf,
+
+    /// Devices to drop from the library on push.
+    #[arg(long)]
+    pub remove: Vec<String>,
+
+    /// Commit message of the push.
+    #[arg(short, long, default_value = "Update stubs")]
+    pub message: String,
+
+    /// Side that wins for devices changed both here and on the remote.
+    #[arg(long, value_enum, default_value_t = ResolveArg::Abort)]
+    pub resolve: ResolveArg,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+fn print_report(report: &SyncReport) {
+    let lines = [("+", &report.added), ("~", &report.updated), ("-", &report.removed)];
+    for (mark, devices) in lines.iter() {
+        for device in devices.iter() {
+            println!("{} {}", mark, device);
+        }
+    }
+}
+
+pub fn run(args: SyncArgs) -> Result<(), CliError> {
+    let remote = args.remote.clone().ok_or(CliError::RemoteRequired)?;
+    let library = GitLibrary::new(remote, args.branch.clone(), args.checkout.clone());
+
+    let report = match args.action {
+        SyncAction::Pull => library.pull(args.resolve.into())?,
+        SyncAction::Push => {
+            if !args.inputs.is_empty() {
+                let device = args.source.device.as_deref().ok_or(CliError::LibraryDeviceRequired)?;
+                let mut stubs = Vec::with_capacity(args.inputs.len());
+                for path in &args.inputs {
+                    stubs.push(load_stub(path, None, &args.options, &args.source)?.validate()?.into_inner());
+                }
+                library.stage(device, &stubs)?;
+            }
+            for device in &args.remove {
+                if !library.unstage(device)? {
+                    tracing::warn!(device = %device, "not in the library");
+                }
+            }
+            library.push(&args.message, args.resolve.into())?
+        }
+    };
+
+    print_report(&report);
+    Ok(())
+}
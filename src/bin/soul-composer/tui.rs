@@ -0,0 +1,261 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use soulcomposer::{
+    diagnostic::{Diagnostic, Severity},
+    prog::arm::flash_stub_gen::{ArmFlashStub, SectorRegion},
+    progress::NoProgress,
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{compose, SourceOptions, StubOptions},
+    pack::device_algorithms,
+    validate::lint,
+};
+
+/// Colours the sector map cycles through, one per region.
+const REGION_COLORS: [Color; 4] = [Color::Blue, Color::Green, Color::Magenta, Color::Cyan];
+
+#[derive(Debug, Args)]
+pub struct TuiArgs {
+    /// Algorithms to browse, or a CMSIS pack together with --device.
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+/// One algorithm in the list on the left.
+struct Entry {
+    title: String,
+    stub: Result<ArmFlashStub, String>,
+    findings: Vec<Diagnostic>,
+}
+
+impl Entry {
+    fn new(title: String, stub: Result<ArmFlashStub, CliError>) -> Self {
+        let findings = stub.as_ref().map(|stub| lint(stub, None, None)).unwrap_or_default();
+        Entry { title, stub: stub.map_err(|err| err.to_string()), findings }
+    }
+}
+
+struct App {
+    entries: Vec<Entry>,
+    list: ListState,
+    region: usize,
+}
+
+impl App {
+    fn selected(&self) -> &Entry {
+        &self.entries[self.list.selected().unwrap_or(0)]
+    }
+
+    fn select(&mut self, offset: isize) {
+        let count = self.entries.len() as isize;
+        let current = self.list.selected().unwrap_or(0) as isize;
+        self.list.select(Some((current + offset).rem_euclid(count) as usize));
+        self.region = 0;
+    }
+
+    fn select_region(&mut self, offset: isize) {
+        let count = self.selected().stub.as_ref().map_or(0, |stub| stub.sectors.len()) as isize;
+        if count > 0 {
+            self.region = (self.region as isize + offset).rem_euclid(count) as usize;
+        }
+    }
+}
+
+fn load(args: &TuiArgs) -> Result<Vec<Entry>, CliError> {
+    let mut entries = Vec::new();
+    for path in &args.inputs {
+        let is_pack = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pack"));
+        if is_pack {
+            let device = args.source.device.as_deref().ok_or(CliError::DeviceRequired)?;
+            for algorithm in device_algorithms(path, device, &args.options, &mut NoProgress)? {
+                entries.push(Entry::new(algorithm.file, algorithm.stub));
+            }
+        } else {
+            entries.push(Entry::new(path.display().to_string(), compose(path, None, &args.options, &args.source)));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Splits `width` cells between the regions by size, every region gets at least one.
+fn region_widths(regions: &[SectorRegion], width: u16) -> Vec<u16> {
+    let total: u64 = regions.iter().map(|region| u64::from(region.size) * u64::from(region.count)).sum();
+    let available = u64::from(width.saturating_sub(regions.len() as u16));
+    regions
+        .iter()
+        .map(|region| {
+            let bytes = u64::from(region.size) * u64::from(region.count);
+            1 + (bytes * available).checked_div(total).unwrap_or(0) as u16
+        })
+        .collect()
+}
+
+fn summary(stub: &ArmFlashStub) -> Vec<Line<'static>> {
+    let row = |label: &str, value: String| Line::from(vec![Span::styled(format!("{:<14}", label), Style::default().fg(Color::Yellow)), Span::raw(value)]);
+    vec![
+        row("Device", stub.description.clone()),
+        row("Region kind", format!("{:?}", stub.region_kind)),
+        row("Flash", format!("{:#010x}..{:#010x} ({} bytes)", stub.flash_start_addr, stub.flash_end_addr, stub.flash_size)),
+        row("Page size", format!("{} bytes", stub.flash_page_size)),
+        row("Timeouts", format!("program page {} ms, erase sector {} ms", stub.program_timeout, stub.erase_timeout)),
+        row("RAM required", format!("{} bytes, {} of them stack", stub.ram_required, stub.stack_size)),
+    ]
+}
+
+fn sector_map(stub: &ArmFlashStub, selected: usize, width: u16) -> Vec<Line<'static>> {
+    let mut bar = Vec::new();
+    for (index, cells) in region_widths(&stub.sectors, width).into_iter().enumerate() {
+        let mut style = Style::default().fg(REGION_COLORS[index % REGION_COLORS.len()]);
+        if index == selected {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let glyph = if index % 2 == 0 { "█" } else { "▓" };
+        bar.push(Span::styled(glyph.repeat(cells as usize), style));
+    }
+
+    let mut lines = vec![Line::from(bar), Line::default()];
+    for (index, region) in stub.sectors.iter().enumerate() {
+        let end = u64::from(region.address) + u64::from(region.size) * u64::from(region.count);
+        let marker = if index == selected { "> " } else { "  " };
+        let text = format!("{}{:#010x}..{:#010x}  {} x {} bytes", marker, region.address, end, region.count, region.size);
+        let style = Style::default().fg(REGION_COLORS[index % REGION_COLORS.len()]);
+        lines.push(Line::styled(text, if index == selected { style.add_modifier(Modifier::BOLD) } else { style }));
+    }
+    lines
+}
+
+fn entry_points(stub: &ArmFlashStub) -> Vec<Line<'static>> {
+    let entries = [
+        ("Init", stub.pc_init),
+        ("UnInit", stub.pc_uninit),
+        ("EraseChip", stub.pc_erase_all),
+        ("EraseSector", Some(stub.pc_erase_sector)),
+        ("ProgramPage", Some(stub.pc_program_page)),
+    ];
+    entries
+        .iter()
+        .map(|(name, pc)| match pc {
+            Some(pc) => Line::raw(format!("{:<14}{:#010x}", name, pc)),
+            None => Line::styled(format!("{:<14}-", name), Style::default().fg(Color::DarkGray)),
+        })
+        .collect()
+}
+
+fn findings(entry: &Entry) -> Vec<Line<'static>> {
+    if entry.findings.is_empty() {
+        return vec![Line::styled("No findings", Style::default().fg(Color::Green))];
+    }
+
+    entry
+        .findings
+        .iter()
+        .map(|finding| {
+            let color = match finding.severity {
+                Severity::Error => Color::Red,
+                Severity::Warning => Color::Yellow,
+            };
+            Line::from(vec![Span::styled(format!("{}: ", finding.severity), Style::default().fg(color)), Span::raw(finding.message.clone())])
+        })
+        .collect()
+}
+
+fn draw(frame: &mut Frame<'_>, app: &mut App) {
+    let [main, help] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [list_area, detail] = Layout::horizontal([Constraint::Percentage(25), Constraint::Min(0)]).areas(main);
+
+    let items: Vec<ListItem<'_>> = app
+        .entries
+        .iter()
+        .map(|entry| {
+            let style = if entry.stub.is_err() { Style::default().fg(Color::Red) } else { Style::default() };
+            ListItem::new(entry.title.clone()).style(style)
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Algorithms"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, list_area, &mut app.list);
+
+    let help_text = " ↑/↓ algorithm   ←/→ sector region   q quit";
+    frame.render_widget(Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray)), help);
+
+    let entry = app.selected();
+    let stub = match &entry.stub {
+        Ok(stub) => stub,
+        Err(err) => {
+            let block = Block::default().borders(Borders::ALL).title("Error");
+            let text = Paragraph::new(err.clone()).style(Style::default().fg(Color::Red)).wrap(Wrap { trim: true });
+            frame.render_widget(text.block(block), detail);
+            return;
+        }
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(8),
+            Constraint::Length(stub.sectors.len() as u16 + 4),
+            Constraint::Min(0),
+        ])
+        .split(detail);
+    let [entries_area, findings_area] = Layout::horizontal([Constraint::Length(30), Constraint::Min(0)]).areas(rows[2]);
+
+    let block = |title: &'static str| Block::default().borders(Borders::ALL).title(title);
+    frame.render_widget(Paragraph::new(summary(stub)).block(block("Summary")), rows[0]);
+    let map_width = inner_width(rows[1]);
+    frame.render_widget(Paragraph::new(sector_map(stub, app.region, map_width)).block(block("Sector map")), rows[1]);
+    frame.render_widget(Paragraph::new(entry_points(stub)).block(block("Entry points")), entries_area);
+    let findings = Paragraph::new(findings(entry)).wrap(Wrap { trim: true }).block(block("Findings"));
+    frame.render_widget(findings, findings_area);
+}
+
+fn inner_width(area: Rect) -> u16 {
+    area.width.saturating_sub(2)
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => app.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => app.select(1),
+            KeyCode::Left | KeyCode::Char('h') => app.select_region(-1),
+            KeyCode::Right | KeyCode::Char('l') => app.select_region(1),
+            _ => {}
+        }
+    }
+}
+
+pub fn run(args: TuiArgs) -> Result<(), CliError> {
+    let entries = load(&args)?;
+    let mut app = App { entries, list: ListState::default().with_selected(Some(0)), region: 0 };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result.map_err(CliError::io("terminal"))
+}
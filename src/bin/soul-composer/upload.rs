@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use soulcomposer::{
+    injector::{open_serial, FileKind, Uploader, DEFAULT_BAUD_RATE},
+    progress::Progress,
+    prog::{
+        arm::stub_group::ArmFlashStubGroup,
+        export::{export_model, export_validated, OutputFormat},
+    },
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{load_stub, SourceOptions, StubOptions},
+    input,
+};
+
+#[derive(Debug, Args)]
+pub struct UploadArgs {
+    /// Algorithms to upload, FLMs or stubs written by `convert`.
+    pub inputs: Vec<PathBuf>,
+
+    /// Manifests written by `merge` to upload after the algorithms.
+    #[arg(long)]
+    pub manifest: Vec<PathBuf>,
+
+    /// Serial port of the programmer, e.g. /dev/ttyACM0 or COM3.
+    #[arg(long)]
+    pub port: String,
+
+    #[arg(long, default_value_t = DEFAULT_BAUD_RATE)]
+    pub baud: u32,
+
+    /// Times a frame is sent before giving up.
+    #[arg(long, default_value_t = 3)]
+    pub attempts: u32,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn run(args: UploadArgs) -> Result<(), CliError> {
+    // Convert everything before opening the port, so a bad input doesn't leave half an upload.
+    let mut files = Vec::new();
+    for path in &args.inputs {
+        let stub = load_stub(path, None, &args.options, &args.source)?.validate()?;
+        files.push((FileKind::Stub, stub.name.clone(), export_validated(&stub, OutputFormat::Json)?));
+    }
+    for path in &args.manifest {
+        let group: ArmFlashStubGroup = serde_json::from_slice(&input::read(path)?)
+            .map_err(|err| CliError::StubParse { path: path.clone(), reason: err.to_string() })?;
+        files.push((FileKind::Manifest, group.name.clone(), export_model(&group, OutputFormat::Json)?));
+    }
+    if files.is_empty() {
+        return Err(CliError::NothingToUpload);
+    }
+
+    let mut uploader = Uploader::connect(open_serial(&args.port, args.baud)?)?.with_attempts(args.attempts);
+    for (kind, name, data) in &files {
+        let mut progress = |progress: Progress<'_>| {
+            tracing::info!(name = progress.current, sent = progress.completed, total = progress.total, "uploading");
+        };
+        uploader.upload(*kind, name, data, &mut progress)?;
+        println!("Uploaded {} {}, {} bytes", kind, name, data.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, ValueEnum};
+use goblin::elf::Elf;
+use serde::Serialize;
+
+use soulcomposer::{
+    diagnostic::{Diagnostic, Severity},
+    prog::arm::{build_attributes::BuildAttributes, core_isa::Core, flash_stub_gen::ArmFlashStub},
+};
+
+use crate::{
+    cli_error::CliError,
+    convert::{compose_with_diagnostics, SourceOptions, StrictnessLevel, StubOptions},
+    input,
+    watch::watch,
+};
+
+/// File extensions picked up when validating a directory.
+const EXTENSIONS: [&str; 4] = ["flm", "elf", "axf", "json"];
+
+/// How findings are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// `path: severity[code]: message` lines.
+    Human,
+    /// One JSON object per line, a diagnostic per finding and a summary at the end.
+    Json,
+}
+
+/// A line of `--message-format json` output.
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+enum Message<'a> {
+    Diagnostic {
+        file: String,
+        #[serde(flatten)]
+        finding: &'a Diagnostic,
+    },
+    Summary {
+        files: usize,
+        errors: usize,
+        warnings: usize,
+    },
+}
+
+impl Message<'_> {
+    fn print(&self) {
+        // Plain data with string keys, serializing it can't fail.
+        println!("{}", serde_json::to_string(self).unwrap_or_default());
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    /// Algorithm or stub to check, `-` for stdin, or a directory searched recursively for them.
+    pub input: PathBuf,
+
+    /// How findings are printed.
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
+
+    /// Also check the code against this core, e.g. "M0+" or "Cortex-M4".
+    #[arg(long, value_parser = parse_core)]
+    pub core: Option<Core>,
+
+    /// Check again whenever one of the files changes.
+    #[arg(long)]
+    pub watch: bool,
+
+    #[command(flatten)]
+    pub options: StubOptions,
+
+    #[command(flatten)]
+    pub source: SourceOptions,
+}
+
+pub fn parse_core(name: &str) -> Result<Core, String> {
+    Core::from_name(name).ok_or_else(|| format!("unknown core {}", name))
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), CliError> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .map_err(CliError::io(path))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(CliError::io(path))?;
+    entries.sort();
+    for entry in entries {
+        let extension = entry.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if extension.is_some_and(|extension| EXTENSIONS.contains(&extension.as_str())) {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a converted stub for problems that don't stop it from being composed.
+pub fn lint(stub: &ArmFlashStub, attributes: Option<&BuildAttributes>, core: Option<Core>) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+
+    if let Err(err) = stub.verify_crc32() {
+        findings.push(Diagnostic::error("crc-mismatch", err.to_string()));
+    }
+    if let Some(core) = core {
+        match stub.check_core(core, attributes) {
+            Ok(violations) => findings.extend(violations.iter().map(|violation| {
+                let finding = Diagnostic::error("core-violation", format!("not executable on {}, {}", core, violation.description));
+                match violation.offset {
+                    Some(offset) => finding.at(offset),
+                    None => finding,
+                }
+            })),
+            Err(err) => findings.push(Diagnostic::error("core-check-failed", err.to_string())),
+        }
+    }
+
+    if stub.stack_usage.is_none() {
+        findings.push(Diagnostic::warning(
+            "unbounded-stack",
+            format!("stack usage could not be bounded, {} bytes are reserved", stub.stack_size),
+        ));
+    }
+    if stub.program_timeout == 0 || stub.erase_timeout == 0 {
+        findings.push(Diagnostic::warning("zero-timeout", "a timeout of 0 ms is declared"));
+    }
+    for region in &stub.sectors {
+        if stub.flash_page_size == 0 || region.size % stub.flash_page_size != 0 {
+            let message = format!(
+                "{} byte sectors are not a multiple of the {} byte page size",
+                region.size, stub.flash_page_size
+            );
+            findings.push(Diagnostic::warning("sector-page-mismatch", message).at(region.address));
+        }
+    }
+    if stub.pc_init.is_none() {
+        findings.push(Diagnostic::warning("no-init", "no Init function, the flash is assumed ready to program"));
+    }
+    if stub.region_kind.requires_confirmation() {
+        findings.push(Diagnostic::warning(
+            "irreversible-region",
+            format!("programs {:?}, which can't be undone", stub.region_kind),
+        ));
+    }
+
+    findings
+}
+
+fn validate_file(path: &Path, args: &ValidateArgs) -> Result<Vec<Diagnostic>, CliError> {
+    let data = input::read(path)?;
+    if path.extension().and_then(|extension| extension.to_str()) == Some("json") {
+        let stub: ArmFlashStub = serde_json::from_slice(&data)
+            .map_err(|err| CliError::StubParse { path: path.to_path_buf(), reason: err.to_string() })?;
+        return Ok(lint(&stub, None, args.core));
+    }
+
+    // Strict composition stops at the first warning, report them all and fail the run instead.
+    let mut options = args.options.clone();
+    if options.strictness == StrictnessLevel::Strict {
+        options.strictness = StrictnessLevel::Normal;
+    }
+    let (stub, mut findings) = compose_with_diagnostics(path, None, &options, &args.source)?;
+    let attributes = Elf::parse(&data).ok().and_then(|elf| BuildAttributes::from_elf(&elf, &data));
+    findings.extend(lint(&stub, attributes.as_ref(), args.core));
+    Ok(findings)
+}
+
+pub fn run(args: ValidateArgs) -> Result<(), CliError> {
+    if !args.watch {
+        return validate(&args);
+    }
+    if input::is_stdio(&args.input) {
+        return Err(CliError::WatchStdin);
+    }
+
+    let files = || {
+        let mut files = Vec::new();
+        // Unreadable directories are reported by the run itself.
+        let _ = collect_files(&args.input, &mut files);
+        files.extend(args.source.descriptor.iter().chain(args.source.pdsc.iter()).cloned());
+        files
+    };
+    watch(files, || validate(&args))
+}
+
+/// Lenient turns every finding into a warning, strict into an error.
+fn with_strictness(mut finding: Diagnostic, strictness: StrictnessLevel) -> Diagnostic {
+    finding.severity = match strictness {
+        StrictnessLevel::Lenient => Severity::Warning,
+        StrictnessLevel::Strict => Severity::Error,
+        StrictnessLevel::Normal => finding.severity,
+    };
+    finding
+}
+
+fn location(path: &Path, finding: &Diagnostic) -> String {
+    match finding.offset {
+        Some(offset) => format!("{}@{:#010x}", path.display(), offset),
+        None => path.display().to_string(),
+    }
+}
+
+fn validate(args: &ValidateArgs) -> Result<(), CliError> {
+    let mut files = Vec::new();
+    collect_files(&args.input, &mut files)?;
+
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &files {
+        let findings = match validate_file(path, args) {
+            Ok(findings) => findings.into_iter().map(|finding| with_strictness(finding, args.options.strictness)).collect(),
+            // Anything that stops the conversion fails at every strictness.
+            Err(err) => {
+                let finding = Diagnostic::error("conversion-failed", err.to_string());
+                vec![match err.code() {
+                    Some(code) => finding.with_id(code),
+                    None => finding,
+                }]
+            }
+        };
+
+        for finding in &findings {
+            match finding.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            match args.message_format {
+                MessageFormat::Human => println!("{}: {}", location(path, finding), finding),
+                MessageFormat::Json => Message::Diagnostic { file: path.display().to_string(), finding }.print(),
+            }
+        }
+    }
+
+    match args.message_format {
+        MessageFormat::Human => println!("{} files checked, {} errors, {} warnings", files.len(), errors, warnings),
+        MessageFormat::Json => Message::Summary { files: files.len(), errors, warnings }.print(),
+    }
+    if errors > 0 {
+        return Err(CliError::ValidationFailed(errors));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::cli_error::CliError;
+
+/// How often the watched files are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Modification time and size of each path, `None` while a file is missing.
+fn fingerprint(paths: &[PathBuf]) -> Vec<(PathBuf, Option<(SystemTime, u64)>)> {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok();
+            let stamp = metadata.and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+            (path.clone(), stamp)
+        })
+        .collect()
+}
+
+/// Runs `task`, then runs it again whenever one of the files from `paths` changes, until interrupted.
+///
+/// Files are polled rather than watched through OS events, toolchains often replace the output
+/// through a rename, which event based watchers tend to lose track of. A change only triggers once
+/// the file has stopped changing for one interval, so half-written files are not picked up.
+pub fn watch<P, T>(paths: P, mut task: T) -> Result<(), CliError>
+where
+    P: Fn() -> Vec<PathBuf>,
+    T: FnMut() -> Result<(), CliError>,
+{
+    let mut last = fingerprint(&paths());
+    loop {
+        if let Err(err) = task() {
+            println!("error: {}", err);
+        }
+        println!("Watching for changes, press Ctrl-C to stop");
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = fingerprint(&paths());
+            if current == last {
+                continue;
+            }
+
+            // Wait for the writer to finish.
+            let mut settled = current;
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let again = fingerprint(&paths());
+                if again == settled {
+                    break;
+                }
+                settled = again;
+            }
+
+            for (path, _) in settled.iter().filter(|entry| !last.contains(entry)) {
+                println!("\nChanged: {}", path.display());
+            }
+            last = settled;
+            break;
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::{path::Path, time::Duration};
+
+use serde::Serialize;
+
+/// Time a receiver gets to answer, notices never hold up a run for longer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An algorithm a batch failed on.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFailure {
+    pub file: String,
+    pub error: String,
+}
+
+impl BatchFailure {
+    pub fn new(path: &Path, error: &str) -> Self {
+        BatchFailure { file: path.display().to_string(), error: error.to_string() }
+    }
+}
+
+/// What happened, tagged with `event`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum Notice {
+    /// The server converted an upload or the algorithms of a cached pack.
+    #[cfg(feature = "serve")]
+    #[serde(rename = "conversion.completed")]
+    ConversionCompleted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+        stubs: Vec<String>,
+        /// Findings of the conversion, see the response for them.
+        diagnostics: usize,
+    },
+    /// The server refused an upload, with the stable code `validate` reports.
+    #[cfg(feature = "serve")]
+    #[serde(rename = "conversion.failed")]
+    ConversionFailed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+        error: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<String>,
+    },
+    /// `batch` finished, with the algorithms that failed to convert or validate.
+    #[serde(rename = "batch.completed")]
+    BatchCompleted { pattern: String, converted: usize, failures: Vec<BatchFailure> },
+}
+
+impl Notice {
+    /// One line for people, Slack and Teams show it as the message.
+    fn text(&self) -> String {
+        #[cfg(feature = "serve")]
+        let on = |device: &Option<String>| device.as_ref().map(|device| format!(" for {}", device)).unwrap_or_default();
+        match self {
+            #[cfg(feature = "serve")]
+            Notice::ConversionCompleted { device, stubs, diagnostics } => {
+                format!("Converted {}{} with {} diagnostics", stubs.join(", "), on(device), diagnostics)
+            }
+            #[cfg(feature = "serve")]
+            Notice::ConversionFailed { device, error, .. } => format!("Conversion{} failed: {}", on(device), error),
+            Notice::BatchCompleted { pattern, converted, failures } if failures.is_empty() => {
+                format!("Batch {}: {} converted", pattern, converted)
+            }
+            Notice::BatchCompleted { pattern, converted, failures } => {
+                let files: Vec<&str> = failures.iter().map(|failure| failure.file.as_str()).collect();
+                format!("Batch {}: {} converted, {} failed: {}", pattern, converted, failures.len(), files.join(", "))
+            }
+        }
+    }
+}
+
+/// The JSON body of a notice.
+#[derive(Serialize)]
+struct Payload<'a> {
+    text: String,
+    /// The subcommand that sent it, `serve` or `batch`.
+    source: &'a str,
+    #[serde(flatten)]
+    notice: &'a Notice,
+}
+
+/// URLs that are POSTed a JSON notice of each conversion.
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    urls: Vec<String>,
+    source: &'static str,
+}
+
+impl Webhooks {
+    pub fn new(urls: Vec<String>, source: &'static str) -> Self {
+        Webhooks { urls, source }
+    }
+
+    /// Posts `notice` to every URL. A receiver that fails is logged, the run carries on.
+    pub fn notify(&self, notice: &Notice) {
+        if self.urls.is_empty() {
+            return;
+        }
+        let payload = Payload { text: notice.text(), source: self.source, notice };
+        let body = match serde_json::to_string(&payload) {
+            Ok(body) => body,
+            Err(err) => return tracing::warn!("Dropping a webhook notice, {}", err),
+        };
+
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        for url in &self.urls {
+            match agent.post(url).set("Content-Type", "application/json").send_string(&body) {
+                Ok(_) => tracing::debug!(url = %url, "notified"),
+                Err(err) => tracing::warn!("Webhook {} failed, {}", url, err),
+            }
+        }
+    }
+
+    /// Posts `notice` from a thread of its own, so requests don't wait for the receivers.
+    #[cfg(feature = "serve")]
+    pub fn notify_in_background(&self, notice: Notice) {
+        if self.urls.is_empty() {
+            return;
+        }
+        let webhooks = self.clone();
+        std::thread::spawn(move || webhooks.notify(&notice));
+    }
+}
@@ -0,0 +1,48 @@
+pub use goblin::elf::Elf;
+
+use crate::{
+    diagnostic::Diagnostic,
+    prog::arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub, parse_options::ParseOptions},
+};
+
+/// Everything `compose_stub` needs besides the ELF itself.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ComposeOptions {
+    /// Stub name, usually the FLM file name without extension.
+    pub name: String,
+    /// Mark the stub as the default algorithm of the target.
+    pub default: bool,
+    /// RAM available on the target, 0 fills in the computed requirement.
+    pub ram_size: u32,
+    pub parse: ParseOptions,
+}
+
+impl ComposeOptions {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ..Default::default() }
+    }
+}
+
+/// Composes the stub for a CMSIS flash algorithm: reads the `FlashDevice` descriptor, resolves
+/// the entry point symbols, lays out the code and data blobs and plans the RAM they need.
+///
+/// `elf` must have been parsed from `buffer`, e.g. with `Elf::parse(&buffer)`. Problems that don't
+/// stop the conversion, like suspicious timeouts or a region kind guessed from the name, come back
+/// next to the stub.
+pub fn compose_stub(
+    elf: &Elf,
+    buffer: &[u8],
+    options: &ComposeOptions,
+) -> Result<(ArmFlashStub, Vec<Diagnostic>), ArmError> {
+    let mut diagnostics = Vec::new();
+    let stub = ArmFlashStub::from_parsed_elf(
+        elf,
+        buffer,
+        options.name.clone(),
+        options.default,
+        options.ram_size,
+        &options.parse,
+        &mut diagnostics,
+    )?;
+    Ok((stub, diagnostics))
+}
@@ -0,0 +1,3 @@
+//! One call from a parsed algorithm to a finished stub, per architecture.
+
+pub mod arm;
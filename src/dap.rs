@@ -0,0 +1,724 @@
+//! A small CMSIS-DAP driver, enough to run a stub on a Cortex-M target without probe-rs: SWD
+//! through any DAPLink, J-Link OB in CMSIS-DAP mode or similar probe, over USB bulk endpoints
+//! (CMSIS-DAP v2) or HID reports (v1).
+//!
+//! `CmsisDap` speaks the DAP commands to a `DapTransport` and does the ADIv5 part itself: it
+//! powers up the debug port, reads and writes memory through the MEM-AP and drives the core
+//! through its debug registers. `self_test` is the counterpart of `probe::self_test`: it loads the
+//! stub into RAM, calls Init, EraseSector, ProgramPage and UnInit with LR pointing at a `bkpt`,
+//! and reads the scratch sector back.
+//!
+//! Only SWD and ADIv5 MEM-APs are supported, JTAG and ADIv6 targets need probe-rs.
+
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::prog::{
+    arm::{
+        arm_error::ArmError,
+        flash_stub_gen::{INIT_FUNCTION_ERASE, INIT_FUNCTION_PROGRAM},
+        ram_layout::{RunLayout, TRAMPOLINE},
+        validated::ValidatedArmFlashStub,
+    },
+    flash_algorithm::FlashAlgorithm,
+};
+
+/// SWD clock used unless `CmsisDap::connect` is given another.
+pub const DEFAULT_CLOCK: u32 = 1_000_000;
+
+/// Packet size of CMSIS-DAP v1 probes, and of v2 probes that don't report one.
+const DEFAULT_PACKET_SIZE: usize = 64;
+
+/// Largest response read from a v2 probe, more than any packet size it may report.
+const MAX_RESPONSE: usize = 4096;
+
+/// Time a probe gets to answer a command.
+const USB_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time Init and UnInit get to return, FlashDevice only has timeouts for the other routines.
+const INIT_TIMEOUT_MS: u32 = 2000;
+
+/// The MEM-AP only increments TAR within a 1 KiB block.
+const AUTO_INCREMENT_BLOCK: u32 = 1024;
+
+// DAP commands.
+const DAP_INFO: u8 = 0x00;
+const DAP_CONNECT: u8 = 0x02;
+const DAP_DISCONNECT: u8 = 0x03;
+const DAP_TRANSFER_CONFIGURE: u8 = 0x04;
+const DAP_TRANSFER: u8 = 0x05;
+const DAP_TRANSFER_BLOCK: u8 = 0x06;
+const DAP_SWJ_CLOCK: u8 = 0x11;
+const DAP_SWJ_SEQUENCE: u8 = 0x12;
+const DAP_SWD_CONFIGURE: u8 = 0x13;
+
+const INFO_CAPABILITIES: u8 = 0xF0;
+const INFO_PACKET_SIZE: u8 = 0xFF;
+const PORT_SWD: u8 = 0x01;
+
+// Transfer requests: APnDP, RnW and A[3:2].
+const AP: u8 = 0x01;
+const READ: u8 = 0x02;
+const ACK_OK: u8 = 0x01;
+
+// Debug port registers.
+const DP_IDCODE: u8 = 0x00;
+const DP_ABORT: u8 = 0x00;
+const DP_CTRL_STAT: u8 = 0x04;
+const DP_SELECT: u8 = 0x08;
+
+/// Clears every sticky error flag.
+const ABORT_CLEAR: u32 = 0x1E;
+const CSYSPWRUPREQ: u32 = 1 << 30;
+const CDBGPWRUPREQ: u32 = 1 << 28;
+const CSYSPWRUPACK: u32 = 1 << 31;
+const CDBGPWRUPACK: u32 = 1 << 29;
+
+// MEM-AP registers.
+const AP_CSW: u8 = 0x00;
+const AP_TAR: u8 = 0x04;
+const AP_DRW: u8 = 0x0C;
+
+/// 32-bit accesses with TAR incrementing, privileged data accesses as the debugger.
+const CSW_WORD_INCREMENT: u32 = 0x2300_0052;
+
+// Cortex-M debug registers.
+const AIRCR: u32 = 0xE000_ED0C;
+const DHCSR: u32 = 0xE000_EDF0;
+const DCRSR: u32 = 0xE000_EDF4;
+const DCRDR: u32 = 0xE000_EDF8;
+const DEMCR: u32 = 0xE000_EDFC;
+
+const DBGKEY: u32 = 0xA05F_0000;
+const C_DEBUGEN: u32 = 1 << 0;
+const C_HALT: u32 = 1 << 1;
+const S_REGRDY: u32 = 1 << 16;
+const S_HALT: u32 = 1 << 17;
+const DCRSR_WRITE: u32 = 1 << 16;
+const VC_CORERESET: u32 = 1 << 0;
+const SYSRESETREQ: u32 = 0x05FA_0004;
+
+/// Core registers as DCRSR numbers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreRegister {
+    /// r0 to r12.
+    R(u8),
+    Sp,
+    Lr,
+    Pc,
+    Xpsr,
+}
+
+impl CoreRegister {
+    fn number(self) -> u32 {
+        match self {
+            CoreRegister::R(index) => u32::from(index.min(12)),
+            CoreRegister::Sp => 13,
+            CoreRegister::Lr => 14,
+            CoreRegister::Pc => 15,
+            CoreRegister::Xpsr => 16,
+        }
+    }
+}
+
+/// Thumb state, the only one a Cortex-M runs in.
+const XPSR_THUMB: u32 = 1 << 24;
+
+#[derive(Debug, Error)]
+pub enum DapError {
+    #[error("USB error, {0}")]
+    Usb(String),
+
+    #[error("No CMSIS-DAP probe found")]
+    NoProbe,
+
+    #[error("The probe answered command {command:#04x} with {response:02x?}")]
+    BadResponse { command: u8, response: Vec<u8> },
+
+    #[error("The probe doesn't support SWD")]
+    NoSwd,
+
+    #[error("SWD transfer failed with ACK {0:#x}")]
+    Transfer(u8),
+
+    #[error("The debug port didn't power up")]
+    PowerUp,
+
+    #[error("The core didn't halt within {0:?}")]
+    HaltTimeout(Duration),
+
+    #[error("{routine} didn't return within {timeout} ms")]
+    RoutineTimeout { routine: &'static str, timeout: u32 },
+
+    #[error("{routine} halted at {pc:#010x} instead of returning")]
+    RoutineHalted { routine: &'static str, pc: u32 },
+
+    #[error("{routine} failed with {code:#x}")]
+    Routine { routine: &'static str, code: u32 },
+
+    #[error("Memory accesses must be word aligned, got {length} bytes at {address:#010x}")]
+    Unaligned { address: u32, length: usize },
+
+    #[error("Scratch address {0:#010x} isn't in the algorithm's flash")]
+    ScratchOutOfRange(u32),
+
+    #[error("Read back {found:#04x} at {address:#010x} after programming, expected {expected:#04x}")]
+    Mismatch { address: u32, expected: u8, found: u8 },
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+}
+
+impl DapError {
+    /// Whether the probe itself failed, not the target or the stub.
+    pub fn is_link(&self) -> bool {
+        matches!(self, DapError::Usb(_) | DapError::NoProbe | DapError::BadResponse { .. } | DapError::NoSwd)
+    }
+}
+
+/// Carries DAP command and response packets.
+pub trait DapTransport {
+    /// Sends one command and returns its response.
+    fn exchange(&mut self, command: &[u8]) -> Result<Vec<u8>, DapError>;
+}
+
+/// Talks CMSIS-DAP to a probe, see the module documentation.
+#[derive(Debug)]
+pub struct CmsisDap<T> {
+    transport: T,
+    packet_size: usize,
+}
+
+impl<T: DapTransport> CmsisDap<T> {
+    /// Asks the probe for its packet size, `connect` before anything else.
+    pub fn new(transport: T) -> Result<Self, DapError> {
+        let mut dap = CmsisDap { transport, packet_size: DEFAULT_PACKET_SIZE };
+        let size = dap.info(INFO_PACKET_SIZE)?;
+        if let [low, high] = size[..] {
+            dap.packet_size = usize::from(u16::from_le_bytes([low, high])).max(16);
+        }
+        Ok(dap)
+    }
+
+    pub fn packet_size(&self) -> usize {
+        self.packet_size
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Sends `command`, checking the response echoes it.
+    fn command(&mut self, command: &[u8]) -> Result<Vec<u8>, DapError> {
+        let response = self.transport.exchange(command)?;
+        match response.first() {
+            Some(&id) if id == command[0] => Ok(response),
+            _ => Err(DapError::BadResponse { command: command[0], response }),
+        }
+    }
+
+    /// Sends a command whose response is a status byte, 0 for success.
+    fn status_command(&mut self, command: &[u8]) -> Result<(), DapError> {
+        let response = self.command(command)?;
+        match response.get(1) {
+            Some(0) => Ok(()),
+            _ => Err(DapError::BadResponse { command: command[0], response }),
+        }
+    }
+
+    fn info(&mut self, id: u8) -> Result<Vec<u8>, DapError> {
+        let response = self.command(&[DAP_INFO, id])?;
+        let length = usize::from(*response.get(1).unwrap_or(&0));
+        Ok(response.get(2..2 + length).unwrap_or_default().to_vec())
+    }
+
+    /// Switches the probe to SWD at `clock` Hz, powers up the debug port and selects the MEM-AP
+    /// `ap`. Returns the IDCODE of the debug port.
+    pub fn connect(&mut self, clock: u32, ap: u8) -> Result<u32, DapError> {
+        if self.info(INFO_CAPABILITIES)?.first().is_some_and(|capabilities| capabilities & 0x01 == 0) {
+            return Err(DapError::NoSwd);
+        }
+        let response = self.command(&[DAP_CONNECT, PORT_SWD])?;
+        if response.get(1) != Some(&PORT_SWD) {
+            return Err(DapError::NoSwd);
+        }
+
+        let mut command = vec![DAP_SWJ_CLOCK];
+        command.extend_from_slice(&clock.to_le_bytes());
+        self.status_command(&command)?;
+        // No idle cycles, 80 retries on WAIT, none on value mismatch.
+        self.status_command(&[DAP_TRANSFER_CONFIGURE, 0, 80, 0, 0, 0])?;
+        self.status_command(&[DAP_SWD_CONFIGURE, 0])?;
+
+        // Line reset, the JTAG-to-SWD switch, line reset and idle cycles.
+        self.swj_sequence(51, &[0xFF; 7])?;
+        self.swj_sequence(16, &[0x9E, 0xE7])?;
+        self.swj_sequence(51, &[0xFF; 7])?;
+        self.swj_sequence(8, &[0x00])?;
+        let idcode = self.transfer(&[(READ | DP_IDCODE, 0)])?[0];
+
+        self.transfer(&[(DP_ABORT, ABORT_CLEAR), (DP_SELECT, 0), (DP_CTRL_STAT, CSYSPWRUPREQ | CDBGPWRUPREQ)])?;
+        let started = Instant::now();
+        while self.transfer(&[(READ | DP_CTRL_STAT, 0)])?[0] & (CSYSPWRUPACK | CDBGPWRUPACK) != CSYSPWRUPACK | CDBGPWRUPACK {
+            if started.elapsed() > USB_TIMEOUT {
+                return Err(DapError::PowerUp);
+            }
+        }
+        self.transfer(&[(DP_SELECT, u32::from(ap) << 24), (AP | AP_CSW, CSW_WORD_INCREMENT)])?;
+
+        Ok(idcode)
+    }
+
+    /// Releases the target.
+    pub fn disconnect(&mut self) -> Result<(), DapError> {
+        self.status_command(&[DAP_DISCONNECT])
+    }
+
+    fn swj_sequence(&mut self, bits: u8, data: &[u8]) -> Result<(), DapError> {
+        let mut command = vec![DAP_SWJ_SEQUENCE, bits];
+        command.extend_from_slice(data);
+        self.status_command(&command)
+    }
+
+    /// Runs register transfers, a request and the value to write for each, and returns what the
+    /// reads among them returned.
+    fn transfer(&mut self, requests: &[(u8, u32)]) -> Result<Vec<u32>, DapError> {
+        let mut command = vec![DAP_TRANSFER, 0, requests.len() as u8];
+        for (request, value) in requests {
+            command.push(*request);
+            if request & READ == 0 {
+                command.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let response = self.command(&command)?;
+        let ack = *response.get(2).unwrap_or(&0);
+        if response.get(1) != Some(&(requests.len() as u8)) || ack != ACK_OK {
+            return Err(self.transfer_failed(ack));
+        }
+
+        let reads = requests.iter().filter(|(request, _)| request & READ != 0).count();
+        words(response.get(3..3 + reads * 4), DAP_TRANSFER, &response)
+    }
+
+    /// Clears the sticky errors after a failed transfer, so the next one can go through.
+    fn transfer_failed(&mut self, ack: u8) -> DapError {
+        let mut command = vec![DAP_TRANSFER, 0, 1, DP_ABORT];
+        command.extend_from_slice(&ABORT_CLEAR.to_le_bytes());
+        let _ = self.command(&command);
+        DapError::Transfer(ack)
+    }
+
+    /// Words per `DAP_TransferBlock` that fit a packet.
+    fn block_words(&self, read: bool) -> usize {
+        match read {
+            true => (self.packet_size - 4) / 4,
+            false => (self.packet_size - 5) / 4,
+        }
+    }
+
+    /// Word aligned chunks of `length` bytes at `address`, split where TAR stops incrementing
+    /// and where a packet is full.
+    fn chunks(&self, address: u32, length: usize, read: bool) -> Result<Vec<(u32, usize)>, DapError> {
+        if !address.is_multiple_of(4) || !length.is_multiple_of(4) {
+            return Err(DapError::Unaligned { address, length });
+        }
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < length {
+            let start = address + offset as u32;
+            let block_left = (AUTO_INCREMENT_BLOCK - start % AUTO_INCREMENT_BLOCK) as usize;
+            let size = (length - offset).min(block_left).min(self.block_words(read) * 4);
+            chunks.push((start, size));
+            offset += size;
+        }
+        Ok(chunks)
+    }
+
+    /// Writes `data`, a whole number of words, at the word aligned `address`.
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) -> Result<(), DapError> {
+        let mut offset = 0;
+        for (start, size) in self.chunks(address, data.len(), false)? {
+            self.transfer(&[(AP | AP_TAR, start)])?;
+            let count = (size / 4) as u16;
+            let mut command = vec![DAP_TRANSFER_BLOCK, 0];
+            command.extend_from_slice(&count.to_le_bytes());
+            command.push(AP | AP_DRW);
+            command.extend_from_slice(&data[offset..offset + size]);
+            self.block(&command, count)?;
+            offset += size;
+        }
+        Ok(())
+    }
+
+    /// Reads `data.len()`, a whole number of words, from the word aligned `address`.
+    pub fn read_memory(&mut self, address: u32, data: &mut [u8]) -> Result<(), DapError> {
+        let mut offset = 0;
+        for (start, size) in self.chunks(address, data.len(), true)? {
+            self.transfer(&[(AP | AP_TAR, start)])?;
+            let count = (size / 4) as u16;
+            let mut command = vec![DAP_TRANSFER_BLOCK, 0];
+            command.extend_from_slice(&count.to_le_bytes());
+            command.push(AP | READ | AP_DRW);
+            let response = self.block(&command, count)?;
+            let read = response.get(4..4 + size).ok_or_else(|| DapError::BadResponse { command: DAP_TRANSFER_BLOCK, response: response.clone() })?;
+            data[offset..offset + size].copy_from_slice(read);
+            offset += size;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, command: &[u8], count: u16) -> Result<Vec<u8>, DapError> {
+        let response = self.command(command)?;
+        let ack = *response.get(3).unwrap_or(&0);
+        if response.get(1..3) != Some(&count.to_le_bytes()[..]) || ack != ACK_OK {
+            return Err(self.transfer_failed(ack));
+        }
+        Ok(response)
+    }
+
+    pub fn read_word(&mut self, address: u32) -> Result<u32, DapError> {
+        Ok(self.transfer(&[(AP | AP_TAR, address), (AP | READ | AP_DRW, 0)])?[0])
+    }
+
+    pub fn write_word(&mut self, address: u32, value: u32) -> Result<(), DapError> {
+        self.transfer(&[(AP | AP_TAR, address), (AP | AP_DRW, value)]).map(drop)
+    }
+
+    /// Waits up to `timeout` for the core to halt.
+    pub fn wait_halted(&mut self, timeout: Duration) -> Result<(), DapError> {
+        let started = Instant::now();
+        while self.read_word(DHCSR)? & S_HALT == 0 {
+            if started.elapsed() > timeout {
+                return Err(DapError::HaltTimeout(timeout));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn halt(&mut self) -> Result<(), DapError> {
+        self.write_word(DHCSR, DBGKEY | C_HALT | C_DEBUGEN)?;
+        self.wait_halted(USB_TIMEOUT)
+    }
+
+    /// Resets the target and halts it before the first instruction, so nothing the firmware set
+    /// up gets in the way of the stub.
+    pub fn reset_and_halt(&mut self) -> Result<(), DapError> {
+        self.halt()?;
+        let demcr = self.read_word(DEMCR)?;
+        self.write_word(DEMCR, demcr | VC_CORERESET)?;
+        // Some targets drop the SWD transfer that resets them.
+        let _ = self.write_word(AIRCR, SYSRESETREQ);
+        self.wait_halted(USB_TIMEOUT)?;
+        self.write_word(DEMCR, demcr & !VC_CORERESET)
+    }
+
+    /// Resumes the core, which must be halted.
+    pub fn run(&mut self) -> Result<(), DapError> {
+        self.write_word(DHCSR, DBGKEY | C_DEBUGEN)
+    }
+
+    fn wait_register(&mut self) -> Result<(), DapError> {
+        let started = Instant::now();
+        while self.read_word(DHCSR)? & S_REGRDY == 0 {
+            if started.elapsed() > USB_TIMEOUT {
+                return Err(DapError::HaltTimeout(USB_TIMEOUT));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_register(&mut self, register: CoreRegister) -> Result<u32, DapError> {
+        self.write_word(DCRSR, register.number())?;
+        self.wait_register()?;
+        self.read_word(DCRDR)
+    }
+
+    pub fn write_register(&mut self, register: CoreRegister, value: u32) -> Result<(), DapError> {
+        self.write_word(DCRDR, value)?;
+        self.write_word(DCRSR, DCRSR_WRITE | register.number())?;
+        self.wait_register()
+    }
+}
+
+fn words(bytes: Option<&[u8]>, command: u8, response: &[u8]) -> Result<Vec<u32>, DapError> {
+    let bytes = bytes.ok_or_else(|| DapError::BadResponse { command, response: response.to_vec() })?;
+    Ok(bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect())
+}
+
+/// RAM the stub runs from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DapTarget {
+    pub ram_start: u32,
+    pub ram_size: u32,
+}
+
+/// What `self_test` did on the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DapSelfTestReport {
+    /// The scratch sector.
+    pub sector: Range<u32>,
+    pub erase_time: Duration,
+    pub program_time: Duration,
+    /// From loading the algorithm to reading the sector back.
+    pub total_time: Duration,
+}
+
+/// Calls the routine at `pc`, an offset into the blob, and waits for it to return to the
+/// trampoline.
+fn call<T: DapTransport>(
+    dap: &mut CmsisDap<T>,
+    layout: &RunLayout,
+    routine: &'static str,
+    pc: u32,
+    args: [u32; 3],
+    timeout: u32,
+) -> Result<(), DapError> {
+    for (index, arg) in args.iter().enumerate() {
+        dap.write_register(CoreRegister::R(index as u8), *arg)?;
+    }
+    if let Some(static_base) = layout.static_base {
+        dap.write_register(CoreRegister::R(9), static_base)?;
+    }
+    dap.write_register(CoreRegister::Sp, layout.stack_top)?;
+    dap.write_register(CoreRegister::Lr, layout.trampoline | 1)?;
+    dap.write_register(CoreRegister::Pc, (layout.base + pc) & !1)?;
+    dap.write_register(CoreRegister::Xpsr, XPSR_THUMB)?;
+    dap.run()?;
+
+    match dap.wait_halted(Duration::from_millis(timeout.into())) {
+        Err(DapError::HaltTimeout(_)) => {
+            let _ = dap.halt();
+            return Err(DapError::RoutineTimeout { routine, timeout });
+        }
+        result => result?,
+    }
+    let halted_at = dap.read_register(CoreRegister::Pc)?;
+    if halted_at & !1 != layout.trampoline {
+        return Err(DapError::RoutineHalted { routine, pc: halted_at });
+    }
+    match dap.read_register(CoreRegister::R(0))? {
+        0 => Ok(()),
+        code => Err(DapError::Routine { routine, code }),
+    }
+}
+
+/// Erases and programs one sector through the stub and reads it back, like `probe::self_test`.
+/// The target is reset first.
+///
+/// The sector holding `scratch`, or the last sector of the flash, is overwritten: pick one
+/// nothing on the target needs.
+pub fn self_test<T: DapTransport>(
+    dap: &mut CmsisDap<T>,
+    stub: &ValidatedArmFlashStub,
+    target: &DapTarget,
+    scratch: Option<u32>,
+) -> Result<DapSelfTestReport, DapError> {
+    let sector = stub.scratch_sector(scratch).ok_or(DapError::ScratchOutOfRange(scratch.unwrap_or(stub.flash_start_addr)))?;
+    let sector = sector.start as u32..sector.end as u32;
+    let data = stub.test_pattern((sector.end - sector.start) as usize);
+
+    let mut blob = stub.blob()?;
+    blob.resize((blob.len() + 3) & !3, 0);
+    let layout = RunLayout::plan(stub, target.ram_start, blob.len() as u32, target.ram_size)?;
+    let started = Instant::now();
+
+    dap.reset_and_halt()?;
+    dap.write_memory(layout.base, &blob)?;
+    dap.write_memory(layout.trampoline, &TRAMPOLINE)?;
+
+    let init = &stub.init_parameters;
+    let init_args = |function: u32| [init.address, init.clock, function];
+    let erase_started = Instant::now();
+    if let Some(pc) = stub.pc_init {
+        call(dap, &layout, "Init", pc, init_args(INIT_FUNCTION_ERASE), INIT_TIMEOUT_MS)?;
+    }
+    call(dap, &layout, "EraseSector", stub.pc_erase_sector, [stub.program_address(sector.start), 0, 0], stub.erase_timeout.max(INIT_TIMEOUT_MS))?;
+    if let Some(pc) = stub.pc_uninit {
+        call(dap, &layout, "UnInit", pc, [INIT_FUNCTION_ERASE, 0, 0], INIT_TIMEOUT_MS)?;
+    }
+    let erase_time = erase_started.elapsed();
+
+    let program_started = Instant::now();
+    if let Some(pc) = stub.pc_init {
+        call(dap, &layout, "Init", pc, init_args(INIT_FUNCTION_PROGRAM), INIT_TIMEOUT_MS)?;
+    }
+    let page_size = stub.flash_page_size.max(4) as usize;
+    for (index, page) in data.chunks(page_size).enumerate() {
+        let address = sector.start + (index * page_size) as u32;
+        let mut page = page.to_vec();
+        page.resize((page.len() + 3) & !3, stub.erased_byte_value);
+        dap.write_memory(layout.buffer, &page)?;
+        let args = [stub.program_address(address), page.len() as u32, layout.buffer];
+        call(dap, &layout, "ProgramPage", stub.pc_program_page, args, stub.program_timeout.max(INIT_TIMEOUT_MS))?;
+    }
+    if let Some(pc) = stub.pc_uninit {
+        call(dap, &layout, "UnInit", pc, [INIT_FUNCTION_PROGRAM, 0, 0], INIT_TIMEOUT_MS)?;
+    }
+    let program_time = program_started.elapsed();
+
+    let mut read_back = vec![0; data.len()];
+    dap.read_memory(sector.start, &mut read_back)?;
+    if let Some(offset) = data.iter().zip(&read_back).position(|(expected, found)| expected != found) {
+        return Err(DapError::Mismatch { address: sector.start + offset as u32, expected: data[offset], found: read_back[offset] });
+    }
+
+    Ok(DapSelfTestReport { sector, erase_time, program_time, total_time: started.elapsed() })
+}
+
+/// A CMSIS-DAP probe on USB, see `UsbDap::list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DapProbeInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product: String,
+    pub serial: Option<String>,
+    /// Bulk endpoints of CMSIS-DAP v2, rather than HID reports.
+    pub bulk: bool,
+}
+
+enum Pipes {
+    Bulk {
+        out: nusb::Endpoint<nusb::transfer::Bulk, nusb::transfer::Out>,
+        input: nusb::Endpoint<nusb::transfer::Bulk, nusb::transfer::In>,
+    },
+    Hid {
+        out: nusb::Endpoint<nusb::transfer::Interrupt, nusb::transfer::Out>,
+        input: nusb::Endpoint<nusb::transfer::Interrupt, nusb::transfer::In>,
+    },
+}
+
+/// The USB side of a CMSIS-DAP probe.
+pub struct UsbDap {
+    info: DapProbeInfo,
+    pipes: Pipes,
+}
+
+impl std::fmt::Debug for UsbDap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UsbDap").field("info", &self.info).finish()
+    }
+}
+
+fn usb_error(err: impl ToString) -> DapError {
+    DapError::Usb(err.to_string())
+}
+
+/// The interface a probe talks CMSIS-DAP on, the v2 one when it has both, and whether it is.
+fn dap_interface(device: &nusb::DeviceInfo) -> Option<(u8, bool)> {
+    let named = |interface: &&nusb::InterfaceInfo| interface.interface_string().is_some_and(|name| name.contains("CMSIS-DAP"));
+    let product = device.product_string().is_some_and(|name| name.contains("CMSIS-DAP"));
+
+    let bulk = device.interfaces().filter(named).find(|interface| interface.class() == 0xFF);
+    let hid = device.interfaces().find(|interface| interface.class() == 0x03 && (product || named(interface)));
+    bulk.map(|interface| (interface.interface_number(), true)).or_else(|| hid.map(|interface| (interface.interface_number(), false)))
+}
+
+impl UsbDap {
+    /// CMSIS-DAP probes on USB, recognised by "CMSIS-DAP" in their product or interface name as
+    /// the specification asks.
+    pub fn list() -> Result<Vec<DapProbeInfo>, DapError> {
+        use nusb::MaybeFuture;
+
+        let devices = nusb::list_devices().wait().map_err(usb_error)?;
+        Ok(devices
+            .filter_map(|device| {
+                let (_, bulk) = dap_interface(&device)?;
+                Some(DapProbeInfo {
+                    vendor_id: device.vendor_id(),
+                    product_id: device.product_id(),
+                    product: device.product_string().unwrap_or_default().to_string(),
+                    serial: device.serial_number().map(str::to_string),
+                    bulk,
+                })
+            })
+            .collect())
+    }
+
+    /// Opens the probe with serial number `serial`, the first one found without one.
+    pub fn open(serial: Option<&str>) -> Result<Self, DapError> {
+        use nusb::{
+            descriptors::TransferType,
+            transfer::{Direction, In, Out},
+            MaybeFuture,
+        };
+
+        let mut devices = nusb::list_devices().wait().map_err(usb_error)?;
+        let (device, (number, bulk)) = devices
+            .find_map(|device| match serial {
+                Some(serial) if device.serial_number() != Some(serial) => None,
+                _ => dap_interface(&device).map(|interface| (device, interface)),
+            })
+            .ok_or(DapError::NoProbe)?;
+
+        let info = DapProbeInfo {
+            vendor_id: device.vendor_id(),
+            product_id: device.product_id(),
+            product: device.product_string().unwrap_or_default().to_string(),
+            serial: device.serial_number().map(str::to_string),
+            bulk,
+        };
+        let opened = device.open().wait().map_err(usb_error)?;
+        let interface = match bulk {
+            true => opened.claim_interface(number).wait(),
+            // HID interfaces usually have the kernel's driver bound.
+            false => opened.detach_and_claim_interface(number).wait(),
+        }
+        .map_err(usb_error)?;
+
+        let kind = if bulk { TransferType::Bulk } else { TransferType::Interrupt };
+        let endpoint = |direction: Direction| {
+            interface
+                .descriptor()
+                .and_then(|descriptor| {
+                    descriptor.endpoints().find(|endpoint| endpoint.transfer_type() == kind && endpoint.direction() == direction)
+                })
+                .map(|endpoint| endpoint.address())
+                .ok_or_else(|| DapError::Usb(format!("interface {} has no {:?} {:?} endpoint", number, kind, direction)))
+        };
+        let (out, input) = (endpoint(Direction::Out)?, endpoint(Direction::In)?);
+        let pipes = match bulk {
+            true => Pipes::Bulk {
+                out: interface.endpoint::<nusb::transfer::Bulk, Out>(out).map_err(usb_error)?,
+                input: interface.endpoint::<nusb::transfer::Bulk, In>(input).map_err(usb_error)?,
+            },
+            false => Pipes::Hid {
+                out: interface.endpoint::<nusb::transfer::Interrupt, Out>(out).map_err(usb_error)?,
+                input: interface.endpoint::<nusb::transfer::Interrupt, In>(input).map_err(usb_error)?,
+            },
+        };
+
+        Ok(UsbDap { info, pipes })
+    }
+
+    pub fn info(&self) -> &DapProbeInfo {
+        &self.info
+    }
+}
+
+impl DapTransport for UsbDap {
+    fn exchange(&mut self, command: &[u8]) -> Result<Vec<u8>, DapError> {
+        use nusb::transfer::Buffer;
+
+        let completion = match &mut self.pipes {
+            Pipes::Bulk { out, input } => {
+                out.transfer_blocking(Buffer::from(command), USB_TIMEOUT).status.map_err(usb_error)?;
+                let size = input.max_packet_size();
+                input.transfer_blocking(Buffer::new(MAX_RESPONSE.div_ceil(size) * size), USB_TIMEOUT)
+            }
+            Pipes::Hid { out, input } => {
+                // Reports have a fixed size.
+                let mut report = command.to_vec();
+                report.resize(out.max_packet_size().max(command.len()), 0);
+                out.transfer_blocking(Buffer::from(report), USB_TIMEOUT).status.map_err(usb_error)?;
+                let size = input.max_packet_size();
+                input.transfer_blocking(Buffer::new(size), USB_TIMEOUT)
+            }
+        };
+        completion.status.map_err(usb_error)?;
+        Ok(completion.buffer.into_vec())
+    }
+}
@@ -0,0 +1,459 @@
+//! Converted stubs kept in SQLite with where they came from, their hashes and what validation
+//! found, instead of directories of JSON files named after devices.
+//!
+//! Each device holds one stub per algorithm name; storing a stub again replaces it. Queries
+//! filter by device, vendor and flash address, see `StubQuery`.
+//!
+//! Runs of the stubs on hardware, see `hil`, are kept next to them. A run counts for the code
+//! that ran, so a stub stored again with another build is unverified until it runs again.
+
+use std::{
+    ops::Range,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, types::Value, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    prog::arm::flash_stub_gen::ArmFlashStub,
+};
+
+/// Bumped with every change to the tables, stored in `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS stubs (
+    id INTEGER PRIMARY KEY,
+    device TEXT NOT NULL COLLATE NOCASE,
+    vendor TEXT COLLATE NOCASE,
+    pack TEXT,
+    pack_version TEXT,
+    file TEXT,
+    name TEXT NOT NULL,
+    flash_start INTEGER NOT NULL,
+    flash_end INTEGER NOT NULL,
+    crc32 INTEGER,
+    image_crc32 INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    findings TEXT NOT NULL,
+    errors INTEGER NOT NULL,
+    stored_at INTEGER NOT NULL,
+    UNIQUE (device, name)
+);
+CREATE INDEX IF NOT EXISTS stubs_vendor ON stubs (vendor);
+CREATE INDEX IF NOT EXISTS stubs_flash ON stubs (flash_start, flash_end);
+";
+
+/// Added in version 2.
+const HIL_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS hil_runs (
+    id INTEGER PRIMARY KEY,
+    stub_id INTEGER NOT NULL REFERENCES stubs (id),
+    image_crc32 INTEGER NOT NULL,
+    backend TEXT NOT NULL,
+    passed INTEGER NOT NULL,
+    detail TEXT,
+    sector_start INTEGER,
+    sector_end INTEGER,
+    duration_ms INTEGER NOT NULL,
+    tested_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS hil_runs_stub ON hil_runs (stub_id);
+";
+
+/// Whether the last run of a stub's current code passed, for the `stubs` table.
+const VERIFIED: &str = "(SELECT passed FROM hil_runs WHERE stub_id = stubs.id AND image_crc32 = stubs.image_crc32
+    ORDER BY id DESC LIMIT 1) = 1";
+
+const COLUMNS: &str = "id, device, vendor, pack, pack_version, file, crc32, image_crc32, model, findings, stored_at";
+
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("SQLite error, {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("Stored stub model is invalid, {0}")]
+    Model(#[from] serde_json::Error),
+
+    #[error("The database has schema version {0}, {} is supported", SCHEMA_VERSION)]
+    Schema(i64),
+}
+
+/// Where a stub came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StubSource {
+    /// Device the stub programs, matched without regard to case.
+    pub device: String,
+    pub vendor: Option<String>,
+    /// Name of the CMSIS pack, e.g. `STM32F4xx_DFP`.
+    pub pack: Option<String>,
+    pub pack_version: Option<String>,
+    /// The algorithm file, inside the pack when there is one.
+    pub file: Option<String>,
+}
+
+impl StubSource {
+    pub fn new(device: impl Into<String>) -> Self {
+        StubSource { device: device.into(), ..Default::default() }
+    }
+}
+
+/// A validation finding as stored, `Diagnostic` with owned strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredFinding {
+    pub severity: Severity,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    pub message: String,
+}
+
+impl From<&Diagnostic> for StoredFinding {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        StoredFinding {
+            severity: diagnostic.severity,
+            code: diagnostic.code.to_string(),
+            id: diagnostic.id.map(str::to_string),
+            offset: diagnostic.offset,
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// A stub read back from the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredStub {
+    pub id: i64,
+    pub source: StubSource,
+    pub stub: ArmFlashStub,
+    /// `ArmFlashStub::crc32` when it was stored, over the blob and key metadata.
+    pub crc32: Option<u32>,
+    /// CRC32 of the decoded code blob alone, the same for the same build of an algorithm.
+    pub image_crc32: u32,
+    /// What validation found when the stub was stored.
+    pub findings: Vec<StoredFinding>,
+    /// Seconds since the Unix epoch.
+    pub stored_at: u64,
+}
+
+impl StoredStub {
+    /// Whether validation found errors.
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|finding| finding.severity == Severity::Error)
+    }
+}
+
+/// One run of a stored stub on hardware, see `hil`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HilRun {
+    pub stub_id: i64,
+    /// `StoredStub::image_crc32` of the code that ran.
+    pub image_crc32: u32,
+    /// What ran it, `probe-rs` or `injector`.
+    pub backend: String,
+    pub passed: bool,
+    /// Why it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The scratch sector, when the run got as far as picking one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sector: Option<Range<u32>>,
+    pub duration_ms: u64,
+    /// Seconds since the Unix epoch.
+    pub tested_at: u64,
+}
+
+/// Filters of `StubDatabase::query`, every one that is set must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StubQuery {
+    pub device: Option<String>,
+    pub vendor: Option<String>,
+    /// Stubs whose flash overlaps this range.
+    pub flash: Option<Range<u32>>,
+    /// Leave out stubs validation found errors in.
+    pub without_errors: bool,
+    /// Only stubs whose code last passed on hardware.
+    pub verified: bool,
+}
+
+impl StubQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    pub fn vendor(mut self, vendor: impl Into<String>) -> Self {
+        self.vendor = Some(vendor.into());
+        self
+    }
+
+    /// Stubs whose flash overlaps `range`.
+    pub fn flash(mut self, range: Range<u32>) -> Self {
+        self.flash = Some(range);
+        self
+    }
+
+    /// Stubs whose flash holds `address`.
+    pub fn covering(self, address: u32) -> Self {
+        self.flash(address..address.saturating_add(1))
+    }
+
+    pub fn without_errors(mut self) -> Self {
+        self.without_errors = true;
+        self
+    }
+
+    pub fn verified(mut self) -> Self {
+        self.verified = true;
+        self
+    }
+}
+
+/// Stubs in a SQLite database.
+#[derive(Debug)]
+pub struct StubDatabase {
+    connection: Connection,
+}
+
+fn read_stored(row: &Row<'_>) -> rusqlite::Result<(StoredStub, String, String)> {
+    let model: String = row.get(8)?;
+    let findings: String = row.get(9)?;
+    let stored = StoredStub {
+        id: row.get(0)?,
+        source: StubSource {
+            device: row.get(1)?,
+            vendor: row.get(2)?,
+            pack: row.get(3)?,
+            pack_version: row.get(4)?,
+            file: row.get(5)?,
+        },
+        stub: ArmFlashStub::default(),
+        crc32: row.get(6)?,
+        image_crc32: row.get(7)?,
+        findings: Vec::new(),
+        stored_at: row.get::<_, i64>(10)? as u64,
+    };
+    Ok((stored, model, findings))
+}
+
+impl StubDatabase {
+    /// Opens the database at `path`, creating it and its tables when needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives as long as the value, for tests and one-off runs.
+    pub fn in_memory() -> Result<Self, DatabaseError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, DatabaseError> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        match version {
+            0 => {
+                connection.execute_batch(SCHEMA)?;
+                connection.execute_batch(HIL_SCHEMA)?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            1 => {
+                connection.execute_batch(HIL_SCHEMA)?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            SCHEMA_VERSION => {}
+            other => return Err(DatabaseError::Schema(other)),
+        }
+        Ok(StubDatabase { connection })
+    }
+
+    /// Stores `stub` under its source with what validation found, replacing a stub of the same
+    /// name for the same device. Returns its id.
+    pub fn insert(&self, source: &StubSource, stub: &ArmFlashStub, findings: &[Diagnostic]) -> Result<i64, DatabaseError> {
+        let findings: Vec<StoredFinding> = findings.iter().map(Into::into).collect();
+        let errors = findings.iter().filter(|finding| finding.severity == Severity::Error).count() as i64;
+        let image = base64::decode(&stub.instructions).unwrap_or_default();
+        let stored_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as i64);
+
+        self.connection.execute(
+            "INSERT INTO stubs (device, vendor, pack, pack_version, file, name, flash_start, flash_end, crc32,
+                image_crc32, model, findings, errors, stored_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+             ON CONFLICT (device, name) DO UPDATE SET
+                vendor = excluded.vendor, pack = excluded.pack, pack_version = excluded.pack_version,
+                file = excluded.file, flash_start = excluded.flash_start, flash_end = excluded.flash_end,
+                crc32 = excluded.crc32, image_crc32 = excluded.image_crc32, model = excluded.model,
+                findings = excluded.findings, errors = excluded.errors, stored_at = excluded.stored_at",
+            params![
+                source.device,
+                source.vendor,
+                source.pack,
+                source.pack_version,
+                source.file,
+                stub.name,
+                stub.flash_start_addr,
+                stub.flash_end_addr,
+                stub.crc32,
+                crc32fast::hash(&image),
+                serde_json::to_string(stub)?,
+                serde_json::to_string(&findings)?,
+                errors,
+                stored_at,
+            ],
+        )?;
+
+        let id = self.connection.query_row(
+            "SELECT id FROM stubs WHERE device = ?1 AND name = ?2",
+            params![source.device, stub.name],
+            |row| row.get(0),
+        )?;
+        Ok(id)
+    }
+
+    /// The stubs matching every filter of `query`, by device and name.
+    pub fn query(&self, query: &StubQuery) -> Result<Vec<StoredStub>, DatabaseError> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(device) = &query.device {
+            conditions.push("device = ?");
+            values.push(Value::Text(device.clone()));
+        }
+        if let Some(vendor) = &query.vendor {
+            conditions.push("vendor = ?");
+            values.push(Value::Text(vendor.clone()));
+        }
+        if let Some(range) = &query.flash {
+            conditions.push("flash_start < ? AND flash_end > ?");
+            values.push(Value::Integer(range.end.into()));
+            values.push(Value::Integer(range.start.into()));
+        }
+        if query.without_errors {
+            conditions.push("errors = 0");
+        }
+        if query.verified {
+            conditions.push(VERIFIED);
+        }
+
+        let filter = if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) };
+        let sql = format!("SELECT {} FROM stubs{} ORDER BY device, name", COLUMNS, filter);
+        let mut statement = self.connection.prepare(&sql)?;
+        let rows = statement.query_map(rusqlite::params_from_iter(values), read_stored)?;
+
+        let mut stubs = Vec::new();
+        for row in rows {
+            let (mut stored, model, findings) = row?;
+            stored.stub = serde_json::from_str(&model)?;
+            stored.findings = serde_json::from_str(&findings)?;
+            stubs.push(stored);
+        }
+        Ok(stubs)
+    }
+
+    /// The stub stored under `id`.
+    pub fn get(&self, id: i64) -> Result<Option<StoredStub>, DatabaseError> {
+        let sql = format!("SELECT {} FROM stubs WHERE id = ?1", COLUMNS);
+        let row = self.connection.query_row(&sql, [id], read_stored).optional()?;
+        match row {
+            Some((mut stored, model, findings)) => {
+                stored.stub = serde_json::from_str(&model)?;
+                stored.findings = serde_json::from_str(&findings)?;
+                Ok(Some(stored))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The stubs of `device`, the same as querying by device.
+    pub fn device_stubs(&self, device: &str) -> Result<Vec<ArmFlashStub>, DatabaseError> {
+        Ok(self.query(&StubQuery::new().device(device))?.into_iter().map(|stored| stored.stub).collect())
+    }
+
+    /// Names of the devices with stubs, sorted.
+    pub fn devices(&self) -> Result<Vec<String>, DatabaseError> {
+        let mut statement = self.connection.prepare("SELECT DISTINCT device FROM stubs ORDER BY device")?;
+        let devices = statement.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        Ok(devices)
+    }
+
+    /// Removes the stubs of `device` and their runs, returning how many stubs there were.
+    pub fn remove_device(&self, device: &str) -> Result<usize, DatabaseError> {
+        self.connection
+            .execute("DELETE FROM hil_runs WHERE stub_id IN (SELECT id FROM stubs WHERE device = ?1)", [device])?;
+        Ok(self.connection.execute("DELETE FROM stubs WHERE device = ?1", [device])?)
+    }
+
+    /// Keeps a run of a stub on hardware, returning its id.
+    pub fn record_hil(&self, run: &HilRun) -> Result<i64, DatabaseError> {
+        self.connection.execute(
+            "INSERT INTO hil_runs (stub_id, image_crc32, backend, passed, detail, sector_start, sector_end, duration_ms,
+                tested_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                run.stub_id,
+                run.image_crc32,
+                run.backend,
+                run.passed,
+                run.detail,
+                run.sector.as_ref().map(|sector| sector.start),
+                run.sector.as_ref().map(|sector| sector.end),
+                run.duration_ms as i64,
+                run.tested_at as i64,
+            ],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// The runs of the stub stored under `stub_id`, the latest first.
+    pub fn hil_runs(&self, stub_id: i64) -> Result<Vec<HilRun>, DatabaseError> {
+        let mut statement = self.connection.prepare(
+            "SELECT stub_id, image_crc32, backend, passed, detail, sector_start, sector_end, duration_ms, tested_at
+             FROM hil_runs WHERE stub_id = ?1 ORDER BY id DESC",
+        )?;
+        let runs = statement
+            .query_map([stub_id], |row| {
+                let sector: (Option<u32>, Option<u32>) = (row.get(5)?, row.get(6)?);
+                Ok(HilRun {
+                    stub_id: row.get(0)?,
+                    image_crc32: row.get(1)?,
+                    backend: row.get(2)?,
+                    passed: row.get(3)?,
+                    detail: row.get(4)?,
+                    sector: match sector {
+                        (Some(start), Some(end)) => Some(start..end),
+                        _ => None,
+                    },
+                    duration_ms: row.get::<_, i64>(7)? as u64,
+                    tested_at: row.get::<_, i64>(8)? as u64,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(runs)
+    }
+
+    /// Whether the last run of the current code of the stub stored under `stub_id` passed.
+    pub fn is_verified(&self, stub_id: i64) -> Result<bool, DatabaseError> {
+        let sql = format!("SELECT {} FROM stubs WHERE id = ?1", VERIFIED);
+        let verified: Option<Option<bool>> = self.connection.query_row(&sql, [stub_id], |row| row.get(0)).optional()?;
+        Ok(verified.flatten().unwrap_or(false))
+    }
+
+    pub fn len(&self) -> Result<usize, DatabaseError> {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM stubs", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, DatabaseError> {
+        Ok(self.len()? == 0)
+    }
+}
@@ -0,0 +1,93 @@
+//! Problems that don't stop a conversion, returned next to the result so lenient callers can show
+//! them and strict ones can refuse the stub.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Stable numeric codes of the diagnostics, `SC1001` and up next to the `SC00xx` of `ArmError`.
+/// New diagnostics get the next free code, codes are never reused.
+const IDS: [(&str, &str); 17] = [
+    ("breakpoint-entry", "SC1001"),
+    ("unexpected-section", "SC1002"),
+    ("region-kind-guessed", "SC1003"),
+    ("suspicious-timeout", "SC1004"),
+    ("unpinned-core", "SC1005"),
+    ("blank-value-conflict", "SC1006"),
+    ("page-size-conflict", "SC1007"),
+    ("conversion-failed", "SC1008"),
+    ("core-check-failed", "SC1009"),
+    ("core-violation", "SC1010"),
+    ("crc-mismatch", "SC1011"),
+    ("unbounded-stack", "SC1012"),
+    ("zero-timeout", "SC1013"),
+    ("sector-page-mismatch", "SC1014"),
+    ("no-init", "SC1015"),
+    ("irreversible-region", "SC1016"),
+    ("broken-sector-table", "SC1017"),
+];
+
+/// The numeric code of the diagnostic `code`, if it has one.
+pub fn id_of(code: &str) -> Option<&'static str> {
+    IDS.iter().find(|&&(name, _)| name == code).map(|&(_, id)| id)
+}
+
+/// One problem found in an algorithm.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable kebab-case identifier for the kind of problem, e.g. "unbounded-stack".
+    pub code: &'static str,
+    /// Stable numeric code, e.g. "SC1012", see `id_of`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<&'static str>,
+    /// Where in the code blob or flash the problem is, if it has a location.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Error, code, id: id_of(code), offset: None, message: message.into() }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Warning, code, id: id_of(code), offset: None, message: message.into() }
+    }
+
+    pub fn at(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Replaces the numeric code, e.g. with the `ArmError::code` behind a failed conversion.
+    pub fn with_id(mut self, id: &'static str) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
+
+/// `severity[code]: message`, the way the command line prints it.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
@@ -0,0 +1,281 @@
+//! Encrypting the instruction blobs of stubs with AES-256-GCM, for algorithms that are
+//! confidential and shipped to contract manufacturers. Only the blobs are sealed, the flash
+//! layout, entry points and timeouts stay readable so stubs can still be listed and matched.
+//!
+//! Keys are per device or per fleet, 64 hex digits in a file named after the key ID, e.g.
+//! `STM32F407VG.key` or `fleet.key`. A `KeyRing` loads a directory of them and picks the device's
+//! own key before the fleet key.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use thiserror::Error;
+
+use crate::prog::arm::{
+    encryption::{EncryptionAlgorithm, StubEncryption},
+    flash_stub_gen::ArmFlashStub,
+};
+
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 12;
+
+/// ID of the key used for devices without one of their own.
+pub const FLEET_KEY_ID: &str = "fleet";
+/// Extension of key files in a key directory.
+pub const KEY_EXTENSION: &str = "key";
+
+/// Field labels of the blobs in their additional authenticated data.
+const INSTRUCTIONS_FIELD: &str = "instructions";
+const DATA_INSTRUCTIONS_FIELD: &str = "dataInstructions";
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("Failed to read {path}, {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Invalid key {id}, {reason}")]
+    InvalidKey { id: String, reason: String },
+
+    #[error("Failed to generate a nonce, {0}")]
+    Random(getrandom::Error),
+
+    #[error("No key for {0} and no fleet key")]
+    NoKey(String),
+
+    #[error("The stub is encrypted with key {0}, which isn't in the key ring")]
+    UnknownKey(String),
+
+    #[error("The stub is already encrypted with key {0}")]
+    AlreadyEncrypted(String),
+
+    #[error("The stub isn't encrypted")]
+    NotEncrypted,
+
+    #[error("Invalid base64 in the stub, {0}")]
+    Decode(String),
+
+    #[error("Failed to decrypt with key {0}, the key is wrong or the stub was altered")]
+    Decrypt(String),
+
+    #[error("The blob is too large to encrypt")]
+    TooLarge,
+}
+
+/// A named AES-256 key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey {
+    pub id: String,
+    key: [u8; KEY_SIZE],
+}
+
+/// Leaves the key material out of logs.
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    pub fn new(id: impl Into<String>, key: [u8; KEY_SIZE]) -> Self {
+        EncryptionKey { id: id.into(), key }
+    }
+
+    /// A new key from the operating system's random source.
+    pub fn generate(id: impl Into<String>) -> Result<Self, EncryptionError> {
+        let mut key = [0; KEY_SIZE];
+        getrandom::getrandom(&mut key).map_err(EncryptionError::Random)?;
+        Ok(EncryptionKey::new(id, key))
+    }
+
+    /// Reads the 64 hex digits of a key file, ignoring surrounding whitespace.
+    pub fn from_hex(id: impl Into<String>, text: &str) -> Result<Self, EncryptionError> {
+        let id = id.into();
+        let text = text.trim();
+        if text.len() != KEY_SIZE * 2 || !text.is_ascii() {
+            let reason = format!("expected {} hex digits, found {} characters", KEY_SIZE * 2, text.chars().count());
+            return Err(EncryptionError::InvalidKey { id, reason });
+        }
+
+        let mut key = [0; KEY_SIZE];
+        for (byte, digits) in key.iter_mut().zip(text.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).unwrap_or_default();
+            *byte = u8::from_str_radix(digits, 16)
+                .map_err(|_| EncryptionError::InvalidKey { id: id.clone(), reason: format!("{:?} isn't hex", digits) })?;
+        }
+        Ok(EncryptionKey::new(id, key))
+    }
+
+    /// The key as the 64 hex digits of a key file.
+    pub fn to_hex(&self) -> String {
+        self.key.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn seal(&self, blob: &[u8], aad: &[u8]) -> Result<(Vec<u8>, [u8; NONCE_SIZE]), EncryptionError> {
+        let mut nonce = [0; NONCE_SIZE];
+        getrandom::getrandom(&mut nonce).map_err(EncryptionError::Random)?;
+        let cipher = Aes256Gcm::new(&self.key.into());
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: blob, aad })
+            .map_err(|_| EncryptionError::TooLarge)?;
+        Ok((sealed, nonce))
+    }
+
+    /// Opens a blob sealed under `key_id`, which can differ from `id` in case.
+    fn open(&self, sealed: &[u8], nonce: &[u8], aad: &[u8], key_id: &str) -> Result<Vec<u8>, EncryptionError> {
+        if nonce.len() != NONCE_SIZE {
+            return Err(EncryptionError::Decode(format!("nonce of {} bytes, expected {}", nonce.len(), NONCE_SIZE)));
+        }
+        let cipher = Aes256Gcm::new(&self.key.into());
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad })
+            .map_err(|_| EncryptionError::Decrypt(key_id.to_string()))
+    }
+}
+
+/// Keys by ID, matched ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct KeyRing {
+    keys: BTreeMap<String, EncryptionKey>,
+}
+
+impl KeyRing {
+    pub fn new() -> Self {
+        KeyRing::default()
+    }
+
+    /// Reads a key file, named by its file stem, or every `.key` file in a directory.
+    pub fn load(path: &Path) -> Result<Self, EncryptionError> {
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| EncryptionError::Io { path, source }
+        };
+
+        let files = if path.is_dir() {
+            let mut files = Vec::new();
+            for entry in fs::read_dir(path).map_err(io_error(path))? {
+                let file = entry.map_err(io_error(path))?.path();
+                if file.extension().is_some_and(|extension| extension == KEY_EXTENSION) {
+                    files.push(file);
+                }
+            }
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut ring = KeyRing::new();
+        for file in files {
+            let id = file.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let text = fs::read_to_string(&file).map_err(io_error(&file))?;
+            ring.insert(EncryptionKey::from_hex(id, &text)?);
+        }
+        Ok(ring)
+    }
+
+    pub fn insert(&mut self, key: EncryptionKey) {
+        self.keys.insert(key.id.to_lowercase(), key);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EncryptionKey> {
+        self.keys.get(&id.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The key of `device`, else the fleet key. Without a device the only key of the ring also
+    /// does; a named device never falls back to another device's key.
+    pub fn select(&self, device: Option<&str>) -> Result<&EncryptionKey, EncryptionError> {
+        let key = match device {
+            Some(device) => self.get(device).or_else(|| self.get(FLEET_KEY_ID)),
+            None => self.get(FLEET_KEY_ID).or_else(|| if self.keys.len() == 1 { self.keys.values().next() } else { None }),
+        };
+        key.ok_or_else(|| EncryptionError::NoKey(device.unwrap_or("the stub").to_string()))
+    }
+}
+
+/// Additional authenticated data of one blob: the key ID, the stub name, the field the blob is
+/// stored in and the CRC32 of the decrypted stub, each string prefixed with its length. A blob
+/// moved to another stub or field, or a stub whose layout was edited, fails to open.
+fn associated_data(key_id: &str, name: &str, field: &str, crc32: u32) -> Vec<u8> {
+    let mut aad = Vec::new();
+    for text in [key_id, name, field] {
+        aad.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        aad.extend_from_slice(text.as_bytes());
+    }
+    aad.extend_from_slice(&crc32.to_le_bytes());
+    aad
+}
+
+impl ArmFlashStub {
+    /// A copy of the stub with `instructions` and `data_instructions` sealed with `key`.
+    ///
+    /// A stub without a CRC32 gets one, the CRC is part of what the blobs are bound to.
+    pub fn encrypt(&self, key: &EncryptionKey) -> Result<ArmFlashStub, EncryptionError> {
+        if let Some(encryption) = &self.encryption {
+            return Err(EncryptionError::AlreadyEncrypted(encryption.key_id.clone()));
+        }
+        let crc32 = match self.crc32 {
+            Some(crc32) => crc32,
+            None => self.compute_crc32().map_err(|err| EncryptionError::Decode(err.to_string()))?,
+        };
+
+        let seal = |blob: &str, field: &str| -> Result<(String, String), EncryptionError> {
+            let blob = base64::decode(blob).map_err(|err| EncryptionError::Decode(err.to_string()))?;
+            let (sealed, nonce) = key.seal(&blob, &associated_data(&key.id, &self.name, field, crc32))?;
+            Ok((base64::encode(sealed), base64::encode(nonce)))
+        };
+
+        let mut stub = self.clone();
+        stub.crc32 = Some(crc32);
+        let (instructions, nonce) = seal(&self.instructions, INSTRUCTIONS_FIELD)?;
+        stub.instructions = instructions;
+        let mut data_nonce = None;
+        if let Some(data) = &self.data_instructions {
+            let (data, nonce) = seal(data, DATA_INSTRUCTIONS_FIELD)?;
+            stub.data_instructions = Some(data);
+            data_nonce = Some(nonce);
+        }
+        stub.encryption = Some(Box::new(StubEncryption {
+            algorithm: EncryptionAlgorithm::Aes256Gcm,
+            key_id: key.id.clone(),
+            nonce,
+            data_nonce,
+        }));
+        Ok(stub)
+    }
+
+    /// A copy of the stub with its blobs decrypted by the key it names from `keys`.
+    pub fn decrypt(&self, keys: &KeyRing) -> Result<ArmFlashStub, EncryptionError> {
+        let encryption = self.encryption.as_ref().ok_or(EncryptionError::NotEncrypted)?;
+        let key = keys.get(&encryption.key_id).ok_or_else(|| EncryptionError::UnknownKey(encryption.key_id.clone()))?;
+        let crc32 = self.crc32.ok_or_else(|| EncryptionError::Decode("no CRC32".to_string()))?;
+
+        let open = |blob: &str, nonce: &str, field: &str| -> Result<String, EncryptionError> {
+            let decode = |text: &str| base64::decode(text).map_err(|err| EncryptionError::Decode(err.to_string()));
+            let aad = associated_data(&encryption.key_id, &self.name, field, crc32);
+            Ok(base64::encode(key.open(&decode(blob)?, &decode(nonce)?, &aad, &encryption.key_id)?))
+        };
+
+        let mut stub = self.clone();
+        stub.instructions = open(&self.instructions, &encryption.nonce, INSTRUCTIONS_FIELD)?;
+        if let Some(data) = &self.data_instructions {
+            let nonce = encryption.data_nonce.as_deref().ok_or_else(|| EncryptionError::Decode("no data nonce".to_string()))?;
+            stub.data_instructions = Some(open(data, nonce, DATA_INSTRUCTIONS_FIELD)?);
+        }
+        stub.encryption = None;
+        Ok(stub)
+    }
+}
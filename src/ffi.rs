@@ -0,0 +1,224 @@
+//! C interface for production tooling that can't link Rust, declared in `include/soul_composer.h`.
+//!
+//! Functions return `SC_OK` or set the message `sc_last_error` returns, and the stable code
+//! `sc_last_error_code` returns when there is one. Everything the library
+//! allocates is released with the matching `sc_*_free`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    ptr, slice,
+};
+
+use goblin::elf::Elf;
+
+use crate::{
+    compose::arm::{compose_stub, ComposeOptions},
+    prog::{
+        arm::{arm_error::ArmError, flash_device::FlashDevice},
+        export::{export, OutputFormat},
+    },
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+    static LAST_ERROR_CODE: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Result of every call that can fail.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScStatus {
+    Ok = 0,
+    /// A pointer was null or a string wasn't UTF-8.
+    InvalidArgument = 1,
+    /// The FLM was refused, see `sc_last_error`.
+    ParseFailed = 2,
+}
+
+/// One run of equally sized sectors, `address` relative to the flash start.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ScSector {
+    pub address: u32,
+    pub size: u32,
+}
+
+/// The `FlashDevice` of an FLM.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ScFlashDevice {
+    pub driver_version: u16,
+    pub device_type: u16,
+    /// NUL terminated.
+    pub name: [c_char; 129],
+    pub start_address: u32,
+    pub device_size: u32,
+    pub page_size: u32,
+    pub erased_default_value: u8,
+    pub program_page_timeout: u32,
+    pub erase_sector_timeout: u32,
+    /// Owned by the library, released by `sc_flash_device_free`.
+    pub sectors: *mut ScSector,
+    pub sector_count: usize,
+}
+
+fn fail(status: ScStatus, code: Option<&'static str>, message: impl ToString) -> ScStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    LAST_ERROR_CODE.with(|last| *last.borrow_mut() = code.and_then(|code| CString::new(code).ok()));
+    status
+}
+
+fn fail_arm(err: ArmError) -> ScStatus {
+    fail(ScStatus::ParseFailed, Some(err.code()), err)
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn string(text: *const c_char) -> Option<String> {
+    if text.is_null() {
+        return None;
+    }
+    CStr::from_ptr(text).to_str().ok().map(str::to_string)
+}
+
+/// Message of the last failed call on this thread, or null. Valid until the next call fails.
+#[no_mangle]
+pub extern "C" fn sc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Stable code of the last failed call on this thread, e.g. "SC0011", or null when the failure
+/// has none. Valid until the next call fails.
+#[no_mangle]
+pub extern "C" fn sc_last_error_code() -> *const c_char {
+    LAST_ERROR_CODE.with(|last| last.borrow().as_ref().map_or(ptr::null(), |code| code.as_ptr()))
+}
+
+/// Parses the `FlashDevice` of the FLM in `data` into `out`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `out` to a writable `ScFlashDevice`.
+#[no_mangle]
+pub unsafe extern "C" fn sc_parse_flm(data: *const u8, len: usize, out: *mut ScFlashDevice) -> ScStatus {
+    let data = match bytes(data, len) {
+        Some(data) if !out.is_null() => data,
+        _ => return fail(ScStatus::InvalidArgument, None, "data and out must not be null"),
+    };
+    let device = match Elf::parse(data).map_err(ArmError::from).and_then(|elf| FlashDevice::from_elf(&elf, data)) {
+        Ok(device) => device,
+        Err(err) => return fail_arm(err),
+    };
+
+    let mut name = [0; 129];
+    for (to, from) in name.iter_mut().zip(device.name().bytes().take(128)) {
+        *to = from as c_char;
+    }
+    let sectors: Box<[ScSector]> =
+        device.sectors().iter().map(|sector| ScSector { address: sector.address, size: sector.size }).collect();
+    let sector_count = sectors.len();
+
+    out.write(ScFlashDevice {
+        driver_version: device.driver_version(),
+        device_type: device.device_type(),
+        name,
+        start_address: device.start_address(),
+        device_size: device.device_size(),
+        page_size: device.page_size(),
+        erased_default_value: device.erased_default_value(),
+        program_page_timeout: device.program_page_timeout(),
+        erase_sector_timeout: device.erase_sector_timeout(),
+        sectors: Box::into_raw(sectors) as *mut ScSector,
+        sector_count,
+    });
+    ScStatus::Ok
+}
+
+/// Releases the sector table `sc_parse_flm` allocated.
+///
+/// # Safety
+///
+/// `device` must be null or filled in by `sc_parse_flm` and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn sc_flash_device_free(device: *mut ScFlashDevice) {
+    if let Some(device) = device.as_mut() {
+        if !device.sectors.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(device.sectors, device.sector_count)));
+        }
+        device.sectors = ptr::null_mut();
+        device.sector_count = 0;
+    }
+}
+
+/// Converts the FLM in `data` into a stub, written to `out` as a NUL terminated string in
+/// `format` (`json`, `yaml`, `c-header` or `rust`, null for JSON).
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, `name` and `format` to NUL terminated strings or
+/// null, and `out` to a writable pointer. The string must be released with `sc_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn sc_compose_stub(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    default: bool,
+    ram_size: u32,
+    format: *const c_char,
+    out: *mut *mut c_char,
+) -> ScStatus {
+    let data = match bytes(data, len) {
+        Some(data) if !out.is_null() => data,
+        _ => return fail(ScStatus::InvalidArgument, None, "data and out must not be null"),
+    };
+    let name = if name.is_null() { Some("flash".to_string()) } else { string(name) };
+    let format = if format.is_null() { Some("json".to_string()) } else { string(format) };
+    let (name, format) = match (name, format) {
+        (Some(name), Some(format)) => (name, format),
+        _ => return fail(ScStatus::InvalidArgument, None, "name and format must be UTF-8"),
+    };
+    let format: OutputFormat = match format.parse() {
+        Ok(format) if !matches!(format, OutputFormat::Bin | OutputFormat::Cbor | OutputFormat::Msgpack) => format,
+        Ok(format) => return fail(ScStatus::InvalidArgument, None, format!("{} output is binary, use a text format", format)),
+        Err(err) => return fail(ScStatus::InvalidArgument, None, err),
+    };
+
+    let mut options = ComposeOptions::new(name);
+    options.default = default;
+    options.ram_size = ram_size;
+    let stub = match Elf::parse(data).map_err(ArmError::from).and_then(|elf| compose_stub(&elf, data, &options)) {
+        Ok((stub, _)) => stub,
+        Err(err) => return fail_arm(err),
+    };
+    let text = export(&stub, format)
+        .map_err(|err| err.to_string())
+        .and_then(|text| CString::new(text).map_err(|err| err.to_string()));
+    match text {
+        Ok(text) => {
+            out.write(text.into_raw());
+            ScStatus::Ok
+        }
+        Err(err) => fail(ScStatus::ParseFailed, None, err),
+    }
+}
+
+/// Releases a string returned by `sc_compose_stub`.
+///
+/// # Safety
+///
+/// `text` must be null or returned by `sc_compose_stub` and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn sc_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
@@ -0,0 +1,418 @@
+//! A stub library kept in a git repository, so several factory sites share the same converted
+//! stubs without a server of their own. Each device's stubs are one JSON file, `index.json` maps
+//! devices to their files and the SHA-256 of each.
+//!
+//! The checkout is shallow, only the tip of the branch is ever fetched. `HEAD` is the last state
+//! of the remote seen by a sync, so what differs from it in the working tree is local work. A
+//! device changed both here and on the remote since is a conflict, which stops the sync unless a
+//! side is picked with `Resolve`.
+//!
+//! Runs the `git` executable, which must be on the `PATH` and set up for the remote's
+//! authentication.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    prog::{
+        arm::flash_stub_gen::ArmFlashStub,
+        export::{export_model, OutputFormat},
+    },
+    registry::StubRegistry,
+};
+
+pub const INDEX_FILE: &str = "index.json";
+
+/// Commit identity used when git has none configured.
+const FALLBACK_NAME: &str = "soul-composer";
+const FALLBACK_EMAIL: &str = "soul-composer@localhost";
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("Failed to run git, {0}")]
+    Spawn(io::Error),
+
+    #[error("git {command} failed, {stderr}")]
+    Git { command: String, stderr: String },
+
+    #[error("Failed to access {path}, {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Invalid library index, {0}")]
+    Index(serde_json::Error),
+
+    #[error("Invalid stubs in {file}, {reason}")]
+    Model { file: String, reason: String },
+
+    #[error("{} changed both here and on the remote: {}", .0.len(), .0.join(", "))]
+    Conflicts(Vec<String>),
+
+    #[error("{file} has SHA-256 {actual}, the index says {expected}")]
+    HashMismatch { file: String, expected: String, actual: String },
+
+    #[error("The remote moved on while pushing, pull and push again")]
+    Rejected,
+}
+
+/// Where a device's stubs are kept and the SHA-256 of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexEntry {
+    pub file: String,
+    pub sha256: String,
+}
+
+/// `index.json`, the devices of the library.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryIndex {
+    pub devices: BTreeMap<String, IndexEntry>,
+}
+
+impl LibraryIndex {
+    fn sha256(&self, device: &str) -> Option<&str> {
+        self.devices.get(device).map(|entry| entry.sha256.as_str())
+    }
+}
+
+/// Devices a sync changed, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SyncReport {
+    fn between(from: &LibraryIndex, to: &LibraryIndex) -> Self {
+        let mut report = SyncReport::default();
+        for (device, entry) in &to.devices {
+            match from.sha256(device) {
+                None => report.added.push(device.clone()),
+                Some(sha256) if sha256 != entry.sha256 => report.updated.push(device.clone()),
+                Some(_) => {}
+            }
+        }
+        report.removed = from.devices.keys().filter(|device| !to.devices.contains_key(*device)).cloned().collect();
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Which side wins for devices changed both here and on the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolve {
+    /// Stop the sync and list the conflicts.
+    #[default]
+    Abort,
+    /// Keep the local stubs, they replace the remote ones on the next push.
+    Ours,
+    /// Take the remote stubs, dropping the local changes.
+    Theirs,
+}
+
+/// Lowercase hex of the SHA-256 of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// File the stubs of `device` are kept in, the name with anything but letters, digits, `-`,
+/// `_` and `.` replaced.
+pub fn device_file(device: &str) -> String {
+    let name: String =
+        device.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' }).collect();
+    format!("{}.json", name)
+}
+
+/// A checkout of the library's branch.
+#[derive(Debug, Clone)]
+pub struct GitLibrary {
+    remote: String,
+    branch: String,
+    checkout: PathBuf,
+}
+
+impl GitLibrary {
+    /// The library on `branch` of the repository at `remote`, checked out in `checkout`. Nothing
+    /// is cloned before the first `pull` or `push`.
+    pub fn new(remote: impl Into<String>, branch: impl Into<String>, checkout: impl Into<PathBuf>) -> Self {
+        GitLibrary { remote: remote.into(), branch: branch.into(), checkout: checkout.into() }
+    }
+
+    pub fn checkout(&self) -> &Path {
+        &self.checkout
+    }
+
+    fn io_error(&self, path: &Path) -> impl FnOnce(io::Error) -> SyncError {
+        let path = path.to_path_buf();
+        move |source| SyncError::Io { path, source }
+    }
+
+    /// Runs git in the checkout, returning its output or its error output as the error.
+    fn git(&self, args: &[&str]) -> Result<String, SyncError> {
+        let output = Command::new("git").arg("-C").arg(&self.checkout).args(args).output().map_err(SyncError::Spawn)?;
+        if !output.status.success() {
+            return Err(SyncError::Git {
+                command: args.first().copied().unwrap_or_default().to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn init(&self) -> Result<(), SyncError> {
+        if self.checkout.join(".git").exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.checkout).map_err(self.io_error(&self.checkout))?;
+        self.git(&["init", "-q"])?;
+        // git resolves a relative path against the checkout, the caller meant the working directory.
+        let remote = match fs::canonicalize(&self.remote) {
+            Ok(path) => path.display().to_string(),
+            Err(_) => self.remote.clone(),
+        };
+        self.git(&["remote", "add", "origin", &remote])?;
+        Ok(())
+    }
+
+    /// Fetches the tip of the branch into `FETCH_HEAD`, false if the remote has no such branch yet.
+    fn fetch(&self) -> Result<bool, SyncError> {
+        match self.git(&["fetch", "-q", "--depth", "1", "origin", &self.branch]) {
+            Ok(_) => Ok(true),
+            Err(SyncError::Git { stderr, .. }) if stderr.contains("couldn't find remote ref") => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn revision(&self, revision: &str) -> Option<String> {
+        self.git(&["rev-parse", "-q", "--verify", revision]).ok().map(|hash| hash.trim().to_string())
+    }
+
+    /// The index at a commit, empty before the first commit.
+    fn index_at(&self, revision: &str) -> Result<LibraryIndex, SyncError> {
+        match self.git(&["show", &format!("{}:{}", revision, INDEX_FILE)]) {
+            Ok(text) => serde_json::from_str(&text).map_err(SyncError::Index),
+            Err(SyncError::Git { .. }) => Ok(LibraryIndex::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The index in the working tree, including stubs staged but not pushed yet.
+    pub fn index(&self) -> Result<LibraryIndex, SyncError> {
+        let path = self.checkout.join(INDEX_FILE);
+        match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(SyncError::Index),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(LibraryIndex::default()),
+            Err(err) => Err(SyncError::Io { path, source: err }),
+        }
+    }
+
+    fn write_index(&self, index: &LibraryIndex) -> Result<(), SyncError> {
+        let path = self.checkout.join(INDEX_FILE);
+        let mut text = serde_json::to_string_pretty(index).map_err(SyncError::Index)?;
+        text.push('\n');
+        fs::write(&path, text).map_err(self.io_error(&path))
+    }
+
+    fn read_file(&self, file: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let path = self.checkout.join(file);
+        match fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(SyncError::Io { path, source: err }),
+        }
+    }
+
+    /// Hashes of the device files in the working tree, for the devices of either index.
+    fn working_hashes(&self, base: &LibraryIndex, working: &LibraryIndex) -> Result<BTreeMap<String, Option<String>>, SyncError> {
+        let mut hashes = BTreeMap::new();
+        for (device, entry) in base.devices.iter().chain(&working.devices) {
+            let file = working.devices.get(device).unwrap_or(entry);
+            hashes.insert(device.clone(), self.read_file(&file.file)?.map(|data| sha256_hex(&data)));
+        }
+        Ok(hashes)
+    }
+
+    /// Writes `stubs` as the stubs of `device` in the working tree, to be pushed by `push`.
+    pub fn stage(&self, device: &str, stubs: &[ArmFlashStub]) -> Result<(), SyncError> {
+        self.init()?;
+        let file = device_file(device);
+        let data = export_model(&stubs, OutputFormat::Json).map_err(|err| SyncError::Model { file: file.clone(), reason: err.to_string() })?;
+        let path = self.checkout.join(&file);
+        fs::write(&path, &data).map_err(self.io_error(&path))?;
+
+        let mut index = self.index()?;
+        index.devices.insert(device.to_string(), IndexEntry { file, sha256: sha256_hex(&data) });
+        self.write_index(&index)
+    }
+
+    /// Drops `device` from the working tree, to be pushed by `push`. False if it wasn't there.
+    pub fn unstage(&self, device: &str) -> Result<bool, SyncError> {
+        let mut index = self.index()?;
+        let entry = match index.devices.remove(device) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        let path = self.checkout.join(&entry.file);
+        if path.exists() {
+            fs::remove_file(&path).map_err(self.io_error(&path))?;
+        }
+        self.write_index(&index)?;
+        Ok(true)
+    }
+
+    /// Brings the checkout up to the remote's branch, keeping local changes that don't conflict.
+    pub fn pull(&self, resolve: Resolve) -> Result<SyncReport, SyncError> {
+        self.init()?;
+        let remote_exists = self.fetch()?;
+        let base = self.index_at("HEAD")?;
+        let working = self.index()?;
+        let remote = if remote_exists { self.index_at("FETCH_HEAD")? } else { base.clone() };
+
+        // Local changes are what the working tree holds against the last synced commit.
+        let hashes = self.working_hashes(&base, &working)?;
+        let changed: BTreeSet<&String> =
+            hashes.iter().filter(|(device, hash)| hash.as_deref() != base.sha256(device)).map(|(device, _)| device).collect();
+        let conflicts: Vec<String> = changed
+            .iter()
+            .filter(|device| {
+                let remote_hash = remote.sha256(device);
+                remote_hash != base.sha256(device) && remote_hash != hashes[**device].as_deref()
+            })
+            .map(|device| device.to_string())
+            .collect();
+
+        let mut keep: BTreeSet<&String> = changed.clone();
+        if !conflicts.is_empty() {
+            match resolve {
+                Resolve::Abort => return Err(SyncError::Conflicts(conflicts)),
+                Resolve::Ours => {}
+                Resolve::Theirs => keep.retain(|device| !conflicts.contains(device)),
+            }
+        }
+
+        if !remote_exists || self.revision("HEAD") == self.revision("FETCH_HEAD") {
+            return Ok(SyncReport::between(&base, &remote));
+        }
+
+        // Set the local changes aside, move to the remote's tip and put them back on top.
+        let mut saved = Vec::new();
+        for device in &keep {
+            let entry = working.devices.get(*device);
+            let data = match entry {
+                Some(entry) => self.read_file(&entry.file)?,
+                None => None,
+            };
+            saved.push(((*device).clone(), entry.cloned(), data));
+        }
+        self.git(&["reset", "-q", "--hard", "FETCH_HEAD"])?;
+        self.verify(&remote)?;
+
+        let mut index = remote.clone();
+        for (device, entry, data) in saved {
+            match (entry, data) {
+                (Some(entry), Some(data)) => {
+                    let path = self.checkout.join(&entry.file);
+                    fs::write(&path, &data).map_err(self.io_error(&path))?;
+                    index.devices.insert(device, IndexEntry { file: entry.file, sha256: sha256_hex(&data) });
+                }
+                _ => {
+                    if let Some(entry) = index.devices.remove(&device) {
+                        let path = self.checkout.join(&entry.file);
+                        fs::remove_file(&path).map_err(self.io_error(&path))?;
+                    }
+                }
+            }
+        }
+        if index != remote {
+            self.write_index(&index)?;
+        }
+
+        Ok(SyncReport::between(&base, &remote))
+    }
+
+    /// Pulls, then commits the staged changes and pushes them. Reports what the push changed on
+    /// the remote.
+    pub fn push(&self, message: &str, resolve: Resolve) -> Result<SyncReport, SyncError> {
+        self.pull(resolve)?;
+        let base = self.index_at("HEAD")?;
+        let index = self.index()?;
+        self.verify(&index)?;
+
+        self.git(&["add", "-A"])?;
+        if self.git(&["diff", "--cached", "--quiet"]).is_ok() {
+            return Ok(SyncReport::default());
+        }
+
+        let parent = self.revision("HEAD");
+        let identity = self.git(&["config", "user.email"]).is_ok();
+        let mut commit = vec!["commit", "-q", "-m", message];
+        let fallback = [format!("user.name={}", FALLBACK_NAME), format!("user.email={}", FALLBACK_EMAIL)];
+        if !identity {
+            commit.splice(0..0, ["-c", fallback[0].as_str(), "-c", fallback[1].as_str()]);
+        }
+        self.git(&commit)?;
+
+        let target = format!("HEAD:refs/heads/{}", self.branch);
+        if let Err(err) = self.git(&["push", "-q", "origin", &target]) {
+            // Leave the changes in the working tree and HEAD at the last synced commit.
+            match &parent {
+                Some(parent) => self.git(&["reset", "-q", "--soft", parent])?,
+                None => self.git(&["update-ref", "-d", "HEAD"])?,
+            };
+            return match err {
+                SyncError::Git { stderr, .. } if stderr.contains("rejected") => Err(SyncError::Rejected),
+                err => Err(err),
+            };
+        }
+
+        Ok(SyncReport::between(&base, &index))
+    }
+
+    /// Checks the device files against the hashes of `index`.
+    fn verify(&self, index: &LibraryIndex) -> Result<(), SyncError> {
+        for entry in index.devices.values() {
+            let data = self.read_file(&entry.file)?.unwrap_or_default();
+            let actual = sha256_hex(&data);
+            if actual != entry.sha256 {
+                return Err(SyncError::HashMismatch { file: entry.file.clone(), expected: entry.sha256.clone(), actual });
+            }
+        }
+        Ok(())
+    }
+
+    /// The stubs of every device in the working tree, checked against their hashes.
+    pub fn load(&self) -> Result<BTreeMap<String, Vec<ArmFlashStub>>, SyncError> {
+        let index = self.index()?;
+        self.verify(&index)?;
+
+        let mut devices = BTreeMap::new();
+        for (device, entry) in index.devices {
+            let data = self.read_file(&entry.file)?.unwrap_or_default();
+            let stubs = serde_json::from_slice(&data).map_err(|err| SyncError::Model { file: entry.file, reason: err.to_string() })?;
+            devices.insert(device, stubs);
+        }
+        Ok(devices)
+    }
+
+    /// Stores every device of the library in `registry`, returning how many there were.
+    pub fn load_into(&self, registry: &StubRegistry) -> Result<usize, SyncError> {
+        let devices = self.load()?;
+        let count = devices.len();
+        for (device, stubs) in devices {
+            registry.insert(device, stubs);
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,96 @@
+//! The protobuf model of `proto/soul_composer.proto` and the generated gRPC client and server,
+//! for manufacturing backends that standardize on gRPC. `soul-composer serve --grpc-port`
+//! implements the service.
+//!
+//! Stubs carry the common fields typed and the whole JSON model in `model_json`, so converting
+//! back with `ArmFlashStub::try_from` loses nothing.
+
+use std::convert::TryFrom;
+
+use crate::{
+    diagnostic::Diagnostic,
+    prog::arm::flash_stub_gen::{ArmFlashStub, SectorRegion},
+};
+
+/// Messages, `soul_composer_client` and `soul_composer_server` generated from the schema.
+pub mod proto {
+    tonic::include_proto!("soul_composer.v1");
+}
+
+/// The blobs are base64 in the model; one that doesn't decode comes out empty, `model_json`
+/// still has it.
+fn decode_blob(blob: &str) -> Vec<u8> {
+    base64::decode(blob).unwrap_or_default()
+}
+
+impl From<&SectorRegion> for proto::SectorRegion {
+    fn from(region: &SectorRegion) -> Self {
+        proto::SectorRegion { address: region.address, size: region.size, count: region.count }
+    }
+}
+
+impl From<&ArmFlashStub> for proto::Stub {
+    fn from(stub: &ArmFlashStub) -> Self {
+        let region_kind = serde_json::to_value(stub.region_kind).ok();
+        proto::Stub {
+            name: stub.name.clone(),
+            description: stub.description.clone(),
+            default: stub.default,
+            region_kind: region_kind.as_ref().and_then(|kind| kind.as_str()).unwrap_or_default().to_string(),
+            instructions: decode_blob(&stub.instructions),
+            data_instructions: stub.data_instructions.as_deref().map(decode_blob),
+            pc_init: stub.pc_init,
+            pc_uninit: stub.pc_uninit,
+            pc_program_page: stub.pc_program_page,
+            pc_erase_sector: stub.pc_erase_sector,
+            pc_erase_all: stub.pc_erase_all,
+            data_section_offset: stub.data_section_offset,
+            static_base: stub.static_base,
+            flash_start_addr: stub.flash_start_addr,
+            flash_end_addr: stub.flash_end_addr,
+            flash_page_size: stub.flash_page_size,
+            erased_byte_value: stub.erased_byte_value.into(),
+            flash_sector_size: stub.flash_sector_size,
+            sectors: stub.sectors.iter().map(Into::into).collect(),
+            program_timeout: stub.program_timeout,
+            erase_timeout: stub.erase_timeout,
+            ram_size: stub.ram_size,
+            crc32: stub.crc32,
+            stack_usage: stub.stack_usage,
+            stack_size: stub.stack_size,
+            ram_required: stub.ram_required,
+            flash_size: stub.flash_size,
+            // The model has no maps with non-string keys, serializing it can't fail.
+            model_json: serde_json::to_string(stub).unwrap_or_default(),
+        }
+    }
+}
+
+/// Reads the stub back from `model_json`, the typed fields are only a view of it.
+impl TryFrom<&proto::Stub> for ArmFlashStub {
+    type Error = serde_json::Error;
+
+    fn try_from(stub: &proto::Stub) -> Result<Self, Self::Error> {
+        serde_json::from_str(&stub.model_json)
+    }
+}
+
+impl proto::Diagnostic {
+    /// The diagnostic, about `file` in a pack when given.
+    pub fn new(diagnostic: &Diagnostic, file: Option<String>) -> Self {
+        proto::Diagnostic {
+            severity: diagnostic.severity.to_string(),
+            code: diagnostic.code.to_string(),
+            id: diagnostic.id.map(str::to_string),
+            offset: diagnostic.offset,
+            message: diagnostic.message.clone(),
+            file,
+        }
+    }
+}
+
+impl From<&Diagnostic> for proto::Diagnostic {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        proto::Diagnostic::new(diagnostic, None)
+    }
+}
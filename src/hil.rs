@@ -0,0 +1,206 @@
+//! Hardware-in-the-loop verification of the stubs in a `StubDatabase`, the last check before a
+//! stub library ships.
+//!
+//! Each stub erases a scratch sector of a connected target, programs it with a pattern and reads
+//! it back, through a debug probe (`ProbeRsBackend`, the `probe` feature), a CMSIS-DAP probe with
+//! the built-in driver (`DapBackend`, the `dap` feature) or a Soul Injector programmer
+//! (`InjectorBackend`, the `injector` feature). Every run is recorded with the code that ran,
+//! `StubQuery::verified` then picks the stubs whose last run passed.
+//!
+//! A stub failing, whether the read back differs or a routine returns an error, is a failed run.
+//! Losing the probe or the programmer stops the whole run instead, nothing is learnt about the
+//! stubs left.
+
+use std::{
+    ops::Range,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+use crate::{
+    database::{DatabaseError, HilRun, StoredStub, StubDatabase},
+    prog::arm::validated::ValidatedArmFlashStub,
+    progress::{Progress, ProgressSink},
+};
+
+#[derive(Debug, Error)]
+pub enum HilError {
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+
+    #[cfg(feature = "probe")]
+    #[error(transparent)]
+    Probe(#[from] crate::probe::ProbeError),
+
+    #[cfg(feature = "injector")]
+    #[error(transparent)]
+    Upload(#[from] crate::injector::UploadError),
+
+    #[cfg(feature = "dap")]
+    #[error(transparent)]
+    Dap(#[from] crate::dap::DapError),
+
+    #[error(transparent)]
+    Export(#[from] crate::prog::export::export_error::ExportError),
+}
+
+/// What a backend saw running one stub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HilCheck {
+    pub passed: bool,
+    pub detail: Option<String>,
+    pub sector: Option<Range<u32>>,
+}
+
+impl HilCheck {
+    pub fn passed(sector: Range<u32>) -> Self {
+        HilCheck { passed: true, detail: None, sector: Some(sector) }
+    }
+
+    pub fn failed(detail: impl ToString, sector: Option<Range<u32>>) -> Self {
+        HilCheck { passed: false, detail: Some(detail.to_string()), sector }
+    }
+}
+
+/// Something that runs stubs on a target.
+pub trait HilBackend {
+    /// Recorded with each run.
+    fn name(&self) -> &'static str;
+
+    /// Erases, programs and reads back the sector holding `scratch`, or the last sector of the
+    /// flash, with `stub`. Errors are for the backend failing, not the stub.
+    fn check(&mut self, stub: &ValidatedArmFlashStub, scratch: Option<u32>) -> Result<HilCheck, HilError>;
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Runs every stub in `stubs` on `backend` and records the runs in `database`, returning them in
+/// the same order. Stubs that don't validate are recorded as failed without running.
+pub fn verify_stubs(
+    database: &StubDatabase,
+    backend: &mut impl HilBackend,
+    stubs: &[StoredStub],
+    scratch: Option<u32>,
+    progress: &mut impl ProgressSink,
+) -> Result<Vec<HilRun>, HilError> {
+    let mut runs = Vec::with_capacity(stubs.len());
+    for (completed, stored) in stubs.iter().enumerate() {
+        progress.progress(Progress { completed, total: stubs.len(), current: &stored.stub.name });
+
+        let started = Instant::now();
+        let check = match stored.stub.clone().validate() {
+            Ok(stub) => backend.check(&stub, scratch)?,
+            Err(err) => HilCheck::failed(err, None),
+        };
+        let run = HilRun {
+            stub_id: stored.id,
+            image_crc32: stored.image_crc32,
+            backend: backend.name().to_string(),
+            passed: check.passed,
+            detail: check.detail,
+            sector: check.sector,
+            duration_ms: started.elapsed().as_millis() as u64,
+            tested_at: now(),
+        };
+        database.record_hil(&run)?;
+        runs.push(run);
+    }
+    progress.finish(stubs.len());
+
+    Ok(runs)
+}
+
+/// Runs stubs through a debug probe with `probe::self_test`, attaching anew for each stub.
+#[cfg(feature = "probe")]
+#[derive(Debug, Clone)]
+pub struct ProbeRsBackend {
+    pub target: crate::probe::ProbeTarget,
+    pub selector: Option<probe_rs::probe::DebugProbeSelector>,
+}
+
+#[cfg(feature = "probe")]
+impl HilBackend for ProbeRsBackend {
+    fn name(&self) -> &'static str {
+        "probe-rs"
+    }
+
+    fn check(&mut self, stub: &ValidatedArmFlashStub, scratch: Option<u32>) -> Result<HilCheck, HilError> {
+        use crate::probe::{attach, self_test, ProbeError};
+
+        // No probe at all stops the run, a target the stub can't attach to fails the stub.
+        let mut session = match attach(stub, &self.target, self.selector.clone()) {
+            Ok(session) => session,
+            Err(ProbeError::NoProbe) => return Err(ProbeError::NoProbe.into()),
+            Err(err) => return Ok(HilCheck::failed(err, None)),
+        };
+        match self_test(&mut session, stub, scratch) {
+            Ok(report) => Ok(HilCheck::passed(report.sector.start as u32..report.sector.end as u32)),
+            Err(err) => Ok(HilCheck::failed(err, None)),
+        }
+    }
+}
+
+/// Runs stubs through a CMSIS-DAP probe with `dap::self_test`, `connect` it first.
+#[cfg(feature = "dap")]
+#[derive(Debug)]
+pub struct DapBackend<T> {
+    pub dap: crate::dap::CmsisDap<T>,
+    pub target: crate::dap::DapTarget,
+}
+
+#[cfg(feature = "dap")]
+impl<T: crate::dap::DapTransport> HilBackend for DapBackend<T> {
+    fn name(&self) -> &'static str {
+        "cmsis-dap"
+    }
+
+    fn check(&mut self, stub: &ValidatedArmFlashStub, scratch: Option<u32>) -> Result<HilCheck, HilError> {
+        match crate::dap::self_test(&mut self.dap, stub, &self.target, scratch) {
+            Ok(report) => Ok(HilCheck::passed(report.sector)),
+            Err(err) if err.is_link() => Err(err.into()),
+            Err(err) => Ok(HilCheck::failed(err, None)),
+        }
+    }
+}
+
+/// Runs stubs on a Soul Injector programmer: uploads each one, then sends `Verify`.
+#[cfg(feature = "injector")]
+#[derive(Debug)]
+pub struct InjectorBackend<T> {
+    pub uploader: crate::injector::Uploader<T>,
+}
+
+#[cfg(feature = "injector")]
+impl<T: std::io::Read + std::io::Write> HilBackend for InjectorBackend<T> {
+    fn name(&self) -> &'static str {
+        "injector"
+    }
+
+    fn check(&mut self, stub: &ValidatedArmFlashStub, scratch: Option<u32>) -> Result<HilCheck, HilError> {
+        use crate::{
+            injector::{FileKind, UploadError},
+            prog::export::{export_validated, OutputFormat},
+            progress::NoProgress,
+        };
+
+        let data = export_validated(stub, OutputFormat::Json)?;
+        // The programmer refusing the stub or the run fails the stub, anything else the link.
+        let report = match self
+            .uploader
+            .upload(FileKind::Stub, &stub.name, &data, &mut NoProgress)
+            .and_then(|_| self.uploader.verify(&stub.name, scratch))
+        {
+            Ok(report) => report,
+            Err(err @ UploadError::Rejected { .. }) => return Ok(HilCheck::failed(err, None)),
+            Err(err) => return Err(err.into()),
+        };
+        match (report.passed, report.mismatch) {
+            (true, _) => Ok(HilCheck::passed(report.sector)),
+            (false, Some(address)) => Ok(HilCheck::failed(format!("read back differs at {:#010x}", address), Some(report.sector))),
+            (false, None) => Ok(HilCheck::failed("the programmer reported a failure", Some(report.sector))),
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Pushes stubs and manifests onto a Soul Injector programmer over its USB CDC serial port.
+//!
+//! Every message is one frame, little endian throughout:
+//!
+//! ```text
+//! offset  size  field
+//! 0       1     magic, 0xA5
+//! 1       1     frame type
+//! 2       2     sequence number
+//! 4       2     payload length, at most MAX_PAYLOAD
+//! 6       n     payload
+//! 6 + n   4     CRC32 of bytes 1 to 6 + n, the magic excluded
+//! ```
+//!
+//! The host sends `Hello`, then per file `Begin` (kind, size, CRC32 and name), `Chunk`s of data
+//! at increasing offsets and `Finish`. The programmer answers every frame with an `Ack` carrying
+//! the same sequence number, or a `Nack` with an error code and message. A frame that isn't
+//! answered in time is sent again with the same sequence number, so the programmer acks a repeated
+//! frame without storing it twice.
+//!
+//! `Verify` (scratch address and name) asks the programmer to run a stub it stores against its
+//! target: erase the scratch sector, program it with the test pattern through the stub and read
+//! it back. Byte `i` of the pattern is `i * 31 + 7`, truncated, or its complement where that is the
+//! erased value, `ArmFlashStub::test_pattern`. The `Ack` carries a `VerifyReport`, also for
+//! a repeated `Verify`, which is answered from the last run.
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, Read, Write},
+    ops::Range,
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::progress::{Progress, ProgressSink};
+
+pub const MAGIC: u8 = 0xA5;
+
+/// Version of the protocol sent in `Hello`.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Largest payload of a frame.
+pub const MAX_PAYLOAD: usize = 4096;
+
+const HEADER_SIZE: usize = 6;
+const CRC_SIZE: usize = 4;
+
+/// Bytes of a `Begin` payload before the name: kind, size and CRC32.
+const BEGIN_HEADER_SIZE: usize = 9;
+
+/// Bytes of a `Chunk` payload before the data: the offset.
+const CHUNK_HEADER_SIZE: usize = 4;
+
+/// Bytes of a `Verify` payload before the name: the scratch address.
+const VERIFY_HEADER_SIZE: usize = 4;
+
+/// Scratch address of a `Verify` that leaves the sector to the programmer, the last one of the
+/// stub's flash.
+pub const LAST_SECTOR: u32 = u32::MAX;
+
+/// Size of a `VerifyReport`.
+const VERIFY_REPORT_SIZE: usize = 17;
+
+/// Default baud rate of the programmer. USB CDC ignores it, USB to UART bridges don't.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("Serial port error, {0}")]
+    Serial(String),
+
+    #[error("Serial I/O failed, {0}")]
+    Io(#[from] io::Error),
+
+    #[error("No answer to frame {sequence} after {attempts} attempts")]
+    Timeout { sequence: u16, attempts: u32 },
+
+    #[error("Invalid frame, {0}")]
+    BadFrame(String),
+
+    #[error("The programmer refused frame {sequence} with code {code}, {message}")]
+    Rejected { sequence: u16, code: u8, message: String },
+
+    #[error("Expected an answer to frame {expected}, got one to frame {found}")]
+    OutOfSequence { expected: u16, found: u16 },
+
+    #[error("The programmer speaks protocol version {0}, {} is supported", PROTOCOL_VERSION)]
+    Version(u8),
+
+    #[error("{name} is {size} bytes, more than a file can hold")]
+    TooLarge { name: String, size: usize },
+
+    #[error("The name {0:?} doesn't fit in a frame")]
+    NameTooLong(String),
+}
+
+/// What a frame is, the second byte of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    /// Host to programmer: protocol version.
+    Hello,
+    /// Host to programmer: file kind, size, CRC32 and name.
+    Begin,
+    /// Host to programmer: offset and data.
+    Chunk,
+    /// Host to programmer: the file is complete, check and store it.
+    Finish,
+    /// Host to programmer: scratch address and name, run the stored stub on the target.
+    Verify,
+    /// Programmer to host: the frame was taken.
+    Ack,
+    /// Programmer to host: error code and message.
+    Nack,
+}
+
+impl FrameType {
+    pub fn code(self) -> u8 {
+        match self {
+            FrameType::Hello => 0x01,
+            FrameType::Begin => 0x02,
+            FrameType::Chunk => 0x03,
+            FrameType::Finish => 0x04,
+            FrameType::Verify => 0x05,
+            FrameType::Ack => 0x80,
+            FrameType::Nack => 0x81,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(FrameType::Hello),
+            0x02 => Some(FrameType::Begin),
+            0x03 => Some(FrameType::Chunk),
+            0x04 => Some(FrameType::Finish),
+            0x05 => Some(FrameType::Verify),
+            0x80 => Some(FrameType::Ack),
+            0x81 => Some(FrameType::Nack),
+            _ => None,
+        }
+    }
+}
+
+/// One frame, see the module documentation for the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub typ: FrameType,
+    pub sequence: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(typ: FrameType, sequence: u16, payload: Vec<u8>) -> Self {
+        Frame { typ, sequence, payload }
+    }
+
+    /// The frame as sent, panics if the payload is longer than `MAX_PAYLOAD`.
+    pub fn encode(&self) -> Vec<u8> {
+        assert!(self.payload.len() <= MAX_PAYLOAD, "frame payload of {} bytes", self.payload.len());
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.payload.len() + CRC_SIZE);
+        bytes.push(MAGIC);
+        bytes.push(self.typ.code());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        let crc = crc32fast::hash(&bytes[1..]);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes the frame at the start of `data`, returning it and the bytes it took.
+    ///
+    /// `Ok(None)` means `data` ends before the frame does. Bytes before the magic are an error,
+    /// skip to the next magic to resynchronise.
+    pub fn decode(data: &[u8]) -> Result<Option<(Frame, usize)>, UploadError> {
+        if data.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        if data[0] != MAGIC {
+            return Err(UploadError::BadFrame(format!("expected magic {:#04x}, got {:#04x}", MAGIC, data[0])));
+        }
+
+        let length = usize::from(u16::from_le_bytes([data[4], data[5]]));
+        if length > MAX_PAYLOAD {
+            return Err(UploadError::BadFrame(format!("payload of {} bytes", length)));
+        }
+        let size = HEADER_SIZE + length + CRC_SIZE;
+        if data.len() < size {
+            return Ok(None);
+        }
+
+        let crc = u32::from_le_bytes([data[size - 4], data[size - 3], data[size - 2], data[size - 1]]);
+        let expected = crc32fast::hash(&data[1..size - CRC_SIZE]);
+        if crc != expected {
+            return Err(UploadError::BadFrame(format!("CRC32 {:#010x}, expected {:#010x}", crc, expected)));
+        }
+        let typ = FrameType::from_code(data[1])
+            .ok_or_else(|| UploadError::BadFrame(format!("unknown frame type {:#04x}", data[1])))?;
+        let sequence = u16::from_le_bytes([data[2], data[3]]);
+
+        Ok(Some((Frame::new(typ, sequence, data[HEADER_SIZE..HEADER_SIZE + length].to_vec()), size)))
+    }
+
+    /// Reads one frame, skipping whatever comes before the magic.
+    pub fn read_from(reader: &mut impl Read) -> Result<Frame, UploadError> {
+        let mut byte = [0];
+        loop {
+            reader.read_exact(&mut byte)?;
+            if byte[0] == MAGIC {
+                break;
+            }
+        }
+
+        let mut data = vec![MAGIC; HEADER_SIZE];
+        reader.read_exact(&mut data[1..])?;
+        let length = usize::from(u16::from_le_bytes([data[4], data[5]]));
+        if length > MAX_PAYLOAD {
+            return Err(UploadError::BadFrame(format!("payload of {} bytes", length)));
+        }
+        data.resize(HEADER_SIZE + length + CRC_SIZE, 0);
+        reader.read_exact(&mut data[HEADER_SIZE..])?;
+
+        match Frame::decode(&data)? {
+            Some((frame, _)) => Ok(frame),
+            None => unreachable!("the whole frame was read"),
+        }
+    }
+}
+
+/// What an uploaded file holds, the first byte of a `Begin` payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// One algorithm, a stub written by `convert`.
+    Stub,
+    /// Algorithms grouped by bank, written by `merge`.
+    Manifest,
+}
+
+impl FileKind {
+    pub fn code(self) -> u8 {
+        match self {
+            FileKind::Stub => 1,
+            FileKind::Manifest => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(FileKind::Stub),
+            2 => Some(FileKind::Manifest),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileKind::Stub => f.write_str("stub"),
+            FileKind::Manifest => f.write_str("manifest"),
+        }
+    }
+}
+
+/// The `Begin` payload: kind, size, CRC32, then the name up to the end of the frame.
+pub fn begin_payload(kind: FileKind, name: &str, data: &[u8]) -> Vec<u8> {
+    let mut payload = vec![kind.code()];
+    payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    payload.extend_from_slice(name.as_bytes());
+    payload
+}
+
+/// The `Verify` payload: scratch address, `LAST_SECTOR` when `None`, then the stub's name.
+pub fn verify_payload(name: &str, scratch: Option<u32>) -> Vec<u8> {
+    let mut payload = scratch.unwrap_or(LAST_SECTOR).to_le_bytes().to_vec();
+    payload.extend_from_slice(name.as_bytes());
+    payload
+}
+
+/// What the programmer found running a stub, the `Ack` payload of `Verify`:
+///
+/// ```text
+/// offset  size  field
+/// 0       1     1 when the read back matched
+/// 1       4     start of the scratch sector
+/// 5       4     end of the scratch sector
+/// 9       4     first address that didn't match, 0xFFFFFFFF when none
+/// 13      4     milliseconds from Init to the read back
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub passed: bool,
+    pub sector: Range<u32>,
+    pub mismatch: Option<u32>,
+    pub elapsed: Duration,
+}
+
+impl VerifyReport {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = vec![u8::from(self.passed)];
+        payload.extend_from_slice(&self.sector.start.to_le_bytes());
+        payload.extend_from_slice(&self.sector.end.to_le_bytes());
+        payload.extend_from_slice(&self.mismatch.unwrap_or(u32::MAX).to_le_bytes());
+        payload.extend_from_slice(&(self.elapsed.as_millis() as u32).to_le_bytes());
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, UploadError> {
+        if payload.len() < VERIFY_REPORT_SIZE {
+            return Err(UploadError::BadFrame(format!("Verify answered with {} bytes", payload.len())));
+        }
+        let word = |at: usize| u32::from_le_bytes([payload[at], payload[at + 1], payload[at + 2], payload[at + 3]]);
+        Ok(VerifyReport {
+            passed: payload[0] == 1,
+            sector: word(1)..word(5),
+            mismatch: Some(word(9)).filter(|address| *address != u32::MAX),
+            elapsed: Duration::from_millis(word(13).into()),
+        })
+    }
+}
+
+/// Talks to a programmer over a serial port or anything else that reads and writes bytes.
+///
+/// Reads must time out, with `io::ErrorKind::TimedOut` or `WouldBlock`, for lost frames to be
+/// sent again; `open_serial` sets that up.
+#[derive(Debug)]
+pub struct Uploader<T> {
+    transport: T,
+    sequence: u16,
+    /// Data bytes per `Chunk`, the smaller of ours and the programmer's limit.
+    chunk_size: usize,
+    /// Times a frame is sent before giving up.
+    attempts: u32,
+}
+
+impl<T: Read + Write> Uploader<T> {
+    /// Greets the programmer and agrees on the chunk size.
+    ///
+    /// The `Ack` to `Hello` carries the programmer's protocol version and the largest payload it
+    /// takes.
+    pub fn connect(transport: T) -> Result<Self, UploadError> {
+        let mut uploader = Uploader { transport, sequence: 0, chunk_size: MAX_PAYLOAD - CHUNK_HEADER_SIZE, attempts: 3 };
+
+        let reply = uploader.send(FrameType::Hello, vec![PROTOCOL_VERSION])?;
+        match reply.as_slice() {
+            [version, low, high, ..] => {
+                if *version != PROTOCOL_VERSION {
+                    return Err(UploadError::Version(*version));
+                }
+                let max_payload = usize::from(u16::from_le_bytes([*low, *high])).min(MAX_PAYLOAD);
+                if max_payload <= CHUNK_HEADER_SIZE {
+                    return Err(UploadError::BadFrame(format!("largest payload of {} bytes", max_payload)));
+                }
+                uploader.chunk_size = max_payload - CHUNK_HEADER_SIZE;
+            }
+            _ => return Err(UploadError::BadFrame(format!("Hello answered with {} bytes", reply.len()))),
+        }
+
+        Ok(uploader)
+    }
+
+    /// Times each frame is sent before giving up, at least one.
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Uploads one file, reporting the bytes the programmer acked so far to `progress`.
+    pub fn upload(
+        &mut self,
+        kind: FileKind,
+        name: &str,
+        data: &[u8],
+        progress: &mut impl ProgressSink,
+    ) -> Result<(), UploadError> {
+        if u32::try_from(data.len()).is_err() {
+            return Err(UploadError::TooLarge { name: name.to_string(), size: data.len() });
+        }
+        if BEGIN_HEADER_SIZE + name.len() > MAX_PAYLOAD {
+            return Err(UploadError::NameTooLong(name.to_string()));
+        }
+
+        self.send(FrameType::Begin, begin_payload(kind, name, data))?;
+        let mut offset = 0;
+        for chunk in data.chunks(self.chunk_size) {
+            progress.progress(Progress { completed: offset, total: data.len(), current: name });
+            let mut payload = (offset as u32).to_le_bytes().to_vec();
+            payload.extend_from_slice(chunk);
+            self.send(FrameType::Chunk, payload)?;
+            offset += chunk.len();
+        }
+        self.send(FrameType::Finish, Vec::new())?;
+        progress.finish(data.len());
+
+        Ok(())
+    }
+
+    /// Runs the stub uploaded as `name` on the programmer's target, see `VerifyReport`.
+    ///
+    /// The sector holding `scratch`, or the last sector of the stub's flash, is overwritten.
+    pub fn verify(&mut self, name: &str, scratch: Option<u32>) -> Result<VerifyReport, UploadError> {
+        if VERIFY_HEADER_SIZE + name.len() > MAX_PAYLOAD {
+            return Err(UploadError::NameTooLong(name.to_string()));
+        }
+        VerifyReport::decode(&self.send(FrameType::Verify, verify_payload(name, scratch))?)
+    }
+
+    /// Sends a frame until it is answered, returning the `Ack` payload.
+    fn send(&mut self, typ: FrameType, payload: Vec<u8>) -> Result<Vec<u8>, UploadError> {
+        let frame = Frame::new(typ, self.sequence, payload).encode();
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        for attempt in 1..=self.attempts {
+            self.transport.write_all(&frame)?;
+            self.transport.flush()?;
+
+            let reply = match Frame::read_from(&mut self.transport) {
+                Ok(reply) => reply,
+                Err(UploadError::Io(err)) if matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+                    tracing::debug!(sequence, attempt, "no answer, sending again");
+                    continue;
+                }
+                Err(UploadError::BadFrame(reason)) => {
+                    tracing::debug!(sequence, attempt, %reason, "garbled answer, sending again");
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            if reply.sequence != sequence {
+                return Err(UploadError::OutOfSequence { expected: sequence, found: reply.sequence });
+            }
+            return match reply.typ {
+                FrameType::Ack => Ok(reply.payload),
+                FrameType::Nack => {
+                    let code = reply.payload.first().copied().unwrap_or_default();
+                    let message = String::from_utf8_lossy(reply.payload.get(1..).unwrap_or_default()).into_owned();
+                    Err(UploadError::Rejected { sequence, code, message })
+                }
+                other => Err(UploadError::BadFrame(format!("{:?} sent by the programmer", other))),
+            };
+        }
+
+        Err(UploadError::Timeout { sequence, attempts: self.attempts })
+    }
+}
+
+/// Opens the programmer's serial port, reads time out after a second.
+pub fn open_serial(path: &str, baud_rate: u32) -> Result<Box<dyn serialport::SerialPort>, UploadError> {
+    serialport::new(path, baud_rate)
+        .timeout(std::time::Duration::from_secs(1))
+        .open()
+        .map_err(|err| UploadError::Serial(err.to_string()))
+}
@@ -1,19 +1,85 @@
+//! Turns flash algorithms into stubs for the Soul Injector programmer.
+//!
+//! The common types are re-exported here and in `prelude`: parse an FLM with `Elf::parse`, then
+//! `compose_stub` gives the `ArmFlashStub` that `export` writes out. The modules below hold the
+//! rest, other architectures, CMSIS packs and the bindings.
+
+#[cfg(feature = "wasm")]
 mod utils;
+#[cfg(feature = "artifact")]
+pub mod artifact;
+pub mod compose;
+#[cfg(feature = "dap")]
+pub mod dap;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod diagnostic;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "hil")]
+pub mod hil;
+#[cfg(feature = "injector")]
+pub mod injector;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "msc")]
+pub mod msc;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "git")]
+pub mod git_library;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+#[cfg(feature = "ota")]
+pub mod ota;
+pub mod pack;
+#[cfg(feature = "probe")]
+pub mod probe;
+pub mod prog;
+pub mod progress;
+#[cfg(feature = "s3")]
+pub mod publish;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod registry;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use compose::arm::{compose_stub, ComposeOptions, Elf};
+pub use diagnostic::{Diagnostic, Severity};
+pub use prog::{
+    arm::{
+        arm_error::ArmError,
+        flash_device::{FlashDevice, SectorInfo},
+        flash_stub_gen::ArmFlashStub,
+        parse_options::{ConflictPolicy, ParseOptions, Strictness},
+        stub_diff::{FieldChange, StubDiff},
+        validated::ValidatedArmFlashStub,
+    },
+    export::{export, export_error::ExportError, export_validated, OutputFormat},
+    prog_error::ProgError,
+};
+pub use progress::{NoProgress, Progress, ProgressSink};
+pub use registry::StubRegistry;
 
-use wasm_bindgen::prelude::*;
+/// The types most callers need, for a glob import.
+pub mod prelude {
+    pub use crate::{
+        compose_stub, export, ArmError, ArmFlashStub, ComposeOptions, Diagnostic, Elf, ExportError, FlashDevice,
+        OutputFormat, ParseOptions, ProgressSink,
+    };
+}
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-
-#[wasm_bindgen]
-extern {
-    fn alert(s: &str);
-}
-
-#[wasm_bindgen]
-pub fn greet() {
-    alert("Hello, soulcomposer!");
-}
@@ -0,0 +1,420 @@
+//! Announcing new and updated stubs to deployed programmers through an MQTT broker.
+//!
+//! Each algorithm is a retained message on its device's topic, so a programmer subscribing to
+//! `<prefix>/devices/<device>/#` gets every current algorithm of its target on connect and every
+//! update after that. A notice of each change also goes to `<prefix>/updates`, not retained, for
+//! dashboards and loggers. An empty retained message withdraws an algorithm.
+//!
+//! Only the part of MQTT 3.1.1 a publisher needs is spoken: CONNECT, PUBLISH at QoS 0 or 1, PING
+//! and DISCONNECT, over plain TCP. Brokers that require TLS are reached through a local bridge.
+
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Protocol level of MQTT 3.1.1 in CONNECT.
+const PROTOCOL_LEVEL: u8 = 4;
+
+/// Largest remaining length the four length bytes can express.
+const MAX_REMAINING_LENGTH: usize = 268_435_455;
+
+pub const DEFAULT_PORT: u16 = 1883;
+
+#[derive(Debug, Error)]
+pub enum MqttError {
+    #[error("Broker connection failed, {0}")]
+    Io(#[from] io::Error),
+
+    #[error("The broker refused the connection, {}", refusal(*.0))]
+    Refused(u8),
+
+    #[error("Invalid packet from the broker, {0}")]
+    Protocol(String),
+
+    #[error("Expected an acknowledgement of packet {expected}, got one of {found}")]
+    OutOfSequence { expected: u16, found: u16 },
+
+    #[error("{0} is too long for an MQTT packet")]
+    TooLarge(String),
+}
+
+fn refusal(code: u8) -> &'static str {
+    match code {
+        1 => "unacceptable protocol version",
+        2 => "client identifier rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown return code",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QoS {
+    AtMostOnce,
+    /// The broker acknowledges every message, which is sent again by the next run if it doesn't.
+    #[default]
+    AtLeastOnce,
+}
+
+/// The control packets of a publishing client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Connect { client_id: String, username: Option<String>, password: Option<String>, keep_alive: u16 },
+    ConnAck { session_present: bool, code: u8 },
+    Publish { topic: String, payload: Vec<u8>, qos: QoS, retain: bool, packet_id: Option<u16> },
+    PubAck { packet_id: u16 },
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+fn put_string(buffer: &mut Vec<u8>, text: &[u8]) -> Result<(), MqttError> {
+    let length = u16::try_from(text.len()).map_err(|_| MqttError::TooLarge(String::from_utf8_lossy(text).into_owned()))?;
+    buffer.extend_from_slice(&length.to_be_bytes());
+    buffer.extend_from_slice(text);
+    Ok(())
+}
+
+/// Reads from a packet body, failing on truncation.
+struct Body<'a>(&'a [u8]);
+
+impl<'a> Body<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], MqttError> {
+        if self.0.len() < count {
+            return Err(MqttError::Protocol("packet ends early".to_string()));
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, MqttError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MqttError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], MqttError> {
+        let length = self.u16()? as usize;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, MqttError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| MqttError::Protocol("string isn't UTF-8".to_string()))
+    }
+}
+
+impl Packet {
+    /// The packet as sent on the wire.
+    pub fn encode(&self) -> Result<Vec<u8>, MqttError> {
+        let mut body = Vec::new();
+        let first = match self {
+            Packet::Connect { client_id, username, password, keep_alive } => {
+                put_string(&mut body, b"MQTT")?;
+                body.push(PROTOCOL_LEVEL);
+                // Always a clean session, the publisher keeps no state across runs.
+                let flags = 0x02 | if username.is_some() { 0x80 } else { 0 } | if password.is_some() { 0x40 } else { 0 };
+                body.push(flags);
+                body.extend_from_slice(&keep_alive.to_be_bytes());
+                put_string(&mut body, client_id.as_bytes())?;
+                for field in [username, password].iter().copied().flatten() {
+                    put_string(&mut body, field.as_bytes())?;
+                }
+                0x10
+            }
+            Packet::ConnAck { session_present, code } => {
+                body.extend_from_slice(&[*session_present as u8, *code]);
+                0x20
+            }
+            Packet::Publish { topic, payload, qos, retain, packet_id } => {
+                put_string(&mut body, topic.as_bytes())?;
+                if let Some(packet_id) = packet_id {
+                    body.extend_from_slice(&packet_id.to_be_bytes());
+                }
+                body.extend_from_slice(payload);
+                let qos = match qos {
+                    QoS::AtMostOnce => 0,
+                    QoS::AtLeastOnce => 1,
+                };
+                0x30 | qos << 1 | *retain as u8
+            }
+            Packet::PubAck { packet_id } => {
+                body.extend_from_slice(&packet_id.to_be_bytes());
+                0x40
+            }
+            Packet::PingReq => 0xC0,
+            Packet::PingResp => 0xD0,
+            Packet::Disconnect => 0xE0,
+        };
+
+        if body.len() > MAX_REMAINING_LENGTH {
+            return Err(MqttError::TooLarge(format!("a payload of {} bytes", body.len())));
+        }
+        let mut packet = vec![first];
+        let mut length = body.len();
+        loop {
+            let byte = (length % 128) as u8;
+            length /= 128;
+            packet.push(if length > 0 { byte | 0x80 } else { byte });
+            if length == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(&body);
+        Ok(packet)
+    }
+
+    /// Reads one packet, blocking until it is complete.
+    pub fn read_from(reader: &mut impl Read) -> Result<Packet, MqttError> {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        let first = byte[0];
+
+        let mut length = 0usize;
+        for shift in 0..4 {
+            reader.read_exact(&mut byte)?;
+            length |= ((byte[0] & 0x7F) as usize) << (7 * shift);
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            if shift == 3 {
+                return Err(MqttError::Protocol("remaining length over four bytes".to_string()));
+            }
+        }
+        let mut data = vec![0; length];
+        reader.read_exact(&mut data)?;
+        let mut body = Body(&data);
+
+        let packet = match first >> 4 {
+            1 => {
+                let protocol = body.bytes()?;
+                let level = body.u8()?;
+                if protocol != b"MQTT" || level != PROTOCOL_LEVEL {
+                    return Err(MqttError::Protocol(format!("protocol {:?} level {}", String::from_utf8_lossy(protocol), level)));
+                }
+                let flags = body.u8()?;
+                let keep_alive = body.u16()?;
+                let client_id = body.string()?;
+                let username = if flags & 0x80 != 0 { Some(body.string()?) } else { None };
+                let password = if flags & 0x40 != 0 { Some(body.string()?) } else { None };
+                Packet::Connect { client_id, username, password, keep_alive }
+            }
+            2 => Packet::ConnAck { session_present: body.u8()? & 1 != 0, code: body.u8()? },
+            3 => {
+                let qos = match (first >> 1) & 0x03 {
+                    0 => QoS::AtMostOnce,
+                    1 => QoS::AtLeastOnce,
+                    qos => return Err(MqttError::Protocol(format!("unsupported QoS {}", qos))),
+                };
+                let topic = body.string()?;
+                let packet_id = if qos == QoS::AtMostOnce { None } else { Some(body.u16()?) };
+                Packet::Publish { topic, payload: body.0.to_vec(), qos, retain: first & 0x01 != 0, packet_id }
+            }
+            4 => Packet::PubAck { packet_id: body.u16()? },
+            12 => Packet::PingReq,
+            13 => Packet::PingResp,
+            14 => Packet::Disconnect,
+            kind => return Err(MqttError::Protocol(format!("unexpected packet type {}", kind))),
+        };
+        Ok(packet)
+    }
+}
+
+/// How to log in to the broker.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Seconds the broker waits without a packet before dropping the client.
+    pub keep_alive: u16,
+}
+
+impl ConnectOptions {
+    pub fn new(client_id: impl Into<String>) -> Self {
+        ConnectOptions { client_id: client_id.into(), username: None, password: None, keep_alive: 60 }
+    }
+
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+}
+
+/// A connection to the broker that publishes.
+#[derive(Debug)]
+pub struct MqttClient<T> {
+    stream: T,
+    next_id: u16,
+}
+
+impl<T: Read + Write> MqttClient<T> {
+    /// Sends CONNECT over `stream` and waits for the broker to accept it.
+    pub fn connect(mut stream: T, options: &ConnectOptions) -> Result<Self, MqttError> {
+        let connect = Packet::Connect {
+            client_id: options.client_id.clone(),
+            username: options.username.clone(),
+            password: options.password.clone(),
+            keep_alive: options.keep_alive,
+        };
+        stream.write_all(&connect.encode()?)?;
+        stream.flush()?;
+
+        match Packet::read_from(&mut stream)? {
+            Packet::ConnAck { code: 0, .. } => Ok(MqttClient { stream, next_id: 1 }),
+            Packet::ConnAck { code, .. } => Err(MqttError::Refused(code)),
+            other => Err(MqttError::Protocol(format!("expected CONNACK, got {:?}", other))),
+        }
+    }
+
+    /// Publishes `payload` on `topic`, waiting for the broker's acknowledgement at QoS 1.
+    pub fn publish(&mut self, topic: &str, payload: &[u8], qos: QoS, retain: bool) -> Result<(), MqttError> {
+        let packet_id = match qos {
+            QoS::AtMostOnce => None,
+            QoS::AtLeastOnce => {
+                let id = self.next_id;
+                // Packet identifiers are non-zero.
+                self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+                Some(id)
+            }
+        };
+        let publish = Packet::Publish { topic: topic.to_string(), payload: payload.to_vec(), qos, retain, packet_id };
+        self.stream.write_all(&publish.encode()?)?;
+        self.stream.flush()?;
+
+        if let Some(expected) = packet_id {
+            loop {
+                match Packet::read_from(&mut self.stream)? {
+                    Packet::PubAck { packet_id } if packet_id == expected => break,
+                    Packet::PubAck { packet_id } => return Err(MqttError::OutOfSequence { expected, found: packet_id }),
+                    // Nothing is subscribed, anything else is noise from the broker.
+                    _ => continue,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the broker still answers.
+    pub fn ping(&mut self) -> Result<(), MqttError> {
+        self.stream.write_all(&Packet::PingReq.encode()?)?;
+        self.stream.flush()?;
+        match Packet::read_from(&mut self.stream)? {
+            Packet::PingResp => Ok(()),
+            other => Err(MqttError::Protocol(format!("expected PINGRESP, got {:?}", other))),
+        }
+    }
+
+    /// Says goodbye, giving the stream back.
+    pub fn disconnect(mut self) -> Result<T, MqttError> {
+        self.stream.write_all(&Packet::Disconnect.encode()?)?;
+        self.stream.flush()?;
+        Ok(self.stream)
+    }
+}
+
+/// Connects to the broker at `address`, `host` or `host:port`.
+pub fn connect_tcp(address: &str, options: &ConnectOptions) -> Result<MqttClient<TcpStream>, MqttError> {
+    let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+    let target = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't resolve", address)))?;
+    let stream = TcpStream::connect_timeout(&target, Duration::from_secs(10))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    MqttClient::connect(stream, options)
+}
+
+/// A topic level from a name, with the separator and wildcards replaced.
+fn topic_level(name: &str) -> String {
+    name.chars().map(|c| if matches!(c, '/' | '+' | '#' | '\0') { '_' } else { c }).collect()
+}
+
+/// What an announced algorithm is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateKind {
+    Stub,
+    Manifest,
+}
+
+/// The notice on the updates topic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StubUpdate {
+    pub device: String,
+    pub name: String,
+    pub kind: UpdateKind,
+    /// Topic the algorithm is retained on.
+    pub topic: String,
+    /// CRC32 and size of the retained payload, empty for a withdrawn algorithm.
+    pub crc32: u32,
+    pub size: u64,
+}
+
+/// Topics of a fleet under a prefix, e.g. `soul-injector` or `factory/line-3`.
+#[derive(Debug, Clone)]
+pub struct FleetTopics {
+    prefix: String,
+}
+
+impl FleetTopics {
+    pub fn new(prefix: &str) -> Self {
+        FleetTopics { prefix: prefix.trim_matches('/').to_string() }
+    }
+
+    /// Where the algorithm `name` of `device` is retained.
+    pub fn algorithm(&self, device: &str, name: &str) -> String {
+        format!("{}/devices/{}/{}", self.prefix, topic_level(device), topic_level(name))
+    }
+
+    /// Filter a programmer of `device` subscribes to.
+    pub fn device_filter(&self, device: &str) -> String {
+        format!("{}/devices/{}/#", self.prefix, topic_level(device))
+    }
+
+    pub fn updates(&self) -> String {
+        format!("{}/updates", self.prefix)
+    }
+
+    /// Retains `payload` as the algorithm `name` of `device` and posts the notice of it.
+    pub fn announce<T: Read + Write>(
+        &self,
+        client: &mut MqttClient<T>,
+        device: &str,
+        kind: UpdateKind,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<StubUpdate, MqttError> {
+        let topic = self.algorithm(device, name);
+        client.publish(&topic, payload, QoS::AtLeastOnce, true)?;
+
+        let update = StubUpdate {
+            device: device.to_string(),
+            name: name.to_string(),
+            kind,
+            topic,
+            crc32: crc32fast::hash(payload),
+            size: payload.len() as u64,
+        };
+        let notice = serde_json::to_vec(&update).map_err(|err| MqttError::Protocol(err.to_string()))?;
+        client.publish(&self.updates(), &notice, QoS::AtLeastOnce, false)?;
+        Ok(update)
+    }
+
+    /// Clears the retained algorithm `name` of `device`, programmers drop it.
+    pub fn withdraw<T: Read + Write>(&self, client: &mut MqttClient<T>, device: &str, kind: UpdateKind, name: &str) -> Result<StubUpdate, MqttError> {
+        self.announce(client, device, kind, name, &[])
+    }
+}
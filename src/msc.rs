@@ -0,0 +1,212 @@
+//! The directory layout a Soul Injector programmer reads from its USB mass-storage volume, so
+//! operators update programmers by copying a directory onto the drive instead of `upload`.
+//!
+//! ```text
+//! soul/
+//!   manifest.json      every file below: name, kind, path, size, CRC32 and SHA-256
+//!   stubs/<name>.json  one stub each, as `convert` writes it
+//!   groups/<name>.json stubs grouped by bank, as `merge` writes them
+//!   SHA256SUMS         the same files in `sha256sum -c` format, for checking a copy on a host
+//! ```
+//!
+//! The programmer reads `manifest.json` once the volume is ejected and installs each file whose
+//! size and CRC32 match, so a copy cut short installs nothing half written. Files it doesn't list
+//! are ignored. The volume is FAT, file names keep to letters, digits, `-` and `_`.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::prog::{
+    arm::{stub_group::ArmFlashStubGroup, validated::ValidatedArmFlashStub},
+    export::{export_error::ExportError, export_model, export_validated, OutputFormat},
+};
+
+/// Directory of the layout at the root of the volume.
+pub const ROOT: &str = "soul";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const CHECKSUM_FILE: &str = "SHA256SUMS";
+
+/// Version of the layout in `manifest.json`, raised when programmers have to read it differently.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// Longest file name stem, leaving room for the extension within FAT's 255 characters.
+const MAX_STEM: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum MscError {
+    #[error("{name} and {other} are both stored as {path}")]
+    Duplicate { name: String, other: String, path: String },
+
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Invalid {path}, {reason}")]
+    Manifest { path: PathBuf, reason: String },
+
+    #[error(transparent)]
+    Export(#[from] ExportError),
+}
+
+/// What a file of the layout holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MscFileKind {
+    Stub,
+    Group,
+}
+
+impl MscFileKind {
+    fn directory(self) -> &'static str {
+        match self {
+            MscFileKind::Stub => "stubs",
+            MscFileKind::Group => "groups",
+        }
+    }
+}
+
+/// An entry of `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MscEntry {
+    pub name: String,
+    pub kind: MscFileKind,
+    /// Path from the root of the volume, `/` separated.
+    pub path: String,
+    pub size: u64,
+    pub crc32: u32,
+    pub sha256: String,
+}
+
+/// `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MscManifest {
+    pub layout_version: u32,
+    pub files: Vec<MscEntry>,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `name` as a file name stem FAT takes, "stub" if nothing usable is left.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .take(MAX_STEM)
+        .collect();
+    match stem.is_empty() {
+        true => "stub".to_string(),
+        false => stem,
+    }
+}
+
+/// The files of a layout, built up one stub or group at a time.
+#[derive(Debug, Clone, Default)]
+pub struct MscLayout {
+    entries: Vec<MscEntry>,
+    /// Contents by path. FAT ignores case, so the keys are lowercase.
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl MscLayout {
+    pub fn new() -> Self {
+        MscLayout::default()
+    }
+
+    fn add(&mut self, kind: MscFileKind, name: &str, data: Vec<u8>) -> Result<&MscEntry, MscError> {
+        let path = format!("{}/{}/{}.json", ROOT, kind.directory(), file_stem(name));
+        if let Some(other) = self.entries.iter().find(|entry| entry.path.eq_ignore_ascii_case(&path)) {
+            return Err(MscError::Duplicate { name: name.to_string(), other: other.name.clone(), path });
+        }
+
+        self.entries.push(MscEntry {
+            name: name.to_string(),
+            kind,
+            path: path.clone(),
+            size: data.len() as u64,
+            crc32: crc32fast::hash(&data),
+            sha256: sha256_hex(&data),
+        });
+        self.files.insert(path.to_ascii_lowercase(), data);
+        Ok(&self.entries[self.entries.len() - 1])
+    }
+
+    pub fn add_stub(&mut self, stub: &ValidatedArmFlashStub) -> Result<&MscEntry, MscError> {
+        let data = export_validated(stub, OutputFormat::Json)?;
+        self.add(MscFileKind::Stub, &stub.name, data)
+    }
+
+    pub fn add_group(&mut self, group: &ArmFlashStubGroup) -> Result<&MscEntry, MscError> {
+        let data = export_model(group, OutputFormat::Json)?;
+        self.add(MscFileKind::Group, &group.name, data)
+    }
+
+    pub fn manifest(&self) -> MscManifest {
+        MscManifest { layout_version: LAYOUT_VERSION, files: self.entries.clone() }
+    }
+
+    /// Every file of the layout by its path from the root of the volume, the manifest and the
+    /// checksums included.
+    pub fn files(&self) -> Vec<(String, Vec<u8>)> {
+        let mut files: Vec<(String, Vec<u8>)> =
+            self.entries.iter().map(|entry| (entry.path.clone(), self.files[&entry.path.to_ascii_lowercase()].clone())).collect();
+
+        let mut manifest = serde_json::to_vec_pretty(&self.manifest()).expect("the manifest serializes");
+        manifest.push(b'\n');
+        // Paths in SHA256SUMS are relative to it, `cd soul && sha256sum -c SHA256SUMS`.
+        let prefix = format!("{}/", ROOT);
+        let mut checksums = format!("{}  {}\n", sha256_hex(&manifest), MANIFEST_FILE);
+        for entry in &self.entries {
+            checksums.push_str(&format!("{}  {}\n", entry.sha256, entry.path.trim_start_matches(&prefix)));
+        }
+
+        files.push((format!("{}/{}", ROOT, MANIFEST_FILE), manifest));
+        files.push((format!("{}/{}", ROOT, CHECKSUM_FILE), checksums.into_bytes()));
+        files
+    }
+
+    /// Writes the layout below `directory`, e.g. the mounted volume, returning the paths written.
+    /// Files already there are left alone.
+    pub fn write_to(&self, directory: &Path) -> Result<Vec<PathBuf>, MscError> {
+        let mut written = Vec::new();
+        for (path, data) in self.files() {
+            let path = directory.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|source| MscError::Io { path: parent.to_path_buf(), source })?;
+            }
+            fs::write(&path, data).map_err(|source| MscError::Io { path: path.clone(), source })?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// Reads the layout below `directory` back, returning the entries of `manifest.json` whose file
+/// is missing or differs from it. Run on the mounted volume after copying.
+pub fn check(directory: &Path) -> Result<Vec<MscEntry>, MscError> {
+    let path = directory.join(ROOT).join(MANIFEST_FILE);
+    let data = fs::read(&path).map_err(|source| MscError::Io { path: path.clone(), source })?;
+    let manifest: MscManifest = serde_json::from_slice(&data).map_err(|err| MscError::Manifest { path, reason: err.to_string() })?;
+
+    let mut bad = Vec::new();
+    for entry in manifest.files {
+        let matches = match fs::read(directory.join(&entry.path)) {
+            Ok(data) => data.len() as u64 == entry.size && crc32fast::hash(&data) == entry.crc32 && sha256_hex(&data) == entry.sha256,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+            Err(source) => return Err(MscError::Io { path: directory.join(&entry.path), source }),
+        };
+        if !matches {
+            bad.push(entry);
+        }
+    }
+    Ok(bad)
+}
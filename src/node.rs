@@ -0,0 +1,94 @@
+//! Node.js bindings, for operator UIs converting algorithms locally. Built with `napi build`,
+//! see `package.json`.
+
+use napi::{bindgen_prelude::Buffer, Error, Result};
+use napi_derive::napi;
+
+use crate::{
+    compose::arm::{compose_stub, ComposeOptions, Elf},
+    prog::{
+        arm::{flash_device::FlashDevice, flash_stub_gen::ArmFlashStub},
+        export::{export, OutputFormat},
+    },
+};
+
+fn js_error(err: impl ToString) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+/// One run of equally sized sectors, `address` relative to the flash start.
+#[napi(object)]
+pub struct Sector {
+    pub address: u32,
+    pub size: u32,
+}
+
+/// The FlashDevice of an FLM.
+#[napi(object)]
+pub struct FlashDeviceInfo {
+    pub driver_version: u32,
+    pub name: String,
+    pub device_type: u32,
+    pub start_address: u32,
+    pub device_size: u32,
+    pub page_size: u32,
+    pub erased_default_value: u32,
+    pub program_page_timeout: u32,
+    pub erase_sector_timeout: u32,
+    pub sectors: Vec<Sector>,
+}
+
+/// How `convertFlm` and `exportFlm` name and size the stub.
+#[napi(object)]
+#[derive(Default)]
+pub struct ConvertOptions {
+    /// `flash` when not given.
+    pub name: Option<String>,
+    pub default: Option<bool>,
+    /// RAM of the target, the computed requirement when not given.
+    pub ram_size: Option<u32>,
+}
+
+fn compose(data: &[u8], options: Option<ConvertOptions>) -> Result<ArmFlashStub> {
+    let options = options.unwrap_or_default();
+    let elf = Elf::parse(data).map_err(js_error)?;
+    let mut compose = ComposeOptions::new(options.name.unwrap_or_else(|| "flash".to_string()));
+    compose.default = options.default.unwrap_or(false);
+    compose.ram_size = options.ram_size.unwrap_or(0);
+    compose_stub(&elf, data, &compose).map(|(stub, _)| stub).map_err(js_error)
+}
+
+/// Parses the FlashDevice of an FLM.
+#[napi]
+pub fn parse_flm(data: Buffer) -> Result<FlashDeviceInfo> {
+    let elf = Elf::parse(&data).map_err(js_error)?;
+    let device = FlashDevice::from_elf(&elf, &data).map_err(js_error)?;
+    Ok(FlashDeviceInfo {
+        driver_version: device.driver_version().into(),
+        name: device.name().to_string(),
+        device_type: device.device_type().into(),
+        start_address: device.start_address(),
+        device_size: device.device_size(),
+        page_size: device.page_size(),
+        erased_default_value: device.erased_default_value().into(),
+        program_page_timeout: device.program_page_timeout(),
+        erase_sector_timeout: device.erase_sector_timeout(),
+        sectors: device.sectors().iter().map(|sector| Sector { address: sector.address, size: sector.size }).collect(),
+    })
+}
+
+/// Converts an FLM into the stub JSON Soul Injector reads.
+#[napi]
+pub fn convert_flm(data: Buffer, options: Option<ConvertOptions>) -> Result<String> {
+    let stub = compose(&data, options)?;
+    let json = export(&stub, OutputFormat::Json).map_err(js_error)?;
+    String::from_utf8(json).map_err(js_error)
+}
+
+/// Converts an FLM into `format`, one of the `--output-format` names of the command line.
+#[napi]
+pub fn export_flm(data: Buffer, format: String, options: Option<ConvertOptions>) -> Result<Buffer> {
+    let format: OutputFormat = format.parse().map_err(js_error)?;
+    let stub = compose(&data, options)?;
+    Ok(export(&stub, format).map_err(js_error)?.into())
+}
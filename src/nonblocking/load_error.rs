@@ -0,0 +1,27 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{pack::pack_error::PackError, prog::arm::arm_error::ArmError};
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Failed to access {path}, {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to download {url}, {reason}")]
+    Download { url: String, reason: String },
+
+    #[error(transparent)]
+    Pack(#[from] PackError),
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+}
+
+impl LoadError {
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> LoadError {
+        let path = path.into();
+        move |source| LoadError::Io { path, source }
+    }
+}
@@ -0,0 +1,32 @@
+//! Async front end for the I/O around conversions: pack index and pack downloads, the pack cache
+//! and reading input files. Parsing stays synchronous, it is CPU bound and quick next to the I/O.
+
+pub mod load_error;
+pub mod pack_cache;
+
+use std::path::Path;
+
+use crate::{
+    compose::arm::{compose_stub, ComposeOptions, Elf},
+    diagnostic::Diagnostic,
+    prog::arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub},
+};
+
+use load_error::LoadError;
+
+/// Reads a whole file without blocking the runtime.
+pub async fn read_file(path: impl AsRef<Path>) -> Result<Vec<u8>, LoadError> {
+    let path = path.as_ref();
+    tokio::fs::read(path).await.map_err(LoadError::io(path))
+}
+
+/// Reads the FLM at `path` and composes its stub, see `compose_stub`.
+#[tracing::instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub async fn compose_file(
+    path: impl AsRef<Path>,
+    options: &ComposeOptions,
+) -> Result<(ArmFlashStub, Vec<Diagnostic>), LoadError> {
+    let buffer = read_file(path).await?;
+    let elf = Elf::parse(&buffer).map_err(ArmError::from)?;
+    Ok(compose_stub(&elf, &buffer, options)?)
+}
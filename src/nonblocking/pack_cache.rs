@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    pack::{
+        index::{parse_index, PackIndexEntry},
+        pdsc::Pdsc,
+    },
+    progress::{Progress, ProgressSink},
+};
+
+use super::load_error::LoadError;
+
+/// The pack cache the `search` and `serve` commands share: the index in `index.pidx`, PDSC files
+/// in `pdsc/` and pack archives in `packs/`.
+#[derive(Debug, Clone)]
+pub struct PackCache {
+    root: PathBuf,
+    client: reqwest::Client,
+}
+
+impl PackCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), client: reqwest::Client::new() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Downloads `url` as bytes.
+    #[tracing::instrument(skip(self))]
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, LoadError> {
+        let failed = |err: reqwest::Error| LoadError::Download { url: url.to_string(), reason: err.to_string() };
+        let response = self.client.get(url).send().await.map_err(failed)?.error_for_status().map_err(failed)?;
+        Ok(response.bytes().await.map_err(failed)?.to_vec())
+    }
+
+    async fn store(&self, path: PathBuf, data: &[u8]) -> Result<PathBuf, LoadError> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(LoadError::io(dir))?;
+        }
+        tokio::fs::write(&path, data).await.map_err(LoadError::io(&path))?;
+        Ok(path)
+    }
+
+    /// Fetches the pack index into the cache and returns its entries.
+    pub async fn update_index(&self, index_url: &str) -> Result<Vec<PackIndexEntry>, LoadError> {
+        let index = self.download(index_url).await?;
+        self.store(self.root.join("index.pidx"), &index).await?;
+        Ok(parse_index(&String::from_utf8_lossy(&index))?)
+    }
+
+    /// Fetches the PDSC of a pack into the cache.
+    pub async fn fetch_pdsc(&self, entry: &PackIndexEntry) -> Result<PathBuf, LoadError> {
+        let pdsc = self.download(&entry.pdsc_url()).await?;
+        self.store(self.root.join("pdsc").join(entry.pdsc_file_name()), &pdsc).await
+    }
+
+    /// Fetches the PDSC of every entry, one after the other. A failed download doesn't stop the
+    /// others, the results are in the order of `entries`.
+    pub async fn fetch_pdscs(
+        &self,
+        entries: &[PackIndexEntry],
+        progress: &mut impl ProgressSink,
+    ) -> Vec<Result<PathBuf, LoadError>> {
+        let mut results = Vec::with_capacity(entries.len());
+        for (completed, entry) in entries.iter().enumerate() {
+            progress.progress(Progress { completed, total: entries.len(), current: &entry.pdsc_url() });
+            results.push(self.fetch_pdsc(entry).await);
+        }
+        progress.finish(entries.len());
+
+        results
+    }
+
+    /// Fetches the pack archive, unless the listed version is cached already.
+    pub async fn fetch_pack(&self, entry: &PackIndexEntry) -> Result<PathBuf, LoadError> {
+        let path = self.root.join("packs").join(format!("{}.{}.{}.pack", entry.vendor, entry.name, entry.version));
+        if tokio::fs::metadata(&path).await.is_ok() {
+            return Ok(path);
+        }
+        let pack = self.download(&entry.pack_url()).await?;
+        self.store(path, &pack).await
+    }
+
+    /// Every cached PDSC, skipping the ones that fail to parse.
+    pub async fn pdscs(&self) -> Result<Vec<Pdsc>, LoadError> {
+        let dir = self.root.join("pdsc");
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(LoadError::io(&dir))? {
+            paths.push(entry.path());
+        }
+        paths.sort();
+
+        let mut pdscs = Vec::new();
+        for path in paths {
+            let text = tokio::fs::read_to_string(&path).await.map_err(LoadError::io(&path))?;
+            match Pdsc::parse(&text) {
+                Ok(pdsc) => pdscs.push(pdsc),
+                Err(err) => tracing::warn!(path = %path.display(), error = %err, "skipping unreadable PDSC"),
+            }
+        }
+
+        Ok(pdscs)
+    }
+}
@@ -0,0 +1,128 @@
+//! OTA manifests of stub library releases, what deployed programmers poll to find updates.
+//!
+//! A manifest names a release by version and points at its signed bundle: where to download it,
+//! its SHA-256 and size, and the programmer models it is for. The manifest itself is JSON wrapped
+//! in a signed bundle (see `signing`), so a programmer checks it with the key it already trusts
+//! before fetching anything, then checks the download against the hash.
+//!
+//! Versions are dot separated numbers, `1.4` or `2024.6.1`, compared number by number with
+//! missing ones taken as 0.
+
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::signing::{self, SignedBundle, SigningError, PUBLIC_KEY_SIZE};
+
+#[derive(Debug, Error)]
+pub enum OtaError {
+    #[error("Invalid version {0}, expected numbers separated by dots such as 1.4.0")]
+    Version(String),
+
+    #[error("Invalid download URL {0}, expected http:// or https:// and a host")]
+    Url(String),
+
+    #[error("Invalid manifest, {0}")]
+    Manifest(String),
+
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+}
+
+/// A release of a stub library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtaManifest {
+    pub version: String,
+    /// Where the signed bundle of the release is downloaded from.
+    pub url: String,
+    /// SHA-256 of the bundle as lowercase hex.
+    pub sha256: String,
+    pub size: u64,
+    /// Programmer models the release is for, every model when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// Seconds since the Unix epoch.
+    pub released_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+/// The numbers of a version.
+fn parse_version(version: &str) -> Result<Vec<u64>, OtaError> {
+    version.split('.').map(|part| part.parse().map_err(|_| OtaError::Version(version.to_string()))).collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    signing::to_hex(&Sha256::digest(data))
+}
+
+impl OtaManifest {
+    /// Describes the release `version` of `bundle`, a signed bundle downloaded from `url`.
+    pub fn new(version: &str, url: &str, bundle: &[u8], models: Vec<String>) -> Result<Self, OtaError> {
+        parse_version(version)?;
+        let host = url.split_once("://").filter(|(scheme, _)| matches!(*scheme, "http" | "https")).map(|(_, rest)| rest);
+        if host.is_none_or(|host| host.is_empty() || host.starts_with('/')) {
+            return Err(OtaError::Url(url.to_string()));
+        }
+        // Programmers only install signed bundles, catch a plain stub before it ships.
+        SignedBundle::parse(bundle).map_err(SigningError::from)?;
+
+        Ok(OtaManifest {
+            version: version.to_string(),
+            url: url.to_string(),
+            sha256: sha256_hex(bundle),
+            size: bundle.len() as u64,
+            models,
+            released_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+            notes: None,
+        })
+    }
+
+    pub fn notes(mut self, notes: &str) -> Self {
+        self.notes = Some(notes.to_string());
+        self
+    }
+
+    /// Whether a programmer of `model` should install the release, ignoring case.
+    pub fn applies_to(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|listed| listed.eq_ignore_ascii_case(model))
+    }
+
+    /// Whether the release is newer than the `installed` version.
+    pub fn is_newer_than(&self, installed: &str) -> Result<bool, OtaError> {
+        let (mut release, mut installed) = (parse_version(&self.version)?, parse_version(installed)?);
+        let length = release.len().max(installed.len());
+        release.resize(length, 0);
+        installed.resize(length, 0);
+        Ok(release.cmp(&installed) == Ordering::Greater)
+    }
+
+    /// Whether a download is the bundle the manifest describes.
+    pub fn matches(&self, bundle: &[u8]) -> bool {
+        bundle.len() as u64 == self.size && sha256_hex(bundle) == self.sha256
+    }
+
+    pub fn to_json(&self) -> Result<String, OtaError> {
+        serde_json::to_string_pretty(self).map_err(|err| OtaError::Manifest(err.to_string()))
+    }
+
+    /// The manifest as JSON in a bundle signed with `key`, what programmers poll.
+    pub fn sign(&self, key: &SigningKey) -> Result<Vec<u8>, OtaError> {
+        Ok(signing::sign(self.to_json()?.as_bytes(), key)?)
+    }
+
+    /// Reads a manifest written by `sign`, checking that one of `trusted` signed it.
+    pub fn verify(signed: &[u8], trusted: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<Self, OtaError> {
+        let payload = signing::verify(signed, trusted)?;
+        let manifest: OtaManifest = serde_json::from_slice(payload).map_err(|err| OtaError::Manifest(err.to_string()))?;
+        parse_version(&manifest.version)?;
+        Ok(manifest)
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Seek};
+
+use zip::ZipArchive;
+
+use super::{pack_error::PackError, pdsc::Pdsc};
+
+/// A CMSIS pack (`.pack`), which is a zip archive with the `.pdsc` at its root.
+pub struct PackArchive<R> {
+    archive: ZipArchive<R>,
+}
+
+impl<R: Read + Seek> PackArchive<R> {
+    pub fn new(reader: R) -> Result<Self, PackError> {
+        let archive = ZipArchive::new(reader).map_err(|err| PackError::Archive(err.to_string()))?;
+        Ok(Self { archive })
+    }
+
+    /// Names of all files in the pack.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.archive.file_names()
+    }
+
+    /// Parses the pack description.
+    pub fn pdsc(&mut self) -> Result<Pdsc, PackError> {
+        let name = self
+            .file_names()
+            .filter(|name| !name.contains('/') && name.to_ascii_lowercase().ends_with(".pdsc"))
+            .min()
+            .map(str::to_string)
+            .ok_or(PackError::PdscNotFound)?;
+
+        let data = self.read_file(&name)?;
+        Pdsc::parse(&String::from_utf8_lossy(&data))
+    }
+
+    /// Reads a file by its path in the PDSC, which may use backslashes and differ in case.
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, PackError> {
+        let mut data = Vec::new();
+        self.open_file(path)?.read_to_end(&mut data).map_err(|err| PackError::Archive(err.to_string()))?;
+        Ok(data)
+    }
+
+    /// Same as `read_file`, streaming the decompressed file instead of reading it whole.
+    pub fn open_file(&mut self, path: &str) -> Result<impl Read + '_, PackError> {
+        let wanted = path.replace('\\', "/");
+        let wanted = wanted.trim_start_matches("./");
+        let name = self
+            .file_names()
+            .find(|name| name.eq_ignore_ascii_case(wanted))
+            .map(str::to_string)
+            .ok_or_else(|| PackError::FileNotFound(path.to_string()))?;
+
+        self.archive.by_name(&name).map_err(|err| PackError::Archive(err.to_string()))
+    }
+}
@@ -0,0 +1,57 @@
+use roxmltree::Document;
+
+use super::pack_error::PackError;
+
+/// Where Keil publishes the index of all public packs.
+pub const KEIL_INDEX_URL: &str = "https://www.keil.com/pack/index.pidx";
+
+/// One pack listed in a pack index (`.pidx`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackIndexEntry {
+    /// Base URL the pack and its PDSC are served from, ending in a slash.
+    pub url: String,
+    pub vendor: String,
+    pub name: String,
+    pub version: String,
+}
+
+impl PackIndexEntry {
+    /// File name of the PDSC, e.g. "Keil.STM32F4xx_DFP.pdsc".
+    pub fn pdsc_file_name(&self) -> String {
+        format!("{}.{}.pdsc", self.vendor, self.name)
+    }
+
+    pub fn pdsc_url(&self) -> String {
+        format!("{}{}", self.url, self.pdsc_file_name())
+    }
+
+    /// URL of the pack archive for the listed version.
+    pub fn pack_url(&self) -> String {
+        format!("{}{}.{}.{}.pack", self.url, self.vendor, self.name, self.version)
+    }
+}
+
+/// Parses the XML text of a pack index.
+pub fn parse_index(xml: &str) -> Result<Vec<PackIndexEntry>, PackError> {
+    let document = Document::parse(xml).map_err(|err| PackError::PdscParse(err.to_string()))?;
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name("pdsc"))
+        .map(|node| {
+            let attribute = |name: &str| node.attribute(name).unwrap_or_default().to_string();
+            let mut url = attribute("url");
+            if !url.ends_with('/') {
+                url.push('/');
+            }
+
+            PackIndexEntry {
+                url,
+                vendor: attribute("vendor"),
+                name: attribute("name"),
+                version: attribute("version"),
+            }
+        })
+        .collect();
+
+    Ok(entries)
+}
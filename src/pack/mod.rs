@@ -0,0 +1,5 @@
+#[cfg(feature = "pack")]
+pub mod archive;
+pub mod index;
+pub mod pack_error;
+pub mod pdsc;
@@ -0,0 +1,27 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("Failed to parse PDSC file, {0}")]
+    PdscParse(String),
+
+    #[error("Invalid number {value:?} in attribute {attribute}")]
+    InvalidNumber { attribute: String, value: String },
+
+    #[error("Device {0} not found in the pack")]
+    DeviceNotFound(String),
+
+    #[error("Failed to read the pack archive, {0}")]
+    Archive(String),
+
+    #[error("The pack has no .pdsc file at its root")]
+    PdscNotFound,
+
+    #[error("File {0} is referenced by the PDSC but missing from the pack")]
+    FileNotFound(String),
+
+    #[error("Failed to read the PDSC file, {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,320 @@
+use std::io::Read;
+
+use roxmltree::{Document, Node};
+
+use super::pack_error::PackError;
+
+/// A processor (core) of a device, from `<processor>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Processor {
+    /// `Pname`, only set on multi-core devices.
+    pub name: Option<String>,
+    /// `Dcore`, e.g. "Cortex-M4".
+    pub core: Option<String>,
+    /// `Dclock`, the maximum core clock in Hz.
+    pub clock: Option<u32>,
+    /// `Dfpu`
+    pub fpu: Option<String>,
+    /// `Dendian`
+    pub endian: Option<String>,
+    /// Access port the core is debugged through, from the `<debug>` element naming it.
+    pub ap: Option<u32>,
+}
+
+/// A memory region of a device, from `<memory>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Memory {
+    /// `name`, or the legacy `id` such as "IROM1".
+    pub name: String,
+    pub start: u32,
+    pub size: u32,
+    /// `access`, e.g. "rx" or "rwx".
+    pub access: Option<String>,
+    pub default: bool,
+    pub startup: bool,
+    /// `Pname` of the core this region belongs to, if restricted.
+    pub processor: Option<String>,
+}
+
+/// A flash algorithm referenced by a device, from `<algorithm>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Algorithm {
+    /// Path of the FLM inside the pack.
+    pub file: String,
+    pub start: u32,
+    pub size: u32,
+    pub ram_start: Option<u32>,
+    pub ram_size: Option<u32>,
+    pub default: bool,
+    /// `Pname` of the core the algorithm runs on, if restricted.
+    pub processor: Option<String>,
+}
+
+/// Flash programming parameters from `<flashinfo>`, which some packs give next to the FLM.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlashInfo {
+    pub name: Option<String>,
+    pub start: u32,
+    /// `pagesize`, the programming page size in bytes.
+    pub page_size: Option<u32>,
+    /// `blankval`, the erased value.
+    pub blank_value: Option<u32>,
+    /// `ptime`, page program time in microseconds.
+    pub program_time: Option<u32>,
+    /// `etime`, sector erase time in microseconds.
+    pub erase_time: Option<u32>,
+    pub processor: Option<String>,
+}
+
+/// A device with all properties inherited from its family, sub-family and device elements applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PdscDevice {
+    pub name: String,
+    pub vendor: Option<String>,
+    pub family: Option<String>,
+    pub sub_family: Option<String>,
+    pub processors: Vec<Processor>,
+    pub memories: Vec<Memory>,
+    pub algorithms: Vec<Algorithm>,
+    pub flash_infos: Vec<FlashInfo>,
+}
+
+impl PdscDevice {
+    /// The recommended Init clock, taken from the first processor declaring one.
+    pub fn clock(&self) -> Option<u32> {
+        self.processors.iter().find_map(|processor| processor.clock)
+    }
+
+    /// The algorithm covering `address`, preferring default ones.
+    pub fn algorithm_for(&self, address: u32) -> Option<&Algorithm> {
+        let mut covering = self
+            .algorithms
+            .iter()
+            .filter(|algorithm| address >= algorithm.start && u64::from(address) < u64::from(algorithm.start) + u64::from(algorithm.size));
+        let first = covering.clone().next();
+        covering.find(|algorithm| algorithm.default).or(first)
+    }
+
+    /// The flash info starting at `address`.
+    pub fn flash_info_for(&self, address: u32) -> Option<&FlashInfo> {
+        self.flash_infos.iter().find(|info| info.start == address)
+    }
+
+    /// The memory region covering `address`.
+    pub fn memory_for(&self, address: u32) -> Option<&Memory> {
+        self.memories
+            .iter()
+            .find(|memory| address >= memory.start && u64::from(address) < u64::from(memory.start) + u64::from(memory.size))
+    }
+}
+
+/// The parts of a CMSIS pack description (`.pdsc`) the composer uses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pdsc {
+    pub vendor: String,
+    pub name: String,
+    /// Version of the newest release.
+    pub version: Option<String>,
+    pub devices: Vec<PdscDevice>,
+}
+
+impl Pdsc {
+    /// Parses a `.pdsc` file from `reader`, e.g. a pack archive entry.
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, PackError> {
+        let mut xml = String::new();
+        reader.read_to_string(&mut xml)?;
+        Self::parse(&xml)
+    }
+
+    /// Parses the XML text of a `.pdsc` file.
+    pub fn parse(xml: &str) -> Result<Self, PackError> {
+        let document = Document::parse(xml).map_err(|err| PackError::PdscParse(err.to_string()))?;
+        let package = document.root_element();
+
+        let child_text = |name: &str| {
+            package
+                .children()
+                .find(|node| node.has_tag_name(name))
+                .and_then(|node| node.text())
+                .map(|text| text.trim().to_string())
+        };
+
+        let version = package
+            .children()
+            .find(|node| node.has_tag_name("releases"))
+            .and_then(|releases| releases.children().find(|node| node.has_tag_name("release")))
+            .and_then(|release| release.attribute("version"))
+            .map(str::to_string);
+
+        let mut devices = Vec::new();
+        if let Some(list) = package.children().find(|node| node.has_tag_name("devices")) {
+            for family in list.children().filter(|node| node.has_tag_name("family")) {
+                collect_devices(family, &PdscDevice::default(), &mut devices)?;
+            }
+        }
+
+        Ok(Self {
+            vendor: child_text("vendor").unwrap_or_default(),
+            name: child_text("name").unwrap_or_default(),
+            version,
+            devices,
+        })
+    }
+
+    /// Looks up a device or variant by name, ignoring case.
+    pub fn device(&self, name: &str) -> Result<&PdscDevice, PackError> {
+        self.devices
+            .iter()
+            .find(|device| device.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| PackError::DeviceNotFound(name.to_string()))
+    }
+}
+
+/// Walks family, sub-family, device and variant elements, inheriting properties on the way down.
+fn collect_devices(node: Node<'_, '_>, inherited: &PdscDevice, devices: &mut Vec<PdscDevice>) -> Result<(), PackError> {
+    let mut current = inherited.clone();
+    match node.tag_name().name() {
+        "family" => {
+            current.family = node.attribute("Dfamily").map(str::to_string);
+            current.vendor = node.attribute("Dvendor").map(|vendor| vendor.split(':').next().unwrap_or(vendor).to_string());
+        }
+        "subFamily" => current.sub_family = node.attribute("DsubFamily").map(str::to_string),
+        "device" => current.name = node.attribute("Dname").unwrap_or_default().to_string(),
+        "variant" => current.name = node.attribute("Dvariant").unwrap_or_default().to_string(),
+        _ => return Ok(()),
+    }
+
+    for child in node.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "processor" => merge_processor(&mut current.processors, parse_processor(child)?),
+            "memory" => current.memories.push(parse_memory(child)?),
+            "algorithm" => current.algorithms.push(parse_algorithm(child)?),
+            "flashinfo" => current.flash_infos.push(parse_flash_info(child)?),
+            "debug" => apply_debug(&mut current.processors, child)?,
+            _ => {}
+        }
+    }
+
+    if matches!(node.tag_name().name(), "device" | "variant") {
+        devices.push(current.clone());
+    }
+
+    for child in node.children().filter(Node::is_element) {
+        collect_devices(child, &current, devices)?;
+    }
+
+    Ok(())
+}
+
+/// Lower levels refine the processor of the same `Pname` rather than adding a new one.
+fn merge_processor(processors: &mut Vec<Processor>, processor: Processor) {
+    match processors.iter_mut().find(|existing| existing.name == processor.name) {
+        Some(existing) => {
+            existing.core = processor.core.or_else(|| existing.core.take());
+            existing.clock = processor.clock.or(existing.clock);
+            existing.fpu = processor.fpu.or_else(|| existing.fpu.take());
+            existing.endian = processor.endian.or_else(|| existing.endian.take());
+            existing.ap = processor.ap.or(existing.ap);
+        }
+        None => processors.push(processor),
+    }
+}
+
+fn parse_processor(node: Node<'_, '_>) -> Result<Processor, PackError> {
+    Ok(Processor {
+        name: node.attribute("Pname").map(str::to_string),
+        core: node.attribute("Dcore").map(str::to_string),
+        clock: optional_number(node, "Dclock")?,
+        fpu: node.attribute("Dfpu").map(str::to_string),
+        endian: node.attribute("Dendian").map(str::to_string),
+        ap: None,
+    })
+}
+
+/// Records the `__ap` of a `<debug>` element on the processor it names, or on the only one.
+fn apply_debug(processors: &mut Vec<Processor>, node: Node<'_, '_>) -> Result<(), PackError> {
+    let ap = match optional_number(node, "__ap")? {
+        Some(ap) => ap,
+        None => return Ok(()),
+    };
+
+    let name = node.attribute("Pname");
+    let position = match processors.iter().position(|processor| processor.name.as_deref() == name) {
+        None if name.is_none() && processors.len() == 1 => Some(0),
+        position => position,
+    };
+    match position {
+        Some(position) => processors[position].ap = Some(ap),
+        None => processors.push(Processor {
+            name: name.map(str::to_string),
+            ap: Some(ap),
+            ..Default::default()
+        }),
+    }
+
+    Ok(())
+}
+
+fn parse_memory(node: Node<'_, '_>) -> Result<Memory, PackError> {
+    Ok(Memory {
+        name: node.attribute("name").or_else(|| node.attribute("id")).unwrap_or_default().to_string(),
+        start: number(node, "start")?,
+        size: number(node, "size")?,
+        access: node.attribute("access").map(str::to_string),
+        default: flag(node, "default"),
+        startup: flag(node, "startup"),
+        processor: node.attribute("Pname").map(str::to_string),
+    })
+}
+
+fn parse_algorithm(node: Node<'_, '_>) -> Result<Algorithm, PackError> {
+    Ok(Algorithm {
+        file: node.attribute("name").unwrap_or_default().replace('\\', "/"),
+        start: number(node, "start")?,
+        size: number(node, "size")?,
+        ram_start: optional_number(node, "RAMstart")?,
+        ram_size: optional_number(node, "RAMsize")?,
+        default: flag(node, "default"),
+        processor: node.attribute("Pname").map(str::to_string),
+    })
+}
+
+fn parse_flash_info(node: Node<'_, '_>) -> Result<FlashInfo, PackError> {
+    Ok(FlashInfo {
+        name: node.attribute("name").map(str::to_string),
+        start: number(node, "start")?,
+        page_size: optional_number(node, "pagesize")?,
+        blank_value: optional_number(node, "blankval")?,
+        program_time: optional_number(node, "ptime")?,
+        erase_time: optional_number(node, "etime")?,
+        processor: node.attribute("Pname").map(str::to_string),
+    })
+}
+
+fn flag(node: Node<'_, '_>, attribute: &str) -> bool {
+    matches!(node.attribute(attribute), Some("1") | Some("true"))
+}
+
+fn number(node: Node<'_, '_>, attribute: &str) -> Result<u32, PackError> {
+    optional_number(node, attribute)?.ok_or_else(|| PackError::InvalidNumber {
+        attribute: attribute.to_string(),
+        value: String::new(),
+    })
+}
+
+fn optional_number(node: Node<'_, '_>, attribute: &str) -> Result<Option<u32>, PackError> {
+    let value = match node.attribute(attribute) {
+        Some(value) => value.trim(),
+        None => return Ok(None),
+    };
+
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.map(Some).map_err(|_| PackError::InvalidNumber {
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+    })
+}
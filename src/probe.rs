@@ -0,0 +1,198 @@
+//! Runs converted algorithms on real hardware through probe-rs: the stub is the only flash
+//! algorithm of a generated target, so whatever probe-rs does with the flash goes through it.
+//!
+//! `self_test` is the end-to-end check of a conversion. It erases and programs one scratch sector
+//! with a pattern and reads it back, which takes the stub through Init, EraseSector, ProgramPage
+//! and UnInit on the target.
+
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use probe_rs::{
+    config::{Chip, ChipFamily, MemoryRegion, NvmRegion, RamRegion, RawFlashAlgorithm, Registry, TargetDescriptionSource},
+    flashing::{DownloadOptions, FlashProgress, ProgressEvent, ProgressOperation},
+    probe::{list::Lister, DebugProbeSelector},
+    CoreType, MemoryInterface, Permissions, Session,
+};
+use probe_rs_target::{ApAddress, CoreAccessOptions};
+use thiserror::Error;
+
+use crate::prog::{
+    arm::{arm_error::ArmError, core_isa::Core, validated::ValidatedArmFlashStub},
+    export::probe_rs::ProbeRsAlgorithm,
+};
+
+/// Name of the one core of the generated target.
+const CORE_NAME: &str = "main";
+
+#[derive(Debug, Error)]
+pub enum ProbeError {
+    #[error("Debug probe error, {0}")]
+    Probe(String),
+
+    #[error("No debug probe found")]
+    NoProbe,
+
+    #[error("Scratch address {0:#010x} isn't in the algorithm's flash")]
+    ScratchOutOfRange(u32),
+
+    #[error("Read back {found:#04x} at {address:#010x} after programming, expected {expected:#04x}")]
+    Mismatch { address: u64, expected: u8, found: u8 },
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+}
+
+fn probe_error(err: impl ToString) -> ProbeError {
+    ProbeError::Probe(err.to_string())
+}
+
+/// The parts of the target probe-rs needs besides the algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeTarget {
+    pub core: Core,
+    /// RAM the algorithm is loaded into and runs from.
+    pub ram_start: u32,
+    pub ram_size: u32,
+}
+
+fn core_type(core: Core) -> CoreType {
+    match core {
+        Core::CortexM0 | Core::CortexM0Plus => CoreType::Armv6m,
+        Core::CortexM3 => CoreType::Armv7m,
+        Core::CortexM4 | Core::CortexM7 => CoreType::Armv7em,
+        Core::CortexM33 => CoreType::Armv8m,
+    }
+}
+
+/// A single chip probe-rs can attach to, with the stub as its only flash algorithm.
+pub fn chip_family(stub: &ValidatedArmFlashStub, target: &ProbeTarget) -> Result<ChipFamily, ProbeError> {
+    // The export already follows the probe-rs target description layout.
+    let exported = serde_json::to_value(ProbeRsAlgorithm::from_stub(stub)?).map_err(probe_error)?;
+    let mut algorithm: RawFlashAlgorithm = serde_json::from_value(exported).map_err(probe_error)?;
+    algorithm.cores = vec![CORE_NAME.to_string()];
+
+    let mut chip = Chip::generic_arm(&stub.name, core_type(target.core));
+    if let (Some(core), Some(ap)) = (chip.cores.first_mut(), stub.pinned_core.as_ref().and_then(|pinned| pinned.ap)) {
+        if let CoreAccessOptions::Arm(options) = &mut core.core_access_options {
+            options.ap = ApAddress::V1(ap as u8);
+        }
+    }
+
+    chip.memory_map = vec![
+        MemoryRegion::Nvm(NvmRegion {
+            name: Some("flash".to_string()),
+            range: u64::from(stub.flash_start_addr)..u64::from(stub.flash_end_addr),
+            cores: vec![CORE_NAME.to_string()],
+            is_alias: false,
+            access: None,
+        }),
+        MemoryRegion::Ram(RamRegion {
+            name: Some("ram".to_string()),
+            range: u64::from(target.ram_start)..u64::from(target.ram_start) + u64::from(target.ram_size),
+            cores: vec![CORE_NAME.to_string()],
+            is_alias: false,
+            access: None,
+        }),
+    ];
+    chip.flash_algorithms = vec![algorithm.name.clone()];
+
+    Ok(ChipFamily {
+        name: stub.name.clone(),
+        manufacturer: None,
+        chip_detection: Vec::new(),
+        generated_from_pack: false,
+        pack_file_release: None,
+        variants: vec![chip],
+        flash_algorithms: vec![algorithm],
+        source: TargetDescriptionSource::External,
+    })
+}
+
+/// Attaches to the target through the probe `selector` picks, the first one found without one.
+pub fn attach(
+    stub: &ValidatedArmFlashStub,
+    target: &ProbeTarget,
+    selector: Option<DebugProbeSelector>,
+) -> Result<Session, ProbeError> {
+    let mut registry = Registry::new();
+    let family = registry.add_target_family(chip_family(stub, target)?).map_err(probe_error)?;
+
+    let lister = Lister::new();
+    let probe = match selector {
+        Some(selector) => lister.open(selector).map_err(probe_error)?,
+        None => lister.list_all().first().ok_or(ProbeError::NoProbe)?.open().map_err(probe_error)?,
+    };
+
+    probe.attach_with_registry(family, Permissions::new(), &registry).map_err(probe_error)
+}
+
+/// What `self_test` did on the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The scratch sector, absolute addresses.
+    pub sector: Range<u64>,
+    /// Bytes EraseSector reported erased.
+    pub erased: u64,
+    /// Bytes ProgramPage reported written.
+    pub programmed: u64,
+    pub erase_time: Duration,
+    pub program_time: Duration,
+    /// From loading the algorithm to reading the sector back.
+    pub total_time: Duration,
+}
+
+/// Erases and programs one sector through the stub and reads it back.
+///
+/// The sector holding `scratch`, or the last sector of the flash, is overwritten: pick one
+/// nothing on the target needs.
+pub fn self_test(
+    session: &mut Session,
+    stub: &ValidatedArmFlashStub,
+    scratch: Option<u32>,
+) -> Result<SelfTestReport, ProbeError> {
+    let sector = stub.scratch_sector(scratch).ok_or(ProbeError::ScratchOutOfRange(scratch.unwrap_or(stub.flash_start_addr)))?;
+    let data = stub.test_pattern((sector.end - sector.start) as usize);
+    let started = Instant::now();
+
+    let mut events = Vec::new();
+    let mut loader = session.target().flash_loader();
+    loader.add_data(sector.start, &data).map_err(probe_error)?;
+    let mut options = DownloadOptions::new();
+    options.progress = FlashProgress::new(|event| events.push(event));
+    loader.commit(session, options).map_err(probe_error)?;
+
+    let mut read_back = vec![0; data.len()];
+    session.core(0).map_err(probe_error)?.read(sector.start, &mut read_back).map_err(probe_error)?;
+    if let Some(offset) = data.iter().zip(&read_back).position(|(expected, found)| expected != found) {
+        return Err(ProbeError::Mismatch { address: sector.start + offset as u64, expected: data[offset], found: read_back[offset] });
+    }
+
+    let mut report = SelfTestReport {
+        sector,
+        erased: 0,
+        programmed: 0,
+        erase_time: Duration::ZERO,
+        program_time: Duration::ZERO,
+        total_time: started.elapsed(),
+    };
+    for event in events {
+        if let ProgressEvent::Progress { operation, size, time } = event {
+            match operation {
+                ProgressOperation::Erase => {
+                    report.erased += size;
+                    report.erase_time += time;
+                }
+                ProgressOperation::Program => {
+                    report.programmed += size;
+                    report.program_time += time;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(report)
+}
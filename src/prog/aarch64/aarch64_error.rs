@@ -0,0 +1,30 @@
+use thiserror::Error;
+
+use crate::prog::arm::arm_error::ArmError;
+
+#[derive(Debug, Error)]
+pub enum Aarch64Error {
+    #[error("Failed to parse ELF file")]
+    ElfParse,
+
+    #[error("ELF machine {0} is not AArch64")]
+    WrongMachine(u16),
+
+    #[error("AArch64 loaders must be ELF64")]
+    NotElf64,
+
+    #[error("Required entry point {0} not found")]
+    EntryPointNotFound(String),
+
+    #[error("Entry point {name} at offset {offset:#x} is invalid: {reason}")]
+    InvalidEntryPoint { name: String, offset: u64, reason: String },
+
+    #[error("FlashDevice field {field} does not fit in 32 bits: {value:#x}")]
+    DescriptorOverflow { field: &'static str, value: u64 },
+
+    #[error("Failed to decode instructions: {0}")]
+    InstructionDecode(String),
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+}
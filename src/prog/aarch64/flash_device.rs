@@ -0,0 +1,94 @@
+use scroll::Pread;
+
+use crate::prog::arm::{
+    arm_error::ArmError,
+    flash_device::{FlashDevice, SectorInfo},
+};
+
+use super::aarch64_error::Aarch64Error;
+
+/// The `FlashDevice` descriptor as laid out by an LP64 compiler.
+///
+/// `unsigned long` is 64 bits wide there, so every address, size and timeout doubles in size and
+/// is 8-byte aligned. Sector offsets and the device size must still fit in 32 bits, only the base
+/// address may live above 4 GiB.
+#[derive(Clone, Debug)]
+pub struct Aarch64FlashDevice {
+    pub(crate) name: String,
+    pub(crate) start_address: u64,
+    pub(crate) device_size: u32,
+    pub(crate) page_size: u32,
+    pub(crate) erased_default_value: u8,
+    pub(crate) program_page_timeout: u32,
+    pub(crate) erase_sector_timeout: u32,
+    pub(crate) sectors: Vec<SectorInfo>,
+}
+
+impl Aarch64FlashDevice {
+    const INFO_SIZE: u32 = 192;
+    const SECTOR_INFO_SIZE: u32 = 16;
+    const MAX_ID_STRING_LENGTH: usize = 128;
+    // `SECTOR_END` is `0xFFFFFFFF` even with 64-bit fields.
+    const SECTOR_END: u64 = 0xFFFF_FFFF;
+
+    /// Parses the descriptor at `address` in the loader ELF.
+    pub fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8], address: u32) -> Result<Self, Aarch64Error> {
+        let data = FlashDevice::read_elf_bin_data(elf, buffer, address, Self::INFO_SIZE)
+            .ok_or(ArmError::MalformedDescriptor { address, size: Self::INFO_SIZE })?;
+
+        let name_length = data[2..2 + Self::MAX_ID_STRING_LENGTH]
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(Self::MAX_ID_STRING_LENGTH);
+        let narrow = |field: &'static str, offset: usize| -> Result<u32, Aarch64Error> {
+            let value: u64 = data.pread(offset).unwrap();
+            if value > u64::from(u32::MAX) {
+                return Err(Aarch64Error::DescriptorOverflow { field, value });
+            }
+            Ok(value as u32)
+        };
+
+        let device_size = narrow("szDev", 144)?;
+        let sectors = Self::parse_sectors(elf, buffer, address)?;
+        FlashDevice::validate_sectors(&sectors, device_size)?;
+
+        Ok(Self {
+            name: String::from_utf8_lossy(&data[2..2 + name_length]).to_string(),
+            start_address: data.pread(136).unwrap(),
+            device_size,
+            page_size: narrow("szPage", 152)?,
+            erased_default_value: data.pread(168).unwrap(),
+            program_page_timeout: narrow("toProg", 176)?,
+            erase_sector_timeout: narrow("toErase", 184)?,
+            sectors,
+        })
+    }
+
+    fn parse_sectors(elf: &goblin::elf::Elf<'_>, buffer: &[u8], address: u32) -> Result<Vec<SectorInfo>, Aarch64Error> {
+        let mut sectors = vec![];
+        let mut offset = Self::INFO_SIZE;
+        loop {
+            let entry = address.checked_add(offset).ok_or(ArmError::MalformedDescriptor { address, size: offset })?;
+            let data = match FlashDevice::read_elf_bin_data(elf, buffer, entry, Self::SECTOR_INFO_SIZE) {
+                Some(data) => data,
+                None => break,
+            };
+            let size: u64 = data.pread(0).unwrap();
+            let sector_address: u64 = data.pread(8).unwrap();
+            if size == Self::SECTOR_END || sector_address == Self::SECTOR_END {
+                break;
+            }
+
+            if size > u64::from(u32::MAX) {
+                return Err(Aarch64Error::DescriptorOverflow { field: "szSector", value: size });
+            }
+            if sector_address > u64::from(u32::MAX) {
+                return Err(Aarch64Error::DescriptorOverflow { field: "AddrSector", value: sector_address });
+            }
+            sectors.push(SectorInfo { address: sector_address as u32, size: size as u32 });
+            offset += Self::SECTOR_INFO_SIZE;
+        }
+
+        Ok(sectors)
+    }
+}
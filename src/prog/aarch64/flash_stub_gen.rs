@@ -0,0 +1,160 @@
+use std::convert::TryFrom;
+
+use goblin::elf::{header::EM_AARCH64, Elf};
+use serde::{Deserialize, Serialize};
+
+use crate::prog::arm::{
+    algorithm_binary::{AlgorithmBinary, BlobLayout},
+    arm_error::ArmError,
+    ram_layout::{RamRequirement, DEFAULT_STACK_SIZE},
+};
+
+use super::{aarch64_error::Aarch64Error, flash_device::Aarch64FlashDevice};
+
+/// A region of equally sized sectors, with a 64-bit start address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aarch64SectorRegion {
+    pub address: u64,
+    pub size: u32,
+    pub count: u32,
+}
+
+/// A flash algorithm for 64-bit ARM cores (Cortex-A), the counterpart of `ArmFlashStub`.
+///
+/// Entry points are A64 code, so they are word aligned offsets into the blob without a Thumb bit.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aarch64FlashStub {
+    pub name: String,
+    pub description: String,
+    pub default: bool,
+    pub instructions: String,
+    pub pc_init: Option<u32>,
+    pub pc_uninit: Option<u32>,
+    pub pc_program_page: u32,
+    pub pc_erase_sector: u32,
+    pub pc_erase_all: Option<u32>,
+    pub data_section_offset: u32,
+    pub flash_start_addr: u64,
+    pub flash_end_addr: u64,
+    pub flash_page_size: u32,
+    pub erased_byte_value: u8,
+    pub sectors: Vec<Aarch64SectorRegion>,
+    pub program_timeout: u32,
+    pub erase_timeout: u32,
+    /// RAM declared for the target, or the computed requirement if none was declared.
+    pub ram_size: u32,
+    pub flash_size: u32,
+}
+
+fn sector_regions(flash_device: &Aarch64FlashDevice) -> Vec<Aarch64SectorRegion> {
+    let sectors = &flash_device.sectors;
+    sectors
+        .iter()
+        .enumerate()
+        .map(|(index, sector)| {
+            let end = sectors.get(index + 1).map_or(flash_device.device_size, |next| next.address);
+            Aarch64SectorRegion {
+                address: flash_device.start_address + u64::from(sector.address),
+                size: sector.size,
+                count: (end - sector.address) / sector.size,
+            }
+        })
+        .collect()
+}
+
+impl Aarch64FlashStub {
+    /// Build a stub from an AArch64 flash algorithm ELF.
+    ///
+    /// `ram_size` is the RAM available on the target; pass 0 to fill it in with the computed requirement.
+    pub fn from_elf(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<Aarch64FlashStub, Aarch64Error> {
+        let elf = Elf::parse(buf).map_err(|_| Aarch64Error::ElfParse)?;
+        if elf.header.e_machine != EM_AARCH64 {
+            return Err(Aarch64Error::WrongMachine(elf.header.e_machine));
+        }
+        if !elf.is_64 {
+            return Err(Aarch64Error::NotElf64);
+        }
+        if !elf.little_endian {
+            return Err(ArmError::BigEndianElf.into());
+        }
+
+        let mut flash_device = None;
+        let mut entry_points = Vec::new();
+        for sym in elf.syms.iter() {
+            let symbol = &elf.strtab[sym.st_name];
+            match symbol {
+                "FlashDevice" => {
+                    let address = u32::try_from(sym.st_value).map_err(|_| Aarch64Error::DescriptorOverflow {
+                        field: "FlashDevice",
+                        value: sym.st_value,
+                    })?;
+                    flash_device = Some(Aarch64FlashDevice::new(&elf, buf, address)?);
+                }
+                "Init" | "UnInit" | "EraseChip" | "EraseSector" | "ProgramPage" => entry_points.push((symbol, sym.st_value)),
+                _ => {}
+            }
+        }
+        let flash_device = flash_device.ok_or(ArmError::SymbolNotFound("FlashDevice"))?;
+
+        let algorithm_binary = AlgorithmBinary::new(&elf, buf)?;
+        if let Some(diagnostic) = algorithm_binary.suspicious_sections_diagnostic() {
+            tracing::warn!("{}", diagnostic);
+        }
+        let blobs = algorithm_binary.layout(&BlobLayout::default(), false)?;
+        let code = &algorithm_binary.code_section;
+
+        let mut algo = Aarch64FlashStub::default();
+        let mut program_page = None;
+        let mut erase_sector = None;
+        for (symbol, address) in entry_points {
+            let offset = address.wrapping_sub(u64::from(code.start));
+            let reason = if offset >= u64::from(code.length) {
+                Some("outside the code section")
+            } else if offset % 4 != 0 {
+                Some("A64 instructions are word aligned")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(Aarch64Error::InvalidEntryPoint { name: symbol.to_string(), offset, reason: reason.to_string() });
+            }
+
+            let offset = offset as u32;
+            match symbol {
+                "Init" => algo.pc_init = Some(offset),
+                "UnInit" => algo.pc_uninit = Some(offset),
+                "EraseChip" => algo.pc_erase_all = Some(offset),
+                "EraseSector" => erase_sector = Some(offset),
+                _ => program_page = Some(offset),
+            }
+        }
+        algo.pc_program_page = program_page.ok_or_else(|| Aarch64Error::EntryPointNotFound("ProgramPage".to_string()))?;
+        algo.pc_erase_sector = erase_sector.ok_or_else(|| Aarch64Error::EntryPointNotFound("EraseSector".to_string()))?;
+
+        algo.instructions = base64::encode(blobs.unified());
+        algo.name = name;
+        algo.sectors = sector_regions(&flash_device);
+        algo.description = flash_device.name;
+        algo.data_section_offset = blobs.data_offset;
+        algo.flash_start_addr = flash_device.start_address;
+        algo.flash_end_addr = flash_device.start_address + u64::from(flash_device.device_size);
+        algo.flash_size = flash_device.device_size;
+        algo.flash_page_size = flash_device.page_size;
+        algo.erase_timeout = flash_device.erase_sector_timeout;
+        algo.program_timeout = flash_device.program_page_timeout;
+        algo.erased_byte_value = flash_device.erased_default_value;
+        algo.default = default;
+
+        let ram_requirement = RamRequirement::new(&blobs, flash_device.page_size, DEFAULT_STACK_SIZE);
+        algo.ram_size = if ram_size == 0 {
+            ram_requirement.total()
+        } else {
+            ram_requirement.check(ram_size)?;
+            ram_size
+        };
+
+        Ok(algo)
+    }
+}
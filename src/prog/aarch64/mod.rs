@@ -0,0 +1,3 @@
+pub mod aarch64_error;
+pub mod flash_device;
+pub mod flash_stub_gen;
@@ -3,7 +3,7 @@ use goblin::{
     elf64::section_header::{SHT_NOBITS, SHT_PROGBITS},
 };
 
-use crate::arm::arm_error::ArmError;
+use crate::{diagnostic::Diagnostic, prog::arm::arm_error::ArmError};
 
 use super::memory_range::MemoryRange;
 
@@ -16,7 +16,7 @@ const BSS_SECTION_KEY: (&str, u32) = ("PrgData", SHT_NOBITS);
 /// These sections are usually present in Rust/C binaries,
 /// but should not be present in flash loader binaries.
 ///
-/// If these are observed in the binary, we issue a warning.
+/// If these are observed in the binary, we issue a diagnostic.
 const SUSPICIOUS_SECTION_NAMES: &[&str] = &[".text", ".rodata", ".data", ".sdata", ".bss", ".sbss"];
 
 /// An ELF section of the flash algorithm ELF.
@@ -27,12 +27,62 @@ pub(crate) struct Section {
     pub(crate) data: Vec<u8>,
 }
 
+/// How the code and data blobs are laid out when emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobLayout {
+    /// Emit the data (with zeroed bss) as a blob of its own instead of appending it to the code.
+    pub split: bool,
+    /// Alignment of the data relative to the load address, must be a power of two.
+    pub data_alignment: u32,
+    /// Byte used to pad the code up to the data.
+    pub padding: u8,
+}
+
+impl Default for BlobLayout {
+    fn default() -> Self {
+        Self {
+            split: false,
+            data_alignment: 1,
+            padding: 0,
+        }
+    }
+}
+
+/// The emitted blobs of a flash algorithm.
+#[derive(Debug, Clone)]
+pub(crate) struct Blobs {
+    /// Code, padded up to `data_offset`.
+    pub(crate) code: Vec<u8>,
+    pub(crate) data: Vec<u8>,
+    pub(crate) bss_length: u32,
+    /// Offset of the data from the load address.
+    pub(crate) data_offset: u32,
+}
+
+impl Blobs {
+    /// Data followed by the zeroed bss.
+    pub(crate) fn data_with_bss(&self) -> Vec<u8> {
+        let mut blob = self.data.clone();
+        blob.extend(&vec![0; self.bss_length as usize]);
+        blob
+    }
+
+    /// Assembles one huge binary blob as u8 values to write to RAM from the three sections.
+    pub(crate) fn unified(&self) -> Vec<u8> {
+        let mut blob = self.code.clone();
+        blob.extend(self.data_with_bss());
+        blob
+    }
+}
+
 /// A struct to hold all the binary sections of a flash algorithm ELF that go into flash.
 #[derive(Debug, Clone)]
 pub(crate) struct AlgorithmBinary {
     pub(crate) code_section: Section,
     pub(crate) data_section: Section,
     pub(crate) bss_section: Section,
+    /// Loadable sections from `SUSPICIOUS_SECTION_NAMES`.
+    pub(crate) suspicious_sections: Vec<String>,
 }
 
 impl AlgorithmBinary {
@@ -71,7 +121,7 @@ impl AlgorithmBinary {
                             BSS_SECTION_KEY => bss_section = section,
                             (name, _section_type) => {
                                 if SUSPICIOUS_SECTION_NAMES.contains(&name) {
-                                    suspicious_sections.push(name);
+                                    suspicious_sections.push(name.to_string());
                                 }
                             }
                         }
@@ -80,16 +130,6 @@ impl AlgorithmBinary {
             }
         }
 
-        if !suspicious_sections.is_empty() {
-            log::warn!("The ELF file contains some unexpected sections, which should not be part of a flash loader: ");
-
-            for section in suspicious_sections {
-                log::warn!("\t{}", section);
-            }
-
-            log::warn!("Code should be placed in the '{}' section, and data should be placed in the '{}' section.", CODE_SECTION_KEY.0, DATA_SECTION_KEY.0);
-        }
-
         // Check all the sections for validity and return the binary blob if possible.
         let code_section = code_section.ok_or_else(|| {
             ArmError::StubSectionNotFound(CODE_SECTION_KEY.0.to_string())
@@ -111,17 +151,51 @@ impl AlgorithmBinary {
                 length: 0,
                 data: Vec::new(),
             }),
+            suspicious_sections,
         })
     }
 
-    /// Assembles one huge binary blob as u8 values to write to RAM from the three sections.
-    pub(crate) fn blob(&self) -> Vec<u8> {
-        let mut blob = Vec::new();
+    /// Points out sections that should not be part of a flash loader, if there are any.
+    pub(crate) fn suspicious_sections_diagnostic(&self) -> Option<Diagnostic> {
+        if self.suspicious_sections.is_empty() {
+            return None;
+        }
 
-        blob.extend(&self.code_section.data);
-        blob.extend(&self.data_section.data);
-        blob.extend(&vec![0; self.bss_section.length as usize]);
+        Some(Diagnostic::warning(
+            "unexpected-section",
+            format!(
+                "the ELF file contains sections a flash loader should not have ({}), code belongs in '{}' and data in '{}'",
+                self.suspicious_sections.join(", "),
+                CODE_SECTION_KEY.0,
+                DATA_SECTION_KEY.0
+            ),
+        ))
+    }
 
-        blob
+    /// Lays out the code and data blobs.
+    ///
+    /// The data keeps its linked offset from the code unless `relocatable_data` is set
+    /// (static base addressing), in which case it may be moved up to honour the alignment.
+    pub(crate) fn layout(&self, layout: &BlobLayout, relocatable_data: bool) -> Result<Blobs, ArmError> {
+        let alignment = layout.data_alignment.max(1);
+        if !alignment.is_power_of_two() {
+            return Err(ArmError::InvalidAlignment(alignment));
+        }
+
+        let linked_offset = (self.data_section.start.saturating_sub(self.code_section.start)).max(self.code_section.length);
+        let data_offset = (linked_offset + alignment - 1) & !(alignment - 1);
+        if data_offset != linked_offset && !relocatable_data {
+            return Err(ArmError::DataMisaligned { offset: linked_offset, alignment });
+        }
+
+        let mut code = self.code_section.data.clone();
+        code.resize(data_offset as usize, layout.padding);
+
+        Ok(Blobs {
+            code,
+            data: self.data_section.data.clone(),
+            bss_length: self.bss_section.length,
+            data_offset,
+        })
     }
 }
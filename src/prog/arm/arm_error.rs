@@ -1,16 +1,208 @@
+use std::io;
+
+use soulcomposer_core::DescriptorError;
 use thiserror::Error;
 
+use crate::{diagnostic::Diagnostic, prog::generic::generic_error::GenericError};
+
+use super::trustzone::Security;
+
 #[derive(Debug, Error)]
 pub enum ArmError {
     #[error("Section {0} not found, which is required to be present.")]
     StubSectionNotFound(String),
 
-    #[error("Failed to read binary data for flash device, {0}")]
-    ReadBinaryInfoFail(String),
+    #[error("FlashDevice at {address:#010x} is malformed, {size} bytes can't be read from the loadable segments")]
+    MalformedDescriptor { address: u32, size: u32 },
+
+    #[error("Failed to parse ELF file, {0}")]
+    ElfParse(#[from] goblin::error::Error),
+
+    #[error("Symbol {0} not found in the ELF symbol table")]
+    SymbolNotFound(&'static str),
+
+    #[error("FlashDevice at {address:#010x} has driver version {version:#06x}, only 1.x descriptors are supported")]
+    UnsupportedDriverVersion { address: u32, version: u16 },
+
+    #[error("FlashDevice at {address:#010x} has a {length} byte name, the limit is {limit} bytes")]
+    NameTooLong { address: u32, length: usize, limit: usize },
+
+    #[error("FlashDevice at {address:#010x} has more than {limit} sector regions")]
+    TooManySectors { address: u32, limit: usize },
+
+    #[error("FlashDevice sector table is empty, at least one sector region is required")]
+    SectorTableEmpty,
+
+    #[error("Sector region {index} at offset {address:#010x} has a sector size of 0 bytes")]
+    SectorSizeZero { index: usize, address: u32 },
+
+    #[error("Sector region {index} must start at offset 0x00000000 to cover the device, but starts at {address:#010x}")]
+    SectorTableGap { index: usize, address: u32 },
+
+    #[error("Sector region {index} at offset {address:#010x} does not come after the previous region at {previous:#010x}; the sector table must be sorted and non-overlapping")]
+    SectorTableUnsorted { index: usize, address: u32, previous: u32 },
+
+    #[error("Sector region {index} spans {start:#010x}..{end:#010x}, which is not a multiple of its {size} byte sector size; check the region boundary or the device size")]
+    SectorRegionMisaligned { index: usize, start: u32, end: u32, size: u32 },
+
+    #[error("Sector region {index} at offset {address:#010x} starts beyond the device size of {device_size} bytes")]
+    SectorRegionOutOfRange { index: usize, address: u32, device_size: u32 },
+
+    #[error("Flash algorithm needs {required} bytes of RAM, but the target only declares {available} bytes")]
+    RamOverflow { required: u32, available: u32 },
+
+    #[error("Failed to decode stub instructions, {0}")]
+    InstructionDecode(String),
+
+    #[error("Data offset {offset:#x} is past the end of the {length} byte instruction blob")]
+    DataOffsetOutOfRange { offset: u32, length: u32 },
+
+    #[error("Stub CRC mismatch, expected {expected:#010x} but computed {actual:#010x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+
+    #[error("Entry point {name} at offset {offset:#010x} looks invalid: {reason}")]
+    InvalidEntryPoint { name: String, offset: u32, reason: String },
+
+    #[error("Big-endian ELF files are not supported, flash algorithms must be little-endian")]
+    BigEndianElf,
+
+    #[error("Emulator error, {0}")]
+    Emulator(String),
+
+    #[error("Range {address:#010x} + {size} bytes is outside the flash described by the stub")]
+    AddressOutOfRange { address: u32, size: u32 },
 
-    #[error("Failed to parse ELF file")]
-    ElfParse,
+    #[error("Page size conflict, the FLM says {flm} bytes but the PDSC says {pdsc} bytes")]
+    PageSizeConflict { flm: u32, pdsc: u32 },
+
+    #[error("Data alignment must be a power of two, got {0}")]
+    InvalidAlignment(u32),
+
+    #[error("Data is linked at offset {offset:#x}, which is not {alignment} byte aligned, and can't be moved without static base addressing")]
+    DataMisaligned { offset: u32, alignment: u32 },
+
+    #[error("Verify failed at offset {offset:#x}, expected {expected:#04x} but read {actual:#04x}")]
+    VerifyMismatch { offset: u32, expected: u8, actual: u8 },
+
+    #[error("Bank {second} starting at {address:#010x} overlaps bank {first}")]
+    BankOverlap { first: u8, second: u8, address: u32 },
+
+    #[error("Breakpoint shims have already been applied to this stub")]
+    ShimAlreadyApplied,
+
+    #[error("Entry point at {target:#010x} is out of range for a shim branch")]
+    ShimOutOfRange { target: u32 },
+
+    #[error("{what} at {address:#010x} is not in the {expected} alias")]
+    SecurityAliasMismatch { what: &'static str, address: u32, expected: Security },
+
+    #[error("Processor {0} is not part of the device")]
+    UnknownProcessor(String),
+
+    #[error("Device has {0} cores, the algorithm must be pinned to one")]
+    CorePinningRequired(usize),
+
+    #[error("Core index {index} is out of range, the device has {count} cores")]
+    CoreIndexOutOfRange { index: u32, count: usize },
+
+    #[error("Core pinning {field} mismatch, the stub says {stub} but the PDSC says {pdsc}")]
+    CorePinningMismatch { field: &'static str, stub: String, pdsc: String },
+
+    #[error("Loader descriptor is for {0}, not ARM")]
+    WrongArchitecture(String),
+
+    #[error("Entry point {0} is required but missing")]
+    EntryPointMissing(&'static str),
+
+    #[error("No entry point {name}, the algorithm has {available}")]
+    UnknownEntryPoint { name: String, available: String },
+
+    #[error("Descriptor {field} {value:#x} does not fit in 32 bits")]
+    DescriptorOverflow { field: &'static str, value: u64 },
+
+    #[error(transparent)]
+    Descriptor(#[from] GenericError),
+
+    #[error("Refused in strict mode, {0}")]
+    Rejected(Diagnostic),
+
+    #[error("Failed to read the algorithm, {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Stub is encrypted with key {0}, decrypt it first")]
+    StubEncrypted(String),
+}
+
+impl ArmError {
+    /// Stable code of the error, `SC0001` and up, for FFI callers and CI pipelines to branch on
+    /// instead of the message. New errors get the next free code, codes are never reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ArmError::StubSectionNotFound(..) => "SC0001",
+            ArmError::MalformedDescriptor { .. } => "SC0002",
+            ArmError::ElfParse(..) => "SC0003",
+            ArmError::SymbolNotFound(..) => "SC0004",
+            ArmError::UnsupportedDriverVersion { .. } => "SC0005",
+            ArmError::NameTooLong { .. } => "SC0006",
+            ArmError::TooManySectors { .. } => "SC0007",
+            ArmError::SectorTableEmpty => "SC0008",
+            ArmError::SectorSizeZero { .. } => "SC0009",
+            ArmError::SectorTableGap { .. } => "SC0010",
+            ArmError::SectorTableUnsorted { .. } => "SC0011",
+            ArmError::SectorRegionMisaligned { .. } => "SC0012",
+            ArmError::SectorRegionOutOfRange { .. } => "SC0013",
+            ArmError::RamOverflow { .. } => "SC0014",
+            ArmError::InstructionDecode(..) => "SC0015",
+            ArmError::DataOffsetOutOfRange { .. } => "SC0016",
+            ArmError::CrcMismatch { .. } => "SC0017",
+            ArmError::InvalidEntryPoint { .. } => "SC0018",
+            ArmError::BigEndianElf => "SC0019",
+            ArmError::Emulator(..) => "SC0020",
+            ArmError::AddressOutOfRange { .. } => "SC0021",
+            ArmError::PageSizeConflict { .. } => "SC0022",
+            ArmError::InvalidAlignment(..) => "SC0023",
+            ArmError::DataMisaligned { .. } => "SC0024",
+            ArmError::VerifyMismatch { .. } => "SC0025",
+            ArmError::BankOverlap { .. } => "SC0026",
+            ArmError::ShimAlreadyApplied => "SC0027",
+            ArmError::ShimOutOfRange { .. } => "SC0028",
+            ArmError::SecurityAliasMismatch { .. } => "SC0029",
+            ArmError::UnknownProcessor(..) => "SC0030",
+            ArmError::CorePinningRequired(..) => "SC0031",
+            ArmError::CoreIndexOutOfRange { .. } => "SC0032",
+            ArmError::CorePinningMismatch { .. } => "SC0033",
+            ArmError::WrongArchitecture(..) => "SC0034",
+            ArmError::EntryPointMissing(..) => "SC0035",
+            ArmError::UnknownEntryPoint { .. } => "SC0036",
+            ArmError::DescriptorOverflow { .. } => "SC0037",
+            ArmError::Descriptor(..) => "SC0038",
+            ArmError::Rejected(..) => "SC0039",
+            ArmError::Io(..) => "SC0040",
+            ArmError::StubEncrypted(..) => "SC0041",
+        }
+    }
+}
 
-    #[error("FlashDevice information not found")]
-    FlashDeviceInfoNotFound,
+impl From<DescriptorError> for ArmError {
+    fn from(err: DescriptorError) -> Self {
+        match err {
+            DescriptorError::Malformed { address, size } => ArmError::MalformedDescriptor { address, size },
+            DescriptorError::SymbolNotFound(name) => ArmError::SymbolNotFound(name),
+            DescriptorError::UnsupportedDriverVersion { address, version } => ArmError::UnsupportedDriverVersion { address, version },
+            DescriptorError::NameTooLong { address, length, limit } => ArmError::NameTooLong { address, length, limit },
+            DescriptorError::TooManySectors { address, limit } => ArmError::TooManySectors { address, limit },
+            DescriptorError::SectorTableEmpty => ArmError::SectorTableEmpty,
+            DescriptorError::SectorSizeZero { index, address } => ArmError::SectorSizeZero { index, address },
+            DescriptorError::SectorTableGap { index, address } => ArmError::SectorTableGap { index, address },
+            DescriptorError::SectorTableUnsorted { index, address, previous } => {
+                ArmError::SectorTableUnsorted { index, address, previous }
+            }
+            DescriptorError::SectorRegionMisaligned { index, start, end, size } => {
+                ArmError::SectorRegionMisaligned { index, start, end, size }
+            }
+            DescriptorError::SectorRegionOutOfRange { index, address, device_size } => {
+                ArmError::SectorRegionOutOfRange { index, address, device_size }
+            }
+        }
+    }
 }
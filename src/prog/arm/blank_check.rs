@@ -0,0 +1,60 @@
+use std::ops::Range;
+
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+impl ArmFlashStub {
+    /// A page as it reads right after erasing, which isn't always 0xFF (e.g. 0x00 on some FRAM/EEPROM).
+    pub fn erased_page(&self) -> Vec<u8> {
+        vec![self.erased_byte_value; self.flash_page_size as usize]
+    }
+
+    /// Returns true if every byte holds the erased value.
+    pub fn is_blank(&self, data: &[u8]) -> bool {
+        data.iter().all(|&byte| byte == self.erased_byte_value)
+    }
+
+    /// Pads `data` up to a whole number of pages with the erased value, so the tail stays untouched.
+    pub fn pad_to_page(&self, data: &mut Vec<u8>) {
+        let page_size = self.flash_page_size.max(1) as usize;
+        let padded = data.len().div_ceil(page_size) * page_size;
+        data.resize(padded, self.erased_byte_value);
+    }
+
+    /// Compares data read back from flash with what was written.
+    ///
+    /// Bytes beyond `written` must still be erased.
+    pub fn verify(&self, written: &[u8], read_back: &[u8]) -> Result<(), ArmError> {
+        let expected = written.iter().copied().chain(std::iter::repeat(self.erased_byte_value));
+        match read_back.iter().zip(expected).position(|(&actual, expected)| actual != expected) {
+            None => Ok(()),
+            Some(offset) => Err(ArmError::VerifyMismatch {
+                offset: offset as u32,
+                expected: written.get(offset).copied().unwrap_or(self.erased_byte_value),
+                actual: read_back[offset],
+            }),
+        }
+    }
+
+    /// The sector holding `address`, the last sector of the flash without one. Hardware checks
+    /// overwrite it.
+    pub fn scratch_sector(&self, address: Option<u32>) -> Option<Range<u64>> {
+        let mut sectors = self.sectors.iter().flat_map(|region| {
+            (0..u64::from(region.count)).map(move |index| {
+                let start = u64::from(region.address) + index * u64::from(region.size);
+                start..start + u64::from(region.size)
+            })
+        });
+
+        match address {
+            Some(address) => sectors.find(|sector| sector.contains(&u64::from(address))),
+            None => sectors.last(),
+        }
+    }
+
+    /// What hardware checks program: the erased value is nowhere in it, so a sector that was only
+    /// erased can't pass.
+    pub fn test_pattern(&self, length: usize) -> Vec<u8> {
+        let erased = self.erased_byte_value;
+        (0..length).map(|index| (index as u8).wrapping_mul(31).wrapping_add(7)).map(|byte| if byte == erased { !byte } else { byte }).collect()
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use goblin::elf::Elf;
+
+/// Name of the section holding the ARM EABI build attributes.
+const ATTRIBUTES_SECTION: &str = ".ARM.attributes";
+/// Vendor name of the public EABI attributes subsection.
+const AEABI_VENDOR: &[u8] = b"aeabi";
+/// Tag of the sub-subsection whose attributes apply to the whole file.
+const TAG_FILE: u64 = 1;
+
+pub const TAG_CPU_NAME: u64 = 5;
+pub const TAG_CPU_ARCH: u64 = 6;
+pub const TAG_CPU_ARCH_PROFILE: u64 = 7;
+pub const TAG_THUMB_ISA_USE: u64 = 9;
+pub const TAG_FP_ARCH: u64 = 10;
+pub const TAG_ABI_PCS_RW_DATA: u64 = 15;
+pub const TAG_DSP_EXTENSION: u64 = 46;
+
+/// `Tag_ABI_PCS_RW_data` value for RW data addressed relative to the static base (r9).
+pub const RW_DATA_SB_RELATIVE: u64 = 2;
+
+/// File-scope build attributes from the `.ARM.attributes` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildAttributes {
+    integers: BTreeMap<u64, u64>,
+    strings: BTreeMap<u64, String>,
+}
+
+impl BuildAttributes {
+    /// Reads the attributes of an ELF, returning `None` if it has no (readable) attribute section.
+    pub fn from_elf(elf: &Elf<'_>, buffer: &[u8]) -> Option<Self> {
+        let section = elf
+            .section_headers
+            .iter()
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(ATTRIBUTES_SECTION))?;
+        let data = buffer.get(section.sh_offset as usize..)?.get(..section.sh_size as usize)?;
+
+        Self::parse(data)
+    }
+
+    /// Parses the raw contents of an attribute section.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut attributes = Self::default();
+        for body in file_attributes(data, AEABI_VENDOR)? {
+            attributes.parse_entries(body)?;
+        }
+
+        Some(attributes)
+    }
+
+    fn parse_entries(&mut self, mut body: &[u8]) -> Option<()> {
+        while !body.is_empty() {
+            let (tag, len) = read_uleb128(body)?;
+            body = &body[len..];
+
+            // Tag_compatibility is an integer followed by a string, other tags follow the
+            // EABI rule: CPU names and odd tags from 32 up are strings, the rest integers.
+            if tag == 32 {
+                let (value, len) = read_uleb128(body)?;
+                self.integers.insert(tag, value);
+                body = &body[len..];
+            }
+
+            if matches!(tag, 4 | TAG_CPU_NAME | 32) || (tag > 32 && tag % 2 == 1) {
+                let end = body.iter().position(|&c| c == 0)?;
+                self.strings.insert(tag, String::from_utf8_lossy(&body[..end]).to_string());
+                body = &body[end + 1..];
+            } else {
+                let (value, len) = read_uleb128(body)?;
+                self.integers.insert(tag, value);
+                body = &body[len..];
+            }
+        }
+
+        Some(())
+    }
+
+    /// Value of an integer attribute.
+    pub fn integer(&self, tag: u64) -> Option<u64> {
+        self.integers.get(&tag).copied()
+    }
+
+    /// Value of a string attribute.
+    pub fn string(&self, tag: u64) -> Option<&str> {
+        self.strings.get(&tag).map(String::as_str)
+    }
+}
+
+/// Splits an attribute section into the file-scope attribute bodies of `vendor`.
+///
+/// The container format is shared by the ARM EABI and the RISC-V psABI, only the vendor name
+/// and the tag types differ.
+pub(crate) fn file_attributes<'a>(data: &'a [u8], vendor: &[u8]) -> Option<Vec<&'a [u8]>> {
+    let (&format, mut subsections) = data.split_first()?;
+    if format != b'A' {
+        return None;
+    }
+
+    let mut bodies = Vec::new();
+    while subsections.len() >= 4 {
+        let length = read_u32(subsections)? as usize;
+        let subsection = subsections.get(4..length)?;
+        subsections = &subsections[length.max(4)..];
+
+        let vendor_end = subsection.iter().position(|&c| c == 0)?;
+        if &subsection[..vendor_end] != vendor {
+            continue;
+        }
+
+        let mut entries = &subsection[vendor_end + 1..];
+        while !entries.is_empty() {
+            let (tag, tag_len) = read_uleb128(entries)?;
+            let size = read_u32(entries.get(tag_len..)?)? as usize;
+            let body = entries.get(tag_len + 4..size)?;
+            entries = &entries[size.max(tag_len + 4)..];
+
+            if tag == TAG_FILE {
+                bodies.push(body);
+            }
+        }
+    }
+
+    Some(bodies)
+}
+
+fn read_u32(data: &[u8]) -> Option<u32> {
+    data.get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn read_uleb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, &byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7F) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashSet, fmt};
+
+use super::{
+    arm_error::ArmError,
+    build_attributes::{BuildAttributes, TAG_CPU_ARCH, TAG_DSP_EXTENSION, TAG_FP_ARCH},
+    flash_stub_gen::ArmFlashStub,
+    thumb::{self, Instruction},
+};
+
+/// Upper bound on instructions walked, guards against garbage input.
+const MAX_INSTRUCTIONS: usize = 0x10000;
+
+// `Tag_CPU_arch` values for M-profile architectures.
+const ARCH_V7: u64 = 10;
+const ARCH_V6_M: u64 = 11;
+const ARCH_V6S_M: u64 = 12;
+const ARCH_V7E_M: u64 = 13;
+const ARCH_V8_M_BASE: u64 = 16;
+const ARCH_V8_M_MAIN: u64 = 17;
+
+/// Cortex-M cores an algorithm can be checked against.
+///
+/// Optional extensions are assumed present when the core commonly ships with them: DSP and a
+/// single precision FPU on M4 and M33, a double precision FPU on M7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Core {
+    CortexM0,
+    CortexM0Plus,
+    CortexM3,
+    CortexM4,
+    CortexM7,
+    CortexM33,
+}
+
+impl Core {
+    /// Parses names like "M0+", "Cortex-M4" or the PDSC `Dcore` values.
+    pub fn from_name(name: &str) -> Option<Core> {
+        let name = name.trim().to_ascii_uppercase();
+        let name = name.trim_start_matches("CORTEX-").trim_start_matches("ARM");
+        match name {
+            "M0" => Some(Core::CortexM0),
+            "M0+" | "M0PLUS" => Some(Core::CortexM0Plus),
+            "M3" => Some(Core::CortexM3),
+            "M4" => Some(Core::CortexM4),
+            "M7" => Some(Core::CortexM7),
+            "M33" => Some(Core::CortexM33),
+            _ => None,
+        }
+    }
+
+    fn thumb2(self) -> bool {
+        !matches!(self, Core::CortexM0 | Core::CortexM0Plus)
+    }
+
+    fn dsp(self) -> bool {
+        matches!(self, Core::CortexM4 | Core::CortexM7 | Core::CortexM33)
+    }
+
+    fn fpu(self) -> bool {
+        self.dsp()
+    }
+
+    fn double_precision(self) -> bool {
+        self == Core::CortexM7
+    }
+
+    /// `Tag_CPU_arch` values whose code runs on this core.
+    fn architectures(self) -> &'static [u64] {
+        match self {
+            Core::CortexM0 | Core::CortexM0Plus => &[ARCH_V6_M, ARCH_V6S_M],
+            Core::CortexM3 => &[ARCH_V6_M, ARCH_V6S_M, ARCH_V7],
+            Core::CortexM4 | Core::CortexM7 => &[ARCH_V6_M, ARCH_V6S_M, ARCH_V7, ARCH_V7E_M],
+            Core::CortexM33 => &[ARCH_V6_M, ARCH_V6S_M, ARCH_V7, ARCH_V7E_M, ARCH_V8_M_BASE, ARCH_V8_M_MAIN],
+        }
+    }
+}
+
+impl fmt::Display for Core {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Core::CortexM0 => "Cortex-M0",
+            Core::CortexM0Plus => "Cortex-M0+",
+            Core::CortexM3 => "Cortex-M3",
+            Core::CortexM4 => "Cortex-M4",
+            Core::CortexM7 => "Cortex-M7",
+            Core::CortexM33 => "Cortex-M33",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The ISA feature a violation needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsaFeature {
+    /// The build attributes name an architecture the core doesn't implement.
+    Architecture,
+    /// 32-bit Thumb-2 instructions beyond what ARMv6-M has, and CBZ/IT.
+    Thumb2,
+    Dsp,
+    Fpu,
+    DoublePrecision,
+}
+
+/// One place where the algorithm needs more than the core offers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsaViolation {
+    pub feature: IsaFeature,
+    /// Offset of the instruction in the code blob, `None` for build attribute findings.
+    pub offset: Option<u32>,
+    pub description: String,
+}
+
+impl fmt::Display for IsaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{:#010x}: {}", offset, self.description),
+            None => f.write_str(&self.description),
+        }
+    }
+}
+
+/// 32-bit instructions available on ARMv6-M: BL, MSR, MRS, the barriers and UDF.W.
+fn is_v6m_wide(hw1: u16, hw2: u16) -> bool {
+    let bl = hw1 & 0xF800 == 0xF000 && hw2 & 0xD000 == 0xD000;
+    let msr = hw1 & 0xFFE0 == 0xF380 && hw2 & 0xD000 == 0x8000;
+    let mrs = hw1 == 0xF3EF && hw2 & 0xD000 == 0x8000;
+    let barrier = hw1 == 0xF3BF && hw2 & 0xFF00 == 0x8F00;
+    let udf = hw1 & 0xFFF0 == 0xF7F0 && hw2 & 0xF000 == 0xA000;
+    bl || msr || mrs || barrier || udf
+}
+
+/// Classifies a 32-bit instruction by the extension it needs, if any beyond Thumb-2.
+fn wide_feature(hw1: u16, hw2: u16) -> Option<IsaFeature> {
+    // Coprocessor space with cp10/cp11 is the FPU, cp11 (or sz set) is double precision.
+    if hw1 & 0xEC00 == 0xEC00 && hw2 & 0x0E00 == 0x0A00 {
+        return Some(if hw2 & 0x0100 != 0 { IsaFeature::DoublePrecision } else { IsaFeature::Fpu });
+    }
+
+    // Parallel add/subtract and saturating arithmetic.
+    if hw1 & 0xFF80 == 0xFA80 && hw2 & 0xF000 == 0xF000 {
+        return Some(IsaFeature::Dsp);
+    }
+
+    // Multiply and multiply-accumulate, only MUL/MLA/MLS (op1 000) are baseline Thumb-2.
+    if hw1 & 0xFF80 == 0xFB00 && hw1 & 0x0070 != 0 {
+        return Some(IsaFeature::Dsp);
+    }
+
+    // Long multiply: SMULL, SDIV, UMULL, UDIV and plain SMLAL/UMLAL are baseline, the rest is DSP.
+    if hw1 & 0xFF80 == 0xFB80 {
+        let op1 = (hw1 >> 4) & 0x7;
+        let op2 = (hw2 >> 4) & 0xF;
+        let baseline = matches!((op1, op2), (0..=3, _) | (4, 0) | (6, 0));
+        if !baseline {
+            return Some(IsaFeature::Dsp);
+        }
+    }
+
+    None
+}
+
+/// Checks that `code` (and its build attributes) only uses what `core` implements.
+///
+/// Only code reachable from `entries` (offsets into `code`, Thumb bit ignored) is decoded, so
+/// literal pools are not mistaken for instructions.
+pub fn check_core(code: &[u8], entries: &[u32], attributes: Option<&BuildAttributes>, core: Core) -> Vec<IsaViolation> {
+    let mut violations = Vec::new();
+
+    if let Some(attributes) = attributes {
+        if let Some(arch) = attributes.integer(TAG_CPU_ARCH) {
+            if !core.architectures().contains(&arch) {
+                violations.push(IsaViolation {
+                    feature: IsaFeature::Architecture,
+                    offset: None,
+                    description: format!("built for Tag_CPU_arch {}, which {} does not implement", arch, core),
+                });
+            }
+        }
+        if attributes.integer(TAG_FP_ARCH).unwrap_or(0) != 0 && !core.fpu() {
+            violations.push(IsaViolation {
+                feature: IsaFeature::Fpu,
+                offset: None,
+                description: format!("built with floating point instructions, {} has no FPU", core),
+            });
+        }
+        if attributes.integer(TAG_DSP_EXTENSION).unwrap_or(0) != 0 && !core.dsp() {
+            violations.push(IsaViolation {
+                feature: IsaFeature::Dsp,
+                offset: None,
+                description: format!("built with the DSP extension, which {} lacks", core),
+            });
+        }
+    }
+
+    let supported = |feature: IsaFeature| match feature {
+        IsaFeature::Architecture => true,
+        IsaFeature::Thumb2 => core.thumb2(),
+        IsaFeature::Dsp => core.dsp(),
+        IsaFeature::Fpu => core.fpu(),
+        IsaFeature::DoublePrecision => core.double_precision(),
+    };
+
+    let mut visited = HashSet::new();
+    let mut pending: Vec<u32> = entries.iter().map(|entry| entry & !1).collect();
+    while let Some(offset) = pending.pop() {
+        if !visited.insert(offset) || visited.len() > MAX_INSTRUCTIONS {
+            continue;
+        }
+
+        let decoded = match thumb::decode(code, 0, offset) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+
+        let feature = match decoded.instruction {
+            Instruction::CompareBranch { .. } => Some(IsaFeature::Thumb2),
+            Instruction::Other16 { raw } if raw & 0xFF00 == 0xBF00 && raw & 0xF != 0 => Some(IsaFeature::Thumb2),
+            Instruction::Other32 { raw } => {
+                let (hw1, hw2) = ((raw >> 16) as u16, raw as u16);
+                wide_feature(hw1, hw2).or(if is_v6m_wide(hw1, hw2) { None } else { Some(IsaFeature::Thumb2) })
+            }
+            Instruction::BranchLink { .. } | Instruction::Undefined { .. } => None,
+            // B.W, B<cond>.W, PUSH.W/POP.W and ADD.W/SUB.W sp.
+            _ if decoded.size == 4 => Some(IsaFeature::Thumb2),
+            _ => None,
+        };
+
+        if let Some(feature) = feature {
+            if !supported(feature) {
+                violations.push(IsaViolation {
+                    feature,
+                    offset: Some(offset),
+                    description: format!("{} needs {:?}, not available on {}", decoded.instruction, feature, core),
+                });
+            }
+        }
+
+        match decoded.instruction {
+            Instruction::Pop { registers } if registers & (1 << 15) != 0 => continue,
+            Instruction::BranchExchange { .. } | Instruction::Undefined { .. } => continue,
+            Instruction::BranchLink { target } => pending.push(target),
+            Instruction::Branch { target, cond } => {
+                pending.push(target);
+                if cond.is_none() {
+                    continue;
+                }
+            }
+            Instruction::CompareBranch { target, .. } => pending.push(target),
+            _ => {}
+        }
+        pending.push(offset + decoded.size);
+    }
+
+    violations.sort_by_key(|violation| violation.offset);
+    violations
+}
+
+impl ArmFlashStub {
+    /// Checks the algorithm code against `core`, see `check_core`.
+    ///
+    /// Build attributes aren't kept in the stub, pass them in if the ELF is at hand.
+    pub fn check_core(&self, core: Core, attributes: Option<&BuildAttributes>) -> Result<Vec<IsaViolation>, ArmError> {
+        let mut code = base64::decode(&self.instructions).map_err(|err| ArmError::InstructionDecode(err.to_string()))?;
+        if self.data_instructions.is_none() {
+            code.truncate(self.data_section_offset as usize);
+        }
+
+        let mut entries = vec![self.pc_program_page, self.pc_erase_sector];
+        entries.extend(vec![self.pc_init, self.pc_uninit, self.pc_erase_all].into_iter().flatten());
+        Ok(check_core(&code, &entries, attributes, core))
+    }
+}
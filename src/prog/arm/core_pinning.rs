@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pack::pdsc::PdscDevice;
+
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+/// The core an algorithm has to run on, for multi-core devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorePinning {
+    /// Index of the core in the device's processor list.
+    pub index: u32,
+    /// Access port the core is reached through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ap: Option<u32>,
+    /// `Pname` of the core in the PDSC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processor: Option<String>,
+}
+
+impl CorePinning {
+    /// Pins to the processor called `pname` in `device`.
+    pub fn from_pdsc(device: &PdscDevice, pname: &str) -> Result<CorePinning, ArmError> {
+        let (index, processor) = device
+            .processors
+            .iter()
+            .enumerate()
+            .find(|(_, processor)| processor.name.as_deref() == Some(pname))
+            .ok_or_else(|| ArmError::UnknownProcessor(pname.to_string()))?;
+
+        Ok(CorePinning {
+            index: index as u32,
+            ap: processor.ap,
+            processor: Some(pname.to_string()),
+        })
+    }
+}
+
+impl ArmFlashStub {
+    /// Checks the core pinning against the processors of `device`.
+    ///
+    /// Multi-core devices need a pinning, and it has to agree with the PDSC on index, name and
+    /// access port, as well as with the `Pname` of the PDSC algorithm for this flash, if any.
+    pub fn check_core_pinning(&self, device: &PdscDevice) -> Result<(), ArmError> {
+        let count = device.processors.len();
+        let pinning = match &self.pinned_core {
+            Some(pinning) => pinning,
+            None if count > 1 => return Err(ArmError::CorePinningRequired(count)),
+            None => return Ok(()),
+        };
+
+        let processor = device
+            .processors
+            .get(pinning.index as usize)
+            .ok_or(ArmError::CoreIndexOutOfRange { index: pinning.index, count })?;
+
+        if let Some(name) = &pinning.processor {
+            if processor.name.as_ref() != Some(name) {
+                return Err(ArmError::CorePinningMismatch {
+                    field: "processor",
+                    stub: name.clone(),
+                    pdsc: processor.name.clone().unwrap_or_default(),
+                });
+            }
+        }
+
+        if let (Some(stub), Some(pdsc)) = (pinning.ap, processor.ap) {
+            if stub != pdsc {
+                return Err(ArmError::CorePinningMismatch { field: "ap", stub: stub.to_string(), pdsc: pdsc.to_string() });
+            }
+        }
+
+        let algorithm = device.algorithm_for(self.flash_start_addr);
+        if let Some(pname) = algorithm.and_then(|algorithm| algorithm.processor.as_ref()) {
+            if processor.name.as_ref() != Some(pname) {
+                return Err(ArmError::CorePinningMismatch {
+                    field: "algorithm Pname",
+                    stub: processor.name.clone().unwrap_or_default(),
+                    pdsc: pname.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
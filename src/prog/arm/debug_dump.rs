@@ -0,0 +1,229 @@
+//! Annotated hex dumps of the `FlashDevice` struct and its sector table, for finding out why
+//! parsing produced a value: every field is shown at its offset with its raw bytes next to the
+//! value decoded from them.
+
+use std::fmt;
+
+use scroll::Pread;
+use soulcomposer_core::elf::{find_symbol, read_segment_data};
+
+use super::{
+    arm_error::ArmError,
+    flash_device::{FlashDevice, SectorInfo},
+    parse_options::ParseOptions,
+};
+
+const INFO_SIZE: u32 = soulcomposer_core::FlashDevice::INFO_SIZE;
+const SECTOR_INFO_SIZE: u32 = soulcomposer_core::FlashDevice::SECTOR_INFO_SIZE;
+const SECTOR_END: u32 = 0xFFFF_FFFF;
+const MAX_ID_STRING_LENGTH: usize = soulcomposer_core::FlashDevice::MAX_ID_STRING_LENGTH;
+
+/// Bytes shown per row, longer fields are cut and the rest counted.
+const ROW_BYTES: usize = 16;
+
+/// One field of a dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpRow {
+    /// Offset from the start of the dump.
+    pub offset: u32,
+    /// Field name, in the casing of the model, e.g. `sectors[1].size`.
+    pub field: String,
+    pub bytes: Vec<u8>,
+    /// The value parsing reads from `bytes`.
+    pub value: String,
+}
+
+/// The rows of a dump, offsets relative to `base`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HexDump {
+    /// Address the first row was linked at, 0 when it isn't known.
+    pub base: u32,
+    pub rows: Vec<DumpRow>,
+}
+
+impl HexDump {
+    fn row(&mut self, offset: u32, field: impl Into<String>, bytes: &[u8], value: String) {
+        self.rows.push(DumpRow { offset, field: field.into(), bytes: bytes.to_vec(), value });
+    }
+}
+
+/// `address  +offset  field  bytes  value`, one row per line.
+impl fmt::Display for HexDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.rows.iter().map(|row| row.field.len()).max().unwrap_or(0);
+        for row in &self.rows {
+            let mut bytes: Vec<String> = row.bytes.iter().take(ROW_BYTES).map(|byte| format!("{:02x}", byte)).collect();
+            if row.bytes.len() > ROW_BYTES {
+                bytes.push(format!("+{}", row.bytes.len() - ROW_BYTES));
+            }
+            let line = format!(
+                "{:#010x}  +{:#06x}  {:<width$}  {:<bytes_width$}  {}",
+                self.base.wrapping_add(row.offset),
+                row.offset,
+                row.field,
+                bytes.join(" "),
+                row.value,
+                width = width,
+                bytes_width = ROW_BYTES * 3 + 3,
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Annotated hex dump of a parsed structure.
+pub trait DebugDump {
+    fn debug_dump(&self) -> HexDump;
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data.pread_with(offset, scroll::LE).unwrap_or_default()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.pread_with(offset, scroll::LE).unwrap_or_default()
+}
+
+/// Dumps the 160 byte struct in `data` and the sector table after it, as far as `data` goes.
+fn dump_descriptor(dump: &mut HexDump, data: &[u8]) {
+    let field = |offset: usize, size: usize| &data[offset.min(data.len())..(offset + size).min(data.len())];
+
+    let version = read_u16(data, 0);
+    dump.row(0, "driverVersion", field(0, 2), format!("{:#06x}", version));
+
+    let name = field(2, MAX_ID_STRING_LENGTH);
+    let value = match name.iter().position(|&c| c == 0) {
+        Some(length) => format!("{:?}", String::from_utf8_lossy(&name[..length])),
+        None => format!("{:?}, not terminated", String::from_utf8_lossy(name)),
+    };
+    dump.row(2, "name", name, value);
+
+    let device_type = match read_u16(data, 130) {
+        1 => "1, on-chip".to_string(),
+        2 => "2, external".to_string(),
+        other => other.to_string(),
+    };
+    dump.row(130, "deviceType", field(130, 2), device_type);
+    dump.row(132, "startAddress", field(132, 4), format!("{:#010x}", read_u32(data, 132)));
+    dump.row(136, "deviceSize", field(136, 4), format!("{:#x}", read_u32(data, 136)));
+    dump.row(140, "pageSize", field(140, 4), read_u32(data, 140).to_string());
+    dump.row(144, "reserved", field(144, 4), format!("{:#x}", read_u32(data, 144)));
+    dump.row(148, "erasedDefaultValue", field(148, 1), format!("{:#04x}", data.get(148).copied().unwrap_or_default()));
+    dump.row(149, "padding", field(149, 3), String::new());
+    dump.row(152, "programPageTimeout", field(152, 4), format!("{} ms", read_u32(data, 152)));
+    dump.row(156, "eraseSectorTimeout", field(156, 4), format!("{} ms", read_u32(data, 156)));
+
+    dump_sectors(dump, data.get(INFO_SIZE as usize..).unwrap_or_default(), INFO_SIZE);
+}
+
+/// Dumps sector table entries from `data`, which starts `offset` bytes into the dump.
+fn dump_sectors(dump: &mut HexDump, data: &[u8], offset: u32) {
+    for (index, entry) in data.chunks_exact(SECTOR_INFO_SIZE as usize).enumerate() {
+        let offset = offset + index as u32 * SECTOR_INFO_SIZE;
+        let (size, address) = (read_u32(entry, 0), read_u32(entry, 4));
+        if size == SECTOR_END || address == SECTOR_END {
+            dump.row(offset, "sectors.end", entry, "end of table".to_string());
+            break;
+        }
+        dump.row(offset, format!("sectors[{}].size", index), &entry[..4], format!("{:#x}", size));
+        dump.row(offset + 4, format!("sectors[{}].address", index), &entry[4..], format!("{:#x}", address));
+    }
+}
+
+fn encode_sectors(sectors: &[SectorInfo], bytes: &mut Vec<u8>) {
+    for sector in sectors {
+        bytes.extend_from_slice(&sector.size.to_le_bytes());
+        bytes.extend_from_slice(&sector.address.to_le_bytes());
+    }
+    bytes.extend_from_slice(&SECTOR_END.to_le_bytes());
+    bytes.extend_from_slice(&SECTOR_END.to_le_bytes());
+}
+
+/// The parsed values laid out the way the FLM stores them. Bytes parsing drops, whatever follows
+/// the name and the reserved word, read as zero; dump a `RawDescriptor` to see those.
+impl DebugDump for FlashDevice {
+    fn debug_dump(&self) -> HexDump {
+        let mut bytes = vec![0; INFO_SIZE as usize];
+        bytes[..2].copy_from_slice(&self.driver_version.to_le_bytes());
+        let name = &self.name.as_bytes()[..self.name.len().min(MAX_ID_STRING_LENGTH - 1)];
+        bytes[2..2 + name.len()].copy_from_slice(name);
+        bytes[130..132].copy_from_slice(&self.typ.to_le_bytes());
+        for (offset, value) in [
+            (132, self.start_address),
+            (136, self.device_size),
+            (140, self.page_size),
+            (152, self.program_page_timeout),
+            (156, self.erase_sector_timeout),
+        ] {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[148] = self.erased_default_value;
+        encode_sectors(&self.sectors, &mut bytes);
+
+        let mut dump = HexDump::default();
+        dump_descriptor(&mut dump, &bytes);
+        dump
+    }
+}
+
+/// A sector table on its own, offsets from its first entry.
+impl DebugDump for [SectorInfo] {
+    fn debug_dump(&self) -> HexDump {
+        let mut bytes = Vec::new();
+        encode_sectors(self, &mut bytes);
+
+        let mut dump = HexDump::default();
+        dump_sectors(&mut dump, &bytes, 0);
+        dump
+    }
+}
+
+/// The bytes of a `FlashDevice` struct and its sector table as linked into an FLM, before any
+/// checks. Useful when parsing refuses the descriptor or reads something unexpected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawDescriptor<'a> {
+    /// Address the struct is linked at.
+    pub address: u32,
+    /// The struct, then the sector table up to and including the end marker when found.
+    pub data: &'a [u8],
+}
+
+impl<'a> RawDescriptor<'a> {
+    /// Finds the descriptor symbol, see `ParseOptions::descriptor_symbol`, and takes the bytes
+    /// behind it. The sector table is read up to the end marker or one entry past
+    /// `ParseOptions::max_sectors`, whichever comes first.
+    pub fn from_elf(elf: &goblin::elf::Elf<'_>, buffer: &'a [u8], options: &ParseOptions) -> Result<Self, ArmError> {
+        let address = options
+            .descriptor_symbol
+            .as_deref()
+            .and_then(|symbol| find_symbol(elf, symbol))
+            .or_else(|| find_symbol(elf, "FlashDevice"))
+            .ok_or(ArmError::SymbolNotFound("FlashDevice"))?;
+        let mut data = read_segment_data(elf, buffer, address, INFO_SIZE)
+            .ok_or(ArmError::MalformedDescriptor { address, size: INFO_SIZE })?;
+
+        for _ in 0..=options.max_sectors {
+            let size = data.len() as u32 + SECTOR_INFO_SIZE;
+            let extended = match read_segment_data(elf, buffer, address, size) {
+                Some(extended) => extended,
+                None => break,
+            };
+            data = extended;
+            let entry = &data[data.len() - SECTOR_INFO_SIZE as usize..];
+            if read_u32(entry, 0) == SECTOR_END || read_u32(entry, 4) == SECTOR_END {
+                break;
+            }
+        }
+
+        Ok(RawDescriptor { address, data })
+    }
+}
+
+impl DebugDump for RawDescriptor<'_> {
+    fn debug_dump(&self) -> HexDump {
+        let mut dump = HexDump { base: self.address, rows: Vec::new() };
+        dump_descriptor(&mut dump, self.data);
+        dump
+    }
+}
@@ -0,0 +1,134 @@
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+/// A contiguous piece of the algorithm image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadRegion {
+    /// Offset from the load address.
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+impl LoadRegion {
+    /// The first offset past the region.
+    pub fn end(&self) -> u32 {
+        self.offset + self.data.len() as u32
+    }
+}
+
+/// Entry points as offsets from the load address, without the Thumb bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryOffsets {
+    pub init: Option<u32>,
+    pub uninit: Option<u32>,
+    pub program_page: u32,
+    pub erase_sector: u32,
+    pub erase_all: Option<u32>,
+}
+
+impl EntryOffsets {
+    /// Every entry point present, by its CMSIS name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u32)> {
+        let entries = vec![
+            ("Init", self.init),
+            ("UnInit", self.uninit),
+            ("ProgramPage", Some(self.program_page)),
+            ("EraseSector", Some(self.erase_sector)),
+            ("EraseChip", self.erase_all),
+        ];
+        entries.into_iter().filter_map(|(name, offset)| offset.map(|offset| (name, offset)))
+    }
+}
+
+/// A stored stub taken apart again into the regions and entry points it was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecomposedStub {
+    /// The code at offset 0, padded up to the data.
+    pub code: LoadRegion,
+    /// The data with its zeroed bss, empty for algorithms without any.
+    pub data: LoadRegion,
+    pub entry_points: EntryOffsets,
+    /// Offset of the RW data for static base relative algorithms, see `ArmFlashStub::static_base`.
+    pub static_base: Option<u32>,
+}
+
+impl DecomposedStub {
+    /// The image as it is loaded into RAM.
+    pub fn image(&self) -> Vec<u8> {
+        let mut image = self.code.data.clone();
+        image.extend(&self.data.data);
+        image
+    }
+}
+
+impl ArmFlashStub {
+    /// Decodes the instruction payload and splits it back into code, data and entry points.
+    ///
+    /// The CRC is checked first when the stub carries one, and every entry point must land in
+    /// the code. An encrypted stub is refused.
+    pub fn decompose(&self) -> Result<DecomposedStub, ArmError> {
+        if let Some(encryption) = &self.encryption {
+            return Err(ArmError::StubEncrypted(encryption.key_id.clone()));
+        }
+        if self.crc32.is_some() {
+            self.verify_crc32()?;
+        }
+
+        let decode = |blob: &str| base64::decode(blob).map_err(|err| ArmError::InstructionDecode(err.to_string()));
+        let mut code = decode(&self.instructions)?;
+        let data = match &self.data_instructions {
+            Some(data) => {
+                code.resize(self.data_section_offset as usize, 0);
+                decode(data)?
+            }
+            None => {
+                if self.data_section_offset as usize > code.len() {
+                    return Err(ArmError::DataOffsetOutOfRange { offset: self.data_section_offset, length: code.len() as u32 });
+                }
+                code.split_off(self.data_section_offset as usize)
+            }
+        };
+
+        let offset = |pc: u32| pc & !1;
+        let entry_points = EntryOffsets {
+            init: self.pc_init.map(offset),
+            uninit: self.pc_uninit.map(offset),
+            program_page: offset(self.pc_program_page),
+            erase_sector: offset(self.pc_erase_sector),
+            erase_all: self.pc_erase_all.map(offset),
+        };
+        for (name, offset) in entry_points.iter() {
+            if offset as usize >= code.len() {
+                return Err(ArmError::InvalidEntryPoint {
+                    name: name.to_string(),
+                    offset,
+                    reason: format!("the code is only {} bytes", code.len()),
+                });
+            }
+        }
+
+        Ok(DecomposedStub {
+            code: LoadRegion { offset: 0, data: code },
+            data: LoadRegion { offset: self.data_section_offset, data },
+            entry_points,
+            static_base: self.static_base,
+        })
+    }
+
+    /// The same stub with the data emitted as a blob of its own (`split`) or appended to the code.
+    pub fn repackaged(&self, split: bool) -> Result<ArmFlashStub, ArmError> {
+        let parts = self.decompose()?;
+        let mut stub = self.clone();
+        if split {
+            stub.instructions = base64::encode(&parts.code.data);
+            stub.data_instructions = Some(base64::encode(&parts.data.data));
+        } else {
+            stub.instructions = base64::encode(parts.image());
+            stub.data_instructions = None;
+        }
+        if stub.crc32.is_some() {
+            stub.crc32 = Some(stub.compute_crc32()?);
+        }
+
+        Ok(stub)
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::BTreeMap;
+
+use crate::prog::flash_algorithm::FlashAlgorithm;
+
+use super::{
+    arm_error::ArmError,
+    flash_stub_gen::ArmFlashStub,
+    thumb::{self, Decoded, Instruction, CONDITIONS},
+};
+
+/// Upper bound on instructions listed per routine, guards against garbage input.
+const MAX_INSTRUCTIONS: usize = 0x4000;
+
+const REGISTERS: [&str; 16] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr", "pc",
+];
+
+const SHIFTS: [&str; 4] = ["lsl", "lsr", "asr", "ror"];
+
+/// One instruction of a disassembled routine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    /// Offset from the start of the blob.
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+fn reg(register: u32) -> &'static str {
+    REGISTERS[(register & 0xF) as usize]
+}
+
+fn register_list(registers: u32) -> String {
+    let names: Vec<&str> = (0..16).filter(|bit| registers & (1 << bit) != 0).map(reg).collect();
+    format!("{{{}}}", names.join(", "))
+}
+
+fn condition(cond: u32) -> &'static str {
+    CONDITIONS.get(cond as usize).copied().unwrap_or("")
+}
+
+fn signed_imm(value: u32, add: bool) -> String {
+    if add {
+        format!("#{:#x}", value)
+    } else {
+        format!("#-{:#x}", value)
+    }
+}
+
+/// The word aligned PC that literal loads and `adr` at `address` are relative to.
+fn literal_base(address: u32) -> u32 {
+    address.wrapping_add(4) & !3
+}
+
+fn it_block(firstcond: u32, mask: u32) -> String {
+    let suffix: String = (mask.trailing_zeros() + 1..4)
+        .rev()
+        .map(|bit| if (mask >> bit) & 1 == firstcond & 1 { 't' } else { 'e' })
+        .collect();
+    format!("it{} {}", suffix, condition(firstcond))
+}
+
+/// Instructions in the IT block opened by the 16-bit `raw`, or `None` if it isn't an IT.
+fn it_length(raw: u16) -> Option<u32> {
+    match raw & 0xF {
+        mask if raw & 0xFF00 == 0xBF00 && mask != 0 => Some(4 - mask.trailing_zeros()),
+        _ => None,
+    }
+}
+
+/// Conditions of the instructions in the IT block opened by `raw`, in order.
+fn it_conditions(raw: u16) -> Vec<u32> {
+    let raw = u32::from(raw);
+    let firstcond = (raw >> 4) & 0xF;
+    let length = it_length(raw as u16).unwrap_or(0);
+    (0..length)
+        .map(|slot| match slot {
+            0 => firstcond,
+            slot => (firstcond & !1) | ((raw >> (4 - slot)) & 1),
+        })
+        .collect()
+}
+
+/// Puts the condition of an IT block slot after the mnemonic, `mov.w r0, r1` becomes `moveq.w r0, r1`.
+fn with_condition(text: &str, cond: u32) -> String {
+    let end = text.find(['.', ' ']).unwrap_or(text.len());
+    format!("{}{}{}", &text[..end], condition(cond), &text[end..])
+}
+
+/// Miscellaneous 16-bit instructions, `1011 xxxx` not already broken out by the decoder.
+fn misc(h: u32) -> Option<String> {
+    let low = |shift: u32| reg((h >> shift) & 7);
+    let text = match h & 0xFFC0 {
+        0xB200 => format!("sxth {}, {}", low(0), low(3)),
+        0xB240 => format!("sxtb {}, {}", low(0), low(3)),
+        0xB280 => format!("uxth {}, {}", low(0), low(3)),
+        0xB2C0 => format!("uxtb {}, {}", low(0), low(3)),
+        0xBA00 => format!("rev {}, {}", low(0), low(3)),
+        0xBA40 => format!("rev16 {}, {}", low(0), low(3)),
+        0xBAC0 => format!("revsh {}, {}", low(0), low(3)),
+        _ if h & 0xFFE8 == 0xB660 => {
+            let flags: String = [(2, 'i'), (1, 'f')].iter().filter(|(bit, _)| h & bit != 0).map(|(_, flag)| *flag).collect();
+            format!("{} {}", if h & 0x10 == 0 { "cpsie" } else { "cpsid" }, flags)
+        }
+        _ if h & 0xFF0F == 0xBF00 => ["nop", "yield", "wfe", "wfi", "sev"].get(((h >> 4) & 0xF) as usize)?.to_string(),
+        _ if h & 0xFF00 == 0xBF00 => it_block((h >> 4) & 0xF, h & 0xF),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// 16-bit Thumb instructions, all of ARMv6-M plus the ARMv7-M additions.
+fn narrow(hw: u16, address: u32) -> Option<String> {
+    let h = u32::from(hw);
+    let low = |shift: u32| reg((h >> shift) & 7);
+    let imm5 = (h >> 6) & 0x1F;
+
+    let text = match h >> 11 {
+        0b00000 if imm5 == 0 => format!("movs {}, {}", low(0), low(3)),
+        0b00000 => format!("lsls {}, {}, #{}", low(0), low(3), imm5),
+        0b00001 | 0b00010 => {
+            let op = if h >> 11 == 1 { "lsrs" } else { "asrs" };
+            format!("{} {}, {}, #{}", op, low(0), low(3), if imm5 == 0 { 32 } else { imm5 })
+        }
+        0b00011 => {
+            let op = if h & 0x200 == 0 { "adds" } else { "subs" };
+            match h & 0x400 {
+                0 => format!("{} {}, {}, {}", op, low(0), low(3), low(6)),
+                _ => format!("{} {}, {}, #{}", op, low(0), low(3), (h >> 6) & 7),
+            }
+        }
+        0b00100..=0b00111 => {
+            let op = ["movs", "cmp", "adds", "subs"][((h >> 11) & 3) as usize];
+            format!("{} {}, #{}", op, low(8), h & 0xFF)
+        }
+        0b01000 if h & 0x400 == 0 => {
+            let ops = [
+                "ands", "eors", "lsls", "lsrs", "asrs", "adcs", "sbcs", "rors", "tst", "rsbs", "cmp", "cmn", "orrs", "muls",
+                "bics", "mvns",
+            ];
+            match (h >> 6) & 0xF {
+                9 => format!("rsbs {}, {}, #0", low(0), low(3)),
+                13 => format!("muls {}, {}, {}", low(0), low(3), low(0)),
+                op => format!("{} {}, {}", ops[op as usize], low(0), low(3)),
+            }
+        }
+        0b01000 => {
+            let rdn = (h >> 4) & 8 | h & 7;
+            let rm = (h >> 3) & 0xF;
+            match (h >> 8) & 3 {
+                0 => format!("add {}, {}", reg(rdn), reg(rm)),
+                1 => format!("cmp {}, {}", reg(rdn), reg(rm)),
+                2 if rdn == 8 && rm == 8 => "nop".to_string(),
+                2 => format!("mov {}, {}", reg(rdn), reg(rm)),
+                _ => return None,
+            }
+        }
+        0b01001 => {
+            let offset = (h & 0xFF) << 2;
+            format!("ldr {}, [pc, #{:#x}] ; {:#x}", low(8), offset, literal_base(address) + offset)
+        }
+        0b01010 | 0b01011 => {
+            let op = ["str", "strh", "strb", "ldrsb", "ldr", "ldrh", "ldrb", "ldrsh"][((h >> 9) & 7) as usize];
+            format!("{} {}, [{}, {}]", op, low(0), low(3), low(6))
+        }
+        0b01100..=0b01111 => {
+            let (op, scale) = match (h >> 11) & 3 {
+                0 => ("str", 4),
+                1 => ("ldr", 4),
+                2 => ("strb", 1),
+                _ => ("ldrb", 1),
+            };
+            format!("{} {}, [{}, #{:#x}]", op, low(0), low(3), imm5 * scale)
+        }
+        0b10000 | 0b10001 => {
+            let op = if h & 0x800 == 0 { "strh" } else { "ldrh" };
+            format!("{} {}, [{}, #{:#x}]", op, low(0), low(3), imm5 * 2)
+        }
+        0b10010 | 0b10011 => {
+            let op = if h & 0x800 == 0 { "str" } else { "ldr" };
+            format!("{} {}, [sp, #{:#x}]", op, low(8), (h & 0xFF) << 2)
+        }
+        0b10100 => format!("adr {}, {:#x}", low(8), literal_base(address) + ((h & 0xFF) << 2)),
+        0b10101 => format!("add {}, sp, #{:#x}", low(8), (h & 0xFF) << 2),
+        0b10110 | 0b10111 => return misc(h),
+        0b11000 => format!("stm {}!, {}", low(8), register_list(h & 0xFF)),
+        0b11001 => {
+            let rn = (h >> 8) & 7;
+            let writeback = if h & (1 << rn) == 0 { "!" } else { "" };
+            format!("ldm {}{}, {}", reg(rn), writeback, register_list(h & 0xFF))
+        }
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Names of the data processing opcodes shared by the immediate and register forms.
+fn data_processing(op: u32) -> Option<&'static str> {
+    Some(match op {
+        0 => "and",
+        1 => "bic",
+        2 => "orr",
+        3 => "orn",
+        4 => "eor",
+        8 => "add",
+        10 => "adc",
+        11 => "sbc",
+        13 => "sub",
+        14 => "rsb",
+        _ => return None,
+    })
+}
+
+/// The compare forms of `and`, `eor`, `add` and `sub`, used when the result is discarded.
+fn compare(op: u32) -> Option<&'static str> {
+    Some(match op {
+        0 => "tst",
+        4 => "teq",
+        8 => "cmn",
+        13 => "cmp",
+        _ => return None,
+    })
+}
+
+fn special_register(sysm: u32) -> String {
+    match sysm {
+        0 => "apsr".to_string(),
+        8 => "msp".to_string(),
+        9 => "psp".to_string(),
+        16 => "primask".to_string(),
+        17 => "basepri".to_string(),
+        18 => "basepri_max".to_string(),
+        19 => "faultmask".to_string(),
+        20 => "control".to_string(),
+        sysm => format!("sysm{}", sysm),
+    }
+}
+
+/// Data processing with an immediate, `11110x0` (modified) and `11110x1` (plain binary).
+fn wide_immediate(h1: u32, h2: u32, address: u32) -> Option<String> {
+    let (rn, rd) = (h1 & 0xF, (h2 >> 8) & 0xF);
+    let s = if h1 & 0x10 != 0 { "s" } else { "" };
+    let imm12 = ((h1 >> 10) & 1) << 11 | ((h2 >> 12) & 7) << 8 | h2 & 0xFF;
+
+    if h1 & 0x200 == 0 {
+        let op = (h1 >> 5) & 0xF;
+        let imm = thumb::thumb_expand_imm(imm12);
+        return Some(match op {
+            _ if rd == 15 && !s.is_empty() => format!("{} {}, #{:#x}", compare(op)?, reg(rn), imm),
+            2 if rn == 15 => format!("mov{} {}, #{:#x}", s, reg(rd), imm),
+            3 if rn == 15 => format!("mvn{} {}, #{:#x}", s, reg(rd), imm),
+            _ => format!("{}{} {}, {}, #{:#x}", data_processing(op)?, s, reg(rd), reg(rn), imm),
+        });
+    }
+
+    let lsb = ((h2 >> 12) & 7) << 2 | (h2 >> 6) & 3;
+    Some(match h1 & 0xFBF0 {
+        0xF200 if rn == 15 => format!("adr {}, {:#x}", reg(rd), literal_base(address).wrapping_add(imm12)),
+        0xF200 => format!("addw {}, {}, #{:#x}", reg(rd), reg(rn), imm12),
+        0xF2A0 if rn == 15 => format!("adr {}, {:#x}", reg(rd), literal_base(address).wrapping_sub(imm12)),
+        0xF2A0 => format!("subw {}, {}, #{:#x}", reg(rd), reg(rn), imm12),
+        0xF240 => format!("movw {}, #{:#x}", reg(rd), (h1 & 0xF) << 12 | imm12),
+        0xF2C0 => format!("movt {}, #{:#x}", reg(rd), (h1 & 0xF) << 12 | imm12),
+        0xF340 => format!("sbfx {}, {}, #{}, #{}", reg(rd), reg(rn), lsb, (h2 & 0x1F) + 1),
+        0xF3C0 => format!("ubfx {}, {}, #{}, #{}", reg(rd), reg(rn), lsb, (h2 & 0x1F) + 1),
+        0xF360 => {
+            let width = ((h2 & 0x1F) + 1).saturating_sub(lsb);
+            match rn {
+                15 => format!("bfc {}, #{}, #{}", reg(rd), lsb, width),
+                _ => format!("bfi {}, {}, #{}, #{}", reg(rd), reg(rn), lsb, width),
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Data processing with a shifted register, `1110101`.
+fn wide_register(h1: u32, h2: u32) -> Option<String> {
+    let (rn, rd, rm) = (h1 & 0xF, (h2 >> 8) & 0xF, h2 & 0xF);
+    let s = if h1 & 0x10 != 0 { "s" } else { "" };
+    let op = (h1 >> 5) & 0xF;
+    let kind = ((h2 >> 4) & 3) as usize;
+    let imm = ((h2 >> 12) & 7) << 2 | (h2 >> 6) & 3;
+    let shift = match (kind, imm) {
+        (0, 0) => String::new(),
+        (3, 0) => ", rrx".to_string(),
+        (_, 0) => format!(", {} #32", SHIFTS[kind]),
+        _ => format!(", {} #{}", SHIFTS[kind], imm),
+    };
+
+    Some(match op {
+        _ if rd == 15 && !s.is_empty() => format!("{} {}, {}{}", compare(op)?, reg(rn), reg(rm), shift),
+        2 if rn == 15 && shift.is_empty() => format!("mov{} {}, {}", s, reg(rd), reg(rm)),
+        2 if rn == 15 && kind == 3 && imm == 0 => format!("rrx{} {}, {}", s, reg(rd), reg(rm)),
+        2 if rn == 15 => format!("{}{} {}, {}, #{}", SHIFTS[kind], s, reg(rd), reg(rm), if imm == 0 { 32 } else { imm }),
+        3 if rn == 15 => format!("mvn{} {}, {}{}", s, reg(rd), reg(rm), shift),
+        _ => format!("{}{} {}, {}, {}{}", data_processing(op)?, s, reg(rd), reg(rn), reg(rm), shift),
+    })
+}
+
+/// Single loads and stores, `1111100`.
+fn wide_load_store(h1: u32, h2: u32, address: u32) -> Option<String> {
+    let (rn, rt) = (h1 & 0xF, h2 >> 12);
+    let size = (h1 >> 5) & 3;
+    let load = h1 & 0x10 != 0;
+    let signed = h1 & 0x100 != 0;
+    let op = match (load, signed, size) {
+        (false, false, 0) => "strb",
+        (false, false, 1) => "strh",
+        (false, false, 2) => "str",
+        (true, false, 0) => "ldrb",
+        (true, false, 1) => "ldrh",
+        (true, false, 2) => "ldr",
+        (true, true, 0) => "ldrsb",
+        (true, true, 1) => "ldrsh",
+        _ => return None,
+    };
+    // Byte and halfword loads into pc are the preload hints.
+    if load && rt == 15 && size != 2 {
+        return None;
+    }
+
+    if rn == 15 {
+        let (offset, up) = (h2 & 0xFFF, h1 & 0x80 != 0);
+        let target = if up { literal_base(address) + offset } else { literal_base(address).wrapping_sub(offset) };
+        return Some(format!("{} {}, [pc, {}] ; {:#x}", op, reg(rt), signed_imm(offset, up), target));
+    }
+    if h1 & 0x80 != 0 {
+        return Some(format!("{} {}, [{}, #{:#x}]", op, reg(rt), reg(rn), h2 & 0xFFF));
+    }
+    if h2 & 0x800 != 0 {
+        let imm = h2 & 0xFF;
+        let (index, up, writeback) = (h2 & 0x400 != 0, h2 & 0x200 != 0, h2 & 0x100 != 0);
+        return Some(match (index, writeback) {
+            (true, false) if up => format!("{}t {}, [{}, #{:#x}]", op, reg(rt), reg(rn), imm),
+            (true, false) => format!("{} {}, [{}, {}]", op, reg(rt), reg(rn), signed_imm(imm, false)),
+            (true, true) => format!("{} {}, [{}, {}]!", op, reg(rt), reg(rn), signed_imm(imm, up)),
+            (false, true) => format!("{} {}, [{}], {}", op, reg(rt), reg(rn), signed_imm(imm, up)),
+            (false, false) => return None,
+        });
+    }
+    if h2 & 0xFC0 == 0 {
+        let rm = h2 & 0xF;
+        return Some(match (h2 >> 4) & 3 {
+            0 => format!("{} {}, [{}, {}]", op, reg(rt), reg(rn), reg(rm)),
+            shift => format!("{} {}, [{}, {}, lsl #{}]", op, reg(rt), reg(rn), reg(rm), shift),
+        });
+    }
+
+    None
+}
+
+/// Multiple and dual loads and stores and table branches, `1110100`.
+fn wide_multiple(h1: u32, h2: u32) -> Option<String> {
+    let rn = h1 & 0xF;
+    let writeback = if h1 & 0x20 != 0 { "!" } else { "" };
+
+    if h1 & 0xFFF0 == 0xE8D0 && h2 & 0xFFE0 == 0xF000 {
+        return Some(match h2 & 0x10 {
+            0 => format!("tbb [{}, {}]", reg(rn), reg(h2 & 0xF)),
+            _ => format!("tbh [{}, {}, lsl #1]", reg(rn), reg(h2 & 0xF)),
+        });
+    }
+
+    let op = match h1 & 0xFFD0 {
+        0xE880 => Some("stm"),
+        0xE890 => Some("ldm"),
+        0xE900 => Some("stmdb"),
+        0xE910 => Some("ldmdb"),
+        _ => None,
+    };
+    if let Some(op) = op {
+        return Some(format!("{}.w {}{}, {}", op, reg(rn), writeback, register_list(h2)));
+    }
+
+    let (index, up) = (h1 & 0x100 != 0, h1 & 0x80 != 0);
+    if h1 & 0xFE40 == 0xE840 && (index || !writeback.is_empty()) {
+        let op = if h1 & 0x10 != 0 { "ldrd" } else { "strd" };
+        let (rt, rt2) = (h2 >> 12, (h2 >> 8) & 0xF);
+        let imm = signed_imm((h2 & 0xFF) << 2, up);
+        return Some(match index {
+            true => format!("{} {}, {}, [{}, {}]{}", op, reg(rt), reg(rt2), reg(rn), imm, writeback),
+            false => format!("{} {}, {}, [{}], {}", op, reg(rt), reg(rt2), reg(rn), imm),
+        });
+    }
+
+    None
+}
+
+/// Multiplies, divides, extends and the other `11111` data processing instructions.
+fn wide_arithmetic(h1: u32, h2: u32) -> Option<String> {
+    let (rn, rd, rm, ra) = (h1 & 0xF, (h2 >> 8) & 0xF, h2 & 0xF, h2 >> 12);
+    let s = if h1 & 0x10 != 0 { "s" } else { "" };
+
+    if h1 & 0xFF8F == 0xFA0F && h2 & 0xF0C0 == 0xF080 {
+        let op = match (h1 >> 4) & 7 {
+            0 => "sxth",
+            1 => "uxth",
+            4 => "sxtb",
+            5 => "uxtb",
+            _ => return None,
+        };
+        return Some(match (h2 >> 4) & 3 {
+            0 => format!("{}.w {}, {}", op, reg(rd), reg(rm)),
+            rotation => format!("{}.w {}, {}, ror #{}", op, reg(rd), reg(rm), rotation * 8),
+        });
+    }
+    if h1 & 0xFF80 == 0xFA00 && h2 & 0xF0F0 == 0xF000 {
+        let kind = ((h1 >> 5) & 3) as usize;
+        return Some(format!("{}{}.w {}, {}, {}", SHIFTS[kind], s, reg(rd), reg(rn), reg(rm)));
+    }
+    if h1 & 0xFFF0 == 0xFAB0 && h2 & 0xF0F0 == 0xF080 {
+        return Some(format!("clz {}, {}", reg(rd), reg(rm)));
+    }
+
+    Some(match (h1 & 0xFFF0, h2 & 0xF0) {
+        (0xFB00, 0x00) if ra == 15 => format!("mul {}, {}, {}", reg(rd), reg(rn), reg(rm)),
+        (0xFB00, 0x00) => format!("mla {}, {}, {}, {}", reg(rd), reg(rn), reg(rm), reg(ra)),
+        (0xFB00, 0x10) => format!("mls {}, {}, {}, {}", reg(rd), reg(rn), reg(rm), reg(ra)),
+        (0xFB90, 0xF0) => format!("sdiv {}, {}, {}", reg(rd), reg(rn), reg(rm)),
+        (0xFBB0, 0xF0) => format!("udiv {}, {}, {}", reg(rd), reg(rn), reg(rm)),
+        (0xFB80, 0x00) => format!("smull {}, {}, {}, {}", reg(ra), reg(rd), reg(rn), reg(rm)),
+        (0xFBA0, 0x00) => format!("umull {}, {}, {}, {}", reg(ra), reg(rd), reg(rn), reg(rm)),
+        (0xFBC0, 0x00) => format!("smlal {}, {}, {}, {}", reg(ra), reg(rd), reg(rn), reg(rm)),
+        (0xFBE0, 0x00) => format!("umlal {}, {}, {}, {}", reg(ra), reg(rd), reg(rn), reg(rm)),
+        _ => return None,
+    })
+}
+
+/// The common 32-bit Thumb-2 instructions of compiled flash algorithms, FPU and DSP excluded.
+fn wide(raw: u32, address: u32) -> Option<String> {
+    let (h1, h2) = (raw >> 16, raw & 0xFFFF);
+
+    match h1 >> 11 {
+        0b11110 if h2 & 0x8000 == 0 => wide_immediate(h1, h2, address),
+        0b11110 => match h1 {
+            0xF3BF if h2 & 0xFF00 == 0x8F00 => {
+                let op = match (h2 >> 4) & 0xF {
+                    4 => "dsb",
+                    5 => "dmb",
+                    6 => "isb",
+                    _ => return None,
+                };
+                Some(match h2 & 0xF {
+                    0xF => format!("{} sy", op),
+                    option => format!("{} #{}", op, option),
+                })
+            }
+            0xF3AF if h2 & 0xFF00 == 0x8000 => {
+                ["nop.w", "yield.w", "wfe.w", "wfi.w", "sev.w"].get((h2 & 0xFF) as usize).map(|hint| hint.to_string())
+            }
+            0xF3EF if h2 & 0xF000 == 0x8000 => Some(format!("mrs {}, {}", reg((h2 >> 8) & 0xF), special_register(h2 & 0xFF))),
+            _ if h1 & 0xFFF0 == 0xF380 && h2 & 0xFF00 == 0x8800 => {
+                Some(format!("msr {}, {}", special_register(h2 & 0xFF), reg(h1 & 0xF)))
+            }
+            _ => None,
+        },
+        0b11111 if h1 & 0xFE00 == 0xF800 => wide_load_store(h1, h2, address),
+        0b11111 => wide_arithmetic(h1, h2),
+        0b11101 if h1 & 0xFE00 == 0xEA00 => wide_register(h1, h2),
+        0b11101 if h1 & 0xFE00 == 0xE800 => wide_multiple(h1, h2),
+        _ => None,
+    }
+}
+
+/// The assembly text of a decoded instruction, covering the Thumb instructions compilers emit
+/// for flash algorithms.
+///
+/// Encodings it doesn't know, such as FPU and DSP instructions, are shown as `.short` or
+/// `.word` with the raw value.
+pub fn render(decoded: &Decoded) -> String {
+    let text = match decoded.instruction {
+        Instruction::Other16 { raw } => narrow(raw, decoded.address),
+        Instruction::Other32 { raw } => wide(raw, decoded.address),
+        _ => None,
+    };
+    text.unwrap_or_else(|| decoded.instruction.to_string())
+}
+
+/// Walks the routine at `entry` (an offset into `code`, Thumb bit ignored) along its branches and
+/// returns its instructions in address order.
+///
+/// Calls aren't followed, the callees are routines of their own. The walk stops at returns,
+/// indirect branches and table branches, whose targets can't be resolved statically.
+pub fn routine(code: &[u8], entry: u32) -> Vec<Decoded> {
+    let mut found = BTreeMap::new();
+    // Offset and the number of instructions left in the current IT block.
+    let mut pending = vec![(entry & !1, 0u32)];
+
+    while let Some((offset, it_left)) = pending.pop() {
+        if found.contains_key(&offset) || found.len() >= MAX_INSTRUCTIONS {
+            continue;
+        }
+
+        let decoded = match thumb::decode(code, 0, offset) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        found.insert(offset, decoded);
+
+        // Returns and branches inside an IT block may not be taken.
+        let conditional = it_left > 0;
+        let mut next_it = it_left.saturating_sub(1);
+        match decoded.instruction {
+            Instruction::Other16 { raw } => next_it = it_length(raw).unwrap_or(next_it),
+            Instruction::Other32 { raw } if raw & 0xFFF0_FFE0 == 0xE8D0_F000 => continue,
+            Instruction::Pop { registers } if registers & (1 << 15) != 0 && !conditional => continue,
+            Instruction::BranchExchange { .. } | Instruction::Undefined { .. } if !conditional => continue,
+            Instruction::Branch { target, cond } => {
+                pending.push((target, 0));
+                if cond.is_none() && !conditional {
+                    continue;
+                }
+            }
+            Instruction::CompareBranch { target, .. } => pending.push((target, 0)),
+            _ => {}
+        }
+        pending.push((offset + decoded.size, next_it));
+    }
+
+    found.into_values().collect()
+}
+
+impl ArmFlashStub {
+    /// Disassembles the entry point with the CMSIS name `function`, e.g. "ProgramPage", ignoring case.
+    ///
+    /// Offsets are relative to the start of the blob. Calls to other entry points are annotated
+    /// with their names.
+    pub fn disassemble(&self, function: &str) -> Result<Vec<DisassembledInstruction>, ArmError> {
+        let entries: Vec<(&'static str, u64)> = self.entry_points().iter().collect();
+        let entry = entries.iter().find(|(name, _)| name.eq_ignore_ascii_case(function)).ok_or_else(|| {
+            ArmError::UnknownEntryPoint {
+                name: function.to_string(),
+                available: entries.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", "),
+            }
+        })?;
+
+        let code = self.blob()?;
+        let mut it_block: Vec<u32> = Vec::new();
+        let lines = routine(&code, entry.1 as u32)
+            .iter()
+            .map(|decoded| {
+                let mut text = render(decoded);
+                if !it_block.is_empty() {
+                    text = with_condition(&text, it_block.remove(0));
+                }
+                if let Instruction::Other16 { raw } = decoded.instruction {
+                    if it_length(raw).is_some() {
+                        it_block = it_conditions(raw);
+                    }
+                }
+                if let Instruction::BranchLink { target } = decoded.instruction {
+                    if let Some((name, _)) = entries.iter().find(|(_, offset)| *offset == u64::from(target)) {
+                        text.push_str(&format!(" ; {}", name));
+                    }
+                }
+
+                let start = decoded.address as usize;
+                DisassembledInstruction {
+                    offset: decoded.address,
+                    bytes: code[start..start + decoded.size as usize].to_vec(),
+                    text,
+                }
+            })
+            .collect();
+
+        Ok(lines)
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use unicorn_engine::{
+    unicorn_const::{Arch, HookType, MemType, Mode, Prot},
+    RegisterARM, Unicorn,
+};
+
+use crate::{
+    prog::flash_algorithm::FlashAlgorithm,
+    progress::{NoProgress, Progress, ProgressSink},
+};
+
+use super::{
+    arm_error::ArmError,
+    flash_stub_gen::{ArmFlashStub, INIT_FUNCTION_ERASE, INIT_FUNCTION_PROGRAM},
+    ram_layout::{RunLayout, TRAMPOLINE},
+    thumb,
+};
+
+/// Cortex-M peripheral window, backed by zero-filled memory so status polls see "idle, no error".
+const PERIPHERAL_BASE: u32 = 0x4000_0000;
+const PERIPHERAL_SIZE: u32 = 0x2000_0000;
+/// Cortex-M system control space.
+const SYSTEM_BASE: u32 = 0xE000_0000;
+const SYSTEM_SIZE: u32 = 0x1000_0000;
+/// Unicorn maps memory in 4K pages.
+const MAP_ALIGN: u32 = 0x1000;
+/// Most fake flash mapped, from the flash start. Only the first sector and page are touched.
+const FLASH_WINDOW_LIMIT: u32 = 0x100_0000;
+
+/// Memory map and limits for a dry run.
+#[derive(Debug, Clone)]
+pub struct EmulatorConfig {
+    /// Where the stub is loaded, like the RAM base of the real target.
+    pub ram_base: u32,
+    /// Data written by ProgramPage, repeated or truncated to the page size.
+    pub page_data: Vec<u8>,
+    /// Maximum instructions per routine before it is considered hung.
+    pub instruction_limit: usize,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            ram_base: 0x2000_0000,
+            page_data: vec![0xA5, 0x5A, 0x12, 0x34],
+            instruction_limit: 10_000_000,
+        }
+    }
+}
+
+/// Why an emulated routine did not return normally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulationFault {
+    /// Access to unmapped or protected memory.
+    InvalidMemory { pc: u32, address: u32, write: bool },
+    /// The core hit an instruction it can't execute.
+    UndefinedInstruction { pc: u32 },
+    /// An exception such as SVC or BKPT was raised inside the algorithm.
+    Exception { pc: u32, number: u32 },
+    /// The routine didn't return within the instruction limit.
+    Timeout { pc: u32 },
+    /// Unicorn itself reported an error.
+    Engine(String),
+}
+
+impl fmt::Display for EmulationFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulationFault::InvalidMemory { pc, address, write } => {
+                let access = if *write { "write to" } else { "read from" };
+                write!(f, "invalid {} {:#010x} at pc {:#010x}", access, address, pc)
+            }
+            EmulationFault::UndefinedInstruction { pc } => write!(f, "undefined instruction at pc {:#010x}", pc),
+            EmulationFault::Exception { pc, number } => write!(f, "exception {} at pc {:#010x}", number, pc),
+            EmulationFault::Timeout { pc } => write!(f, "instruction limit reached at pc {:#010x}", pc),
+            EmulationFault::Engine(reason) => write!(f, "emulator error, {}", reason),
+        }
+    }
+}
+
+/// Outcome of one emulated call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutineResult {
+    pub name: &'static str,
+    /// Value of r0 on return, 0 means success for CMSIS algorithms.
+    pub return_value: Option<u32>,
+    pub fault: Option<EmulationFault>,
+    pub instructions: u64,
+    /// Estimated Cortex-M3/M4 cycles, see [`thumb::Instruction::cycles`].
+    pub cycles: u64,
+}
+
+/// Outcome of a full dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmulationReport {
+    pub routines: Vec<RoutineResult>,
+    /// Whether the first sector read back as erased after EraseSector, `None` if erasing faulted.
+    pub erased_blank: Option<bool>,
+    /// Whether the fake flash held the page data afterwards, `None` if programming never ran.
+    pub flash_matches: Option<bool>,
+}
+
+impl EmulationReport {
+    /// True if every routine returned 0 without faulting and the flash contents checked out.
+    pub fn passed(&self) -> bool {
+        self.routines.iter().all(|routine| routine.fault.is_none() && routine.return_value == Some(0))
+            && self.erased_blank != Some(false)
+            && self.flash_matches != Some(false)
+    }
+}
+
+fn align_up(value: u64) -> u64 {
+    let align = u64::from(MAP_ALIGN);
+    (value + align - 1) & !(align - 1)
+}
+
+fn engine_error(err: unicorn_engine::unicorn_const::uc_error) -> ArmError {
+    ArmError::Emulator(err.to_string())
+}
+
+/// Loads the stub into an emulated Cortex-M and runs Init, EraseSector, ProgramPage and UnInit
+/// against the first sector of a fake flash.
+pub fn dry_run(stub: &ArmFlashStub, config: &EmulatorConfig) -> Result<EmulationReport, ArmError> {
+    dry_run_with_progress(stub, config, &mut NoProgress)
+}
+
+/// Same as `dry_run`, reporting each routine to `progress` before it runs.
+pub fn dry_run_with_progress(
+    stub: &ArmFlashStub,
+    config: &EmulatorConfig,
+    progress: &mut impl ProgressSink,
+) -> Result<EmulationReport, ArmError> {
+    let blob = stub.blob()?;
+
+    // The layout a debugger uses, [blob][stack][page buffer][return trampoline].
+    let layout = RunLayout::plan(stub, config.ram_base, blob.len() as u32, u32::MAX)?;
+    let blob_end = layout.base + blob.len() as u32;
+    let ram_size = align_up(layout.size().into());
+
+    let mut emu = Unicorn::new(Arch::ARM, Mode::LITTLE_ENDIAN | Mode::THUMB | Mode::MCLASS).map_err(engine_error)?;
+    emu.mem_map(config.ram_base.into(), ram_size, Prot::ALL).map_err(engine_error)?;
+    emu.mem_map(PERIPHERAL_BASE.into(), PERIPHERAL_SIZE.into(), Prot::READ | Prot::WRITE).map_err(engine_error)?;
+    emu.mem_map(SYSTEM_BASE.into(), SYSTEM_SIZE.into(), Prot::READ | Prot::WRITE).map_err(engine_error)?;
+
+    // Only the first sector and page of the flash are mapped, filled with the opposite of the
+    // erased value so that an EraseSector doing nothing doesn't read back blank.
+    let sector_size = stub.sectors.first().map_or(stub.flash_sector_size, |region| region.size);
+    let touched = sector_size.max(stub.flash_page_size).min(FLASH_WINDOW_LIMIT);
+    let window_end = (u64::from(stub.flash_start_addr) + u64::from(touched)).min(stub.flash_end_addr.into());
+    let window = window_end.checked_sub(stub.flash_start_addr.into()).filter(|&window| window > 0);
+    let window = window.ok_or(ArmError::AddressOutOfRange { address: stub.flash_start_addr, size: touched })? as usize;
+    let flash_base = stub.flash_start_addr & !(MAP_ALIGN - 1);
+    let flash_size = align_up(window_end - u64::from(flash_base));
+    emu.mem_map(flash_base.into(), flash_size, Prot::READ | Prot::WRITE).map_err(engine_error)?;
+    emu.mem_write(flash_base.into(), &vec![!stub.erased_byte_value; flash_size as usize]).map_err(engine_error)?;
+
+    let page: Vec<u8> = config.page_data.iter().cycle().take(stub.flash_page_size as usize).copied().collect();
+    emu.mem_write(config.ram_base.into(), &blob).map_err(engine_error)?;
+    emu.mem_write(layout.buffer.into(), &page).map_err(engine_error)?;
+    // Never executed, emulation stops when PC reaches it.
+    emu.mem_write(layout.trampoline.into(), &TRAMPOLINE).map_err(engine_error)?;
+
+    let fault: Rc<RefCell<Option<EmulationFault>>> = Rc::new(RefCell::new(None));
+    let executed = Rc::new(Cell::new(0u64));
+    let cycles = Rc::new(Cell::new(0u64));
+
+    let memory_fault = fault.clone();
+    emu.add_mem_hook(HookType::MEM_INVALID, 1, 0, move |uc, kind, address, _size, _value| {
+        let pc = uc.reg_read(RegisterARM::PC).unwrap_or(0) as u32;
+        let write = matches!(kind, MemType::WRITE_UNMAPPED | MemType::WRITE_PROT);
+        memory_fault.borrow_mut().get_or_insert(EmulationFault::InvalidMemory { pc, address: address as u32, write });
+        false
+    })
+    .map_err(engine_error)?;
+
+    let instruction_fault = fault.clone();
+    emu.add_insn_invalid_hook(move |uc| {
+        let pc = uc.reg_read(RegisterARM::PC).unwrap_or(0) as u32;
+        instruction_fault.borrow_mut().get_or_insert(EmulationFault::UndefinedInstruction { pc });
+        false
+    })
+    .map_err(engine_error)?;
+
+    let exception_fault = fault.clone();
+    emu.add_intr_hook(move |uc, number| {
+        let pc = uc.reg_read(RegisterARM::PC).unwrap_or(0) as u32;
+        exception_fault.borrow_mut().get_or_insert(EmulationFault::Exception { pc, number });
+        let _ = uc.emu_stop();
+    })
+    .map_err(engine_error)?;
+
+    let counter = executed.clone();
+    let cycle_counter = cycles.clone();
+    let code = blob.clone();
+    let ram_base = config.ram_base;
+    emu.add_code_hook(config.ram_base.into(), blob_end.into(), move |_, address, _| {
+        counter.set(counter.get() + 1);
+        let decoded = thumb::decode(&code, ram_base, address as u32 - ram_base);
+        cycle_counter.set(cycle_counter.get() + u64::from(decoded.map_or(1, |decoded| decoded.instruction.cycles())));
+    })
+    .map_err(engine_error)?;
+
+    let address = stub.flash_start_addr;
+    let init = &stub.init_parameters;
+    let calls: [(&'static str, Option<u32>, [u32; 3]); 6] = [
+        ("Init", stub.pc_init, [init.address, init.clock, INIT_FUNCTION_ERASE]),
+        ("EraseSector", Some(stub.pc_erase_sector), [stub.program_address(address), 0, 0]),
+        ("UnInit", stub.pc_uninit, [INIT_FUNCTION_ERASE, 0, 0]),
+        ("Init", stub.pc_init, [init.address, init.clock, INIT_FUNCTION_PROGRAM]),
+        ("ProgramPage", Some(stub.pc_program_page), [stub.program_address(address), stub.flash_page_size, layout.buffer]),
+        ("UnInit", stub.pc_uninit, [INIT_FUNCTION_PROGRAM, 0, 0]),
+    ];
+
+    let mut report = EmulationReport { routines: Vec::new(), erased_blank: None, flash_matches: None };
+    let total = calls.iter().filter(|(_, pc, _)| pc.is_some()).count();
+    for (name, pc, args) in calls.iter() {
+        let pc = match pc {
+            Some(pc) => *pc,
+            None => continue,
+        };
+        progress.progress(Progress { completed: report.routines.len(), total, current: name });
+
+        executed.set(0);
+        cycles.set(0);
+        *fault.borrow_mut() = None;
+
+        let registers = [RegisterARM::R0, RegisterARM::R1, RegisterARM::R2];
+        for (register, value) in registers.iter().zip(args.iter()) {
+            emu.reg_write(*register, (*value).into()).map_err(engine_error)?;
+        }
+        if let Some(static_base) = layout.static_base {
+            emu.reg_write(RegisterARM::R9, static_base.into()).map_err(engine_error)?;
+        }
+        emu.reg_write(RegisterARM::SP, layout.stack_top.into()).map_err(engine_error)?;
+        emu.reg_write(RegisterARM::LR, (layout.trampoline | 1).into()).map_err(engine_error)?;
+
+        let entry = layout.base.checked_add(pc).filter(|&entry| entry < blob_end).ok_or_else(|| ArmError::InvalidEntryPoint {
+            name: name.to_string(),
+            offset: pc,
+            reason: format!("past the end of the {} byte image", blob.len()),
+        })?;
+        let outcome = emu.emu_start((entry | 1).into(), layout.trampoline.into(), 0, config.instruction_limit);
+        let final_pc = emu.reg_read(RegisterARM::PC).map_err(engine_error)? as u32;
+
+        let mut routine_fault = fault.borrow_mut().take();
+        if routine_fault.is_none() {
+            routine_fault = match outcome {
+                Err(err) => Some(EmulationFault::Engine(err.to_string())),
+                Ok(()) if final_pc & !1 != layout.trampoline => Some(EmulationFault::Timeout { pc: final_pc }),
+                Ok(()) => None,
+            };
+        }
+
+        let return_value = match routine_fault {
+            None => Some(emu.reg_read(RegisterARM::R0).map_err(engine_error)? as u32),
+            Some(_) => None,
+        };
+
+        if routine_fault.is_none() {
+            match *name {
+                "EraseSector" => {
+                    let sector = emu.mem_read_as_vec(address.into(), (sector_size as usize).min(window)).map_err(engine_error)?;
+                    report.erased_blank = Some(stub.is_blank(&sector));
+                }
+                "ProgramPage" => {
+                    let written = emu.mem_read_as_vec(address.into(), page.len().min(window)).map_err(engine_error)?;
+                    report.flash_matches = Some(stub.verify(&page, &written).is_ok());
+                }
+                _ => {}
+            }
+        }
+
+        report.routines.push(RoutineResult {
+            name: *name,
+            return_value,
+            fault: routine_fault,
+            instructions: executed.get(),
+            cycles: cycles.get(),
+        });
+    }
+    progress.finish(report.routines.len());
+
+    Ok(report)
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Cipher an encrypted stub's blobs are sealed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EncryptionAlgorithm {
+    /// AES-256 in GCM mode, 96 bit nonce, 128 bit tag appended to the ciphertext.
+    Aes256Gcm,
+}
+
+/// How the blobs of an encrypted stub were sealed, see `crate::encryption`.
+///
+/// `instructions` and `data_instructions` then hold the ciphertext with its tag. The additional
+/// authenticated data is the key ID, the stub name, the blob's field and `crc32`, so a blob can't
+/// be passed off as sealed with another key or moved to another stub or field. `crc32` covers the
+/// decrypted stub including its layout, the programmer checks it after decrypting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StubEncryption {
+    pub algorithm: EncryptionAlgorithm,
+    /// Name of the per-device or per-fleet key, the programmer looks its copy up by it.
+    pub key_id: String,
+    /// Base64 of the nonce of `instructions`.
+    pub nonce: String,
+    /// Base64 of the nonce of `data_instructions`, when the data is a separate blob.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_nonce: Option<String>,
+}
@@ -0,0 +1,52 @@
+use crate::diagnostic::Diagnostic;
+
+use super::{
+    arm_error::ArmError,
+    thumb::{self, Instruction},
+};
+
+/// Sanity checks the first instruction of each named entry point.
+///
+/// `entries` holds offsets relative to the start of `code`, with the Thumb bit still set,
+/// as they end up in the stub. Suspicious but runnable entry points end up in `diagnostics`.
+pub(crate) fn check_entry_points(
+    code: &[u8],
+    entries: &[(&str, u32)],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), ArmError> {
+    for &(name, offset) in entries {
+        let invalid = |reason: &str| ArmError::InvalidEntryPoint {
+            name: name.to_string(),
+            offset,
+            reason: reason.to_string(),
+        };
+
+        // Cortex-M only executes Thumb code, so function symbols always carry bit 0.
+        if offset & 1 == 0 {
+            return Err(invalid("the Thumb bit is not set, the symbol may point to ARM code or data"));
+        }
+
+        let decoded = thumb::decode(code, 0, offset & !1)
+            .ok_or_else(|| invalid("it lies outside the code section"))?;
+
+        match decoded.instruction {
+            Instruction::Undefined { .. } => {
+                return Err(invalid("the first instruction is undefined, check the offset and byte order"));
+            }
+            Instruction::Other16 { raw: 0 } => {
+                return Err(invalid("the first instruction is zero-filled padding, check the offset"));
+            }
+            Instruction::Breakpoint { .. } => {
+                diagnostics.push(
+                    Diagnostic::warning("breakpoint-entry", format!("entry point {} starts with a breakpoint", name))
+                        .at(offset & !1),
+                );
+            }
+            _ => {}
+        }
+
+        tracing::debug!(name, offset, instruction = %decoded.instruction, "entry point");
+    }
+
+    Ok(())
+}
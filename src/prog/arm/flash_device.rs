@@ -1,29 +1,14 @@
-use scroll::Pread;
+use std::{fmt, ops::Range};
 
-use super::arm_error::ArmError;
+use soulcomposer_core::{
+    elf::read_segment_data,
+    flash_device::{sector_containing, sector_ranges, total_size_from_sectors, validate_sectors},
+};
 
-/// A struct to describe one sector in Flash.
-#[derive(Clone, Debug)]
-pub struct SectorInfo {
-    pub address: u32,
-    pub size: u32,
-}
+use super::{arm_error::ArmError, parse_options::ParseOptions};
 
-impl SectorInfo {
-    // This value signalizes the end of a sector.
-    const SECTOR_END: u32 = 0xFFFF_FFFF;
-
-    /// Creates a new `SectorInfo` struct for a chunk of ELF data.
-    fn new(data: &[u8]) -> Option<Self> {
-        let size = data.pread(0).unwrap();
-        let address = data.pread(4).unwrap();
-        if size != Self::SECTOR_END && address != Self::SECTOR_END {
-            Some(Self { address, size })
-        } else {
-            None
-        }
-    }
-}
+// Parsing itself lives in `soulcomposer-core`, which the firmware shares without std.
+pub use soulcomposer_core::SectorInfo;
 
 /// This struct describes the flash algorithm.
 /// It can be parsed from an ELF symbol.
@@ -46,7 +31,6 @@ pub struct FlashDevice {
     pub(crate) device_size: u32,
     /// The flash page size in bytes.
     pub(crate) page_size: u32,
-    _reserved: u32,
     /// The default erased value of one byte in flash.
     pub(crate) erased_default_value: u8,
     //  _pad: u24,
@@ -59,99 +43,161 @@ pub struct FlashDevice {
 }
 
 impl FlashDevice {
-    const INFO_SIZE: u32 = 160;
-    const SECTOR_INFO_SIZE: u32 = 8;
-    const MAX_ID_STRING_LENGTH: usize = 128;
-
-    /// Parses the `FlashDevice` struct from ELF binary data.
-    pub fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8], address: u32) -> Result<Self, ArmError> {
-        // Extract all the sector data from the ELF blob.
-        let sectors = Self::parse_sectors(elf, buffer, address);
-
-        // Get the rest of the data stored in the struct.
-        let data = match FlashDevice::read_elf_bin_data(elf, buffer, address, Self::INFO_SIZE) {
-            Some(data) => data,
-            None => return Err(ArmError::ReadBinaryInfoFail(format!("Read address: {:#010x}, size: {} bytes", address, Self::INFO_SIZE))),
-        };
+    /// Parses the `FlashDevice` struct from ELF binary data, within the limits of `options`.
+    pub fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8], address: u32, options: &ParseOptions) -> Result<Self, ArmError> {
+        Ok(soulcomposer_core::FlashDevice::at(elf, buffer, address, &options.limits())?.into())
+    }
 
-        // Get the string length of the name
-        let hypothetical_length = data[2..2 + Self::MAX_ID_STRING_LENGTH]
-            .iter()
-            .position(|&c| c == 0)
-            .unwrap_or(Self::MAX_ID_STRING_LENGTH);
-        let sanitized_length = Self::MAX_ID_STRING_LENGTH.min(hypothetical_length);
-
-        // Finally parse the struct data and return the struct.
-        Ok(Self {
-            driver_version: data.pread(0).unwrap(),
-            name: String::from_utf8_lossy(&data[2..2 + sanitized_length]).to_string(),
-            typ: data.pread(130).unwrap(),
-            start_address: data.pread(132).unwrap(),
-            device_size: data.pread(136).unwrap(),
-            page_size: data.pread(140).unwrap(),
-            _reserved: data.pread(144).unwrap(),
-            erased_default_value: data.pread(148).unwrap(),
-            program_page_timeout: data.pread(152).unwrap(),
-            erase_sector_timeout: data.pread(156).unwrap(),
-            sectors,
-        })
-    }
-
-    /// Parse the sector infos in the device struct.
-    pub(crate) fn parse_sectors(
+    /// Finds the `FlashDevice` symbol of an FLM and parses the struct behind it.
+    pub fn from_elf(elf: &goblin::elf::Elf<'_>, buffer: &[u8]) -> Result<Self, ArmError> {
+        Self::from_elf_with_options(elf, buffer, &ParseOptions::default())
+    }
+
+    /// Same as `from_elf`, with the descriptor symbol and limits of `options`.
+    pub fn from_elf_with_options(
         elf: &goblin::elf::Elf<'_>,
         buffer: &[u8],
-        address: u32,
-    ) -> Vec<SectorInfo> {
-        let mut sectors = vec![];
-        let mut offset = Self::INFO_SIZE;
-        // As long as we find new sectors, keep em comming.
-        while let Some(data) =
-        FlashDevice::read_elf_bin_data(elf, buffer, address + offset, Self::SECTOR_INFO_SIZE)
-        {
-            if let Some(sector) = SectorInfo::new(data) {
-                sectors.push(sector);
-                offset += 8;
-            } else {
-                break;
-            }
-        }
+        options: &ParseOptions,
+    ) -> Result<Self, ArmError> {
+        let symbol = options.descriptor_symbol.as_deref();
+        Ok(soulcomposer_core::FlashDevice::from_elf_with_limits(elf, buffer, symbol, &options.limits())?.into())
+    }
+
+    /// The flash algorithm version, `0x0101` for the current CMSIS layout.
+    pub fn driver_version(&self) -> u16 {
+        self.driver_version
+    }
 
-        sectors
+    /// The device name, as the vendor wrote it.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type of flash, 1 for on-chip and 2 for external flash.
+    pub fn device_type(&self) -> u16 {
+        self.typ
+    }
+
+    /// The flash start address.
+    pub fn start_address(&self) -> u32 {
+        self.start_address
+    }
+
+    /// The flash size in bytes.
+    pub fn device_size(&self) -> u32 {
+        self.device_size
+    }
+
+    /// The first address past the flash.
+    pub fn end_address(&self) -> u64 {
+        u64::from(self.start_address) + u64::from(self.device_size)
+    }
+
+    /// The flash page size in bytes, the most ProgramPage writes at once.
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// The value of one byte of erased flash.
+    pub fn erased_default_value(&self) -> u8 {
+        self.erased_default_value
+    }
+
+    /// ProgramPage timeout in milliseconds.
+    pub fn program_page_timeout(&self) -> u32 {
+        self.program_page_timeout
+    }
+
+    /// EraseSector timeout in milliseconds.
+    pub fn erase_sector_timeout(&self) -> u32 {
+        self.erase_sector_timeout
+    }
+
+    /// The sector table, one entry per region of equally sized sectors, addresses relative
+    /// to the flash start.
+    pub fn sectors(&self) -> &[SectorInfo] {
+        &self.sectors
+    }
+
+    /// Absolute address range of the sector holding `address`, `None` outside the flash.
+    ///
+    /// The last sector of a region that isn't a whole number of sectors is cut at the region end,
+    /// ranges never reach past the flash.
+    pub fn sector_containing(&self, address: u32) -> Option<Range<u64>> {
+        sector_containing(&self.sectors, self.start_address, self.device_size, address)
+    }
+
+    /// Absolute address range of every sector, in address order.
+    pub fn iter_sector_ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        sector_ranges(&self.sectors, self.start_address, self.device_size)
+    }
+
+    /// Bytes covered by whole sectors, `device_size` for a valid sector table.
+    pub fn total_size_from_sectors(&self) -> u64 {
+        total_size_from_sectors(&self.sectors, self.device_size)
+    }
+
+    /// Check that the sector table is sorted, non-overlapping and tiles the device.
+    ///
+    /// Each entry describes a region starting at `address` (relative to the flash start)
+    /// made of sectors of `size` bytes, which runs until the next entry or the device end.
+    pub fn validate_sectors(sectors: &[SectorInfo], device_size: u32) -> Result<(), ArmError> {
+        Ok(validate_sectors(sectors, device_size)?)
     }
 
     pub(crate) fn read_elf_bin_data<'a>(
-        elf: &'a goblin::elf::Elf<'_>,
+        elf: &goblin::elf::Elf<'_>,
         buffer: &'a [u8],
         address: u32,
         size: u32,
     ) -> Option<&'a [u8]> {
-        // Iterate all segments.
-        for ph in &elf.program_headers {
-            let segment_address = ph.p_paddr as u32;
-            let segment_size = ph.p_memsz.min(ph.p_filesz) as u32;
-    
-            log::debug!("Segment address: {:#010x}", segment_address);
-            log::debug!("Segment size:    {} bytes", segment_size);
-    
-            // If the requested data is above the current segment, skip the segment.
-            if address > segment_address + segment_size {
-                continue;
-            }
-    
-            // If the requested data is below the current segment, skip the segment.
-            if address + size <= segment_address {
-                continue;
-            }
-    
-            // If the requested data chunk is fully contained in the segment, extract and return the data segment.
-            if address >= segment_address && address + size <= segment_address + segment_size {
-                let start = ph.p_offset as u32 + address - segment_address;
-                return Some(&buffer[start as usize..][..size as usize]);
-            }
+        read_segment_data(elf, buffer, address, size)
+    }
+}
+
+/// A summary for people: the flash, page size, timeouts and the sector table with absolute
+/// addresses.
+impl fmt::Display for FlashDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let typ = match self.typ {
+            1 => "on-chip",
+            2 => "external",
+            _ => "unknown",
+        };
+        writeln!(f, "{:<14}{}", "Device", self.name)?;
+        writeln!(f, "{:<14}{:#06x}, {} flash", "Version", self.driver_version, typ)?;
+        writeln!(f, "{:<14}{:#010x}..{:#010x} ({} bytes)", "Flash", self.start_address, self.end_address(), self.device_size)?;
+        writeln!(f, "{:<14}{} bytes", "Page size", self.page_size)?;
+        writeln!(f, "{:<14}{:#04x}", "Erased value", self.erased_default_value)?;
+        writeln!(
+            f,
+            "{:<14}program page {} ms, erase sector {} ms",
+            "Timeouts", self.program_page_timeout, self.erase_sector_timeout
+        )?;
+
+        writeln!(f, "\nSectors")?;
+        writeln!(f, "  {:<12}{:>10}", "Address", "Size")?;
+        for sector in &self.sectors {
+            writeln!(f, "  {:#010x}  {:>10}", u64::from(self.start_address) + u64::from(sector.address), sector.size)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<soulcomposer_core::FlashDevice> for FlashDevice {
+    fn from(device: soulcomposer_core::FlashDevice) -> Self {
+        Self {
+            driver_version: device.driver_version,
+            name: device.name,
+            typ: device.device_type,
+            start_address: device.start_address,
+            device_size: device.device_size,
+            page_size: device.page_size,
+            erased_default_value: device.erased_default_value,
+            program_page_timeout: device.program_page_timeout,
+            erase_sector_timeout: device.erase_sector_timeout,
+            sectors: device.sectors,
         }
-    
-        None
     }
-    
 }
@@ -1,91 +1,620 @@
+use std::{collections::BTreeMap, fmt, io::Read, ops::Range};
+
 use goblin::elf::Elf;
 use serde::{Serialize, Deserialize};
+use soulcomposer_core::elf::EntrySymbols;
+
+use crate::{diagnostic::Diagnostic, pack::pdsc::PdscDevice, prog::arm::flash_device::FlashDevice};
+
+use super::{core_pinning::CorePinning, encryption::StubEncryption, parse_options::{ConflictPolicy, ParseOptions, Strictness}, algorithm_binary::{AlgorithmBinary}, arm_error::ArmError, build_attributes::BuildAttributes, entry_check::check_entry_points, static_base::uses_static_base, ram_layout::{plan_stack, RamRequirement}, stack_usage::{StackAnalyzer, StackEstimate}, trustzone::{AliasScheme, Security}};
+
+/// A run of equally sized sectors in flash.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectorRegion {
+    /// Absolute address of the first sector in this region.
+    pub address: u32,
+    /// Size of each sector in bytes.
+    pub size: u32,
+    /// Number of sectors in this region.
+    pub count: u32,
+}
+
+/// What kind of memory an algorithm programs.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RegionKind {
+    #[default]
+    MainFlash,
+    OptionBytes,
+    Otp,
+    Eeprom,
+}
+
+impl RegionKind {
+    /// Guesses the kind from an algorithm or device name, e.g. "STM32F4xx Flash OPT".
+    pub fn detect(name: &str) -> Self {
+        let words: Vec<String> = name
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .map(str::to_ascii_uppercase)
+            .collect();
+        let has = |candidates: &[&str]| words.iter().any(|word| candidates.contains(&word.as_str()));
+
+        if has(&["OTP"]) {
+            RegionKind::Otp
+        } else if has(&["OPT", "OPTION", "OPTIONS", "OPTIONBYTES", "OB"]) {
+            RegionKind::OptionBytes
+        } else if has(&["EEPROM", "EEP", "DATAFLASH"]) {
+            RegionKind::Eeprom
+        } else {
+            RegionKind::MainFlash
+        }
+    }
 
-use crate::{arm::flash_device::FlashDevice};
+    /// Writing these can't be undone or may lock the device for good (e.g. read protection levels),
+    /// so consumers should ask for explicit confirmation first.
+    pub fn requires_confirmation(self) -> bool {
+        matches!(self, RegionKind::OptionBytes | RegionKind::Otp)
+    }
+}
+
+/// Maps memory-mapped (XIP) addresses to the addresses an external flash algorithm programs.
+///
+/// `program = (xip & mask) + offset`, e.g. a QSPI flash mapped at 0x90000000 but programmed from 0
+/// uses `mask: 0x0FFF_FFFF, offset: 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTranslation {
+    pub mask: u32,
+    pub offset: u32,
+}
+
+/// Function code passed to Init/UnInit before erasing.
+pub const INIT_FUNCTION_ERASE: u32 = 1;
+/// Function code passed to Init/UnInit before programming.
+pub const INIT_FUNCTION_PROGRAM: u32 = 2;
+/// Function code passed to Init/UnInit before verifying.
+pub const INIT_FUNCTION_VERIFY: u32 = 3;
+
+/// Where the Init clock value came from.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClockSource {
+    /// Nothing better known, the algorithm gets 0.
+    #[default]
+    Unspecified,
+    /// The `Dclock` of the device processor in a PDSC file.
+    Pdsc,
+    /// Set explicitly by the user.
+    User,
+}
 
-use super::{algorithm_binary::{AlgorithmBinary}, arm_error::ArmError};
+/// Arguments for the CMSIS `Init(address, clock, function)` call.
+///
+/// `address` goes in r0, `clock` (Hz) in r1 and one of the `INIT_FUNCTION_*` codes in r2.
+/// Some vendor algorithms derive flash wait states or timings from `clock` and fail with 0.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct InitParameters {
+    /// Base address of the flash device.
+    pub address: u32,
+    /// Recommended core clock in Hz.
+    pub clock: u32,
+    pub clock_source: ClockSource,
+}
 
+/// A converted flash algorithm, the model every output format is written from.
+///
+/// More fields are added between releases, so code outside this crate starts from `new`,
+/// `Default` or `from_elf` and sets the fields it cares about.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[non_exhaustive]
 pub struct ArmFlashStub {
     pub name: String,
     pub description: String,
     pub default: bool,
+    /// Kind of memory the algorithm programs.
+    #[serde(default)]
+    pub region_kind: RegionKind,
+    /// Base64 of the code, or of the whole algorithm unless the data is emitted separately.
     pub instructions: String,
+    /// Base64 of the data with its zeroed bss, when emitted as a separate blob.
+    ///
+    /// It must be loaded at the load address + `data_section_offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_instructions: Option<String>,
     pub pc_init: Option<u32>,
+    #[serde(default)]
+    pub init_parameters: InitParameters,
+    /// Entry points go through shims that end in `bkpt`, see `ArmFlashStub::with_breakpoint_shims`.
+    #[serde(default)]
+    pub breakpoint_shims: bool,
     pub pc_uninit: Option<u32>,
     pub pc_program_page: u32,
     pub pc_erase_sector: u32,
     pub pc_erase_all: Option<u32>,
+    /// Offset of the data from the load address, where it starts in `instructions` or where
+    /// `data_instructions` goes.
+    ///
+    /// Stubs from before `BlobLayout` hold the linked address of `PrgData` here instead. That is the
+    /// same offset for FLMs linked at 0, which they all are, but not once the data is aligned.
     pub data_section_offset: u32,
+    /// Offset of the RW data from the start of the blob, set for algorithms built with
+    /// static base relative (RWPI) data.
+    ///
+    /// When present, r9 must hold `load address + static_base` whenever an entry point is called,
+    /// otherwise every access to global data lands at the wrong address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_base: Option<u32>,
+    /// Core the algorithm must run on, for multi-core devices.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_core: Option<CorePinning>,
+    /// Security state of the flash alias the algorithm was built for, on TrustZone-M targets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<Security>,
+    /// How the target aliases secure and non-secure memory, see `ArmFlashStub::check_security`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_scheme: Option<AliasScheme>,
+    /// Start of the flash as the core sees it, the XIP address for memory-mapped flash.
     pub flash_start_addr: u32,
     pub flash_end_addr: u32,
     pub flash_page_size: u32,
+    /// Translation from the flash range to the addresses passed to the algorithm, if they differ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_translation: Option<AddressTranslation>,
     pub erased_byte_value: u8,
+    /// Size of the first sector, kept for consumers that predate `sectors`.
     pub flash_sector_size: u32,
+    #[serde(default)]
+    pub sectors: Vec<SectorRegion>,
     pub program_timeout: u32,
     pub erase_timeout: u32,
+    /// RAM declared for the target, or the computed requirement if none was declared.
     pub ram_size: u32,
+    /// CRC32 over the decoded instruction blob and key metadata, see `ArmFlashStub::compute_crc32`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc32: Option<u32>,
+    /// Set when `instructions` and `data_instructions` are encrypted, see `StubEncryption`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Box<StubEncryption>>,
+    /// Statically estimated worst-case stack usage over all entry points, if it could be bounded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_usage: Option<u32>,
+    /// Stack the RAM planner reserves for the algorithm.
+    #[serde(default)]
+    pub stack_size: u32,
+    /// RAM needed for code, data, bss, stack and the page buffer.
+    #[serde(default)]
+    pub ram_required: u32,
     pub flash_size: u32,
+    /// Free-form metadata of the integrator, e.g. provenance, a ticket number or the factory line.
+    ///
+    /// Kept through every model format and left out of the CRC, this crate never reads it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
-fn extract_flash_device(elf: &goblin::elf::Elf, buffer: &[u8]) -> Result<FlashDevice, ArmError> {
-    // Extract the flash device info.
-    for sym in elf.syms.iter() {
-        let name = &elf.strtab[sym.st_name];
+pub(crate) fn extract_flash_device(
+    elf: &goblin::elf::Elf,
+    buffer: &[u8],
+    options: &ParseOptions,
+) -> Result<FlashDevice, ArmError> {
+    FlashDevice::from_elf_with_options(elf, buffer, options)
+}
 
-        if let "FlashDevice" = name {
-            // This struct contains information about the FLM file structure.
-            let address = sym.st_value as u32;
-            return FlashDevice::new(&elf, buffer, address);
-        }
-    }
+/// Timeouts above this are most likely in the wrong unit, CMSIS declares them in milliseconds.
+const TIMEOUT_LIMIT_MS: u32 = 3_600_000;
+
+fn suspicious_timeouts(flash_device: &FlashDevice) -> Option<Diagnostic> {
+    let (program, erase) = (flash_device.program_page_timeout, flash_device.erase_sector_timeout);
+    let reason = if program > TIMEOUT_LIMIT_MS || erase > TIMEOUT_LIMIT_MS {
+        "over an hour, check the unit"
+    } else if program > erase {
+        "programming a page takes longer than erasing a sector"
+    } else {
+        return None;
+    };
+
+    Some(Diagnostic::warning(
+        "suspicious-timeout",
+        format!("program timeout {} ms, erase timeout {} ms: {}", program, erase, reason),
+    ))
+}
+
+pub(crate) fn sector_regions(flash_device: &FlashDevice) -> Result<Vec<SectorRegion>, ArmError> {
+    let sectors = &flash_device.sectors;
+    sectors
+        .iter()
+        .enumerate()
+        .map(|(index, sector)| {
+            // Each region runs until the next one starts, the last one until the device end.
+            let end = sectors
+                .get(index + 1)
+                .map_or(flash_device.device_size, |next| next.address);
+            let address = flash_device.start_address.checked_add(sector.address).ok_or(ArmError::DescriptorOverflow {
+                field: "sector region address",
+                value: u64::from(flash_device.start_address) + u64::from(sector.address),
+            })?;
+
+            // Broken tables only get here when parsing leniently, their regions come out empty.
+            let count = end.saturating_sub(sector.address).checked_div(sector.size).unwrap_or(0);
+            Ok(SectorRegion { address, size: sector.size, count })
+        })
+        .collect()
+}
 
-    // Failed to find flash device
-    Err(ArmError::FlashDeviceInfoNotFound)
+impl InitParameters {
+    pub fn new(address: u32, clock: u32) -> Self {
+        Self { address, clock, ..Default::default() }
+    }
 }
 
 impl ArmFlashStub {
-    pub fn from_elf(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<ArmFlashStub, ArmError> {
-        let elf = match Elf::parse(buf) {
-            Ok(elf) => elf,
-            Err(_) => return Err(ArmError::ElfParse),
-        };
+    /// An empty stub called `name`, to fill in by hand.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ..Default::default() }
+    }
+
+    /// Addresses the algorithm programs, as the core sees them.
+    pub fn flash_range(&self) -> Range<u32> {
+        self.flash_start_addr..self.flash_end_addr
+    }
+
+    /// Build a stub from a flash algorithm ELF.
+    ///
+    /// `ram_size` is the RAM available on the target; pass 0 to fill it in with the computed requirement.
+    pub fn from_elf(buf: impl AsRef<[u8]>, name: String, default: bool, ram_size: u32) -> Result<ArmFlashStub, ArmError> {
+        Self::from_elf_with_options(buf, name, default, ram_size, &ParseOptions::default())
+    }
 
-        let flash_device = extract_flash_device(&elf, buf)?;
-        let algorithm_binary = AlgorithmBinary::new(&elf, buf)?;
+    /// Same as `from_elf`, reading the ELF from `reader`, e.g. an archive entry or a socket.
+    pub fn from_reader(mut reader: impl Read, name: String, default: bool, ram_size: u32) -> Result<ArmFlashStub, ArmError> {
+        // ELF headers point anywhere in the file, it has to be in memory to be parsed.
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Self::from_elf(buf, name, default, ram_size)
+    }
+
+    /// Same as `from_elf`, with control over parsing and blob layout. Diagnostics are only logged,
+    /// `compose_stub` returns them.
+    pub fn from_elf_with_options(
+        buf: impl AsRef<[u8]>,
+        name: String,
+        default: bool,
+        ram_size: u32,
+        options: &ParseOptions,
+    ) -> Result<ArmFlashStub, ArmError> {
+        let buf = buf.as_ref();
+        let elf = Elf::parse(buf)?;
+
+        let mut diagnostics = Vec::new();
+        let algo = Self::from_parsed_elf(&elf, buf, name, default, ram_size, options, &mut diagnostics)?;
+        for diagnostic in diagnostics {
+            tracing::warn!("{}", diagnostic);
+        }
+
+        Ok(algo)
+    }
+
+    /// Same as `from_elf_with_options`, for an ELF the caller already parsed from `buf`. Problems
+    /// that don't stop the conversion are added to `diagnostics`.
+    #[tracing::instrument(level = "debug", skip_all, fields(name = %name, size = buf.len()))]
+    pub(crate) fn from_parsed_elf(
+        elf: &Elf,
+        buf: &[u8],
+        name: String,
+        default: bool,
+        ram_size: u32,
+        options: &ParseOptions,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<ArmFlashStub, ArmError> {
+        if !elf.little_endian {
+            return Err(ArmError::BigEndianElf);
+        }
+
+        let first_diagnostic = diagnostics.len();
+        let flash_device = extract_flash_device(elf, buf, options)?;
+        if options.strictness == Strictness::Lenient {
+            if let Err(err) = FlashDevice::validate_sectors(&flash_device.sectors, flash_device.device_size) {
+                diagnostics.push(Diagnostic::warning("broken-sector-table", err.to_string()));
+            }
+        }
+        let algorithm_binary = AlgorithmBinary::new(elf, buf)?;
+        diagnostics.extend(algorithm_binary.suspicious_sections_diagnostic());
         let mut algo = ArmFlashStub::default();
 
         // Extract the function pointers.
         let code_section_offset = algorithm_binary.code_section.start;
-        for sym in elf.syms.iter() {
-            let name = &elf.strtab[sym.st_name];
-
-            match name {
-                "Init" => algo.pc_init = Some(sym.st_value as u32 - code_section_offset),
-                "UnInit" => algo.pc_uninit = Some(sym.st_value as u32 - code_section_offset),
-                "EraseChip" => algo.pc_erase_all = Some(sym.st_value as u32 - code_section_offset),
-                "EraseSector" => algo.pc_erase_sector = sym.st_value as u32 - code_section_offset,
-                "ProgramPage" => algo.pc_program_page = sym.st_value as u32 - code_section_offset,
-                _ => {}
+        let symbols = EntrySymbols::from_elf(elf);
+        let offset = |name: &str, pc: Option<u32>| -> Result<Option<u32>, ArmError> {
+            pc.map(|pc| {
+                pc.checked_sub(code_section_offset).ok_or_else(|| ArmError::InvalidEntryPoint {
+                    name: name.to_string(),
+                    offset: pc,
+                    reason: format!("linked below the code section at {:#010x}", code_section_offset),
+                })
+            })
+            .transpose()
+        };
+        algo.pc_init = offset("Init", symbols.init)?;
+        algo.pc_uninit = offset("UnInit", symbols.uninit)?;
+        algo.pc_erase_all = offset("EraseChip", symbols.erase_all)?;
+        algo.pc_erase_sector = offset("EraseSector", symbols.erase_sector)?.ok_or(ArmError::EntryPointMissing("EraseSector"))?;
+        algo.pc_program_page = offset("ProgramPage", symbols.program_page)?.ok_or(ArmError::EntryPointMissing("ProgramPage"))?;
+
+        // Catch offset, byte order and Thumb bit mistakes before the stub reaches hardware.
+        let mut entries = vec![("ProgramPage", algo.pc_program_page), ("EraseSector", algo.pc_erase_sector)];
+        let optional_entries = [("Init", algo.pc_init), ("UnInit", algo.pc_uninit), ("EraseChip", algo.pc_erase_all)];
+        entries.extend(optional_entries.iter().filter_map(|&(name, pc)| pc.map(|pc| (name, pc))));
+        check_entry_points(&algorithm_binary.code_section.data, &entries, diagnostics)?;
+
+        let mut analyzer = StackAnalyzer::new(&algorithm_binary.code_section.data);
+        let stack = entries.iter().fold(StackEstimate { bytes: 0, bounded: true }, |worst, &(_, pc)| {
+            let estimate = analyzer.estimate(pc);
+            StackEstimate {
+                bytes: worst.bytes.max(estimate.bytes),
+                bounded: worst.bounded && estimate.bounded,
             }
+        });
+        algo.stack_usage = if stack.bounded { Some(stack.bytes) } else { None };
+        algo.stack_size = plan_stack(stack);
+
+        let attributes = BuildAttributes::from_elf(elf, buf);
+        let rw_size = algorithm_binary.data_section.length + algorithm_binary.bss_section.length;
+        let relocatable_data = uses_static_base(attributes.as_ref(), &algorithm_binary.code_section.data, rw_size);
+        let blobs = algorithm_binary.layout(&options.blob_layout, relocatable_data)?;
+        if relocatable_data {
+            algo.static_base = Some(blobs.data_offset);
         }
 
-        algo.instructions = base64::encode(algorithm_binary.blob());
+        algo.sectors = sector_regions(&flash_device)?;
+        if options.blob_layout.split {
+            algo.instructions = base64::encode(&blobs.code);
+            algo.data_instructions = Some(base64::encode(blobs.data_with_bss()));
+        } else {
+            algo.instructions = base64::encode(blobs.unified());
+        }
+        algo.region_kind = match RegionKind::detect(&flash_device.name) {
+            RegionKind::MainFlash => RegionKind::detect(&name),
+            kind => kind,
+        };
+        if algo.region_kind != RegionKind::MainFlash {
+            diagnostics.push(Diagnostic::warning(
+                "region-kind-guessed",
+                format!("taken for {:?} from the names \"{}\" and \"{}\"", algo.region_kind, flash_device.name, name),
+            ));
+        }
+        diagnostics.extend(suspicious_timeouts(&flash_device));
         algo.name = name;
         algo.description = flash_device.name;
-        algo.data_section_offset = algorithm_binary.data_section.start;
-        algo.flash_sector_size = flash_device.sectors[0].size;
+        algo.data_section_offset = blobs.data_offset;
+        algo.flash_sector_size = flash_device.sectors.first().map_or(0, |sector| sector.size);
         algo.flash_start_addr = flash_device.start_address;
-        algo.flash_end_addr = flash_device.start_address + flash_device.device_size;
+        // The end is exclusive, flash that ends at 4 GiB can't be described.
+        algo.flash_end_addr = flash_device.start_address.checked_add(flash_device.device_size).ok_or(ArmError::DescriptorOverflow {
+            field: "flash end",
+            value: u64::from(flash_device.start_address) + u64::from(flash_device.device_size),
+        })?;
         algo.flash_size = flash_device.device_size;
         algo.flash_page_size = flash_device.page_size;
         algo.erase_timeout = flash_device.erase_sector_timeout;
         algo.program_timeout = flash_device.program_page_timeout;
         algo.erased_byte_value = flash_device.erased_default_value;
         algo.default = default;
-        algo.ram_size = ram_size;
-        
+        algo.address_translation = options.address_translation;
+        algo.init_parameters.address = algo.program_address(algo.flash_start_addr);
+        if let Some(scheme) = options.trustzone {
+            algo.security = Some(scheme.range_security("flash range", algo.flash_range())?);
+            algo.alias_scheme = Some(scheme);
+        }
+
+        let ram_requirement = RamRequirement::new(&blobs, flash_device.page_size, algo.stack_size);
+        algo.ram_required = ram_requirement.total();
+        algo.ram_size = if ram_size == 0 {
+            algo.ram_required
+        } else {
+            ram_requirement.check(ram_size)?;
+            ram_size
+        };
+        algo.crc32 = Some(algo.compute_crc32()?);
+
+        if options.breakpoint_shims {
+            algo = algo.with_breakpoint_shims(options.blob_layout.data_alignment)?;
+        }
+
+        options.check(&diagnostics[first_diagnostic..])?;
         Ok(algo)
     }
+
+    /// The address to pass to the algorithm for a mapped address in the flash range.
+    pub fn program_address(&self, address: u32) -> u32 {
+        match self.address_translation {
+            Some(translation) => (address & translation.mask).wrapping_add(translation.offset),
+            None => address,
+        }
+    }
+
+    /// The mapped address to read back for an address the algorithm programmed.
+    pub fn mapped_address(&self, program_address: u32) -> u32 {
+        match self.address_translation {
+            Some(translation) => {
+                (program_address.wrapping_sub(translation.offset) & translation.mask) | (self.flash_start_addr & !translation.mask)
+            }
+            None => program_address,
+        }
+    }
+
+    /// Fills in what the PDSC knows better and reconciles what both describe.
+    ///
+    /// The Init clock is taken from the device unless the user already set one,
+    /// the core pinning from the PDSC algorithm if it names one,
+    /// page size conflicts are settled by `options.page_size_policy`. Disagreements that were
+    /// settled are returned as diagnostics, or refused in strict mode.
+    pub fn apply_pdsc_device(&mut self, device: &PdscDevice, options: &ParseOptions) -> Result<Vec<Diagnostic>, ArmError> {
+        let mut diagnostics = Vec::new();
+        if self.init_parameters.clock_source != ClockSource::User {
+            if let Some(clock) = device.clock() {
+                self.init_parameters.clock = clock;
+                self.init_parameters.clock_source = ClockSource::Pdsc;
+            }
+        }
+
+        let algorithm_core = device.algorithm_for(self.flash_start_addr).and_then(|algorithm| algorithm.processor.as_deref());
+        if let (None, Some(pname)) = (&self.pinned_core, algorithm_core) {
+            self.pinned_core = Some(CorePinning::from_pdsc(device, pname)?);
+        }
+        match self.pinned_core {
+            Some(_) => self.check_core_pinning(device)?,
+            None if device.processors.len() > 1 => {
+                diagnostics.push(Diagnostic::warning(
+                    "unpinned-core",
+                    format!("{} has {} cores but no algorithm names one, the stub is not pinned", device.name, device.processors.len()),
+                ));
+            }
+            None => {}
+        }
+
+        let flash_info = device.flash_info_for(self.flash_start_addr);
+        if let Some(blank) = flash_info.and_then(|info| info.blank_value) {
+            if blank != u32::from(self.erased_byte_value) {
+                diagnostics.push(Diagnostic::warning(
+                    "blank-value-conflict",
+                    format!("PDSC blank value {:#x} differs from the FLM erased value {:#04x}, keeping the FLM value", blank, self.erased_byte_value),
+                ));
+            }
+        }
+
+        let pdsc_page_size = flash_info.and_then(|info| info.page_size);
+        if let Some(pdsc) = pdsc_page_size.filter(|&pdsc| pdsc != self.flash_page_size) {
+            let flm = self.flash_page_size;
+            match options.page_size_policy {
+                ConflictPolicy::PreferFlm => {
+                    diagnostics.push(Diagnostic::warning(
+                        "page-size-conflict",
+                        format!("keeping {} bytes from the FLM over {} bytes from the PDSC", flm, pdsc),
+                    ));
+                }
+                ConflictPolicy::PreferPdsc => {
+                    diagnostics.push(Diagnostic::warning(
+                        "page-size-conflict",
+                        format!("taking {} bytes from the PDSC over {} bytes from the FLM", pdsc, flm),
+                    ));
+                    // The page buffer grows or shrinks with the page, a filled in RAM size follows it
+                    // and a declared one has to hold it.
+                    let mut requirement = RamRequirement::of_stub(self);
+                    requirement.page_buffer = pdsc;
+                    if self.ram_size == self.ram_required {
+                        self.ram_size = requirement.total();
+                    } else {
+                        requirement.check(self.ram_size)?;
+                    }
+                    self.ram_required = requirement.total();
+                    self.flash_page_size = pdsc;
+                    if self.crc32.is_some() {
+                        self.crc32 = Some(self.compute_crc32()?);
+                    }
+                }
+                ConflictPolicy::Error => return Err(ArmError::PageSizeConflict { flm, pdsc }),
+            }
+        }
+
+        options.check(&diagnostics)?;
+        Ok(diagnostics)
+    }
+
+    /// Computes the CRC32 (IEEE) the embedded consumer checks before running the stub.
+    ///
+    /// The CRC covers the decoded instruction blob, then the decoded data blob if it is separate,
+    /// followed by these fields as little-endian u32,
+    /// with absent entry points encoded as 0xFFFFFFFF: `pc_init`, `pc_uninit`, `pc_program_page`,
+    /// `pc_erase_sector`, `pc_erase_all`, `data_section_offset`, `flash_start_addr`, `flash_end_addr`,
+    /// `flash_page_size`, `flash_sector_size`, then `erased_byte_value` as a single byte.
+    pub fn compute_crc32(&self) -> Result<u32, ArmError> {
+        let blob = base64::decode(&self.instructions).map_err(|err| ArmError::InstructionDecode(err.to_string()))?;
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&blob);
+        if let Some(data) = &self.data_instructions {
+            let data = base64::decode(data).map_err(|err| ArmError::InstructionDecode(err.to_string()))?;
+            hasher.update(&data);
+        }
+
+        let optional = |pc: Option<u32>| pc.unwrap_or(0xFFFF_FFFF);
+        let fields = [
+            optional(self.pc_init),
+            optional(self.pc_uninit),
+            self.pc_program_page,
+            self.pc_erase_sector,
+            optional(self.pc_erase_all),
+            self.data_section_offset,
+            self.flash_start_addr,
+            self.flash_end_addr,
+            self.flash_page_size,
+            self.flash_sector_size,
+        ];
+        for field in fields.iter() {
+            hasher.update(&field.to_le_bytes());
+        }
+        hasher.update(&[self.erased_byte_value]);
+
+        Ok(hasher.finalize())
+    }
+
+    /// Checks the stored CRC32 against the stub contents, stubs without a CRC pass.
+    ///
+    /// The CRC of an encrypted stub covers the decrypted blobs, it passes too and is checked once
+    /// decrypted. The cipher's tag already catches a changed blob.
+    pub fn verify_crc32(&self) -> Result<(), ArmError> {
+        if self.encryption.is_some() {
+            return Ok(());
+        }
+        if let Some(expected) = self.crc32 {
+            let actual = self.compute_crc32()?;
+            if actual != expected {
+                return Err(ArmError::CrcMismatch { expected, actual });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A summary for people: the flash, page and sector sizes and the entry points, one table each.
+/// `soul-composer inspect` prints this.
+impl fmt::Display for ArmFlashStub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<14}{}", "Name", self.name)?;
+        writeln!(f, "{:<14}{}", "Device", self.description)?;
+        writeln!(f, "{:<14}{:?}", "Region kind", self.region_kind)?;
+        writeln!(f, "{:<14}{:#010x}..{:#010x} ({} bytes)", "Flash", self.flash_start_addr, self.flash_end_addr, self.flash_size)?;
+        writeln!(f, "{:<14}{} bytes", "Page size", self.flash_page_size)?;
+        writeln!(f, "{:<14}{:#04x}", "Erased value", self.erased_byte_value)?;
+        writeln!(f, "{:<14}program page {} ms, erase sector {} ms", "Timeouts", self.program_timeout, self.erase_timeout)?;
+        writeln!(f, "{:<14}{} bytes, {} of them stack", "RAM required", self.ram_required, self.stack_size)?;
+
+        writeln!(f, "\nSectors")?;
+        writeln!(f, "  {:<12}{:>10}{:>8}", "Address", "Size", "Count")?;
+        for region in &self.sectors {
+            writeln!(f, "  {:#010x}  {:>10}{:>8}", region.address, region.size, region.count)?;
+        }
+
+        writeln!(f, "\nEntry points")?;
+        writeln!(f, "  {:<14}Offset", "Function")?;
+        let entries = [
+            ("Init", self.pc_init),
+            ("UnInit", self.pc_uninit),
+            ("EraseChip", self.pc_erase_all),
+            ("EraseSector", Some(self.pc_erase_sector)),
+            ("ProgramPage", Some(self.pc_program_page)),
+        ];
+        for (name, pc) in entries.iter() {
+            match pc {
+                Some(pc) => writeln!(f, "  {:<14}{:#010x}", name, pc)?,
+                None => writeln!(f, "  {:<14}-", name)?,
+            }
+        }
+
+        Ok(())
+    }
 }
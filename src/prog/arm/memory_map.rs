@@ -0,0 +1,115 @@
+use std::{fmt, ops::Range};
+
+use crate::pack::pdsc::PdscDevice;
+
+use super::{flash_stub_gen::ArmFlashStub, memory_range::MemoryRange};
+
+/// What lives in a region of the target address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    Flash,
+    Ram,
+    Peripheral,
+    Reserved,
+}
+
+/// A named region of the target memory map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub range: Range<u32>,
+    pub kind: MemoryKind,
+}
+
+/// The target memory map used to sanity check flash ranges and RAM layouts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+}
+
+/// A flash range or RAM layout that ends up somewhere it shouldn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutWarning {
+    /// "flash" or "RAM".
+    pub what: &'static str,
+    pub range: Range<u32>,
+    /// The offending region, `None` if the range isn't covered by any declared memory.
+    pub region: Option<MemoryRegion>,
+}
+
+impl fmt::Display for LayoutWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(
+                f,
+                "{} range {:#010x}..{:#010x} overlaps {:?} region {} ({:#010x}..{:#010x})",
+                self.what, self.range.start, self.range.end, region.kind, region.name, region.range.start, region.range.end
+            ),
+            None => write!(
+                f,
+                "{} range {:#010x}..{:#010x} is not inside any declared {} region",
+                self.what, self.range.start, self.range.end, self.what
+            ),
+        }
+    }
+}
+
+impl MemoryMap {
+    /// The architectural Cortex-M peripheral and system ranges, which never hold flash or RAM.
+    pub fn cortex_m() -> Self {
+        Self {
+            regions: vec![
+                MemoryRegion { name: "Peripherals".to_string(), range: 0x4000_0000..0x6000_0000, kind: MemoryKind::Peripheral },
+                MemoryRegion { name: "System".to_string(), range: 0xE000_0000..0xFFFF_FFFF, kind: MemoryKind::Peripheral },
+            ],
+        }
+    }
+
+    /// Builds a map from the memories of a PDSC device on top of the Cortex-M defaults.
+    pub fn from_pdsc(device: &PdscDevice) -> Self {
+        let mut map = Self::cortex_m();
+        for memory in &device.memories {
+            let name = memory.name.to_ascii_uppercase();
+            let writable = memory.access.as_deref().map_or(name.contains("RAM"), |access| access.contains('w'));
+            let kind = if writable { MemoryKind::Ram } else { MemoryKind::Flash };
+            let end = memory.start.saturating_add(memory.size);
+            map.regions.push(MemoryRegion { name: memory.name.clone(), range: memory.start..end, kind });
+        }
+
+        map
+    }
+
+    /// Checks the stub's flash range and a RAM layout starting at `ram_base`.
+    ///
+    /// Every finding is also logged as a warning.
+    pub fn check(&self, stub: &ArmFlashStub, ram_base: u32) -> Vec<LayoutWarning> {
+        let ram_end = ram_base.saturating_add(stub.ram_required.max(stub.ram_size));
+        let mut warnings = Vec::new();
+        warnings.extend(self.check_range("flash", stub.flash_range(), MemoryKind::Flash));
+        warnings.extend(self.check_range("RAM", ram_base..ram_end, MemoryKind::Ram));
+
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+
+        warnings
+    }
+
+    fn check_range(&self, what: &'static str, range: Range<u32>, expected: MemoryKind) -> Vec<LayoutWarning> {
+        let mut warnings: Vec<LayoutWarning> = self
+            .regions
+            .iter()
+            .filter(|region| matches!(region.kind, MemoryKind::Peripheral | MemoryKind::Reserved))
+            .filter(|region| region.range.intersects_range(&range))
+            .map(|region| LayoutWarning { what, range: range.clone(), region: Some(region.clone()) })
+            .collect();
+
+        // Only complain about coverage when the map declares memories of that kind at all.
+        let mut declared = self.regions.iter().filter(|region| region.kind == expected).peekable();
+        if declared.peek().is_some() && !declared.any(|region| region.range.contains_range(&range)) {
+            warnings.push(LayoutWarning { what, range, region: None });
+        }
+
+        warnings
+    }
+}
@@ -1,5 +1,29 @@
 pub mod algorithm_binary;
 pub mod arm_error;
-pub mod memory_range;
+mod blank_check;
+pub mod build_attributes;
+pub mod core_isa;
+pub mod core_pinning;
+pub mod debug_dump;
+pub mod decompose;
+pub mod disasm;
+#[cfg(feature = "emulator")]
+pub mod emulator;
+pub mod encryption;
+pub(crate) mod entry_check;
+pub mod memory_map;
+pub(crate) mod memory_range;
+pub mod parse_options;
 pub mod flash_device;
-pub mod flash_stub_gen;
\ No newline at end of file
+pub mod flash_stub_gen;
+pub mod ram_layout;
+mod raw_image;
+mod shim;
+pub mod stack_usage;
+pub(crate) mod static_base;
+pub mod stub_diff;
+pub mod stub_group;
+pub mod thumb;
+pub mod timing;
+pub mod trustzone;
+pub mod validated;
\ No newline at end of file
@@ -0,0 +1,88 @@
+use soulcomposer_core::ParseLimits;
+
+use crate::diagnostic::{Diagnostic, Severity};
+
+use super::{
+    algorithm_binary::BlobLayout, arm_error::ArmError, flash_stub_gen::AddressTranslation, trustzone::AliasScheme,
+};
+
+/// How to settle a value that the FLM and the PDSC disagree on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the value from the FlashDevice descriptor in the FLM.
+    PreferFlm,
+    /// Take the value from the PDSC.
+    PreferPdsc,
+    /// Refuse to compose the stub.
+    Error,
+}
+
+/// How picky parsing is about descriptors and the problems found while composing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Cut over-long names and sector tables down to the limits and accept other driver
+    /// versions, for salvaging algorithms from broken packs. Sector tables that don't tile the
+    /// flash are reported as diagnostics.
+    Lenient,
+    /// Refuse malformed descriptors, report everything else as diagnostics.
+    Normal,
+    /// Also refuse stubs that come with warnings.
+    Strict,
+}
+
+/// Options controlling how algorithms are parsed and combined with pack metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Policy for FLM and PDSC page sizes that differ.
+    pub page_size_policy: ConflictPolicy,
+    /// Layout of the emitted code and data blobs.
+    pub blob_layout: BlobLayout,
+    /// Route entry points through shims that end in `bkpt`, for halt-on-breakpoint flashers.
+    pub breakpoint_shims: bool,
+    /// Translation for external flash programmed at different addresses than it is mapped at.
+    pub address_translation: Option<AddressTranslation>,
+    /// TrustZone-M alias scheme of the target, records which alias the flash base uses.
+    pub trustzone: Option<AliasScheme>,
+    /// Sector table entries read before the descriptor counts as malformed.
+    pub max_sectors: usize,
+    /// Longest device name accepted, at most the 128 bytes the descriptor has room for.
+    pub max_name_length: usize,
+    pub strictness: Strictness,
+    /// Symbol of the descriptor, tried before `FlashDevice` for vendors that renamed it.
+    pub descriptor_symbol: Option<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            page_size_policy: ConflictPolicy::PreferFlm,
+            blob_layout: BlobLayout::default(),
+            breakpoint_shims: false,
+            address_translation: None,
+            trustzone: None,
+            max_sectors: ParseLimits::default().max_sectors,
+            max_name_length: ParseLimits::default().max_name_length,
+            strictness: Strictness::Normal,
+            descriptor_symbol: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// The limits the descriptor parser in `soulcomposer-core` applies.
+    pub fn limits(&self) -> ParseLimits {
+        ParseLimits {
+            max_sectors: self.max_sectors,
+            max_name_length: self.max_name_length,
+            lenient: self.strictness == Strictness::Lenient,
+        }
+    }
+
+    /// Fails on the first warning in strict mode.
+    pub(crate) fn check(&self, diagnostics: &[Diagnostic]) -> Result<(), ArmError> {
+        match diagnostics.iter().find(|diagnostic| diagnostic.severity >= Severity::Warning) {
+            Some(diagnostic) if self.strictness == Strictness::Strict => Err(ArmError::Rejected(diagnostic.clone())),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::convert::TryFrom;
+
+use super::{algorithm_binary::Blobs, arm_error::ArmError, flash_stub_gen::ArmFlashStub, stack_usage::StackEstimate};
+
+/// Stack reserved for the algorithm when nothing better is known.
+pub const DEFAULT_STACK_SIZE: u32 = 512;
+
+/// Extra stack added on top of a static estimate, e.g. for exception frames.
+pub const STACK_MARGIN: u32 = 64;
+
+/// Picks the stack size to reserve from a static estimate.
+///
+/// Bounded estimates get a margin, unbounded ones never go below `DEFAULT_STACK_SIZE`. An
+/// estimate near 4 GiB saturates, it fails any RAM check after.
+pub fn plan_stack(estimate: StackEstimate) -> u32 {
+    let planned = align_up(estimate.bytes.saturating_add(STACK_MARGIN), 8);
+    if estimate.bounded {
+        planned
+    } else {
+        planned.max(DEFAULT_STACK_SIZE)
+    }
+}
+
+/// Breakdown of the RAM a flash algorithm needs while running on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamRequirement {
+    /// Size of the `PrgCode` section, including padding up to the data.
+    pub code: u32,
+    /// Size of the `PrgData` section.
+    pub data: u32,
+    /// Size of the zero-initialised data after `PrgData`.
+    pub bss: u32,
+    /// Stack reserved after the blob.
+    pub stack: u32,
+    /// Buffer holding one page of data for `ProgramPage`.
+    pub page_buffer: u32,
+}
+
+impl RamRequirement {
+    pub(crate) fn new(blobs: &Blobs, page_size: u32, stack_size: u32) -> Self {
+        Self {
+            code: blobs.data_offset,
+            data: blobs.data.len() as u32,
+            bss: blobs.bss_length,
+            stack: stack_size,
+            page_buffer: page_size,
+        }
+    }
+
+    /// The requirement of a composed stub. The stub only keeps code, data and bss summed up in
+    /// `ram_required`, so they come back together as `code`.
+    pub fn of_stub(stub: &ArmFlashStub) -> Self {
+        let code = stub.ram_required.saturating_sub(align_up(stub.stack_size, 4).saturating_add(align_up(stub.flash_page_size, 4)));
+        Self { code, data: 0, bss: 0, stack: stub.stack_size, page_buffer: stub.flash_page_size }
+    }
+
+    /// Total bytes needed, with the stack and page buffer starting word aligned. Saturates at
+    /// `u32::MAX` instead of wrapping.
+    pub fn total(&self) -> u32 {
+        let blob = align_up(self.code.saturating_add(self.data).saturating_add(self.bss), 4);
+        blob.saturating_add(align_up(self.stack, 4)).saturating_add(align_up(self.page_buffer, 4))
+    }
+
+    /// Check the requirement against the RAM available on the target.
+    pub fn check(&self, available: u32) -> Result<(), ArmError> {
+        let required = self.total();
+        if required > available {
+            return Err(ArmError::RamOverflow { required, available });
+        }
+
+        Ok(())
+    }
+}
+
+/// `bkpt #0` twice, what routines called by a debugger return to.
+pub const TRAMPOLINE: [u8; 4] = [0x00, 0xBE, 0x00, 0xBE];
+
+/// Where a debugger puts an algorithm to call its routines one at a time:
+/// `[blob][stack][page buffer][trampoline]` from `base`.
+///
+/// Each call gets SP at `stack_top`, LR at `trampoline | 1`, r9 at `static_base` when the
+/// algorithm has one, and returns by halting on the trampoline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunLayout {
+    pub base: u32,
+    pub stack_top: u32,
+    /// The page ProgramPage writes from.
+    pub buffer: u32,
+    pub trampoline: u32,
+    pub static_base: Option<u32>,
+}
+
+impl RunLayout {
+    /// Lays out `stub`, whose blob is `blob_len` bytes, from `base` in `available` bytes of RAM.
+    ///
+    /// A layout that runs past the end of the address space is refused.
+    pub fn plan(stub: &ArmFlashStub, base: u32, blob_len: u32, available: u32) -> Result<Self, ArmError> {
+        let stack_size = match stub.stack_size {
+            0 => DEFAULT_STACK_SIZE,
+            size => size,
+        };
+        let narrow = |field, value: u64| u32::try_from(value).map_err(|_| ArmError::DescriptorOverflow { field, value });
+        let align = |value: u64, align: u64| (value + align - 1) & !(align - 1);
+        let stack_top = align(u64::from(base) + u64::from(blob_len) + u64::from(stack_size), 8);
+        let trampoline = align(stack_top + u64::from(stub.flash_page_size), 4);
+        narrow("RAM layout end", trampoline + TRAMPOLINE.len() as u64)?;
+        let static_base = match stub.static_base {
+            Some(offset) => Some(narrow("static base", u64::from(base) + u64::from(offset))?),
+            None => None,
+        };
+        let layout = RunLayout {
+            base,
+            stack_top: stack_top as u32,
+            buffer: stack_top as u32,
+            trampoline: trampoline as u32,
+            static_base,
+        };
+        if layout.size() > available {
+            return Err(ArmError::RamOverflow { required: layout.size(), available });
+        }
+        Ok(layout)
+    }
+
+    /// Bytes from `base` to the end of the trampoline.
+    pub fn size(&self) -> u32 {
+        self.trampoline + TRAMPOLINE.len() as u32 - self.base
+    }
+}
+
+/// Rounds up to `align`, saturating at the last aligned value below 4 GiB.
+fn align_up(value: u32, align: u32) -> u32 {
+    value.saturating_add(align - 1) & !(align - 1)
+}
@@ -0,0 +1,96 @@
+use std::convert::TryFrom;
+
+use crate::prog::generic::descriptor::{Architecture, LoaderDescriptor};
+
+use super::{
+    arm_error::ArmError,
+    entry_check::check_entry_points,
+    flash_stub_gen::{ArmFlashStub, SectorRegion},
+    ram_layout::{plan_stack, RamRequirement},
+    stack_usage::StackEstimate,
+};
+
+fn narrow(field: &'static str, value: u64) -> Result<u32, ArmError> {
+    u32::try_from(value).map_err(|_| ArmError::DescriptorOverflow { field, value })
+}
+
+impl ArmFlashStub {
+    /// Build a stub from a raw algorithm image, such as a HEX or bin dump of an FLM, that has no
+    /// symbols or FlashDevice left to read.
+    ///
+    /// The entry points and flash geometry come from `descriptor`, whose entries must use the
+    /// CMSIS names (`Init`, `UnInit`, `ProgramPage`, `EraseSector`, `EraseChip`). The image is
+    /// emitted as one blob, so any data it holds stays where it was linked. Raw images carry no
+    /// timeouts, they are left at 0.
+    pub fn from_loader_descriptor(descriptor: &LoaderDescriptor, blob: &[u8]) -> Result<ArmFlashStub, ArmError> {
+        if descriptor.architecture != Architecture::Arm {
+            return Err(ArmError::WrongArchitecture(descriptor.architecture.to_string()));
+        }
+
+        let composed = descriptor.compose_with_blob(blob)?;
+        let mut algo = ArmFlashStub::default();
+        let mut program_page = None;
+        let mut erase_sector = None;
+        for entry in &composed.entry_points {
+            let pc = narrow("entry offset", entry.offset)? | u32::from(entry.thumb);
+            match entry.name.as_str() {
+                "Init" => algo.pc_init = Some(pc),
+                "UnInit" => algo.pc_uninit = Some(pc),
+                "ProgramPage" => program_page = Some(pc),
+                "EraseSector" => erase_sector = Some(pc),
+                "EraseChip" => algo.pc_erase_all = Some(pc),
+                name => tracing::warn!(name, "ignoring entry point, it is not a CMSIS flash algorithm function"),
+            }
+        }
+        algo.pc_program_page = program_page.ok_or(ArmError::EntryPointMissing("ProgramPage"))?;
+        algo.pc_erase_sector = erase_sector.ok_or(ArmError::EntryPointMissing("EraseSector"))?;
+
+        let mut entries = vec![("ProgramPage", algo.pc_program_page), ("EraseSector", algo.pc_erase_sector)];
+        let optional_entries = [("Init", algo.pc_init), ("UnInit", algo.pc_uninit), ("EraseChip", algo.pc_erase_all)];
+        entries.extend(optional_entries.iter().filter_map(|&(name, pc)| pc.map(|pc| (name, pc))));
+        let mut diagnostics = Vec::new();
+        check_entry_points(blob, &entries, &mut diagnostics)?;
+        for diagnostic in diagnostics {
+            tracing::warn!("{}", diagnostic);
+        }
+
+        let flash = &composed.flash;
+        let start = narrow("flash start", flash.start)?;
+        let size = narrow("flash size", flash.size)?;
+        // Both fit in 32 bits, so only their sum can overflow.
+        let end = start.checked_add(size).ok_or(ArmError::DescriptorOverflow { field: "flash end", value: flash.start + flash.size })?;
+        if flash.sector_size == 0 {
+            return Err(ArmError::SectorSizeZero { index: 0, address: 0 });
+        }
+
+        algo.name = composed.name;
+        algo.description = composed.description;
+        algo.instructions = composed.instructions;
+        algo.data_section_offset = blob.len() as u32;
+        algo.flash_start_addr = start;
+        algo.flash_end_addr = end;
+        algo.flash_size = size;
+        algo.flash_page_size = flash.page_size;
+        algo.flash_sector_size = flash.sector_size;
+        algo.sectors = vec![SectorRegion { address: start, size: flash.sector_size, count: size / flash.sector_size }];
+        algo.erased_byte_value = flash.erased_value;
+        algo.init_parameters.address = start;
+        algo.stack_size = match descriptor.stack_size {
+            0 => plan_stack(StackEstimate { bytes: 0, bounded: false }),
+            stack_size => stack_size,
+        };
+
+        let requirement = RamRequirement {
+            code: blob.len() as u32,
+            data: 0,
+            bss: 0,
+            stack: algo.stack_size,
+            page_buffer: flash.page_size,
+        };
+        algo.ram_required = requirement.total();
+        algo.ram_size = algo.ram_required;
+        algo.crc32 = Some(algo.compute_crc32()?);
+
+        Ok(algo)
+    }
+}
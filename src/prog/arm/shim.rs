@@ -0,0 +1,60 @@
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub, thumb};
+
+/// Size of one shim: `bl entry; bkpt #0; nop`.
+const SHIM_SIZE: u32 = 8;
+/// The shim area is padded so the data keeps its alignment.
+const SHIM_AREA_ALIGN: u32 = 16;
+const BKPT: [u8; 2] = [0x00, 0xBE];
+const NOP: [u8; 2] = [0x00, 0xBF];
+
+impl ArmFlashStub {
+    /// Prepends a shim per entry point that calls it and then hits `bkpt`, for flashers that only
+    /// detect completion by halting on a breakpoint instead of setting LR to one.
+    ///
+    /// Entry points are redirected to the shims and every other offset moves up by the shim area.
+    pub fn with_breakpoint_shims(&self, data_alignment: u32) -> Result<ArmFlashStub, ArmError> {
+        if self.breakpoint_shims {
+            return Err(ArmError::ShimAlreadyApplied);
+        }
+
+        let code = base64::decode(&self.instructions).map_err(|err| ArmError::InstructionDecode(err.to_string()))?;
+        let mut stub = self.clone();
+        let mut entries = vec![&mut stub.pc_program_page, &mut stub.pc_erase_sector];
+        entries.extend(vec![stub.pc_init.as_mut(), stub.pc_uninit.as_mut(), stub.pc_erase_all.as_mut()].into_iter().flatten());
+
+        let align = SHIM_AREA_ALIGN.max(data_alignment).next_power_of_two();
+        let area = (entries.len() as u32 * SHIM_SIZE).div_ceil(align) * align;
+
+        let mut blob = Vec::with_capacity(area as usize + code.len());
+        for (index, entry) in entries.into_iter().enumerate() {
+            let shim = index as u32 * SHIM_SIZE;
+            let target = area + (*entry & !1);
+            let bl = thumb::encode_bl(shim, target).ok_or(ArmError::ShimOutOfRange { target })?;
+
+            blob.extend(bl);
+            blob.extend(BKPT);
+            blob.extend(NOP);
+            *entry = shim | 1;
+        }
+        blob.resize(area as usize, 0);
+        blob.extend(code);
+
+        stub.instructions = base64::encode(blob);
+        stub.data_section_offset += area;
+        stub.static_base = stub.static_base.map(|base| base + area);
+        stub.ram_required += area;
+        if stub.ram_required > stub.ram_size {
+            // A RAM size that was filled in from the requirement grows along, a declared one is a limit.
+            if self.ram_size != self.ram_required {
+                return Err(ArmError::RamOverflow { required: stub.ram_required, available: stub.ram_size });
+            }
+            stub.ram_size = stub.ram_required;
+        }
+        stub.breakpoint_shims = true;
+        if stub.crc32.is_some() {
+            stub.crc32 = Some(stub.compute_crc32()?);
+        }
+
+        Ok(stub)
+    }
+}
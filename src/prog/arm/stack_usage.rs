@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+
+use super::thumb::{self, Instruction};
+
+/// Upper bound on instructions walked per function, guards against garbage input.
+const MAX_INSTRUCTIONS: usize = 0x4000;
+
+/// Worst-case stack usage of a routine including everything it calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackEstimate {
+    /// Deepest stack usage found, in bytes.
+    pub bytes: u32,
+    /// False when recursion, indirect calls or unresolvable branches were found,
+    /// in which case `bytes` is only a lower bound.
+    pub bounded: bool,
+}
+
+/// Estimates stack usage by walking the call graph of a Thumb code blob.
+pub struct StackAnalyzer<'a> {
+    code: &'a [u8],
+    cache: HashMap<u32, StackEstimate>,
+    in_progress: HashSet<u32>,
+}
+
+impl<'a> StackAnalyzer<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Self {
+            code,
+            cache: HashMap::new(),
+            in_progress: HashSet::new(),
+        }
+    }
+
+    /// Estimates the routine at `entry`, an offset into the code blob (the Thumb bit is ignored).
+    pub fn estimate(&mut self, entry: u32) -> StackEstimate {
+        let entry = entry & !1;
+        if let Some(estimate) = self.cache.get(&entry) {
+            return *estimate;
+        }
+
+        // Recursion can't be bounded statically.
+        if !self.in_progress.insert(entry) {
+            return StackEstimate { bytes: 0, bounded: false };
+        }
+
+        let estimate = self.walk(entry);
+        self.in_progress.remove(&entry);
+        self.cache.insert(entry, estimate);
+        estimate
+    }
+
+    fn walk(&mut self, entry: u32) -> StackEstimate {
+        let mut worst = StackEstimate { bytes: 0, bounded: true };
+        let mut visited = HashSet::new();
+        let mut pending = vec![(entry, 0u32)];
+
+        while let Some((offset, mut depth)) = pending.pop() {
+            if !visited.insert(offset) {
+                continue;
+            }
+
+            if visited.len() > MAX_INSTRUCTIONS {
+                worst.bounded = false;
+                break;
+            }
+
+            let decoded = match thumb::decode(self.code, 0, offset) {
+                Some(decoded) => decoded,
+                None => {
+                    worst.bounded = false;
+                    continue;
+                }
+            };
+            let next = offset + decoded.size;
+
+            match decoded.instruction {
+                Instruction::Push { registers } => depth = deeper(depth, 4 * registers.count_ones(), &mut worst),
+                Instruction::SubSp { imm } => depth = deeper(depth, imm, &mut worst),
+                Instruction::AddSp { imm } => depth = depth.saturating_sub(imm),
+                Instruction::Pop { registers } => {
+                    depth = depth.saturating_sub(4 * registers.count_ones());
+                    if registers & (1 << 15) != 0 {
+                        continue;
+                    }
+                }
+                Instruction::BranchExchange { .. } | Instruction::Undefined { .. } => continue,
+                Instruction::BranchLink { target } => {
+                    let callee = self.estimate(target);
+                    worst.bytes = worst.bytes.max(deeper(depth, callee.bytes, &mut worst));
+                    worst.bounded &= callee.bounded;
+                }
+                Instruction::BranchLinkExchange { .. } => worst.bounded = false,
+                Instruction::Branch { target, cond } => {
+                    if (target as usize) < self.code.len() {
+                        pending.push((target, depth));
+                    } else {
+                        worst.bounded = false;
+                    }
+
+                    if cond.is_none() {
+                        continue;
+                    }
+                }
+                Instruction::CompareBranch { target, .. } => pending.push((target, depth)),
+                _ => {}
+            }
+
+            worst.bytes = worst.bytes.max(depth);
+            pending.push((next, depth));
+        }
+
+        worst
+    }
+}
+
+/// `depth + bytes`, a stack deeper than the address space saturates and can't be bounded.
+fn deeper(depth: u32, bytes: u32, worst: &mut StackEstimate) -> u32 {
+    depth.checked_add(bytes).unwrap_or_else(|| {
+        worst.bounded = false;
+        u32::MAX
+    })
+}
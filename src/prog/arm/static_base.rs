@@ -0,0 +1,36 @@
+use super::{
+    build_attributes::{BuildAttributes, RW_DATA_SB_RELATIVE, TAG_ABI_PCS_RW_DATA},
+    thumb::{self, Instruction},
+};
+
+/// The static base register used by RWPI code.
+const SB_REGISTER: u32 = 9;
+
+/// Returns true if the algorithm addresses its RW data through the static base register (r9).
+///
+/// The build attributes are trusted when present. Without them the code is scanned for
+/// `LDR.W`/`STR.W rt, [r9, #imm12]` whose offset lands in the `rw_size` bytes of data and bss.
+/// Other uses of r9, such as `mov r0, r9` or offsets past the data, are what code built without
+/// RWPI does with r9 as an ordinary callee-saved register, so they don't count.
+pub(crate) fn uses_static_base(attributes: Option<&BuildAttributes>, code: &[u8], rw_size: u32) -> bool {
+    if let Some(rw_data) = attributes.and_then(|attributes| attributes.integer(TAG_ABI_PCS_RW_DATA)) {
+        return rw_data == RW_DATA_SB_RELATIVE;
+    }
+    if rw_size == 0 {
+        return false;
+    }
+
+    let mut offset = 0;
+    while let Some(decoded) = thumb::decode(code, 0, offset) {
+        if let Instruction::Other32 { raw } = decoded.instruction {
+            let (hw1, imm12) = (raw >> 16, raw & 0xFFF);
+            if hw1 & 0xFFE0 == 0xF8C0 && hw1 & 0xF == SB_REGISTER && imm12 < rw_size {
+                return true;
+            }
+        }
+
+        offset += decoded.size;
+    }
+
+    false
+}
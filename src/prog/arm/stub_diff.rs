@@ -0,0 +1,164 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::flash_stub_gen::ArmFlashStub;
+
+/// Fields holding base64 blobs, compared by size and CRC32 instead of content.
+const BLOB_FIELDS: [&str; 2] = ["instructions", "dataInstructions"];
+
+/// Fields that are expected to differ between two builds of the same algorithm.
+const IGNORED_FIELDS: [&str; 2] = ["name", "crc32"];
+
+/// One field that differs, `None` where it is missing on one side.
+///
+/// `field` is the camelCase path of the model, e.g. `sectors[1].count` or `instructions.crc32`.
+/// Values are formatted for reading: addresses in hex, strings quoted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl FieldChange {
+    /// Whether the change is in the sector table, its length included.
+    pub fn is_sector(&self) -> bool {
+        self.field.starts_with("sectors.") || self.field.starts_with("sectors[")
+    }
+
+    /// Whether the change is in the size or CRC32 of one of the code or data blobs.
+    pub fn is_blob(&self) -> bool {
+        BLOB_FIELDS.iter().any(|blob| self.field.strip_prefix(blob).is_some_and(|rest| rest.starts_with('.')))
+    }
+}
+
+/// Field-level differences between two stubs, sorted by field, see `ArmFlashStub::diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct StubDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl StubDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, FieldChange> {
+        self.changes.iter()
+    }
+
+    /// The change of `field`, if it differs.
+    pub fn get(&self, field: &str) -> Option<&FieldChange> {
+        self.changes.iter().find(|change| change.field == field)
+    }
+
+    /// Whether the sector tables differ.
+    pub fn sectors_changed(&self) -> bool {
+        self.changes.iter().any(FieldChange::is_sector)
+    }
+
+    /// Whether the code or data blobs differ.
+    pub fn blobs_changed(&self) -> bool {
+        self.changes.iter().any(FieldChange::is_blob)
+    }
+}
+
+impl<'a> IntoIterator for &'a StubDiff {
+    type Item = &'a FieldChange;
+    type IntoIter = std::slice::Iter<'a, FieldChange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+/// One `field  old -> new` line per change, fields padded to the same width.
+impl fmt::Display for StubDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.changes.iter().map(|change| change.field.len()).max().unwrap_or(0);
+        let side = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_string());
+        for change in &self.changes {
+            writeln!(f, "{:<width$}  {} -> {}", change.field, side(&change.old), side(&change.new), width = width)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_address(field: &str) -> bool {
+    let leaf = field.rsplit(['.', ']']).next().unwrap_or(field);
+    leaf.starts_with("pc") || leaf.ends_with("Addr") || leaf == "address" || leaf == "staticBase"
+}
+
+/// Flattens a JSON value into `field.path[index]` leaves.
+fn flatten(prefix: &str, value: &Value, leaves: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                if BLOB_FIELDS.contains(&path.as_str()) {
+                    let blob = value.as_str().and_then(|blob| base64::decode(blob).ok()).unwrap_or_default();
+                    leaves.insert(format!("{}.size", path), blob.len().to_string());
+                    leaves.insert(format!("{}.crc32", path), format!("{:#010x}", crc32fast::hash(&blob)));
+                } else {
+                    flatten(&path, value, leaves);
+                }
+            }
+        }
+        Value::Array(items) => {
+            leaves.insert(format!("{}.len", prefix), items.len().to_string());
+            for (index, item) in items.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, index), item, leaves);
+            }
+        }
+        Value::Number(number) if is_address(prefix) => {
+            leaves.insert(prefix.to_string(), format!("{:#010x}", number.as_u64().unwrap_or_default()));
+        }
+        Value::String(text) => {
+            leaves.insert(prefix.to_string(), format!("{:?}", text));
+        }
+        value => {
+            leaves.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+fn leaves(stub: &ArmFlashStub) -> BTreeMap<String, String> {
+    // The model has no maps with non-string keys, serializing it into a `Value` can't fail.
+    let value = serde_json::to_value(stub).unwrap_or_default();
+    let mut leaves = BTreeMap::new();
+    flatten("", &value, &mut leaves);
+    for field in IGNORED_FIELDS {
+        leaves.remove(field);
+    }
+    leaves
+}
+
+impl ArmFlashStub {
+    /// Field-level differences from `self` to `other`, ignoring the name and the overall CRC.
+    ///
+    /// Blobs are compared by size and CRC32, sector regions field by field, so a changed sector
+    /// table shows up as e.g. `sectors[1].count` and `sectors.len`.
+    pub fn diff(&self, other: &ArmFlashStub) -> StubDiff {
+        let (mut old, new) = (leaves(self), leaves(other));
+
+        let mut changes = Vec::new();
+        for (field, new_value) in new {
+            match old.remove(&field) {
+                Some(old_value) if old_value == new_value => {}
+                old_value => changes.push(FieldChange { field, old: old_value, new: Some(new_value) }),
+            }
+        }
+        changes.extend(old.into_iter().map(|(field, old_value)| FieldChange { field, old: Some(old_value), new: None }));
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+
+        StubDiff { changes }
+    }
+}
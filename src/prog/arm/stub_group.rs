@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+/// One bank of a grouped device and the algorithm that programs it.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlashBank {
+    /// Bank number, starting at 1.
+    pub bank: u8,
+    pub start: u32,
+    /// End address, exclusive.
+    pub end: u32,
+    /// Index into `ArmFlashStubGroup::algorithms`.
+    pub algorithm: usize,
+}
+
+/// A part of a programming request routed to one bank.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedRange<'a> {
+    pub address: u32,
+    pub size: u32,
+    pub bank: u8,
+    pub algorithm: &'a ArmFlashStub,
+}
+
+/// Several algorithms grouped into one entry, e.g. both banks of a dual-bank device or
+/// flash plus EEPROM.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ArmFlashStubGroup {
+    pub name: String,
+    pub algorithms: Vec<ArmFlashStub>,
+    /// Banks (or regions) sorted by address.
+    pub banks: Vec<FlashBank>,
+}
+
+impl ArmFlashStubGroup {
+    /// Groups two FLMs, one per bank.
+    pub fn dual_bank(name: String, bank1: ArmFlashStub, bank2: ArmFlashStub) -> Result<Self, ArmError> {
+        Self::merge(name, vec![bank1, bank2])
+    }
+
+    /// Combines algorithms covering different ranges of one device, e.g. main flash, a second bank
+    /// and EEPROM, into one entry. Regions are ordered and numbered by address.
+    pub fn merge(name: String, mut algorithms: Vec<ArmFlashStub>) -> Result<Self, ArmError> {
+        algorithms.sort_by_key(|algorithm| algorithm.flash_start_addr);
+        let banks = algorithms
+            .iter()
+            .enumerate()
+            .map(|(index, algorithm)| FlashBank {
+                bank: index as u8 + 1,
+                start: algorithm.flash_start_addr,
+                end: algorithm.flash_end_addr,
+                algorithm: index,
+            })
+            .collect();
+
+        Self::new(name, algorithms, banks)
+    }
+
+    /// Adds another algorithm to the group as a new region.
+    pub fn push(self, algorithm: ArmFlashStub) -> Result<Self, ArmError> {
+        let mut algorithms = self.algorithms;
+        let mut banks = self.banks;
+        banks.push(FlashBank {
+            bank: banks.iter().map(|bank| bank.bank).max().unwrap_or(0) + 1,
+            start: algorithm.flash_start_addr,
+            end: algorithm.flash_end_addr,
+            algorithm: algorithms.len(),
+        });
+        algorithms.push(algorithm);
+
+        Self::new(self.name, algorithms, banks)
+    }
+
+    /// Splits one FLM that covers both banks at the start of the second bank.
+    pub fn split_banks(name: String, algorithm: ArmFlashStub, bank2_start: u32) -> Result<Self, ArmError> {
+        if bank2_start <= algorithm.flash_start_addr || bank2_start >= algorithm.flash_end_addr {
+            return Err(ArmError::AddressOutOfRange { address: bank2_start, size: 0 });
+        }
+
+        let banks = vec![
+            FlashBank { bank: 1, start: algorithm.flash_start_addr, end: bank2_start, algorithm: 0 },
+            FlashBank { bank: 2, start: bank2_start, end: algorithm.flash_end_addr, algorithm: 0 },
+        ];
+
+        Self::new(name, vec![algorithm], banks)
+    }
+
+    fn new(name: String, algorithms: Vec<ArmFlashStub>, mut banks: Vec<FlashBank>) -> Result<Self, ArmError> {
+        banks.sort_by_key(|bank| bank.start);
+        for pair in banks.windows(2) {
+            if pair[1].start < pair[0].end {
+                return Err(ArmError::BankOverlap {
+                    first: pair[0].bank,
+                    second: pair[1].bank,
+                    address: pair[1].start,
+                });
+            }
+        }
+
+        for bank in &banks {
+            let algorithm = &algorithms[bank.algorithm];
+            if bank.start < algorithm.flash_start_addr || bank.end > algorithm.flash_end_addr {
+                return Err(ArmError::AddressOutOfRange { address: bank.start, size: bank.end - bank.start });
+            }
+        }
+
+        Ok(Self { name, algorithms, banks })
+    }
+
+    /// The bank and algorithm responsible for `address`.
+    pub fn route(&self, address: u32) -> Option<(&FlashBank, &ArmFlashStub)> {
+        self.banks
+            .iter()
+            .find(|bank| address >= bank.start && address < bank.end)
+            .map(|bank| (bank, &self.algorithms[bank.algorithm]))
+    }
+
+    /// Splits `size` bytes at `address` into per-bank ranges with the algorithm to use for each.
+    pub fn route_range(&self, address: u32, size: u32) -> Result<Vec<RoutedRange<'_>>, ArmError> {
+        let end = u64::from(address) + u64::from(size);
+        let mut routed = Vec::new();
+        let mut cursor = u64::from(address);
+
+        while cursor < end {
+            let (bank, algorithm) = self
+                .route(cursor as u32)
+                .ok_or(ArmError::AddressOutOfRange { address: cursor as u32, size: (end - cursor) as u32 })?;
+            let chunk_end = end.min(u64::from(bank.end));
+            routed.push(RoutedRange {
+                address: cursor as u32,
+                size: (chunk_end - cursor) as u32,
+                bank: bank.bank,
+                algorithm,
+            });
+            cursor = chunk_end;
+        }
+
+        Ok(routed)
+    }
+}
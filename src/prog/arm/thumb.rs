@@ -0,0 +1,294 @@
+use std::fmt;
+
+/// A decoded Thumb instruction, only the classes the checks care about are broken out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `push {..}`, bit 14 of the register list is LR.
+    Push { registers: u16 },
+    /// `pop {..}`, bit 15 of the register list is PC.
+    Pop { registers: u16 },
+    /// `add sp, sp, #imm`
+    AddSp { imm: u32 },
+    /// `sub sp, sp, #imm`
+    SubSp { imm: u32 },
+    /// `b` / `b<cond>` with an absolute target address.
+    Branch { target: u32, cond: Option<u8> },
+    /// `bl` with an absolute target address.
+    BranchLink { target: u32 },
+    /// `bx rm`
+    BranchExchange { rm: u8 },
+    /// `blx rm`
+    BranchLinkExchange { rm: u8 },
+    /// `cbz` / `cbnz`
+    CompareBranch { rn: u8, target: u32, nonzero: bool },
+    /// `bkpt #imm`
+    Breakpoint { imm: u8 },
+    /// `svc #imm`
+    Supervisor { imm: u8 },
+    /// Permanently undefined or erased-flash encodings.
+    Undefined { raw: u32 },
+    /// Any other 16-bit instruction.
+    Other16 { raw: u16 },
+    /// Any other 32-bit instruction, first halfword in the upper 16 bits.
+    Other32 { raw: u32 },
+}
+
+/// One instruction decoded at a given address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    pub address: u32,
+    /// Instruction size in bytes, 2 or 4.
+    pub size: u32,
+    pub instruction: Instruction,
+}
+
+pub(crate) const CONDITIONS: [&str; 14] = ["eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le"];
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+fn branch_target(address: u32, offset: i32) -> u32 {
+    address.wrapping_add(4).wrapping_add(offset as u32)
+}
+
+/// Expands a Thumb-2 modified immediate constant.
+pub(crate) fn thumb_expand_imm(imm12: u32) -> u32 {
+    let imm8 = imm12 & 0xFF;
+    if imm12 >> 10 == 0 {
+        match (imm12 >> 8) & 0x3 {
+            0 => imm8,
+            1 => imm8 << 16 | imm8,
+            2 => imm8 << 24 | imm8 << 8,
+            _ => imm8 << 24 | imm8 << 16 | imm8 << 8 | imm8,
+        }
+    } else {
+        (0x80 | (imm12 & 0x7F)).rotate_right(imm12 >> 7)
+    }
+}
+
+/// Returns true if the halfword starts a 32-bit Thumb-2 instruction.
+pub fn is_wide(halfword: u16) -> bool {
+    halfword >> 11 >= 0b11101
+}
+
+/// Decodes the instruction at `offset` in `code`, which is loaded at `base`.
+///
+/// Returns `None` if the code ends before the instruction does.
+pub fn decode(code: &[u8], base: u32, offset: u32) -> Option<Decoded> {
+    let halfword = |at: u32| -> Option<u16> {
+        let at = at as usize;
+        code.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+
+    let address = base.wrapping_add(offset);
+    let hw1 = halfword(offset)?;
+    if !is_wide(hw1) {
+        return Some(Decoded { address, size: 2, instruction: decode_narrow(hw1, address) });
+    }
+
+    let hw2 = halfword(offset + 2)?;
+    Some(Decoded { address, size: 4, instruction: decode_wide(hw1, hw2, address) })
+}
+
+/// Encodes `bl` from `address` to `target`, `None` if the target is out of the ±16 MB range.
+pub fn encode_bl(address: u32, target: u32) -> Option<[u8; 4]> {
+    let offset = target.wrapping_sub(address.wrapping_add(4)) as i32;
+    if !(-(1 << 24)..(1 << 24)).contains(&offset) || offset & 1 != 0 {
+        return None;
+    }
+
+    let imm = offset as u32;
+    let s = (imm >> 24) & 1;
+    let j1 = (!(imm >> 23) ^ s) & 1;
+    let j2 = (!(imm >> 22) ^ s) & 1;
+    let hw1 = 0xF000 | s << 10 | (imm >> 12) & 0x3FF;
+    let hw2 = 0xD000 | j1 << 13 | j2 << 11 | (imm >> 1) & 0x7FF;
+
+    let mut bytes = [0; 4];
+    bytes[..2].copy_from_slice(&(hw1 as u16).to_le_bytes());
+    bytes[2..].copy_from_slice(&(hw2 as u16).to_le_bytes());
+    Some(bytes)
+}
+
+fn decode_narrow(hw: u16, address: u32) -> Instruction {
+    let raw = u32::from(hw);
+    match hw {
+        _ if hw & 0xFF80 == 0xB000 => Instruction::AddSp { imm: (raw & 0x7F) << 2 },
+        _ if hw & 0xFF80 == 0xB080 => Instruction::SubSp { imm: (raw & 0x7F) << 2 },
+        _ if hw & 0xFE00 == 0xB400 => Instruction::Push { registers: (hw & 0xFF) | (hw & 0x100) << 6 },
+        _ if hw & 0xFE00 == 0xBC00 => Instruction::Pop { registers: (hw & 0xFF) | (hw & 0x100) << 7 },
+        _ if hw & 0xFF00 == 0xBE00 => Instruction::Breakpoint { imm: hw as u8 },
+        _ if hw & 0xF500 == 0xB100 => Instruction::CompareBranch {
+            rn: (hw & 0x7) as u8,
+            target: branch_target(address, (((raw >> 9) & 1) << 6 | ((raw >> 3) & 0x1F) << 1) as i32),
+            nonzero: hw & 0x0800 != 0,
+        },
+        _ if hw & 0xFF80 == 0x4700 => Instruction::BranchExchange { rm: ((hw >> 3) & 0xF) as u8 },
+        _ if hw & 0xFF80 == 0x4780 => Instruction::BranchLinkExchange { rm: ((hw >> 3) & 0xF) as u8 },
+        _ if hw & 0xF000 == 0xD000 => match (hw >> 8) & 0xF {
+            0xE => Instruction::Undefined { raw },
+            0xF => Instruction::Supervisor { imm: hw as u8 },
+            cond => Instruction::Branch {
+                target: branch_target(address, sign_extend((raw & 0xFF) << 1, 9)),
+                cond: Some(cond as u8),
+            },
+        },
+        _ if hw & 0xF800 == 0xE000 => Instruction::Branch {
+            target: branch_target(address, sign_extend((raw & 0x7FF) << 1, 12)),
+            cond: None,
+        },
+        _ => Instruction::Other16 { raw: hw },
+    }
+}
+
+fn decode_wide(hw1: u16, hw2: u16, address: u32) -> Instruction {
+    let (h1, h2) = (u32::from(hw1), u32::from(hw2));
+    let raw = h1 << 16 | h2;
+
+    // Erased flash and the permanently undefined UDF.W encoding.
+    if hw1 == 0xFFFF || (hw1 & 0xFFF0 == 0xF7F0 && hw2 & 0xF000 == 0xA000) {
+        return Instruction::Undefined { raw };
+    }
+
+    match (hw1, hw2) {
+        (0xE92D, _) => return Instruction::Push { registers: hw2 },
+        (0xE8BD, _) => return Instruction::Pop { registers: hw2 },
+        // SUB.W / ADD.W sp, sp, #modified immediate
+        _ if hw1 & 0xFBEF == 0xF1AD && hw2 & 0x8F00 == 0x0D00 => {
+            return Instruction::SubSp { imm: thumb_expand_imm(((h1 >> 10) & 1) << 11 | ((h2 >> 12) & 0x7) << 8 | (h2 & 0xFF)) };
+        }
+        _ if hw1 & 0xFBEF == 0xF10D && hw2 & 0x8F00 == 0x0D00 => {
+            return Instruction::AddSp { imm: thumb_expand_imm(((h1 >> 10) & 1) << 11 | ((h2 >> 12) & 0x7) << 8 | (h2 & 0xFF)) };
+        }
+        // SUBW / ADDW sp, sp, #imm12
+        _ if hw1 & 0xFBFF == 0xF2AD && hw2 & 0x8F00 == 0x0D00 => {
+            return Instruction::SubSp { imm: ((h1 >> 10) & 1) << 11 | ((h2 >> 12) & 0x7) << 8 | (h2 & 0xFF) };
+        }
+        _ if hw1 & 0xFBFF == 0xF20D && hw2 & 0x8F00 == 0x0D00 => {
+            return Instruction::AddSp { imm: ((h1 >> 10) & 1) << 11 | ((h2 >> 12) & 0x7) << 8 | (h2 & 0xFF) };
+        }
+        _ => {}
+    }
+
+    if hw1 & 0xF800 == 0xF000 && hw2 & 0x8000 == 0x8000 {
+        let s = (h1 >> 10) & 1;
+        let j1 = (h2 >> 13) & 1;
+        let j2 = (h2 >> 11) & 1;
+
+        // BL and B.W (T4) share the same offset layout.
+        let long_offset = || {
+            let i1 = !(j1 ^ s) & 1;
+            let i2 = !(j2 ^ s) & 1;
+            sign_extend(s << 24 | i1 << 23 | i2 << 22 | (h1 & 0x3FF) << 12 | (h2 & 0x7FF) << 1, 25)
+        };
+
+        match hw2 & 0xD000 {
+            0xD000 => return Instruction::BranchLink { target: branch_target(address, long_offset()) },
+            0x9000 => return Instruction::Branch { target: branch_target(address, long_offset()), cond: None },
+            0x8000 if (hw1 >> 6) & 0xE != 0xE => {
+                let offset = sign_extend(s << 20 | j2 << 19 | j1 << 18 | (h1 & 0x3F) << 12 | (h2 & 0x7FF) << 1, 21);
+                return Instruction::Branch {
+                    target: branch_target(address, offset),
+                    cond: Some(((hw1 >> 6) & 0xF) as u8),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Instruction::Other32 { raw }
+}
+
+fn write_registers(f: &mut fmt::Formatter<'_>, registers: u16) -> fmt::Result {
+    let names = (0..16u8).filter(|bit| registers & (1 << bit) != 0).map(register_name);
+    write!(f, "{{{}}}", names.collect::<Vec<_>>().join(", "))
+}
+
+/// Cycles a taken branch spends refilling the pipeline, 1 to 3 on a Cortex-M3/M4.
+const PIPELINE_REFILL: u32 = 2;
+
+impl Instruction {
+    /// Rough cycle count on a Cortex-M3/M4 running from zero wait state memory, taking every
+    /// branch and the slowest divide. Good for comparing routines, not for timing them.
+    pub fn cycles(&self) -> u32 {
+        let transfers = |registers: u32| 1 + registers.count_ones();
+        match *self {
+            Instruction::Push { registers } => transfers(registers.into()),
+            Instruction::Pop { registers } if registers & (1 << 15) != 0 => transfers(registers.into()) + PIPELINE_REFILL,
+            Instruction::Pop { registers } => transfers(registers.into()),
+            Instruction::Branch { .. }
+            | Instruction::BranchLink { .. }
+            | Instruction::BranchExchange { .. }
+            | Instruction::BranchLinkExchange { .. }
+            | Instruction::CompareBranch { .. } => 1 + PIPELINE_REFILL,
+            Instruction::Other16 { raw } => match raw >> 12 {
+                // Register and immediate offset loads and stores.
+                0b0101..=0b1001 => 2,
+                0b0100 if raw & 0xF800 == 0x4800 => 2,
+                0b1100 => transfers(u32::from(raw & 0xFF)),
+                // mov pc, rm and add pc, rm
+                0b0100 if raw & 0xFC87 == 0x4487 => 1 + PIPELINE_REFILL,
+                _ => 1,
+            },
+            Instruction::Other32 { raw } => {
+                let hw1 = raw >> 16;
+                if hw1 & 0xFE00 == 0xF800 {
+                    2
+                } else if hw1 & 0xFE40 == 0xE800 {
+                    let refill = if hw1 & 0x10 != 0 && raw & (1 << 15) != 0 { PIPELINE_REFILL } else { 0 };
+                    transfers(raw & 0xFFFF) + refill
+                } else if hw1 & 0xFE00 == 0xE800 {
+                    3
+                } else if hw1 & 0xFFD0 == 0xFB90 {
+                    12
+                } else {
+                    1
+                }
+            }
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Push { registers } => {
+                f.write_str("push ")?;
+                write_registers(f, registers)
+            }
+            Instruction::Pop { registers } => {
+                f.write_str("pop ")?;
+                write_registers(f, registers)
+            }
+            Instruction::AddSp { imm } => write!(f, "add sp, #{:#x}", imm),
+            Instruction::SubSp { imm } => write!(f, "sub sp, #{:#x}", imm),
+            Instruction::Branch { target, cond } => {
+                let cond = cond.and_then(|cond| CONDITIONS.get(cond as usize)).copied().unwrap_or("");
+                write!(f, "b{} {:#010x}", cond, target)
+            }
+            Instruction::BranchLink { target } => write!(f, "bl {:#010x}", target),
+            Instruction::BranchExchange { rm } => write!(f, "bx {}", register_name(rm)),
+            Instruction::BranchLinkExchange { rm } => write!(f, "blx {}", register_name(rm)),
+            Instruction::CompareBranch { rn, target, nonzero } => {
+                write!(f, "{} r{}, {:#010x}", if nonzero { "cbnz" } else { "cbz" }, rn, target)
+            }
+            Instruction::Breakpoint { imm } => write!(f, "bkpt #{:#04x}", imm),
+            Instruction::Supervisor { imm } => write!(f, "svc #{:#04x}", imm),
+            Instruction::Undefined { raw } => write!(f, "udf ; {:#x}", raw),
+            Instruction::Other16 { raw } => write!(f, ".short {:#06x}", raw),
+            Instruction::Other32 { raw } => write!(f, ".word {:#010x}", raw),
+        }
+    }
+}
+
+fn register_name(register: u8) -> String {
+    match register {
+        13 => "sp".to_string(),
+        14 => "lr".to_string(),
+        15 => "pc".to_string(),
+        _ => format!("r{}", register),
+    }
+}
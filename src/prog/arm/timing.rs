@@ -0,0 +1,82 @@
+use super::{
+    arm_error::ArmError,
+    flash_stub_gen::{ArmFlashStub, SectorRegion},
+};
+
+/// Worst-case duration of erasing and programming an image, derived from the algorithm timeouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgrammingEstimate {
+    /// Number of sectors the image touches.
+    pub sectors_erased: u32,
+    /// Number of pages written.
+    pub pages_programmed: u32,
+    /// Erase time in milliseconds.
+    pub erase_ms: u64,
+    /// Program time in milliseconds.
+    pub program_ms: u64,
+}
+
+impl ProgrammingEstimate {
+    /// Total erase and program time in milliseconds.
+    pub fn total_ms(&self) -> u64 {
+        self.erase_ms + self.program_ms
+    }
+}
+
+impl ArmFlashStub {
+    /// Estimates how long writing `size` bytes at `address` takes at most.
+    ///
+    /// The timeouts are upper bounds, so this is a worst-case figure suited to production planning.
+    pub fn estimate_programming_time(&self, address: u32, size: u32) -> Result<ProgrammingEstimate, ArmError> {
+        let end = u64::from(address) + u64::from(size);
+        if address < self.flash_start_addr || end > u64::from(self.flash_end_addr) {
+            return Err(ArmError::AddressOutOfRange { address, size });
+        }
+
+        if size == 0 {
+            return Ok(ProgrammingEstimate { sectors_erased: 0, pages_programmed: 0, erase_ms: 0, program_ms: 0 });
+        }
+
+        // Older stubs only carry a single sector size.
+        let uniform;
+        let regions = if self.sectors.is_empty() {
+            uniform = [SectorRegion {
+                address: self.flash_start_addr,
+                size: self.flash_sector_size,
+                count: self.flash_end_addr.saturating_sub(self.flash_start_addr) / self.flash_sector_size.max(1),
+            }];
+            &uniform[..]
+        } else {
+            &self.sectors[..]
+        };
+
+        let end = end as u32;
+        let mut sectors_erased = 0u32;
+        for region in regions.iter().filter(|region| region.size > 0) {
+            let region_end = region.size.checked_mul(region.count).and_then(|size| region.address.checked_add(size));
+            let region_end = region_end.ok_or(ArmError::DescriptorOverflow {
+                field: "sector region end",
+                value: u64::from(region.address) + u64::from(region.size) * u64::from(region.count),
+            })?;
+            let (low, high) = (address.max(region.address), end.min(region_end));
+            if low >= high {
+                continue;
+            }
+
+            let first = (low - region.address) / region.size;
+            let last = (high - region.address).div_ceil(region.size);
+            sectors_erased = sectors_erased.saturating_add(last - first);
+        }
+
+        let page_size = self.flash_page_size.max(1);
+        let page_start = address - (address - self.flash_start_addr) % page_size;
+        let pages_programmed = (end - page_start).div_ceil(page_size);
+
+        Ok(ProgrammingEstimate {
+            sectors_erased,
+            pages_programmed,
+            erase_ms: u64::from(sectors_erased) * u64::from(self.erase_timeout),
+            program_ms: u64::from(pages_programmed) * u64::from(self.program_timeout),
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use std::{fmt, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use super::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+/// TrustZone-M security state of an address alias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Security {
+    NonSecure,
+    Secure,
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Security::NonSecure => f.write_str("non-secure"),
+            Security::Secure => f.write_str("secure"),
+        }
+    }
+}
+
+/// How the IDAU splits the address space into Secure and Non-secure aliases.
+///
+/// Parts following ARM's reference IDAU (LPC55S6x, SSE-200 based) tell the aliases apart by
+/// bit 28 for both flash and RAM, which is the default. Others differ per region, STM32L5/U5 use
+/// bit 26 for flash (0x0800_0000 / 0x0C00_0000) but bit 28 for SRAM, and Nuvoton M2351 has the
+/// secure alias at the lower address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AliasScheme {
+    /// Address bit(s) that differ between the two flash aliases.
+    pub flash_mask: u32,
+    /// Address bit(s) that differ between the two RAM aliases.
+    pub ram_mask: u32,
+    /// Whether the secure alias is the one with the mask set.
+    pub secure_when_set: bool,
+}
+
+impl Default for AliasScheme {
+    fn default() -> Self {
+        Self {
+            flash_mask: 1 << 28,
+            ram_mask: 1 << 28,
+            secure_when_set: true,
+        }
+    }
+}
+
+impl AliasScheme {
+    fn security(&self, address: u32, mask: u32) -> Security {
+        if (address & mask != 0) == self.secure_when_set {
+            Security::Secure
+        } else {
+            Security::NonSecure
+        }
+    }
+
+    fn alias(&self, address: u32, mask: u32, security: Security) -> u32 {
+        if (security == Security::Secure) == self.secure_when_set {
+            address | mask
+        } else {
+            address & !mask
+        }
+    }
+
+    /// The security state a flash address belongs to.
+    pub fn flash_security(&self, address: u32) -> Security {
+        self.security(address, self.flash_mask)
+    }
+
+    /// The security state a RAM address belongs to.
+    pub fn ram_security(&self, address: u32) -> Security {
+        self.security(address, self.ram_mask)
+    }
+
+    /// The same flash location seen through the alias for `security`.
+    pub fn flash_alias(&self, address: u32, security: Security) -> u32 {
+        self.alias(address, self.flash_mask, security)
+    }
+
+    /// The same RAM location seen through the alias for `security`.
+    pub fn ram_alias(&self, address: u32, security: Security) -> u32 {
+        self.alias(address, self.ram_mask, security)
+    }
+
+    /// Checks that the flash `range` sits entirely inside one alias and returns it.
+    pub(crate) fn range_security(&self, what: &'static str, range: Range<u32>) -> Result<Security, ArmError> {
+        let security = self.flash_security(range.start);
+        let last = range.end.saturating_sub(1).max(range.start);
+        if self.flash_security(last) != security {
+            return Err(ArmError::SecurityAliasMismatch { what, address: last, expected: security });
+        }
+        Ok(security)
+    }
+}
+
+impl ArmFlashStub {
+    /// The flash range as seen through the alias for `security`, the plain range for stubs
+    /// without TrustZone information.
+    pub fn flash_alias(&self, security: Security) -> Range<u32> {
+        match self.alias_scheme {
+            Some(scheme) => scheme.flash_alias(self.flash_start_addr, security)..scheme.flash_alias(self.flash_end_addr, security),
+            None => self.flash_range(),
+        }
+    }
+
+    /// Whether `address` is in the flash, through either alias.
+    pub fn contains_flash_address(&self, address: u32) -> bool {
+        match self.alias_scheme {
+            Some(scheme) => {
+                let address = scheme.flash_alias(address, Security::NonSecure);
+                self.flash_alias(Security::NonSecure).contains(&address)
+            }
+            None => self.flash_range().contains(&address),
+        }
+    }
+
+    /// Checks that the flash range, the Init address and the entry points, once loaded at
+    /// `ram_base`, all use the alias recorded in `security`.
+    ///
+    /// A secure algorithm called through a non-secure alias (or the other way round) faults or
+    /// silently programs through the wrong view on the first flash controller access.
+    pub fn check_security(&self, ram_base: u32) -> Result<(), ArmError> {
+        let (scheme, expected) = match (self.alias_scheme, self.security) {
+            (Some(scheme), Some(security)) => (scheme, security),
+            _ => return Ok(()),
+        };
+
+        scheme.range_security("flash range", self.flash_range())?;
+        let flash_addresses = [("flash start", self.flash_start_addr), ("Init address", self.init_parameters.address)];
+        for &(what, address) in flash_addresses.iter() {
+            if scheme.flash_security(address) != expected {
+                return Err(ArmError::SecurityAliasMismatch { what, address, expected });
+            }
+        }
+
+        let mut entries = vec![("ProgramPage", self.pc_program_page), ("EraseSector", self.pc_erase_sector)];
+        let optional = [("Init", self.pc_init), ("UnInit", self.pc_uninit), ("EraseChip", self.pc_erase_all)];
+        entries.extend(optional.iter().filter_map(|&(what, pc)| pc.map(|pc| (what, pc))));
+        for (what, pc) in entries {
+            let address = ram_base.wrapping_add(pc & !1);
+            if scheme.ram_security(address) != expected {
+                return Err(ArmError::SecurityAliasMismatch { what, address, expected });
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use std::ops::Deref;
+
+use serde::Serialize;
+
+use crate::prog::flash_algorithm::FlashAlgorithm;
+
+use super::{
+    arm_error::ArmError,
+    flash_device::{FlashDevice, SectorInfo},
+    flash_stub_gen::ArmFlashStub,
+};
+
+/// An `ArmFlashStub` that passed `ArmFlashStub::validate`.
+///
+/// The exporters that feed hardware, the RAM image, the C and Rust sources and the probe-rs
+/// algorithm, only take this type, so a hand-edited stub can't reach a device without being
+/// checked first. It derefs to the stub for reading and serializes as the stub.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ValidatedArmFlashStub(ArmFlashStub);
+
+impl ValidatedArmFlashStub {
+    /// Gives the stub back, e.g. to edit it and validate it again.
+    pub fn into_inner(self) -> ArmFlashStub {
+        self.0
+    }
+}
+
+impl Deref for ValidatedArmFlashStub {
+    type Target = ArmFlashStub;
+
+    fn deref(&self) -> &ArmFlashStub {
+        &self.0
+    }
+}
+
+impl AsRef<ArmFlashStub> for ValidatedArmFlashStub {
+    fn as_ref(&self) -> &ArmFlashStub {
+        &self.0
+    }
+}
+
+impl ArmFlashStub {
+    /// Checks what a flasher relies on: the CRC matches, the sector table tiles the flash,
+    /// every entry point lands in the image and the image fits the declared RAM.
+    ///
+    /// Stubs from before the sector table have no `sectors`, their flash is checked as one
+    /// region of `flash_sector_size` sectors. An encrypted stub is refused, its blobs are ciphertext until decrypted.
+    pub fn validate(self) -> Result<ValidatedArmFlashStub, ArmError> {
+        if let Some(encryption) = &self.encryption {
+            return Err(ArmError::StubEncrypted(encryption.key_id.clone()));
+        }
+        self.verify_crc32()?;
+
+        let sectors: Vec<SectorInfo> = if self.sectors.is_empty() {
+            vec![SectorInfo { address: 0, size: self.flash_sector_size }]
+        } else {
+            self.sectors
+                .iter()
+                .map(|region| SectorInfo { address: region.address.wrapping_sub(self.flash_start_addr), size: region.size })
+                .collect()
+        };
+        FlashDevice::validate_sectors(&sectors, self.flash_size)?;
+
+        let length = self.blob()?.len();
+        for (name, offset) in self.entry_points().iter() {
+            if offset >= length as u64 {
+                return Err(ArmError::InvalidEntryPoint {
+                    name: name.to_string(),
+                    offset: offset as u32,
+                    reason: format!("past the end of the {} byte image", length),
+                });
+            }
+        }
+
+        if self.ram_size < self.ram_required {
+            return Err(ArmError::RamOverflow { required: self.ram_required, available: self.ram_size });
+        }
+
+        Ok(ValidatedArmFlashStub(self))
+    }
+}
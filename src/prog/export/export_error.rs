@@ -0,0 +1,38 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::prog::arm::arm_error::ArmError;
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Unknown output format {0}")]
+    UnknownFormat(String),
+
+    #[error("Unknown field naming {0}, expected camel-case, snake-case or kebab-case")]
+    UnknownNaming(String),
+
+    #[error("Output format {0} is not enabled in this build")]
+    FormatDisabled(&'static str),
+
+    #[error("{0} only holds a single algorithm")]
+    SingleAlgorithmFormat(&'static str),
+
+    #[error("probe-rs has no core type for {0}")]
+    UnsupportedCore(String),
+
+    #[error("No device of the pack is in the family {0}")]
+    UnknownFamily(String),
+
+    #[error("No sector of the flash holds the scratch address {0:#010x}")]
+    ScratchOutOfRange(u32),
+
+    #[error("Failed to write {format}, {reason}")]
+    Serialize { format: &'static str, reason: String },
+
+    #[error("Failed to write the output, {0}")]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+}
@@ -0,0 +1,123 @@
+//! J-Link Commander scripts that run a converted algorithm by hand, for labs with SEGGER probes
+//! and nothing else.
+//!
+//! The script does what `dap::self_test` does, one command at a time: loads the blob with
+//! `loadbin`, erases the scratch sector, programs its first page from a second file and checks it
+//! with `verifybin`. The core halts on a `bkpt` after each routine, J-Link prints the registers
+//! and R0 holds what the routine returned.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::prog::{
+    arm::{
+        flash_stub_gen::{INIT_FUNCTION_ERASE, INIT_FUNCTION_PROGRAM},
+        ram_layout::{RunLayout, TRAMPOLINE},
+        validated::ValidatedArmFlashStub,
+    },
+    export::export_error::ExportError,
+    flash_algorithm::FlashAlgorithm,
+};
+
+/// SWD clock of the script in kHz, what most targets take from reset.
+pub const DEFAULT_SPEED_KHZ: u32 = 4000;
+
+/// Time Init and UnInit get to return, FlashDevice only has timeouts for the other routines.
+const INIT_TIMEOUT_MS: u32 = 2000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JLinkOptions {
+    /// RAM the algorithm is loaded into.
+    pub ram_start: u32,
+    pub ram_size: u32,
+    /// J-Link's name of the device, J-Link asks for it when not given.
+    pub device: Option<String>,
+    pub speed_khz: u32,
+    /// An address in the sector to overwrite, the last sector of the flash by default.
+    pub scratch: Option<u32>,
+    /// Paths the script loads the image and the test page from, as JLinkExe will find them.
+    pub image_file: String,
+    pub page_file: String,
+}
+
+/// A script and the files it loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JLinkScript {
+    pub script: String,
+    /// The blob, padded to whole words.
+    pub image: Vec<u8>,
+    /// The page programmed into the scratch sector.
+    pub page: Vec<u8>,
+    pub layout: RunLayout,
+    pub sector: Range<u32>,
+}
+
+/// Writes the commands calling the routine at `pc` with `args` and halting after `timeout`.
+fn call(script: &mut String, layout: &RunLayout, routine: &str, pc: u32, args: [u32; 3], timeout: u32) {
+    let _ = writeln!(script, "\n// {}({:#x}, {:#x}, {:#x})", routine, args[0], args[1], args[2]);
+    for (index, arg) in args.iter().enumerate() {
+        let _ = writeln!(script, "wreg R{}, {:#010X}", index, arg);
+    }
+    if let Some(static_base) = layout.static_base {
+        let _ = writeln!(script, "wreg R9, {:#010X}", static_base);
+    }
+    let _ = writeln!(script, "wreg MSP, {:#010X}", layout.stack_top);
+    let _ = writeln!(script, "wreg R14, {:#010X}", layout.trampoline | 1);
+    let _ = writeln!(script, "wreg XPSR, 0x01000000");
+    let _ = writeln!(script, "SetPC {:#010X}", (layout.base + pc) & !1);
+    let _ = writeln!(script, "go\nsleep {}\nh", timeout);
+    let _ = writeln!(script, "// {} returned if PC is {:#010X}, and succeeded if R0 is 0", routine, layout.trampoline);
+}
+
+/// A J-Link Commander script that erases the scratch sector with `stub`, programs its first page
+/// and verifies it. The sector is overwritten: pick one nothing on the target needs.
+pub fn jlink_script(stub: &ValidatedArmFlashStub, options: &JLinkOptions) -> Result<JLinkScript, ExportError> {
+    let sector = stub.scratch_sector(options.scratch).ok_or(ExportError::ScratchOutOfRange(options.scratch.unwrap_or(stub.flash_start_addr)))?;
+    let sector = sector.start as u32..sector.end as u32;
+    let page_size = stub.flash_page_size.max(4).min(sector.end - sector.start);
+    let mut page = stub.test_pattern(page_size as usize);
+    page.resize((page.len() + 3) & !3, stub.erased_byte_value);
+
+    let mut image = stub.blob()?;
+    image.resize((image.len() + 3) & !3, 0);
+    let layout = RunLayout::plan(stub, options.ram_start, image.len() as u32, options.ram_size)?;
+
+    let mut script = String::new();
+    let _ = writeln!(script, "// {}, generated by soul-composer", stub.name);
+    let _ = writeln!(script, "// Run with JLinkExe -CommandFile <this file>, it loads {} and {}", options.image_file, options.page_file);
+    let _ = writeln!(script, "// Erases {:#010x}..{:#010x} and programs its first {} bytes", sector.start, sector.end, page.len());
+    match &options.device {
+        Some(device) => {
+            let _ = writeln!(script, "device {}", device);
+        }
+        None => script.push_str("// No device given, J-Link asks for it when connecting\n"),
+    }
+    let _ = writeln!(script, "si SWD\nspeed {}\nconnect\nr\nh", options.speed_khz);
+    let _ = writeln!(script, "loadbin {}, {:#010X}", options.image_file, layout.base);
+    let _ = writeln!(script, "w4 {:#010X}, {:#010X}", layout.trampoline, u32::from_le_bytes(TRAMPOLINE));
+
+    let init = &stub.init_parameters;
+    if let Some(pc) = stub.pc_init {
+        call(&mut script, &layout, "Init", pc, [init.address, init.clock, INIT_FUNCTION_ERASE], INIT_TIMEOUT_MS);
+    }
+    let erase_timeout = stub.erase_timeout.max(INIT_TIMEOUT_MS);
+    call(&mut script, &layout, "EraseSector", stub.pc_erase_sector, [stub.program_address(sector.start), 0, 0], erase_timeout);
+    if let Some(pc) = stub.pc_uninit {
+        call(&mut script, &layout, "UnInit", pc, [INIT_FUNCTION_ERASE, 0, 0], INIT_TIMEOUT_MS);
+    }
+
+    if let Some(pc) = stub.pc_init {
+        call(&mut script, &layout, "Init", pc, [init.address, init.clock, INIT_FUNCTION_PROGRAM], INIT_TIMEOUT_MS);
+    }
+    let _ = writeln!(script, "\nloadbin {}, {:#010X}", options.page_file, layout.buffer);
+    let args = [stub.program_address(sector.start), page.len() as u32, layout.buffer];
+    call(&mut script, &layout, "ProgramPage", stub.pc_program_page, args, stub.program_timeout.max(INIT_TIMEOUT_MS));
+    if let Some(pc) = stub.pc_uninit {
+        call(&mut script, &layout, "UnInit", pc, [INIT_FUNCTION_PROGRAM, 0, 0], INIT_TIMEOUT_MS);
+    }
+
+    let _ = writeln!(script, "\nverifybin {}, {:#010X}", options.page_file, sector.start);
+    let _ = writeln!(script, "mem32 {:#010X}, {}", sector.start, page.len().min(64) / 4);
+    script.push_str("qc\n");
+
+    Ok(JLinkScript { script, image, page, layout, sector })
+}
@@ -0,0 +1,217 @@
+use std::{fmt, io::Write, str::FromStr};
+
+use serde::Serialize;
+
+use super::{
+    arm::{flash_stub_gen::ArmFlashStub, validated::ValidatedArmFlashStub},
+    flash_algorithm::FlashAlgorithm,
+};
+
+use export_error::ExportError;
+use naming::{FieldNaming, Renamed};
+
+pub mod export_error;
+pub mod jlink;
+pub mod naming;
+pub mod probe_rs;
+pub mod source;
+
+/// A way of writing out a stub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The stub model as pretty printed JSON, what Soul Injector reads.
+    Json,
+    /// The stub model as YAML.
+    Yaml,
+    /// The stub model as CBOR.
+    Cbor,
+    /// The stub model as MessagePack.
+    Msgpack,
+    /// Only the image loaded into target RAM.
+    Bin,
+    /// A C header with the image as an array and the rest as defines.
+    CHeader,
+    /// Rust constants, for `include!` into firmware.
+    Rust,
+    /// A probe-rs `flash_algorithms` entry.
+    ProbeRsYaml,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 8] = [
+        OutputFormat::Json,
+        OutputFormat::Yaml,
+        OutputFormat::Cbor,
+        OutputFormat::Msgpack,
+        OutputFormat::Bin,
+        OutputFormat::CHeader,
+        OutputFormat::Rust,
+        OutputFormat::ProbeRsYaml,
+    ];
+
+    /// The kebab-case name used on the command line and in configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Bin => "bin",
+            OutputFormat::CHeader => "c-header",
+            OutputFormat::Rust => "rust",
+            OutputFormat::ProbeRsYaml => "probe-rs-yaml",
+        }
+    }
+
+    /// Whether the format writes the serde model as is, so it can hold any serializable value.
+    pub fn is_model(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Cbor | OutputFormat::Msgpack)
+    }
+
+    /// File extension for output in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml | OutputFormat::ProbeRsYaml => "yaml",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Bin => "bin",
+            OutputFormat::CHeader => "h",
+            OutputFormat::Rust => "rs",
+        }
+    }
+
+    /// MIME type for serving output in this format.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Yaml | OutputFormat::ProbeRsYaml => "application/yaml",
+            OutputFormat::Cbor => "application/cbor",
+            OutputFormat::Msgpack => "application/msgpack",
+            OutputFormat::Bin => "application/octet-stream",
+            OutputFormat::CHeader => "text/x-c",
+            OutputFormat::Rust => "text/x-rust",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ExportError;
+
+    fn from_str(name: &str) -> Result<Self, ExportError> {
+        OutputFormat::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| ExportError::UnknownFormat(name.to_string()))
+    }
+}
+
+fn serialize_error(format: OutputFormat) -> impl FnOnce(String) -> ExportError {
+    move |reason| ExportError::Serialize { format: format.name(), reason }
+}
+
+/// Runs `write` against a fresh buffer, for the variants that return the output.
+fn buffered(write: impl FnOnce(&mut Vec<u8>) -> Result<(), ExportError>) -> Result<Vec<u8>, ExportError> {
+    let mut buf = Vec::new();
+    write(&mut buf)?;
+    Ok(buf)
+}
+
+/// Writes any serializable value, e.g. an `ArmFlashStubGroup`, in one of the model formats.
+pub fn export_model<T: Serialize + ?Sized>(value: &T, format: OutputFormat) -> Result<Vec<u8>, ExportError> {
+    export_model_named(value, format, FieldNaming::Camel)
+}
+
+/// Same as `export_model`, with the struct fields named by `naming`.
+pub fn export_model_named<T: Serialize + ?Sized>(
+    value: &T,
+    format: OutputFormat,
+    naming: FieldNaming,
+) -> Result<Vec<u8>, ExportError> {
+    buffered(|buf| export_model_to(value, format, naming, buf))
+}
+
+/// Same as `export_model_named`, streaming into `writer`.
+pub fn export_model_to<T: Serialize + ?Sized>(
+    value: &T,
+    format: OutputFormat,
+    naming: FieldNaming,
+    writer: impl Write,
+) -> Result<(), ExportError> {
+    if !format.is_model() {
+        return Err(ExportError::SingleAlgorithmFormat(format.name()));
+    }
+
+    write_model(&Renamed::new(value, naming), format, writer)
+}
+
+#[allow(unused_mut)] // Only the backends behind features borrow the writer mutably.
+fn write_model<T: Serialize + ?Sized>(value: &T, format: OutputFormat, mut writer: impl Write) -> Result<(), ExportError> {
+    match format {
+        #[cfg(feature = "yaml")]
+        OutputFormat::Yaml | OutputFormat::ProbeRsYaml => {
+            serde_yaml::to_writer(writer, value).map_err(|err| serialize_error(format)(err.to_string()))
+        }
+        #[cfg(feature = "cbor")]
+        OutputFormat::Cbor => ciborium::into_writer(value, writer).map_err(|err| serialize_error(format)(err.to_string())),
+        #[cfg(feature = "msgpack")]
+        OutputFormat::Msgpack => {
+            rmp_serde::encode::write_named(&mut writer, value).map_err(|err| serialize_error(format)(err.to_string()))
+        }
+        OutputFormat::Json => serde_json::to_writer_pretty(writer, value).map_err(|err| serialize_error(format)(err.to_string())),
+        // The backend of the format is compiled out.
+        _ => Err(ExportError::FormatDisabled(format.name())),
+    }
+}
+
+/// Writes the probe-rs target description of a device family, in the YAML probe-rs reads.
+pub fn export_probe_rs_family(family: &probe_rs::ProbeRsChipFamily) -> Result<Vec<u8>, ExportError> {
+    buffered(|buf| write_model(family, OutputFormat::ProbeRsYaml, buf))
+}
+
+/// Writes `stub` in `format`.
+///
+/// The model formats (JSON, YAML, CBOR, MessagePack) round-trip through serde, the others are
+/// meant for consumers that don't read the stub model. Those feed hardware, so the stub is
+/// validated first, see `export_validated`.
+pub fn export(stub: &ArmFlashStub, format: OutputFormat) -> Result<Vec<u8>, ExportError> {
+    export_named(stub, format, FieldNaming::Camel)
+}
+
+/// Same as `export`, with the fields of the model formats named by `naming`. The other formats
+/// have fixed names and ignore it.
+pub fn export_named(stub: &ArmFlashStub, format: OutputFormat, naming: FieldNaming) -> Result<Vec<u8>, ExportError> {
+    buffered(|buf| export_to(stub, format, naming, buf))
+}
+
+/// Same as `export_named`, streaming into `writer`, e.g. a file, a socket or an archive entry.
+pub fn export_to(stub: &ArmFlashStub, format: OutputFormat, naming: FieldNaming, writer: impl Write) -> Result<(), ExportError> {
+    if format.is_model() {
+        return write_model(&Renamed::new(stub, naming), format, writer);
+    }
+
+    export_validated_to(&stub.clone().validate()?, format, writer)
+}
+
+/// Same as `export`, for a stub that is already validated.
+pub fn export_validated(stub: &ValidatedArmFlashStub, format: OutputFormat) -> Result<Vec<u8>, ExportError> {
+    buffered(|buf| export_validated_to(stub, format, buf))
+}
+
+/// Same as `export_validated`, streaming into `writer`.
+pub fn export_validated_to(stub: &ValidatedArmFlashStub, format: OutputFormat, mut writer: impl Write) -> Result<(), ExportError> {
+    match format {
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Cbor | OutputFormat::Msgpack => write_model(stub, format, writer),
+        OutputFormat::Bin => Ok(writer.write_all(&stub.blob()?)?),
+        OutputFormat::CHeader => Ok(writer.write_all(source::c_header(stub)?.as_bytes())?),
+        OutputFormat::Rust => Ok(writer.write_all(source::rust(stub)?.as_bytes())?),
+        OutputFormat::ProbeRsYaml => write_model(&[probe_rs::ProbeRsAlgorithm::from_stub(stub)?], format, writer),
+    }
+}
@@ -0,0 +1,356 @@
+//! Field naming of the model formats. The stub model is camelCase, some firmware and older JSON
+//! consumers want snake_case or kebab-case; `Renamed` rewrites the struct field names while the
+//! value is serialized, so no second pass over the output is needed.
+//!
+//! Only struct fields are renamed. Map keys are data, e.g. device names, and enum variants keep
+//! their names.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    str::FromStr,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct,
+    SerializeTupleVariant, Serializer,
+};
+
+use super::export_error::ExportError;
+
+/// How struct fields are named in the model formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldNaming {
+    /// `flashStartAddr`, the names of the model.
+    #[default]
+    Camel,
+    /// `flash_start_addr`
+    Snake,
+    /// `flash-start-addr`
+    Kebab,
+}
+
+impl FieldNaming {
+    pub const ALL: [FieldNaming; 3] = [FieldNaming::Camel, FieldNaming::Snake, FieldNaming::Kebab];
+
+    /// The name used on the command line and in configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldNaming::Camel => "camel-case",
+            FieldNaming::Snake => "snake-case",
+            FieldNaming::Kebab => "kebab-case",
+        }
+    }
+
+    /// `field`, a camelCase name, in this naming.
+    pub fn rename(self, field: &str) -> String {
+        let separator = match self {
+            FieldNaming::Camel => return field.to_string(),
+            FieldNaming::Snake => '_',
+            FieldNaming::Kebab => '-',
+        };
+
+        let mut renamed = String::with_capacity(field.len() + 4);
+        for c in field.chars() {
+            if c.is_ascii_uppercase() {
+                if !renamed.is_empty() {
+                    renamed.push(separator);
+                }
+                renamed.push(c.to_ascii_lowercase());
+            } else {
+                renamed.push(c);
+            }
+        }
+        renamed
+    }
+
+    /// Serializers take field names as `&'static str`. The model has a few dozen fields, each
+    /// renamed one is leaked once and reused.
+    fn rename_static(self, field: &'static str) -> &'static str {
+        if self == FieldNaming::Camel {
+            return field;
+        }
+
+        static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+        let renamed = self.rename(field);
+        let mut names = NAMES.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner);
+        match names.get(renamed.as_str()) {
+            Some(name) => name,
+            None => {
+                let name: &'static str = Box::leak(renamed.into_boxed_str());
+                names.insert(name);
+                name
+            }
+        }
+    }
+}
+
+impl fmt::Display for FieldNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FieldNaming {
+    type Err = ExportError;
+
+    fn from_str(name: &str) -> Result<Self, ExportError> {
+        FieldNaming::ALL
+            .iter()
+            .copied()
+            .find(|naming| naming.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| ExportError::UnknownNaming(name.to_string()))
+    }
+}
+
+/// Serializes `value` with its struct fields named by `naming`.
+pub struct Renamed<'a, T: ?Sized> {
+    pub value: &'a T,
+    pub naming: FieldNaming,
+}
+
+impl<'a, T: ?Sized> Renamed<'a, T> {
+    pub fn new(value: &'a T, naming: FieldNaming) -> Self {
+        Renamed { value, naming }
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for Renamed<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(RenamingSerializer { inner: serializer, naming: self.naming })
+    }
+}
+
+/// Forwards everything to `inner`, wrapping nested values so their fields are renamed too.
+struct RenamingSerializer<S> {
+    inner: S,
+    naming: FieldNaming,
+}
+
+/// The compound serializers of `inner`, with the same wrapping.
+struct Compound<C> {
+    inner: C,
+    naming: FieldNaming,
+}
+
+impl<C> Compound<C> {
+    fn wrap<'a, T: ?Sized>(&self, value: &'a T) -> Renamed<'a, T> {
+        Renamed::new(value, self.naming)
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(fn $method(self, $($arg: $ty),*) -> Result<S::Ok, S::Error> {
+            self.inner.$method($($arg),*)
+        })*
+    };
+}
+
+impl<S: Serializer> Serializer for RenamingSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&Renamed::new(value, self.naming))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, &Renamed::new(value, self.naming))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(name, index, variant, &Renamed::new(value, self.naming))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_seq(len)?, naming: self.naming })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_tuple(len)?, naming: self.naming })
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_tuple_struct(name, len)?, naming: self.naming })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_tuple_variant(name, index, variant, len)?, naming: self.naming })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_map(len)?, naming: self.naming })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_struct(name, len)?, naming: self.naming })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Ok(Compound { inner: self.inner.serialize_struct_variant(name, index, variant, len)?, naming: self.naming })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+impl<C: SerializeSeq> SerializeSeq for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTuple> SerializeTuple for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleStruct> SerializeTupleStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleVariant> SerializeTupleVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeMap> SerializeMap for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), C::Error> {
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStruct> SerializeStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(self.naming.rename_static(key), &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(self.naming.rename_static(key))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStructVariant> SerializeStructVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(self.naming.rename_static(key), &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(self.naming.rename_static(key))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
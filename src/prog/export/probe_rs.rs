@@ -0,0 +1,290 @@
+use serde::Serialize;
+
+use crate::{
+    pack::pdsc::{Memory, Pdsc, PdscDevice},
+    prog::{
+        arm::{arm_error::ArmError, core_isa::Core, validated::ValidatedArmFlashStub},
+        export::export_error::ExportError,
+        flash_algorithm::FlashAlgorithm,
+    },
+};
+
+/// A flash algorithm as listed under `flash_algorithms` in a probe-rs target description.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsAlgorithm {
+    pub name: String,
+    pub description: String,
+    pub default: bool,
+    /// Base64 of the whole RAM image, data included.
+    pub instructions: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pc_init: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pc_uninit: Option<u32>,
+    pub pc_program_page: u32,
+    pub pc_erase_sector: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pc_erase_all: Option<u32>,
+    pub data_section_offset: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_size: Option<u32>,
+    pub flash_properties: ProbeRsFlashProperties,
+    /// probe-rs core names the algorithm may run on, all cores if empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cores: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsFlashProperties {
+    pub address_range: ProbeRsRange,
+    pub page_size: u32,
+    pub erased_byte_value: u8,
+    pub program_page_timeout: u32,
+    pub erase_sector_timeout: u32,
+    pub sectors: Vec<ProbeRsSector>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProbeRsRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Where a run of equally sized sectors starts, relative to the flash start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProbeRsSector {
+    pub size: u32,
+    pub address: u32,
+}
+
+impl ProbeRsAlgorithm {
+    pub fn from_stub(stub: &ValidatedArmFlashStub) -> Result<ProbeRsAlgorithm, ArmError> {
+        let sectors = stub
+            .sectors
+            .iter()
+            .map(|region| ProbeRsSector { size: region.size, address: region.address.saturating_sub(stub.flash_start_addr) })
+            .collect();
+
+        Ok(ProbeRsAlgorithm {
+            name: stub.name.clone(),
+            description: stub.description.clone(),
+            default: stub.default,
+            instructions: base64::encode(stub.blob()?),
+            pc_init: stub.pc_init,
+            pc_uninit: stub.pc_uninit,
+            pc_program_page: stub.pc_program_page,
+            pc_erase_sector: stub.pc_erase_sector,
+            pc_erase_all: stub.pc_erase_all,
+            data_section_offset: stub.data_section_offset,
+            stack_size: Some(stub.stack_size).filter(|&size| size != 0),
+            flash_properties: ProbeRsFlashProperties {
+                address_range: ProbeRsRange { start: stub.flash_start_addr.into(), end: stub.flash_end_addr.into() },
+                page_size: stub.flash_page_size,
+                erased_byte_value: stub.erased_byte_value,
+                program_page_timeout: stub.program_timeout,
+                erase_sector_timeout: stub.erase_timeout,
+                sectors,
+            },
+            cores: stub.pinned_core.iter().filter_map(|core| core.processor.clone()).collect(),
+        })
+    }
+}
+
+/// A probe-rs target description of a whole device family, as kept under `probe-rs/targets`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsChipFamily {
+    pub name: String,
+    pub generated_from_pack: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack_file_release: Option<String>,
+    pub variants: Vec<ProbeRsChip>,
+    pub flash_algorithms: Vec<ProbeRsAlgorithm>,
+}
+
+/// A device of the family.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsChip {
+    pub name: String,
+    pub cores: Vec<ProbeRsCore>,
+    pub memory_map: Vec<ProbeRsMemoryRegion>,
+    /// Names of the family's algorithms the device uses.
+    pub flash_algorithms: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsCore {
+    pub name: String,
+    /// probe-rs core type, e.g. `armv7em`.
+    #[serde(rename = "type")]
+    pub core_type: String,
+    pub core_access_options: ProbeRsCoreAccess,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ProbeRsCoreAccess {
+    Arm { ap: ProbeRsAccessPort },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ProbeRsAccessPort {
+    /// Access port number of ADIv5.
+    #[serde(rename = "v1")]
+    V1(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ProbeRsMemoryRegion {
+    Ram(ProbeRsRegion),
+    Nvm(ProbeRsRegion),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeRsRegion {
+    pub name: String,
+    pub range: ProbeRsRange,
+    pub cores: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access: Option<ProbeRsMemoryAccess>,
+}
+
+/// Set on the region the device boots from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProbeRsMemoryAccess {
+    pub boot: bool,
+}
+
+/// probe-rs core type of a Cortex-M core.
+fn core_type(core: Core) -> &'static str {
+    match core {
+        Core::CortexM0 | Core::CortexM0Plus => "armv6m",
+        Core::CortexM3 => "armv7m",
+        Core::CortexM4 | Core::CortexM7 => "armv7em",
+        Core::CortexM33 => "armv8m",
+    }
+}
+
+/// Core names of `device`, `main` for a single core and the lowercased `Pname` otherwise. An
+/// unnamed processor inherited from the family gives way to the named ones of the device.
+fn cores(device: &PdscDevice) -> Result<Vec<(Option<&str>, ProbeRsCore)>, ExportError> {
+    let named = device.processors.iter().filter(|processor| processor.name.is_some()).count();
+    let processors = device.processors.iter().filter(|processor| named == 0 || processor.name.is_some());
+    let single = named <= 1;
+    let mut cores = Vec::new();
+    for processor in processors {
+        let name = processor.core.as_deref().unwrap_or_default();
+        let core = Core::from_name(name).ok_or_else(|| ExportError::UnsupportedCore(name.to_string()))?;
+        let core_name = match &processor.name {
+            Some(pname) if !single => pname.to_ascii_lowercase(),
+            _ => "main".to_string(),
+        };
+        let ap = ProbeRsAccessPort::V1(processor.ap.unwrap_or(0) as u8);
+        cores.push((
+            processor.name.as_deref(),
+            ProbeRsCore { name: core_name, core_type: core_type(core).to_string(), core_access_options: ProbeRsCoreAccess::Arm { ap } },
+        ));
+    }
+    if cores.is_empty() {
+        return Err(ExportError::UnsupportedCore(format!("no processor of {}", device.name)));
+    }
+    Ok(cores)
+}
+
+/// Flash is whatever can't be written by the core, or is named like it by packs using the legacy ids.
+fn is_flash(memory: &Memory) -> bool {
+    match &memory.access {
+        Some(access) => !access.contains('w'),
+        None => memory.name.starts_with("IROM"),
+    }
+}
+
+impl ProbeRsChip {
+    /// The device with its memories from the PDSC, using the algorithms named by `algorithm`,
+    /// which maps the FLM paths of the pack to names in the family.
+    pub fn from_pdsc(device: &PdscDevice, algorithm: impl Fn(&str) -> Option<String>) -> Result<ProbeRsChip, ExportError> {
+        let cores = cores(device)?;
+        let cores_of = |processor: &Option<String>| -> Vec<String> {
+            cores
+                .iter()
+                .filter(|(pname, _)| processor.is_none() || pname.is_some_and(|pname| Some(pname) == processor.as_deref()))
+                .map(|(_, core)| core.name.clone())
+                .collect()
+        };
+
+        let memory_map = device
+            .memories
+            .iter()
+            .map(|memory| {
+                let region = ProbeRsRegion {
+                    name: memory.name.clone(),
+                    range: ProbeRsRange { start: memory.start.into(), end: u64::from(memory.start) + u64::from(memory.size) },
+                    cores: cores_of(&memory.processor),
+                    access: Some(ProbeRsMemoryAccess { boot: true }).filter(|_| memory.startup),
+                };
+                if is_flash(memory) {
+                    ProbeRsMemoryRegion::Nvm(region)
+                } else {
+                    ProbeRsMemoryRegion::Ram(region)
+                }
+            })
+            .collect();
+
+        let mut flash_algorithms: Vec<String> = Vec::new();
+        for name in device.algorithms.iter().filter_map(|flm| algorithm(&flm.file)) {
+            if !flash_algorithms.contains(&name) {
+                flash_algorithms.push(name);
+            }
+        }
+
+        Ok(ProbeRsChip {
+            name: device.name.clone(),
+            cores: cores.into_iter().map(|(_, core)| core).collect(),
+            memory_map,
+            flash_algorithms,
+        })
+    }
+}
+
+impl ProbeRsChipFamily {
+    /// The devices of `family` in `pdsc` with `algorithms`, the converted FLMs of the pack by
+    /// their path in it. Devices keep the FLMs that converted, algorithms nobody uses are left out.
+    pub fn from_pdsc(pdsc: &Pdsc, family: &str, algorithms: &[(String, ValidatedArmFlashStub)]) -> Result<ProbeRsChipFamily, ExportError> {
+        let devices: Vec<&PdscDevice> =
+            pdsc.devices.iter().filter(|device| device.family.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(family))).collect();
+        let name = match devices.first() {
+            Some(device) => device.family.clone().unwrap_or_default(),
+            None => return Err(ExportError::UnknownFamily(family.to_string())),
+        };
+
+        // Stub names are FLM file names, which repeat in different directories of some packs.
+        let mut names: Vec<(&str, String)> = Vec::new();
+        for (file, stub) in algorithms {
+            let mut name = stub.name.clone();
+            let mut suffix = 2;
+            while names.iter().any(|(_, taken)| *taken == name) {
+                name = format!("{}_{}", stub.name, suffix);
+                suffix += 1;
+            }
+            names.push((file, name));
+        }
+        let name_of = |file: &str| names.iter().find(|(path, _)| *path == file).map(|(_, name)| name.clone());
+
+        let variants = devices.iter().map(|device| ProbeRsChip::from_pdsc(device, name_of)).collect::<Result<Vec<_>, _>>()?;
+        let mut flash_algorithms = Vec::new();
+        for ((file, stub), (_, name)) in algorithms.iter().zip(&names) {
+            if variants.iter().any(|chip| chip.flash_algorithms.contains(name)) {
+                let mut algorithm = ProbeRsAlgorithm::from_stub(stub)?;
+                algorithm.name = name.clone();
+                // Only multi-core devices name their cores after the processors, see `cores`.
+                let pinned = devices
+                    .iter()
+                    .filter(|device| device.processors.iter().filter(|processor| processor.name.is_some()).count() > 1)
+                    .flat_map(|device| device.algorithms.iter())
+                    .find_map(|flm| flm.processor.as_ref().filter(|_| flm.file == *file));
+                algorithm.cores = pinned.map(|pname| pname.to_ascii_lowercase()).into_iter().collect();
+                flash_algorithms.push(algorithm);
+            }
+        }
+
+        Ok(ProbeRsChipFamily { name, generated_from_pack: true, pack_file_release: pdsc.version.clone(), variants, flash_algorithms })
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::Write;
+
+use crate::prog::{
+    arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub, validated::ValidatedArmFlashStub},
+    flash_algorithm::FlashAlgorithm,
+};
+
+/// Bytes per line of the image arrays.
+const BYTES_PER_LINE: usize = 16;
+
+/// `name` made into an upper-case identifier, "STUB" if nothing usable is left.
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if ident.is_empty() {
+        ident.push_str("STUB");
+    }
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// The scalar properties of the stub, as (suffix, value) pairs.
+fn properties(stub: &ArmFlashStub) -> Vec<(&'static str, u32)> {
+    let mut properties = vec![
+        ("FLASH_START", stub.flash_start_addr),
+        ("FLASH_END", stub.flash_end_addr),
+        ("PAGE_SIZE", stub.flash_page_size),
+        ("ERASED_VALUE", stub.erased_byte_value.into()),
+        ("PROGRAM_TIMEOUT", stub.program_timeout),
+        ("ERASE_TIMEOUT", stub.erase_timeout),
+        ("DATA_SECTION_OFFSET", stub.data_section_offset),
+        ("STACK_SIZE", stub.stack_size),
+        ("RAM_REQUIRED", stub.ram_required),
+        ("PC_PROGRAM_PAGE", stub.pc_program_page),
+        ("PC_ERASE_SECTOR", stub.pc_erase_sector),
+    ];
+    let optional = [
+        ("PC_INIT", stub.pc_init),
+        ("PC_UNINIT", stub.pc_uninit),
+        ("PC_ERASE_ALL", stub.pc_erase_all),
+        ("STATIC_BASE", stub.static_base),
+    ];
+    properties.extend(optional.iter().filter_map(|(suffix, value)| value.map(|value| (*suffix, value))));
+    properties
+}
+
+fn byte_lines(blob: &[u8], indent: &str) -> String {
+    let mut lines = String::new();
+    for chunk in blob.chunks(BYTES_PER_LINE) {
+        let bytes: Vec<String> = chunk.iter().map(|byte| format!("0x{:02x},", byte)).collect();
+        let _ = writeln!(lines, "{}{}", indent, bytes.join(" "));
+    }
+    lines
+}
+
+/// A C header with the RAM image as `<NAME>_BLOB` and everything needed to call it as defines.
+pub fn c_header(stub: &ValidatedArmFlashStub) -> Result<String, ArmError> {
+    let blob = stub.blob()?;
+    let prefix = identifier(&stub.name);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "/* Generated by soul-composer from {}, do not edit. */", stub.name);
+    let _ = writeln!(out, "#ifndef {}_H\n#define {}_H\n\n#include <stdint.h>\n", prefix, prefix);
+    for (suffix, value) in properties(stub) {
+        let _ = writeln!(out, "#define {}_{} 0x{:08x}u", prefix, suffix, value);
+    }
+
+    let _ = writeln!(out, "\n/* address, sector size, sector count */");
+    let _ = writeln!(out, "static const uint32_t {}_SECTORS[{}][3] = {{", prefix, stub.sectors.len());
+    for region in &stub.sectors {
+        let _ = writeln!(out, "    {{ 0x{:08x}u, 0x{:08x}u, {}u }},", region.address, region.size, region.count);
+    }
+    let _ = writeln!(out, "}};\n");
+
+    let _ = writeln!(out, "static const uint8_t {}_BLOB[{}] = {{", prefix, blob.len());
+    out.push_str(&byte_lines(&blob, "    "));
+    let _ = writeln!(out, "}};\n\n#endif /* {}_H */", prefix);
+
+    Ok(out)
+}
+
+/// Rust constants for the stub, without a prefix so the file can be `include!`d into a module.
+pub fn rust(stub: &ValidatedArmFlashStub) -> Result<String, ArmError> {
+    let blob = stub.blob()?;
+    let mut out = String::new();
+
+    let _ = writeln!(out, "// Generated by soul-composer from {}, do not edit.\n", stub.name);
+    let _ = writeln!(out, "pub const NAME: &str = {:?};", stub.name);
+    for (suffix, value) in properties(stub) {
+        let _ = writeln!(out, "pub const {}: u32 = 0x{:08x};", suffix, value);
+    }
+
+    let _ = writeln!(out, "\n/// (address, sector size, sector count)");
+    let _ = writeln!(out, "pub const SECTORS: [(u32, u32, u32); {}] = [", stub.sectors.len());
+    for region in &stub.sectors {
+        let _ = writeln!(out, "    (0x{:08x}, 0x{:08x}, {}),", region.address, region.size, region.count);
+    }
+    let _ = writeln!(out, "];\n");
+
+    let _ = writeln!(out, "pub const BLOB: [u8; {}] = [", blob.len());
+    out.push_str(&byte_lines(&blob, "    "));
+    let _ = writeln!(out, "];");
+
+    Ok(out)
+}
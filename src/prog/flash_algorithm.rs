@@ -0,0 +1,237 @@
+use std::io::Read;
+
+use goblin::elf::{
+    header::{EM_AARCH64, EM_ARM, EM_RISCV, EM_XTENSA},
+    Elf,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    aarch64::{aarch64_error::Aarch64Error, flash_stub_gen::Aarch64FlashStub},
+    arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub},
+    prog_error::ProgError,
+    riscv::{flash_stub_gen::RiscvFlashStub, riscv_error::RiscvError},
+    xtensa::{flash_stub_gen::XtensaFlashStub, xtensa_error::XtensaError},
+};
+
+/// Entry points of a loader as offsets from the start of its blob.
+///
+/// Mode bits such as the Thumb bit are stripped, these are locations, not branch targets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryPoints {
+    pub init: Option<u64>,
+    pub uninit: Option<u64>,
+    pub program_page: Option<u64>,
+    pub erase_sector: Option<u64>,
+    pub erase_all: Option<u64>,
+}
+
+impl EntryPoints {
+    /// All entry points that are present, with their CMSIS names.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> {
+        vec![
+            ("Init", self.init),
+            ("UnInit", self.uninit),
+            ("ProgramPage", self.program_page),
+            ("EraseSector", self.erase_sector),
+            ("EraseChip", self.erase_all),
+        ]
+        .into_iter()
+        .filter_map(|(name, offset)| offset.map(|offset| (name, offset)))
+    }
+}
+
+/// A flash loader of one architecture.
+///
+/// Each architecture parses its own ELF flavour into its own stub model; code that only needs
+/// entry points and the RAM image (exporters, validators) works on this trait instead.
+pub trait FlashAlgorithm: Sized {
+    type Error: std::error::Error + 'static;
+
+    /// The ELF `e_machine` this implementation handles.
+    const MACHINE: u16;
+
+    /// Parse a loader ELF and compose the stub.
+    ///
+    /// `ram_size` is the RAM available on the target; pass 0 to fill it in with the computed requirement.
+    fn compose_stub(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<Self, Self::Error>;
+
+    /// The resolved entry points.
+    fn entry_points(&self) -> EntryPoints;
+
+    /// The image loaded into target RAM, starting at offset 0 of the entry points.
+    fn blob(&self) -> Result<Vec<u8>, Self::Error>;
+}
+
+impl FlashAlgorithm for ArmFlashStub {
+    type Error = ArmError;
+    const MACHINE: u16 = EM_ARM;
+
+    fn compose_stub(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<Self, ArmError> {
+        ArmFlashStub::from_elf(buf, name, default, ram_size)
+    }
+
+    fn entry_points(&self) -> EntryPoints {
+        let offset = |pc: u32| u64::from(pc & !1);
+        EntryPoints {
+            init: self.pc_init.map(offset),
+            uninit: self.pc_uninit.map(offset),
+            program_page: Some(offset(self.pc_program_page)),
+            erase_sector: Some(offset(self.pc_erase_sector)),
+            erase_all: self.pc_erase_all.map(offset),
+        }
+    }
+
+    fn blob(&self) -> Result<Vec<u8>, ArmError> {
+        if let Some(encryption) = &self.encryption {
+            return Err(ArmError::StubEncrypted(encryption.key_id.clone()));
+        }
+        let decode = |blob: &str| base64::decode(blob).map_err(|err| ArmError::InstructionDecode(err.to_string()));
+        let mut blob = decode(&self.instructions)?;
+        if let Some(data) = &self.data_instructions {
+            blob.resize(self.data_section_offset as usize, 0);
+            blob.extend(decode(data)?);
+        }
+        Ok(blob)
+    }
+}
+
+impl FlashAlgorithm for RiscvFlashStub {
+    type Error = RiscvError;
+    const MACHINE: u16 = EM_RISCV;
+
+    fn compose_stub(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<Self, RiscvError> {
+        RiscvFlashStub::from_elf(buf, name, default, ram_size)
+    }
+
+    fn entry_points(&self) -> EntryPoints {
+        EntryPoints {
+            init: self.pc_init.map(u64::from),
+            uninit: self.pc_uninit.map(u64::from),
+            program_page: Some(self.pc_program_page.into()),
+            erase_sector: Some(self.pc_erase_sector.into()),
+            erase_all: self.pc_erase_all.map(u64::from),
+        }
+    }
+
+    fn blob(&self) -> Result<Vec<u8>, RiscvError> {
+        base64::decode(&self.instructions).map_err(|err| RiscvError::InstructionDecode(err.to_string()))
+    }
+}
+
+impl FlashAlgorithm for Aarch64FlashStub {
+    type Error = Aarch64Error;
+    const MACHINE: u16 = EM_AARCH64;
+
+    fn compose_stub(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<Self, Aarch64Error> {
+        Aarch64FlashStub::from_elf(buf, name, default, ram_size)
+    }
+
+    fn entry_points(&self) -> EntryPoints {
+        EntryPoints {
+            init: self.pc_init.map(u64::from),
+            uninit: self.pc_uninit.map(u64::from),
+            program_page: Some(self.pc_program_page.into()),
+            erase_sector: Some(self.pc_erase_sector.into()),
+            erase_all: self.pc_erase_all.map(u64::from),
+        }
+    }
+
+    fn blob(&self) -> Result<Vec<u8>, Aarch64Error> {
+        base64::decode(&self.instructions).map_err(|err| Aarch64Error::InstructionDecode(err.to_string()))
+    }
+}
+
+impl FlashAlgorithm for XtensaFlashStub {
+    type Error = XtensaError;
+    const MACHINE: u16 = EM_XTENSA;
+
+    /// Xtensa loaders are linked to fixed addresses and clear their own bss, `default` and
+    /// `ram_size` don't apply.
+    fn compose_stub(buf: &[u8], name: String, _default: bool, _ram_size: u32) -> Result<Self, XtensaError> {
+        XtensaFlashStub::from_elf(buf, name)
+    }
+
+    /// Offsets are relative to `text_start`.
+    fn entry_points(&self) -> EntryPoints {
+        let offset = |address: u32| u64::from(address.wrapping_sub(self.text_start));
+        EntryPoints {
+            init: self.pc_init.map(offset),
+            uninit: self.pc_uninit.map(offset),
+            program_page: self.pc_program_page.map(offset),
+            erase_sector: self.pc_erase_sector.map(offset),
+            erase_all: self.pc_erase_all.map(offset),
+        }
+    }
+
+    /// Only the text segment, the data segment is loaded at `data_start` on its own.
+    fn blob(&self) -> Result<Vec<u8>, XtensaError> {
+        base64::decode(&self.text).map_err(|err| XtensaError::SegmentDecode { segment: "text", reason: err.to_string() })
+    }
+}
+
+/// Checks that every entry point of `algorithm` lands inside its blob.
+pub fn validate_entry_points<A>(algorithm: &A) -> Result<(), ProgError>
+where
+    A: FlashAlgorithm,
+    ProgError: From<A::Error>,
+{
+    let blob = algorithm.blob()?;
+    for (name, offset) in algorithm.entry_points().iter() {
+        if offset >= blob.len() as u64 {
+            return Err(ProgError::EntryPointOutOfRange { name, offset, size: blob.len() });
+        }
+    }
+
+    Ok(())
+}
+
+/// A stub of any supported architecture, tagged with `arch` when serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "arch", rename_all = "camelCase")]
+pub enum AnyFlashStub {
+    Arm(ArmFlashStub),
+    Aarch64(Aarch64FlashStub),
+    Riscv(RiscvFlashStub),
+    Xtensa(XtensaFlashStub),
+}
+
+impl AnyFlashStub {
+    /// Picks the implementation from the ELF machine and composes the stub with it.
+    pub fn from_elf(buf: impl AsRef<[u8]>, name: String, default: bool, ram_size: u32) -> Result<AnyFlashStub, ProgError> {
+        let buf = buf.as_ref();
+        let machine = Elf::parse_header(buf).map_err(|_| ProgError::ElfParse)?.e_machine;
+        Ok(match machine {
+            ArmFlashStub::MACHINE => AnyFlashStub::Arm(ArmFlashStub::compose_stub(buf, name, default, ram_size)?),
+            Aarch64FlashStub::MACHINE => AnyFlashStub::Aarch64(Aarch64FlashStub::compose_stub(buf, name, default, ram_size)?),
+            RiscvFlashStub::MACHINE => AnyFlashStub::Riscv(RiscvFlashStub::compose_stub(buf, name, default, ram_size)?),
+            XtensaFlashStub::MACHINE => AnyFlashStub::Xtensa(XtensaFlashStub::compose_stub(buf, name, default, ram_size)?),
+            machine => return Err(ProgError::UnsupportedMachine(machine)),
+        })
+    }
+
+    /// Same as `from_elf`, reading the ELF from `reader`.
+    pub fn from_reader(mut reader: impl Read, name: String, default: bool, ram_size: u32) -> Result<AnyFlashStub, ProgError> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Self::from_elf(buf, name, default, ram_size)
+    }
+
+    pub fn entry_points(&self) -> EntryPoints {
+        match self {
+            AnyFlashStub::Arm(stub) => stub.entry_points(),
+            AnyFlashStub::Aarch64(stub) => stub.entry_points(),
+            AnyFlashStub::Riscv(stub) => stub.entry_points(),
+            AnyFlashStub::Xtensa(stub) => stub.entry_points(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ProgError> {
+        match self {
+            AnyFlashStub::Arm(stub) => validate_entry_points(stub),
+            AnyFlashStub::Aarch64(stub) => validate_entry_points(stub),
+            AnyFlashStub::Riscv(stub) => validate_entry_points(stub),
+            AnyFlashStub::Xtensa(stub) => validate_entry_points(stub),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::{collections::HashSet, fmt, io::Read};
+
+use serde::{Deserialize, Serialize};
+
+use super::generic_error::GenericError;
+
+/// Architecture of a raw loader blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Architecture {
+    Arm,
+    Aarch64,
+    Riscv,
+    Xtensa,
+}
+
+impl Architecture {
+    fn is_register(self, name: &str) -> bool {
+        let number = |prefix: &str, count: u32| {
+            name.strip_prefix(prefix)
+                .and_then(|number| number.parse::<u32>().ok())
+                .is_some_and(|number| number < count)
+        };
+
+        match self {
+            Architecture::Arm => number("r", 13) || matches!(name, "sp" | "lr" | "ip" | "sb" | "fp"),
+            Architecture::Aarch64 => number("x", 31) || number("w", 31) || matches!(name, "sp" | "lr" | "fp"),
+            Architecture::Riscv => {
+                number("x", 32)
+                    || number("a", 8)
+                    || number("s", 12)
+                    || number("t", 7)
+                    || matches!(name, "zero" | "ra" | "sp" | "gp" | "tp" | "fp")
+            }
+            Architecture::Xtensa => number("a", 16),
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Architecture::Arm => "arm",
+            Architecture::Aarch64 => "aarch64",
+            Architecture::Riscv => "riscv",
+            Architecture::Xtensa => "xtensa",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How arguments reach an entry point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CallingConvention {
+    /// AAPCS / AAPCS64, arguments in r0-r3 or x0-x7.
+    Aapcs,
+    /// RISC-V psABI, arguments in a0-a7.
+    RiscvAbi,
+    /// Xtensa call0, arguments in a2-a7.
+    XtensaCall0,
+    /// Xtensa windowed as seen by a `callx8` caller, arguments in a10-a15.
+    XtensaWindowed,
+    /// No standard convention, every entry point names its registers.
+    Custom,
+}
+
+impl CallingConvention {
+    fn registers(self, architecture: Architecture) -> &'static [&'static str] {
+        match (self, architecture) {
+            (CallingConvention::Aapcs, Architecture::Aarch64) => &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"],
+            (CallingConvention::Aapcs, _) => &["r0", "r1", "r2", "r3"],
+            (CallingConvention::RiscvAbi, _) => &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+            (CallingConvention::XtensaCall0, _) => &["a2", "a3", "a4", "a5", "a6", "a7"],
+            (CallingConvention::XtensaWindowed, _) => &["a10", "a11", "a12", "a13", "a14", "a15"],
+            (CallingConvention::Custom, _) => &[],
+        }
+    }
+}
+
+impl fmt::Display for CallingConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CallingConvention::Aapcs => "aapcs",
+            CallingConvention::RiscvAbi => "riscvAbi",
+            CallingConvention::XtensaCall0 => "xtensaCall0",
+            CallingConvention::XtensaWindowed => "xtensaWindowed",
+            CallingConvention::Custom => "custom",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What the flasher puts in an argument register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArgumentValue {
+    /// Target address of the operation.
+    Address,
+    /// Length of the operation in bytes.
+    Size,
+    /// RAM address of the data buffer.
+    Buffer,
+    /// Core clock in Hz.
+    Clock,
+    /// Operation code, like the CMSIS Init function argument.
+    Function,
+    /// A fixed value.
+    Constant(u64),
+}
+
+/// One entry point as written in a descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryDescriptor {
+    pub name: String,
+    /// Offset into the blob.
+    pub offset: u64,
+    /// Enter in Thumb state, 32-bit ARM only.
+    #[serde(default)]
+    pub thumb: bool,
+    /// Values passed, in argument order.
+    #[serde(default)]
+    pub arguments: Vec<ArgumentValue>,
+    /// Registers for `arguments`, taken from the calling convention when left out.
+    #[serde(default)]
+    pub registers: Vec<String>,
+    /// Register holding the result, the first argument register when left out.
+    #[serde(default)]
+    pub return_register: Option<String>,
+    /// Result meaning success.
+    #[serde(default)]
+    pub success_value: u64,
+}
+
+/// Flash the loader programs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlashGeometry {
+    pub start: u64,
+    pub size: u64,
+    pub page_size: u32,
+    pub sector_size: u32,
+    #[serde(default = "default_erased_value")]
+    pub erased_value: u8,
+}
+
+fn default_erased_value() -> u8 {
+    0xFF
+}
+
+/// Vendor-neutral description of a loader blob that doesn't follow CMSIS.
+///
+/// Written as TOML or JSON, e.g.
+///
+/// ```toml
+/// name = "Vendor boot ROM helper"
+/// architecture = "arm"
+/// callingConvention = "aapcs"
+/// blobPath = "helper.bin"
+///
+/// [flash]
+/// start = 0x08000000
+/// size = 0x80000
+/// pageSize = 256
+/// sectorSize = 4096
+///
+/// [[entries]]
+/// name = "program"
+/// offset = 0x40
+/// thumb = true
+/// arguments = ["address", "size", "buffer"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoaderDescriptor {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub architecture: Architecture,
+    pub calling_convention: CallingConvention,
+    /// Base64 of the blob, when it is embedded.
+    #[serde(default)]
+    pub blob: Option<String>,
+    /// Path of the blob relative to the descriptor, for tools that load it.
+    #[serde(default)]
+    pub blob_path: Option<String>,
+    /// Address the blob must be loaded at, `None` if it is position independent.
+    #[serde(default)]
+    pub load_address: Option<u64>,
+    #[serde(default)]
+    pub stack_size: u32,
+    pub flash: FlashGeometry,
+    pub entries: Vec<EntryDescriptor>,
+}
+
+/// An argument bound to its register.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Argument {
+    pub register: String,
+    pub value: ArgumentValue,
+}
+
+/// An entry point with its registers resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericEntryPoint {
+    pub name: String,
+    pub offset: u64,
+    pub thumb: bool,
+    pub arguments: Vec<Argument>,
+    pub return_register: String,
+    pub success_value: u64,
+}
+
+/// A loader described by a `LoaderDescriptor`, ready to be executed by a flasher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericFlashStub {
+    pub name: String,
+    pub description: String,
+    pub architecture: Architecture,
+    pub instructions: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_address: Option<u64>,
+    pub stack_size: u32,
+    pub flash: FlashGeometry,
+    pub entry_points: Vec<GenericEntryPoint>,
+}
+
+impl LoaderDescriptor {
+    pub fn from_json(json: &str) -> Result<LoaderDescriptor, GenericError> {
+        serde_json::from_str(json).map_err(|err| GenericError::Descriptor(err.to_string()))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<LoaderDescriptor, GenericError> {
+        toml::from_str(toml).map_err(|err| GenericError::Descriptor(err.to_string()))
+    }
+
+    /// Same as `from_json`, streaming from `reader`.
+    pub fn from_json_reader(reader: impl Read) -> Result<LoaderDescriptor, GenericError> {
+        serde_json::from_reader(reader).map_err(|err| GenericError::Descriptor(err.to_string()))
+    }
+
+    /// Composes the stub with the embedded blob.
+    pub fn compose(&self) -> Result<GenericFlashStub, GenericError> {
+        let blob = self.blob.as_ref().ok_or(GenericError::BlobMissing)?;
+        let blob = base64::decode(blob).map_err(|err| GenericError::BlobDecode(err.to_string()))?;
+        self.compose_with_blob(&blob)
+    }
+
+    /// Composes the stub with a blob loaded by the caller, e.g. from `blob_path`.
+    pub fn compose_with_blob(&self, blob: &[u8]) -> Result<GenericFlashStub, GenericError> {
+        let mut names = HashSet::new();
+        let mut entry_points = Vec::new();
+        for entry in &self.entries {
+            if !names.insert(entry.name.as_str()) {
+                return Err(GenericError::DuplicateEntryPoint(entry.name.clone()));
+            }
+            entry_points.push(self.resolve_entry(entry, blob.len())?);
+        }
+
+        Ok(GenericFlashStub {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            architecture: self.architecture,
+            instructions: base64::encode(blob),
+            load_address: self.load_address,
+            stack_size: self.stack_size,
+            flash: self.flash.clone(),
+            entry_points,
+        })
+    }
+
+    fn resolve_entry(&self, entry: &EntryDescriptor, blob_size: usize) -> Result<GenericEntryPoint, GenericError> {
+        if entry.offset >= blob_size as u64 {
+            return Err(GenericError::EntryPointOutOfRange { name: entry.name.clone(), offset: entry.offset, size: blob_size });
+        }
+        if entry.thumb && self.architecture != Architecture::Arm {
+            return Err(GenericError::ThumbNotSupported(self.architecture.to_string()));
+        }
+
+        let registers: Vec<String> = if entry.registers.is_empty() {
+            let available = self.calling_convention.registers(self.architecture);
+            if available.is_empty() && !entry.arguments.is_empty() {
+                return Err(GenericError::RegistersRequired(entry.name.clone()));
+            }
+            if entry.arguments.len() > available.len() {
+                return Err(GenericError::TooManyArguments {
+                    entry: entry.name.clone(),
+                    count: entry.arguments.len(),
+                    max: available.len(),
+                    convention: self.calling_convention.to_string(),
+                });
+            }
+            available.iter().map(|register| register.to_string()).collect()
+        } else {
+            if entry.registers.len() < entry.arguments.len() {
+                return Err(GenericError::TooManyArguments {
+                    entry: entry.name.clone(),
+                    count: entry.arguments.len(),
+                    max: entry.registers.len(),
+                    convention: self.calling_convention.to_string(),
+                });
+            }
+            entry.registers.clone()
+        };
+
+        let return_register = match &entry.return_register {
+            Some(register) => register.clone(),
+            None => registers.first().cloned().ok_or_else(|| GenericError::RegistersRequired(entry.name.clone()))?,
+        };
+
+        for register in registers.iter().take(entry.arguments.len()).chain(Some(&return_register)) {
+            if !self.architecture.is_register(register) {
+                return Err(GenericError::UnknownRegister {
+                    entry: entry.name.clone(),
+                    register: register.clone(),
+                    architecture: self.architecture.to_string(),
+                });
+            }
+        }
+
+        Ok(GenericEntryPoint {
+            name: entry.name.clone(),
+            offset: entry.offset,
+            thumb: entry.thumb,
+            arguments: registers
+                .into_iter()
+                .zip(entry.arguments.iter())
+                .map(|(register, &value)| Argument { register, value })
+                .collect(),
+            return_register,
+            success_value: entry.success_value,
+        })
+    }
+}
+
+impl GenericFlashStub {
+    /// The entry point called `name`.
+    pub fn entry_point(&self, name: &str) -> Option<&GenericEntryPoint> {
+        self.entry_points.iter().find(|entry| entry.name == name)
+    }
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GenericError {
+    #[error("Invalid loader descriptor: {0}")]
+    Descriptor(String),
+
+    #[error("Loader descriptor has no blob, embed one or pass it in")]
+    BlobMissing,
+
+    #[error("Invalid base64 blob: {0}")]
+    BlobDecode(String),
+
+    #[error("Entry point {name} at offset {offset:#x} lies outside the {size} byte blob")]
+    EntryPointOutOfRange { name: String, offset: u64, size: usize },
+
+    #[error("Entry point {0} is defined twice")]
+    DuplicateEntryPoint(String),
+
+    #[error("Register {register} of entry point {entry} does not exist on {architecture}")]
+    UnknownRegister { entry: String, register: String, architecture: String },
+
+    #[error("Entry point {entry} takes {count} arguments, the {convention} convention passes at most {max} in registers")]
+    TooManyArguments { entry: String, count: usize, max: usize, convention: String },
+
+    #[error("Entry point {0} needs explicit registers with the custom calling convention")]
+    RegistersRequired(String),
+
+    #[error("The Thumb bit only applies to 32-bit ARM, not {0}")]
+    ThumbNotSupported(String),
+}
@@ -0,0 +1,2 @@
+pub mod descriptor;
+pub mod generic_error;
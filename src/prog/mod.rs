@@ -0,0 +1,10 @@
+pub mod aarch64;
+pub mod arm;
+pub mod export;
+pub mod flash_algorithm;
+pub mod generic;
+pub mod prog_error;
+pub mod riscv;
+pub mod segments;
+pub mod spi_nor;
+pub mod xtensa;
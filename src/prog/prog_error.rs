@@ -0,0 +1,35 @@
+use std::io;
+
+use thiserror::Error;
+
+use super::{
+    aarch64::aarch64_error::Aarch64Error, arm::arm_error::ArmError, riscv::riscv_error::RiscvError,
+    xtensa::xtensa_error::XtensaError,
+};
+
+#[derive(Debug, Error)]
+pub enum ProgError {
+    #[error("Failed to parse ELF file")]
+    ElfParse,
+
+    #[error("No flash algorithm support for ELF machine {0}")]
+    UnsupportedMachine(u16),
+
+    #[error("Entry point {name} at offset {offset:#x} lies outside the {size} byte blob")]
+    EntryPointOutOfRange { name: &'static str, offset: u64, size: usize },
+
+    #[error("Failed to read the algorithm, {0}")]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Arm(#[from] ArmError),
+
+    #[error(transparent)]
+    Aarch64(#[from] Aarch64Error),
+
+    #[error(transparent)]
+    Riscv(#[from] RiscvError),
+
+    #[error(transparent)]
+    Xtensa(#[from] XtensaError),
+}
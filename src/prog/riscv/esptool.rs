@@ -0,0 +1,49 @@
+use goblin::elf::{header::EM_RISCV, Elf};
+
+use crate::prog::{segments::load_image, xtensa::esptool::EsptoolStub};
+
+use super::riscv_error::RiscvError;
+
+impl EsptoolStub {
+    /// Builds an esptool/espflash stub from an ESP32-C3/C6 loader ELF.
+    ///
+    /// These loaders are linked to the chip's IRAM and DRAM addresses like the Xtensa ones,
+    /// they don't carry a FlashDevice descriptor and don't go through `RiscvFlashStub`.
+    pub fn from_riscv_elf(buf: &[u8]) -> Result<EsptoolStub, RiscvError> {
+        let elf = Elf::parse(buf).map_err(|_| RiscvError::ElfParse)?;
+        if elf.header.e_machine != EM_RISCV {
+            return Err(RiscvError::WrongMachine(elf.header.e_machine));
+        }
+        if elf.is_64 {
+            return Err(RiscvError::UnsupportedXlen(64));
+        }
+
+        let image = load_image(&elf, buf)?;
+        let entry = match elf.header.e_entry as u32 {
+            0 => elf
+                .syms
+                .iter()
+                .find(|sym| &elf.strtab[sym.st_name] == "stub_main")
+                .map(|sym| sym.st_value as u32)
+                .ok_or_else(|| RiscvError::EntryPointNotFound("stub_main".to_string()))?,
+            entry => entry,
+        };
+        if !image.text.contains(entry) {
+            return Err(RiscvError::EntryPointOutOfRange { name: "entry".to_string(), address: entry.into() });
+        }
+
+        let (data, data_start) = match &image.data {
+            Some(data) => (base64::encode(&data.data), data.start),
+            None => (String::new(), 0),
+        };
+
+        Ok(EsptoolStub {
+            entry,
+            text: base64::encode(&image.text.data),
+            text_start: image.text.start,
+            data,
+            data_start,
+            bss_start: image.bss_start,
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeSet, fmt};
+
+use goblin::elf::Elf;
+use serde::{Deserialize, Serialize};
+
+use crate::prog::arm::build_attributes::{file_attributes, read_uleb128};
+
+use super::{flash_stub_gen::RiscvFlashStub, riscv_error::RiscvError};
+
+/// Name of the section holding the RISC-V psABI attributes.
+const ATTRIBUTES_SECTION: &str = ".riscv.attributes";
+const RISCV_VENDOR: &[u8] = b"riscv";
+/// `Tag_RISCV_arch`, the `-march` string the file was built with.
+const TAG_RISCV_ARCH: u64 = 5;
+/// `e_flags` bit set when the file contains compressed instructions.
+const EF_RISCV_RVC: u32 = 0x1;
+
+/// ISA extensions a loader can depend on, beyond the base integer ISA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiscvExtension {
+    M,
+    A,
+    F,
+    D,
+    Q,
+    C,
+    V,
+    Zicsr,
+    Zifencei,
+}
+
+impl RiscvExtension {
+    pub fn name(self) -> &'static str {
+        match self {
+            RiscvExtension::M => "m",
+            RiscvExtension::A => "a",
+            RiscvExtension::F => "f",
+            RiscvExtension::D => "d",
+            RiscvExtension::Q => "q",
+            RiscvExtension::C => "c",
+            RiscvExtension::V => "v",
+            RiscvExtension::Zicsr => "zicsr",
+            RiscvExtension::Zifencei => "zifencei",
+        }
+    }
+}
+
+impl fmt::Display for RiscvExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Strips a `<major>p<minor>` version suffix.
+fn strip_version(name: &str) -> &str {
+    name.trim_end_matches(|c: char| c.is_ascii_digit() || c == 'p')
+}
+
+/// Extensions named in an ISA string such as `rv32imac_zicsr` or `rv32i2p1_m2p0_c2p0`.
+///
+/// `g` expands to `imafd_zicsr_zifencei`, unknown extensions are ignored.
+pub fn parse_isa_string(isa: &str) -> Result<BTreeSet<RiscvExtension>, RiscvError> {
+    let lower = isa.trim().to_ascii_lowercase();
+    let rest = lower
+        .strip_prefix("rv32")
+        .or_else(|| lower.strip_prefix("rv64"))
+        .ok_or_else(|| RiscvError::InvalidIsaString(isa.to_string()))?;
+
+    let mut extensions = BTreeSet::new();
+    let mut parts = rest.split('_');
+    let mut single = parts.next().unwrap_or("").chars().peekable();
+    while let Some(letter) = single.next() {
+        // Skip the version, the 'p' only counts as a separator when a digit follows.
+        while single.peek().is_some_and(char::is_ascii_digit) {
+            single.next();
+            let mut lookahead = single.clone();
+            if lookahead.next() == Some('p') && lookahead.peek().is_some_and(char::is_ascii_digit) {
+                single.next();
+            }
+        }
+
+        match letter {
+            'm' => extensions.insert(RiscvExtension::M),
+            'a' => extensions.insert(RiscvExtension::A),
+            'f' => extensions.insert(RiscvExtension::F),
+            'd' => extensions.insert(RiscvExtension::D),
+            'q' => extensions.insert(RiscvExtension::Q),
+            'c' => extensions.insert(RiscvExtension::C),
+            'v' => extensions.insert(RiscvExtension::V),
+            'g' => {
+                extensions.extend(vec![
+                    RiscvExtension::M,
+                    RiscvExtension::A,
+                    RiscvExtension::F,
+                    RiscvExtension::D,
+                    RiscvExtension::Zicsr,
+                    RiscvExtension::Zifencei,
+                ]);
+                true
+            }
+            _ => false,
+        };
+    }
+
+    for part in parts {
+        match strip_version(part) {
+            "m" => extensions.insert(RiscvExtension::M),
+            "a" => extensions.insert(RiscvExtension::A),
+            "f" => extensions.insert(RiscvExtension::F),
+            "d" => extensions.insert(RiscvExtension::D),
+            "q" => extensions.insert(RiscvExtension::Q),
+            "c" => extensions.insert(RiscvExtension::C),
+            "v" => extensions.insert(RiscvExtension::V),
+            "zicsr" => extensions.insert(RiscvExtension::Zicsr),
+            "zifencei" => extensions.insert(RiscvExtension::Zifencei),
+            _ => false,
+        };
+    }
+
+    Ok(extensions)
+}
+
+/// Extensions used by the instructions in `code`, found by a linear sweep.
+pub fn scan_code(code: &[u8]) -> BTreeSet<RiscvExtension> {
+    let mut extensions = BTreeSet::new();
+    let mut offset = 0;
+    while let Some(bytes) = code.get(offset..offset + 2) {
+        let low = u16::from_le_bytes([bytes[0], bytes[1]]);
+
+        // Zero halfwords are padding (and c.unimp), not evidence of C.
+        if low == 0 {
+            offset += 2;
+            continue;
+        }
+
+        if low & 0x3 != 0x3 {
+            extensions.insert(RiscvExtension::C);
+            offset += 2;
+            continue;
+        }
+
+        let instruction = match code.get(offset..offset + 4) {
+            Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            None => break,
+        };
+        offset += 4;
+
+        let funct3 = (instruction >> 12) & 0x7;
+        let funct7 = instruction >> 25;
+        // LOAD-FP and STORE-FP widths 2, 3 and 4 are the scalar FLW, FLD and FLQ, the others
+        // vector loads and stores.
+        let load_store = |width: u32| match width {
+            2 => RiscvExtension::F,
+            3 => RiscvExtension::D,
+            4 => RiscvExtension::Q,
+            _ => RiscvExtension::V,
+        };
+        // The fmt field of OP-FP and the fused multiply-adds: S, D, H or Q. H is Zfh, not tracked.
+        let precision = |fmt: u32| match fmt {
+            0 => Some(RiscvExtension::F),
+            1 => Some(RiscvExtension::D),
+            3 => Some(RiscvExtension::Q),
+            _ => None,
+        };
+        let extension = match instruction & 0x7F {
+            // OP and OP-32 with the MULDIV funct7.
+            0x33 | 0x3B if funct7 == 1 => Some(RiscvExtension::M),
+            0x2F => Some(RiscvExtension::A),
+            0x73 if funct3 != 0 && funct3 != 4 => Some(RiscvExtension::Zicsr),
+            0x0F if funct3 == 1 => Some(RiscvExtension::Zifencei),
+            0x07 | 0x27 => Some(load_store(funct3)),
+            0x53 | 0x43 | 0x47 | 0x4B | 0x4F => precision((instruction >> 25) & 0x3),
+            // OP-V
+            0x57 => Some(RiscvExtension::V),
+            _ => None,
+        };
+        extensions.extend(extension);
+    }
+
+    extensions
+}
+
+/// The `Tag_RISCV_arch` string of an ELF, if it has one.
+fn arch_attribute(elf: &Elf<'_>, buffer: &[u8]) -> Option<String> {
+    let section = elf
+        .section_headers
+        .iter()
+        .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(ATTRIBUTES_SECTION))?;
+    let data = buffer.get(section.sh_offset as usize..)?.get(..section.sh_size as usize)?;
+
+    for mut body in file_attributes(data, RISCV_VENDOR)? {
+        while !body.is_empty() {
+            let (tag, len) = read_uleb128(body)?;
+            body = &body[len..];
+
+            // Odd tags are strings, even ones integers.
+            if tag % 2 == 1 {
+                let end = body.iter().position(|&c| c == 0)?;
+                if tag == TAG_RISCV_ARCH {
+                    return Some(String::from_utf8_lossy(&body[..end]).to_string());
+                }
+                body = &body[end + 1..];
+            } else {
+                let (_, len) = read_uleb128(body)?;
+                body = &body[len..];
+            }
+        }
+    }
+
+    None
+}
+
+/// Extensions a loader needs: its `-march` attribute, the RVC flag and what its code uses.
+pub(crate) fn required_extensions(elf: &Elf<'_>, buffer: &[u8], code: &[u8]) -> Vec<RiscvExtension> {
+    let mut extensions = scan_code(code);
+    if elf.header.e_flags & EF_RISCV_RVC != 0 {
+        extensions.insert(RiscvExtension::C);
+    }
+    if let Some(arch) = arch_attribute(elf, buffer) {
+        match parse_isa_string(&arch) {
+            Ok(declared) => extensions.extend(declared),
+            Err(err) => tracing::warn!("Ignoring the ELF architecture attribute: {}", err),
+        }
+    }
+
+    extensions.into_iter().collect()
+}
+
+impl RiscvFlashStub {
+    /// Checks that a hart implementing `isa` (e.g. `rv32imc_zicsr`) can run the loader.
+    pub fn check_hart(&self, isa: &str) -> Result<(), RiscvError> {
+        let available = parse_isa_string(isa)?;
+        match self.extensions.iter().find(|extension| !available.contains(extension)) {
+            Some(&extension) => Err(RiscvError::MissingExtension { extension, isa: isa.to_string() }),
+            None => Ok(()),
+        }
+    }
+}
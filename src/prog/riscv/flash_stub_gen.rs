@@ -0,0 +1,125 @@
+use goblin::elf::{header::EM_RISCV, Elf};
+use serde::{Deserialize, Serialize};
+
+use crate::prog::arm::{
+    algorithm_binary::{AlgorithmBinary, BlobLayout},
+    arm_error::ArmError,
+    flash_stub_gen::{extract_flash_device, sector_regions, SectorRegion},
+    parse_options::ParseOptions,
+    ram_layout::{RamRequirement, DEFAULT_STACK_SIZE},
+};
+
+use super::{
+    extensions::{required_extensions, RiscvExtension},
+    riscv_error::RiscvError,
+};
+
+/// A flash loader for RISC-V targets, the counterpart of `ArmFlashStub`.
+///
+/// Loaders follow the same CMSIS style entry points and `FlashDevice` descriptor as ARM FLMs,
+/// entry offsets are plain byte offsets into the blob.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiscvFlashStub {
+    pub name: String,
+    pub description: String,
+    pub default: bool,
+    /// Register width of the loader, 32 or 64.
+    pub xlen: u8,
+    /// ISA extensions the loader needs on top of the base integer ISA.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<RiscvExtension>,
+    pub instructions: String,
+    pub pc_init: Option<u32>,
+    pub pc_uninit: Option<u32>,
+    pub pc_program_page: u32,
+    pub pc_erase_sector: u32,
+    pub pc_erase_all: Option<u32>,
+    pub data_section_offset: u32,
+    pub flash_start_addr: u32,
+    pub flash_end_addr: u32,
+    pub flash_page_size: u32,
+    pub erased_byte_value: u8,
+    pub sectors: Vec<SectorRegion>,
+    pub program_timeout: u32,
+    pub erase_timeout: u32,
+    /// RAM declared for the target, or the computed requirement if none was declared.
+    pub ram_size: u32,
+    pub flash_size: u32,
+}
+
+impl RiscvFlashStub {
+    /// Build a stub from a RISC-V loader ELF.
+    ///
+    /// `ram_size` is the RAM available on the target; pass 0 to fill it in with the computed requirement.
+    pub fn from_elf(buf: &[u8], name: String, default: bool, ram_size: u32) -> Result<RiscvFlashStub, RiscvError> {
+        let elf = Elf::parse(buf).map_err(|_| RiscvError::ElfParse)?;
+        if elf.header.e_machine != EM_RISCV {
+            return Err(RiscvError::WrongMachine(elf.header.e_machine));
+        }
+
+        let flash_device = extract_flash_device(&elf, buf, &ParseOptions::default())?;
+        let algorithm_binary = AlgorithmBinary::new(&elf, buf)?;
+        if let Some(diagnostic) = algorithm_binary.suspicious_sections_diagnostic() {
+            tracing::warn!("{}", diagnostic);
+        }
+        let blobs = algorithm_binary.layout(&BlobLayout::default(), false)?;
+        let mut algo = RiscvFlashStub::default();
+
+        // Resolve the entry points relative to the start of the code.
+        let code = &algorithm_binary.code_section;
+        let code_range = u64::from(code.start)..u64::from(code.start) + u64::from(code.length);
+        let offset = |name: &str, address: u64| {
+            if code_range.contains(&address) {
+                Ok((address - code_range.start) as u32)
+            } else {
+                Err(RiscvError::EntryPointOutOfRange { name: name.to_string(), address })
+            }
+        };
+
+        let mut program_page = None;
+        let mut erase_sector = None;
+        for sym in elf.syms.iter() {
+            let symbol = &elf.strtab[sym.st_name];
+            match symbol {
+                "Init" => algo.pc_init = Some(offset(symbol, sym.st_value)?),
+                "UnInit" => algo.pc_uninit = Some(offset(symbol, sym.st_value)?),
+                "EraseChip" => algo.pc_erase_all = Some(offset(symbol, sym.st_value)?),
+                "EraseSector" => erase_sector = Some(offset(symbol, sym.st_value)?),
+                "ProgramPage" => program_page = Some(offset(symbol, sym.st_value)?),
+                _ => {}
+            }
+        }
+        algo.pc_program_page = program_page.ok_or_else(|| RiscvError::EntryPointNotFound("ProgramPage".to_string()))?;
+        algo.pc_erase_sector = erase_sector.ok_or_else(|| RiscvError::EntryPointNotFound("EraseSector".to_string()))?;
+
+        algo.xlen = if elf.is_64 { 64 } else { 32 };
+        algo.extensions = required_extensions(&elf, buf, &code.data);
+        algo.instructions = base64::encode(blobs.unified());
+        algo.name = name;
+        algo.sectors = sector_regions(&flash_device)?;
+        algo.description = flash_device.name;
+        algo.data_section_offset = blobs.data_offset;
+        algo.flash_start_addr = flash_device.start_address;
+        algo.flash_end_addr = flash_device.start_address.checked_add(flash_device.device_size).ok_or(ArmError::DescriptorOverflow {
+            field: "flash end",
+            value: u64::from(flash_device.start_address) + u64::from(flash_device.device_size),
+        })?;
+        algo.flash_size = flash_device.device_size;
+        algo.flash_page_size = flash_device.page_size;
+        algo.erase_timeout = flash_device.erase_sector_timeout;
+        algo.program_timeout = flash_device.program_page_timeout;
+        algo.erased_byte_value = flash_device.erased_default_value;
+        algo.default = default;
+
+        let ram_requirement = RamRequirement::new(&blobs, flash_device.page_size, DEFAULT_STACK_SIZE);
+        algo.ram_size = if ram_size == 0 {
+            ram_requirement.total()
+        } else {
+            ram_requirement.check(ram_size)?;
+            ram_size
+        };
+
+        Ok(algo)
+    }
+}
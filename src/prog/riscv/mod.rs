@@ -0,0 +1,4 @@
+pub mod esptool;
+pub mod extensions;
+pub mod flash_stub_gen;
+pub mod riscv_error;
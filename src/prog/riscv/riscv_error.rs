@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+use crate::prog::{arm::arm_error::ArmError, segments::SegmentError};
+
+use super::extensions::RiscvExtension;
+
+#[derive(Debug, Error)]
+pub enum RiscvError {
+    #[error("Failed to parse ELF file")]
+    ElfParse,
+
+    #[error("ELF machine {0} is not RISC-V")]
+    WrongMachine(u16),
+
+    #[error("Required entry point {0} not found")]
+    EntryPointNotFound(String),
+
+    #[error("Entry point {name} at {address:#010x} lies outside the code section")]
+    EntryPointOutOfRange { name: String, address: u64 },
+
+    #[error("Failed to decode instructions: {0}")]
+    InstructionDecode(String),
+
+    #[error("Invalid ISA string {0}")]
+    InvalidIsaString(String),
+
+    #[error("Loader needs the {extension} extension, which {isa} lacks")]
+    MissingExtension { extension: RiscvExtension, isa: String },
+
+    #[error("ESP loaders are RV32, got a {0}-bit ELF")]
+    UnsupportedXlen(u8),
+
+    #[error(transparent)]
+    Segment(#[from] SegmentError),
+
+    #[error(transparent)]
+    Descriptor(#[from] ArmError),
+}
@@ -0,0 +1,109 @@
+use std::convert::TryFrom;
+
+use goblin::elf::{
+    section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_NOBITS, SHT_PROGBITS},
+    Elf,
+};
+use thiserror::Error;
+
+/// Largest hole tolerated between two sections merged into one segment.
+const MAX_SEGMENT_GAP: u32 = 0x1000;
+
+#[derive(Debug, Error)]
+pub enum SegmentError {
+    #[error("Section data lies outside the ELF file")]
+    Truncated,
+
+    #[error("Loader has no {0} segment")]
+    NotFound(&'static str),
+
+    #[error("Sections of the {segment} segment are {gap:#x} bytes apart at {address:#010x}")]
+    Gap { segment: &'static str, address: u32, gap: u32 },
+
+    #[error("Section at {address:#010x} overlaps the {segment} segment, which runs to {end:#010x}")]
+    Overlap { segment: &'static str, address: u32, end: u64 },
+}
+
+/// Allocated sections merged into one image, gaps filled with zeroes.
+#[derive(Debug, Clone)]
+pub(crate) struct Segment {
+    pub(crate) start: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Segment {
+    pub(crate) fn contains(&self, address: u32) -> bool {
+        (self.start..self.start + self.data.len() as u32).contains(&address)
+    }
+}
+
+/// The load image of a loader linked to fixed addresses, as used by the ESP ROM loaders.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadImage {
+    /// Code, with any literal pools in front of it.
+    pub(crate) text: Segment,
+    pub(crate) data: Option<Segment>,
+    /// Lowest address of the zero-initialised sections.
+    pub(crate) bss_start: Option<u32>,
+}
+
+fn merge_sections(segment: &'static str, mut sections: Vec<(u32, &[u8])>) -> Result<Option<Segment>, SegmentError> {
+    sections.sort_by_key(|(address, _)| *address);
+    let mut sections = sections.into_iter();
+    let (start, first) = match sections.next() {
+        Some(section) => section,
+        None => return Ok(None),
+    };
+
+    let mut data = first.to_vec();
+    for (address, bytes) in sections {
+        let end = u64::from(start) + data.len() as u64;
+        if u64::from(address) < end {
+            return Err(SegmentError::Overlap { segment, address, end });
+        }
+        let gap = u64::from(address) - end;
+        if gap > u64::from(MAX_SEGMENT_GAP) {
+            return Err(SegmentError::Gap { segment, address, gap: gap as u32 });
+        }
+        data.resize((address - start) as usize, 0);
+        data.extend_from_slice(bytes);
+    }
+
+    Ok(Some(Segment { start, data }))
+}
+
+/// Splits the allocated sections of an ELF into a text and a data segment.
+///
+/// Executable sections and literal pools (`.literal*`) go to text, other initialised sections to data.
+pub(crate) fn load_image(elf: &Elf<'_>, buf: &[u8]) -> Result<LoadImage, SegmentError> {
+    let mut text_sections = Vec::new();
+    let mut data_sections = Vec::new();
+    let mut bss_start: Option<u32> = None;
+    for sh in &elf.section_headers {
+        if sh.sh_flags & u64::from(SHF_ALLOC) == 0 || sh.sh_size == 0 {
+            continue;
+        }
+
+        let address = sh.sh_addr as u32;
+        let section_name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("");
+        match sh.sh_type {
+            SHT_NOBITS => bss_start = Some(bss_start.map_or(address, |start| start.min(address))),
+            SHT_PROGBITS => {
+                let end = sh.sh_offset.checked_add(sh.sh_size).and_then(|end| usize::try_from(end).ok());
+                let bytes = end.and_then(|end| buf.get(sh.sh_offset as usize..end)).ok_or(SegmentError::Truncated)?;
+                if sh.sh_flags & u64::from(SHF_EXECINSTR) != 0 || section_name.contains("literal") {
+                    text_sections.push((address, bytes));
+                } else {
+                    data_sections.push((address, bytes));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(LoadImage {
+        text: merge_sections("text", text_sections)?.ok_or(SegmentError::NotFound("text"))?,
+        data: merge_sections("data", data_sections)?,
+        bss_start,
+    })
+}
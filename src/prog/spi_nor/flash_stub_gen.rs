@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prog::arm::flash_stub_gen::SectorRegion;
+
+use super::{sfdp::SpiNorParameters, spi_nor_error::SpiNorError};
+
+/// A generic SPI-NOR programming stub.
+///
+/// There is no algorithm blob, the programmer's built-in SPI-NOR driver runs the part with
+/// these parameters. `flash_start_addr` is where the flash is mapped (for XIP parts), so
+/// images linked for the mapped range can be programmed as they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpiNorFlashStub {
+    pub name: String,
+    pub description: String,
+    pub flash_start_addr: u32,
+    pub flash_end_addr: u32,
+    pub flash_page_size: u32,
+    pub flash_sector_size: u32,
+    pub sectors: Vec<SectorRegion>,
+    pub erased_byte_value: u8,
+    pub program_timeout: u32,
+    pub erase_timeout: u32,
+    pub parameters: SpiNorParameters,
+}
+
+impl SpiNorFlashStub {
+    /// Builds the stub for a part mapped at `base_address`.
+    ///
+    /// Sectors use the smallest erase type, timeouts are the maximum times rounded up to
+    /// whole ms, with a floor of one second when the part doesn't specify them.
+    pub fn new(name: String, base_address: u32, parameters: SpiNorParameters) -> Result<Self, SpiNorError> {
+        let sector = *parameters.sector_erase().ok_or(SpiNorError::NoEraseType)?;
+        // Anything past 4 GiB can't be mapped in a 32-bit address space anyway.
+        let size = parameters.size.min(u64::from(u32::MAX - base_address)) as u32;
+
+        let description = match parameters.jedec_id {
+            Some(id) => format!(
+                "{} SPI-NOR {:02X}{:02X}{:02X}",
+                super::jedec::part_name(id).unwrap_or("Generic"),
+                id[0],
+                id[1],
+                id[2]
+            ),
+            None => format!("Generic SPI-NOR {} KiB", size / 1024),
+        };
+
+        let or_default = |ms: u32| if ms == 0 { 1000 } else { ms };
+        Ok(Self {
+            name,
+            description,
+            flash_start_addr: base_address,
+            flash_end_addr: base_address + size,
+            flash_page_size: parameters.page_size,
+            flash_sector_size: sector.size,
+            sectors: vec![SectorRegion { address: base_address, size: sector.size, count: size / sector.size }],
+            erased_byte_value: 0xFF,
+            program_timeout: or_default(parameters.page_program_max_us.div_ceil(1000)),
+            erase_timeout: or_default(sector.max_ms),
+            parameters,
+        })
+    }
+}
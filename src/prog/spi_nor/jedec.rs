@@ -0,0 +1,52 @@
+use super::{
+    sfdp::{EraseType, SpiNorParameters},
+    spi_nor_error::SpiNorError,
+};
+
+/// A known part for boards where SFDP can't be read.
+struct KnownPart {
+    id: [u8; 2],
+    name: &'static str,
+    /// Parts with a 64K block erase as well as the 4K sector erase.
+    block_erase: bool,
+}
+
+/// Manufacturer and memory type bytes of common parts, the capacity byte is `log2(size)`.
+const KNOWN_PARTS: &[KnownPart] = &[
+    KnownPart { id: [0xEF, 0x40], name: "Winbond W25Qxx", block_erase: true },
+    KnownPart { id: [0xEF, 0x70], name: "Winbond W25QxxJV-M", block_erase: true },
+    KnownPart { id: [0xC2, 0x20], name: "Macronix MX25Lxx", block_erase: true },
+    KnownPart { id: [0xC8, 0x40], name: "GigaDevice GD25Qxx", block_erase: true },
+    KnownPart { id: [0x9D, 0x60], name: "ISSI IS25LPxx", block_erase: true },
+    KnownPart { id: [0x20, 0xBA], name: "Micron N25Q/MT25QL", block_erase: true },
+    KnownPart { id: [0x1F, 0x84], name: "Adesto AT25SF", block_erase: true },
+];
+
+/// Name of the part family for a JEDEC ID, if known.
+pub fn part_name(id: [u8; 3]) -> Option<&'static str> {
+    KNOWN_PARTS.iter().find(|part| part.id == [id[0], id[1]]).map(|part| part.name)
+}
+
+impl SpiNorParameters {
+    /// Conservative parameters for a known JEDEC ID (from the 0x9F command).
+    ///
+    /// Timings are left at 0, the flasher has to poll the busy bit without a deadline hint.
+    pub fn from_jedec_id(id: [u8; 3]) -> Result<Self, SpiNorError> {
+        let part = KNOWN_PARTS
+            .iter()
+            .find(|part| part.id == [id[0], id[1]])
+            .ok_or(SpiNorError::UnknownJedecId(id))?;
+        if !(16..=31).contains(&id[2]) {
+            return Err(SpiNorError::UnknownJedecId(id));
+        }
+
+        let mut erase_types = vec![EraseType { size: 0x1000, opcode: 0x20, typical_ms: 0, max_ms: 0 }];
+        if part.block_erase {
+            erase_types.push(EraseType { size: 0x10000, opcode: 0xD8, typical_ms: 0, max_ms: 0 });
+        }
+
+        let mut parameters = Self::common(1 << id[2], erase_types);
+        parameters.jedec_id = Some(id);
+        Ok(parameters)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod flash_stub_gen;
+pub mod jedec;
+pub mod sfdp;
+pub mod spi_nor_error;
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use super::spi_nor_error::SpiNorError;
+
+const SFDP_SIGNATURE: &[u8; 4] = b"SFDP";
+/// Parameter ID of the JEDEC Basic Flash Parameter Table.
+const BASIC_TABLE_ID: u16 = 0xFF00;
+/// DWORDs in a JESD216 (rev 1.0) basic table, later revisions append more.
+const BASIC_TABLE_MIN_DWORDS: usize = 9;
+
+/// One sector/block erase command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EraseType {
+    pub size: u32,
+    pub opcode: u8,
+    /// Typical and maximum erase time in ms, 0 when the table doesn't say.
+    pub typical_ms: u32,
+    pub max_ms: u32,
+}
+
+/// Everything a generic SPI-NOR driver needs to program a part.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpiNorParameters {
+    /// Manufacturer and device ID as read with 0x9F, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jedec_id: Option<[u8; 3]>,
+    /// Size in bytes.
+    pub size: u64,
+    pub page_size: u32,
+    /// Address bytes the part is used with, 3 or 4.
+    pub address_bytes: u8,
+    pub read_opcode: u8,
+    pub program_opcode: u8,
+    pub chip_erase_opcode: u8,
+    /// Erase types sorted by size, smallest first.
+    pub erase_types: Vec<EraseType>,
+    pub page_program_typical_us: u32,
+    pub page_program_max_us: u32,
+    pub chip_erase_typical_ms: u32,
+    pub chip_erase_max_ms: u32,
+}
+
+impl SpiNorParameters {
+    /// Defaults shared by practically every SPI-NOR part: 256 byte pages, READ (0x03),
+    /// PAGE PROGRAM (0x02) and CHIP ERASE (0xC7).
+    pub(crate) fn common(size: u64, erase_types: Vec<EraseType>) -> Self {
+        Self {
+            jedec_id: None,
+            size,
+            page_size: 256,
+            address_bytes: if size > 1 << 24 { 4 } else { 3 },
+            read_opcode: 0x03,
+            program_opcode: 0x02,
+            chip_erase_opcode: 0xC7,
+            erase_types,
+            page_program_typical_us: 0,
+            page_program_max_us: 0,
+            chip_erase_typical_ms: 0,
+            chip_erase_max_ms: 0,
+        }
+    }
+
+    /// The smallest erase type, the sector size of the part.
+    pub fn sector_erase(&self) -> Option<&EraseType> {
+        self.erase_types.first()
+    }
+
+    /// Parses the SFDP area as read with the 0x5A command, starting at SFDP address 0.
+    pub fn from_sfdp(sfdp: &[u8]) -> Result<Self, SpiNorError> {
+        if sfdp.get(..4) != Some(&SFDP_SIGNATURE[..]) {
+            return Err(SpiNorError::SfdpSignature);
+        }
+        let header_count = usize::from(*sfdp.get(6).ok_or(SpiNorError::SfdpTruncated(sfdp.len()))?) + 1;
+
+        let mut basic = None;
+        for index in 0..header_count {
+            let offset = 8 + index * 8;
+            let header = sfdp.get(offset..offset + 8).ok_or(SpiNorError::SfdpTruncated(sfdp.len()))?;
+            let id = u16::from(header[7]) << 8 | u16::from(header[0]);
+            if id != BASIC_TABLE_ID {
+                continue;
+            }
+
+            let dwords = usize::from(header[3]);
+            let pointer = usize::from(header[4]) | usize::from(header[5]) << 8 | usize::from(header[6]) << 16;
+            let table = sfdp.get(pointer..pointer + dwords * 4).ok_or(SpiNorError::SfdpTruncated(sfdp.len()))?;
+            // Headers are listed by ascending revision, keep the newest table.
+            basic = Some(table);
+        }
+
+        let table = basic.ok_or(SpiNorError::BasicTableMissing)?;
+        let dwords: Vec<u32> = table
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        Self::from_basic_table(&dwords)
+    }
+
+    /// Parses the DWORDs of a Basic Flash Parameter Table (JESD216).
+    pub fn from_basic_table(dwords: &[u32]) -> Result<Self, SpiNorError> {
+        if dwords.len() < BASIC_TABLE_MIN_DWORDS {
+            return Err(SpiNorError::BasicTableTooShort(dwords.len()));
+        }
+        // DWORDs are numbered from 1 in the standard.
+        let dword = |number: usize| dwords.get(number - 1).copied();
+        let bits = |value: u32, low: u32, count: u32| (value >> low) & ((1 << count) - 1);
+
+        let density = dword(2).unwrap_or(0);
+        let size_bits = if density & (1 << 31) == 0 {
+            u64::from(density) + 1
+        } else {
+            1u64 << (density & 0x7FFF_FFFF).min(63)
+        };
+
+        // Erase types 1-4: size exponent and opcode pairs in DWORDs 8 and 9,
+        // typical times in DWORD 10.
+        let erase_words = [dword(8).unwrap_or(0), dword(9).unwrap_or(0)];
+        let timing = dword(10);
+        let max_multiplier = timing.map_or(0, |timing| 2 * (bits(timing, 0, 4) + 1));
+        let mut erase_types = Vec::new();
+        for index in 0..4u32 {
+            let word = erase_words[(index / 2) as usize];
+            let shift = 16 * (index % 2);
+            let exponent = bits(word, shift, 8);
+            let opcode = bits(word, shift + 8, 8) as u8;
+            if exponent == 0 {
+                continue;
+            }
+
+            let typical_ms = timing.map_or(0, |timing| {
+                let field = bits(timing, 4 + 7 * index, 7);
+                let unit = [1, 16, 128, 1000][bits(field, 5, 2) as usize];
+                (bits(field, 0, 5) + 1) * unit
+            });
+            erase_types.push(EraseType {
+                size: 1 << exponent.min(31),
+                opcode,
+                typical_ms,
+                max_ms: typical_ms * max_multiplier,
+            });
+        }
+        if erase_types.is_empty() {
+            return Err(SpiNorError::NoEraseType);
+        }
+        erase_types.sort_by_key(|erase| erase.size);
+
+        let mut parameters = Self::common(size_bits / 8, erase_types);
+        // DWORD 1 bits 18:17: 00 three byte only, 01 three or four, 10 four byte only.
+        parameters.address_bytes = match bits(dword(1).unwrap_or(0), 17, 2) {
+            0 => 3,
+            2 => 4,
+            _ => parameters.address_bytes,
+        };
+
+        if let Some(program) = dword(11) {
+            let multiplier = 2 * (bits(program, 0, 4) + 1);
+            parameters.page_size = 1 << bits(program, 4, 4);
+            let unit_us = if bits(program, 13, 1) == 0 { 8 } else { 64 };
+            parameters.page_program_typical_us = (bits(program, 8, 5) + 1) * unit_us;
+            parameters.page_program_max_us = parameters.page_program_typical_us * multiplier;
+
+            let unit_ms = [16, 256, 4000, 64000][bits(program, 29, 2) as usize];
+            parameters.chip_erase_typical_ms = (bits(program, 24, 5) + 1) * unit_ms;
+            parameters.chip_erase_max_ms = parameters.chip_erase_typical_ms * multiplier;
+        }
+
+        Ok(parameters)
+    }
+}
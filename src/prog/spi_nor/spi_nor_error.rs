@@ -0,0 +1,22 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SpiNorError {
+    #[error("SFDP signature not found")]
+    SfdpSignature,
+
+    #[error("SFDP data ends at {0:#x}, before the tables it points to")]
+    SfdpTruncated(usize),
+
+    #[error("SFDP has no Basic Flash Parameter Table")]
+    BasicTableMissing,
+
+    #[error("Basic Flash Parameter Table has {0} DWORDs, at least 9 are needed")]
+    BasicTableTooShort(usize),
+
+    #[error("Flash supports no erase type")]
+    NoEraseType,
+
+    #[error("JEDEC ID {0:02x?} is not in the table")]
+    UnknownJedecId([u8; 3]),
+}
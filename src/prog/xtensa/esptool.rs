@@ -0,0 +1,192 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    flash_stub_gen::{XtensaAbi, XtensaFlashStub},
+    xtensa_error::XtensaError,
+};
+
+/// The flasher stub format shipped with esptool.py (`stub_flasher_*.json`).
+///
+/// Field names follow esptool, not the camelCase used by the rest of the crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EsptoolStub {
+    pub entry: u32,
+    pub text: String,
+    pub text_start: u32,
+    #[serde(default)]
+    pub data: String,
+    #[serde(default)]
+    pub data_start: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bss_start: Option<u32>,
+}
+
+/// First byte of an ESP application image.
+const IMAGE_MAGIC: u8 = 0xE9;
+
+/// Seed of the image checksum, which XORs every segment byte into it.
+const CHECKSUM_SEED: u8 = 0xEF;
+
+/// The ESP chips the stubs are built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EspChip {
+    Esp32,
+    Esp32S2,
+    Esp32S3,
+    Esp32C2,
+    Esp32C3,
+    Esp32C6,
+    Esp32H2,
+}
+
+impl EspChip {
+    pub const ALL: [EspChip; 7] =
+        [EspChip::Esp32, EspChip::Esp32S2, EspChip::Esp32S3, EspChip::Esp32C2, EspChip::Esp32C3, EspChip::Esp32C6, EspChip::Esp32H2];
+
+    /// Name esptool and espflash take for `--chip`, also the file name of their stubs.
+    pub fn name(self) -> &'static str {
+        match self {
+            EspChip::Esp32 => "esp32",
+            EspChip::Esp32S2 => "esp32s2",
+            EspChip::Esp32S3 => "esp32s3",
+            EspChip::Esp32C2 => "esp32c2",
+            EspChip::Esp32C3 => "esp32c3",
+            EspChip::Esp32C6 => "esp32c6",
+            EspChip::Esp32H2 => "esp32h2",
+        }
+    }
+
+    /// Chip id of the image's extended header, esptool refuses images for another chip.
+    pub fn chip_id(self) -> u16 {
+        match self {
+            EspChip::Esp32 => 0,
+            EspChip::Esp32S2 => 2,
+            EspChip::Esp32S3 => 9,
+            EspChip::Esp32C2 => 12,
+            EspChip::Esp32C3 => 5,
+            EspChip::Esp32C6 => 13,
+            EspChip::Esp32H2 => 16,
+        }
+    }
+
+    /// The C and H series are RISC-V, the others Xtensa.
+    pub fn is_riscv(self) -> bool {
+        !matches!(self, EspChip::Esp32 | EspChip::Esp32S2 | EspChip::Esp32S3)
+    }
+}
+
+impl fmt::Display for EspChip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for EspChip {
+    type Err = XtensaError;
+
+    /// Takes the esptool names, ignoring case and dashes, e.g. "ESP32-S3".
+    fn from_str(name: &str) -> Result<EspChip, XtensaError> {
+        let normalized = name.replace(['-', '_'], "").to_ascii_lowercase();
+        EspChip::ALL.iter().copied().find(|chip| chip.name() == normalized).ok_or_else(|| XtensaError::UnknownChip(name.to_string()))
+    }
+}
+
+impl From<&XtensaFlashStub> for EsptoolStub {
+    fn from(stub: &XtensaFlashStub) -> Self {
+        EsptoolStub {
+            entry: stub.entry,
+            text: stub.text.clone(),
+            text_start: stub.text_start,
+            data: stub.data.clone(),
+            data_start: stub.data_start,
+            bss_start: stub.bss_start,
+        }
+    }
+}
+
+impl EsptoolStub {
+    pub fn from_json(json: &str) -> Result<EsptoolStub, XtensaError> {
+        serde_json::from_str(json).map_err(|err| XtensaError::StubJson(err.to_string()))
+    }
+
+    /// The JSON esptool.py loads its stubs from.
+    pub fn to_json(&self) -> Result<String, XtensaError> {
+        serde_json::to_string_pretty(self).map_err(|err| XtensaError::StubEncode(err.to_string()))
+    }
+
+    /// The TOML espflash loads its stubs from, same fields as the JSON.
+    pub fn to_espflash_toml(&self) -> Result<String, XtensaError> {
+        toml::to_string(self).map_err(|err| XtensaError::StubEncode(err.to_string()))
+    }
+
+    /// An application image of the stub for `chip`, which `esptool.py load_ram` writes to RAM and
+    /// starts at the entry point.
+    ///
+    /// The image holds the text and data segments behind the common header and the extended
+    /// header, then the checksum at the end of the padding to 16 bytes. No SHA-256 is appended.
+    pub fn to_ram_image(&self, chip: EspChip) -> Result<Vec<u8>, XtensaError> {
+        let text = base64::decode(&self.text).map_err(|err| XtensaError::SegmentDecode { segment: "text", reason: err.to_string() })?;
+        let data = base64::decode(&self.data).map_err(|err| XtensaError::SegmentDecode { segment: "data", reason: err.to_string() })?;
+        let segments: Vec<(u32, Vec<u8>)> =
+            vec![(self.text_start, text), (self.data_start, data)].into_iter().filter(|(_, data)| !data.is_empty()).collect();
+
+        // Header: magic, segment count, flash mode DIO and 4 MB at 40 MHz, which RAM ignores.
+        let mut image = vec![IMAGE_MAGIC, segments.len() as u8, 0x02, 0x20];
+        image.extend_from_slice(&self.entry.to_le_bytes());
+        // Extended header: no WP pin, default SPI pins, the chip, any revision, no hash.
+        image.extend_from_slice(&[0xEE, 0, 0, 0]);
+        image.extend_from_slice(&chip.chip_id().to_le_bytes());
+        image.push(0);
+        image.extend_from_slice(&0u16.to_le_bytes());
+        image.extend_from_slice(&u16::MAX.to_le_bytes());
+        image.extend_from_slice(&[0; 5]);
+
+        let mut checksum = CHECKSUM_SEED;
+        for (address, mut data) in segments {
+            // Segments are word aligned in length.
+            data.resize((data.len() + 3) & !3, 0);
+            image.extend_from_slice(&address.to_le_bytes());
+            image.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            checksum = data.iter().fold(checksum, |checksum, byte| checksum ^ byte);
+            image.extend_from_slice(&data);
+        }
+        image.resize((image.len() + 1 + 15) & !15, 0);
+        *image.last_mut().expect("image is never empty") = checksum;
+
+        Ok(image)
+    }
+}
+
+impl XtensaFlashStub {
+    /// Import an esptool.py flasher stub.
+    ///
+    /// The stub only has the `stub_main` entry, the CMSIS style entry points are left unset.
+    pub fn from_esptool_json(json: &str, name: String) -> Result<XtensaFlashStub, XtensaError> {
+        let stub = EsptoolStub::from_json(json)?;
+        let text = base64::decode(&stub.text)
+            .map_err(|err| XtensaError::SegmentDecode { segment: "text", reason: err.to_string() })?;
+        base64::decode(&stub.data)
+            .map_err(|err| XtensaError::SegmentDecode { segment: "data", reason: err.to_string() })?;
+
+        if text.is_empty() {
+            return Err(XtensaError::SegmentNotFound("text"));
+        }
+        if !(stub.text_start..stub.text_start + text.len() as u32).contains(&stub.entry) {
+            return Err(XtensaError::EntryPointOutOfRange { name: "entry".to_string(), address: stub.entry });
+        }
+
+        Ok(XtensaFlashStub {
+            name,
+            abi: XtensaAbi::detect(&text, stub.text_start, stub.entry),
+            entry: stub.entry,
+            text: stub.text,
+            text_start: stub.text_start,
+            data: stub.data,
+            data_start: stub.data_start,
+            bss_start: stub.bss_start,
+            ..Default::default()
+        })
+    }
+}
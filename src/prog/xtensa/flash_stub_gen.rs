@@ -0,0 +1,120 @@
+use goblin::elf::{header::EM_XTENSA, Elf};
+use serde::{Deserialize, Serialize};
+
+use crate::prog::segments::load_image;
+
+use super::xtensa_error::XtensaError;
+
+/// First byte of the windowed ABI `entry a1, N` instruction.
+const ENTRY_OPCODE: u8 = 0x36;
+
+/// Calling convention the loader was built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum XtensaAbi {
+    /// Register windows, every function starts with `entry`. Callers must use `callx8` or set up
+    /// the window base before jumping in.
+    #[default]
+    Windowed,
+    /// Plain `call0` convention.
+    Call0,
+}
+
+impl XtensaAbi {
+    /// Tells the ABI apart by whether the instruction at `entry` is `entry a1, N`.
+    pub(crate) fn detect(text: &[u8], text_start: u32, entry: u32) -> XtensaAbi {
+        match text.get(entry.wrapping_sub(text_start) as usize) {
+            Some(&ENTRY_OPCODE) => XtensaAbi::Windowed,
+            _ => XtensaAbi::Call0,
+        }
+    }
+}
+
+/// A flash loader for Xtensa targets (ESP32, ESP32-S2/S3).
+///
+/// Unlike ARM algorithms these are not position independent: the text and data segments must be
+/// written to the addresses they were linked at, and all entry points are absolute.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtensaFlashStub {
+    pub name: String,
+    pub abi: XtensaAbi,
+    /// Absolute address the loader starts at.
+    pub entry: u32,
+    /// Literal pools and code, base64 encoded.
+    pub text: String,
+    pub text_start: u32,
+    /// Initialised data, base64 encoded, may be empty.
+    pub data: String,
+    pub data_start: u32,
+    /// Start of the zero-initialised data, if any. The loader clears it itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bss_start: Option<u32>,
+    /// CMSIS style entry points, for loaders that expose them next to the stub entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_init: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_uninit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_program_page: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_erase_sector: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_erase_all: Option<u32>,
+}
+
+impl XtensaFlashStub {
+    /// Build a stub from an Xtensa loader ELF.
+    ///
+    /// `.literal` sections are kept in front of the code they belong to, `l32r` can only reach backwards.
+    pub fn from_elf(buf: &[u8], name: String) -> Result<XtensaFlashStub, XtensaError> {
+        let elf = Elf::parse(buf).map_err(|_| XtensaError::ElfParse)?;
+        if elf.header.e_machine != EM_XTENSA {
+            return Err(XtensaError::WrongMachine(elf.header.e_machine));
+        }
+
+        let image = load_image(&elf, buf)?;
+        let text = image.text;
+        let in_text = |name: &str, address: u32| {
+            if text.contains(address) {
+                Ok(address)
+            } else {
+                Err(XtensaError::EntryPointOutOfRange { name: name.to_string(), address })
+            }
+        };
+
+        let mut algo = XtensaFlashStub::default();
+        let mut stub_main = None;
+        for sym in elf.syms.iter() {
+            let symbol = &elf.strtab[sym.st_name];
+            let address = sym.st_value as u32;
+            match symbol {
+                "stub_main" => stub_main = Some(address),
+                "Init" => algo.pc_init = Some(in_text(symbol, address)?),
+                "UnInit" => algo.pc_uninit = Some(in_text(symbol, address)?),
+                "ProgramPage" => algo.pc_program_page = Some(in_text(symbol, address)?),
+                "EraseSector" => algo.pc_erase_sector = Some(in_text(symbol, address)?),
+                "EraseChip" => algo.pc_erase_all = Some(in_text(symbol, address)?),
+                _ => {}
+            }
+        }
+
+        let entry = match elf.header.e_entry as u32 {
+            0 => stub_main.ok_or(XtensaError::EntryPointNotFound)?,
+            entry => entry,
+        };
+        algo.entry = in_text("entry", entry)?;
+        algo.abi = XtensaAbi::detect(&text.data, text.start, entry);
+
+        algo.name = name;
+        algo.text = base64::encode(&text.data);
+        algo.text_start = text.start;
+        if let Some(data) = image.data {
+            algo.data = base64::encode(&data.data);
+            algo.data_start = data.start;
+        }
+        algo.bss_start = image.bss_start;
+
+        Ok(algo)
+    }
+}
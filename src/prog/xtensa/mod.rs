@@ -0,0 +1,3 @@
+pub mod esptool;
+pub mod flash_stub_gen;
+pub mod xtensa_error;
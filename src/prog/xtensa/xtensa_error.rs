@@ -0,0 +1,36 @@
+use thiserror::Error;
+
+use crate::prog::segments::SegmentError;
+
+#[derive(Debug, Error)]
+pub enum XtensaError {
+    #[error("Failed to parse ELF file")]
+    ElfParse,
+
+    #[error("ELF machine {0} is not Xtensa")]
+    WrongMachine(u16),
+
+    #[error("Loader has no {0} segment")]
+    SegmentNotFound(&'static str),
+
+    #[error(transparent)]
+    Segment(#[from] SegmentError),
+
+    #[error("No entry point, neither e_entry nor stub_main is set")]
+    EntryPointNotFound,
+
+    #[error("Entry point {name} at {address:#010x} lies outside the text segment")]
+    EntryPointOutOfRange { name: String, address: u32 },
+
+    #[error("Invalid esptool stub JSON: {0}")]
+    StubJson(String),
+
+    #[error("Failed to encode esptool stub: {0}")]
+    StubEncode(String),
+
+    #[error("Unknown ESP chip {0}, expected esp32, esp32s2, esp32s3, esp32c2, esp32c3, esp32c6 or esp32h2")]
+    UnknownChip(String),
+
+    #[error("Invalid base64 in the {segment} segment: {reason}")]
+    SegmentDecode { segment: &'static str, reason: String },
+}
@@ -0,0 +1,47 @@
+//! Progress of long runs: pack downloads, batch conversions and emulation runs report each item
+//! to a `ProgressSink` before working on it, for progress bars and GUI frontends.
+
+/// Where a run stands when an item starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// Items finished so far, successfully or not.
+    pub completed: usize,
+    /// Items in the whole run.
+    pub total: usize,
+    /// The item starting now: a URL, a file or a routine name.
+    pub current: &'a str,
+}
+
+impl Progress<'_> {
+    /// Share of the run finished, from 0.0 to 1.0. An empty run counts as finished.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+}
+
+/// Receives the progress of a run.
+pub trait ProgressSink {
+    /// Called before each item, with `completed` counting the items before it.
+    fn progress(&mut self, progress: Progress<'_>);
+
+    /// Called once after the last item, with the number of items finished.
+    fn finish(&mut self, _completed: usize) {}
+}
+
+/// Discards progress, for callers that don't show any.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn progress(&mut self, _progress: Progress<'_>) {}
+}
+
+impl<F: FnMut(Progress<'_>)> ProgressSink for F {
+    fn progress(&mut self, progress: Progress<'_>) {
+        self(progress)
+    }
+}
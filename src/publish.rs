@@ -0,0 +1,380 @@
+//! Publishing stubs, signed bundles and manifests to S3-compatible object storage, for teams
+//! handing stub libraries to field programmers over the internet.
+//!
+//! Objects are content addressed, stored under `objects/` by their SHA-256, so publishing the same
+//! file twice uploads it once and a programmer can check what it downloaded against its key.
+//! `index.json` lists the objects of each device by name; programmers read it to find what to
+//! fetch. The index is read, updated and written back, two publishers racing on it keep the
+//! entries of the last one.
+
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+pub const INDEX_KEY: &str = "index.json";
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+/// SHA-256 of an empty payload, for requests without a body.
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+#[derive(Debug, Error)]
+pub enum PublishError {
+    #[error("{method} {key} failed with status {status}, {body}")]
+    Status { method: String, key: String, status: u16, body: String },
+
+    #[error("{method} {key} failed, {reason}")]
+    Transport { method: String, key: String, reason: String },
+
+    #[error("Invalid index at {key}, {reason}")]
+    Index { key: String, reason: String },
+
+    #[error("Invalid endpoint {0}, expected http:// or https:// and a host")]
+    Endpoint(String),
+}
+
+/// Where objects go. `S3Store` talks to object storage, tests and local mirrors can bring their own.
+pub trait ObjectStore {
+    /// The object at `key`, `None` if there is none.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, PublishError>;
+
+    fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), PublishError>;
+
+    fn exists(&self, key: &str) -> Result<bool, PublishError>;
+}
+
+/// Access key pair of the storage account.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// Leaves the secret out of logs.
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials").field("access_key", &self.access_key).finish_non_exhaustive()
+    }
+}
+
+impl Credentials {
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`, as every S3 client reads them.
+    pub fn from_env() -> Option<Self> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        Some(Credentials { access_key, secret_key })
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything but the unreserved characters and, in paths, `/`.
+fn uri_encode(text: &str, path: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if path => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `x-amz-date` of a time in seconds since the Unix epoch, `20130524T000000Z`.
+pub fn amz_date(unix: u64) -> String {
+    // Civil from days, after Howard Hinnant's date algorithms.
+    let days = (unix / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let seconds = unix % 86_400;
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// The `Authorization` header of an S3 request, AWS Signature Version 4.
+///
+/// `headers` are the other headers to sign with lowercase names, they must include `x-amz-date`
+/// and `x-amz-content-sha256`. `host` is always signed.
+pub fn authorization(
+    credentials: &Credentials,
+    region: &str,
+    method: &str,
+    host: &str,
+    path: &str,
+    headers: &BTreeMap<String, String>,
+    payload_sha256: &str,
+) -> String {
+    let date = headers.get("x-amz-date").map(String::as_str).unwrap_or_default();
+    let mut signed = headers.clone();
+    signed.insert("host".to_string(), host.to_string());
+
+    let canonical_headers: String = signed.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let signed_headers = signed.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_request =
+        format!("{}\n{}\n\n{}\n{}\n{}", method, uri_encode(path, true), canonical_headers, signed_headers, payload_sha256);
+
+    let scope = format!("{}/{}/s3/aws4_request", &date[..date.len().min(8)], region);
+    let string_to_sign = format!("{}\n{}\n{}\n{}", ALGORITHM, date, scope, sha256_hex(canonical_request.as_bytes()));
+
+    let key = [&date[..date.len().min(8)], region, "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", credentials.secret_key).into_bytes(), |key, part| hmac(&key, part));
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    format!("{} Credential={}/{},SignedHeaders={},Signature={}", ALGORITHM, credentials.access_key, scope, signed_headers, signature)
+}
+
+/// A bucket of an S3-compatible service: AWS, MinIO, Cloudflare R2 and the like.
+#[derive(Debug, Clone)]
+pub struct S3Store {
+    /// Scheme and host of the service, e.g. `https://s3.eu-central-1.amazonaws.com`.
+    scheme: String,
+    host: String,
+    region: String,
+    bucket: String,
+    /// Bucket in the path instead of the host name, what MinIO and most self-hosted services want.
+    path_style: bool,
+    credentials: Credentials,
+}
+
+impl S3Store {
+    pub fn new(endpoint: &str, region: &str, bucket: &str, credentials: Credentials) -> Result<Self, PublishError> {
+        let (scheme, rest) = endpoint.split_once("://").ok_or_else(|| PublishError::Endpoint(endpoint.to_string()))?;
+        let host = rest.trim_end_matches('/');
+        if !matches!(scheme, "http" | "https") || host.is_empty() || host.contains('/') {
+            return Err(PublishError::Endpoint(endpoint.to_string()));
+        }
+
+        Ok(S3Store {
+            scheme: scheme.to_string(),
+            host: host.to_string(),
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            path_style: true,
+            credentials,
+        })
+    }
+
+    /// Puts the bucket in the host name, `bucket.host`, as AWS prefers.
+    pub fn virtual_hosted(mut self) -> Self {
+        self.path_style = false;
+        self
+    }
+
+    fn request(&self, method: &str, key: &str, data: &[u8], content_type: Option<&str>) -> Result<Option<ureq::Response>, PublishError> {
+        let (host, path) = if self.path_style {
+            (self.host.clone(), format!("/{}/{}", self.bucket, key))
+        } else {
+            (format!("{}.{}", self.bucket, self.host), format!("/{}", key))
+        };
+        let payload_sha256 = if data.is_empty() { EMPTY_SHA256.to_string() } else { sha256_hex(data) };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+
+        let mut headers = BTreeMap::new();
+        headers.insert("x-amz-content-sha256".to_string(), payload_sha256.clone());
+        headers.insert("x-amz-date".to_string(), amz_date(now));
+        if let Some(content_type) = content_type {
+            headers.insert("content-type".to_string(), content_type.to_string());
+        }
+        let authorization = authorization(&self.credentials, &self.region, method, &host, &path, &headers, &payload_sha256);
+
+        let url = format!("{}://{}{}", self.scheme, host, uri_encode(&path, true));
+        let mut request = ureq::request(method, &url).set("Authorization", &authorization);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+
+        let failed = |reason: String| PublishError::Transport { method: method.to_string(), key: key.to_string(), reason };
+        match request.send_bytes(data) {
+            Ok(response) => Ok(Some(response)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(ureq::Error::Status(status, response)) => Err(PublishError::Status {
+                method: method.to_string(),
+                key: key.to_string(),
+                status,
+                body: response.into_string().unwrap_or_default(),
+            }),
+            Err(err) => Err(failed(err.to_string())),
+        }
+    }
+}
+
+impl ObjectStore for S3Store {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, PublishError> {
+        let response = match self.request("GET", key, &[], None)? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|err| PublishError::Transport { method: "GET".to_string(), key: key.to_string(), reason: err.to_string() })?;
+        Ok(Some(data))
+    }
+
+    fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), PublishError> {
+        self.request("PUT", key, data, Some(content_type)).map(drop)
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, PublishError> {
+        self.request("HEAD", key, &[], None).map(|response| response.is_some())
+    }
+}
+
+/// What a published object holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ObjectKind {
+    /// A stub as JSON.
+    Stub,
+    /// A group of stubs written by `merge`, as JSON.
+    Manifest,
+    /// A signed bundle, see `signing`.
+    Bundle,
+}
+
+impl ObjectKind {
+    fn extension(self) -> &'static str {
+        match self {
+            ObjectKind::Stub | ObjectKind::Manifest => "json",
+            ObjectKind::Bundle => "bin",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ObjectKind::Stub | ObjectKind::Manifest => "application/json",
+            ObjectKind::Bundle => "application/octet-stream",
+        }
+    }
+}
+
+/// An entry of the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishedObject {
+    pub name: String,
+    pub kind: ObjectKind,
+    /// Key of the object, relative to the bucket.
+    pub key: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch.
+    pub published_at: u64,
+}
+
+/// `index.json`, the published objects of each device.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishIndex {
+    pub devices: BTreeMap<String, Vec<PublishedObject>>,
+}
+
+/// A file to publish for a device.
+#[derive(Debug, Clone, Copy)]
+pub struct Publication<'a> {
+    pub kind: ObjectKind,
+    pub name: &'a str,
+    pub data: &'a [u8],
+}
+
+/// Publishes into an `ObjectStore`, under a key prefix so one bucket can hold several libraries.
+#[derive(Debug, Clone)]
+pub struct Publisher<S> {
+    store: S,
+    prefix: String,
+}
+
+impl<S: ObjectStore> Publisher<S> {
+    /// Publishes under `prefix`, e.g. `stubs/` or `customer-a/`, or at the bucket root if empty.
+    pub fn new(store: S, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        let prefix = if prefix.is_empty() { String::new() } else { format!("{}/", prefix) };
+        Publisher { store, prefix }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn index_key(&self) -> String {
+        format!("{}{}", self.prefix, INDEX_KEY)
+    }
+
+    /// Key of an object with this content, `objects/<first two digits>/<sha256>.<extension>`.
+    pub fn object_key(&self, kind: ObjectKind, sha256: &str) -> String {
+        format!("{}objects/{}/{}.{}", self.prefix, &sha256[..2], sha256, kind.extension())
+    }
+
+    /// The published index, empty before the first publication.
+    pub fn index(&self) -> Result<PublishIndex, PublishError> {
+        let key = self.index_key();
+        match self.store.get(&key)? {
+            Some(data) => serde_json::from_slice(&data).map_err(|err| PublishError::Index { key, reason: err.to_string() }),
+            None => Ok(PublishIndex::default()),
+        }
+    }
+
+    /// Uploads what isn't stored yet and lists the files under `device` in the index, replacing
+    /// earlier entries of the same name and kind.
+    pub fn publish(&self, device: &str, publications: &[Publication<'_>]) -> Result<Vec<PublishedObject>, PublishError> {
+        let published_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let mut objects = Vec::with_capacity(publications.len());
+        for publication in publications {
+            let sha256 = sha256_hex(publication.data);
+            let key = self.object_key(publication.kind, &sha256);
+            if self.store.exists(&key)? {
+                tracing::debug!(key = %key, "already published");
+            } else {
+                self.store.put(&key, publication.data, publication.kind.content_type())?;
+            }
+            objects.push(PublishedObject {
+                name: publication.name.to_string(),
+                kind: publication.kind,
+                key,
+                sha256,
+                size: publication.data.len() as u64,
+                published_at,
+            });
+        }
+
+        let mut index = self.index()?;
+        let entries = index.devices.entry(device.to_string()).or_default();
+        for object in &objects {
+            entries.retain(|entry| entry.name != object.name || entry.kind != object.kind);
+            entries.push(object.clone());
+        }
+        entries.sort_by(|a, b| (&a.name, a.kind as u8).cmp(&(&b.name, b.kind as u8)));
+
+        let mut data = serde_json::to_vec_pretty(&index).map_err(|err| PublishError::Index { key: self.index_key(), reason: err.to_string() })?;
+        data.push(b'\n');
+        self.store.put(&self.index_key(), &data, "application/json")?;
+        Ok(objects)
+    }
+}
@@ -0,0 +1,119 @@
+//! The `soul_composer` Python module, for provisioning scripts. Build it with maturin, see
+//! `pyproject.toml`.
+
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyBytes, PyDict, PyList},
+};
+use serde_json::Value;
+
+use crate::{
+    compose::arm::{self, ComposeOptions, Elf},
+    prog::{
+        arm::{flash_device::FlashDevice, flash_stub_gen::ArmFlashStub},
+        export::{export as export_stub, OutputFormat},
+    },
+};
+
+create_exception!(soul_composer, SoulComposerError, PyException, "The FLM or stub was refused.");
+
+fn py_error(err: impl ToString) -> PyErr {
+    SoulComposerError::new_err(err.to_string())
+}
+
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(value) => value.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => value.into_pyobject(py)?.into_any(),
+            (None, Some(value)) => value.into_pyobject(py)?.into_any(),
+            _ => number.as_f64().unwrap_or_default().into_pyobject(py)?.into_any(),
+        },
+        Value::String(text) => text.into_pyobject(py)?.into_any(),
+        Value::Array(items) => {
+            let items = items.iter().map(|item| to_python(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, value) in fields {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+fn compose(data: &[u8], name: String, default: bool, ram_size: u32) -> PyResult<ArmFlashStub> {
+    let elf = Elf::parse(data).map_err(py_error)?;
+    let mut options = ComposeOptions::new(name);
+    options.default = default;
+    options.ram_size = ram_size;
+    arm::compose_stub(&elf, data, &options).map(|(stub, _)| stub).map_err(py_error)
+}
+
+/// The FlashDevice of an FLM as a dict, addresses in the sector table relative to the flash start.
+#[pyfunction]
+pub fn parse_flm<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    let elf = Elf::parse(data).map_err(py_error)?;
+    let device = FlashDevice::from_elf(&elf, data).map_err(py_error)?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("driver_version", device.driver_version())?;
+    dict.set_item("name", device.name())?;
+    dict.set_item("device_type", device.device_type())?;
+    dict.set_item("start_address", device.start_address())?;
+    dict.set_item("device_size", device.device_size())?;
+    dict.set_item("page_size", device.page_size())?;
+    dict.set_item("erased_default_value", device.erased_default_value())?;
+    dict.set_item("program_page_timeout", device.program_page_timeout())?;
+    dict.set_item("erase_sector_timeout", device.erase_sector_timeout())?;
+    let sectors: Vec<(u32, u32)> = device.sectors().iter().map(|sector| (sector.address, sector.size)).collect();
+    dict.set_item("sectors", sectors)?;
+    Ok(dict)
+}
+
+/// Converts an FLM into the stub model as a dict, with the same keys as the JSON output.
+#[pyfunction]
+#[pyo3(signature = (data, name = "flash".to_string(), default = false, ram_size = 0))]
+pub fn compose_stub<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    name: String,
+    default: bool,
+    ram_size: u32,
+) -> PyResult<Bound<'py, PyAny>> {
+    let stub = compose(data, name, default, ram_size)?;
+    let value = serde_json::to_value(&stub).map_err(py_error)?;
+    to_python(py, &value)
+}
+
+/// Converts an FLM into `format`, one of the `--output-format` names of the command line.
+#[pyfunction]
+#[pyo3(signature = (data, format = "json", name = "flash".to_string(), default = false, ram_size = 0))]
+pub fn export<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    format: &str,
+    name: String,
+    default: bool,
+    ram_size: u32,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let format: OutputFormat = format.parse().map_err(py_error)?;
+    let stub = compose(data, name, default, ram_size)?;
+    let output = export_stub(&stub, format).map_err(py_error)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+#[pymodule]
+#[pyo3(name = "soul_composer")]
+fn soul_composer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("SoulComposerError", m.py().get_type::<SoulComposerError>())?;
+    m.add_function(wrap_pyfunction!(parse_flm, m)?)?;
+    m.add_function(wrap_pyfunction!(compose_stub, m)?)?;
+    m.add_function(wrap_pyfunction!(export, m)?)?;
+    Ok(())
+}
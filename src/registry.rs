@@ -0,0 +1,79 @@
+//! Converted stubs shared between threads, for server mode and GUI frontends answering many
+//! queries at once.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::prog::{
+    arm::flash_stub_gen::ArmFlashStub,
+    export::{export_error::ExportError, export_model, OutputFormat},
+};
+
+type Devices = BTreeMap<String, Arc<[ArmFlashStub]>>;
+
+/// The stubs of each device, keyed by device name.
+///
+/// Clones share the same registry. Lookups hand out the stored list behind an `Arc`, so readers
+/// never hold the lock while serializing and a replaced list stays valid for whoever has it.
+#[derive(Debug, Clone, Default)]
+pub struct StubRegistry {
+    devices: Arc<RwLock<Devices>>,
+}
+
+impl StubRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Every write replaces a whole entry, a panicking writer can't leave a half updated map.
+    fn read(&self) -> RwLockReadGuard<'_, Devices> {
+        self.devices.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Devices> {
+        self.devices.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stores the stubs of `device`, returning the ones it replaces.
+    pub fn insert(&self, device: impl Into<String>, stubs: Vec<ArmFlashStub>) -> Option<Arc<[ArmFlashStub]>> {
+        self.write().insert(device.into(), stubs.into())
+    }
+
+    /// The stubs of `device`, if any were stored.
+    pub fn get(&self, device: &str) -> Option<Arc<[ArmFlashStub]>> {
+        self.read().get(device).cloned()
+    }
+
+    pub fn remove(&self, device: &str) -> Option<Arc<[ArmFlashStub]>> {
+        self.write().remove(device)
+    }
+
+    pub fn contains(&self, device: &str) -> bool {
+        self.read().contains_key(device)
+    }
+
+    /// Names of the devices with stubs, sorted.
+    pub fn devices(&self) -> Vec<String> {
+        self.read().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// A copy of every entry at one point in time.
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<ArmFlashStub>> {
+        self.read().iter().map(|(device, stubs)| (device.clone(), stubs.to_vec())).collect()
+    }
+
+    /// The snapshot as a device to stubs map in one of the model formats.
+    pub fn export_snapshot(&self, format: OutputFormat) -> Result<Vec<u8>, ExportError> {
+        export_model(&self.snapshot(), format)
+    }
+}
@@ -0,0 +1,89 @@
+//! Signing exported stubs, groups and manifests into bundles the programmer can check before it
+//! runs the loader code in them. The bundle layout and verification live in
+//! `soulcomposer_core::signed`, so the firmware verifies with the same code.
+//!
+//! Keys are Ed25519, kept in files as 64 hex digits: the secret seed for signing, the public key
+//! for verifying.
+
+use std::fmt::Write;
+
+use ed25519_dalek::{Signer, SigningKey};
+use thiserror::Error;
+
+pub use soulcomposer_core::signed::{
+    signed_message, verify_bundle, BundleError, SignedBundle, PUBLIC_KEY_SIZE, SIGNATURE_SIZE,
+};
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error("Invalid key, {0}")]
+    Key(String),
+
+    #[error("Failed to generate a key, {0}")]
+    Random(getrandom::Error),
+
+    #[error("Payload of {0} bytes is too large to sign, at most 4GB fits a bundle")]
+    TooLarge(usize),
+
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+}
+
+/// A new signing key from the operating system's random source.
+pub fn generate_key() -> Result<SigningKey, SigningError> {
+    let mut seed = [0; 32];
+    getrandom::getrandom(&mut seed).map_err(SigningError::Random)?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// `bytes` as lowercase hex, the way keys are stored.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Reads 32 bytes written as 64 hex digits, ignoring surrounding whitespace.
+fn parse_key_bytes(text: &str) -> Result<[u8; 32], SigningError> {
+    let text = text.trim();
+    if text.len() != 64 || !text.is_ascii() {
+        return Err(SigningError::Key(format!("expected 64 hex digits, found {} characters", text.chars().count())));
+    }
+
+    let mut key = [0; 32];
+    for (byte, digits) in key.iter_mut().zip(text.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).unwrap_or_default();
+        *byte = u8::from_str_radix(digits, 16).map_err(|_| SigningError::Key(format!("{:?} isn't hex", digits)))?;
+    }
+    Ok(key)
+}
+
+/// A signing key from the hex seed in a key file.
+pub fn parse_signing_key(text: &str) -> Result<SigningKey, SigningError> {
+    parse_key_bytes(text).map(|seed| SigningKey::from_bytes(&seed))
+}
+
+/// A trusted public key from a key file, checked to be a valid Ed25519 key.
+pub fn parse_public_key(text: &str) -> Result<[u8; PUBLIC_KEY_SIZE], SigningError> {
+    let key = parse_key_bytes(text)?;
+    ed25519_dalek::VerifyingKey::from_bytes(&key).map_err(|_| SigningError::Key("not a valid Ed25519 public key".to_string()))?;
+    Ok(key)
+}
+
+/// Wraps `payload` in a bundle signed with `key`.
+pub fn sign(payload: &[u8], key: &SigningKey) -> Result<Vec<u8>, SigningError> {
+    if payload.len() > u32::MAX as usize {
+        return Err(SigningError::TooLarge(payload.len()));
+    }
+
+    let mut bundle = signed_message(&key.verifying_key().to_bytes(), payload);
+    let signature = key.sign(&bundle);
+    bundle.extend_from_slice(&signature.to_bytes());
+    Ok(bundle)
+}
+
+/// Checks that one of `trusted` signed `bundle`, returning the payload.
+pub fn verify<'a>(bundle: &'a [u8], trusted: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<&'a [u8], SigningError> {
+    Ok(verify_bundle(bundle, trusted)?)
+}
@@ -0,0 +1,366 @@
+//! Synthetic FLMs, descriptor blobs and golden files for tests, here and in downstream crates,
+//! so nobody has to redistribute vendor binaries to exercise parsing. Enabled by the `testing`
+//! feature.
+//!
+//! `build_flm` gives the FLM most tests start from. `DescriptorFields` writes a `FlashDevice`
+//! struct with chosen fields, including broken ones, and `build_flm_with` links it into an FLM.
+//! `build_flm64` does the same for AArch64 with the LP64 layout of `to_lp64_bytes`.
+
+use std::{env, fs, path::Path};
+
+use crate::prog::arm::flash_device::SectorInfo;
+
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHF_WRITE: u32 = 1;
+pub const SHF_ALLOC: u32 = 2;
+pub const SHF_EXECINSTR: u32 = 4;
+
+/// One section of an image built by `build_elf`.
+pub struct TestSection {
+    pub name: &'static str,
+    pub typ: u32,
+    pub flags: u32,
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Builds an ELF32 little-endian image with the given sections and no symbols.
+pub fn build_elf(machine: u16, entry: u32, sections: &[TestSection]) -> Vec<u8> {
+    write_elf(false, machine, entry.into(), sections, 0)
+}
+
+/// Same as `build_elf` for ELF64, as AArch64 and RV64 loaders are.
+pub fn build_elf64(machine: u16, entry: u64, sections: &[TestSection]) -> Vec<u8> {
+    write_elf(true, machine, entry, sections, 0)
+}
+
+/// Writes a little-endian ELF of `sections`, with a PT_LOAD at address 0 over the first `loaded`
+/// of them when there are any. A symbol table links to the string table after it.
+fn write_elf(is_64: bool, machine: u16, entry: u64, sections: &[TestSection], loaded: usize) -> Vec<u8> {
+    let (header_size, program_header_size, section_header_size) = if is_64 { (64, 56, 64) } else { (52, 32, 40) };
+    // Address sized fields, the rest stay 32 bits in both classes.
+    let word = |elf: &mut Vec<u8>, value: u64| match is_64 {
+        true => elf.extend_from_slice(&value.to_le_bytes()),
+        false => elf.extend_from_slice(&(value as u32).to_le_bytes()),
+    };
+
+    let mut shstrtab = vec![0u8];
+    let mut names = Vec::new();
+    for name in sections.iter().map(|section| section.name).chain(Some(".shstrtab")) {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    let program_headers = usize::from(loaded > 0);
+    let mut elf = vec![0u8; header_size + program_headers * program_header_size];
+    let mut offsets = Vec::new();
+    for section in sections {
+        elf.resize((elf.len() + 3) & !3, 0);
+        offsets.push(elf.len() as u64);
+        if section.typ != SHT_NOBITS {
+            elf.extend_from_slice(&section.data);
+        }
+    }
+    let shstrtab_offset = elf.len() as u64;
+    elf.extend_from_slice(&shstrtab);
+    elf.resize((elf.len() + 7) & !7, 0);
+    let shoff = elf.len() as u64;
+
+    let symbol_size = if is_64 { 24 } else { 16 };
+    let mut header = |name: u32, typ: u32, flags: u32, address: u32, offset: u64, size: u64, link: u32, entsize: u64| {
+        elf.extend_from_slice(&name.to_le_bytes());
+        elf.extend_from_slice(&typ.to_le_bytes());
+        word(&mut elf, flags.into());
+        word(&mut elf, address.into());
+        word(&mut elf, offset);
+        word(&mut elf, size);
+        elf.extend_from_slice(&link.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        word(&mut elf, 1);
+        word(&mut elf, entsize);
+    };
+    header(0, 0, 0, 0, 0, 0, 0, 0);
+    for (index, section) in sections.iter().enumerate() {
+        let (link, entsize) = if section.typ == SHT_SYMTAB { (index as u32 + 2, symbol_size) } else { (0, 0) };
+        header(names[index], section.typ, section.flags, section.address, offsets[index], section.data.len() as u64, link, entsize);
+    }
+    header(names[sections.len()], SHT_STRTAB, 0, 0, shstrtab_offset, shstrtab.len() as u64, 0, 0);
+
+    if loaded > 0 {
+        let load_size = offsets[loaded - 1] - offsets[0] + sections[loaded - 1].data.len() as u64;
+        let mut program_header = Vec::new();
+        program_header.extend_from_slice(&1u32.to_le_bytes());
+        if is_64 {
+            program_header.extend_from_slice(&7u32.to_le_bytes());
+        }
+        for value in [offsets[0], 0, 0, load_size, load_size] {
+            word(&mut program_header, value);
+        }
+        if !is_64 {
+            program_header.extend_from_slice(&7u32.to_le_bytes());
+        }
+        word(&mut program_header, 4);
+        elf[header_size..header_size + program_header_size].copy_from_slice(&program_header);
+    }
+
+    let mut fields = Vec::new();
+    fields.extend_from_slice(&2u16.to_le_bytes());
+    fields.extend_from_slice(&machine.to_le_bytes());
+    fields.extend_from_slice(&1u32.to_le_bytes());
+    word(&mut fields, entry);
+    word(&mut fields, if loaded > 0 { header_size as u64 } else { 0 });
+    word(&mut fields, shoff);
+    fields.extend_from_slice(&0u32.to_le_bytes());
+    for value in [header_size, program_header_size, program_headers, section_header_size, sections.len() + 2, sections.len() + 1] {
+        fields.extend_from_slice(&(value as u16).to_le_bytes());
+    }
+
+    elf[..4].copy_from_slice(b"\x7fELF");
+    elf[4] = if is_64 { 2 } else { 1 };
+    elf[5] = 1;
+    elf[6] = 1;
+    elf[16..header_size].copy_from_slice(&fields);
+    elf
+}
+
+/// A symbol table and its string table, symbols given as name, value, `st_info` and section index.
+fn symbol_table(is_64: bool, symbols: &[(&str, u64, u8, u16)]) -> (Vec<u8>, Vec<u8>) {
+    let mut strtab = vec![0u8];
+    let mut symtab = vec![0u8; if is_64 { 24 } else { 16 }];
+    for &(name, value, info, section) in symbols {
+        symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        if is_64 {
+            symtab.extend_from_slice(&[info, 0]);
+            symtab.extend_from_slice(&section.to_le_bytes());
+            symtab.extend_from_slice(&value.to_le_bytes());
+            symtab.extend_from_slice(&0u64.to_le_bytes());
+        } else {
+            symtab.extend_from_slice(&(value as u32).to_le_bytes());
+            symtab.extend_from_slice(&0u32.to_le_bytes());
+            symtab.extend_from_slice(&[info, 0]);
+            symtab.extend_from_slice(&section.to_le_bytes());
+        }
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+    (symtab, strtab)
+}
+
+/// Name of the test FLM's FlashDevice.
+pub const FLM_DEVICE_NAME: &str = "Test 192kB Flash";
+/// Code of the test FLM: `movs r0, #0; bx lr` for Init, UnInit, EraseChip, EraseSector and ProgramPage.
+pub const FLM_ENTRIES: [(&str, u32); 5] = [("Init", 0), ("UnInit", 4), ("EraseChip", 8), ("EraseSector", 12), ("ProgramPage", 16)];
+
+/// The fields of a `FlashDevice` struct, written out by `to_bytes` in the CMSIS layout.
+///
+/// `Default` is the descriptor of `build_flm`. Nothing is checked, so broken descriptors are
+/// as easy to write as good ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorFields {
+    pub driver_version: u16,
+    /// Cut to 128 bytes, a name of 128 bytes or more isn't terminated.
+    pub name: String,
+    pub device_type: u16,
+    pub start_address: u32,
+    pub device_size: u32,
+    pub page_size: u32,
+    pub erased_default_value: u8,
+    pub program_page_timeout: u32,
+    pub erase_sector_timeout: u32,
+    /// Sector table, addresses relative to the flash start.
+    pub sectors: Vec<SectorInfo>,
+    /// End the sector table with the `0xFFFFFFFF` marker.
+    pub end_marker: bool,
+}
+
+impl Default for DescriptorFields {
+    /// 192kB at 0x08000000, four 16kB sectors followed by two 64kB sectors, 256 byte pages and
+    /// 100/3000 ms timeouts.
+    fn default() -> Self {
+        Self {
+            driver_version: 0x0101,
+            name: FLM_DEVICE_NAME.to_string(),
+            device_type: 1,
+            start_address: 0x0800_0000,
+            device_size: 0x30000,
+            page_size: 256,
+            erased_default_value: 0xFF,
+            program_page_timeout: 100,
+            erase_sector_timeout: 3000,
+            sectors: vec![SectorInfo { address: 0, size: 0x4000 }, SectorInfo { address: 0x10000, size: 0x10000 }],
+            end_marker: true,
+        }
+    }
+}
+
+impl DescriptorFields {
+    /// The 160 byte struct followed by the sector table.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut device = vec![0u8; 160];
+        device[0..2].copy_from_slice(&self.driver_version.to_le_bytes());
+        let name = &self.name.as_bytes()[..self.name.len().min(128)];
+        device[2..2 + name.len()].copy_from_slice(name);
+        device[130..132].copy_from_slice(&self.device_type.to_le_bytes());
+        for (offset, value) in [
+            (132, self.start_address),
+            (136, self.device_size),
+            (140, self.page_size),
+            (152, self.program_page_timeout),
+            (156, self.erase_sector_timeout),
+        ] {
+            device[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        device[148] = self.erased_default_value;
+
+        for sector in &self.sectors {
+            device.extend_from_slice(&sector.size.to_le_bytes());
+            device.extend_from_slice(&sector.address.to_le_bytes());
+        }
+        if self.end_marker {
+            device.extend_from_slice(&[0xFF; 8]);
+        }
+        device
+    }
+
+    /// The struct as an LP64 compiler lays it out, 192 bytes with every `unsigned long` 64 bits
+    /// wide, followed by the sector table in 16 byte entries.
+    pub fn to_lp64_bytes(&self) -> Vec<u8> {
+        let mut device = vec![0u8; 192];
+        device[0..2].copy_from_slice(&self.driver_version.to_le_bytes());
+        let name = &self.name.as_bytes()[..self.name.len().min(128)];
+        device[2..2 + name.len()].copy_from_slice(name);
+        device[130..132].copy_from_slice(&self.device_type.to_le_bytes());
+        for (offset, value) in [
+            (136, self.start_address),
+            (144, self.device_size),
+            (152, self.page_size),
+            (176, self.program_page_timeout),
+            (184, self.erase_sector_timeout),
+        ] {
+            device[offset..offset + 8].copy_from_slice(&u64::from(value).to_le_bytes());
+        }
+        device[168] = self.erased_default_value;
+
+        for sector in &self.sectors {
+            device.extend_from_slice(&u64::from(sector.size).to_le_bytes());
+            device.extend_from_slice(&u64::from(sector.address).to_le_bytes());
+        }
+        // SECTOR_END is `0xFFFFFFFF, 0xFFFFFFFF`, zero extended.
+        if self.end_marker {
+            device.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0].repeat(2));
+        }
+        device
+    }
+}
+
+/// Builds a minimal CMSIS FLM: a `PrgCode` section with the `FLM_ENTRIES` functions and a
+/// `DevDscr` section holding the `DescriptorFields::default()` descriptor.
+pub fn build_flm() -> Vec<u8> {
+    build_flm_with(&DescriptorFields::default().to_bytes(), "FlashDevice")
+}
+
+/// Same as `build_flm`, with `descriptor` as the `DevDscr` section behind the symbol `symbol`.
+pub fn build_flm_with(descriptor: &[u8], symbol: &'static str) -> Vec<u8> {
+    build_flm_sections(descriptor, symbol, &[], None)
+}
+
+/// Same as `build_flm`, with a `PrgData` section holding `data` linked at `data_address`.
+pub fn build_flm_with_data(data: &[u8], data_address: u32) -> Vec<u8> {
+    build_flm_with_code(&[], data, data_address)
+}
+
+/// Same as `build_flm_with_data`, with `code` in `PrgCode` after the entry points. No `PrgData`
+/// when `data` is empty.
+pub fn build_flm_with_code(code: &[u8], data: &[u8], data_address: u32) -> Vec<u8> {
+    let prg_data = Some((data_address, data)).filter(|_| !data.is_empty());
+    build_flm_sections(&DescriptorFields::default().to_bytes(), "FlashDevice", code, prg_data)
+}
+
+fn build_flm_sections(descriptor: &[u8], symbol: &'static str, extra_code: &[u8], prg_data: Option<(u32, &[u8])>) -> Vec<u8> {
+    let mut code: Vec<u8> = FLM_ENTRIES.iter().flat_map(|_| vec![0x00, 0x20, 0x70, 0x47]).collect();
+    code.extend_from_slice(extra_code);
+
+    // STT_FUNC in PrgCode or STT_OBJECT in DevDscr, both global.
+    let device_address = code.len() as u32;
+    let mut symbols: Vec<_> = FLM_ENTRIES.iter().map(|&(name, offset)| (name, u64::from(offset | 1), 0x12, 1)).collect();
+    symbols.push((symbol, device_address.into(), 0x11, 2));
+    let (symtab, strtab) = symbol_table(false, &symbols);
+
+    let section = |name, typ, flags, address, data| TestSection { name, typ, flags, address, data };
+    let mut sections = vec![
+        section("PrgCode", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0, code),
+        section("DevDscr", SHT_PROGBITS, SHF_ALLOC, device_address, descriptor.to_vec()),
+    ];
+    // After DevDscr, so the symbols keep their section indices.
+    if let Some((address, data)) = prg_data {
+        sections.push(section("PrgData", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, address, data.to_vec()));
+    }
+    let loaded = sections.len();
+    sections.push(section(".symtab", SHT_SYMTAB, 0, 0, symtab));
+    sections.push(section(".strtab", SHT_STRTAB, 0, 0, strtab));
+
+    write_elf(false, 40, 0, &sections, loaded)
+}
+
+/// Code of the AArch64 test FLM: `mov w0, #0; ret` for each of `FLM_ENTRIES`, 8 bytes apart.
+pub const FLM64_ENTRY_SIZE: u32 = 8;
+
+/// Builds a minimal AArch64 FLM, `build_flm` for 64-bit cores, with `descriptor` as its
+/// `FlashDevice`, e.g. `DescriptorFields::to_lp64_bytes`.
+pub fn build_flm64(descriptor: &[u8]) -> Vec<u8> {
+    let code: Vec<u8> = FLM_ENTRIES.iter().flat_map(|_| [0x00, 0x00, 0x80, 0x52, 0xC0, 0x03, 0x5F, 0xD6]).collect();
+
+    let device_address = code.len() as u32;
+    let mut symbols: Vec<_> =
+        FLM_ENTRIES.iter().enumerate().map(|(index, &(name, _))| (name, u64::from(index as u32 * FLM64_ENTRY_SIZE), 0x12, 1)).collect();
+    symbols.push(("FlashDevice", device_address.into(), 0x11, 2));
+    let (symtab, strtab) = symbol_table(true, &symbols);
+
+    let section = |name, typ, flags, address, data| TestSection { name, typ, flags, address, data };
+    let sections = vec![
+        section("PrgCode", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0, code),
+        section("DevDscr", SHT_PROGBITS, SHF_ALLOC, device_address, descriptor.to_vec()),
+        section(".symtab", SHT_SYMTAB, 0, 0, symtab),
+        section(".strtab", SHT_STRTAB, 0, 0, strtab),
+    ];
+
+    write_elf(true, 183, 0, &sections, 2)
+}
+
+/// Set to rewrite golden files with the current output instead of comparing against them.
+pub const BLESS_VARIABLE: &str = "SOUL_COMPOSER_BLESS";
+
+/// Compares `actual` with the golden file at `path`, panicking with the first differing offset.
+///
+/// With `SOUL_COMPOSER_BLESS` set the file is written instead, a missing file fails like a
+/// differing one. Review the new file like any other change before committing it.
+pub fn assert_golden(path: impl AsRef<Path>, actual: &[u8]) {
+    let path = path.as_ref();
+    if env::var_os(BLESS_VARIABLE).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|err| panic!("can't create {}: {}", parent.display(), err));
+        }
+        fs::write(path, actual).unwrap_or_else(|err| panic!("can't write {}: {}", path.display(), err));
+        return;
+    }
+
+    if !path.exists() {
+        panic!("{} is missing, set {}=1 to create it", path.display(), BLESS_VARIABLE);
+    }
+    let expected = fs::read(path).unwrap_or_else(|err| panic!("can't read {}: {}", path.display(), err));
+    if expected != actual {
+        let offset = expected.iter().zip(actual).position(|(a, b)| a != b).unwrap_or(expected.len().min(actual.len()));
+        panic!(
+            "{} differs at byte {} ({} bytes expected, {} written), set {}=1 to update it",
+            path.display(),
+            offset,
+            expected.len(),
+            actual.len(),
+            BLESS_VARIABLE
+        );
+    }
+}
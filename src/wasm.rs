@@ -0,0 +1,34 @@
+//! Bindings for converting in the browser, so proprietary FLMs never have to leave the machine.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    prog::{
+        arm::flash_stub_gen::ArmFlashStub,
+        export::{export, OutputFormat},
+    },
+    utils,
+};
+
+fn js_error(err: impl ToString) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
+
+/// Converts an FLM into the stub JSON Soul Injector reads.
+///
+/// `ram_size` is the RAM of the target, 0 fills in what the algorithm needs.
+#[wasm_bindgen(js_name = convertFlm)]
+pub fn convert_flm(flm: &[u8], name: String, default: bool, ram_size: u32) -> Result<String, JsValue> {
+    let json = export_flm(flm, name, default, ram_size, OutputFormat::Json.name())?;
+    String::from_utf8(json).map_err(js_error)
+}
+
+/// Converts an FLM into `format`, one of the `--output-format` names of the command line.
+#[wasm_bindgen(js_name = exportFlm)]
+pub fn export_flm(flm: &[u8], name: String, default: bool, ram_size: u32, format: &str) -> Result<Vec<u8>, JsValue> {
+    utils::set_panic_hook();
+
+    let format: OutputFormat = format.parse().map_err(js_error)?;
+    let stub = ArmFlashStub::from_elf(flm, name, default, ram_size).map_err(js_error)?;
+    export(&stub, format).map_err(js_error)
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{build_elf, build_elf64, build_flm64, DescriptorFields, FLM64_ENTRY_SIZE};
+use soulcomposer::prog::aarch64::{
+    aarch64_error::Aarch64Error,
+    flash_stub_gen::{Aarch64FlashStub, Aarch64SectorRegion},
+};
+
+#[test]
+fn rejects_non_aarch64_elf() {
+    let result = Aarch64FlashStub::from_elf(&build_elf64(40, 0, &[]), "arm".to_string(), false, 0);
+    assert!(matches!(result, Err(Aarch64Error::WrongMachine(40))));
+}
+
+#[test]
+fn rejects_elf32_aarch64() {
+    let result = Aarch64FlashStub::from_elf(&build_elf(183, 0, &[]), "ilp32".to_string(), false, 0);
+    assert!(matches!(result, Err(Aarch64Error::NotElf64)));
+}
+
+#[test]
+fn aarch64_elf_without_descriptor() {
+    let result = Aarch64FlashStub::from_elf(&build_elf64(183, 0, &[]), "a53".to_string(), false, 0);
+    assert!(matches!(result, Err(Aarch64Error::Arm(_))));
+}
+
+#[test]
+fn parses_lp64_flash_device() {
+    let mut descriptor = DescriptorFields::default().to_lp64_bytes();
+    // A base above 4 GiB, only the start address may need the upper half.
+    descriptor[136 + 4] = 0x01;
+    let stub = Aarch64FlashStub::from_elf(&build_flm64(&descriptor), "a53".to_string(), false, 0).unwrap();
+
+    assert_eq!(stub.description, common::FLM_DEVICE_NAME);
+    assert_eq!((stub.flash_start_addr, stub.flash_end_addr), (0x1_0800_0000, 0x1_0803_0000));
+    assert_eq!(stub.flash_size, 0x30000);
+    assert_eq!(stub.flash_page_size, 256);
+    assert_eq!(stub.erased_byte_value, 0xFF);
+    assert_eq!((stub.program_timeout, stub.erase_timeout), (100, 3000));
+    assert_eq!(
+        stub.sectors,
+        [
+            Aarch64SectorRegion { address: 0x1_0800_0000, size: 0x4000, count: 4 },
+            Aarch64SectorRegion { address: 0x1_0801_0000, size: 0x10000, count: 2 },
+        ]
+    );
+
+    let offset = |name: &str| common::FLM_ENTRIES.iter().position(|(entry, _)| *entry == name).unwrap() as u32 * FLM64_ENTRY_SIZE;
+    assert_eq!(stub.pc_init, Some(offset("Init")));
+    assert_eq!(stub.pc_erase_sector, offset("EraseSector"));
+    assert_eq!(stub.pc_program_page, offset("ProgramPage"));
+}
+
+#[test]
+fn rejects_lp64_fields_past_32_bits() {
+    let mut descriptor = DescriptorFields::default().to_lp64_bytes();
+    descriptor[152 + 4] = 0x01;
+    let result = Aarch64FlashStub::from_elf(&build_flm64(&descriptor), "a53".to_string(), false, 0);
+    assert!(matches!(result, Err(Aarch64Error::DescriptorOverflow { field: "szPage", value: 0x1_0000_0100 })));
+}
@@ -0,0 +1,14 @@
+use soulcomposer::prog::arm::flash_stub_gen::{AddressTranslation, ArmFlashStub};
+
+#[test]
+fn translates_xip_addresses() {
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0x9000_0000;
+    stub.flash_end_addr = 0x9100_0000;
+    stub.address_translation = Some(AddressTranslation { mask: 0x0FFF_FFFF, offset: 0 });
+
+    assert_eq!(stub.flash_range(), 0x9000_0000..0x9100_0000);
+    assert_eq!(stub.program_address(0x9000_1000), 0x1000);
+    assert_eq!(stub.mapped_address(0x1000), 0x9000_1000);
+    assert_eq!(ArmFlashStub::default().program_address(0x0800_0000), 0x0800_0000);
+}
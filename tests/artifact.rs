@@ -0,0 +1,167 @@
+#![cfg(feature = "artifact")]
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use soulcomposer::artifact::{ArtifactError, ArtifactUploader, UploadMethod};
+
+/// A request the repository got: method, path and the headers that matter here.
+#[derive(Debug, Clone, Default)]
+struct Received {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    checksum: Option<String>,
+}
+
+#[derive(Default)]
+struct Repository {
+    files: HashMap<String, Vec<u8>>,
+    requests: Vec<Received>,
+    /// Uploads answered 503 before one is taken.
+    unavailable: usize,
+    /// Returns something else than what was uploaded.
+    corrupt: bool,
+}
+
+type Shared = Arc<Mutex<Repository>>;
+
+/// A raw HTTP repository keeping uploads in memory.
+fn fake_repository(repository: Repository) -> (String, Shared) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let shared = Arc::new(Mutex::new(repository));
+    let state = shared.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let state = state.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    let mut parts = line.split_whitespace();
+                    let mut received = Received {
+                        method: parts.next().unwrap().to_string(),
+                        path: parts.next().unwrap().to_string(),
+                        ..Default::default()
+                    };
+
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        let (name, value) = header.split_once(':').unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => length = value.trim().parse().unwrap(),
+                            "authorization" | "x-jfrog-art-api" => received.authorization = Some(value.trim().to_string()),
+                            "x-checksum-sha256" => received.checksum = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let mut repository = state.lock().unwrap();
+                    repository.requests.push(received.clone());
+                    let (status, body) = match received.method.as_str() {
+                        "PUT" | "POST" if repository.unavailable > 0 => {
+                            repository.unavailable -= 1;
+                            (503, Vec::new())
+                        }
+                        "PUT" | "POST" => {
+                            repository.files.insert(received.path, body);
+                            (201, Vec::new())
+                        }
+                        _ => match repository.files.get(&received.path) {
+                            Some(_) if repository.corrupt => (200, b"garbage".to_vec()),
+                            Some(data) => (200, data.clone()),
+                            None => (404, Vec::new()),
+                        },
+                    };
+                    drop(repository);
+                    write!(stream, "HTTP/1.1 {} Status\r\nContent-Length: {}\r\n\r\n", status, body.len()).unwrap();
+                    stream.write_all(&body).unwrap();
+                }
+            });
+        }
+    });
+
+    (format!("http://{}", address), shared)
+}
+
+#[test]
+fn uploads_and_verifies_below_the_base_url() {
+    let (url, repository) = fake_repository(Repository::default());
+    let uploader = ArtifactUploader::new(&format!("{}/stubs-local/", url)).unwrap().bearer("token");
+
+    let artifact = uploader.upload("nightly/STM32 F4.json", b"{}", "application/json").unwrap();
+    assert_eq!(artifact.url, format!("{}/stubs-local/nightly/STM32%20F4.json", url));
+    assert_eq!(artifact.sha256, "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a");
+    assert_eq!((artifact.size, artifact.attempts), (2, 1));
+
+    let repository = repository.lock().unwrap();
+    assert_eq!(repository.files["/stubs-local/nightly/STM32%20F4.json"], b"{}");
+    let methods: Vec<&str> = repository.requests.iter().map(|request| request.method.as_str()).collect();
+    assert_eq!(methods, ["PUT", "GET"]);
+    assert_eq!(repository.requests[0].authorization.as_deref(), Some("Bearer token"));
+    assert_eq!(repository.requests[0].checksum.as_deref(), Some(artifact.sha256.as_str()));
+}
+
+#[test]
+fn retries_unavailable_repositories() {
+    let (url, repository) = fake_repository(Repository { unavailable: 2, ..Default::default() });
+    let uploader = ArtifactUploader::new(&url)
+        .unwrap()
+        .method(UploadMethod::Post)
+        .header("X-JFrog-Art-Api", "key")
+        .retries(2, Duration::from_millis(1))
+        .verify(false);
+
+    let artifact = uploader.upload("a.bin", &[1, 2, 3], "application/octet-stream").unwrap();
+    assert_eq!(artifact.attempts, 3);
+    let repository = repository.lock().unwrap();
+    assert!(repository.requests.iter().all(|request| request.method == "POST" && request.authorization.as_deref() == Some("key")));
+    assert_eq!(repository.files["/a.bin"], [1, 2, 3]);
+
+    let (url, _) = fake_repository(Repository { unavailable: 2, ..Default::default() });
+    let uploader = ArtifactUploader::new(&url).unwrap().retries(1, Duration::from_millis(1));
+    let err = uploader.upload("a.bin", &[1, 2, 3], "application/octet-stream").unwrap_err();
+    assert!(matches!(err, ArtifactError::Status { status: 503, .. }));
+}
+
+#[test]
+fn reports_checksum_mismatches() {
+    let (url, repository) = fake_repository(Repository { corrupt: true, ..Default::default() });
+    let uploader = ArtifactUploader::new(&url).unwrap().basic("ci", "secret").retries(1, Duration::from_millis(1));
+
+    let err = uploader.upload("a.json", b"{}", "application/json").unwrap_err();
+    assert!(matches!(err, ArtifactError::ChecksumMismatch { .. }));
+    let repository = repository.lock().unwrap();
+    assert_eq!(repository.requests.len(), 4);
+    assert_eq!(repository.requests[0].authorization.as_deref(), Some("Basic Y2k6c2VjcmV0"));
+}
+
+#[test]
+fn rejects_invalid_urls_and_keeps_credentials_out_of_logs() {
+    for url in ["ftp://example.com", "https://", "example.com/repo"] {
+        assert!(matches!(ArtifactUploader::new(url), Err(ArtifactError::Url(_))), "{}", url);
+    }
+    let uploader = ArtifactUploader::new("https://example.com/repo").unwrap().bearer("hunter2");
+    assert!(!format!("{:?}", uploader).contains("hunter2"));
+}
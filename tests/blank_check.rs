@@ -0,0 +1,21 @@
+use soulcomposer::prog::arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+#[test]
+fn honours_zero_erased_value() {
+    let mut stub = ArmFlashStub::default();
+    stub.flash_page_size = 4;
+    stub.erased_byte_value = 0x00;
+
+    assert!(stub.is_blank(&stub.erased_page()));
+    assert!(!stub.is_blank(&[0xff; 4]));
+
+    let mut data = vec![0x12, 0x34, 0x56, 0x78, 0x9a];
+    stub.pad_to_page(&mut data);
+    assert_eq!(data, [0x12, 0x34, 0x56, 0x78, 0x9a, 0, 0, 0]);
+
+    assert!(stub.verify(&[0x12], &[0x12, 0x00, 0x00, 0x00]).is_ok());
+    assert!(matches!(
+        stub.verify(&[0x12], &[0x12, 0xff, 0x00, 0x00]),
+        Err(ArmError::VerifyMismatch { offset: 1, expected: 0x00, actual: 0xff })
+    ));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use soulcomposer::prog::arm::{
+    algorithm_binary::BlobLayout,
+    arm_error::ArmError,
+    flash_stub_gen::ArmFlashStub,
+    parse_options::ParseOptions,
+};
+
+const DATA: [u8; 6] = [0xD0, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5];
+
+/// Five 4 byte entry points make 20 bytes of code.
+const CODE_LENGTH: usize = 20;
+
+fn convert(data_address: u32, blob_layout: BlobLayout) -> Result<ArmFlashStub, ArmError> {
+    let flm = common::build_flm_with_data(&DATA, data_address);
+    let options = ParseOptions { blob_layout, ..Default::default() };
+    ArmFlashStub::from_elf_with_options(flm, "algo".to_string(), false, 0, &options)
+}
+
+#[test]
+fn unified_blob_keeps_the_linked_data_offset() {
+    let stub = convert(0x100, BlobLayout::default()).unwrap();
+    let blob = base64::decode(&stub.instructions).unwrap();
+
+    assert_eq!(stub.data_section_offset, 0x100);
+    assert_eq!(blob.len(), 0x100 + DATA.len());
+    assert!(blob[CODE_LENGTH..0x100].iter().all(|&byte| byte == 0));
+    assert_eq!(blob[0x100..], DATA);
+    assert_eq!(stub.data_instructions, None);
+}
+
+#[test]
+fn split_blobs_pad_the_code_up_to_the_data() {
+    let layout = BlobLayout { split: true, data_alignment: 0x40, padding: 0xFF };
+    let stub = convert(0x100, layout).unwrap();
+    let code = base64::decode(&stub.instructions).unwrap();
+
+    assert_eq!(stub.data_section_offset, 0x100);
+    assert_eq!(code.len(), 0x100);
+    assert!(code[CODE_LENGTH..].iter().all(|&byte| byte == 0xFF));
+    assert_eq!(base64::decode(stub.data_instructions.as_ref().unwrap()).unwrap(), DATA);
+    assert!(stub.verify_crc32().is_ok());
+}
+
+#[test]
+fn data_without_static_base_cannot_move() {
+    let layout = BlobLayout { split: true, data_alignment: 0x200, padding: 0 };
+    assert!(matches!(convert(0x100, layout), Err(ArmError::DataMisaligned { offset: 0x100, alignment: 0x200 })));
+}
+
+#[test]
+fn alignment_must_be_a_power_of_two() {
+    let layout = BlobLayout { split: true, data_alignment: 3, padding: 0 };
+    assert!(matches!(convert(0x100, layout), Err(ArmError::InvalidAlignment(3))));
+}
@@ -0,0 +1,21 @@
+use soulcomposer::prog::arm::build_attributes::{BuildAttributes, TAG_ABI_PCS_RW_DATA, TAG_CPU_ARCH, TAG_CPU_NAME};
+
+#[test]
+fn parses_file_attributes() {
+    let mut file = vec![1u8, 0, 0, 0, 0];
+    file.push(TAG_CPU_NAME as u8);
+    file.extend(b"Cortex-M4\0");
+    file.extend([TAG_CPU_ARCH as u8, 13, TAG_ABI_PCS_RW_DATA as u8, 2]);
+    let file_len = file.len() as u32;
+    file[1..5].copy_from_slice(&file_len.to_le_bytes());
+
+    let mut data = vec![b'A'];
+    data.extend((4 + 6 + file.len() as u32).to_le_bytes());
+    data.extend(b"aeabi\0");
+    data.extend(file);
+
+    let attributes = BuildAttributes::parse(&data).unwrap();
+    assert_eq!(attributes.string(TAG_CPU_NAME), Some("Cortex-M4"));
+    assert_eq!(attributes.integer(TAG_CPU_ARCH), Some(13));
+    assert_eq!(attributes.integer(TAG_ABI_PCS_RW_DATA), Some(2));
+}
@@ -0,0 +1,1151 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+
+use common::{build_elf, TestSection, SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+use soulcomposer::prog::arm::{flash_stub_gen::ArmFlashStub, stub_group::ArmFlashStubGroup};
+
+/// `movs r0, #0; bx lr` twice, for ProgramPage and EraseSector.
+const BLOB: [u8; 8] = [0x00, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47];
+
+const HEX: &str = ":020000042000DA\n:0800000000207047002070474A\n:00000001FF\n";
+
+const DESCRIPTOR: &str = r#"
+name = "raw"
+architecture = "arm"
+callingConvention = "aapcs"
+
+[flash]
+start = 0x08000000
+size = 0x10000
+pageSize = 256
+sectorSize = 0x1000
+
+[[entries]]
+name = "ProgramPage"
+offset = 0
+thumb = true
+arguments = ["address", "size", "buffer"]
+
+[[entries]]
+name = "EraseSector"
+offset = 4
+thumb = true
+arguments = ["address"]
+"#;
+
+fn workspace(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("loader.toml"), DESCRIPTOR).unwrap();
+    dir
+}
+
+fn soul_composer() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_soul-composer"))
+}
+
+#[test]
+fn converts_bin_with_descriptor() {
+    let dir = workspace("convert_bin");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    let status = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "loader.toml", "--clock", "48000000", "--default"])
+        .args(["--extra", "ticket=FW-1234", "--extra", "station=3"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stub: ArmFlashStub = serde_json::from_str(&fs::read_to_string(dir.join("algo.json")).unwrap()).unwrap();
+    assert_eq!(stub.name, "raw");
+    assert!(stub.default);
+    assert_eq!(stub.pc_program_page, 1);
+    assert_eq!(stub.pc_erase_sector, 5);
+    assert_eq!(stub.init_parameters.clock, 48_000_000);
+    assert_eq!(stub.sectors[0].count, 16);
+    assert_eq!(stub.extra["ticket"], "FW-1234");
+    assert_eq!(stub.extra["station"], 3);
+    assert!(stub.verify_crc32().is_ok());
+}
+
+#[test]
+fn converts_hex_with_name_and_output() {
+    let dir = workspace("convert_hex");
+    fs::write(dir.join("algo.hex"), HEX).unwrap();
+
+    let status = soul_composer()
+        .args(["convert", "algo.hex", "--descriptor", "loader.toml", "-n", "renamed", "-o", "stub.json"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stub: ArmFlashStub = serde_json::from_str(&fs::read_to_string(dir.join("stub.json")).unwrap()).unwrap();
+    assert_eq!(stub.name, "renamed");
+    assert_eq!(base64::decode(&stub.instructions).unwrap(), BLOB);
+}
+
+#[test]
+fn converts_from_stdin_to_stdout() {
+    let dir = workspace("convert_stdio");
+
+    let mut child = soul_composer()
+        .args(["convert", "-", "--descriptor", "loader.toml"])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(HEX.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stub: ArmFlashStub = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stub.name, "raw");
+    assert_eq!(base64::decode(&stub.instructions).unwrap(), BLOB);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[test]
+fn no_input_fills_in_missing_metadata() {
+    let dir = workspace("convert_no_input");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    let status = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "loader.toml", "--no-input"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stub: ArmFlashStub = serde_json::from_str(&fs::read_to_string(dir.join("algo.json")).unwrap()).unwrap();
+    assert_eq!(stub.description, "raw");
+    assert!(!stub.default);
+}
+
+#[test]
+fn output_format_picks_the_extension() {
+    let dir = workspace("convert_format");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    let status = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "loader.toml", "--output-format", "c-header"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::read_to_string(dir.join("algo.h")).unwrap().contains("static const uint8_t RAW_BLOB[8]"));
+}
+
+#[test]
+fn never_writes_over_the_input() {
+    let dir = workspace("convert_over_input");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    for args in [&["--output-format", "bin"][..], &["-o", "./algo.bin"][..]] {
+        let output = soul_composer()
+            .args(["convert", "algo.bin", "--descriptor", "loader.toml"])
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("is the input"));
+        assert_eq!(fs::read(dir.join("algo.bin")).unwrap(), BLOB);
+    }
+}
+
+#[test]
+fn project_config_sets_flag_defaults() {
+    let dir = workspace("config");
+    fs::write(dir.join("soul-composer.toml"), "output-format = \"yaml\"\noutput-dir = \"stubs\"\n\n[ram]\nsize = 0x8000\n").unwrap();
+    fs::create_dir_all(dir.join("algos")).unwrap();
+    fs::write(dir.join("algos/algo.bin"), BLOB).unwrap();
+
+    // Found from a subdirectory, with the output directory relative to the file.
+    let status = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "../loader.toml"])
+        .current_dir(dir.join("algos"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let stub: ArmFlashStub = serde_yaml::from_str(&fs::read_to_string(dir.join("stubs/algo.yaml")).unwrap()).unwrap();
+    assert_eq!(stub.ram_size, 0x8000);
+
+    // Flags still win.
+    let status = soul_composer()
+        .args(["convert", "algos/algo.bin", "--descriptor", "loader.toml", "--output-format", "json", "-o", "out.json"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(dir.join("out.json").is_file());
+
+    fs::write(dir.join("soul-composer.toml"), "output-format = \"wav\"\n").unwrap();
+    let output = soul_composer().args(["inspect", "algos/algo.bin"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("soul-composer.toml"));
+}
+
+#[test]
+fn raw_image_needs_descriptor() {
+    let dir = workspace("convert_no_descriptor");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    let output = soul_composer().args(["convert", "algo.bin"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--descriptor"));
+}
+
+#[test]
+fn rejects_non_ascii_hex_record() {
+    let dir = workspace("convert_hex_non_ascii");
+    fs::write(dir.join("algo.hex"), ":a\u{e9}0\n:00000001FF\n").unwrap();
+
+    let output = soul_composer().args(["convert", "algo.hex", "--descriptor", "loader.toml"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not a hex digit"), "{}", stderr);
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn raw_image_checks_ram_size() {
+    let dir = workspace("convert_bin_ram_size");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+
+    let output = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "loader.toml", "--ram-size", "64"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs 776 bytes of RAM"));
+
+    let status = soul_composer()
+        .args(["convert", "algo.bin", "--descriptor", "loader.toml", "--ram-size", "0x4000"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let stub: ArmFlashStub = serde_json::from_str(&fs::read_to_string(dir.join("algo.json")).unwrap()).unwrap();
+    assert_eq!(stub.ram_size, 0x4000);
+}
+
+#[test]
+fn raw_image_rejects_flash_past_4gib() {
+    let dir = workspace("convert_bin_flash_end");
+    fs::write(dir.join("algo.bin"), BLOB).unwrap();
+    fs::write(dir.join("loader.toml"), DESCRIPTOR.replace("start = 0x08000000", "start = 0xFFFF8000")).unwrap();
+
+    let output = soul_composer().args(["convert", "algo.bin", "--descriptor", "loader.toml"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("flash end 0x100008000 does not fit in 32 bits"));
+}
+
+#[test]
+fn inspects_flm() {
+    let dir = workspace("inspect");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+
+    let output = soul_composer().args(["inspect", "algo.flm"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains(common::FLM_DEVICE_NAME));
+    assert!(text.contains("0x08000000..0x08030000 (196608 bytes)"));
+    assert!(text.contains("program page 100 ms, erase sector 3000 ms"));
+    assert!(text.contains("  0x08010000       65536       2"));
+    assert!(text.contains("  ProgramPage   0x00000011"));
+
+    let output = soul_composer().args(["inspect", "algo.flm", "--hex-dump"]).current_dir(&dir).output().unwrap();
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.lines().any(|line| line.split_whitespace().skip(1).eq(["+0x0094", "erasedDefaultValue", "ff", "0xff"])));
+    assert!(text.contains(common::FLM_DEVICE_NAME));
+}
+
+#[test]
+fn disassembles_an_entry_point() {
+    let dir = workspace("disasm");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+
+    let output = soul_composer().args(["disasm", "algo.flm", "--function", "EraseSector"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("00 20       movs r0, #0"));
+    assert!(text.trim_end().ends_with("bx lr"));
+
+    let output = soul_composer().args(["disasm", "algo.flm", "--function", "Verify"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ProgramPage"));
+}
+
+#[test]
+fn batch_continues_past_failures() {
+    let dir = workspace("batch");
+    for path in ["packs/a/one.FLM", "packs/b/nested/two.flm", "packs/b/broken.flm"] {
+        fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+    }
+    fs::write(dir.join("packs/a/one.FLM"), common::build_flm()).unwrap();
+    fs::write(dir.join("packs/b/nested/two.flm"), common::build_flm()).unwrap();
+    fs::write(dir.join("packs/b/broken.flm"), b"not an elf").unwrap();
+
+    let output = soul_composer().args(["batch", "packs/**/*.flm", "-o", "out"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 converted, 1 failed"));
+    assert!(dir.join("out/a/one.json").is_file());
+    assert!(dir.join("out/b/nested/two.json").is_file());
+}
+
+#[test]
+fn batch_dry_run_writes_nothing() {
+    let dir = workspace("batch_dry_run");
+    fs::create_dir_all(dir.join("in")).unwrap();
+    fs::write(dir.join("in/a.flm"), common::build_flm()).unwrap();
+
+    let output = soul_composer().args(["batch", "in/*.flm", "-o", "out", "--dry-run"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "a".to_string(), false, 0).unwrap();
+    let json = serde_json::to_vec_pretty(&stub).unwrap();
+    let expected = format!("would write out/a.json: {} bytes, crc32 {:08x}", json.len(), crc32fast::hash(&json));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&expected));
+    assert!(!dir.join("out").exists());
+}
+
+/// Accepts one HTTP request on a free port, answering 200, and returns its address and the body.
+fn webhook_receiver() -> (String, thread::JoinHandle<serde_json::Value>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let receiver = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        serde_json::from_slice(&body).unwrap()
+    });
+    (address, receiver)
+}
+
+#[test]
+fn batch_notifies_configured_webhooks() {
+    let dir = workspace("batch_webhook");
+    fs::create_dir_all(dir.join("in")).unwrap();
+    fs::write(dir.join("in/a.flm"), common::build_flm()).unwrap();
+    fs::write(dir.join("in/broken.flm"), b"not an elf").unwrap();
+    let (address, receiver) = webhook_receiver();
+    fs::write(dir.join("soul-composer.toml"), format!("webhooks = [\"http://{}/hooks/stubs\"]\n", address)).unwrap();
+
+    let output = soul_composer().args(["batch", "in/*.flm", "-o", "out"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    let notice = receiver.join().unwrap();
+    assert_eq!(notice["event"], "batch.completed");
+    assert_eq!(notice["source"], "batch");
+    assert_eq!(notice["converted"], 1);
+    assert!(notice["failures"][0]["file"].as_str().unwrap().ends_with("broken.flm"));
+    assert!(notice["text"].as_str().unwrap().contains("1 converted, 1 failed"));
+}
+
+#[test]
+fn batch_uploads_stubs_to_an_artifact_repository() {
+    let dir = workspace("batch_artifact");
+    fs::create_dir_all(dir.join("in")).unwrap();
+    fs::write(dir.join("in/a.flm"), common::build_flm()).unwrap();
+    let (address, receiver) = webhook_receiver();
+    let config = format!("[artifact]\nurl = \"http://{}/stubs-local\"\nverify = false\n", address);
+    fs::write(dir.join("soul-composer.toml"), config).unwrap();
+
+    let output = soul_composer().args(["batch", "in/*.flm", "-o", "out"]).env("ARTIFACT_TOKEN", "token").current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stub = receiver.join().unwrap();
+    assert_eq!(stub["name"], "a");
+
+    // Nothing is listening any more, the upload fails the file.
+    let output = soul_composer().args(["batch", "in/*.flm", "-o", "out", "--upload-retries", "0"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("0 converted, 1 failed"));
+}
+
+#[test]
+fn diff_reports_changed_fields() {
+    let dir = workspace("diff");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+    let mut stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+    stub.flash_page_size = 512;
+    stub.sectors[1].count = 3;
+    stub.instructions = base64::encode([0x00, 0xBE]);
+    fs::write(dir.join("new.json"), serde_json::to_string(&stub).unwrap()).unwrap();
+
+    let same = soul_composer().args(["diff", "algo.flm", "algo.flm"]).current_dir(&dir).output().unwrap();
+    assert!(String::from_utf8_lossy(&same.stdout).contains("No differences"));
+
+    let output = soul_composer().args(["diff", "algo.flm", "new.json", "--exit-code"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = text.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert!(lines.contains(&vec!["flashPageSize", "256", "->", "512"]));
+    assert!(lines.contains(&vec!["sectors[1].count", "2", "->", "3"]));
+    assert!(lines.contains(&vec!["instructions.size", "20", "->", "2"]));
+    assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn merges_banks_into_a_group() {
+    let dir = workspace("merge");
+    let bank1 = ArmFlashStub::from_elf(common::build_flm(), "bank1".to_string(), false, 0).unwrap();
+    let mut bank2 = bank1.clone();
+    bank2.name = "bank2".to_string();
+    bank2.flash_start_addr = 0x0803_0000;
+    bank2.flash_end_addr = 0x0806_0000;
+    fs::write(dir.join("bank1.json"), serde_json::to_string(&bank1).unwrap()).unwrap();
+    fs::write(dir.join("bank2.json"), serde_json::to_string(&bank2).unwrap()).unwrap();
+
+    let status = soul_composer()
+        .args(["merge", "bank2.json", "bank1.json", "-o", "device.json"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let group: ArmFlashStubGroup = serde_json::from_str(&fs::read_to_string(dir.join("device.json")).unwrap()).unwrap();
+    assert_eq!(group.name, "device");
+    assert_eq!(group.route(0x0804_0000).unwrap().1.name, "bank2");
+    assert_eq!(group.banks[0].bank, 1);
+    assert_eq!(group.algorithms[group.banks[0].algorithm].name, "bank1");
+
+    let split = soul_composer()
+        .args(["merge", "bank1.json", "bank2.json", "--split-at", "0x08010000"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!split.status.success());
+}
+
+#[test]
+fn validate_honours_strictness() {
+    let dir = workspace("validate");
+    fs::create_dir_all(dir.join("stubs/nested")).unwrap();
+    fs::write(dir.join("stubs/good.flm"), common::build_flm()).unwrap();
+
+    let validate = |strictness: &str| {
+        soul_composer().args(["validate", "stubs", "--strictness", strictness]).current_dir(&dir).output().unwrap()
+    };
+    assert!(validate("strict").status.success());
+
+    let mut stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+    stub.erase_timeout = 0;
+    stub.crc32 = Some(stub.compute_crc32().unwrap());
+    fs::write(dir.join("stubs/nested/no_timeout.json"), serde_json::to_string(&stub).unwrap()).unwrap();
+    assert!(validate("normal").status.success());
+    let strict = validate("strict");
+    assert!(!strict.status.success());
+    assert!(String::from_utf8_lossy(&strict.stdout).contains("2 files checked, 1 errors, 0 warnings"));
+
+    stub.flash_page_size = 512;
+    fs::write(dir.join("stubs/nested/bad_crc.json"), serde_json::to_string(&stub).unwrap()).unwrap();
+    assert!(!validate("normal").status.success());
+    assert!(validate("lenient").status.success());
+}
+
+#[test]
+fn validate_emits_json_diagnostics() {
+    let dir = workspace("validate_json");
+    let mut stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+    stub.flash_page_size = 3000;
+    stub.crc32 = Some(stub.compute_crc32().unwrap());
+    fs::write(dir.join("odd_pages.json"), serde_json::to_string(&stub).unwrap()).unwrap();
+
+    let output = soul_composer()
+        .args(["validate", "odd_pages.json", "--message-format", "json"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let messages: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let diagnostic = messages.iter().find(|message| message["code"] == "sector-page-mismatch").unwrap();
+    assert_eq!(diagnostic["reason"], "diagnostic");
+    assert_eq!(diagnostic["severity"], "warning");
+    assert_eq!(diagnostic["file"], "odd_pages.json");
+    assert_eq!(diagnostic["offset"], 0x0800_0000);
+    assert_eq!(diagnostic["id"], "SC1014");
+
+    let summary = messages.last().unwrap();
+    assert_eq!(summary["reason"], "summary");
+    assert_eq!(summary["files"], 1);
+    assert_eq!(summary["errors"], 0);
+}
+
+const PACK_PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <vendor>Test</vendor>
+  <name>Test_DFP</name>
+  <devices>
+    <family Dfamily="Test Series" Dvendor="Test:0">
+      <processor Dcore="Cortex-M4" Dclock="64000000"/>
+      <device Dname="TEST192">
+        <algorithm name="CMSIS\Flash\TEST_192.FLM" start="0x08000000" size="0x30000" RAMstart="0x20000000" RAMsize="0x1000" default="1"/>
+      </device>
+    </family>
+  </devices>
+</package>"#;
+
+fn write_pack(path: PathBuf) {
+    let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    writer.start_file("Test.Test_DFP.pdsc", options).unwrap();
+    writer.write_all(PACK_PDSC.as_bytes()).unwrap();
+    writer.start_file("CMSIS/Flash/TEST_192.FLM", options).unwrap();
+    writer.write_all(&common::build_flm()).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn converts_algorithms_from_pack() {
+    let dir = workspace("pack");
+    write_pack(dir.join("Test.Test_DFP.1.0.0.pack"));
+
+    let status = soul_composer()
+        .args(["pack", "Test.Test_DFP.1.0.0.pack", "--device", "test192", "-o", "out"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stub: ArmFlashStub = serde_json::from_str(&fs::read_to_string(dir.join("out/TEST_192.json")).unwrap()).unwrap();
+    assert!(stub.default);
+    assert_eq!(stub.ram_size, 0x1000);
+    assert_eq!(stub.init_parameters.clock, 64_000_000);
+}
+
+#[test]
+fn describes_a_family_for_probe_rs() {
+    let dir = workspace("family");
+    write_pack(dir.join("Test.Test_DFP.1.0.0.pack"));
+
+    let output = soul_composer().args(["family", "Test.Test_DFP.1.0.0.pack"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let family: serde_yaml::Value = serde_yaml::from_slice(&fs::read(dir.join("Test_Series.yaml")).unwrap()).unwrap();
+    assert_eq!(family["name"], "Test Series");
+    assert_eq!(family["variants"][0]["name"], "TEST192");
+    assert_eq!(family["variants"][0]["flash_algorithms"][0], "TEST_192");
+    assert_eq!(family["flash_algorithms"][0]["name"], "TEST_192");
+
+    let status = soul_composer().args(["family", "Test.Test_DFP.1.0.0.pack", "--family", "Other"]).current_dir(&dir).status().unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn writes_esp_loader_files_and_commands() {
+    let dir = workspace("esp");
+    let sections = [
+        TestSection {
+            name: ".text",
+            typ: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            address: 0x4009_0000,
+            data: vec![0x36, 0x41, 0x00, 0x1D, 0xF0, 0x00],
+        },
+        TestSection { name: ".data", typ: SHT_PROGBITS, flags: SHF_ALLOC, address: 0x3FFE_0000, data: vec![1, 2, 3, 4] },
+    ];
+    fs::write(dir.join("loader.elf"), build_elf(94, 0x4009_0000, &sections)).unwrap();
+
+    let output = soul_composer().args(["esp", "loader.elf", "--chip", "ESP32-S3", "--port", "/dev/ttyUSB0"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stub: serde_json::Value = serde_json::from_slice(&fs::read(dir.join("loader.json")).unwrap()).unwrap();
+    assert_eq!(stub["entry"], 0x4009_0000);
+    assert!(fs::read_to_string(dir.join("loader.toml")).unwrap().contains("text_start = 1074331648"));
+    assert_eq!(fs::read(dir.join("loader.bin")).unwrap()[0], 0xE9);
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("esptool.py --chip esp32s3 --port /dev/ttyUSB0 --no-stub load_ram loader.bin"), "{}", text);
+    assert!(text.contains("espflash flash --chip esp32s3 --port /dev/ttyUSB0 --ram loader.elf"), "{}", text);
+
+    // --run hands the same arguments to the tool.
+    let output = soul_composer().args(["esp", "loader.elf", "--chip", "esp32", "--run", "--esptool", "echo"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("\n--chip esp32 --no-stub load_ram loader.bin\n"));
+
+    // An Xtensa loader is not one for the RISC-V chips.
+    let status = soul_composer().args(["esp", "loader.elf", "--chip", "esp32c3"]).current_dir(&dir).status().unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn writes_a_jlink_script_and_its_files() {
+    let dir = workspace("jlink");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+
+    let output = soul_composer().args(["jlink", "algo.flm", "--chip-ram", "0x20000000:64k", "--jlink-device", "STM32F407VG"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let script = fs::read_to_string(dir.join("algo.jlink")).unwrap();
+    assert!(script.contains("\ndevice STM32F407VG\n"), "{}", script);
+    assert!(script.contains("\nloadbin algo.bin, 0x20000000\n"), "{}", script);
+    assert!(script.contains("\nverifybin algo.page.bin, "), "{}", script);
+    assert!(dir.join("algo.bin").exists() && dir.join("algo.page.bin").exists());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("JLinkExe -CommandFile algo.jlink"));
+
+    let status = soul_composer().args(["jlink", "algo.flm", "--chip-ram", "0x20000000:256"]).current_dir(&dir).status().unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn writes_and_checks_a_usb_drive_layout() {
+    let dir = workspace("msc");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+
+    let output = soul_composer().args(["msc", "algo.flm", "-o", "drive"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(dir.join("drive/soul/manifest.json")).unwrap()).unwrap();
+    let path = manifest["files"][0]["path"].as_str().unwrap();
+    assert!(path.starts_with("soul/stubs/"), "{}", path);
+    assert!(dir.join("drive").join(path).exists());
+    assert!(dir.join("drive/soul/SHA256SUMS").exists());
+
+    let check = || soul_composer().args(["msc", "--check", "drive"]).current_dir(&dir).output().unwrap();
+    assert!(check().status.success());
+    fs::write(dir.join("drive").join(path), b"{}").unwrap();
+    let output = check();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains(path));
+}
+
+#[cfg(feature = "database")]
+#[test]
+fn stores_pack_stubs_in_a_database() {
+    let dir = workspace("pack-database");
+    write_pack(dir.join("Test.Test_DFP.1.0.0.pack"));
+
+    let status = soul_composer()
+        .args(["pack", "Test.Test_DFP.1.0.0.pack", "--device", "test192", "--database", "stubs.db"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!dir.join("TEST_192.json").exists());
+
+    let output = soul_composer().args(["db", "stubs.db", "--address", "0x08010000"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("TEST192") && text.contains("TEST_192"), "{}", text);
+
+    let output = soul_composer().args(["db", "stubs.db", "--vendor", "nobody", "--json"]).current_dir(&dir).output().unwrap();
+    assert_eq!(serde_json::from_slice::<Vec<ArmFlashStub>>(&output.stdout).unwrap(), []);
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn signs_and_verifies_with_configured_keys() {
+    let dir = workspace("sign");
+    fs::write(dir.join("stub.json"), b"{}").unwrap();
+    fs::create_dir_all(dir.join("keys")).unwrap();
+    fs::write(dir.join("soul-composer.toml"), "signing-key = \"keys/release\"\n").unwrap();
+
+    let keygen = |name: &str| soul_composer().args(["keygen", name]).current_dir(&dir).output().unwrap();
+    assert!(keygen("keys/release").status.success());
+    assert!(keygen("keys/other").status.success());
+    assert!(!keygen("keys/release").status.success(), "keygen replaced an existing key");
+
+    // The key comes from the configuration.
+    let status = soul_composer().args(["sign", "stub.json"]).current_dir(&dir).status().unwrap();
+    assert!(status.success());
+
+    let verify = |key: &str| {
+        soul_composer().args(["verify", "stub.json.signed", "--key", key, "-o", "-"]).current_dir(&dir).output().unwrap()
+    };
+    let output = verify("keys/release.pub");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"{}");
+
+    let output = verify("keys/other.pub");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("untrusted key"));
+}
+
+#[cfg(feature = "ota")]
+#[test]
+fn writes_signed_ota_manifests() {
+    let dir = workspace("ota");
+    fs::write(dir.join("library.json"), b"[]").unwrap();
+    assert!(soul_composer().args(["keygen", "release"]).current_dir(&dir).status().unwrap().success());
+    assert!(soul_composer().args(["sign", "library.json", "--key", "release"]).current_dir(&dir).status().unwrap().success());
+
+    let url = "https://updates.example.com/library.json.signed";
+    let ota = |bundle: &str| {
+        soul_composer()
+            .args(["ota", bundle, "--version", "1.4.0", "--url", url, "--model", "soul-injector-s3", "--key", "release"])
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    let output = ota("library.json.signed");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let printed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(printed["url"], url);
+
+    let output = soul_composer().args(["verify", "ota.json.signed", "--key", "release.pub", "-o", "-"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap(), printed);
+
+    // An unsigned library isn't something programmers would install.
+    assert!(!ota("library.json").status.success());
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypts_with_configured_keys() {
+    let dir = workspace("encrypt");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+    fs::create_dir_all(dir.join("keys")).unwrap();
+    fs::write(dir.join("keys/fleet.key"), "11".repeat(32)).unwrap();
+    fs::write(dir.join("keys/TEST192.key"), "22".repeat(32)).unwrap();
+    fs::write(dir.join("soul-composer.toml"), "encryption-keys = \"keys\"\n").unwrap();
+
+    let encrypt = |extra: &[&str], output: &str| {
+        let status =
+            soul_composer().args(["encrypt", "algo.flm", "-o", output]).args(extra).current_dir(&dir).status().unwrap();
+        assert!(status.success());
+        serde_json::from_slice::<ArmFlashStub>(&fs::read(dir.join(output)).unwrap()).unwrap()
+    };
+    let fleet = encrypt(&[], "fleet.json");
+    assert_eq!(fleet.encryption.unwrap().key_id, "fleet");
+    let device = encrypt(&["--device", "test192"], "device.json");
+    assert_eq!(device.encryption.unwrap().key_id, "TEST192");
+
+    let output = soul_composer().args(["decrypt", "device.json"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+    let decrypted: ArmFlashStub = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(decrypted.encryption.is_none());
+    assert_eq!(base64::decode(&decrypted.instructions).unwrap().len() + 16, base64::decode(&device.instructions).unwrap().len());
+
+    fs::write(dir.join("keys/TEST192.key"), "33".repeat(32)).unwrap();
+    let output = soul_composer().args(["decrypt", "device.json"]).current_dir(&dir).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn syncs_the_stub_library_through_git() {
+    let dir = workspace("sync");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+    assert!(Command::new("git").args(["init", "-q", "--bare", "library.git"]).current_dir(&dir).status().unwrap().success());
+    fs::write(dir.join("soul-composer.toml"), "[sync]\nremote = \"library.git\"\ncheckout = \"site-a\"\n").unwrap();
+
+    let output = soul_composer().args(["sync", "push", "algo.flm", "--device", "TEST192"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"+ TEST192\n");
+
+    let output = soul_composer().args(["sync", "pull", "--checkout", "site-b"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"+ TEST192\n");
+    let stubs: Vec<ArmFlashStub> = serde_json::from_slice(&fs::read(dir.join("site-b/TEST192.json")).unwrap()).unwrap();
+    assert_eq!(stubs.len(), 1);
+
+    let status = soul_composer().args(["sync", "push", "algo.flm"]).current_dir(&dir).status().unwrap();
+    assert!(!status.success(), "pushed algorithms without a device");
+}
+
+#[cfg(feature = "announce")]
+#[test]
+fn announces_stubs_to_the_configured_broker() {
+    use soulcomposer::mqtt::Packet;
+
+    let dir = workspace("announce");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let broker = listener.local_addr().unwrap();
+    fs::write(dir.join("soul-composer.toml"), format!("[mqtt]\nbroker = \"{}\"\ntopic-prefix = \"line-3\"\n", broker)).unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut topics = Vec::new();
+        loop {
+            let reply = match Packet::read_from(&mut stream).unwrap() {
+                Packet::Connect { .. } => Packet::ConnAck { session_present: false, code: 0 },
+                Packet::Publish { topic, packet_id: Some(packet_id), retain, .. } => {
+                    topics.push((topic, retain));
+                    Packet::PubAck { packet_id }
+                }
+                _ => break topics,
+            };
+            stream.write_all(&reply.encode().unwrap()).unwrap();
+        }
+    });
+
+    let output = soul_composer().args(["announce", "algo.flm", "--device", "TEST192"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let topics = server.join().unwrap();
+    assert_eq!(topics.len(), 2);
+    assert!(topics[0].0.starts_with("line-3/devices/TEST192/"));
+    assert!(topics[0].1);
+    assert_eq!(topics[1], ("line-3/updates".to_string(), false));
+
+    let status = soul_composer().args(["announce", "algo.flm"]).current_dir(&dir).status().unwrap();
+    assert!(!status.success(), "announced algorithms without a device");
+}
+
+#[test]
+fn searches_cached_pdsc_files() {
+    let dir = workspace("search");
+    fs::create_dir_all(dir.join("cache/pdsc")).unwrap();
+    fs::write(dir.join("cache/pdsc/Test.Test_DFP.pdsc"), PACK_PDSC).unwrap();
+
+    let output = soul_composer().args(["search", "test1", "--cache", "cache"]).current_dir(&dir).output().unwrap();
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    assert_eq!(fields, ["TEST192", "Test.Test_DFP", "?", "TEST_192.FLM"]);
+
+    let missing = soul_composer().args(["search", "nrf52", "--cache", "cache"]).current_dir(&dir).output().unwrap();
+    assert!(!missing.status.success());
+}
+
+#[test]
+fn watch_reconverts_on_change() {
+    let dir = workspace("watch");
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+
+    let mut child = soul_composer()
+        .args(["convert", "algo.flm", "--watch"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let wait_for = |prefix: &str| loop {
+        let line = lines.recv_timeout(Duration::from_secs(10)).expect("watch mode went quiet");
+        if line.starts_with(prefix) {
+            break line;
+        }
+    };
+
+    wait_for("Watching");
+    assert!(dir.join("algo.json").is_file());
+
+    fs::write(dir.join("algo.flm"), b"truncated").unwrap();
+    assert!(wait_for("Changed").ends_with("algo.flm"));
+    assert!(wait_for("error").contains("--descriptor"));
+
+    fs::write(dir.join("algo.flm"), common::build_flm()).unwrap();
+    wait_for("Changed");
+    wait_for("Wrote");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+/// Sends one request and returns the status code and body.
+fn http(address: &str, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", method, path, address, body.len());
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(body).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let split = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+    let status = String::from_utf8_lossy(&response[9..12]).parse().unwrap();
+    (status, response[split + 4..].to_vec())
+}
+
+/// Starts `serve` on a free port in `dir`, returning it and its address.
+fn serve(dir: &Path) -> (Child, String) {
+    let mut child = soul_composer()
+        .args(["serve", "--port", "0", "--cache", "cache"])
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let address = line.trim().trim_start_matches("Listening on http://").to_string();
+    (child, address)
+}
+
+#[test]
+fn serves_conversions_over_http() {
+    let dir = workspace("serve");
+    let (mut child, address) = serve(&dir);
+
+    let (status, body) = http(&address, "POST", "/convert?name=algo&format=json", &common::build_flm());
+    assert_eq!(status, 200);
+    let stub: ArmFlashStub = serde_json::from_slice(&body).unwrap();
+    assert_eq!(stub.name, "algo");
+    assert_eq!(stub.flash_size, 0x30000);
+
+    write_pack(dir.join("test.pack"));
+    let (status, body) = http(&address, "POST", "/packs", &fs::read(dir.join("test.pack")).unwrap());
+    assert_eq!(status, 200);
+    let pack: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(pack["devices"][0], "TEST192");
+    let id = pack["id"].as_str().unwrap();
+    let sha256: String = Sha256::digest(fs::read(dir.join("test.pack")).unwrap()).iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(id, sha256);
+    assert!(dir.join("cache/packs").join(format!("{}.pack", id)).is_file());
+    let (status, _) = http(&address, "POST", "/packs", b"PK\x03\x04 not a pack");
+    assert_eq!(status, 422);
+    assert_eq!(fs::read_dir(dir.join("cache/packs")).unwrap().count(), 1);
+
+    let (status, body) = http(&address, "GET", &format!("/packs/{}/devices/test192", id), &[]);
+    assert_eq!(status, 200);
+    let stubs: Vec<ArmFlashStub> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(stubs[0].name, "TEST_192");
+    let (status, body) = http(&address, "GET", "/devices/test192", &[]);
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_slice::<Vec<ArmFlashStub>>(&body).unwrap(), stubs);
+    let (status, body) = http(&address, "GET", "/devices", &[]);
+    assert_eq!(status, 200);
+    assert!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["test192"].is_array());
+
+    let (status, _) = http(&address, "POST", "/convert?format=wav", &common::build_flm());
+    assert_eq!(status, 400);
+    let (status, _) = http(&address, "GET", "/packs/0000/devices/test192", &[]);
+    assert_eq!(status, 404);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn serves_the_rest_api() {
+    let dir = workspace("serve-rest");
+    let (mut child, address) = serve(&dir);
+
+    let (status, body) = http(&address, "GET", "/packs", &[]);
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), serde_json::json!([]));
+
+    let (status, body) = http(&address, "POST", "/conversions?name=algo&device=bench", &common::build_flm());
+    assert_eq!(status, 200);
+    let conversion: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(conversion["stubs"][0]["name"], "algo");
+    assert!(conversion["diagnostics"].is_array());
+    let (status, body) = http(&address, "GET", "/devices/bench", &[]);
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_slice::<Vec<ArmFlashStub>>(&body).unwrap()[0].name, "algo");
+
+    write_pack(dir.join("test.pack"));
+    let pack = fs::read(dir.join("test.pack")).unwrap();
+    let (status, _) = http(&address, "POST", "/conversions", &pack);
+    assert_eq!(status, 400);
+    let (status, body) = http(&address, "POST", "/conversions?device=TEST192", &pack);
+    assert_eq!(status, 200);
+    let conversion: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(conversion["stubs"][0]["name"], "TEST_192");
+
+    let (status, body) = http(&address, "GET", "/packs", &[]);
+    assert_eq!(status, 200);
+    let packs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(packs[0]["devices"][0], "TEST192");
+    let id = packs[0]["id"].as_str().unwrap();
+    let (status, body) = http(&address, "GET", &format!("/packs/{}", id), &[]);
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), packs[0]);
+
+    // Failures carry the diagnostic `validate` reports, with the stable code.
+    let (status, body) = http(&address, "POST", "/conversions", b"not an elf");
+    assert_eq!(status, 422);
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["diagnostics"][0]["code"], "conversion-failed");
+    assert!(error["diagnostics"][0]["id"].as_str().unwrap().starts_with("SC"));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn reports_metrics_for_prometheus() {
+    let dir = workspace("serve-metrics");
+    let (mut child, address) = serve(&dir);
+
+    http(&address, "POST", "/conversions?device=bench", &common::build_flm());
+    http(&address, "POST", "/conversions", b"not an elf");
+    write_pack(dir.join("test.pack"));
+    let pack = fs::read(dir.join("test.pack")).unwrap();
+    http(&address, "POST", "/packs", &pack);
+    http(&address, "POST", "/packs", &pack);
+    http(&address, "GET", "/devices/bench", &[]);
+    http(&address, "GET", "/devices/nobody", &[]);
+
+    let (status, body) = http(&address, "GET", "/metrics", &[]);
+    assert_eq!(status, 200);
+    let text = String::from_utf8(body).unwrap();
+    let value = |series: &str| -> f64 {
+        let line = text.lines().find(|line| line.starts_with(series)).unwrap_or_else(|| panic!("no {} in\n{}", series, text));
+        line[series.len()..].trim().parse().unwrap()
+    };
+    assert_eq!(value("soul_composer_conversions_total{outcome=\"completed\"}"), 1.0);
+    assert_eq!(value("soul_composer_conversions_total{outcome=\"failed\"}"), 1.0);
+    assert!(text.contains("# TYPE soul_composer_conversion_failures_total counter\nsoul_composer_conversion_failures_total{code=\"SC"));
+    assert_eq!(value("soul_composer_cache_requests_total{cache=\"packs\",result=\"hit\"}"), 1.0);
+    assert_eq!(value("soul_composer_cache_hit_ratio{cache=\"devices\"}"), 0.5);
+    assert_eq!(value("soul_composer_requests_total{route=\"/devices/{device}\",status=\"404\"}"), 1.0);
+    assert_eq!(value("soul_composer_request_duration_seconds_count{route=\"/packs\"}"), 2.0);
+    assert_eq!(value("soul_composer_request_duration_seconds_bucket{route=\"/packs\",le=\"+Inf\"}"), 2.0);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+/// Reads one unmasked frame from the server, returning its opcode and payload.
+fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head).unwrap();
+    let length = match head[1] {
+        126 => {
+            let mut length = [0; 2];
+            stream.read_exact(&mut length).unwrap();
+            u16::from_be_bytes(length) as usize
+        }
+        length => length as usize,
+    };
+    let mut payload = vec![0; length];
+    stream.read_exact(&mut payload).unwrap();
+    (head[0] & 0x0F, payload)
+}
+
+#[test]
+fn streams_progress_over_a_websocket() {
+    let dir = workspace("serve-events");
+    let (mut child, address) = serve(&dir);
+
+    let (status, _) = http(&address, "GET", "/events", &[]);
+    assert_eq!(status, 426);
+
+    let mut socket = TcpStream::connect(&address).unwrap();
+    let head = format!(
+        "GET /events HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        address
+    );
+    socket.write_all(head.as_bytes()).unwrap();
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        let mut byte = [0; 1];
+        socket.read_exact(&mut byte).unwrap();
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+    // The accept key of the handshake example in RFC 6455.
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{}", response);
+
+    write_pack(dir.join("test.pack"));
+    let (status, _) = http(&address, "POST", "/conversions?device=TEST192&operation=bench-1", &fs::read(dir.join("test.pack")).unwrap());
+    assert_eq!(status, 200);
+    let mut events = Vec::new();
+    loop {
+        let (opcode, payload) = read_frame(&mut socket);
+        assert_eq!(opcode, 1);
+        let event: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(event["operation"], "bench-1");
+        let kind = event["event"].as_str().unwrap().to_string();
+        events.push(event);
+        if kind == "finished" {
+            break;
+        }
+    }
+    assert_eq!(events[0], serde_json::json!({ "event": "started", "operation": "bench-1", "task": "conversion" }));
+    assert_eq!(events[1]["event"], "progress");
+    assert_eq!((events[1]["completed"].as_u64(), events[1]["total"].as_u64()), (Some(0), Some(1)));
+    assert!(events[1]["current"].as_str().unwrap().ends_with("TEST_192.FLM"));
+    assert_eq!(events[2]["completed"], 1);
+
+    // Failures end the operation too, under a number when the request didn't name it.
+    let (status, _) = http(&address, "POST", "/conversions", b"not an elf");
+    assert_eq!(status, 422);
+    let mut event = serde_json::Value::Null;
+    while event["event"] != "failed" {
+        event = serde_json::from_slice(&read_frame(&mut socket).1).unwrap();
+    }
+    assert!(event["operation"].as_str().unwrap().parse::<u64>().is_ok());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[cfg(feature = "grpc")]
+#[tokio::test]
+async fn serves_grpc_next_to_rest() {
+    use std::convert::TryFrom;
+
+    use soulcomposer::grpc::proto::{soul_composer_client::SoulComposerClient, ConvertRequest, GetDeviceRequest, ListDevicesRequest};
+    use tonic::{transport::Endpoint, Code};
+
+    let dir = workspace("serve-grpc");
+    let mut child = soul_composer()
+        .args(["serve", "--port", "0", "--grpc-port", "0", "--cache", "cache"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let rest = lines.next().unwrap().unwrap().trim_start_matches("Listening on http://").to_string();
+    let grpc = lines.next().unwrap().unwrap().trim_start_matches("gRPC on ").to_string();
+
+    let channel = Endpoint::from_shared(format!("http://{}", grpc)).unwrap().connect().await.unwrap();
+    let mut client = SoulComposerClient::new(channel);
+    let request = ConvertRequest { data: common::build_flm(), name: "algo".to_string(), device: Some("bench".to_string()), default: None };
+    let conversion = client.convert(request).await.unwrap().into_inner();
+    let stub = &conversion.stubs[0];
+    assert_eq!((stub.name.as_str(), stub.flash_size, stub.sectors.len()), ("algo", 0x30000, 2));
+    assert_eq!(stub.region_kind, "mainFlash");
+
+    // Both sides share the registry, and the typed stub reads back to the model REST serves.
+    let (status, body) = http(&rest, "GET", "/devices/bench", &[]);
+    assert_eq!(status, 200);
+    let served: Vec<ArmFlashStub> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(ArmFlashStub::try_from(stub).unwrap(), served[0]);
+    assert_eq!(base64::decode(&served[0].instructions).unwrap(), stub.instructions);
+    let devices = client.list_devices(ListDevicesRequest {}).await.unwrap().into_inner().devices;
+    assert_eq!(devices, ["bench"]);
+
+    let missing = client.get_device(GetDeviceRequest { device: "nothing".to_string() }).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+    let request = ConvertRequest { data: b"not an elf".to_vec(), ..<ConvertRequest as Default>::default() };
+    assert_eq!(client.convert(request).await.unwrap_err().code(), Code::InvalidArgument);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
@@ -0,0 +1,4 @@
+#![allow(dead_code)]
+
+// The fixtures live in the library so downstream crates can use them too.
+pub use soulcomposer::testing::*;
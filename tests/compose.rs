@@ -0,0 +1,90 @@
+mod common;
+
+use soulcomposer::{
+    compose::arm::{compose_stub, ComposeOptions, Elf},
+    diagnostic::Severity,
+    prog::arm::{
+        arm_error::ArmError,
+        flash_device::SectorInfo,
+        flash_stub_gen::{ArmFlashStub, RegionKind},
+        parse_options::Strictness,
+    },
+};
+
+#[test]
+fn composes_the_same_stub_as_from_elf() {
+    let flm = common::build_flm();
+    let elf = Elf::parse(&flm).unwrap();
+
+    let mut options = ComposeOptions::new("algo");
+    options.default = true;
+    options.ram_size = 0x1000;
+    let (stub, diagnostics) = compose_stub(&elf, &flm, &options).unwrap();
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(stub, ArmFlashStub::from_elf(&flm, "algo".to_string(), true, 0x1000).unwrap());
+    assert_eq!(stub.description, common::FLM_DEVICE_NAME);
+    assert_eq!(stub.pc_program_page, 16 | 1);
+}
+
+#[test]
+fn reports_ram_overflow() {
+    let flm = common::build_flm();
+    let elf = Elf::parse(&flm).unwrap();
+
+    let mut options = ComposeOptions::new("algo");
+    options.ram_size = 16;
+    assert!(matches!(compose_stub(&elf, &flm, &options), Err(ArmError::RamOverflow { available: 16, .. })));
+}
+
+#[test]
+fn reports_guessed_region_kind() {
+    let flm = common::build_flm();
+    let elf = Elf::parse(&flm).unwrap();
+
+    let (stub, diagnostics) = compose_stub(&elf, &flm, &ComposeOptions::new("algo_OTP")).unwrap();
+    assert_eq!(stub.region_kind, RegionKind::Otp);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].severity, diagnostics[0].code), (Severity::Warning, "region-kind-guessed"));
+}
+
+#[test]
+fn applies_parse_limits_and_strictness() {
+    let flm = common::build_flm();
+    let elf = Elf::parse(&flm).unwrap();
+
+    let mut options = ComposeOptions::new("algo_OTP");
+    options.parse.strictness = Strictness::Strict;
+    match compose_stub(&elf, &flm, &options) {
+        Err(ArmError::Rejected(diagnostic)) => assert_eq!(diagnostic.code, "region-kind-guessed"),
+        other => panic!("expected a strict refusal, got {:?}", other.map(|(stub, _)| stub.name)),
+    }
+
+    let mut options = ComposeOptions::new("flash");
+    options.parse.max_sectors = 1;
+    options.parse.descriptor_symbol = Some("VendorDevice".to_string());
+    assert!(matches!(compose_stub(&elf, &flm, &options), Err(ArmError::TooManySectors { limit: 1, .. })));
+
+    options.parse.strictness = Strictness::Lenient;
+    let (stub, _) = compose_stub(&elf, &flm, &options).unwrap();
+    assert_eq!(stub.sectors.len(), 1);
+}
+
+#[test]
+fn lenient_parsing_reports_broken_sector_tables() {
+    let sectors = vec![SectorInfo { address: 0, size: 0x4000 }, SectorInfo { address: 0, size: 0x8000 }];
+    let fields = common::DescriptorFields { sectors, ..Default::default() };
+    let flm = common::build_flm_with(&fields.to_bytes(), "FlashDevice");
+    let elf = Elf::parse(&flm).unwrap();
+
+    let mut options = ComposeOptions::new("flash");
+    assert!(matches!(compose_stub(&elf, &flm, &options), Err(ArmError::SectorTableUnsorted { index: 1, .. })));
+
+    options.parse.strictness = Strictness::Lenient;
+    let (stub, diagnostics) = compose_stub(&elf, &flm, &options).unwrap();
+    let broken = diagnostics.iter().find(|diagnostic| diagnostic.code == "broken-sector-table").unwrap();
+    assert_eq!((broken.severity, broken.id), (Severity::Warning, Some("SC1017")));
+    assert_eq!(stub.sectors[0].count, 0);
+    // Salvaged, but still not fit for a flasher.
+    assert!(matches!(stub.validate(), Err(ArmError::SectorTableUnsorted { .. })));
+}
@@ -0,0 +1,54 @@
+use soulcomposer::prog::arm::{
+    build_attributes::BuildAttributes,
+    core_isa::{check_core, Core, IsaFeature},
+};
+
+// smlad r2, r1, r3, r0; bx lr
+const DSP_CODE: [u8; 6] = [0x21, 0xFB, 0x03, 0x02, 0x70, 0x47];
+// cbz r0, +2; nop; bx lr
+const CBZ_CODE: [u8; 6] = [0x08, 0xB1, 0x00, 0xBF, 0x70, 0x47];
+
+#[test]
+fn dsp_instruction_on_m0() {
+    let violations = check_core(&DSP_CODE, &[1], None, Core::CortexM0);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].feature, IsaFeature::Dsp);
+    assert_eq!(violations[0].offset, Some(0));
+
+    assert!(check_core(&DSP_CODE, &[1], None, Core::CortexM4).is_empty());
+    assert_eq!(check_core(&DSP_CODE, &[1], None, Core::CortexM3)[0].feature, IsaFeature::Dsp);
+}
+
+#[test]
+fn thumb2_branch_on_m0() {
+    let violations = check_core(&CBZ_CODE, &[1], None, Core::CortexM0Plus);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].feature, IsaFeature::Thumb2);
+    assert!(check_core(&CBZ_CODE, &[1], None, Core::CortexM3).is_empty());
+}
+
+#[test]
+fn build_attributes_are_checked() {
+    // "A" subsection for aeabi, file scope with Tag_CPU_arch = v7E-M and Tag_FP_arch = VFPv4-D16.
+    let mut section = vec![b'A'];
+    let file = [0x01, 0x09, 0x00, 0x00, 0x00, 0x06, 0x0D, 0x0A, 0x06];
+    let length = 4 + 6 + file.len() as u32;
+    section.extend_from_slice(&length.to_le_bytes());
+    section.extend_from_slice(b"aeabi\0");
+    section.extend_from_slice(&file);
+    let attributes = BuildAttributes::parse(&section).unwrap();
+
+    let features: Vec<_> = check_core(&[], &[], Some(&attributes), Core::CortexM3)
+        .into_iter()
+        .map(|violation| violation.feature)
+        .collect();
+    assert_eq!(features, vec![IsaFeature::Architecture, IsaFeature::Fpu]);
+    assert!(check_core(&[], &[], Some(&attributes), Core::CortexM4).is_empty());
+}
+
+#[test]
+fn core_names() {
+    assert_eq!(Core::from_name("Cortex-M0+"), Some(Core::CortexM0Plus));
+    assert_eq!(Core::from_name("m33"), Some(Core::CortexM33));
+    assert_eq!(Core::from_name("Cortex-A7"), None);
+}
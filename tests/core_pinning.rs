@@ -0,0 +1,65 @@
+use soulcomposer::pack::pdsc::Pdsc;
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    core_pinning::CorePinning,
+    flash_stub_gen::ArmFlashStub,
+    parse_options::ParseOptions,
+};
+
+const PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.7">
+  <vendor>NXP</vendor>
+  <name>LPC55S69_DFP</name>
+  <devices>
+    <family Dfamily="LPC55S6x" Dvendor="NXP:11">
+      <device Dname="LPC55S69">
+        <processor Pname="cm33_core0" Dcore="Cortex-M33" Dclock="150000000"/>
+        <processor Pname="cm33_core1" Dcore="Cortex-M33" Dclock="150000000"/>
+        <debug Pname="cm33_core0" __ap="0"/>
+        <debug Pname="cm33_core1" __ap="1"/>
+        <memory name="PROGRAM_FLASH" start="0x00000000" size="0x98000" access="rx" default="1"/>
+        <algorithm name="arm/LPC55XX_630.FLM" start="0x00000000" size="0x98000" default="1" Pname="cm33_core0"/>
+      </device>
+    </family>
+  </devices>
+</package>"#;
+
+#[test]
+fn pins_to_algorithm_processor() {
+    let pdsc = Pdsc::parse(PDSC).unwrap();
+    let device = pdsc.device("LPC55S69").unwrap();
+    assert_eq!(device.processors[1].ap, Some(1));
+
+    let mut stub = ArmFlashStub::default();
+    stub.apply_pdsc_device(device, &ParseOptions::default()).unwrap();
+    assert_eq!(
+        stub.pinned_core,
+        Some(CorePinning { index: 0, ap: Some(0), processor: Some("cm33_core0".to_string()) })
+    );
+}
+
+#[test]
+fn rejects_inconsistent_pinning() {
+    let pdsc = Pdsc::parse(PDSC).unwrap();
+    let device = pdsc.device("LPC55S69").unwrap();
+
+    let unpinned = ArmFlashStub::default();
+    assert!(matches!(unpinned.check_core_pinning(device), Err(ArmError::CorePinningRequired(2))));
+
+    let mut other_core = ArmFlashStub::default();
+    other_core.pinned_core = Some(CorePinning::from_pdsc(device, "cm33_core1").unwrap());
+    assert!(matches!(
+        other_core.check_core_pinning(device),
+        Err(ArmError::CorePinningMismatch { field: "algorithm Pname", .. })
+    ));
+
+    let mut wrong_ap = ArmFlashStub::default();
+    wrong_ap.pinned_core = Some(CorePinning { index: 0, ap: Some(2), processor: None });
+    assert!(matches!(wrong_ap.check_core_pinning(device), Err(ArmError::CorePinningMismatch { field: "ap", .. })));
+
+    let mut out_of_range = ArmFlashStub::default();
+    out_of_range.pinned_core = Some(CorePinning { index: 2, ap: None, processor: None });
+    assert!(matches!(out_of_range.check_core_pinning(device), Err(ArmError::CoreIndexOutOfRange { index: 2, count: 2 })));
+
+    assert!(matches!(CorePinning::from_pdsc(device, "cm4"), Err(ArmError::UnknownProcessor(_))));
+}
@@ -0,0 +1,271 @@
+#![cfg(feature = "dap")]
+
+mod common;
+
+use std::collections::HashMap;
+
+use soulcomposer::{
+    dap::{self_test, CmsisDap, DapError, DapTarget, DapTransport},
+    prog::arm::{arm_error::ArmError, validated::ValidatedArmFlashStub},
+    ArmFlashStub,
+};
+
+const RAM: DapTarget = DapTarget { ram_start: 0x2000_0000, ram_size: 0x1_0000 };
+const DHCSR: u32 = 0xE000_EDF0;
+const DCRSR: u32 = 0xE000_EDF4;
+const DCRDR: u32 = 0xE000_EDF8;
+
+/// A CMSIS-DAP probe wired to a Cortex-M that "runs" the stub's routines by doing what they
+/// would: erasing a sector, copying a page into flash.
+struct FakeProbe {
+    packet_size: u16,
+    memory: HashMap<u32, u32>,
+    select: u32,
+    csw: u32,
+    tar: u32,
+    registers: [u32; 17],
+    dcrdr: u32,
+    /// Routine at each entry address, and what ran so far.
+    entries: HashMap<u32, &'static str>,
+    calls: Vec<&'static str>,
+    /// Return value of each routine, 0 by default.
+    returns: HashMap<&'static str, u32>,
+    /// Bits ProgramPage leaves set, a worn cell.
+    stuck: Option<(u32, u32)>,
+    sector_size: u32,
+}
+
+impl FakeProbe {
+    fn new(stub: &ValidatedArmFlashStub, packet_size: u16) -> Self {
+        let mut entries = HashMap::new();
+        let routines = [
+            ("Init", stub.pc_init),
+            ("UnInit", stub.pc_uninit),
+            ("EraseSector", Some(stub.pc_erase_sector)),
+            ("ProgramPage", Some(stub.pc_program_page)),
+        ];
+        for (name, pc) in routines {
+            if let Some(pc) = pc {
+                entries.insert((RAM.ram_start + pc) & !1, name);
+            }
+        }
+        FakeProbe {
+            packet_size,
+            memory: HashMap::new(),
+            select: 0,
+            csw: 0,
+            tar: 0,
+            registers: [0; 17],
+            dcrdr: 0,
+            entries,
+            calls: Vec::new(),
+            returns: HashMap::new(),
+            stuck: None,
+            sector_size: stub.sectors.last().unwrap().size,
+        }
+    }
+
+    fn read(&mut self, address: u32) -> u32 {
+        match address {
+            // Always halted: routines run to completion as soon as the core is resumed.
+            DHCSR => (1 << 17) | (1 << 16),
+            DCRDR => self.dcrdr,
+            _ => self.memory.get(&address).copied().unwrap_or(0),
+        }
+    }
+
+    fn write(&mut self, address: u32, value: u32) {
+        match address {
+            DHCSR if value & 0b11 == 0b01 => self.execute(),
+            DHCSR => {}
+            DCRSR if value & (1 << 16) != 0 => self.registers[(value & 0x1F) as usize] = self.dcrdr,
+            DCRSR => self.dcrdr = self.registers[(value & 0x1F) as usize],
+            DCRDR => self.dcrdr = value,
+            _ => {
+                self.memory.insert(address, value);
+            }
+        }
+    }
+
+    fn execute(&mut self) {
+        let routine = self.entries[&self.registers[15]];
+        let [r0, r1, r2] = [self.registers[0], self.registers[1], self.registers[2]];
+        match routine {
+            "EraseSector" => {
+                for address in (r0..r0 + self.sector_size).step_by(4) {
+                    self.memory.insert(address, u32::MAX);
+                }
+            }
+            "ProgramPage" => {
+                for offset in (0..r1).step_by(4) {
+                    let mut word = self.read(r2 + offset);
+                    if let Some((_, bits)) = self.stuck.filter(|(address, _)| *address == r0 + offset) {
+                        word |= bits;
+                    }
+                    self.memory.insert(r0 + offset, word);
+                }
+            }
+            _ => {}
+        }
+        self.calls.push(routine);
+        self.registers[0] = self.returns.get(routine).copied().unwrap_or(0);
+        self.registers[15] = self.registers[14] & !1;
+    }
+
+    /// Runs one register transfer, returning what a read returned.
+    fn register(&mut self, request: u8, value: u32) -> Option<u32> {
+        let (ap, read, address) = (request & 1 != 0, request & 2 != 0, request & 0x0C);
+        match (ap, read, address) {
+            (false, true, 0x00) => Some(0x2BA0_1477),
+            // Power-up requests are acknowledged at once.
+            (false, true, 0x04) => Some(0xF000_0000),
+            (false, false, 0x08) => {
+                self.select = value;
+                None
+            }
+            (false, true, _) => Some(0),
+            (false, false, _) => None,
+            (true, false, 0x00) => {
+                self.csw = value;
+                None
+            }
+            (true, false, 0x04) => {
+                self.tar = value;
+                None
+            }
+            (true, _, 0x0C) => {
+                let address = self.tar;
+                if self.csw & 0x10 != 0 {
+                    self.tar += 4;
+                }
+                match read {
+                    true => Some(self.read(address)),
+                    false => {
+                        self.write(address, value);
+                        None
+                    }
+                }
+            }
+            (true, _, _) => unreachable!("unused AP register {:#x}", address),
+        }
+    }
+}
+
+impl DapTransport for FakeProbe {
+    fn exchange(&mut self, command: &[u8]) -> Result<Vec<u8>, DapError> {
+        assert!(command.len() <= self.packet_size as usize, "{} bytes don't fit a packet", command.len());
+        let word = |at: usize| u32::from_le_bytes([command[at], command[at + 1], command[at + 2], command[at + 3]]);
+        let response = match command[0] {
+            0x00 => match command[1] {
+                0xFF => {
+                    let [low, high] = self.packet_size.to_le_bytes();
+                    vec![0x00, 2, low, high]
+                }
+                0xF0 => vec![0x00, 1, 0x01],
+                _ => vec![0x00, 0],
+            },
+            0x02 => vec![0x02, command[1]],
+            0x05 => {
+                let count = command[2];
+                let mut response = vec![0x05, count, 1];
+                let mut at = 3;
+                for _ in 0..count {
+                    let request = command[at];
+                    at += 1;
+                    let value = if request & 2 == 0 {
+                        at += 4;
+                        word(at - 4)
+                    } else {
+                        0
+                    };
+                    if let Some(read) = self.register(request, value) {
+                        response.extend_from_slice(&read.to_le_bytes());
+                    }
+                }
+                response
+            }
+            0x06 => {
+                let count = u16::from_le_bytes([command[2], command[3]]);
+                let request = command[4];
+                let mut response = vec![0x06, command[2], command[3], 1];
+                for index in 0..count as usize {
+                    let value = if request & 2 == 0 { word(5 + index * 4) } else { 0 };
+                    if let Some(read) = self.register(request, value) {
+                        response.extend_from_slice(&read.to_le_bytes());
+                    }
+                }
+                assert!(response.len() <= self.packet_size as usize);
+                response
+            }
+            command => vec![command, 0],
+        };
+        Ok(response)
+    }
+}
+
+fn stub() -> ValidatedArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap().validate().unwrap()
+}
+
+#[test]
+fn reads_and_writes_memory_across_packets() {
+    let stub = stub();
+    let mut dap = CmsisDap::new(FakeProbe::new(&stub, 64)).unwrap();
+    assert_eq!(dap.packet_size(), 64);
+    assert_eq!(dap.connect(4_000_000, 0).unwrap(), 0x2BA0_1477);
+
+    // Crosses a 1 KiB block, where TAR has to be written again.
+    let data: Vec<u8> = (0..3000u32).map(|index| (index * 7) as u8).collect();
+    dap.write_memory(0x2000_0300, &data).unwrap();
+    let mut read_back = vec![0; data.len()];
+    dap.read_memory(0x2000_0300, &mut read_back).unwrap();
+    assert_eq!(read_back, data);
+    assert_eq!(dap.read_word(0x2000_0400).unwrap(), u32::from_le_bytes([data[256], data[257], data[258], data[259]]));
+
+    assert!(matches!(dap.write_memory(0x2000_0002, &[0; 4]), Err(DapError::Unaligned { address: 0x2000_0002, .. })));
+    assert!(matches!(dap.read_memory(0x2000_0000, &mut [0; 3]), Err(DapError::Unaligned { length: 3, .. })));
+}
+
+#[test]
+fn runs_a_stub_through_the_self_test() {
+    let stub = stub();
+    let mut dap = CmsisDap::new(FakeProbe::new(&stub, 512)).unwrap();
+    dap.connect(1_000_000, 0).unwrap();
+
+    let report = self_test(&mut dap, &stub, &RAM, None).unwrap();
+    let last = stub.scratch_sector(None).unwrap();
+    assert_eq!(report.sector, last.start as u32..last.end as u32);
+
+    let probe = dap.into_inner();
+    let pages = ((last.end - last.start) / u64::from(stub.flash_page_size)) as usize;
+    let mut expected = vec!["Init", "EraseSector", "UnInit", "Init"];
+    expected.extend(vec!["ProgramPage"; pages]);
+    expected.push("UnInit");
+    assert_eq!(probe.calls, expected);
+    let pattern = stub.test_pattern(8);
+    assert_eq!(probe.memory[&report.sector.start].to_le_bytes(), pattern[..4]);
+}
+
+#[test]
+fn reports_failing_routines_and_read_back() {
+    let stub = stub();
+    let mut probe = FakeProbe::new(&stub, 64);
+    probe.returns.insert("EraseSector", 1);
+    let mut dap = CmsisDap::new(probe).unwrap();
+    dap.connect(1_000_000, 0).unwrap();
+    let err = self_test(&mut dap, &stub, &RAM, None).unwrap_err();
+    assert!(matches!(err, DapError::Routine { routine: "EraseSector", code: 1 }));
+    assert!(!err.is_link());
+
+    let scratch = stub.scratch_sector(None).unwrap().start as u32;
+    let mut probe = FakeProbe::new(&stub, 64);
+    probe.stuck = Some((scratch + 8, 0x0000_0100));
+    let mut dap = CmsisDap::new(probe).unwrap();
+    dap.connect(1_000_000, 0).unwrap();
+    let err = self_test(&mut dap, &stub, &RAM, Some(scratch)).unwrap_err();
+    assert!(matches!(err, DapError::Mismatch { address, .. } if address == scratch + 9));
+
+    let small = DapTarget { ram_start: 0x2000_0000, ram_size: 0x100 };
+    assert!(matches!(self_test(&mut dap, &stub, &small, None), Err(DapError::Arm(ArmError::RamOverflow { available: 0x100, .. }))));
+    assert!(matches!(self_test(&mut dap, &stub, &RAM, Some(0x1000_0000)), Err(DapError::ScratchOutOfRange(0x1000_0000))));
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "database")]
+
+mod common;
+
+use soulcomposer::{
+    database::{HilRun, StubDatabase, StubQuery, StubSource},
+    ArmFlashStub, Diagnostic,
+};
+
+fn stub(name: &str, start: u32) -> ArmFlashStub {
+    let mut stub = ArmFlashStub::from_elf(common::build_flm(), name.to_string(), false, 0).unwrap();
+    stub.flash_end_addr = start + (stub.flash_end_addr - stub.flash_start_addr);
+    stub.flash_start_addr = start;
+    stub
+}
+
+fn source(device: &str, vendor: &str) -> StubSource {
+    StubSource {
+        device: device.to_string(),
+        vendor: Some(vendor.to_string()),
+        pack: Some("TEST_DFP".to_string()),
+        pack_version: Some("1.0.0".to_string()),
+        file: Some(format!("Flash/{}.FLM", device)),
+    }
+}
+
+#[test]
+fn stores_stubs_with_their_source() {
+    let database = StubDatabase::in_memory().unwrap();
+    let warning = Diagnostic::warning("unbounded-stack", "stack usage could not be bounded");
+    let id = database.insert(&source("TEST192", "Acme"), &stub("main", 0x0800_0000), &[warning]).unwrap();
+
+    let stored = database.get(id).unwrap().unwrap();
+    assert_eq!(stored.stub, stub("main", 0x0800_0000));
+    assert_eq!(stored.source, source("TEST192", "Acme"));
+    assert_eq!(stored.crc32, stored.stub.crc32);
+    assert_eq!(stored.image_crc32, crc32fast::hash(&base64::decode(&stored.stub.instructions).unwrap()));
+    assert_eq!(stored.findings.len(), 1);
+    assert_eq!((stored.findings[0].code.as_str(), stored.findings[0].id.as_deref()), ("unbounded-stack", Some("SC1012")));
+    assert!(!stored.has_errors());
+    assert!(database.get(id + 1).unwrap().is_none());
+
+    // Storing a stub of the same name for the same device replaces it.
+    let mut updated = stub("main", 0x0800_0000);
+    updated.description = "second build".to_string();
+    assert_eq!(database.insert(&source("test192", "Acme"), &updated, &[]).unwrap(), id);
+    assert_eq!(database.len().unwrap(), 1);
+    assert_eq!(database.device_stubs("TEST192").unwrap(), [updated]);
+}
+
+#[test]
+fn queries_by_device_vendor_and_flash() {
+    let database = StubDatabase::in_memory().unwrap();
+    let error = Diagnostic::error("crc-mismatch", "stored CRC32 doesn't match");
+    database.insert(&source("TEST192", "Acme"), &stub("main", 0x0800_0000), &[]).unwrap();
+    database.insert(&source("TEST192", "Acme"), &stub("eeprom", 0x0808_0000), &[]).unwrap();
+    database.insert(&source("OTHER", "Initech"), &stub("main", 0x1000_0000), &[error]).unwrap();
+
+    let names = |query: StubQuery| -> Vec<(String, String)> {
+        database.query(&query).unwrap().into_iter().map(|stored| (stored.source.device, stored.stub.name)).collect()
+    };
+    let pair = |device: &str, name: &str| (device.to_string(), name.to_string());
+
+    assert_eq!(database.devices().unwrap(), ["OTHER", "TEST192"]);
+    assert_eq!(names(StubQuery::new().device("test192")), [pair("TEST192", "eeprom"), pair("TEST192", "main")]);
+    assert_eq!(names(StubQuery::new().vendor("initech")), [pair("OTHER", "main")]);
+    assert_eq!(names(StubQuery::new().covering(0x0808_0000)), [pair("TEST192", "eeprom")]);
+    // The fixture spans 192kB, so the range reaches into the first stub only.
+    assert_eq!(names(StubQuery::new().flash(0x0802_0000..0x0803_0000)), [pair("TEST192", "main")]);
+    assert!(names(StubQuery::new().covering(0x0803_0000)).is_empty());
+    assert_eq!(names(StubQuery::new().without_errors()).len(), 2);
+    assert_eq!(names(StubQuery::new().device("OTHER").vendor("Acme")), []);
+
+    assert_eq!(database.remove_device("TEST192").unwrap(), 2);
+    assert_eq!(database.len().unwrap(), 1);
+}
+
+#[test]
+fn records_hil_runs_against_the_stored_image() {
+    let database = StubDatabase::in_memory().unwrap();
+    let main = database.insert(&source("TEST192", "Acme"), &stub("main", 0x0800_0000), &[]).unwrap();
+    let eeprom = database.insert(&source("TEST192", "Acme"), &stub("eeprom", 0x0808_0000), &[]).unwrap();
+    let image_crc32 = database.get(main).unwrap().unwrap().image_crc32;
+
+    let run = |stub_id: i64, passed: bool, tested_at: u64| HilRun {
+        stub_id,
+        image_crc32,
+        backend: "injector".to_string(),
+        passed,
+        detail: Some("read back differs at 0x0807f004".to_string()).filter(|_| !passed),
+        sector: Some(0x0807_0000..0x0808_0000),
+        duration_ms: 120,
+        tested_at,
+    };
+    database.record_hil(&run(main, false, 100)).unwrap();
+    database.record_hil(&run(main, true, 200)).unwrap();
+    database.record_hil(&run(eeprom, false, 200)).unwrap();
+
+    assert_eq!(database.hil_runs(main).unwrap(), [run(main, true, 200), run(main, false, 100)]);
+    assert!(database.is_verified(main).unwrap());
+    assert!(!database.is_verified(eeprom).unwrap());
+    let verified: Vec<i64> = database.query(&StubQuery::new().verified()).unwrap().iter().map(|stored| stored.id).collect();
+    assert_eq!(verified, [main]);
+
+    // Another build of the code hasn't run yet.
+    let mut rebuilt = stub("main", 0x0800_0000);
+    let mut image = base64::decode(&rebuilt.instructions).unwrap();
+    image[0] ^= 0xFF;
+    rebuilt.instructions = base64::encode(&image);
+    assert_eq!(database.insert(&source("TEST192", "Acme"), &rebuilt, &[]).unwrap(), main);
+    assert!(!database.is_verified(main).unwrap());
+    assert!(database.query(&StubQuery::new().verified()).unwrap().is_empty());
+
+    database.remove_device("TEST192").unwrap();
+    assert!(database.hil_runs(main).unwrap().is_empty());
+}
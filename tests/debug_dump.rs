@@ -0,0 +1,50 @@
+mod common;
+
+use soulcomposer::{
+    prog::arm::{
+        debug_dump::{DebugDump, RawDescriptor},
+        flash_device::FlashDevice,
+    },
+    Elf, ParseOptions,
+};
+
+#[test]
+fn dumps_the_descriptor_as_linked_and_as_parsed() {
+    let data = common::build_flm();
+    let elf = Elf::parse(&data).unwrap();
+    let raw = RawDescriptor::from_elf(&elf, &data, &ParseOptions::default()).unwrap().debug_dump();
+    assert_eq!(raw.rows.len(), 16);
+
+    let row = |field: &str| raw.rows.iter().find(|row| row.field == field).unwrap();
+    assert_eq!(row("deviceSize").offset, 136);
+    assert_eq!(row("deviceSize").bytes, [0x00, 0x00, 0x03, 0x00]);
+    assert_eq!(row("deviceSize").value, "0x30000");
+    assert_eq!(row("name").value, format!("{:?}", common::FLM_DEVICE_NAME));
+    assert_eq!(row("sectors[1].address").offset, 172);
+    assert_eq!(row("sectors.end").bytes, [0xFF; 8]);
+
+    // The fixture has nothing parsing drops, so re-encoding the parsed device gives the same rows.
+    let parsed = FlashDevice::from_elf(&elf, &data).unwrap().debug_dump();
+    assert_eq!(parsed.rows, raw.rows);
+    assert_eq!(parsed.base, 0);
+    assert_eq!(FlashDevice::from_elf(&elf, &data).unwrap().sectors().debug_dump().rows.len(), 5);
+
+    let text = raw.to_string();
+    let line = text.lines().find(|line| line.contains("pageSize")).unwrap();
+    assert_eq!(line.split_whitespace().collect::<Vec<_>>(), [&format!("{:#010x}", raw.base + 140), "+0x008c", "pageSize", "00", "01", "00", "00", "256"]);
+}
+
+#[test]
+fn dumps_descriptors_parsing_refuses() {
+    // Second sector region at 0x2000, before the 16kB sectors of the first one end.
+    let mut fields = common::DescriptorFields::default();
+    fields.sectors[1].address = 0x2000;
+    let data = common::build_flm_with(&fields.to_bytes(), "FlashDevice");
+
+    let elf = Elf::parse(&data).unwrap();
+    assert!(FlashDevice::from_elf(&elf, &data).is_err());
+
+    let dump = RawDescriptor::from_elf(&elf, &data, &ParseOptions::default()).unwrap().debug_dump();
+    let row = dump.rows.iter().find(|row| row.field == "sectors[1].address").unwrap();
+    assert_eq!(row.value, "0x2000");
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use soulcomposer::prog::arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+fn stub() -> ArmFlashStub {
+    let mut stub = ArmFlashStub::default();
+    stub.instructions = base64::encode([0x00, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47, 0xAA, 0xBB, 0, 0]);
+    stub.pc_init = Some(1);
+    stub.pc_program_page = 5;
+    stub.pc_erase_sector = 5;
+    stub.data_section_offset = 8;
+    stub
+}
+
+#[test]
+fn splits_unified_blob() {
+    let parts = stub().decompose().unwrap();
+    assert_eq!(parts.code.data.len(), 8);
+    assert_eq!((parts.data.offset, parts.data.data.as_slice()), (8, &[0xAA, 0xBB, 0, 0][..]));
+    assert_eq!(parts.entry_points.program_page, 4);
+    let names: Vec<_> = parts.entry_points.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["Init", "ProgramPage", "EraseSector"]);
+    assert_eq!(parts.image(), base64::decode(&stub().instructions).unwrap());
+}
+
+#[test]
+fn repackages_without_changing_the_image() {
+    let mut original = stub();
+    original.crc32 = Some(original.compute_crc32().unwrap());
+
+    let split = original.repackaged(true).unwrap();
+    assert_eq!(split.data_instructions.as_deref(), Some(base64::encode([0xAA, 0xBB, 0, 0]).as_str()));
+    assert!(split.verify_crc32().is_ok());
+    assert_eq!(split.decompose().unwrap(), original.decompose().unwrap());
+    assert_eq!(split.repackaged(false).unwrap().instructions, original.instructions);
+}
+
+#[test]
+fn decomposes_converted_flm() {
+    let flm = common::build_flm();
+    let stub = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap();
+    let parts = stub.decompose().unwrap();
+    for (name, offset) in common::FLM_ENTRIES {
+        if let Some((_, decoded)) = parts.entry_points.iter().find(|(entry, _)| *entry == name) {
+            assert_eq!(decoded, offset, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn rejects_inconsistent_stubs() {
+    let mut broken = stub();
+    broken.data_section_offset = 64;
+    assert!(matches!(broken.decompose(), Err(ArmError::DataOffsetOutOfRange { offset: 64, length: 12 })));
+
+    let mut broken = stub();
+    broken.pc_erase_sector = 9;
+    assert!(matches!(broken.decompose(), Err(ArmError::InvalidEntryPoint { offset: 8, .. })));
+
+    let mut broken = stub();
+    broken.crc32 = Some(0);
+    assert!(matches!(broken.decompose(), Err(ArmError::CrcMismatch { .. })));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    disasm::{render, routine},
+    flash_stub_gen::ArmFlashStub,
+    thumb::decode,
+};
+
+fn text(code: &[u8]) -> String {
+    render(&decode(code, 0x100, 0).unwrap())
+}
+
+#[test]
+fn renders_thumb_instructions() {
+    assert_eq!(text(&[0x5a, 0x43]), "muls r2, r3, r2");
+    assert_eq!(text(&[0x04, 0x90]), "str r0, [sp, #0x10]");
+    assert_eq!(text(&[0x0c, 0xbf]), "ite eq");
+    assert_eq!(text(&[0x41, 0xf2, 0x34, 0x20]), "movw r0, #0x1234");
+    assert_eq!(text(&[0x4f, 0xf0, 0xff, 0x20]), "mov r0, #0xff00ff00");
+    assert_eq!(text(&[0xc1, 0xf3, 0x07, 0x10]), "ubfx r0, r1, #4, #8");
+    assert_eq!(text(&[0x51, 0xf8, 0x04, 0x09]), "ldr r0, [r1], #-0x4");
+    assert_eq!(text(&[0xdf, 0xf8, 0x00, 0x01]), "ldr r0, [pc, #0x100] ; 0x204");
+    assert_eq!(text(&[0xd0, 0xe8, 0x11, 0xf0]), "tbh [r0, r1, lsl #1]");
+    assert_eq!(text(&[0x81, 0xf3, 0x11, 0x88]), "msr basepri, r1");
+}
+
+#[test]
+fn routine_follows_branches_and_stops_at_returns() {
+    // 0: cmp r0, #0; 2: beq 8; 4: movs r0, #1; 6: bx lr; 8: movs r0, #0; a: bx lr; c: nop (unreachable)
+    let code = [0x00, 0x28, 0x01, 0xd0, 0x01, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47, 0x00, 0xbf];
+    let offsets: Vec<u32> = routine(&code, 1).iter().map(|decoded| decoded.address).collect();
+    assert_eq!(offsets, [0, 2, 4, 6, 8, 10]);
+}
+
+#[test]
+fn disassembles_entry_points_by_name() {
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+
+    let lines = stub.disassemble("programpage").unwrap();
+    let text: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+    assert_eq!(text, ["movs r0, #0", "bx lr"]);
+    assert_eq!(lines[0].bytes, [0x00, 0x20]);
+    assert_eq!(lines[1].offset, lines[0].offset + 2);
+
+    assert!(matches!(stub.disassemble("Verify"), Err(ArmError::UnknownEntryPoint { .. })));
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "emulator")]
+
+mod common;
+
+use soulcomposer::prog::arm::{
+    emulator::{dry_run, EmulationFault, EmulatorConfig},
+    flash_stub_gen::ArmFlashStub,
+};
+
+fn stub() -> ArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap()
+}
+
+/// The stub with the routine at `offset` replaced by `code`.
+fn patched(offset: u32, code: [u8; 4]) -> ArmFlashStub {
+    let mut stub = stub();
+    let mut blob = base64::decode(&stub.instructions).unwrap();
+    blob[offset as usize..offset as usize + 4].copy_from_slice(&code);
+    stub.instructions = base64::encode(blob);
+    stub
+}
+
+fn offset(name: &str) -> u32 {
+    common::FLM_ENTRIES.iter().find(|(entry, _)| *entry == name).unwrap().1
+}
+
+#[test]
+fn runs_every_routine_of_the_fixture() {
+    let report = dry_run(&stub(), &EmulatorConfig::default()).unwrap();
+
+    let names: Vec<_> = report.routines.iter().map(|routine| routine.name).collect();
+    assert_eq!(names, ["Init", "EraseSector", "UnInit", "Init", "ProgramPage", "UnInit"]);
+    for routine in &report.routines {
+        assert_eq!(routine.fault, None, "{}", routine.name);
+        assert_eq!(routine.return_value, Some(0), "{}", routine.name);
+        // movs r0, #0; bx lr
+        assert_eq!(routine.instructions, 2, "{}", routine.name);
+    }
+    // The fixture's EraseSector and ProgramPage return without touching the flash, which starts
+    // out holding the opposite of the erased value.
+    assert_eq!(report.erased_blank, Some(false));
+    assert_eq!(report.flash_matches, Some(false));
+    assert!(!report.passed());
+}
+
+#[test]
+fn maps_only_the_start_of_large_flash() {
+    let mut stub = stub();
+    stub.flash_end_addr = 0xF000_0000;
+    stub.flash_size = stub.flash_end_addr - stub.flash_start_addr;
+    let report = dry_run(&stub, &EmulatorConfig::default()).unwrap();
+    assert_eq!(report.routines.len(), 6);
+    assert_eq!(report.erased_blank, Some(false));
+
+    let mut empty = stub;
+    empty.flash_end_addr = empty.flash_start_addr;
+    assert!(dry_run(&empty, &EmulatorConfig::default()).is_err());
+}
+
+#[test]
+fn reports_what_a_routine_returned() {
+    // movs r0, #1; bx lr
+    let stub = patched(offset("EraseSector"), [0x01, 0x20, 0x70, 0x47]);
+    let report = dry_run(&stub, &EmulatorConfig::default()).unwrap();
+
+    let erase = report.routines.iter().find(|routine| routine.name == "EraseSector").unwrap();
+    assert_eq!((erase.return_value, &erase.fault), (Some(1), &None));
+    assert!(!report.passed());
+}
+
+#[test]
+fn stops_a_routine_that_never_returns() {
+    // b .
+    let stub = patched(offset("ProgramPage"), [0xFE, 0xE7, 0x00, 0xBF]);
+    let config = EmulatorConfig { instruction_limit: 1000, ..Default::default() };
+    let report = dry_run(&stub, &config).unwrap();
+
+    let program = report.routines.iter().find(|routine| routine.name == "ProgramPage").unwrap();
+    assert_eq!(program.return_value, None);
+    assert!(matches!(program.fault, Some(EmulationFault::Timeout { .. })));
+    assert_eq!(report.flash_matches, None);
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use soulcomposer::{
+    encryption::{EncryptionError, EncryptionKey, KeyRing},
+    prog::{
+        arm::{arm_error::ArmError, encryption::EncryptionAlgorithm, flash_stub_gen::ArmFlashStub},
+        flash_algorithm::FlashAlgorithm,
+    },
+};
+
+fn stub() -> ArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), "main".to_string(), false, 0).unwrap()
+}
+
+#[test]
+fn encrypts_and_decrypts_the_blobs() {
+    let stub = stub();
+    let key = EncryptionKey::generate("fleet").unwrap();
+    let encrypted = stub.encrypt(&key).unwrap();
+
+    let encryption = encrypted.encryption.as_ref().unwrap();
+    assert_eq!(encryption.algorithm, EncryptionAlgorithm::Aes256Gcm);
+    assert_eq!(encryption.key_id, "fleet");
+    assert_ne!(encrypted.instructions, stub.instructions);
+    // The tag is appended to the ciphertext.
+    let plain = base64::decode(&stub.instructions).unwrap();
+    assert_eq!(base64::decode(&encrypted.instructions).unwrap().len(), plain.len() + 16);
+    assert_eq!((encrypted.crc32, encrypted.pc_program_page), (stub.crc32, stub.pc_program_page));
+    assert!(encrypted.verify_crc32().is_ok());
+    assert!(matches!(encrypted.encrypt(&key), Err(EncryptionError::AlreadyEncrypted(_))));
+
+    let mut keys = KeyRing::new();
+    keys.insert(key.clone());
+    assert_eq!(encrypted.decrypt(&keys).unwrap(), stub);
+    assert!(matches!(stub.decrypt(&keys), Err(EncryptionError::NotEncrypted)));
+
+    // Encrypting twice uses fresh nonces.
+    assert_ne!(stub.encrypt(&key).unwrap().instructions, encrypted.instructions);
+}
+
+#[test]
+fn encrypted_stubs_are_never_loaded() {
+    let key = EncryptionKey::generate("fleet").unwrap();
+    let encrypted = stub().encrypt(&key).unwrap();
+
+    assert!(matches!(encrypted.blob(), Err(ArmError::StubEncrypted(id)) if id == "fleet"));
+    assert!(matches!(encrypted.decompose(), Err(ArmError::StubEncrypted(_))));
+    let err = encrypted.validate().unwrap_err();
+    assert!(matches!(err, ArmError::StubEncrypted(_)));
+    assert_eq!(err.code(), "SC0041");
+}
+
+#[test]
+fn refuses_wrong_keys_and_altered_blobs() {
+    let key = EncryptionKey::generate("fleet").unwrap();
+    let encrypted = stub().encrypt(&key).unwrap();
+
+    let mut wrong = KeyRing::new();
+    wrong.insert(EncryptionKey::generate("fleet").unwrap());
+    assert!(matches!(encrypted.decrypt(&wrong), Err(EncryptionError::Decrypt(id)) if id == "fleet"));
+
+    let mut other = KeyRing::new();
+    other.insert(EncryptionKey::generate("STM32F407VG").unwrap());
+    assert!(matches!(encrypted.decrypt(&other), Err(EncryptionError::UnknownKey(_))));
+
+    let mut keys = KeyRing::new();
+    keys.insert(key);
+    let mut altered = encrypted.clone();
+    let mut blob = base64::decode(&altered.instructions).unwrap();
+    blob[0] ^= 1;
+    altered.instructions = base64::encode(blob);
+    assert!(matches!(altered.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+
+    // The key ID is authenticated, relabelling the stub doesn't pass.
+    let mut relabelled = encrypted;
+    relabelled.encryption.as_mut().unwrap().key_id = "FLEET".to_string();
+    assert!(matches!(relabelled.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+}
+
+#[test]
+fn binds_blobs_to_their_stub_and_field() {
+    let key = EncryptionKey::generate("fleet").unwrap();
+    let mut keys = KeyRing::new();
+    keys.insert(key.clone());
+
+    let mut split = stub();
+    split.data_section_offset = base64::decode(&split.instructions).unwrap().len() as u32;
+    split.data_instructions = Some(base64::encode([0x5A; 16]));
+    let encrypted = split.encrypt(&key).unwrap();
+    assert_eq!(encrypted.decrypt(&keys).unwrap().data_instructions, split.data_instructions);
+
+    // The data blob passed off as the code.
+    let mut swapped = encrypted.clone();
+    let encryption = swapped.encryption.as_mut().unwrap();
+    std::mem::swap(&mut encryption.nonce, encryption.data_nonce.as_mut().unwrap());
+    std::mem::swap(&mut swapped.instructions, swapped.data_instructions.as_mut().unwrap());
+    assert!(matches!(swapped.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+
+    // The code of another stub sealed with the same key.
+    let mut other = stub();
+    other.name = "other".to_string();
+    let other = other.encrypt(&key).unwrap();
+    let mut transplanted = encrypted.clone();
+    transplanted.instructions = other.instructions.clone();
+    transplanted.encryption.as_mut().unwrap().nonce = other.encryption.as_ref().unwrap().nonce.clone();
+    assert!(matches!(transplanted.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+
+    let mut renamed = encrypted.clone();
+    renamed.name = "other".to_string();
+    assert!(matches!(renamed.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+
+    // The CRC covers the layout, editing it and the CRC doesn't pass either.
+    let mut edited = encrypted;
+    edited.flash_start_addr += 0x1000;
+    edited.crc32 = Some(edited.crc32.unwrap() ^ 1);
+    assert!(matches!(edited.decrypt(&keys), Err(EncryptionError::Decrypt(_))));
+}
+
+#[test]
+fn picks_device_keys_before_the_fleet_key() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("encryption-keys");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let device = EncryptionKey::generate("STM32F407VG").unwrap();
+    let fleet = EncryptionKey::generate("fleet").unwrap();
+    fs::write(dir.join("STM32F407VG.key"), device.to_hex()).unwrap();
+    fs::write(dir.join("fleet.key"), format!("{}\n", fleet.to_hex())).unwrap();
+    fs::write(dir.join("README"), "not a key").unwrap();
+
+    let keys = KeyRing::load(&dir).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys.select(Some("stm32f407vg")).unwrap(), &device);
+    assert_eq!(keys.select(Some("STM32F103C8")).unwrap(), &fleet);
+    assert_eq!(keys.select(None).unwrap(), &fleet);
+
+    let single = KeyRing::load(&dir.join("STM32F407VG.key")).unwrap();
+    assert_eq!(single.select(None).unwrap(), &device);
+    assert_eq!(single.select(Some("STM32F407VG")).unwrap(), &device);
+    assert!(matches!(KeyRing::new().select(Some("STM32F103C8")), Err(EncryptionError::NoKey(_))));
+    assert!(matches!(EncryptionKey::from_hex("short", "abcd"), Err(EncryptionError::InvalidKey { .. })));
+}
+
+#[test]
+fn never_encrypts_with_another_devices_key() {
+    let mut keys = KeyRing::new();
+    keys.insert(EncryptionKey::generate("STM32F407VG").unwrap());
+    let err = keys.select(Some("STM32F103C8")).unwrap_err();
+    assert!(matches!(err, EncryptionError::NoKey(device) if device == "STM32F103C8"));
+
+    let fleet = EncryptionKey::generate("fleet").unwrap();
+    keys.insert(fleet.clone());
+    assert_eq!(keys.select(Some("STM32F103C8")).unwrap(), &fleet);
+}
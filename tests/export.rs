@@ -0,0 +1,304 @@
+mod common;
+
+use soulcomposer::{
+    pack::pdsc::Pdsc,
+    prog::{
+        arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub, stub_group::ArmFlashStubGroup},
+        export::{
+            export, export_error::ExportError, export_model, export_model_named, export_named, export_probe_rs_family,
+            export_to, export_validated,
+            jlink::{jlink_script, JLinkOptions},
+            naming::FieldNaming,
+            probe_rs::{ProbeRsChipFamily, ProbeRsMemoryRegion},
+            OutputFormat,
+        },
+        flash_algorithm::FlashAlgorithm,
+    },
+};
+
+fn stub() -> ArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), "test-192k".to_string(), true, 0).unwrap()
+}
+
+#[test]
+fn format_names_round_trip() {
+    for format in OutputFormat::ALL.iter() {
+        assert_eq!(format.name().parse::<OutputFormat>().unwrap(), *format);
+    }
+    assert_eq!("C-Header".parse::<OutputFormat>().unwrap(), OutputFormat::CHeader);
+    assert!("xml".parse::<OutputFormat>().is_err());
+}
+
+#[test]
+fn model_formats_round_trip() {
+    let stub = stub();
+
+    let json = export(&stub, OutputFormat::Json).unwrap();
+    assert_eq!(serde_json::from_slice::<ArmFlashStub>(&json).unwrap(), stub);
+
+    let yaml = export(&stub, OutputFormat::Yaml).unwrap();
+    assert_eq!(serde_yaml::from_slice::<ArmFlashStub>(&yaml).unwrap(), stub);
+
+    let cbor = export(&stub, OutputFormat::Cbor).unwrap();
+    assert_eq!(ciborium::from_reader::<ArmFlashStub, _>(cbor.as_slice()).unwrap(), stub);
+
+    let msgpack = export(&stub, OutputFormat::Msgpack).unwrap();
+    assert_eq!(rmp_serde::from_slice::<ArmFlashStub>(&msgpack).unwrap(), stub);
+}
+
+#[test]
+fn extra_metadata_survives_the_model_formats() {
+    let plain = String::from_utf8(export(&stub(), OutputFormat::Json).unwrap()).unwrap();
+    assert!(!plain.contains("\"extra\""));
+
+    let mut stub = stub();
+    let crc = stub.compute_crc32().unwrap();
+    stub.extra.insert("ticket".to_string(), "FW-1234".into());
+    stub.extra.insert("line".to_string(), serde_json::json!({ "site": "SZ", "station": 3 }));
+    assert_eq!(stub.compute_crc32().unwrap(), crc);
+
+    let json = export_named(&stub, OutputFormat::Json, FieldNaming::Snake).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["extra"]["line"]["station"], 3);
+
+    let yaml = export(&stub, OutputFormat::Yaml).unwrap();
+    assert_eq!(serde_yaml::from_slice::<ArmFlashStub>(&yaml).unwrap(), stub);
+
+    let cbor = export(&stub, OutputFormat::Cbor).unwrap();
+    assert_eq!(ciborium::from_reader::<ArmFlashStub, _>(cbor.as_slice()).unwrap(), stub);
+}
+
+#[test]
+fn groups_only_export_as_models() {
+    let group = ArmFlashStubGroup::merge("device".to_string(), vec![stub()]).unwrap();
+
+    let yaml = export_model(&group, OutputFormat::Yaml).unwrap();
+    assert_eq!(serde_yaml::from_slice::<ArmFlashStubGroup>(&yaml).unwrap(), group);
+    assert!(export_model(&group, OutputFormat::CHeader).is_err());
+}
+
+#[test]
+fn bin_is_the_ram_image() {
+    let stub = stub();
+    assert_eq!(export(&stub, OutputFormat::Bin).unwrap(), stub.blob().unwrap());
+}
+
+#[test]
+fn c_header_defines_the_stub() {
+    let stub = stub();
+    let header = String::from_utf8(export(&stub, OutputFormat::CHeader).unwrap()).unwrap();
+
+    assert!(header.contains("#ifndef TEST_192K_H"));
+    assert!(header.contains("#define TEST_192K_FLASH_START 0x08000000u"));
+    assert!(header.contains("#define TEST_192K_PAGE_SIZE 0x00000100u"));
+    assert!(header.contains(&format!("#define TEST_192K_PC_INIT 0x{:08x}u", stub.pc_init.unwrap())));
+    assert!(header.contains("static const uint32_t TEST_192K_SECTORS[2][3]"));
+    assert!(header.contains("{ 0x08010000u, 0x00010000u, 2u },"));
+    assert!(header.contains(&format!("static const uint8_t TEST_192K_BLOB[{}]", stub.blob().unwrap().len())));
+}
+
+#[test]
+fn rust_source_defines_the_stub() {
+    let stub = stub();
+    let source = String::from_utf8(export(&stub, OutputFormat::Rust).unwrap()).unwrap();
+
+    assert!(source.contains("pub const NAME: &str = \"test-192k\";"));
+    assert!(source.contains("pub const FLASH_END: u32 = 0x08030000;"));
+    assert!(source.contains("(0x08000000, 0x00004000, 4),"));
+    assert!(source.contains(&format!("pub const BLOB: [u8; {}] = [", stub.blob().unwrap().len())));
+}
+
+#[test]
+fn probe_rs_yaml_lists_the_algorithm() {
+    let stub = stub();
+    let yaml = export(&stub, OutputFormat::ProbeRsYaml).unwrap();
+    let algorithms: serde_yaml::Value = serde_yaml::from_slice(&yaml).unwrap();
+    let algorithm = &algorithms[0];
+
+    assert_eq!(algorithm["name"], "test-192k");
+    assert_eq!(algorithm["default"], true);
+    assert_eq!(algorithm["instructions"], base64::encode(stub.blob().unwrap()).as_str());
+    assert_eq!(algorithm["pc_program_page"], u64::from(stub.pc_program_page));
+    assert_eq!(algorithm["flash_properties"]["address_range"]["start"], 0x0800_0000);
+    assert_eq!(algorithm["flash_properties"]["page_size"], 256);
+    assert_eq!(algorithm["flash_properties"]["sectors"][1]["address"], 0x1_0000);
+    assert_eq!(algorithm["flash_properties"]["sectors"][1]["size"], 0x1_0000);
+}
+
+const FAMILY_PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <vendor>Test</vendor>
+  <name>Test_DFP</name>
+  <releases><release version="1.2.0">Latest</release></releases>
+  <devices>
+    <family Dfamily="Test Series" Dvendor="Test:0">
+      <processor Dcore="Cortex-M4"/>
+      <device Dname="TEST192">
+        <memory name="Flash" access="rx" start="0x08000000" size="0x30000" startup="1" default="1"/>
+        <memory name="SRAM" access="rwx" start="0x20000000" size="0x8000" default="1"/>
+        <algorithm name="CMSIS\Flash\TEST_192.FLM" start="0x08000000" size="0x30000" default="1"/>
+      </device>
+      <device Dname="TEST192DUAL">
+        <processor Pname="CM7" Dcore="Cortex-M7"/>
+        <processor Pname="CM4" Dcore="Cortex-M4"/>
+        <debug Pname="CM4" __ap="3"/>
+        <memory id="IROM1" start="0x08000000" size="0x30000" startup="1"/>
+        <memory id="IRAM2" start="0x30000000" size="0x4000" Pname="CM4"/>
+        <algorithm name="CMSIS\Flash\TEST_192.FLM" start="0x08000000" size="0x30000" default="1"/>
+        <algorithm name="CMSIS\Flash\BROKEN.FLM" start="0x90000000" size="0x1000"/>
+      </device>
+    </family>
+    <family Dfamily="Other Series" Dvendor="Test:0">
+      <processor Dcore="Cortex-M0"/>
+      <device Dname="OTHER"/>
+    </family>
+  </devices>
+</package>"#;
+
+#[test]
+fn probe_rs_family_describes_every_device() {
+    let pdsc = Pdsc::parse(FAMILY_PDSC).unwrap();
+    let algorithms = vec![("CMSIS/Flash/TEST_192.FLM".to_string(), stub().validate().unwrap())];
+    let family = ProbeRsChipFamily::from_pdsc(&pdsc, "test series", &algorithms).unwrap();
+
+    assert_eq!(family.name, "Test Series");
+    assert_eq!(family.pack_file_release.as_deref(), Some("1.2.0"));
+    assert_eq!(family.variants.len(), 2);
+    assert_eq!(family.flash_algorithms.len(), 1);
+    assert!(family.flash_algorithms[0].cores.is_empty());
+    // The FLM that didn't convert is left out of the device.
+    assert_eq!(family.variants[1].flash_algorithms, ["test-192k"]);
+
+    let yaml: serde_yaml::Value = serde_yaml::from_slice(&export_probe_rs_family(&family).unwrap()).unwrap();
+    assert_eq!(yaml["generated_from_pack"], true);
+    let single = &yaml["variants"][0];
+    assert_eq!(single["cores"][0]["name"], "main");
+    assert_eq!(single["cores"][0]["type"], "armv7em");
+    let text = String::from_utf8(export_probe_rs_family(&family).unwrap()).unwrap();
+    assert!(text.contains("!Nvm"), "{}", text);
+    assert!(text.contains("!Ram"), "{}", text);
+    assert!(text.contains("ap: !v1 3"), "{}", text);
+
+    let dual = &family.variants[1];
+    let names: Vec<&str> = dual.cores.iter().map(|core| core.name.as_str()).collect();
+    assert_eq!(names, ["cm7", "cm4"]);
+    assert_eq!(dual.cores[0].core_type, "armv7em");
+    match &dual.memory_map[..] {
+        [ProbeRsMemoryRegion::Nvm(flash), ProbeRsMemoryRegion::Ram(ram)] => {
+            assert_eq!((flash.range.start, flash.range.end), (0x0800_0000, 0x0803_0000));
+            assert!(flash.access.is_some_and(|access| access.boot));
+            assert_eq!(flash.cores, ["cm7", "cm4"]);
+            assert_eq!(ram.cores, ["cm4"]);
+        }
+        regions => panic!("unexpected memory map {:?}", regions),
+    }
+
+    assert!(matches!(ProbeRsChipFamily::from_pdsc(&pdsc, "Missing", &algorithms), Err(ExportError::UnknownFamily(_))));
+}
+
+#[test]
+fn browser_bindings_match_export() {
+    let flm = common::build_flm();
+    let json = soulcomposer::wasm::convert_flm(&flm, "test-192k".to_string(), true, 0).unwrap();
+    assert_eq!(json.into_bytes(), export(&stub(), OutputFormat::Json).unwrap());
+
+    let header = soulcomposer::wasm::export_flm(&flm, "test-192k".to_string(), true, 0, "c-header").unwrap();
+    assert_eq!(header, export(&stub(), OutputFormat::CHeader).unwrap());
+}
+
+#[test]
+fn prelude_covers_a_conversion() {
+    use soulcomposer::prelude::*;
+
+    let data = common::build_flm();
+    let elf = Elf::parse(&data).unwrap();
+    let (composed, diagnostics) = compose_stub(&elf, &data, &ComposeOptions::new("test-192k")).unwrap();
+    assert!(diagnostics.is_empty());
+    assert_eq!(FlashDevice::from_elf(&elf, &data).unwrap().name(), common::FLM_DEVICE_NAME);
+    assert_eq!(composed, ArmFlashStub::from_elf(&data, "test-192k".to_string(), false, 0).unwrap());
+    assert!(export(&composed, OutputFormat::Json).is_ok());
+}
+
+#[test]
+fn hardware_formats_need_a_valid_stub() {
+    let validated = stub().validate().unwrap();
+    assert_eq!(export_validated(&validated, OutputFormat::Bin).unwrap(), export(&stub(), OutputFormat::Bin).unwrap());
+
+    let mut edited = stub();
+    edited.pc_program_page = 0x10_0000;
+    assert!(export(&edited, OutputFormat::Json).is_ok());
+    assert!(matches!(export(&edited, OutputFormat::Bin), Err(ExportError::Arm(ArmError::CrcMismatch { .. }))));
+
+    edited.crc32 = Some(edited.compute_crc32().unwrap());
+    assert!(matches!(edited.validate(), Err(ArmError::InvalidEntryPoint { .. })));
+}
+
+#[test]
+fn renames_fields_while_serializing() {
+    let stub = stub();
+    let snake: serde_json::Value =
+        serde_json::from_slice(&export_named(&stub, OutputFormat::Json, FieldNaming::Snake).unwrap()).unwrap();
+    assert_eq!(snake["flash_start_addr"], 0x0800_0000);
+    assert!(snake["init_parameters"]["clock_source"].is_string());
+    assert!(snake.get("flashStartAddr").is_none());
+
+    let group = vec![stub.clone()];
+    let kebab = export_model_named(&group, OutputFormat::Yaml, FieldNaming::Kebab).unwrap();
+    let kebab: serde_yaml::Value = serde_yaml::from_slice(&kebab).unwrap();
+    assert_eq!(kebab[0]["pc-program-page"], serde_yaml::Value::from(stub.pc_program_page));
+
+    assert_eq!(export_named(&stub, OutputFormat::Json, FieldNaming::Camel).unwrap(), export(&stub, OutputFormat::Json).unwrap());
+    assert_eq!("Snake-Case".parse::<FieldNaming>().unwrap(), FieldNaming::Snake);
+    assert!("pascal-case".parse::<FieldNaming>().is_err());
+}
+
+#[test]
+fn streams_through_readers_and_writers() {
+    let flm = common::build_flm();
+    let read = ArmFlashStub::from_reader(std::io::Cursor::new(&flm), "test-192k".to_string(), true, 0).unwrap();
+    assert_eq!(read, stub());
+
+    for format in [OutputFormat::Json, OutputFormat::Bin, OutputFormat::CHeader].iter() {
+        let mut written = Vec::new();
+        export_to(&read, *format, FieldNaming::Camel, &mut written).unwrap();
+        assert_eq!(written, export(&read, *format).unwrap());
+    }
+}
+
+#[test]
+fn jlink_script_runs_each_routine_from_ram() {
+    let stub = stub().validate().unwrap();
+    let mut options = JLinkOptions {
+        ram_start: 0x2000_0000,
+        ram_size: 0x1_0000,
+        device: Some("STM32F407VG".to_string()),
+        speed_khz: 4000,
+        scratch: None,
+        image_file: "algo.bin".to_string(),
+        page_file: "algo.page.bin".to_string(),
+    };
+    let jlink = jlink_script(&stub, &options).unwrap();
+    let last = stub.scratch_sector(None).unwrap();
+    assert_eq!(jlink.sector, last.start as u32..last.end as u32);
+    assert_eq!(jlink.page, stub.test_pattern(stub.flash_page_size as usize));
+    assert_eq!(&jlink.image[..stub.blob().unwrap().len()], &stub.blob().unwrap()[..]);
+
+    let lines: Vec<&str> = jlink.script.lines().filter(|line| !line.starts_with("//") && !line.is_empty()).collect();
+    assert_eq!(lines[..7], ["device STM32F407VG", "si SWD", "speed 4000", "connect", "r", "h", "loadbin algo.bin, 0x20000000"]);
+    assert_eq!(lines[7], format!("w4 {:#010X}, 0xBE00BE00", jlink.layout.trampoline));
+    let entries: Vec<String> = lines.iter().filter_map(|line| line.strip_prefix("SetPC ")).map(String::from).collect();
+    let entry = |pc: u32| format!("{:#010X}", (0x2000_0000 + pc) & !1);
+    let (init, uninit) = (stub.pc_init.unwrap(), stub.pc_uninit.unwrap());
+    let expected: Vec<String> = [init, stub.pc_erase_sector, uninit, init, stub.pc_program_page, uninit].iter().map(|pc| entry(*pc)).collect();
+    assert_eq!(entries, expected);
+    assert!(lines.contains(&format!("wreg R0, {:#010X}", last.start).as_str()));
+    assert!(lines.contains(&format!("loadbin algo.page.bin, {:#010X}", jlink.layout.buffer).as_str()));
+    assert!(lines.contains(&format!("verifybin algo.page.bin, {:#010X}", last.start).as_str()));
+    assert_eq!(lines.last(), Some(&"qc"));
+
+    options.scratch = Some(0x1000_0000);
+    assert!(matches!(jlink_script(&stub, &options), Err(ExportError::ScratchOutOfRange(0x1000_0000))));
+    options.scratch = None;
+    options.ram_size = 0x100;
+    assert!(matches!(jlink_script(&stub, &options), Err(ExportError::Arm(ArmError::RamOverflow { available: 0x100, .. }))));
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::{
+    ffi::{CStr, CString},
+    mem::MaybeUninit,
+    ptr, slice,
+};
+
+use soulcomposer::ffi::*;
+
+#[test]
+fn parses_flash_device() {
+    let flm = common::build_flm();
+    let mut device = MaybeUninit::uninit();
+    assert_eq!(unsafe { sc_parse_flm(flm.as_ptr(), flm.len(), device.as_mut_ptr()) }, ScStatus::Ok);
+    let mut device = unsafe { device.assume_init() };
+
+    let name = unsafe { CStr::from_ptr(device.name.as_ptr()) };
+    assert_eq!(name.to_str().unwrap(), common::FLM_DEVICE_NAME);
+    assert_eq!((device.start_address, device.device_size, device.page_size), (0x0800_0000, 0x30000, 256));
+    let sectors = unsafe { slice::from_raw_parts(device.sectors, device.sector_count) };
+    assert_eq!((sectors[1].address, sectors[1].size), (0x10000, 0x10000));
+
+    unsafe { sc_flash_device_free(&mut device) };
+    assert!(device.sectors.is_null());
+}
+
+#[test]
+fn composes_stub_json() {
+    let flm = common::build_flm();
+    let name = CString::new("algo").unwrap();
+    let mut json = ptr::null_mut();
+    let status = unsafe { sc_compose_stub(flm.as_ptr(), flm.len(), name.as_ptr(), true, 0, ptr::null(), &mut json) };
+    assert_eq!(status, ScStatus::Ok);
+
+    let stub: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+    assert_eq!(stub["name"], "algo");
+    unsafe { sc_string_free(json) };
+}
+
+#[test]
+fn reports_errors() {
+    let mut json = ptr::null_mut();
+    let garbage = [0u8; 16];
+    let status = unsafe { sc_compose_stub(garbage.as_ptr(), garbage.len(), ptr::null(), false, 0, ptr::null(), &mut json) };
+    assert_eq!(status, ScStatus::ParseFailed);
+    assert!(json.is_null());
+    assert!(!sc_last_error().is_null());
+    assert_eq!(unsafe { CStr::from_ptr(sc_last_error_code()) }.to_str().unwrap(), "SC0003");
+
+    let status = unsafe { sc_parse_flm(ptr::null(), 0, ptr::null_mut()) };
+    assert_eq!(status, ScStatus::InvalidArgument);
+    assert!(sc_last_error_code().is_null());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use soulcomposer::{
+    prog::{
+        arm::{flash_device::SectorInfo, flash_stub_gen::ArmFlashStub},
+        export::{export, OutputFormat},
+    },
+    ArmError, Elf, FlashDevice, ParseOptions,
+};
+
+#[test]
+fn descriptor_fields_end_up_in_the_parsed_device() {
+    let fields = common::DescriptorFields {
+        name: "Scratch 8kB".to_string(),
+        start_address: 0x2000_0000,
+        device_size: 0x2000,
+        page_size: 64,
+        erased_default_value: 0x00,
+        sectors: vec![SectorInfo { address: 0, size: 0x400 }],
+        ..Default::default()
+    };
+    let data = common::build_flm_with(&fields.to_bytes(), "ScratchDevice");
+    let elf = Elf::parse(&data).unwrap();
+    assert!(matches!(FlashDevice::from_elf(&elf, &data), Err(ArmError::SymbolNotFound(_))));
+
+    let options = ParseOptions { descriptor_symbol: Some("ScratchDevice".to_string()), ..Default::default() };
+    let device = FlashDevice::from_elf_with_options(&elf, &data, &options).unwrap();
+    assert_eq!(device.name(), "Scratch 8kB");
+    assert_eq!((device.start_address(), device.device_size(), device.page_size()), (0x2000_0000, 0x2000, 64));
+    assert_eq!(device.erased_default_value(), 0x00);
+    assert_eq!(device.iter_sector_ranges().count(), 8);
+
+    let unterminated = common::DescriptorFields { name: "x".repeat(200), ..Default::default() };
+    let data = common::build_flm_with(&unterminated.to_bytes(), "FlashDevice");
+    let parsed = FlashDevice::from_elf(&Elf::parse(&data).unwrap(), &data).unwrap();
+    assert_eq!(parsed.name().len(), 128);
+    assert_eq!(common::build_flm(), common::build_flm_with(&common::DescriptorFields::default().to_bytes(), "FlashDevice"));
+}
+
+#[test]
+fn fixture_header_matches_the_golden_file() {
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "test-192k".to_string(), true, 0).unwrap();
+    let header = export(&stub, OutputFormat::CHeader).unwrap();
+    common::assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/test-192k.h"), &header);
+}
+
+#[test]
+#[should_panic(expected = "is missing")]
+fn missing_golden_files_fail() {
+    let path = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden/missing.h");
+    let _ = std::fs::remove_file(&path);
+    common::assert_golden(&path, b"not blessed");
+}
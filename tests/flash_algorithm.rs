@@ -0,0 +1,43 @@
+use soulcomposer::prog::{
+    arm::flash_stub_gen::ArmFlashStub,
+    flash_algorithm::{validate_entry_points, AnyFlashStub, FlashAlgorithm},
+    prog_error::ProgError,
+};
+
+#[test]
+fn arm_entry_points_strip_thumb_bit() {
+    let mut stub = ArmFlashStub::default();
+    stub.instructions = base64::encode([0u8; 16]);
+    stub.pc_init = Some(0x1);
+    stub.pc_program_page = 0x9;
+    stub.pc_erase_sector = 0x5;
+
+    let entry_points = stub.entry_points();
+    assert_eq!(entry_points.init, Some(0));
+    assert_eq!(entry_points.program_page, Some(8));
+    assert!(validate_entry_points(&stub).is_ok());
+}
+
+#[test]
+fn entry_point_past_blob_is_rejected() {
+    let mut stub = ArmFlashStub::default();
+    stub.instructions = base64::encode([0u8; 16]);
+    stub.pc_program_page = 0x21;
+
+    let result = AnyFlashStub::Arm(stub).validate();
+    assert!(matches!(result, Err(ProgError::EntryPointOutOfRange { name: "ProgramPage", offset: 0x20, .. })));
+}
+
+#[test]
+fn unknown_machine_is_rejected() {
+    let mut header = vec![0u8; 52];
+    header[..4].copy_from_slice(b"\x7fELF");
+    header[4] = 1;
+    header[5] = 1;
+    header[6] = 1;
+    header[18..20].copy_from_slice(&3u16.to_le_bytes());
+    header[40..42].copy_from_slice(&52u16.to_le_bytes());
+
+    let result = AnyFlashStub::from_elf(&header, "x86".to_string(), false, 0);
+    assert!(matches!(result, Err(ProgError::UnsupportedMachine(3))));
+}
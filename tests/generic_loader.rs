@@ -0,0 +1,69 @@
+use soulcomposer::prog::generic::{
+    descriptor::{ArgumentValue, LoaderDescriptor},
+    generic_error::GenericError,
+};
+
+const DESCRIPTOR: &str = r#"
+name = "Boot ROM helper"
+architecture = "arm"
+callingConvention = "aapcs"
+blob = "AAAAAAAAAAAAAAAAAAAAAA=="
+
+[flash]
+start = 0x08000000
+size = 0x80000
+pageSize = 256
+sectorSize = 4096
+
+[[entries]]
+name = "program"
+offset = 4
+thumb = true
+arguments = ["address", "size", "buffer", { constant = 7 }]
+
+[[entries]]
+name = "erase"
+offset = 8
+thumb = true
+registers = ["r4"]
+returnRegister = "r1"
+arguments = ["address"]
+"#;
+
+#[test]
+fn composes_from_toml() {
+    let descriptor = LoaderDescriptor::from_toml(DESCRIPTOR).unwrap();
+    let stub = descriptor.compose().unwrap();
+
+    assert_eq!(stub.flash.erased_value, 0xFF);
+    let program = stub.entry_point("program").unwrap();
+    assert_eq!(program.arguments[3].register, "r3");
+    assert_eq!(program.arguments[3].value, ArgumentValue::Constant(7));
+    assert_eq!(program.return_register, "r0");
+
+    let erase = stub.entry_point("erase").unwrap();
+    assert_eq!(erase.arguments[0].register, "r4");
+    assert_eq!(erase.return_register, "r1");
+}
+
+#[test]
+fn json_and_toml_agree() {
+    let descriptor = LoaderDescriptor::from_toml(DESCRIPTOR).unwrap();
+    let json = serde_json::to_string(&descriptor).unwrap();
+    assert_eq!(LoaderDescriptor::from_json(&json).unwrap(), descriptor);
+}
+
+#[test]
+fn rejects_bad_descriptors() {
+    let mut descriptor = LoaderDescriptor::from_toml(DESCRIPTOR).unwrap();
+    descriptor.entries[0].arguments.push(ArgumentValue::Clock);
+    assert!(matches!(descriptor.compose(), Err(GenericError::TooManyArguments { max: 4, .. })));
+
+    let mut descriptor = LoaderDescriptor::from_toml(DESCRIPTOR).unwrap();
+    descriptor.entries[1].registers = vec!["a0".to_string()];
+    assert!(matches!(descriptor.compose(), Err(GenericError::UnknownRegister { .. })));
+
+    let mut descriptor = LoaderDescriptor::from_toml(DESCRIPTOR).unwrap();
+    descriptor.entries[1].offset = 16;
+    assert!(matches!(descriptor.compose(), Err(GenericError::EntryPointOutOfRange { .. })));
+}
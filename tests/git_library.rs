@@ -0,0 +1,100 @@
+#![cfg(feature = "git")]
+
+mod common;
+
+use std::{fs, path::PathBuf, process::Command};
+
+use soulcomposer::{
+    git_library::{GitLibrary, Resolve, SyncError, SyncReport},
+    prog::arm::flash_stub_gen::ArmFlashStub,
+    registry::StubRegistry,
+};
+
+/// A bare remote and a library checkout for each of two sites.
+fn sites(name: &str) -> (GitLibrary, GitLibrary) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let remote = dir.join("remote.git");
+    assert!(Command::new("git").args(["init", "-q", "--bare"]).arg(&remote).status().unwrap().success());
+
+    let remote = remote.display().to_string();
+    (GitLibrary::new(remote.clone(), "main", dir.join("a")), GitLibrary::new(remote, "main", dir.join("b")))
+}
+
+fn stub(description: &str) -> ArmFlashStub {
+    let mut stub = ArmFlashStub::from_elf(common::build_flm(), "main".to_string(), false, 0).unwrap();
+    stub.description = description.to_string();
+    stub
+}
+
+fn report(added: &[&str], updated: &[&str], removed: &[&str]) -> SyncReport {
+    let names = |devices: &[&str]| devices.iter().map(|device| device.to_string()).collect();
+    SyncReport { added: names(added), updated: names(updated), removed: names(removed) }
+}
+
+#[test]
+fn shares_stubs_between_sites() {
+    let (a, b) = sites("git-library-share");
+    assert_eq!(b.pull(Resolve::Abort).unwrap(), SyncReport::default());
+
+    a.stage("STM32F407VG", &[stub("first")]).unwrap();
+    assert_eq!(a.push("Add STM32F407VG", Resolve::Abort).unwrap(), report(&["STM32F407VG"], &[], &[]));
+    assert_eq!(a.push("Nothing new", Resolve::Abort).unwrap(), SyncReport::default());
+
+    assert_eq!(b.pull(Resolve::Abort).unwrap(), report(&["STM32F407VG"], &[], &[]));
+    let registry = StubRegistry::new();
+    assert_eq!(b.load_into(&registry).unwrap(), 1);
+    assert_eq!(registry.get("STM32F407VG").unwrap()[0], stub("first"));
+
+    // Changes to different devices merge, the second push pulls the first one in.
+    a.stage("STM32F407VG", &[stub("second")]).unwrap();
+    a.push("Update STM32F407VG", Resolve::Abort).unwrap();
+    b.stage("NRF52840", &[stub("nordic")]).unwrap();
+    assert_eq!(b.push("Add NRF52840", Resolve::Abort).unwrap(), report(&["NRF52840"], &[], &[]));
+    assert_eq!(b.load().unwrap()["STM32F407VG"], [stub("second")]);
+
+    assert!(a.unstage("STM32F407VG").unwrap());
+    assert_eq!(a.push("Drop STM32F407VG", Resolve::Abort).unwrap(), report(&[], &[], &["STM32F407VG"]));
+    assert_eq!(a.load().unwrap().keys().collect::<Vec<_>>(), ["NRF52840"]);
+    assert_eq!(b.pull(Resolve::Abort).unwrap(), report(&[], &[], &["STM32F407VG"]));
+}
+
+#[test]
+fn detects_conflicting_changes() {
+    let (a, b) = sites("git-library-conflict");
+    a.stage("STM32F407VG", &[stub("first")]).unwrap();
+    a.push("Add STM32F407VG", Resolve::Abort).unwrap();
+    b.pull(Resolve::Abort).unwrap();
+
+    a.stage("STM32F407VG", &[stub("site a")]).unwrap();
+    a.push("Site A build", Resolve::Abort).unwrap();
+    b.stage("STM32F407VG", &[stub("site b")]).unwrap();
+
+    match b.push("Site B build", Resolve::Abort) {
+        Err(SyncError::Conflicts(devices)) => assert_eq!(devices, ["STM32F407VG"]),
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+    // Nothing was thrown away, the local change is still there to resolve.
+    assert_eq!(b.load().unwrap()["STM32F407VG"], [stub("site b")]);
+
+    assert_eq!(b.push("Site B build", Resolve::Ours).unwrap(), report(&[], &["STM32F407VG"], &[]));
+    a.pull(Resolve::Abort).unwrap();
+    assert_eq!(a.load().unwrap()["STM32F407VG"], [stub("site b")]);
+
+    a.stage("STM32F407VG", &[stub("site a again")]).unwrap();
+    a.push("Site A build", Resolve::Abort).unwrap();
+    b.stage("STM32F407VG", &[stub("site b again")]).unwrap();
+    b.pull(Resolve::Theirs).unwrap();
+    assert_eq!(b.load().unwrap()["STM32F407VG"], [stub("site a again")]);
+}
+
+#[test]
+fn checks_content_hashes() {
+    let (a, _) = sites("git-library-hashes");
+    a.stage("STM32F407VG", &[stub("first")]).unwrap();
+    fs::write(a.checkout().join("STM32F407VG.json"), b"[]").unwrap();
+
+    assert!(matches!(a.load(), Err(SyncError::HashMismatch { .. })));
+    assert!(matches!(a.push("Tampered", Resolve::Abort), Err(SyncError::HashMismatch { .. })));
+}
@@ -0,0 +1,33 @@
+/* Generated by soul-composer from test-192k, do not edit. */
+#ifndef TEST_192K_H
+#define TEST_192K_H
+
+#include <stdint.h>
+
+#define TEST_192K_FLASH_START 0x08000000u
+#define TEST_192K_FLASH_END 0x08030000u
+#define TEST_192K_PAGE_SIZE 0x00000100u
+#define TEST_192K_ERASED_VALUE 0x000000ffu
+#define TEST_192K_PROGRAM_TIMEOUT 0x00000064u
+#define TEST_192K_ERASE_TIMEOUT 0x00000bb8u
+#define TEST_192K_DATA_SECTION_OFFSET 0x00000014u
+#define TEST_192K_STACK_SIZE 0x00000040u
+#define TEST_192K_RAM_REQUIRED 0x00000154u
+#define TEST_192K_PC_PROGRAM_PAGE 0x00000011u
+#define TEST_192K_PC_ERASE_SECTOR 0x0000000du
+#define TEST_192K_PC_INIT 0x00000001u
+#define TEST_192K_PC_UNINIT 0x00000005u
+#define TEST_192K_PC_ERASE_ALL 0x00000009u
+
+/* address, sector size, sector count */
+static const uint32_t TEST_192K_SECTORS[2][3] = {
+    { 0x08000000u, 0x00004000u, 4u },
+    { 0x08010000u, 0x00010000u, 2u },
+};
+
+static const uint8_t TEST_192K_BLOB[20] = {
+    0x00, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47, 0x00, 0x20, 0x70, 0x47,
+    0x00, 0x20, 0x70, 0x47,
+};
+
+#endif /* TEST_192K_H */
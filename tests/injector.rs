@@ -0,0 +1,262 @@
+#![cfg(feature = "injector")]
+
+#[cfg(feature = "hil")]
+mod common;
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use soulcomposer::{
+    injector::{
+        begin_payload, verify_payload, FileKind, Frame, FrameType, UploadError, Uploader, VerifyReport, LAST_SECTOR, MAX_PAYLOAD,
+        PROTOCOL_VERSION,
+    },
+    progress::Progress,
+};
+
+/// A programmer in memory: answers frames as they are written, stores finished files.
+#[derive(Default)]
+struct Programmer {
+    received: Vec<u8>,
+    replies: VecDeque<u8>,
+    max_payload: u16,
+    /// Answers to drop, counting down, to make the host send again.
+    drop_answers: usize,
+    last_sequence: Option<u16>,
+    current: Option<(FileKind, String, Vec<u8>)>,
+    files: Vec<(FileKind, String, Vec<u8>)>,
+    /// Address the read back of `Verify` differs at, for a target with a bad sector.
+    mismatch: Option<u32>,
+    /// Scratch addresses `Verify` was sent.
+    verified: Vec<u32>,
+}
+
+impl Programmer {
+    fn new(max_payload: u16) -> Self {
+        Programmer { max_payload, ..Programmer::default() }
+    }
+
+    fn answer(&mut self, frame: Frame) {
+        let repeated = self.last_sequence == Some(frame.sequence);
+        self.last_sequence = Some(frame.sequence);
+
+        let reply = match frame.typ {
+            FrameType::Hello => {
+                let mut payload = vec![PROTOCOL_VERSION];
+                payload.extend_from_slice(&self.max_payload.to_le_bytes());
+                Frame::new(FrameType::Ack, frame.sequence, payload)
+            }
+            FrameType::Begin => {
+                let kind = FileKind::from_code(frame.payload[0]).unwrap();
+                let name = String::from_utf8(frame.payload[9..].to_vec()).unwrap();
+                self.current = Some((kind, name, Vec::new()));
+                Frame::new(FrameType::Ack, frame.sequence, Vec::new())
+            }
+            FrameType::Chunk => {
+                let (_, _, data) = self.current.as_mut().unwrap();
+                let offset = u32::from_le_bytes([frame.payload[0], frame.payload[1], frame.payload[2], frame.payload[3]]);
+                if !repeated {
+                    assert_eq!(offset as usize, data.len());
+                    data.extend_from_slice(&frame.payload[4..]);
+                }
+                Frame::new(FrameType::Ack, frame.sequence, (data.len() as u32).to_le_bytes().to_vec())
+            }
+            FrameType::Finish => {
+                if let Some(file) = self.current.take() {
+                    self.files.push(file);
+                }
+                Frame::new(FrameType::Ack, frame.sequence, Vec::new())
+            }
+            FrameType::Verify => {
+                let scratch = u32::from_le_bytes([frame.payload[0], frame.payload[1], frame.payload[2], frame.payload[3]]);
+                let name = String::from_utf8(frame.payload[4..].to_vec()).unwrap();
+                if !self.files.iter().any(|(kind, file, _)| *kind == FileKind::Stub && *file == name) {
+                    let mut payload = vec![2];
+                    payload.extend_from_slice(b"no such stub");
+                    Frame::new(FrameType::Nack, frame.sequence, payload)
+                } else {
+                    self.verified.push(scratch);
+                    let report = VerifyReport {
+                        passed: self.mismatch.is_none(),
+                        sector: 0x0807_0000..0x0808_0000,
+                        mismatch: self.mismatch,
+                        elapsed: Duration::from_millis(85),
+                    };
+                    Frame::new(FrameType::Ack, frame.sequence, report.encode())
+                }
+            }
+            FrameType::Ack | FrameType::Nack => unreachable!(),
+        };
+
+        if self.drop_answers > 0 {
+            self.drop_answers -= 1;
+            return;
+        }
+        self.replies.extend(reply.encode());
+    }
+}
+
+impl Write for Programmer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.received.extend_from_slice(buf);
+        while let Some((frame, size)) = Frame::decode(&self.received).unwrap() {
+            self.received.drain(..size);
+            self.answer(frame);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Programmer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.replies.is_empty() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let count = buf.len().min(self.replies.len());
+        for (byte, reply) in buf.iter_mut().zip(self.replies.drain(..count)) {
+            *byte = reply;
+        }
+        Ok(count)
+    }
+}
+
+#[test]
+fn frames_round_trip() {
+    let frame = Frame::new(FrameType::Chunk, 0x1234, vec![1, 2, 3]);
+    let bytes = frame.encode();
+    assert_eq!(bytes[..6], [0xA5, 0x03, 0x34, 0x12, 3, 0]);
+    assert_eq!(bytes.len(), 13);
+    assert_eq!(Frame::decode(&bytes).unwrap(), Some((frame.clone(), 13)));
+    assert_eq!(Frame::decode(&bytes[..12]).unwrap(), None);
+
+    // Garbage before the magic is skipped when reading from a port.
+    let mut stream = vec![0x00, 0x42];
+    stream.extend_from_slice(&bytes);
+    assert_eq!(Frame::read_from(&mut stream.as_slice()).unwrap(), frame);
+
+    let mut corrupted = bytes;
+    corrupted[7] ^= 0xFF;
+    assert!(matches!(Frame::decode(&corrupted), Err(UploadError::BadFrame(_))));
+}
+
+#[test]
+fn uploads_in_chunks_the_programmer_takes() {
+    let data: Vec<u8> = (0..1000u32).map(|value| value as u8).collect();
+    let mut uploader = Uploader::connect(Programmer::new(260)).unwrap();
+    assert_eq!(uploader.chunk_size(), 256);
+
+    let mut reported = Vec::new();
+    let mut progress = |progress: Progress<'_>| reported.push((progress.completed, progress.total));
+    uploader.upload(FileKind::Stub, "algo", &data, &mut progress).unwrap();
+    uploader.upload(FileKind::Manifest, "banks", b"{}", &mut |_: Progress<'_>| {}).unwrap();
+
+    assert_eq!(reported, [(0, 1000), (256, 1000), (512, 1000), (768, 1000)]);
+    let programmer = uploader.into_inner();
+    assert_eq!(programmer.files.len(), 2);
+    assert_eq!(programmer.files[0], (FileKind::Stub, "algo".to_string(), data));
+    assert_eq!(programmer.files[1].0, FileKind::Manifest);
+}
+
+#[test]
+fn sends_unanswered_frames_again() {
+    let mut programmer = Programmer::new(MAX_PAYLOAD as u16);
+    programmer.drop_answers = 2;
+    let mut uploader = Uploader::connect(programmer).unwrap();
+    uploader.upload(FileKind::Stub, "algo", &[0x5A; 64], &mut |_: Progress<'_>| {}).unwrap();
+    assert_eq!(uploader.into_inner().files[0].2, [0x5A; 64]);
+
+    let mut programmer = Programmer::new(MAX_PAYLOAD as u16);
+    programmer.drop_answers = 3;
+    assert!(matches!(Uploader::connect(programmer), Err(UploadError::Timeout { sequence: 0, attempts: 3 })));
+}
+
+#[test]
+fn reports_refused_files() {
+    let mut nack = Frame::new(FrameType::Nack, 1, vec![4]);
+    nack.payload.extend_from_slice(b"storage full");
+    let mut answers = Frame::new(FrameType::Ack, 0, vec![PROTOCOL_VERSION, 0x00, 0x01]).encode();
+    answers.extend(nack.encode());
+
+    // Writes go nowhere, the answers are canned.
+    struct Canned(io::Cursor<Vec<u8>>);
+    impl Read for Canned {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+    impl Write for Canned {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut uploader = Uploader::connect(Canned(io::Cursor::new(answers))).unwrap();
+    let err = uploader.upload(FileKind::Stub, "algo", &[0; 8], &mut |_: Progress<'_>| {}).unwrap_err();
+    assert!(matches!(err, UploadError::Rejected { sequence: 1, code: 4, ref message } if message == "storage full"));
+    assert_eq!(begin_payload(FileKind::Stub, "algo", &[0; 8])[..5], [1, 8, 0, 0, 0]);
+}
+
+#[test]
+fn verifies_uploaded_stubs() {
+    let report = VerifyReport { passed: false, sector: 0x0800_0000..0x0800_4000, mismatch: Some(0x0800_0010), elapsed: Duration::from_millis(42) };
+    assert_eq!(VerifyReport::decode(&report.encode()).unwrap(), report);
+    assert!(matches!(VerifyReport::decode(&[1, 0, 0]), Err(UploadError::BadFrame(_))));
+    assert_eq!(verify_payload("algo", None)[..4], LAST_SECTOR.to_le_bytes());
+    assert_eq!(verify_payload("algo", Some(0x0800_0000)), [0x00, 0x00, 0x00, 0x08, b'a', b'l', b'g', b'o']);
+
+    let mut uploader = Uploader::connect(Programmer::new(MAX_PAYLOAD as u16)).unwrap();
+    uploader.upload(FileKind::Stub, "algo", &[0; 8], &mut |_: Progress<'_>| {}).unwrap();
+    let report = uploader.verify("algo", Some(0x0807_0000)).unwrap();
+    assert!(report.passed);
+    assert_eq!((report.sector, report.mismatch), (0x0807_0000..0x0808_0000, None));
+
+    let err = uploader.verify("missing", None).unwrap_err();
+    assert!(matches!(err, UploadError::Rejected { code: 2, .. }));
+    assert_eq!(uploader.into_inner().verified, [0x0807_0000]);
+}
+
+#[cfg(feature = "hil")]
+#[test]
+fn records_runs_on_the_programmer() {
+    use soulcomposer::{
+        database::{StubDatabase, StubQuery, StubSource},
+        hil::{verify_stubs, InjectorBackend},
+        progress::NoProgress,
+        ArmFlashStub,
+    };
+
+    let database = StubDatabase::in_memory().unwrap();
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "main".to_string(), false, 0).unwrap();
+    let main = database.insert(&StubSource::new("TEST192"), &stub, &[]).unwrap();
+    let stubs = database.query(&StubQuery::new()).unwrap();
+
+    let uploader = Uploader::connect(Programmer::new(MAX_PAYLOAD as u16)).unwrap();
+    let mut backend = InjectorBackend { uploader };
+    let runs = verify_stubs(&database, &mut backend, &stubs, None, &mut NoProgress).unwrap();
+    assert!(runs[0].passed);
+    assert_eq!((runs[0].backend.as_str(), runs[0].sector.clone()), ("injector", Some(0x0807_0000..0x0808_0000)));
+    assert!(database.is_verified(main).unwrap());
+
+    let programmer = backend.uploader.into_inner();
+    assert_eq!(programmer.files[0].1, "main");
+    assert_eq!(programmer.verified, [LAST_SECTOR]);
+
+    let mut programmer = Programmer::new(MAX_PAYLOAD as u16);
+    programmer.mismatch = Some(0x0807_0004);
+    let mut backend = InjectorBackend { uploader: Uploader::connect(programmer).unwrap() };
+    let runs = verify_stubs(&database, &mut backend, &stubs, None, &mut NoProgress).unwrap();
+    assert!(!runs[0].passed);
+    assert_eq!(runs[0].detail.as_deref(), Some("read back differs at 0x08070004"));
+    assert!(!database.is_verified(main).unwrap());
+    assert_eq!(database.hil_runs(main).unwrap().len(), 2);
+}
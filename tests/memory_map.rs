@@ -0,0 +1,26 @@
+use soulcomposer::prog::arm::{
+    flash_stub_gen::ArmFlashStub,
+    memory_map::{MemoryKind, MemoryMap, MemoryRegion},
+};
+
+#[test]
+fn flags_peripheral_overlap() {
+    let mut map = MemoryMap::cortex_m();
+    map.regions.push(MemoryRegion { name: "IRAM1".to_string(), range: 0x2000_0000..0x2002_0000, kind: MemoryKind::Ram });
+
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0x0800_0000;
+    stub.flash_end_addr = 0x0810_0000;
+    stub.ram_required = 0x1000;
+    assert!(map.check(&stub, 0x2000_0000).is_empty());
+
+    let warnings = map.check(&stub, 0x2001_F800);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].region.is_none());
+
+    let mut broken = stub;
+    broken.flash_start_addr = 0x4002_0000;
+    broken.flash_end_addr = 0x4003_0000;
+    let warnings = map.check(&broken, 0x2000_0000);
+    assert_eq!(warnings[0].region.as_ref().unwrap().kind, MemoryKind::Peripheral);
+}
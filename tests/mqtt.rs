@@ -0,0 +1,133 @@
+#![cfg(feature = "mqtt")]
+
+use std::{
+    io::Write,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use soulcomposer::mqtt::{connect_tcp, ConnectOptions, FleetTopics, MqttError, Packet, QoS, StubUpdate, UpdateKind};
+
+#[test]
+fn encodes_packets_like_the_specification() {
+    let connect = Packet::Connect { client_id: "sc".to_string(), username: None, password: None, keep_alive: 60 };
+    assert_eq!(
+        connect.encode().unwrap(),
+        [0x10, 14, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 60, 0, 2, b's', b'c']
+    );
+
+    let publish =
+        Packet::Publish { topic: "a/b".to_string(), payload: b"hi".to_vec(), qos: QoS::AtLeastOnce, retain: true, packet_id: Some(7) };
+    assert_eq!(publish.encode().unwrap(), [0x33, 9, 0, 3, b'a', b'/', b'b', 0, 7, b'h', b'i']);
+    assert_eq!(Packet::Disconnect.encode().unwrap(), [0xE0, 0]);
+
+    // Remaining lengths past 127 take more than one byte.
+    let large = Packet::Publish { topic: "t".to_string(), payload: vec![0; 200], qos: QoS::AtMostOnce, retain: false, packet_id: None };
+    let encoded = large.encode().unwrap();
+    assert_eq!(&encoded[..3], [0x30, 0xCB, 0x01]);
+
+    for packet in [connect, publish, large, Packet::PubAck { packet_id: 3 }, Packet::PingResp] {
+        let encoded = packet.encode().unwrap();
+        assert_eq!(Packet::read_from(&mut encoded.as_slice()).unwrap(), packet);
+    }
+}
+
+#[test]
+fn rejects_truncated_packets() {
+    let err = Packet::read_from(&mut [0x40, 2, 0].as_slice()).unwrap_err();
+    assert!(matches!(err, MqttError::Io(_)));
+    let err = Packet::read_from(&mut [0x40, 1, 0].as_slice()).unwrap_err();
+    assert!(matches!(err, MqttError::Protocol(_)));
+}
+
+#[test]
+fn names_topics_per_device() {
+    let topics = FleetTopics::new("/fleet/line-3/");
+    assert_eq!(topics.algorithm("STM32F4/x", "bank#1"), "fleet/line-3/devices/STM32F4_x/bank_1");
+    assert_eq!(topics.device_filter("nRF52+"), "fleet/line-3/devices/nRF52_/#");
+    assert_eq!(topics.updates(), "fleet/line-3/updates");
+}
+
+type Received = Arc<Mutex<Vec<Packet>>>;
+
+/// A broker accepting one client, acknowledging everything and keeping what it got.
+fn fake_broker(refuse: u8) -> (String, Received) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let received = Received::default();
+
+    let log = received.clone();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Ok(packet) = Packet::read_from(&mut stream) {
+            let reply = match &packet {
+                Packet::Connect { .. } => Some(Packet::ConnAck { session_present: false, code: refuse }),
+                Packet::Publish { packet_id: Some(packet_id), .. } => Some(Packet::PubAck { packet_id: *packet_id }),
+                Packet::PingReq => Some(Packet::PingResp),
+                _ => None,
+            };
+            let last = packet == Packet::Disconnect;
+            log.lock().unwrap().push(packet);
+            if let Some(reply) = reply {
+                stream.write_all(&reply.encode().unwrap()).unwrap();
+            }
+            if last {
+                break;
+            }
+        }
+    });
+
+    (address, received)
+}
+
+#[test]
+fn announces_retained_algorithms_and_notices() {
+    let (address, received) = fake_broker(0);
+    let options = ConnectOptions::new("tester").credentials("fleet", "secret");
+    let mut client = connect_tcp(&address, &options).unwrap();
+    client.ping().unwrap();
+
+    let topics = FleetTopics::new("soul-injector");
+    let update = topics.announce(&mut client, "STM32F407", UpdateKind::Stub, "flash", b"{}").unwrap();
+    assert_eq!(update.topic, "soul-injector/devices/STM32F407/flash");
+    assert_eq!((update.crc32, update.size), (crc32fast::hash(b"{}"), 2));
+    let withdrawn = topics.withdraw(&mut client, "STM32F407", UpdateKind::Stub, "old").unwrap();
+    assert_eq!(withdrawn.size, 0);
+    client.disconnect().unwrap();
+
+    // The broker thread finishes on DISCONNECT, wait for it to log it.
+    while received.lock().unwrap().last() != Some(&Packet::Disconnect) {
+        thread::yield_now();
+    }
+    let received = received.lock().unwrap();
+    assert!(matches!(
+        &received[0],
+        Packet::Connect { client_id, username: Some(user), password: Some(password), .. }
+            if client_id == "tester" && user == "fleet" && password == "secret"
+    ));
+    assert_eq!(received[1], Packet::PingReq);
+
+    let publishes: Vec<_> = received
+        .iter()
+        .filter_map(|packet| match packet {
+            Packet::Publish { topic, payload, retain, packet_id, .. } => Some((topic.as_str(), payload, *retain, *packet_id)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(publishes.len(), 4);
+    assert_eq!(publishes[0], ("soul-injector/devices/STM32F407/flash", &b"{}".to_vec(), true, Some(1)));
+    assert_eq!(publishes[1].0, "soul-injector/updates");
+    assert!(!publishes[1].2);
+    let notice: StubUpdate = serde_json::from_slice(publishes[1].1).unwrap();
+    assert_eq!(notice, update);
+    assert_eq!(publishes[2], ("soul-injector/devices/STM32F407/old", &Vec::new(), true, Some(3)));
+}
+
+#[test]
+fn reports_a_refused_connection() {
+    let (address, _) = fake_broker(5);
+    let err = connect_tcp(&address, &ConnectOptions::new("tester")).unwrap_err();
+    assert!(matches!(err, MqttError::Refused(5)));
+    assert_eq!(err.to_string(), "The broker refused the connection, not authorized");
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "msc")]
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use soulcomposer::{
+    msc::{check, MscError, MscFileKind, MscLayout, MscManifest, LAYOUT_VERSION},
+    prog::arm::stub_group::ArmFlashStubGroup,
+    ArmFlashStub,
+};
+
+fn stub(name: &str) -> ArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), name.to_string(), false, 0).unwrap()
+}
+
+fn layout() -> MscLayout {
+    let mut layout = MscLayout::new();
+    layout.add_stub(&stub("STM32F4xx 1024/main").validate().unwrap()).unwrap();
+    let group = ArmFlashStubGroup::split_banks("H7".to_string(), stub("both"), 0x0801_0000).unwrap();
+    layout.add_group(&group).unwrap();
+    layout
+}
+
+#[test]
+fn lists_every_file_with_its_checksums() {
+    let layout = layout();
+    let files = layout.files();
+    let paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, ["soul/stubs/STM32F4xx_1024_main.json", "soul/groups/H7.json", "soul/manifest.json", "soul/SHA256SUMS"]);
+
+    let manifest: MscManifest = serde_json::from_slice(&files[2].1).unwrap();
+    assert_eq!(manifest, layout.manifest());
+    assert_eq!(manifest.layout_version, LAYOUT_VERSION);
+    let entry = &manifest.files[0];
+    assert_eq!((entry.name.as_str(), entry.kind), ("STM32F4xx 1024/main", MscFileKind::Stub));
+    assert_eq!(entry.size, files[0].1.len() as u64);
+    assert_eq!(entry.crc32, crc32fast::hash(&files[0].1));
+    let stored: ArmFlashStub = serde_json::from_slice(&files[0].1).unwrap();
+    assert_eq!(stored.name, entry.name);
+
+    let checksums = String::from_utf8(files[3].1.clone()).unwrap();
+    let lines: Vec<&str> = checksums.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("  manifest.json"));
+    assert_eq!(lines[1], format!("{}  stubs/STM32F4xx_1024_main.json", entry.sha256));
+
+    // FAT doesn't tell the case of names apart.
+    let mut layout = layout;
+    let err = layout.add_stub(&stub("stm32f4xx_1024_MAIN").validate().unwrap()).unwrap_err();
+    assert!(matches!(err, MscError::Duplicate { path, .. } if path == "soul/stubs/stm32f4xx_1024_MAIN.json"));
+}
+
+#[test]
+fn checks_a_copied_layout() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("msc-volume");
+    let _ = fs::remove_dir_all(&dir);
+    let written = layout().write_to(&dir).unwrap();
+    assert_eq!(written.len(), 4);
+    assert!(check(&dir).unwrap().is_empty());
+
+    fs::write(dir.join("soul/stubs/STM32F4xx_1024_main.json"), b"{}").unwrap();
+    fs::remove_file(dir.join("soul/groups/H7.json")).unwrap();
+    let bad: Vec<String> = check(&dir).unwrap().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(bad, ["STM32F4xx 1024/main", "H7"]);
+
+    fs::remove_file(dir.join("soul/manifest.json")).unwrap();
+    assert!(matches!(check(&dir), Err(MscError::Io { .. })));
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    thread,
+};
+
+use soulcomposer::{
+    compose::arm::ComposeOptions,
+    nonblocking::{compose_file, load_error::LoadError, pack_cache::PackCache},
+    progress::Progress,
+};
+
+const PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <vendor>Test</vendor>
+  <name>Test_DFP</name>
+  <devices>
+    <family Dfamily="Test Series" Dvendor="Test:0">
+      <device Dname="TEST192"/>
+    </family>
+  </devices>
+</package>"#;
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Answers `requests` GETs, the index for paths ending in `.pidx` and the PDSC otherwise.
+fn serve(requests: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}/", listener.local_addr().unwrap());
+    let index = format!(r#"<index><pindex><pdsc url="{}" vendor="Test" name="Test_DFP" version="1.0.0"/></pindex></index>"#, base);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 1024];
+            let length = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..length]).to_string();
+            let body = if request.starts_with("GET /index.pidx") { index.clone() } else { PDSC.to_string() };
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        }
+    });
+
+    base
+}
+
+#[tokio::test]
+async fn fills_the_cache() {
+    let base = serve(2);
+    let cache = PackCache::new(cache_dir("nonblocking_cache"));
+
+    let entries = cache.update_index(&format!("{}index.pidx", base)).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(cache.root().join("index.pidx").is_file());
+
+    let path = cache.fetch_pdsc(&entries[0]).await.unwrap();
+    assert!(path.ends_with("pdsc/Test.Test_DFP.pdsc"));
+    let pdscs = cache.pdscs().await.unwrap();
+    assert_eq!(pdscs[0].devices[0].name, "TEST192");
+}
+
+#[tokio::test]
+async fn reports_download_progress() {
+    let base = serve(2);
+    let cache = PackCache::new(cache_dir("nonblocking_progress"));
+    let entries = cache.update_index(&format!("{}index.pidx", base)).await.unwrap();
+
+    let mut seen = Vec::new();
+    let results = cache
+        .fetch_pdscs(&entries, &mut |progress: Progress<'_>| {
+            seen.push((progress.completed, progress.total, progress.current.to_string()))
+        })
+        .await;
+    assert!(results[0].is_ok());
+    assert_eq!(seen, vec![(0, 1, entries[0].pdsc_url())]);
+}
+
+#[tokio::test]
+async fn composes_files() {
+    let dir = cache_dir("nonblocking_compose");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("algo.FLM"), common::build_flm()).unwrap();
+
+    let (stub, _) = compose_file(dir.join("algo.FLM"), &ComposeOptions::new("algo")).await.unwrap();
+    assert_eq!(stub.description, common::FLM_DEVICE_NAME);
+    assert!(matches!(compose_file(dir.join("missing.FLM"), &ComposeOptions::new("algo")).await, Err(LoadError::Io { .. })));
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "ota")]
+
+use soulcomposer::{
+    ota::{OtaError, OtaManifest},
+    signing::{generate_key, sign, SigningError},
+};
+
+const LIBRARY: &[u8] = br#"[{"name":"main","flashStartAddr":134217728}]"#;
+const URL: &str = "https://updates.example.com/stubs/1.4.0/library.json.signed";
+
+#[test]
+fn signs_and_reads_back_manifests() {
+    let key = generate_key().unwrap();
+    let bundle = sign(LIBRARY, &key).unwrap();
+    let manifest = OtaManifest::new("1.4.0", URL, &bundle, vec!["soul-injector-s3".to_string()]).unwrap().notes("STM32H7 bank 2");
+    assert_eq!(manifest.size, bundle.len() as u64);
+    assert_eq!(manifest.sha256.len(), 64);
+    assert!(manifest.matches(&bundle));
+    assert!(!manifest.matches(&bundle[1..]));
+
+    let json: serde_json::Value = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+    assert_eq!(json["version"], "1.4.0");
+    assert_eq!(json["models"][0], "soul-injector-s3");
+    assert!(json["releasedAt"].is_u64());
+
+    let signed = manifest.sign(&key).unwrap();
+    assert_eq!(OtaManifest::verify(&signed, &[key.verifying_key().to_bytes()]).unwrap(), manifest);
+    let other = generate_key().unwrap();
+    assert!(matches!(OtaManifest::verify(&signed, &[other.verifying_key().to_bytes()]), Err(OtaError::Signing(_))));
+}
+
+#[test]
+fn compares_versions_and_models() {
+    let bundle = sign(LIBRARY, &generate_key().unwrap()).unwrap();
+    let manifest = OtaManifest::new("1.10", URL, &bundle, vec!["Soul-Injector-S3".to_string()]).unwrap();
+    assert!(manifest.is_newer_than("1.9.7").unwrap());
+    assert!(!manifest.is_newer_than("1.10.0").unwrap());
+    assert!(!manifest.is_newer_than("2").unwrap());
+    assert!(matches!(manifest.is_newer_than("1.10-rc1"), Err(OtaError::Version(_))));
+    assert!(manifest.applies_to("soul-injector-s3"));
+    assert!(!manifest.applies_to("soul-injector-c3"));
+    assert!(OtaManifest::new("2.0", URL, &bundle, Vec::new()).unwrap().applies_to("anything"));
+
+    assert!(matches!(OtaManifest::new("v1", URL, &bundle, Vec::new()), Err(OtaError::Version(_))));
+    assert!(matches!(OtaManifest::new("1.0", "ftp://example.com/a", &bundle, Vec::new()), Err(OtaError::Url(_))));
+    assert!(matches!(OtaManifest::new("1.0", URL, LIBRARY, Vec::new()), Err(OtaError::Signing(SigningError::Bundle(_)))));
+}
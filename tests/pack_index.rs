@@ -0,0 +1,23 @@
+use soulcomposer::pack::index::parse_index;
+
+const INDEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<index schemaVersion="1.1.0">
+  <vendor>Keil</vendor>
+  <url>https://www.keil.com/pack/</url>
+  <pindex>
+    <pdsc url="https://www.keil.com/pack/" vendor="Keil" name="STM32F4xx_DFP" version="2.15.0"/>
+    <pdsc url="http://developer.nordicsemi.com/nRF5_SDK/pieces/nRF_DeviceFamilyPack" vendor="NordicSemiconductor" name="nRF_DeviceFamilyPack" version="8.44.1"/>
+  </pindex>
+</index>"#;
+
+#[test]
+fn parses_pack_index() {
+    let entries = parse_index(INDEX).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].pdsc_url(), "https://www.keil.com/pack/Keil.STM32F4xx_DFP.pdsc");
+    assert_eq!(entries[0].pack_url(), "https://www.keil.com/pack/Keil.STM32F4xx_DFP.2.15.0.pack");
+    assert_eq!(
+        entries[1].pdsc_url(),
+        "http://developer.nordicsemi.com/nRF5_SDK/pieces/nRF_DeviceFamilyPack/NordicSemiconductor.nRF_DeviceFamilyPack.pdsc"
+    );
+}
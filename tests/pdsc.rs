@@ -0,0 +1,94 @@
+mod common;
+
+use soulcomposer::pack::pdsc::Pdsc;
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    flash_stub_gen::{ArmFlashStub, ClockSource},
+    parse_options::{ConflictPolicy, ParseOptions},
+};
+
+const PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <vendor>Keil</vendor>
+  <name>STM32F4xx_DFP</name>
+  <releases><release version="2.15.0">Latest</release></releases>
+  <devices>
+    <family Dfamily="STM32F4 Series" Dvendor="STMicroelectronics:13">
+      <processor Dcore="Cortex-M4" Dfpu="SP_FPU" Dendian="Little-endian" Dclock="168000000"/>
+      <subFamily DsubFamily="STM32F407">
+        <device Dname="STM32F407VG">
+          <memory id="IROM1" start="0x08000000" size="0x100000" startup="1" default="1"/>
+          <memory id="IRAM1" start="0x20000000" size="0x20000" init="0" default="1"/>
+          <flashinfo name="Main" start="0x08000000" pagesize="0x200" blankval="0xFF"/>
+          <algorithm name="CMSIS\Flash\STM32F4xx_1024.FLM" start="0x08000000" size="0x100000" default="1"/>
+        </device>
+      </subFamily>
+    </family>
+  </devices>
+</package>"#;
+
+#[test]
+fn parses_inherited_device_properties() {
+    let pdsc = Pdsc::parse(PDSC).unwrap();
+    assert_eq!(pdsc.version.as_deref(), Some("2.15.0"));
+
+    let device = pdsc.device("stm32f407vg").unwrap();
+    assert_eq!(device.vendor.as_deref(), Some("STMicroelectronics"));
+    assert_eq!(device.clock(), Some(168_000_000));
+    assert_eq!(device.memories.len(), 2);
+    assert_eq!(device.algorithm_for(0x0800_4000).unwrap().file, "CMSIS/Flash/STM32F4xx_1024.FLM");
+    assert_eq!(Pdsc::from_reader(PDSC.as_bytes()).unwrap(), pdsc);
+
+    let mut stub = ArmFlashStub::default();
+    stub.apply_pdsc_device(device, &ParseOptions::default()).unwrap();
+    assert_eq!(stub.init_parameters.clock, 168_000_000);
+    assert_eq!(stub.init_parameters.clock_source, ClockSource::Pdsc);
+}
+
+#[test]
+fn reconciles_page_size() {
+    let pdsc = Pdsc::parse(PDSC).unwrap();
+    let device = pdsc.device("STM32F407VG").unwrap();
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0x0800_0000;
+    stub.flash_page_size = 0x400;
+
+    let mut kept = stub.clone();
+    let diagnostics = kept.apply_pdsc_device(device, &ParseOptions::default()).unwrap();
+    assert_eq!(kept.flash_page_size, 0x400);
+    assert!(diagnostics.iter().any(|diagnostic| diagnostic.code == "page-size-conflict"));
+
+    let mut taken = stub.clone();
+    let prefer_pdsc = ParseOptions { page_size_policy: ConflictPolicy::PreferPdsc, ..Default::default() };
+    taken.apply_pdsc_device(device, &prefer_pdsc).unwrap();
+    assert_eq!(taken.flash_page_size, 0x200);
+
+    let strict = ParseOptions { page_size_policy: ConflictPolicy::Error, ..Default::default() };
+    assert!(matches!(
+        stub.clone().apply_pdsc_device(device, &strict),
+        Err(ArmError::PageSizeConflict { flm: 0x400, pdsc: 0x200 })
+    ));
+}
+
+#[test]
+fn larger_pdsc_page_grows_the_ram_requirement() {
+    let pdsc = Pdsc::parse(PDSC).unwrap();
+    let device = pdsc.device("STM32F407VG").unwrap();
+    let prefer_pdsc = ParseOptions { page_size_policy: ConflictPolicy::PreferPdsc, ..Default::default() };
+
+    // The FLM has 256 byte pages, the PDSC 512.
+    let flm = ArmFlashStub::from_elf(common::build_flm(), "flash".to_string(), false, 0).unwrap();
+    let mut taken = flm.clone();
+    taken.apply_pdsc_device(device, &prefer_pdsc).unwrap();
+    assert_eq!(taken.flash_page_size, 0x200);
+    assert_eq!(taken.ram_required, flm.ram_required + 0x100);
+    assert_eq!(taken.ram_size, taken.ram_required);
+    assert!(taken.verify_crc32().is_ok());
+
+    let declared = flm.ram_required + 0x80;
+    let mut tight = ArmFlashStub::from_elf(common::build_flm(), "flash".to_string(), false, declared).unwrap();
+    assert!(matches!(
+        tight.apply_pdsc_device(device, &prefer_pdsc),
+        Err(ArmError::RamOverflow { available, .. }) if available == declared
+    ));
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "probe")]
+
+mod common;
+
+use probe_rs::config::MemoryRegion;
+use soulcomposer::{
+    probe::{chip_family, ProbeTarget},
+    prog::arm::core_isa::Core,
+    ArmFlashStub,
+};
+
+#[test]
+fn generates_a_target_around_the_stub() {
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap().validate().unwrap();
+    let target = ProbeTarget { core: Core::CortexM4, ram_start: 0x2000_0000, ram_size: 0x1_0000 };
+    let family = chip_family(&stub, &target).unwrap();
+
+    assert_eq!(family.flash_algorithms.len(), 1);
+    assert_eq!(family.flash_algorithms[0].cores, ["main"]);
+    let chip = &family.variants[0];
+    assert_eq!(chip.flash_algorithms, [family.flash_algorithms[0].name.clone()]);
+    assert!(matches!(&chip.memory_map[0], MemoryRegion::Nvm(flash) if flash.range == (0x0800_0000..0x0803_0000)));
+    assert!(matches!(&chip.memory_map[1], MemoryRegion::Ram(ram) if ram.range == (0x2000_0000..0x2001_0000)));
+}
+
+#[test]
+fn family_descriptions_load_in_probe_rs() {
+    use soulcomposer::{
+        pack::pdsc::Pdsc,
+        prog::export::{export_probe_rs_family, probe_rs::ProbeRsChipFamily},
+    };
+
+    let pdsc = Pdsc::parse(
+        r#"<package><vendor>Test</vendor><name>Test_DFP</name><devices>
+             <family Dfamily="Test Series" Dvendor="Test:0"><processor Dcore="Cortex-M33"/>
+               <device Dname="TEST192">
+                 <memory name="Flash" access="rx" start="0x08000000" size="0x30000" startup="1"/>
+                 <memory name="SRAM" access="rwx" start="0x20000000" size="0x8000"/>
+                 <algorithm name="TEST_192.FLM" start="0x08000000" size="0x30000" default="1"/>
+               </device>
+             </family>
+           </devices></package>"#,
+    )
+    .unwrap();
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), true, 0).unwrap().validate().unwrap();
+    let description = ProbeRsChipFamily::from_pdsc(&pdsc, "Test Series", &[("TEST_192.FLM".to_string(), stub)]).unwrap();
+
+    let yaml = export_probe_rs_family(&description).unwrap();
+    let family: probe_rs_target::ChipFamily = serde_yaml::from_slice(&yaml).unwrap();
+    family.validate().unwrap();
+    assert_eq!(family.variants[0].cores[0].core_type, probe_rs_target::CoreType::Armv8m);
+    assert_eq!(family.variants[0].flash_algorithms, ["algo"]);
+    assert!(matches!(&family.variants[0].memory_map[0], MemoryRegion::Nvm(flash) if flash.range == (0x0800_0000..0x0803_0000)));
+}
@@ -0,0 +1,28 @@
+#![cfg(feature = "python")]
+
+mod common;
+
+use pyo3::{prelude::*, types::PyDict};
+use soulcomposer::python::{compose_stub, export, parse_flm};
+
+#[test]
+fn exposes_flm_to_python() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let flm = common::build_flm();
+        let device = parse_flm(py, &flm).unwrap();
+        let name: String = device.get_item("name").unwrap().unwrap().extract().unwrap();
+        assert_eq!(name, common::FLM_DEVICE_NAME);
+        let sectors: Vec<(u32, u32)> = device.get_item("sectors").unwrap().unwrap().extract().unwrap();
+        assert_eq!(sectors, [(0, 0x4000), (0x10000, 0x10000)]);
+
+        let stub = compose_stub(py, &flm, "algo".to_string(), true, 0).unwrap();
+        let stub = stub.downcast::<PyDict>().unwrap();
+        let pc: u32 = stub.get_item("pcProgramPage").unwrap().unwrap().extract().unwrap();
+        assert_eq!(pc, 16 | 1);
+
+        let header = export(py, &flm, "c-header", "algo".to_string(), false, 0).unwrap();
+        assert!(!header.as_bytes().is_empty());
+        assert!(export(py, &flm, "nope", "algo".to_string(), false, 0).is_err());
+    });
+}
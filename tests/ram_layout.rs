@@ -0,0 +1,58 @@
+mod common;
+
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    flash_stub_gen::ArmFlashStub,
+    ram_layout::{plan_stack, RamRequirement, RunLayout},
+    stack_usage::StackEstimate,
+};
+
+#[test]
+fn each_part_starts_word_aligned() {
+    let requirement = RamRequirement { code: 10, data: 3, bss: 2, stack: 510, page_buffer: 255 };
+    // 15 bytes of blob round up to 16, the stack to 512 and the page buffer to 256.
+    assert_eq!(requirement.total(), 784);
+    assert!(requirement.check(784).is_ok());
+    assert!(matches!(requirement.check(783), Err(ArmError::RamOverflow { required: 784, available: 783 })));
+}
+
+#[test]
+fn stub_covers_data_stack_and_page() {
+    let flm = common::build_flm_with_data(&[1, 2, 3, 4, 5, 6], 0x100);
+    let stub = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap();
+
+    let expected = 0x108 + stub.stack_size + 256;
+    assert_eq!(stub.ram_required, expected);
+    assert_eq!(stub.ram_size, expected);
+    assert_eq!(RamRequirement::of_stub(&stub).total(), expected);
+}
+
+#[test]
+fn declared_ram_is_checked() {
+    let flm = common::build_flm();
+    let required = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap().ram_required;
+
+    let roomy = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0x10000).unwrap();
+    assert_eq!((roomy.ram_size, roomy.ram_required), (0x10000, required));
+    assert!(ArmFlashStub::from_elf(&flm, "algo".to_string(), false, required).is_ok());
+    assert!(matches!(
+        ArmFlashStub::from_elf(&flm, "algo".to_string(), false, required - 1),
+        Err(ArmError::RamOverflow { required: r, available }) if r == required && available == required - 1
+    ));
+}
+
+#[test]
+fn sizes_near_4_gib_saturate_or_fail() {
+    let planned = plan_stack(StackEstimate { bytes: u32::MAX, bounded: false });
+    assert_eq!(planned, 0xFFFF_FFF8);
+    let requirement = RamRequirement { code: 0x100, data: 0, bss: 0, stack: planned, page_buffer: 0x400 };
+    assert_eq!(requirement.total(), u32::MAX);
+    assert!(matches!(requirement.check(0x10000), Err(ArmError::RamOverflow { .. })));
+
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+    assert!(RunLayout::plan(&stub, 0x2000_0000, 0x100, 0x10000).is_ok());
+    assert!(matches!(
+        RunLayout::plan(&stub, 0xFFFF_FF00, 0x100, u32::MAX),
+        Err(ArmError::DescriptorOverflow { field: "RAM layout end", .. })
+    ));
+}
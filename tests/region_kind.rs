@@ -0,0 +1,11 @@
+use soulcomposer::prog::arm::flash_stub_gen::RegionKind;
+
+#[test]
+fn detects_special_regions() {
+    assert_eq!(RegionKind::detect("STM32F4xx Flash OPT"), RegionKind::OptionBytes);
+    assert_eq!(RegionKind::detect("STM32F4xx_OTP"), RegionKind::Otp);
+    assert_eq!(RegionKind::detect("STM32L0xx EEPROM"), RegionKind::Eeprom);
+    assert_eq!(RegionKind::detect("STM32F4xx 1024kB Flash"), RegionKind::MainFlash);
+    assert!(RegionKind::Otp.requires_confirmation());
+    assert!(!RegionKind::Eeprom.requires_confirmation());
+}
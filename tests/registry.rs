@@ -0,0 +1,46 @@
+mod common;
+
+use std::{collections::BTreeMap, thread};
+
+use soulcomposer::{
+    prog::{arm::flash_stub_gen::ArmFlashStub, export::OutputFormat},
+    registry::StubRegistry,
+};
+
+fn stub(name: &str) -> ArmFlashStub {
+    ArmFlashStub::from_elf(common::build_flm(), name.to_string(), false, 0).unwrap()
+}
+
+#[test]
+fn shares_stubs_between_threads() {
+    let registry = StubRegistry::new();
+    let template = stub("algo");
+    let writers: Vec<_> = (0..4)
+        .map(|index| {
+            let (registry, mut stub) = (registry.clone(), template.clone());
+            thread::spawn(move || {
+                stub.name = format!("algo{}", index);
+                registry.insert(format!("DEVICE{}", index), vec![stub]);
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    assert_eq!(registry.len(), 4);
+    assert_eq!(registry.devices(), ["DEVICE0", "DEVICE1", "DEVICE2", "DEVICE3"]);
+    assert_eq!(registry.get("DEVICE2").unwrap()[0].name, "algo2");
+    assert!(registry.get("DEVICE4").is_none());
+
+    let held = registry.get("DEVICE0").unwrap();
+    let replaced = registry.insert("DEVICE0", vec![stub("other")]).unwrap();
+    assert_eq!((held[0].name.as_str(), replaced[0].name.as_str()), ("algo0", "algo0"));
+    assert!(registry.remove("DEVICE1").is_some());
+    assert!(!registry.contains("DEVICE1"));
+
+    let json = registry.export_snapshot(OutputFormat::Json).unwrap();
+    let snapshot: BTreeMap<String, Vec<ArmFlashStub>> = serde_json::from_slice(&json).unwrap();
+    assert_eq!(snapshot, registry.snapshot());
+    assert_eq!(snapshot["DEVICE0"][0].name, "other");
+}
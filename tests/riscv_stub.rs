@@ -0,0 +1,94 @@
+mod common;
+
+use common::{build_elf, TestSection, SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+use soulcomposer::prog::{
+    riscv::{
+        extensions::{parse_isa_string, scan_code, RiscvExtension},
+        flash_stub_gen::RiscvFlashStub,
+        riscv_error::RiscvError,
+    },
+    xtensa::esptool::EsptoolStub,
+};
+
+#[test]
+fn rejects_non_riscv_elf() {
+    let result = RiscvFlashStub::from_elf(&build_elf(40, 0, &[]), "arm".to_string(), false, 0);
+    assert!(matches!(result, Err(RiscvError::WrongMachine(40))));
+}
+
+#[test]
+fn riscv_elf_without_descriptor() {
+    let result = RiscvFlashStub::from_elf(&build_elf(243, 0, &[]), "riscv".to_string(), false, 0);
+    assert!(matches!(result, Err(RiscvError::Descriptor(_))));
+}
+
+#[test]
+fn parses_isa_strings() {
+    let extensions: Vec<_> = parse_isa_string("rv32i2p1_m2p0_a2p1_c2p0_zicsr2p0").unwrap().into_iter().collect();
+    assert_eq!(
+        extensions,
+        vec![RiscvExtension::M, RiscvExtension::A, RiscvExtension::C, RiscvExtension::Zicsr]
+    );
+    assert_eq!(parse_isa_string("RV64GC").unwrap().len(), 7);
+    assert!(matches!(parse_isa_string("armv7m"), Err(RiscvError::InvalidIsaString(_))));
+}
+
+#[test]
+fn scans_code_for_extensions() {
+    let mut code = Vec::new();
+    code.extend_from_slice(&0x02B5_0533u32.to_le_bytes()); // mul a0, a0, a1
+    code.extend_from_slice(&0x3000_2573u32.to_le_bytes()); // csrr a0, mstatus
+    code.extend_from_slice(&0x4501u16.to_le_bytes()); // c.li a0, 0
+    code.extend_from_slice(&[0, 0]);
+
+    let extensions: Vec<_> = scan_code(&code).into_iter().collect();
+    assert_eq!(extensions, vec![RiscvExtension::M, RiscvExtension::C, RiscvExtension::Zicsr]);
+}
+
+#[test]
+fn tells_scalar_from_vector_memory_accesses() {
+    let scan = |instruction: u32| scan_code(&instruction.to_le_bytes()).into_iter().collect::<Vec<_>>();
+    assert_eq!(scan(0x0005_2007), [RiscvExtension::F]); // flw f0, 0(a0)
+    assert_eq!(scan(0x0005_3007), [RiscvExtension::D]); // fld f0, 0(a0)
+    assert_eq!(scan(0x0005_4007), [RiscvExtension::Q]); // flq f0, 0(a0)
+    assert_eq!(scan(0x0205_0007), [RiscvExtension::V]); // vle8.v v0, (a0)
+    assert_eq!(scan(0x0205_7027), [RiscvExtension::V]); // vse32.v v0, (a0)
+    assert_eq!(scan(0x0200_0057), [RiscvExtension::V]); // vadd.vv v0, v0, v0
+    assert_eq!(scan(0x0600_0053), [RiscvExtension::Q]); // fadd.q f0, f0, f0
+    assert_eq!(scan(0x0400_0053), Vec::<RiscvExtension>::new()); // fadd.h f0, f0, f0
+}
+
+#[test]
+fn hart_must_have_every_extension() {
+    let stub = RiscvFlashStub {
+        extensions: vec![RiscvExtension::M, RiscvExtension::C],
+        ..Default::default()
+    };
+    assert!(stub.check_hart("rv32imac").is_ok());
+    assert!(matches!(
+        stub.check_hart("rv32ic_zicsr"),
+        Err(RiscvError::MissingExtension { extension: RiscvExtension::M, .. })
+    ));
+}
+
+#[test]
+fn esp_loader_to_esptool_stub() {
+    let sections = vec![
+        TestSection {
+            name: ".text",
+            typ: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            address: 0x4038_0000,
+            data: vec![0x01, 0x45, 0x82, 0x80],
+        },
+        TestSection { name: ".data", typ: SHT_PROGBITS, flags: SHF_ALLOC, address: 0x3FC8_0000, data: vec![1, 2, 3, 4] },
+    ];
+    let stub = EsptoolStub::from_riscv_elf(&build_elf(243, 0x4038_0000, &sections)).unwrap();
+    assert_eq!(stub.text_start, 0x4038_0000);
+    assert_eq!(stub.data_start, 0x3FC8_0000);
+    assert_eq!(stub.bss_start, None);
+
+    let json = stub.to_json().unwrap();
+    assert_eq!(EsptoolStub::from_json(&json).unwrap(), stub);
+    assert!(stub.to_espflash_toml().unwrap().contains("text_start = 1077411840"));
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    flash_device::{FlashDevice, SectorInfo},
+    flash_stub_gen::{ArmFlashStub, SectorRegion},
+};
+
+fn sector(address: u32, size: u32) -> SectorInfo {
+    SectorInfo { address, size }
+}
+
+#[test]
+fn mixed_regions_tile_device() {
+    // 4x16K + 1x64K + 7x128K, like an STM32F4 bank.
+    let sectors = [sector(0, 0x4000), sector(0x10000, 0x10000), sector(0x20000, 0x20000)];
+    assert!(FlashDevice::validate_sectors(&sectors, 0x100000).is_ok());
+}
+
+#[test]
+fn stub_describes_each_sector_region() {
+    let mut fields = common::DescriptorFields { device_size: 0x100000, ..Default::default() };
+    fields.sectors = vec![sector(0, 0x4000), sector(0x10000, 0x10000), sector(0x20000, 0x20000)];
+    let flm = common::build_flm_with(&fields.to_bytes(), "FlashDevice");
+    let stub = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap();
+
+    let region = |address, size, count| SectorRegion { address, size, count };
+    assert_eq!(
+        stub.sectors,
+        [region(0x0800_0000, 0x4000, 4), region(0x0801_0000, 0x10000, 1), region(0x0802_0000, 0x20000, 7)]
+    );
+    assert_eq!(stub.flash_sector_size, 0x4000);
+
+    let json = serde_json::to_value(&stub).unwrap();
+    assert_eq!(json["sectors"][2], serde_json::json!({ "address": 0x0802_0000, "size": 0x20000, "count": 7 }));
+}
+
+#[test]
+fn stubs_without_sector_regions_still_load() {
+    let stub = ArmFlashStub::from_elf(common::build_flm(), "algo".to_string(), false, 0).unwrap();
+    let mut json = serde_json::to_value(&stub).unwrap();
+    json.as_object_mut().unwrap().remove("sectors");
+
+    let old: ArmFlashStub = serde_json::from_value(json).unwrap();
+    assert!(old.sectors.is_empty());
+    assert_eq!(old.flash_sector_size, 0x4000);
+}
+
+/// A stub as written before the sector table, with only the uniform `flashSectorSize`.
+const LEGACY_STUB: &str = r#"{
+    "name": "legacy",
+    "description": "STM32F4xx 64kB Flash",
+    "default": true,
+    "instructions": "cEdwR3BHcEc=",
+    "pcInit": null,
+    "pcUninit": null,
+    "pcProgramPage": 1,
+    "pcEraseSector": 5,
+    "pcEraseAll": null,
+    "dataSectionOffset": 8,
+    "flashStartAddr": 134217728,
+    "flashEndAddr": 134283264,
+    "flashPageSize": 1024,
+    "erasedByteValue": 255,
+    "flashSectorSize": 16384,
+    "programTimeout": 100,
+    "eraseTimeout": 1000,
+    "ramSize": 4096,
+    "flashSize": 65536
+}"#;
+
+#[test]
+fn legacy_stubs_validate_as_one_region() {
+    let stub: ArmFlashStub = serde_json::from_str(LEGACY_STUB).unwrap();
+    assert!(stub.sectors.is_empty());
+    let validated = stub.clone().validate().unwrap();
+    assert_eq!(validated.name, "legacy");
+
+    // The uniform sector size still has to tile the flash.
+    let mut uneven = stub;
+    uneven.flash_sector_size = 0x6000;
+    assert!(matches!(uneven.validate(), Err(ArmError::SectorRegionMisaligned { index: 0, .. })));
+}
+
+#[test]
+fn rejects_broken_tables() {
+    assert!(matches!(FlashDevice::validate_sectors(&[], 0x1000), Err(ArmError::SectorTableEmpty)));
+    assert!(matches!(
+        FlashDevice::validate_sectors(&[sector(0, 0x400), sector(0, 0x800)], 0x1000),
+        Err(ArmError::SectorTableUnsorted { index: 1, .. })
+    ));
+    assert!(matches!(
+        FlashDevice::validate_sectors(&[sector(0, 0x400)], 0x1100),
+        Err(ArmError::SectorRegionMisaligned { index: 0, .. })
+    ));
+    assert!(matches!(
+        FlashDevice::validate_sectors(&[sector(0x400, 0x400)], 0x1000),
+        Err(ArmError::SectorTableGap { .. })
+    ));
+}
+
+#[test]
+fn errors_carry_stable_codes() {
+    let unsorted = FlashDevice::validate_sectors(&[sector(0, 0x400), sector(0, 0x800)], 0x1000).unwrap_err();
+    assert_eq!(unsorted.code(), "SC0011");
+    assert_eq!(ArmError::SectorTableEmpty.code(), "SC0008");
+}
+
+#[test]
+fn exposes_parsed_descriptor() {
+    let flm = common::build_flm();
+    let elf = goblin::elf::Elf::parse(&flm).unwrap();
+    let device = FlashDevice::from_elf(&elf, &flm).unwrap();
+
+    assert_eq!(device.driver_version(), 0x0101);
+    assert_eq!(device.name(), common::FLM_DEVICE_NAME);
+    assert_eq!(device.device_type(), 1);
+    assert_eq!((device.start_address(), device.device_size()), (0x0800_0000, 0x30000));
+    assert_eq!(device.end_address(), 0x0803_0000);
+    assert_eq!(device.page_size(), 256);
+    assert_eq!(device.erased_default_value(), 0xFF);
+    assert_eq!((device.program_page_timeout(), device.erase_sector_timeout()), (100, 3000));
+    let sectors: Vec<_> = device.sectors().iter().map(|sector| (sector.address, sector.size)).collect();
+    assert_eq!(sectors, [(0, 0x4000), (0x10000, 0x10000)]);
+
+    let summary = device.to_string();
+    assert!(summary.contains("0x08000000..0x08030000 (196608 bytes)"));
+    assert!(summary.contains("0x0101, on-chip flash"));
+    assert!(summary.lines().any(|line| line.split_whitespace().eq(["0x08010000", "65536"])));
+
+    let stub = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap().to_string();
+    assert!(stub.starts_with("Name          algo\n"));
+    assert!(stub.lines().any(|line| line.split_whitespace().eq(["EraseSector", "0x0000000d"])));
+}
+
+#[test]
+fn answers_sector_queries() {
+    let flm = common::build_flm();
+    let elf = goblin::elf::Elf::parse(&flm).unwrap();
+    let device = FlashDevice::from_elf(&elf, &flm).unwrap();
+
+    assert_eq!(device.sector_containing(0x0800_0000), Some(0x0800_0000..0x0800_4000));
+    assert_eq!(device.sector_containing(0x0800_fffc), Some(0x0800_c000..0x0801_0000));
+    assert_eq!(device.sector_containing(0x0802_ffff), Some(0x0802_0000..0x0803_0000));
+    assert_eq!(device.sector_containing(0x0803_0000), None);
+    assert_eq!(device.sector_containing(0x07ff_ffff), None);
+
+    let ranges: Vec<_> = device.iter_sector_ranges().collect();
+    assert_eq!(ranges.len(), 6);
+    assert_eq!(ranges[4], 0x0801_0000..0x0802_0000);
+    assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+    assert_eq!(device.total_size_from_sectors(), u64::from(device.device_size()));
+}
+
+#[test]
+fn reports_descriptor_problems() {
+    let mut flm = common::build_flm();
+    let name = common::FLM_DEVICE_NAME.as_bytes();
+    let version = flm.windows(name.len()).position(|window| window == name).unwrap() - 2;
+    flm[version + 1] = 0x02;
+    assert!(matches!(
+        FlashDevice::from_elf(&goblin::elf::Elf::parse(&flm).unwrap(), &flm),
+        Err(ArmError::UnsupportedDriverVersion { version: 0x0201, .. })
+    ));
+
+    let err = ArmFlashStub::from_elf(&flm[..16], "algo".to_string(), false, 0).unwrap_err();
+    assert!(matches!(err, ArmError::ElfParse(_)));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn names_missing_entry_points() {
+    for name in ["ProgramPage", "EraseSector"] {
+        // Rename the symbol in the string table.
+        let mut flm = common::build_flm();
+        let needle = format!("{}\0", name);
+        let symbol = flm.windows(needle.len()).position(|window| window == needle.as_bytes()).unwrap();
+        flm[symbol] = b'X';
+        let err = ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0).unwrap_err();
+        assert!(matches!(err, ArmError::EntryPointMissing(missing) if missing == name), "{}", err);
+    }
+}
+
+#[test]
+fn segments_past_the_address_space_hold_nothing() {
+    // p_paddr of the only program header, the segment would wrap around.
+    let mut flm = common::build_flm();
+    flm[64..68].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    assert!(matches!(
+        FlashDevice::from_elf(&goblin::elf::Elf::parse(&flm).unwrap(), &flm),
+        Err(ArmError::MalformedDescriptor { size: 160, .. })
+    ));
+}
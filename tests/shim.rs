@@ -0,0 +1,27 @@
+use soulcomposer::prog::arm::{
+    flash_stub_gen::ArmFlashStub,
+    thumb::{decode, Instruction},
+};
+
+#[test]
+fn shims_call_entry_then_break() {
+    // ProgramPage at 0 and EraseSector at 2, both `bx lr`.
+    let mut stub = ArmFlashStub::default();
+    stub.instructions = base64::encode([0x70, 0x47, 0x70, 0x47]);
+    stub.pc_program_page = 1;
+    stub.pc_erase_sector = 3;
+    stub.data_section_offset = 4;
+    stub.ram_size = 0x1000;
+
+    let shimmed = stub.with_breakpoint_shims(1).unwrap();
+    let blob = base64::decode(&shimmed.instructions).unwrap();
+    assert_eq!(shimmed.data_section_offset, 16 + 4);
+    assert_eq!(shimmed.pc_erase_sector, 9);
+
+    let call = decode(&blob, 0, shimmed.pc_erase_sector & !1).unwrap();
+    assert_eq!(call.instruction, Instruction::BranchLink { target: 18 });
+    assert_eq!(decode(&blob, 0, 12).unwrap().instruction, Instruction::Breakpoint { imm: 0 });
+    assert_eq!(decode(&blob, 0, 18).unwrap().instruction, Instruction::BranchExchange { rm: 14 });
+
+    assert!(shimmed.with_breakpoint_shims(1).is_err());
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "signing")]
+
+use soulcomposer::signing::{
+    generate_key, parse_public_key, parse_signing_key, sign, to_hex, verify, BundleError, SignedBundle, SigningError,
+    SIGNATURE_SIZE,
+};
+
+const PAYLOAD: &[u8] = br#"{"name":"main","flashStartAddr":134217728}"#;
+
+#[test]
+fn signs_and_verifies_bundles() {
+    let key = generate_key().unwrap();
+    let public = key.verifying_key().to_bytes();
+    let bundle = sign(PAYLOAD, &key).unwrap();
+
+    assert_eq!(&bundle[..4], b"SCSB");
+    assert_eq!(bundle.len(), 44 + PAYLOAD.len() + SIGNATURE_SIZE);
+    assert_eq!(verify(&bundle, &[public]).unwrap(), PAYLOAD);
+
+    let parsed = SignedBundle::parse(&bundle).unwrap();
+    assert_eq!(parsed.public_key, public);
+    assert_eq!(parsed.payload, PAYLOAD);
+}
+
+#[test]
+fn refuses_untrusted_and_altered_bundles() {
+    let key = generate_key().unwrap();
+    let other = generate_key().unwrap();
+    let bundle = sign(PAYLOAD, &key).unwrap();
+
+    let bundle_error = |result: Result<&[u8], SigningError>| match result {
+        Err(SigningError::Bundle(err)) => err,
+        other => panic!("expected a bundle error, got {:?}", other),
+    };
+
+    let untrusted = bundle_error(verify(&bundle, &[other.verifying_key().to_bytes()]));
+    assert_eq!(untrusted, BundleError::UntrustedKey(key.verifying_key().to_bytes()));
+
+    let mut altered = bundle.clone();
+    altered[50] ^= 1;
+    assert_eq!(bundle_error(verify(&altered, &[key.verifying_key().to_bytes()])), BundleError::BadSignature);
+
+    // Swapping in a trusted key doesn't help, the signature no longer matches it.
+    let mut swapped = bundle.clone();
+    swapped[8..40].copy_from_slice(&other.verifying_key().to_bytes());
+    assert_eq!(bundle_error(verify(&swapped, &[other.verifying_key().to_bytes()])), BundleError::BadSignature);
+
+    assert_eq!(SignedBundle::parse(&bundle[..100]), Err(BundleError::Truncated { length: 100, needed: 108 }));
+    let mut longer = bundle.clone();
+    longer.push(0);
+    assert!(matches!(SignedBundle::parse(&longer), Err(BundleError::TrailingData { .. })));
+    assert_eq!(SignedBundle::parse(PAYLOAD.repeat(3).as_slice()), Err(BundleError::BadMagic));
+}
+
+#[test]
+fn reads_hex_key_files() {
+    let key = generate_key().unwrap();
+    let secret = format!("{}\n", to_hex(&key.to_bytes()));
+    let public = to_hex(key.verifying_key().as_bytes());
+
+    assert_eq!(parse_signing_key(&secret).unwrap().to_bytes(), key.to_bytes());
+    assert_eq!(parse_public_key(&public).unwrap(), key.verifying_key().to_bytes());
+    assert!(matches!(parse_signing_key("abcd"), Err(SigningError::Key(_))));
+    assert!(matches!(parse_public_key(&"zz".repeat(32)), Err(SigningError::Key(_))));
+}
@@ -0,0 +1,71 @@
+use soulcomposer::prog::spi_nor::{
+    flash_stub_gen::SpiNorFlashStub, sfdp::SpiNorParameters, spi_nor_error::SpiNorError,
+};
+
+/// A JESD216B basic table for a 128 Mbit part with 4K, 32K and 64K erase.
+fn basic_table() -> Vec<u32> {
+    let mut dwords = vec![0xFFFF_FFFF; 16];
+    dwords[0] = 0xFFF9_20E5;
+    dwords[1] = 0x07FF_FFFF;
+    dwords[7] = 0x520F_200C;
+    dwords[8] = 0xFF00_D810;
+    dwords[9] = 2 | 0x22 << 4 | 0x27 << 11 | 0x29 << 18;
+    dwords[10] = 2 | 8 << 4 | 5 << 8 | 1 << 13 | 9 << 24 | 2 << 29;
+    dwords
+}
+
+fn sfdp_image() -> Vec<u8> {
+    let mut sfdp = b"SFDP".to_vec();
+    sfdp.extend_from_slice(&[0x06, 0x01, 0x00, 0xFF]);
+    sfdp.extend_from_slice(&[0x00, 0x06, 0x01, 16, 0x10, 0x00, 0x00, 0xFF]);
+    for dword in basic_table() {
+        sfdp.extend_from_slice(&dword.to_le_bytes());
+    }
+    sfdp
+}
+
+#[test]
+fn parses_basic_flash_parameters() {
+    let parameters = SpiNorParameters::from_sfdp(&sfdp_image()).unwrap();
+
+    assert_eq!(parameters.size, 16 * 1024 * 1024);
+    assert_eq!(parameters.page_size, 256);
+    assert_eq!(parameters.address_bytes, 3);
+    let erase: Vec<_> = parameters.erase_types.iter().map(|erase| (erase.size, erase.opcode)).collect();
+    assert_eq!(erase, vec![(0x1000, 0x20), (0x8000, 0x52), (0x10000, 0xD8)]);
+    assert_eq!(parameters.erase_types[0].typical_ms, 48);
+    assert_eq!(parameters.erase_types[0].max_ms, 288);
+    assert_eq!(parameters.page_program_typical_us, 384);
+    assert_eq!(parameters.chip_erase_typical_ms, 40_000);
+}
+
+#[test]
+fn builds_stub_from_sfdp() {
+    let parameters = SpiNorParameters::from_sfdp(&sfdp_image()).unwrap();
+    let stub = SpiNorFlashStub::new("qspi".to_string(), 0x9000_0000, parameters).unwrap();
+
+    assert_eq!(stub.flash_end_addr, 0x9100_0000);
+    assert_eq!(stub.sectors[0].count, 4096);
+    assert_eq!(stub.program_timeout, 3);
+    assert_eq!(stub.erase_timeout, 288);
+}
+
+#[test]
+fn falls_back_to_jedec_id() {
+    let parameters = SpiNorParameters::from_jedec_id([0xEF, 0x40, 0x18]).unwrap();
+    assert_eq!(parameters.size, 16 * 1024 * 1024);
+
+    let stub = SpiNorFlashStub::new("w25q128".to_string(), 0, parameters).unwrap();
+    assert_eq!(stub.description, "Winbond W25Qxx SPI-NOR EF4018");
+    assert_eq!(stub.erase_timeout, 1000);
+
+    assert!(matches!(SpiNorParameters::from_jedec_id([0x12, 0x34, 0x18]), Err(SpiNorError::UnknownJedecId(_))));
+}
+
+#[test]
+fn rejects_bad_sfdp() {
+    assert!(matches!(SpiNorParameters::from_sfdp(b"JUNK"), Err(SpiNorError::SfdpSignature)));
+    let mut truncated = sfdp_image();
+    truncated.truncate(32);
+    assert!(matches!(SpiNorParameters::from_sfdp(&truncated), Err(SpiNorError::SfdpTruncated(32))));
+}
@@ -0,0 +1,32 @@
+use soulcomposer::prog::arm::stack_usage::{StackAnalyzer, StackEstimate};
+
+#[test]
+fn includes_callee_frames() {
+    let code = [
+        0x10, 0xb5, // push {r4, lr}
+        0x82, 0xb0, // sub sp, #8
+        0x00, 0xf0, 0x02, 0xf8, // bl callee
+        0x02, 0xb0, // add sp, #8
+        0x10, 0xbd, // pop {r4, pc}
+        0xf0, 0xb5, // callee: push {r4-r7, lr}
+        0xf0, 0xbd, // pop {r4-r7, pc}
+    ];
+
+    let mut analyzer = StackAnalyzer::new(&code);
+    assert_eq!(analyzer.estimate(1), StackEstimate { bytes: 36, bounded: true });
+    assert_eq!(analyzer.estimate(13), StackEstimate { bytes: 20, bounded: true });
+}
+
+#[test]
+fn indirect_calls_are_unbounded() {
+    // push {r4, lr}; blx r3; pop {r4, pc}
+    let code = [0x10, 0xb5, 0x98, 0x47, 0x10, 0xbd];
+    assert!(!StackAnalyzer::new(&code).estimate(1).bounded);
+}
+
+#[test]
+fn overflowing_frames_are_unbounded() {
+    // sub.w sp, sp, #0xff000000 twice, more stack than the address space.
+    let code = [0xad, 0xf1, 0x7f, 0x4d, 0xad, 0xf1, 0x7f, 0x4d, 0x70, 0x47];
+    assert_eq!(StackAnalyzer::new(&code).estimate(1), StackEstimate { bytes: u32::MAX, bounded: false });
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use soulcomposer::prog::arm::flash_stub_gen::ArmFlashStub;
+
+/// `ldr.w r0, [r9, #4]`
+const LOAD_SB_4: [u8; 4] = [0xD9, 0xF8, 0x04, 0x00];
+/// `ldr.w r0, [r9, #0x100]`
+const LOAD_SB_256: [u8; 4] = [0xD9, 0xF8, 0x00, 0x01];
+/// `mov r0, r9`
+const MOVE_SB: [u8; 2] = [0x48, 0x46];
+
+fn static_base(code: &[u8], data: &[u8]) -> Option<u32> {
+    let flm = common::build_flm_with_code(code, data, 0x100);
+    ArmFlashStub::from_elf(flm, "algo".to_string(), false, 0).unwrap().static_base
+}
+
+#[test]
+fn sb_relative_data_access_sets_the_static_base() {
+    assert_eq!(static_base(&LOAD_SB_4, &[0; 8]), Some(0x100));
+}
+
+#[test]
+fn r9_as_an_ordinary_register_is_not_a_static_base() {
+    let mut code = MOVE_SB.to_vec();
+    code.extend_from_slice(&[0x00, 0xBF]);
+    code.extend_from_slice(&LOAD_SB_256);
+    assert_eq!(static_base(&code, &[0; 8]), None);
+    // Without RW data there is nothing for r9 to point at.
+    assert_eq!(static_base(&LOAD_SB_4, &[]), None);
+}
@@ -0,0 +1,14 @@
+use soulcomposer::prog::arm::{arm_error::ArmError, flash_stub_gen::ArmFlashStub};
+
+#[test]
+fn crc_detects_corruption() {
+    let mut stub = ArmFlashStub::default();
+    stub.instructions = base64::encode([0x00, 0xbe, 0x70, 0x47]);
+    stub.pc_init = Some(1);
+    stub.flash_start_addr = 0x0800_0000;
+    stub.crc32 = Some(stub.compute_crc32().unwrap());
+    assert!(stub.verify_crc32().is_ok());
+
+    stub.pc_init = Some(5);
+    assert!(matches!(stub.verify_crc32(), Err(ArmError::CrcMismatch { .. })));
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use soulcomposer::prog::arm::flash_stub_gen::{ArmFlashStub, SectorRegion};
+
+#[test]
+fn diff_reports_fields_sectors_and_blobs() {
+    let old = ArmFlashStub::from_elf(common::build_flm(), "old".to_string(), false, 0).unwrap();
+    let mut new = old.clone();
+    new.name = "new".to_string();
+    assert!(old.diff(&new).is_empty());
+
+    new.flash_page_size = 512;
+    new.pc_erase_all = None;
+    new.sectors.push(SectorRegion { address: 0x0804_0000, size: 0x20000, count: 1 });
+    new.instructions = base64::encode([0x00, 0xbe]);
+    let diff = old.diff(&new);
+
+    let page_size = diff.get("flashPageSize").unwrap();
+    assert_eq!((page_size.old.as_deref(), page_size.new.as_deref()), (Some("256"), Some("512")));
+    assert_eq!(diff.get("pcEraseAll").unwrap().new.as_deref(), Some("null"));
+    assert_eq!(diff.get("sectors.len").unwrap().new.as_deref(), Some("3"));
+    assert_eq!(diff.get("sectors[2].address").unwrap().old, None);
+    assert_eq!(diff.get("instructions.size").unwrap().new.as_deref(), Some("2"));
+    assert!(diff.sectors_changed() && diff.blobs_changed());
+    assert!(diff.iter().all(|change| change.field != "name"));
+    assert!(diff.to_string().lines().any(|line| line.split_whitespace().eq(["sectors.len", "2", "->", "3"])));
+
+    let json = serde_json::to_value(&diff).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), diff.len());
+}
@@ -0,0 +1,55 @@
+use soulcomposer::prog::arm::{flash_stub_gen::ArmFlashStub, stub_group::ArmFlashStubGroup};
+
+fn stub(name: &str, start: u32, end: u32) -> ArmFlashStub {
+    let mut stub = ArmFlashStub::new(name);
+    stub.flash_start_addr = start;
+    stub.flash_end_addr = end;
+    stub
+}
+
+#[test]
+fn routes_across_banks() {
+    let group = ArmFlashStubGroup::dual_bank(
+        "STM32F42x".to_string(),
+        stub("bank1", 0x0800_0000, 0x0810_0000),
+        stub("bank2", 0x0810_0000, 0x0820_0000),
+    )
+    .unwrap();
+
+    let routed = group.route_range(0x080F_F000, 0x2000).unwrap();
+    assert_eq!(routed.len(), 2);
+    assert_eq!((routed[0].bank, routed[0].size, routed[0].algorithm.name.as_str()), (1, 0x1000, "bank1"));
+    assert_eq!((routed[1].bank, routed[1].address, routed[1].algorithm.name.as_str()), (2, 0x0810_0000, "bank2"));
+
+    assert!(group.route_range(0x081F_F000, 0x2000).is_err());
+}
+
+#[test]
+fn rejects_overlapping_banks() {
+    let bank1 = stub("bank1", 0x0800_0000, 0x0810_0000);
+    let bank2 = stub("bank2", 0x080F_0000, 0x0820_0000);
+    assert!(ArmFlashStubGroup::dual_bank("bad".to_string(), bank1, bank2).is_err());
+}
+
+#[test]
+fn splits_single_algorithm() {
+    let group = ArmFlashStubGroup::split_banks("H7".to_string(), stub("both", 0x0800_0000, 0x0820_0000), 0x0810_0000).unwrap();
+    assert_eq!(group.algorithms.len(), 1);
+    assert_eq!(group.route(0x0815_0000).unwrap().0.bank, 2);
+}
+
+#[test]
+fn merges_flash_and_eeprom() {
+    let group = ArmFlashStubGroup::merge(
+        "STM32L0".to_string(),
+        vec![stub("eeprom", 0x0808_0000, 0x0808_1800), stub("flash", 0x0800_0000, 0x0803_0000)],
+    )
+    .unwrap();
+
+    assert_eq!(group.banks[0].start, 0x0800_0000);
+    assert_eq!(group.route(0x0808_0010).unwrap().1.name, "eeprom");
+
+    let group = group.push(stub("otp", 0x1FF0_0000, 0x1FF0_0400)).unwrap();
+    assert_eq!(group.banks.last().unwrap().bank, 3);
+    assert!(group.push(stub("overlap", 0x0802_0000, 0x0804_0000)).is_err());
+}
@@ -0,0 +1,31 @@
+use soulcomposer::prog::arm::thumb::{decode, Instruction};
+
+#[test]
+fn decodes_common_prologue() {
+    // push {r4, lr}; sub sp, #8; bl 0x100; bx lr
+    let code = [0x10, 0xb5, 0x82, 0xb0, 0x00, 0xf0, 0x7c, 0xf8, 0x70, 0x47];
+
+    assert_eq!(decode(&code, 0, 0).unwrap().instruction, Instruction::Push { registers: 0x4010 });
+    assert_eq!(decode(&code, 0, 2).unwrap().instruction, Instruction::SubSp { imm: 8 });
+
+    let bl = decode(&code, 0, 4).unwrap();
+    assert_eq!(bl.size, 4);
+    assert_eq!(bl.instruction, Instruction::BranchLink { target: 0x100 });
+    assert_eq!(bl.instruction.to_string(), "bl 0x00000100");
+
+    assert_eq!(decode(&code, 0, 8).unwrap().instruction, Instruction::BranchExchange { rm: 14 });
+    assert!(decode(&code, 0, 10).is_none());
+}
+
+#[test]
+fn erased_flash_is_undefined() {
+    assert!(matches!(decode(&[0xff; 4], 0, 0).unwrap().instruction, Instruction::Undefined { .. }));
+}
+
+#[test]
+fn estimates_cycles() {
+    // push {r4, r5, lr}; ldr r0, [r1]; adds r0, #1; udiv r0, r1, r2; pop {r4, r5, pc}
+    let code = [0x30, 0xb5, 0x08, 0x68, 0x01, 0x30, 0xb1, 0xfb, 0xf2, 0xf0, 0x30, 0xbd];
+    let cycles: Vec<u32> = [0, 2, 4, 6, 10].iter().map(|&offset| decode(&code, 0, offset).unwrap().instruction.cycles()).collect();
+    assert_eq!(cycles, [4, 2, 1, 12, 6]);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    flash_device::SectorInfo,
+    flash_stub_gen::{ArmFlashStub, SectorRegion},
+};
+
+#[test]
+fn estimates_mixed_sectors() {
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0x0800_0000;
+    stub.flash_end_addr = 0x0810_0000;
+    stub.flash_page_size = 0x400;
+    stub.sectors = vec![
+        SectorRegion { address: 0x0800_0000, size: 0x4000, count: 4 },
+        SectorRegion { address: 0x0801_0000, size: 0x10000, count: 1 },
+        SectorRegion { address: 0x0802_0000, size: 0x20000, count: 7 },
+    ];
+    stub.erase_timeout = 1000;
+    stub.program_timeout = 10;
+
+    // 80K from the start covers 4x16K + the 64K sector.
+    let estimate = stub.estimate_programming_time(0x0800_0000, 0x14000).unwrap();
+    assert_eq!(estimate.sectors_erased, 5);
+    assert_eq!(estimate.pages_programmed, 80);
+    assert_eq!(estimate.total_ms(), 5 * 1000 + 80 * 10);
+
+    assert!(stub.estimate_programming_time(0x0810_0000, 1).is_err());
+}
+
+#[test]
+fn flash_ending_at_4_gib_is_refused() {
+    let fields = common::DescriptorFields {
+        start_address: 0xFFFF_0000,
+        device_size: 0x10000,
+        sectors: vec![SectorInfo { address: 0, size: 0x1000 }],
+        ..Default::default()
+    };
+    let flm = common::build_flm_with(&fields.to_bytes(), "FlashDevice");
+    assert!(matches!(
+        ArmFlashStub::from_elf(&flm, "algo".to_string(), false, 0),
+        Err(ArmError::DescriptorOverflow { field: "flash end", value: 0x1_0000_0000 })
+    ));
+
+    // A hand-edited region running past the address space.
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0xFFFF_0000;
+    stub.flash_end_addr = 0xFFFF_FFFF;
+    stub.sectors = vec![SectorRegion { address: 0xFFFF_0000, size: 0x1000, count: 0x10 }];
+    assert!(matches!(
+        stub.estimate_programming_time(0xFFFF_0000, 0x100),
+        Err(ArmError::DescriptorOverflow { field: "sector region end", .. })
+    ));
+}
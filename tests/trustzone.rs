@@ -0,0 +1,53 @@
+use soulcomposer::prog::arm::{
+    arm_error::ArmError,
+    flash_stub_gen::ArmFlashStub,
+    trustzone::{AliasScheme, Security},
+};
+
+const STM32L5: AliasScheme = AliasScheme {
+    flash_mask: 1 << 26,
+    ram_mask: 1 << 28,
+    secure_when_set: true,
+};
+
+fn secure_stub() -> ArmFlashStub {
+    let mut stub = ArmFlashStub::default();
+    stub.flash_start_addr = 0x0C00_0000;
+    stub.flash_end_addr = 0x0C08_0000;
+    stub.pc_program_page = 0x41;
+    stub.pc_erase_sector = 0x21;
+    stub.security = Some(Security::Secure);
+    stub.alias_scheme = Some(STM32L5);
+    stub.init_parameters.address = 0x0C00_0000;
+    stub
+}
+
+#[test]
+fn default_scheme_uses_bit_28() {
+    let scheme = AliasScheme::default();
+    assert_eq!(scheme.flash_security(0x1000_0000), Security::Secure);
+    assert_eq!(scheme.flash_security(0x0000_0000), Security::NonSecure);
+    assert_eq!(scheme.flash_alias(0x1000_1000, Security::NonSecure), 0x0000_1000);
+    assert_eq!(scheme.ram_alias(0x2000_0000, Security::Secure), 0x3000_0000);
+}
+
+#[test]
+fn accepts_both_flash_aliases() {
+    let stub = secure_stub();
+    assert!(stub.contains_flash_address(0x0800_0100));
+    assert!(stub.contains_flash_address(0x0C00_0100));
+    assert!(!stub.contains_flash_address(0x0808_0000));
+    assert_eq!(stub.flash_alias(Security::NonSecure), 0x0800_0000..0x0808_0000);
+}
+
+#[test]
+fn entry_points_must_use_the_same_alias() {
+    let stub = secure_stub();
+    assert!(stub.check_security(0x3000_0000).is_ok());
+
+    let result = stub.check_security(0x2000_0000);
+    assert!(matches!(
+        result,
+        Err(ArmError::SecurityAliasMismatch { expected: Security::Secure, address: 0x2000_0040, .. })
+    ));
+}
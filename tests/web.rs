@@ -5,9 +5,18 @@
 extern crate wasm_bindgen_test;
 use wasm_bindgen_test::*;
 
+mod common;
+
 wasm_bindgen_test_configure!(run_in_browser);
 
 #[wasm_bindgen_test]
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn converts_flm_in_browser() {
+    let json = soulcomposer::wasm::convert_flm(&common::build_flm(), "algo".to_string(), true, 0).unwrap();
+    assert!(json.contains(common::FLM_DEVICE_NAME));
+    assert!(soulcomposer::wasm::export_flm(&common::build_flm(), "algo".to_string(), true, 0, "nope").is_err());
+}
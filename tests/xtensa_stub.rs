@@ -0,0 +1,127 @@
+mod common;
+
+use common::{build_elf, TestSection, SHF_ALLOC, SHF_EXECINSTR, SHT_NOBITS, SHT_PROGBITS};
+use soulcomposer::prog::{
+    segments::SegmentError,
+    xtensa::{
+        esptool::{EspChip, EsptoolStub},
+        flash_stub_gen::{XtensaAbi, XtensaFlashStub},
+        xtensa_error::XtensaError,
+    },
+};
+
+fn loader_sections() -> Vec<TestSection> {
+    vec![
+        TestSection { name: ".literal", typ: SHT_PROGBITS, flags: SHF_ALLOC, address: 0x4009_0000, data: vec![0x11; 8] },
+        TestSection {
+            name: ".text",
+            typ: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            address: 0x4009_0008,
+            data: vec![0x36, 0x41, 0x00, 0x1D, 0xF0, 0x00],
+        },
+        TestSection { name: ".data", typ: SHT_PROGBITS, flags: SHF_ALLOC, address: 0x3FFE_0000, data: vec![1, 2, 3, 4] },
+        TestSection { name: ".bss", typ: SHT_NOBITS, flags: SHF_ALLOC, address: 0x3FFE_0004, data: vec![0; 16] },
+    ]
+}
+
+#[test]
+fn literals_merge_in_front_of_text() {
+    let elf = build_elf(94, 0x4009_0008, &loader_sections());
+    let stub = XtensaFlashStub::from_elf(&elf, "esp32".to_string()).unwrap();
+
+    assert_eq!(stub.text_start, 0x4009_0000);
+    assert_eq!(base64::decode(&stub.text).unwrap().len(), 14);
+    assert_eq!(stub.data_start, 0x3FFE_0000);
+    assert_eq!(stub.bss_start, Some(0x3FFE_0004));
+    assert_eq!(stub.entry, 0x4009_0008);
+    assert_eq!(stub.abi, XtensaAbi::Windowed);
+}
+
+#[test]
+fn overlapping_sections_are_rejected() {
+    let mut sections = loader_sections();
+    sections[1].address = 0x4009_0004;
+    let elf = build_elf(94, 0x4009_0004, &sections);
+    assert!(matches!(
+        XtensaFlashStub::from_elf(&elf, "esp32".to_string()),
+        Err(XtensaError::Segment(SegmentError::Overlap { segment: "text", address: 0x4009_0004, end: 0x4009_0008 }))
+    ));
+}
+
+#[test]
+fn section_past_the_end_of_the_file_is_rejected() {
+    let mut elf = build_elf(94, 0x4009_0008, &loader_sections());
+    // sh_size of .data, the third section after the null one.
+    let shoff = u32::from_le_bytes([elf[32], elf[33], elf[34], elf[35]]) as usize;
+    elf[shoff + 3 * 40 + 20..shoff + 3 * 40 + 24].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    assert!(matches!(
+        XtensaFlashStub::from_elf(&elf, "esp32".to_string()),
+        Err(XtensaError::Segment(SegmentError::Truncated))
+    ));
+}
+
+#[test]
+fn entry_outside_text_is_rejected() {
+    let elf = build_elf(94, 0x3FFE_0000, &loader_sections());
+    let result = XtensaFlashStub::from_elf(&elf, "esp32".to_string());
+    assert!(matches!(result, Err(XtensaError::EntryPointOutOfRange { .. })));
+}
+
+#[test]
+fn rejects_other_machines() {
+    let elf = build_elf(40, 0x4009_0008, &loader_sections());
+    assert!(matches!(XtensaFlashStub::from_elf(&elf, "arm".to_string()), Err(XtensaError::WrongMachine(40))));
+}
+
+#[test]
+fn imports_esptool_json() {
+    let json = r#"{
+        "entry": 1074331652,
+        "text": "ERERETZBAB3wAA==",
+        "text_start": 1074331648,
+        "data": "AQIDBA==",
+        "data_start": 1073610752,
+        "bss_start": 1073610756
+    }"#;
+    let stub = XtensaFlashStub::from_esptool_json(json, "esp32".to_string()).unwrap();
+
+    assert_eq!(stub.entry, 0x4009_0004);
+    assert_eq!(stub.abi, XtensaAbi::Windowed);
+    assert_eq!(stub.data_start, 0x3FFE_0000);
+    assert_eq!(stub.bss_start, Some(0x3FFE_0004));
+    assert_eq!(stub.pc_program_page, None);
+}
+
+#[test]
+fn esptool_entry_outside_text_is_rejected() {
+    let json = r#"{"entry": 0, "text": "ERERETZBAB3wAA==", "text_start": 1074331648}"#;
+    let result = XtensaFlashStub::from_esptool_json(json, "esp32".to_string());
+    assert!(matches!(result, Err(XtensaError::EntryPointOutOfRange { .. })));
+}
+
+#[test]
+fn ram_image_follows_the_esp_image_format() {
+    let elf = build_elf(94, 0x4009_0008, &loader_sections());
+    let stub = EsptoolStub::from(&XtensaFlashStub::from_elf(&elf, "esp32".to_string()).unwrap());
+    let image = stub.to_ram_image("ESP32-S3".parse().unwrap()).unwrap();
+
+    assert_eq!(image[..2], [0xE9, 2]);
+    assert_eq!(image[4..8], 0x4009_0008u32.to_le_bytes());
+    assert_eq!(image[12..14], EspChip::Esp32S3.chip_id().to_le_bytes());
+    // Text and literals, 14 bytes padded to 16, then the data.
+    assert_eq!(image[24..32], [0x00, 0x00, 0x09, 0x40, 16, 0, 0, 0]);
+    assert_eq!(image[48..60], [0x00, 0x00, 0xFE, 0x3F, 4, 0, 0, 0, 1, 2, 3, 4]);
+
+    // The checksum ends the image on a 16 byte boundary.
+    assert_eq!(image.len(), 64);
+    let checksum = image[32..48].iter().chain(&image[56..60]).fold(0xEF, |checksum, byte| checksum ^ byte);
+    assert_eq!(image[63], checksum);
+}
+
+#[test]
+fn parses_esp_chip_names() {
+    assert_eq!("esp32c3".parse::<EspChip>().unwrap(), EspChip::Esp32C3);
+    assert!(EspChip::Esp32H2.is_riscv() && !EspChip::Esp32S2.is_riscv());
+    assert!(matches!("esp8266".parse::<EspChip>(), Err(XtensaError::UnknownChip(_))));
+}